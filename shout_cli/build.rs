@@ -0,0 +1,13 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto/shout.proto");
+
+    // protox compiles the proto in pure Rust, so building doesn't need `protoc`.
+    #[cfg(feature = "server")]
+    {
+        let descriptors = protox::compile(["proto/shout.proto"], ["proto"])?;
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_fds(descriptors)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Args;
+
+use shout_core::backend::device::DeviceSpec;
+use shout_core::backend::memory::available_memory;
+use shout_core::cache::{ResultCache, model_fingerprint};
+use shout_core::cancel::Timeouts;
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::decoding::prompt::Task;
+use shout_core::output::OutputFormat;
+use shout_core::pipeline::batch::{BatchOptions, read_manifest_paths, run_batch};
+
+use crate::metrics;
+use crate::registry::resolve_model;
+use crate::transcribe::load_transcriber;
+
+#[derive(Args)]
+pub struct BatchArgs {
+    /// JSONL manifest with one `{"audio_path": ...}` object per line.
+    pub manifest: PathBuf,
+
+    /// Model directory (see `shout transcribe --model`).
+    #[arg(long)]
+    pub model: PathBuf,
+
+    /// auto, cpu, cuda[:N] or metal[:N].
+    #[arg(long, default_value = shout_config::get().device.as_str())]
+    pub device: DeviceSpec,
+
+    /// Output format: txt, srt, vtt, json or ctm.
+    #[arg(long, default_value = "txt")]
+    pub format: OutputFormat,
+
+    /// Directory for the transcripts (one file per input).
+    #[arg(long, default_value = "transcripts")]
+    pub out_dir: PathBuf,
+
+    /// Spoken language code, or `auto` to detect it.
+    #[arg(long, default_value = shout_config::get().language.as_str())]
+    pub language: LanguageSelection,
+
+    /// `transcribe` or `translate` (to English).
+    #[arg(long, default_value = "transcribe")]
+    pub task: Task,
+
+    /// Audio decode worker threads (default: number of CPUs).
+    #[arg(long)]
+    pub jobs: Option<usize>,
+
+    /// Feature extraction worker threads.
+    #[arg(long, default_value_t = 2)]
+    pub feature_jobs: usize,
+
+    /// Maximum inputs per model call.
+    #[arg(long, default_value_t = 8)]
+    pub batch_size: usize,
+
+    /// Reuse transcripts of files transcribed before with the same model and settings
+    /// (default: `paths.results_cache_dir` of the config, if set).
+    #[arg(long)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Size limit of the results cache, in MiB; least recently used entries go first.
+    #[arg(long, default_value_t = 1024)]
+    pub cache_max_mb: u64,
+
+    /// Seconds allowed for decoding one file; slower files fail.
+    #[arg(long)]
+    pub decode_timeout: Option<u64>,
+
+    /// Seconds allowed for the model on one 30-second window; a file that
+    /// exceeds it fails.
+    #[arg(long)]
+    pub window_timeout: Option<u64>,
+
+    /// Push metrics of the run (files, RTF, stage latencies, cache hits) to the
+    /// Prometheus Pushgateway at this URL when done, as job `shout_batch`.
+    #[arg(long, value_name = "URL")]
+    pub metrics_push: Option<String>,
+}
+
+pub fn run(mut args: BatchArgs) -> Result<()> {
+    args.model = resolve_model(&args.model)?;
+    let inputs = manifest_inputs(&args.manifest)?;
+
+    let mut transcriber = load_transcriber(&args.model, args.device, 1)?;
+    transcriber.options.language = args.language.clone();
+    transcriber.options.task = args.task;
+    let decode_timeout = args.decode_timeout.map(Duration::from_secs);
+    transcriber.timeouts = Timeouts {
+        decode: decode_timeout,
+        features: None,
+        inference: args.window_timeout.map(Duration::from_secs),
+    };
+
+    let mut opts = BatchOptions {
+        feature_jobs: args.feature_jobs,
+        batch_size: args.batch_size,
+        front_end: transcriber.front_end().clone(),
+        window_ms: transcriber.long_form.window_ms,
+        format: args.format,
+        out_dir: args.out_dir,
+        decode_timeout,
+        ..Default::default()
+    };
+    if let Some(jobs) = args.jobs {
+        opts.jobs = jobs;
+    }
+    let cache_dir = args
+        .cache_dir
+        .or_else(|| shout_config::get().paths.results_cache_dir.clone());
+    if let Some(dir) = cache_dir {
+        opts.cache = Some(ResultCache::open(dir, args.cache_max_mb * 1024 * 1024)?);
+        let fingerprint = model_fingerprint(&args.model)?;
+        opts.cache_context = cache_context(&fingerprint, &args.language, args.task);
+    }
+
+    let summary = run_batch(&inputs, &mut transcriber, &opts)?;
+    print!("{summary}");
+    if let Some(gateway) = &args.metrics_push {
+        let metrics = metrics::get();
+        metrics.jobs_finished("done", summary.succeeded);
+        metrics.jobs_finished("failed", summary.failed.len());
+        metrics.transcribed(summary.audio_seconds, summary.wall_time);
+        if opts.cache.is_some() {
+            metrics.cache_lookups(summary.cached, inputs.len() - summary.cached);
+        }
+        if let Some(free) = available_memory(transcriber.model().device()) {
+            metrics.set_memory_probe(move || Some(free));
+        }
+        metrics.push(gateway, "shout_batch")?;
+    }
+    Ok(())
+}
+
+/// Audio paths of a JSONL manifest, resolved like every stored audio path.
+pub fn manifest_inputs(manifest: &Path) -> Result<Vec<PathBuf>> {
+    Ok(read_manifest_paths(manifest)?
+        .iter()
+        .map(|stored| shout_config::get().audio_path(&stored.to_string_lossy()))
+        .collect())
+}
+
+/// Everything besides the audio that decides what a cached transcript contains.
+pub fn cache_context(model_fingerprint: &str, language: &LanguageSelection, task: Task) -> String {
+    format!("{model_fingerprint}\n{language:?}\n{task:?}")
+}
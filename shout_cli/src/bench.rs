@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+
+use shout_core::audio::decoder::decode_to_f32_mono_16k;
+use shout_core::backend::device::DeviceSpec;
+use shout_core::backend::memory::{available_memory, peak_process_memory};
+use shout_core::decoding::language::LanguageSelection;
+
+use crate::registry::resolve_model;
+use crate::transcribe::load_transcriber;
+
+const AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "flac", "ogg"];
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Model directory or name of a pulled model.
+    #[arg(long)]
+    pub model: PathBuf,
+
+    /// Audio file, or a directory of audio files.
+    #[arg(long)]
+    pub audio: PathBuf,
+
+    /// Devices to benchmark, one after another. May be repeated.
+    #[arg(long = "device", default_value = shout_config::get().device.as_str())]
+    pub devices: Vec<DeviceSpec>,
+
+    /// Untimed runs over the first file before measuring.
+    #[arg(long, default_value_t = 1)]
+    pub warmup: usize,
+
+    /// Timed passes over all files.
+    #[arg(long, default_value_t = 3)]
+    pub runs: usize,
+
+    /// Spoken language code, or `auto` (adds language detection to every run).
+    #[arg(long, default_value = shout_config::get().language.as_str())]
+    pub language: LanguageSelection,
+
+    /// Beam search with this many beams instead of greedy decoding.
+    #[arg(long)]
+    pub beam_size: Option<usize>,
+}
+
+pub fn run(args: BenchArgs) -> Result<()> {
+    let model = resolve_model(&args.model)?;
+    let files = audio_files(&args.audio)?;
+
+    let mut inputs = Vec::with_capacity(files.len());
+    for path in &files {
+        let pcm = decode_to_f32_mono_16k(path)
+            .with_context(|| format!("Failed to decode {}", path.display()))?;
+        inputs.push(pcm);
+    }
+    let audio_secs: f64 = inputs.iter().map(|pcm| pcm.len() as f64 / 16_000.0).sum();
+    println!("Files: {} ({audio_secs:.1} s of audio)", inputs.len());
+
+    for &device in &args.devices {
+        println!();
+        bench_device(&args, &model, device, &inputs, audio_secs)?;
+    }
+    Ok(())
+}
+
+fn bench_device(
+    args: &BenchArgs,
+    model: &Path,
+    device: DeviceSpec,
+    inputs: &[Vec<f32>],
+    audio_secs: f64,
+) -> Result<()> {
+    let load_started = Instant::now();
+    let mut transcriber = load_transcriber(model, device, args.beam_size.unwrap_or(1))?;
+    let load_time = load_started.elapsed();
+    transcriber.options.language = args.language.clone();
+    transcriber.options.beam_size = args.beam_size;
+
+    let location = transcriber.model().device().location();
+    let free_before = available_memory(transcriber.model().device());
+    let mut min_free = free_before;
+
+    for _ in 0..args.warmup {
+        transcriber.transcribe_pcm(&inputs[0])?;
+    }
+
+    let mut latencies = Vec::with_capacity(inputs.len() * args.runs);
+    let mut tokens = 0usize;
+    let started = Instant::now();
+    for _ in 0..args.runs.max(1) {
+        for pcm in inputs {
+            let t = Instant::now();
+            let transcript = transcriber.transcribe_pcm(pcm)?;
+            latencies.push(t.elapsed());
+            tokens += transcript
+                .segments
+                .iter()
+                .map(|s| s.tokens.len())
+                .sum::<usize>();
+
+            if let Some(free) = available_memory(transcriber.model().device()) {
+                min_free = Some(min_free.map_or(free, |m| m.min(free)));
+            }
+        }
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    latencies.sort();
+
+    println!("Device: {device} ({location:?})");
+    println!("Load: {:.2} s", load_time.as_secs_f64());
+    println!(
+        "RTF: {:.3} ({:.1}x real time)",
+        elapsed / (audio_secs * args.runs.max(1) as f64),
+        audio_secs * args.runs.max(1) as f64 / elapsed
+    );
+    println!(
+        "Latency per file: p50 {:.3} s, p90 {:.3} s, p99 {:.3} s, max {:.3} s",
+        percentile(&latencies, 50.0).as_secs_f64(),
+        percentile(&latencies, 90.0).as_secs_f64(),
+        percentile(&latencies, 99.0).as_secs_f64(),
+        latencies.last().copied().unwrap_or_default().as_secs_f64()
+    );
+    println!("Tokens/s: {:.1}", tokens as f64 / elapsed);
+    if let Some(peak) = peak_process_memory() {
+        println!("Peak process memory: {} MiB", peak >> 20);
+    }
+    if !transcriber.model().device().is_cpu()
+        && let (Some(before), Some(min)) = (free_before, min_free)
+    {
+        println!(
+            "Device memory used while decoding: {} MiB",
+            before.saturating_sub(min) >> 20
+        );
+    }
+    Ok(())
+}
+
+/// Nearest-rank percentile of sorted durations.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn audio_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        })
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        bail!("No audio files in {}", path.display());
+    }
+    Ok(files)
+}
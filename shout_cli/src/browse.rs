@@ -0,0 +1,482 @@
+//! `shout data browse`: a terminal UI for curating a manifest.
+//!
+//! Entries are listed on the left and can be narrowed with a text search and
+//! a field filter; the selected one is shown with its metadata and log-mel
+//! spectrogram, and can be played. Entries marked for exclusion are written
+//! to an exclusion list, one `audio_path` per line, which the data tools read.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use shout_core::audio::decoder::decode_to_f32_mono_16k;
+use shout_core::audio::playback::play;
+use shout_core::cancel::CancelToken;
+use shout_core::features::{MelSpec, SAMPLE_RATE, log_mel};
+use shout_eval::manifest::{EntryFilter, ReferenceEntry, read_references};
+
+#[derive(Args)]
+pub struct BrowseArgs {
+    /// JSONL manifest with `{"audio_path": ..., "text": ...}` per line.
+    pub manifest: PathBuf,
+
+    /// Exclusion list to extend (default: the manifest path plus `.exclude`).
+    #[arg(long)]
+    pub exclusions: Option<PathBuf>,
+
+    /// Mel bins of the spectrogram.
+    #[arg(long, default_value_t = 80)]
+    pub n_mels: usize,
+}
+
+pub fn run(args: BrowseArgs) -> Result<()> {
+    let entries = read_references(&args.manifest, usize::MAX)?;
+    let exclusions = args.exclusions.unwrap_or_else(|| {
+        let mut path = args.manifest.clone().into_os_string();
+        path.push(".exclude");
+        PathBuf::from(path)
+    });
+    let mut app = App::new(entries, exclusions, args.n_mels)?;
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result?;
+    app.save()
+}
+
+/// Audio paths in an exclusion list; none if the file does not exist.
+pub fn read_exclusions(path: &Path) -> Result<BTreeSet<String>> {
+    if !path.exists() {
+        return Ok(BTreeSet::new());
+    }
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read exclusion list: {}", path.display()))?;
+    Ok(raw
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+const HELP: &str = "j/k move · / search · f filter · p play · x exclude · w write · q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Input {
+    Keys,
+    Search,
+    Filter,
+}
+
+/// Audio and spectrogram of the selected entry.
+struct Loaded {
+    entry: usize,
+    pcm: Arc<Vec<f32>>,
+    mel: MelSpec,
+}
+
+struct App {
+    entries: Vec<ReferenceEntry>,
+
+    /// Indices into `entries` that pass the search and the filter.
+    visible: Vec<usize>,
+    list: ListState,
+    search: String,
+    filter: Option<EntryFilter>,
+    input: Input,
+    buffer: String,
+    excluded: BTreeSet<String>,
+    exclusions_path: PathBuf,
+    unsaved: bool,
+    n_mels: usize,
+    loaded: Option<Result<Loaded, String>>,
+    playing: Option<CancelToken>,
+    status: String,
+    errors: (Sender<String>, Receiver<String>),
+}
+
+impl App {
+    fn new(entries: Vec<ReferenceEntry>, exclusions_path: PathBuf, n_mels: usize) -> Result<Self> {
+        let mut app = Self {
+            visible: Vec::new(),
+            list: ListState::default(),
+            search: String::new(),
+            filter: None,
+            input: Input::Keys,
+            buffer: String::new(),
+            excluded: read_exclusions(&exclusions_path)?,
+            exclusions_path,
+            unsaved: false,
+            n_mels,
+            loaded: None,
+            playing: None,
+            status: String::new(),
+            errors: mpsc::channel(),
+            entries,
+        };
+        app.refilter();
+        Ok(app)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            self.load_selected();
+            if let Ok(error) = self.errors.1.try_recv() {
+                self.status = error;
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(Duration::from_millis(200))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match self.input {
+                Input::Keys => {
+                    if !self.on_key(key.code)? {
+                        self.stop();
+                        return Ok(());
+                    }
+                }
+                Input::Search | Input::Filter => self.on_input(key.code),
+            }
+        }
+    }
+
+    /// Handle a key in browsing mode; `false` to quit.
+    fn on_key(&mut self, key: KeyCode) -> Result<bool> {
+        let selected = self.list.selected().unwrap_or(0);
+        let last = self.visible.len().saturating_sub(1);
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Down | KeyCode::Char('j') => self.select((selected + 1).min(last)),
+            KeyCode::Up | KeyCode::Char('k') => self.select(selected.saturating_sub(1)),
+            KeyCode::PageDown => self.select((selected + 20).min(last)),
+            KeyCode::PageUp => self.select(selected.saturating_sub(20)),
+            KeyCode::Home => self.select(0),
+            KeyCode::End => self.select(last),
+            KeyCode::Char('/') => {
+                self.input = Input::Search;
+                self.buffer = self.search.clone();
+            }
+            KeyCode::Char('f') => {
+                self.input = Input::Filter;
+                self.buffer.clear();
+            }
+            KeyCode::Char('p') | KeyCode::Char(' ') | KeyCode::Enter => self.toggle_playback(),
+            KeyCode::Char('x') => self.toggle_exclusion(),
+            KeyCode::Char('w') => {
+                self.save()?;
+                self.status = format!("Wrote {}", self.exclusions_path.display());
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Handle a key while typing a search or a filter.
+    fn on_input(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char(c) => self.buffer.push(c),
+            KeyCode::Backspace => {
+                self.buffer.pop();
+            }
+            KeyCode::Esc => self.input = Input::Keys,
+            KeyCode::Enter => {
+                let text = std::mem::take(&mut self.buffer);
+                if self.input == Input::Search {
+                    self.search = text;
+                } else if text.trim().is_empty() {
+                    self.filter = None;
+                } else {
+                    match text.parse() {
+                        Ok(filter) => self.filter = Some(filter),
+                        Err(e) => self.status = format!("{e:#}"),
+                    }
+                }
+                self.input = Input::Keys;
+                self.refilter();
+            }
+            _ => {}
+        }
+    }
+
+    fn refilter(&mut self) {
+        let search = self.search.to_lowercase();
+        self.visible = (0..self.entries.len())
+            .filter(|&i| {
+                let entry = &self.entries[i];
+                let found = search.is_empty()
+                    || entry.text.to_lowercase().contains(&search)
+                    || entry.audio_path.to_lowercase().contains(&search);
+                found && self.filter.as_ref().is_none_or(|f| f.matches(entry))
+            })
+            .collect();
+        self.select(0);
+    }
+
+    fn select(&mut self, index: usize) {
+        self.list
+            .select((!self.visible.is_empty()).then_some(index));
+    }
+
+    fn selected_entry(&self) -> Option<usize> {
+        self.list
+            .selected()
+            .and_then(|i| self.visible.get(i).copied())
+    }
+
+    /// Decode the selected entry unless it is already loaded.
+    fn load_selected(&mut self) {
+        let Some(entry) = self.selected_entry() else {
+            self.loaded = None;
+            return;
+        };
+        if let Some(Ok(loaded)) = &self.loaded
+            && loaded.entry == entry
+        {
+            return;
+        }
+        self.stop();
+        let path = shout_config::get().audio_path(&self.entries[entry].audio_path);
+        self.loaded = Some(match decode_to_f32_mono_16k(&path) {
+            Ok(pcm) => Ok(Loaded {
+                entry,
+                mel: MelSpec::from(log_mel(&pcm, self.n_mels)),
+                pcm: Arc::new(pcm),
+            }),
+            Err(e) => Err(format!("{}: {e}", path.display())),
+        });
+    }
+
+    fn stop(&mut self) {
+        if let Some(token) = self.playing.take() {
+            token.cancel();
+        }
+    }
+
+    fn toggle_playback(&mut self) {
+        if let Some(token) = self.playing.take()
+            && !token.is_cancelled()
+        {
+            token.cancel();
+            return;
+        }
+        let Some(Ok(loaded)) = &self.loaded else {
+            return;
+        };
+        let token = CancelToken::new();
+        let (pcm, done, errors) = (
+            Arc::clone(&loaded.pcm),
+            token.clone(),
+            self.errors.0.clone(),
+        );
+        thread::spawn(move || {
+            if let Err(e) = play(&pcm, SAMPLE_RATE, &done) {
+                let _ = errors.send(format!("Playback failed: {e}"));
+            }
+            // Marks playback as finished for the next toggle.
+            done.cancel();
+        });
+        self.playing = Some(token);
+    }
+
+    fn toggle_exclusion(&mut self) {
+        let Some(entry) = self.selected_entry() else {
+            return;
+        };
+        let path = &self.entries[entry].audio_path;
+        if !self.excluded.remove(path) {
+            self.excluded.insert(path.clone());
+        }
+        self.unsaved = true;
+    }
+
+    /// Write the exclusion list if it changed.
+    fn save(&mut self) -> Result<()> {
+        if !self.unsaved {
+            return Ok(());
+        }
+        let mut contents = String::new();
+        for path in &self.excluded {
+            contents.push_str(path);
+            contents.push('\n');
+        }
+        fs::write(&self.exclusions_path, contents).with_context(|| {
+            format!(
+                "Failed to write exclusion list: {}",
+                self.exclusions_path.display()
+            )
+        })?;
+        self.unsaved = false;
+        Ok(())
+    }
+
+    // -------------------------------------------------------------------------
+    // Drawing
+    // -------------------------------------------------------------------------
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [list, side] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Fill(1)]).areas(main);
+        let [details, spectrogram] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Percentage(50)]).areas(side);
+
+        self.draw_list(frame, list);
+        self.draw_details(frame, details);
+        self.draw_spectrogram(frame, spectrogram);
+
+        let footer_text = match self.input {
+            Input::Search => format!("Search: {}_", self.buffer),
+            Input::Filter => format!("Filter (e.g. duration_ms>15000): {}_", self.buffer),
+            Input::Keys if !self.status.is_empty() => self.status.clone(),
+            Input::Keys => HELP.to_string(),
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .visible
+            .iter()
+            .map(|&i| {
+                let entry = &self.entries[i];
+                let excluded = self.excluded.contains(&entry.audio_path);
+                let mark = if excluded { "x " } else { "  " };
+                let style = if excluded {
+                    Style::new()
+                        .fg(Color::DarkGray)
+                        .add_modifier(Modifier::CROSSED_OUT)
+                } else {
+                    Style::new()
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(mark, Style::new().fg(Color::Red)),
+                    Span::styled(entry.text.clone(), style),
+                ]))
+            })
+            .collect();
+
+        let mut title = format!(" {} of {} ", self.visible.len(), self.entries.len());
+        if !self.search.is_empty() {
+            title.push_str(&format!("· \"{}\" ", self.search));
+        }
+        if self.filter.is_some() {
+            title.push_str("· filtered ");
+        }
+        if !self.excluded.is_empty() {
+            title.push_str(&format!("· {} excluded ", self.excluded.len()));
+        }
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+
+    fn draw_details(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        if let Some(i) = self.selected_entry() {
+            let entry = &self.entries[i];
+            lines.push(Line::from(entry.audio_path.clone()).style(Style::new().fg(Color::Cyan)));
+            lines.push(
+                Line::from(entry.text.clone()).style(Style::new().add_modifier(Modifier::BOLD)),
+            );
+            lines.push(Line::default());
+            if let Some(Ok(loaded)) = &self.loaded {
+                let seconds = loaded.pcm.len() as f64 / SAMPLE_RATE as f64;
+                lines.push(Line::from(format!("duration: {seconds:.2} s")));
+            }
+            for (key, value) in entry.metadata_strings() {
+                lines.push(Line::from(format!("{key}: {value}")));
+            }
+            if let Some(Err(e)) = &self.loaded {
+                lines.push(Line::from(e.clone()).style(Style::new().fg(Color::Red)));
+            }
+        }
+        let paragraph = Paragraph::new(lines)
+            .block(Block::bordered().title(" Entry "))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, area);
+    }
+
+    fn draw_spectrogram(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Log-mel ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        if let Some(Ok(loaded)) = &self.loaded {
+            let lines = spectrogram_lines(&loaded.mel, inner.width as usize, inner.height as usize);
+            frame.render_widget(Paragraph::new(lines), inner);
+        }
+    }
+}
+
+/// `mel` drawn with half blocks, two mel rows per line and low frequencies at
+/// the bottom; frames and bins are averaged to fit.
+fn spectrogram_lines(mel: &MelSpec, width: usize, height: usize) -> Vec<Line<'static>> {
+    let (frames, bins) = (mel.n_frames, mel.n_mels);
+    if frames == 0 || bins == 0 || width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let values = &mel.data[..frames * bins];
+    let lo = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let hi = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = (hi - lo).max(f32::EPSILON);
+
+    let rows = height * 2;
+    // Mean over the frames of column `col` and the bins of row `row` (0 at the top).
+    let cell = |col: usize, row: usize| {
+        let f0 = col * frames / width;
+        let f1 = ((col + 1) * frames / width).max(f0 + 1).min(frames);
+        let b0 = (rows - 1 - row) * bins / rows;
+        let b1 = ((rows - row) * bins / rows).max(b0 + 1).min(bins);
+        let mut sum = 0.0;
+        for f in f0..f1 {
+            sum += values[f * bins + b0..f * bins + b1].iter().sum::<f32>();
+        }
+        let mean = sum / ((f1 - f0) * (b1 - b0)) as f32;
+        heat((mean - lo) / range)
+    };
+
+    (0..height)
+        .map(|line| {
+            let spans: Vec<Span> = (0..width)
+                .map(|col| {
+                    let style = Style::new()
+                        .fg(cell(col, 2 * line))
+                        .bg(cell(col, 2 * line + 1));
+                    Span::styled("▀", style)
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Dark blue for 0 through red to pale yellow for 1.
+fn heat(v: f32) -> Color {
+    let v = v.clamp(0.0, 1.0);
+    let r = v.sqrt() * 255.0;
+    let g = v * v * 235.0;
+    let b = (1.0 - v) * 100.0 + v * v * 120.0;
+    Color::Rgb(r as u8, g as u8, b as u8)
+}
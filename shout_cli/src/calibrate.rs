@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use clap::Args;
+
+use shout_core::backend::device::DeviceSpec;
+use shout_core::confidence::{CALIBRATION_FILE, Calibration};
+use shout_core::decoding::language::LanguageSelection;
+use shout_eval::calibration::{CalibrationReport, ConfidenceSample, PlattScaler, word_samples};
+use shout_eval::manifest::read_references;
+use shout_eval::normalize::Normalizer;
+
+use crate::registry::resolve_model;
+use crate::transcribe::load_transcriber;
+
+#[derive(Args)]
+pub struct CalibrateArgs {
+    /// Model directory or name of a pulled model.
+    #[arg(long)]
+    pub model: PathBuf,
+
+    /// JSONL manifest with `{"audio_path": ..., "text": ...}` per line; use
+    /// held-out data, not the evaluation set.
+    #[arg(long)]
+    pub manifest: PathBuf,
+
+    /// auto, cpu, cuda[:N] or metal[:N].
+    #[arg(long, default_value = shout_config::get().device.as_str())]
+    pub device: DeviceSpec,
+
+    /// Spoken language code, or `auto` to detect it.
+    #[arg(long, default_value = shout_config::get().language.as_str())]
+    pub language: LanguageSelection,
+
+    /// Beam search with this many beams instead of greedy decoding.
+    #[arg(long)]
+    pub beam_size: Option<usize>,
+
+    /// Use only the first N entries.
+    #[arg(long)]
+    pub max_utts: Option<usize>,
+
+    /// Confidence bins of the reliability curve.
+    #[arg(long, default_value_t = 10)]
+    pub bins: usize,
+
+    /// Text normalization before deciding which words are correct.
+    #[arg(long, default_value = "whisper")]
+    pub normalizer: Normalizer,
+
+    /// Store the fitted calibration in the model directory, where transcription
+    /// picks it up.
+    #[arg(long)]
+    pub save: bool,
+}
+
+pub fn run(args: CalibrateArgs) -> Result<()> {
+    let model = resolve_model(&args.model)?;
+    let entries = read_references(&args.manifest, args.max_utts.unwrap_or(usize::MAX))?;
+
+    let mut transcriber = load_transcriber(&model, args.device, args.beam_size.unwrap_or(1))?;
+    transcriber.options.language = args.language.clone();
+    transcriber.options.beam_size = args.beam_size;
+    // Collect uncalibrated confidences, which the fit needs.
+    let current = transcriber.calibration.take();
+
+    let mut samples = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let audio = shout_config::get().audio_path(&entry.audio_path);
+        let transcript = match transcriber.transcribe_file(audio) {
+            Ok(transcript) => transcript,
+            Err(e) => {
+                tracing::warn!("{}: {e:#}", entry.audio_path);
+                continue;
+            }
+        };
+        let words: Vec<(&str, f64)> = transcript
+            .segments
+            .iter()
+            .flat_map(|s| &s.words)
+            .filter_map(|w| Some((w.text.as_str(), f64::from(w.confidence?))))
+            .collect();
+        samples.extend(word_samples(&entry.text, &words, args.normalizer));
+
+        eprint!("\r{}/{}", i + 1, entries.len());
+    }
+    eprintln!();
+    if samples.is_empty() {
+        bail!("No words with confidences to calibrate on");
+    }
+
+    print!(
+        "Uncalibrated: {}",
+        CalibrationReport::new(&samples, args.bins)
+    );
+    if let Some(c) = current {
+        let mapped = map(&samples, |p| {
+            f64::from(c.apply(p.max(f64::MIN_POSITIVE).ln() as f32))
+        });
+        print!(
+            "Current calibration: {}",
+            CalibrationReport::new(&mapped, args.bins)
+        );
+    }
+
+    let Some(fit) = PlattScaler::fit(&samples) else {
+        bail!("Cannot fit a calibration: every word is correct, or none is");
+    };
+    let fitted = CalibrationReport::new(&map(&samples, |p| fit.apply(p)), args.bins);
+    print!("Fitted (on the same data): {fitted}");
+    println!("Slope {:.4}, intercept {:.4}", fit.slope, fit.intercept);
+
+    if args.save {
+        let calibration = Calibration {
+            slope: fit.slope as f32,
+            intercept: fit.intercept as f32,
+        };
+        calibration.save_to_model_dir(&model)?;
+        println!("Saved {}", model.join(CALIBRATION_FILE).display());
+    }
+    Ok(())
+}
+
+fn map(samples: &[ConfidenceSample], f: impl Fn(f64) -> f64) -> Vec<ConfidenceSample> {
+    samples
+        .iter()
+        .map(|s| ConfidenceSample {
+            confidence: f(s.confidence),
+            correct: s.correct,
+        })
+        .collect()
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use clap::Args;
+
+use shout_eval::report::read_results;
+use shout_eval::stats::paired_bootstrap;
+
+#[derive(Args)]
+pub struct CompareArgs {
+    /// Results JSONL of the baseline (from `shout eval --out`).
+    pub a: PathBuf,
+
+    /// Results JSONL of the system compared against it.
+    pub b: PathBuf,
+
+    /// Bootstrap resamples.
+    #[arg(long, default_value_t = 10_000)]
+    pub iterations: usize,
+
+    /// Confidence level of the interval on the WER difference.
+    #[arg(long, default_value_t = 0.95)]
+    pub level: f64,
+
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+pub fn run(args: CompareArgs) -> Result<()> {
+    let a = read_results(&args.a)?;
+    let b: HashMap<String, _> = read_results(&args.b)?
+        .into_iter()
+        .map(|r| (r.audio_path.clone(), r))
+        .collect();
+
+    // Pair by audio path; utterances that failed in either run are left out.
+    let (mut words_a, mut words_b) = (Vec::new(), Vec::new());
+    for result in &a {
+        if let Some(other) = b.get(&result.audio_path) {
+            words_a.push(result.words);
+            words_b.push(other.words);
+        }
+    }
+    if words_a.is_empty() {
+        bail!(
+            "{} and {} have no utterances in common",
+            args.a.display(),
+            args.b.display()
+        );
+    }
+    let skipped = a.len() + b.len() - 2 * words_a.len();
+    if skipped > 0 {
+        tracing::warn!("{skipped} utterance(s) not in both files; comparing the rest");
+    }
+
+    let cmp = paired_bootstrap(&words_a, &words_b, args.iterations, args.level, args.seed);
+    println!("Utterances: {}", cmp.utterances);
+    println!("WER A: {:.2}%", cmp.rate_a * 100.0);
+    println!("WER B: {:.2}%", cmp.rate_b * 100.0);
+    println!(
+        "B - A: {:+.2} points ({:.0}% CI {:+.2} to {:+.2})",
+        cmp.delta.estimate * 100.0,
+        cmp.delta.level * 100.0,
+        cmp.delta.lower * 100.0,
+        cmp.delta.upper * 100.0
+    );
+    println!("p-value: {:.4}", cmp.p_value);
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! `shout data corrections`: export doubtful utterances for human review and
+//! import the corrected transcripts back into the manifest.
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use anyhow::{Result, bail};
+use clap::{Args, Subcommand};
+
+use shout_eval::manifest::{read_references, write_references};
+use shout_eval::report::read_results;
+use shout_eval::review::{Provenance, ReviewCriteria, apply, read_review, select, write_review};
+
+#[derive(Args)]
+pub struct CorrectionsArgs {
+    #[command(subcommand)]
+    pub command: CorrectionsCommand,
+}
+
+#[derive(Subcommand)]
+pub enum CorrectionsCommand {
+    /// Write utterances with a high WER or a low confidence in an eval run to
+    /// a review file (JSONL; reviewers fill in `corrected`).
+    Export(ExportArgs),
+
+    /// Replace manifest transcripts with the corrections of a review file,
+    /// recording the original text, reviewer, time and review file.
+    Import(ImportArgs),
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Per-utterance results of `shout eval` on the manifest.
+    #[arg(long)]
+    pub results: PathBuf,
+
+    /// The manifest the results were computed on.
+    #[arg(long)]
+    pub manifest: PathBuf,
+
+    /// Review file to write.
+    #[arg(long, default_value = "review.jsonl")]
+    pub out: PathBuf,
+
+    /// Export utterances with at least this WER (0.5 = 50%).
+    #[arg(long)]
+    pub min_wer: Option<f64>,
+
+    /// Export utterances whose hypothesis confidence is below this.
+    #[arg(long)]
+    pub max_confidence: Option<f32>,
+
+    /// Export at most this many, worst WER first.
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// Reviewed file written by `export`.
+    pub review: PathBuf,
+
+    /// Manifest to correct.
+    #[arg(long)]
+    pub manifest: PathBuf,
+
+    /// Write the corrected manifest here instead of replacing `--manifest`.
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+
+    /// Reviewer recorded for items that do not name one.
+    #[arg(long)]
+    pub reviewer: Option<String>,
+
+    /// Report what would change without writing the manifest.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+pub fn run(args: CorrectionsArgs) -> Result<()> {
+    match args.command {
+        CorrectionsCommand::Export(args) => export(args),
+        CorrectionsCommand::Import(args) => import(args),
+    }
+}
+
+fn export(args: ExportArgs) -> Result<()> {
+    if args.min_wer.is_none() && args.max_confidence.is_none() {
+        bail!("Nothing would be exported: pass --min-wer and/or --max-confidence");
+    }
+    let results = read_results(&args.results)?;
+    let entries = read_references(&args.manifest, usize::MAX)?;
+    let criteria = ReviewCriteria {
+        min_wer: args.min_wer,
+        max_confidence: args.max_confidence,
+    };
+    let mut items = select(&results, &entries, criteria);
+    if args.max_confidence.is_some() && results.iter().all(|r| r.confidence.is_none()) {
+        tracing::warn!("The results carry no confidences; re-run `shout eval` to get them");
+    }
+    items.truncate(args.limit.unwrap_or(usize::MAX));
+    write_review(&args.out, &items)?;
+    println!(
+        "Exported {} of {} utterances for review to {}",
+        items.len(),
+        results.len(),
+        args.out.display()
+    );
+    Ok(())
+}
+
+fn import(args: ImportArgs) -> Result<()> {
+    let items = read_review(&args.review)?;
+    let mut entries = read_references(&args.manifest, usize::MAX)?;
+    let source = args.review.file_name().map_or_else(
+        || args.review.display().to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    );
+    let provenance = Provenance {
+        reviewer: args.reviewer,
+        source,
+        at: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    };
+    let summary = apply(&mut entries, &items, &provenance);
+
+    println!(
+        "{} corrected, {} confirmed",
+        summary.corrected, summary.confirmed
+    );
+    if summary.unreviewed > 0 {
+        println!("{} not reviewed yet", summary.unreviewed);
+    }
+    if summary.conflicts > 0 {
+        println!(
+            "{} skipped: the manifest text changed since the export; export again",
+            summary.conflicts
+        );
+    }
+    if summary.missing > 0 {
+        println!(
+            "{} skipped: audio no longer in the manifest",
+            summary.missing
+        );
+    }
+
+    if args.dry_run || summary.corrected == 0 {
+        return Ok(());
+    }
+    let out = args.out.as_ref().unwrap_or(&args.manifest);
+    write_references(out, &entries)?;
+    println!("Wrote {}", out.display());
+    Ok(())
+}
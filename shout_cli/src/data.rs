@@ -0,0 +1,144 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+
+use shout_core::audio::decoder::decode_to_f32_mono_16k;
+use shout_core::audio::playback::play;
+use shout_core::cancel::CancelToken;
+use shout_core::features::SAMPLE_RATE;
+use shout_eval::manifest::{EntryFilter, ReferenceEntry, read_references};
+use shout_tools::append::WriteMode;
+
+#[derive(Args)]
+pub struct DataArgs {
+    #[command(subcommand)]
+    pub command: DataCommand,
+}
+
+#[derive(Subcommand)]
+pub enum DataCommand {
+    /// Convert the corpus TSV under `paths.data_root` into `train.jsonl` in
+    /// `paths.manifests_dir` (see shout_tools).
+    Convert {
+        /// Keep the existing manifest and add only audio it does not list yet.
+        #[arg(long)]
+        append: bool,
+    },
+
+    /// Listen to manifest entries while reading their transcripts.
+    Play(PlayArgs),
+
+    /// Send doubtful transcripts out for human review and merge the
+    /// corrections back into the manifest.
+    Corrections(crate::corrections::CorrectionsArgs),
+
+    /// Find test utterances whose speaker or recording is also in the
+    /// training set.
+    Leakage(crate::leakage::LeakageArgs),
+
+    /// Browse a manifest in a terminal UI: search, filter, inspect spectrograms,
+    /// listen, and mark entries for exclusion.
+    #[cfg(feature = "tui")]
+    Browse(crate::browse::BrowseArgs),
+}
+
+#[derive(Args)]
+pub struct PlayArgs {
+    /// JSONL manifest with `{"audio_path": ..., "text": ...}` per line.
+    pub manifest: PathBuf,
+
+    /// Only entries where FIELD OP VALUE holds; OP is =, !=, <, <=, >, >= or ~
+    /// (contains), e.g. `--filter "duration_ms>15000"`. May be repeated; all
+    /// must hold.
+    #[arg(long = "filter", value_name = "EXPR")]
+    pub filters: Vec<EntryFilter>,
+
+    /// Skip this many of the selected entries.
+    #[arg(long, default_value_t = 0)]
+    pub skip: usize,
+
+    /// Play at most this many entries.
+    #[arg(long)]
+    pub limit: Option<usize>,
+
+    /// Play one entry after the other instead of waiting for Enter.
+    #[arg(long)]
+    pub no_wait: bool,
+}
+
+pub fn run(args: DataArgs) -> Result<()> {
+    match args.command {
+        DataCommand::Convert { append } => {
+            let mode = if append {
+                WriteMode::Append
+            } else {
+                WriteMode::Overwrite
+            };
+            shout_tools::tsv_to_jsonl::convert(mode)
+        }
+        DataCommand::Play(args) => run_play(args),
+        DataCommand::Corrections(args) => crate::corrections::run(args),
+        DataCommand::Leakage(args) => crate::leakage::run(args),
+        #[cfg(feature = "tui")]
+        DataCommand::Browse(args) => crate::browse::run(args),
+    }
+}
+
+fn run_play(args: PlayArgs) -> Result<()> {
+    let entries = read_references(&args.manifest, usize::MAX)?;
+    let selected: Vec<(usize, &ReferenceEntry)> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| args.filters.iter().all(|f| f.matches(entry)))
+        .skip(args.skip)
+        .take(args.limit.unwrap_or(usize::MAX))
+        .collect();
+    println!("{} of {} entries selected", selected.len(), entries.len());
+
+    let mut stdin = io::stdin().lock();
+    let mut n = 0;
+    while n < selected.len() {
+        let (line, entry) = selected[n];
+        println!();
+        println!(
+            "[{}/{}] line {}: {}",
+            n + 1,
+            selected.len(),
+            line + 1,
+            entry.audio_path
+        );
+        println!("  {}", entry.text);
+        for (key, value) in entry.metadata_strings() {
+            println!("  {key}: {value}");
+        }
+
+        let path = shout_config::get().audio_path(&entry.audio_path);
+        match decode_to_f32_mono_16k(&path) {
+            Ok(pcm) => {
+                println!("  ({:.1} s)", pcm.len() as f64 / SAMPLE_RATE as f64);
+                play(&pcm, SAMPLE_RATE, &CancelToken::new()).context("Playback failed")?;
+            }
+            Err(e) => tracing::warn!("{}: {e}", path.display()),
+        }
+
+        if args.no_wait {
+            n += 1;
+            continue;
+        }
+        print!("Enter: next, r: replay, b: back, q: quit > ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if stdin.read_line(&mut answer)? == 0 {
+            break;
+        }
+        match answer.trim() {
+            "q" => break,
+            "r" => {}
+            "b" => n = n.saturating_sub(1),
+            _ => n += 1,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! `shout decode`: any supported audio file to a mono WAV file at the sample
+//! rate the models (or another tool) expect.
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+
+use shout_core::audio::decoder::{DEFAULT_CHUNK_SIZE, DecodeOptions, decode_stream};
+use shout_core::audio::resample::{ResamplerKind, SincQuality};
+use shout_core::features::SAMPLE_RATE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Resampler {
+    /// FFT-based; fast and good enough for speech.
+    Fft,
+
+    /// Short sinc filter.
+    SincFast,
+
+    /// Sinc filter of medium length.
+    Sinc,
+
+    /// Long sinc filter; slowest, least aliasing.
+    SincBest,
+}
+
+impl Resampler {
+    pub fn options(self, target_sr: u32) -> DecodeOptions {
+        let sinc = |quality| ResamplerKind::SincFixed { quality };
+        let resampler = match self {
+            Resampler::Fft => ResamplerKind::Fft,
+            Resampler::SincFast => sinc(SincQuality::Fast),
+            Resampler::Sinc => sinc(SincQuality::Balanced),
+            Resampler::SincBest => sinc(SincQuality::Best),
+        };
+        DecodeOptions {
+            target_sr,
+            resampler,
+            ..DecodeOptions::default()
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct DecodeArgs {
+    /// Audio file (WAV, MP3, FLAC, Ogg Vorbis, ...).
+    pub audio: PathBuf,
+
+    /// WAV file to write (32-bit float, mono).
+    #[arg(long, short)]
+    pub out: PathBuf,
+
+    /// Sample rate of the output in Hz.
+    #[arg(long, default_value_t = SAMPLE_RATE)]
+    pub sample_rate: u32,
+
+    /// How to convert the file's sample rate.
+    #[arg(long, value_enum, default_value = "fft")]
+    pub resampler: Resampler,
+}
+
+pub fn run(args: DecodeArgs) -> Result<()> {
+    let file = File::create(&args.out)
+        .with_context(|| format!("Failed to create {}", args.out.display()))?;
+    let mut out = BufWriter::new(file);
+    out.write_all(&wav_header(args.sample_rate, 0))?;
+
+    // Decoded chunk by chunk, so hour-long files need little memory.
+    let mut samples = 0usize;
+    let mut write = |pcm: Vec<f32>, out: &mut BufWriter<File>| -> Result<()> {
+        for s in &pcm {
+            out.write_all(&s.to_le_bytes())?;
+        }
+        samples += pcm.len();
+        Ok(())
+    };
+    let chunks = decode_stream(&args.audio, DEFAULT_CHUNK_SIZE)?
+        .with_options(args.resampler.options(args.sample_rate));
+    for chunk in chunks {
+        write(chunk?, &mut out)?;
+    }
+
+    out.seek(SeekFrom::Start(0))?;
+    out.write_all(&wav_header(args.sample_rate, samples))?;
+    out.flush()?;
+    println!(
+        "{:.2} s at {} Hz -> {}",
+        samples as f64 / args.sample_rate as f64,
+        args.sample_rate,
+        args.out.display()
+    );
+    Ok(())
+}
+
+/// Write `samples` as a mono 32-bit float WAV file.
+pub fn write_wav(path: &Path, sample_rate: u32, samples: &[f32]) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    out.write_all(&wav_header(sample_rate, samples.len()))?;
+    for s in samples {
+        out.write_all(&s.to_le_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Header of a mono 32-bit float WAV file with `samples` samples.
+fn wav_header(sample_rate: u32, samples: usize) -> Vec<u8> {
+    let data_len = (samples * 4) as u32;
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(36 + data_len).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&3u16.to_le_bytes()); // IEEE float
+    header.extend_from_slice(&1u16.to_le_bytes()); // mono
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * 4).to_le_bytes());
+    header.extend_from_slice(&4u16.to_le_bytes());
+    header.extend_from_slice(&32u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    header
+}
@@ -0,0 +1,106 @@
+//! `shout embed`: one embedding vector per manifest entry, pooled from the
+//! model's encoder, for audio search, clustering and dataset dedup.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use serde_json::json;
+
+use shout_core::backend::device::DeviceSpec;
+use shout_core::npy;
+use shout_eval::manifest::read_references;
+
+use crate::registry::resolve_model;
+use crate::transcribe::load_transcriber;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EmbeddingFormat {
+    /// A float32 matrix with one row per embedded entry (`numpy.load`), plus
+    /// `<out>.paths.txt` naming the audio of each row.
+    Npy,
+
+    /// `{"audio_path": ..., "embedding": [...]}` per line.
+    Jsonl,
+}
+
+#[derive(Args)]
+pub struct EmbedArgs {
+    /// Model directory or name of a pulled model.
+    #[arg(long)]
+    pub model: PathBuf,
+
+    /// JSONL manifest with an `audio_path` per line.
+    #[arg(long)]
+    pub manifest: PathBuf,
+
+    /// Output file.
+    #[arg(long, default_value = "embeddings.npy")]
+    pub out: PathBuf,
+
+    #[arg(long, value_enum, default_value = "npy")]
+    pub format: EmbeddingFormat,
+
+    /// Embed at most this many entries.
+    #[arg(long)]
+    pub max_utts: Option<usize>,
+
+    /// auto, cpu, cuda[:N] or metal[:N].
+    #[arg(long, default_value = shout_config::get().device.as_str())]
+    pub device: DeviceSpec,
+}
+
+pub fn run(args: EmbedArgs) -> Result<()> {
+    let model = resolve_model(&args.model)?;
+    let entries = read_references(&args.manifest, args.max_utts.unwrap_or(usize::MAX))?;
+    let mut transcriber = load_transcriber(&model, args.device, 1)?;
+
+    let mut paths = Vec::with_capacity(entries.len());
+    let mut embeddings = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let audio = shout_config::get().audio_path(&entry.audio_path);
+        match transcriber.embed_file(&audio) {
+            Ok(embedding) => {
+                paths.push(entry.audio_path.as_str());
+                embeddings.push(embedding);
+            }
+            Err(e) => tracing::warn!("{}: {e:#}", entry.audio_path),
+        }
+    }
+
+    match args.format {
+        EmbeddingFormat::Npy => {
+            let dim = transcriber.embedding_dim();
+            let values = embeddings.iter().flatten().copied();
+            npy::write_matrix(&args.out, embeddings.len(), dim, values)?;
+            let index = args.out.with_extension("paths.txt");
+            let mut lines = paths.join("\n");
+            lines.push('\n');
+            std::fs::write(&index, lines)
+                .with_context(|| format!("Failed to write {}", index.display()))?;
+        }
+        EmbeddingFormat::Jsonl => write_jsonl(&args.out, &paths, &embeddings)?,
+    }
+    println!(
+        "Embedded {} of {} entries ({} dimensions) -> {}",
+        embeddings.len(),
+        entries.len(),
+        transcriber.embedding_dim(),
+        args.out.display()
+    );
+    Ok(())
+}
+
+fn write_jsonl(path: &Path, paths: &[&str], rows: &[Vec<f32>]) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    for (audio_path, embedding) in paths.iter().zip(rows) {
+        let line = json!({ "audio_path": audio_path, "embedding": embedding });
+        writeln!(out, "{line}")?;
+    }
+    out.flush()?;
+    Ok(())
+}
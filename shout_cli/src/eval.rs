@@ -0,0 +1,347 @@
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use shout_core::audio::decoder::decode_to_f32_mono_16k;
+use shout_core::backend::device::DeviceSpec;
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::model::shout::ShoutModel;
+use shout_core::output::ctm;
+use shout_core::pipeline::streaming::{
+    Stabilization, StreamUpdate, StreamingOptions, StreamingSession,
+};
+use shout_core::pipeline::transcribe::Transcriber;
+use shout_core::transcript::{Transcript, TranscriptMetadata};
+use shout_eval::groups::breakdown;
+use shout_eval::keywords::{KeywordReport, Keywords, TimedWord};
+use shout_eval::manifest::read_references;
+use shout_eval::nist::{ScoredUtterance, write_sclite_reports};
+use shout_eval::normalize::Normalizer;
+use shout_eval::report::{EvalSummary, UtteranceResult};
+use shout_eval::stats::bootstrap_interval;
+use shout_eval::streaming::{FinalWord, StreamScore, StreamStep, StreamingSummary};
+use shout_eval::taxonomy::{ErrorTaxonomy, Vocabulary};
+use shout_eval::visualize::error_report;
+
+use crate::registry::resolve_model;
+use crate::transcribe::load_transcriber;
+
+#[derive(Args)]
+pub struct EvalArgs {
+    /// Model directory or name of a pulled model.
+    #[arg(long)]
+    pub model: PathBuf,
+
+    /// JSONL manifest with `{"audio_path": ..., "text": ...}` per line.
+    #[arg(long)]
+    pub manifest: PathBuf,
+
+    /// Per-utterance results (JSONL).
+    #[arg(long, default_value = "eval_results.jsonl")]
+    pub out: PathBuf,
+
+    /// auto, cpu, cuda[:N] or metal[:N].
+    #[arg(long, default_value = shout_config::get().device.as_str())]
+    pub device: DeviceSpec,
+
+    /// Spoken language code, or `auto` to detect it.
+    #[arg(long, default_value = shout_config::get().language.as_str())]
+    pub language: LanguageSelection,
+
+    /// Beam search with this many beams instead of greedy decoding.
+    #[arg(long)]
+    pub beam_size: Option<usize>,
+
+    /// Evaluate only the first N entries.
+    #[arg(long)]
+    pub max_utts: Option<usize>,
+
+    /// Print aligned reference/hypothesis pairs of the N utterances with the most errors.
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "20")]
+    pub report: Option<usize>,
+
+    /// Manifest fields to break WER down by; `duration` buckets `duration_ms`.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "speaker,gender,age,duration,source"
+    )]
+    pub group_by: Vec<String>,
+
+    /// Leave groups with fewer utterances than this out of the breakdown.
+    #[arg(long, default_value_t = 20)]
+    pub min_group_size: usize,
+
+    /// Training vocabulary for classifying errors as OOV: a word list or a
+    /// training manifest.
+    #[arg(long)]
+    pub vocab: Option<PathBuf>,
+
+    /// Report recall/precision of these terms (one keyword or phrase per line).
+    #[arg(long)]
+    pub keywords: Option<PathBuf>,
+
+    /// Also write the hypotheses as a NIST CTM file.
+    #[arg(long)]
+    pub ctm: Option<PathBuf>,
+
+    /// Write sclite-style `.sys` and `.pra` reports into this directory.
+    #[arg(long)]
+    pub sclite_dir: Option<PathBuf>,
+
+    /// Bootstrap resamples for the WER confidence interval (0 to skip).
+    #[arg(long, default_value_t = 1000)]
+    pub bootstrap: usize,
+
+    /// Feed the audio through the streaming pipeline in simulated real time and
+    /// also report latency and partial-result stability.
+    #[arg(long)]
+    pub streaming: bool,
+
+    /// Streaming: re-decode after this much new audio.
+    #[arg(long, default_value_t = 1000)]
+    pub step_ms: u64,
+
+    /// Streaming: audio per pushed chunk, as a client would send it.
+    #[arg(long, default_value_t = 100)]
+    pub chunk_ms: u64,
+
+    /// Streaming: when text becomes final (margin, chunks:K or agreement:N).
+    #[arg(long, default_value = "margin")]
+    pub stabilization: Stabilization,
+
+    /// Text normalization of both sides before scoring: whisper, whisper-en
+    /// (English; also expands contractions and writes numbers as digits) or none.
+    #[arg(long, default_value = "whisper")]
+    pub normalizer: Normalizer,
+}
+
+pub fn run(args: EvalArgs) -> Result<()> {
+    let model = resolve_model(&args.model)?;
+    let entries = read_references(&args.manifest, args.max_utts.unwrap_or(usize::MAX))?;
+    let vocabulary = args.vocab.as_deref().map(Vocabulary::load).transpose()?;
+    let keywords = match &args.keywords {
+        Some(path) => Some(Keywords::load(path, args.normalizer)?),
+        None => None,
+    };
+
+    let mut transcriber = load_transcriber(&model, args.device, args.beam_size.unwrap_or(1))?;
+    transcriber.options.language = args.language.clone();
+    transcriber.options.beam_size = args.beam_size;
+
+    let file = File::create(&args.out)
+        .with_context(|| format!("Failed to create {}", args.out.display()))?;
+    let mut out = BufWriter::new(file);
+    let mut ctm_out = match &args.ctm {
+        Some(path) => {
+            Some(BufWriter::new(File::create(path).with_context(|| {
+                format!("Failed to create {}", path.display())
+            })?))
+        }
+        None => None,
+    };
+    let mut summary = EvalSummary::default();
+    let mut taxonomy = ErrorTaxonomy::default();
+    let mut keyword_report = KeywordReport::default();
+    let mut streaming = StreamingSummary::default();
+    let streaming_opts = StreamingOptions {
+        step_ms: args.step_ms,
+        stabilization: args.stabilization,
+        ..Default::default()
+    };
+    let mut results = Vec::with_capacity(entries.len());
+
+    for (i, entry) in entries.iter().enumerate() {
+        let audio = shout_config::get().audio_path(&entry.audio_path);
+        let transcript = if args.streaming {
+            let (t, result) = stream_file(transcriber, &audio, &streaming_opts, args.chunk_ms);
+            transcriber = t;
+            result.map(|(transcript, score)| {
+                streaming.add(&score);
+                transcript
+            })
+        } else {
+            transcriber.transcribe_file(&audio).map_err(Into::into)
+        };
+        let transcript = match transcript {
+            Ok(transcript) => transcript,
+            Err(e) => {
+                tracing::warn!("{}: {e:#}", entry.audio_path);
+                summary.failed += 1;
+                continue;
+            }
+        };
+        if let Some(w) = ctm_out.as_mut() {
+            ctm::write_ctm(w, &transcript, &ctm::file_id(&transcript), "1")?;
+        }
+        let raw_hypothesis = transcript.text();
+
+        taxonomy += &ErrorTaxonomy::classify(&entry.text, &raw_hypothesis, vocabulary.as_ref());
+        let reference = args.normalizer.apply(&entry.text);
+        let hypothesis = args.normalizer.apply(&raw_hypothesis);
+        let mut result = UtteranceResult::score(&entry.audio_path, &reference, &hypothesis);
+        result.confidence = transcript.confidence();
+        result.metadata = entry.metadata_strings();
+        if let Some(keywords) = &keywords {
+            keyword_report.add(keywords, &entry.text, &raw_hypothesis);
+            result.keywords = keywords.detect(&entry.text, &timed_words(&transcript));
+        }
+        summary.add(&result);
+        serde_json::to_writer(&mut out, &result)?;
+        writeln!(out)?;
+        results.push(result);
+
+        eprint!("\r{}/{}", i + 1, entries.len());
+    }
+    eprintln!();
+    out.flush()?;
+    if let Some(mut w) = ctm_out {
+        w.flush()?;
+    }
+
+    if let Some(limit) = args.report {
+        let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        print!("{}", error_report(&results, limit, color));
+    }
+    print!("{summary}");
+    if args.bootstrap > 0 && !results.is_empty() {
+        let words: Vec<_> = results.iter().map(|r| r.words).collect();
+        let ci = bootstrap_interval(&words, args.bootstrap, 0.95, 0);
+        println!(
+            "WER 95% CI: {:.2}% - {:.2}%",
+            ci.lower * 100.0,
+            ci.upper * 100.0
+        );
+    }
+    print!("{taxonomy}");
+    if keywords.is_some() {
+        print!("{keyword_report}");
+    }
+    if args.streaming {
+        print!("{streaming}");
+    }
+    for key in &args.group_by {
+        if let Some(groups) = breakdown(&results, key, args.min_group_size) {
+            print!("{groups}");
+        }
+    }
+    println!("Results: {}", args.out.display());
+    if let Some(dir) = &args.sclite_dir {
+        let scored: Vec<_> = results.iter().map(ScoredUtterance::from_result).collect();
+        let name = args
+            .out
+            .file_stem()
+            .map_or("eval".into(), |s| s.to_string_lossy());
+        write_sclite_reports(dir, &name, &scored)?;
+        println!("sclite reports: {}", dir.display());
+    }
+    Ok(())
+}
+
+/// Run `path` through a streaming session as if a client sent it in real time,
+/// `chunk_ms` at a time. The transcriber is handed back even if this fails.
+fn stream_file(
+    transcriber: Transcriber<ShoutModel>,
+    path: &Path,
+    opts: &StreamingOptions,
+    chunk_ms: u64,
+) -> (Transcriber<ShoutModel>, Result<(Transcript, StreamScore)>) {
+    let mut session = StreamingSession::new(transcriber, opts.clone());
+    let result = run_stream(&mut session, path, chunk_ms);
+    (session.into_inner(), result)
+}
+
+fn run_stream(
+    session: &mut StreamingSession<Transcriber<ShoutModel>>,
+    path: &Path,
+    chunk_ms: u64,
+) -> Result<(Transcript, StreamScore)> {
+    let pcm = decode_to_f32_mono_16k(path)
+        .with_context(|| format!("Failed to decode {}", path.display()))?;
+    let chunk = (chunk_ms as usize * 16).max(1);
+
+    // Simulated clock: audio arrives in real time, and a step's result reaches
+    // the client once the audio is in and the step has been computed.
+    let mut clock_ms = 0.0f64;
+    let mut steps = Vec::new();
+    let mut segments = Vec::new();
+    let mut record = |update: StreamUpdate, audio_ms: f64, started: Instant| {
+        clock_ms = clock_ms.max(audio_ms) + started.elapsed().as_secs_f64() * 1000.0;
+        steps.push(stream_step(&update, clock_ms));
+        segments.extend(update.finals);
+    };
+
+    for (i, samples) in pcm.chunks(chunk).enumerate() {
+        let audio_ms = (i * chunk + samples.len()) as f64 / 16.0;
+        let started = Instant::now();
+        if let Some(update) = session.push(samples)? {
+            record(update, audio_ms, started);
+        }
+    }
+    let started = Instant::now();
+    let update = session.finish()?;
+    record(update, pcm.len() as f64 / 16.0, started);
+
+    let score = StreamScore::from_steps(&steps);
+    let transcript = Transcript {
+        segments,
+        metadata: TranscriptMetadata {
+            audio_path: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    Ok((transcript, score))
+}
+
+/// Hypothesis words with their times; segments without word timings give
+/// each of their words the segment's span.
+fn timed_words(transcript: &Transcript) -> Vec<TimedWord> {
+    let mut words = Vec::new();
+    for segment in &transcript.segments {
+        if segment.words.is_empty() {
+            words.extend(segment.text.split_whitespace().map(|text| TimedWord {
+                text: text.to_string(),
+                start_ms: segment.start_ms,
+                end_ms: segment.end_ms,
+            }));
+        } else {
+            words.extend(segment.words.iter().map(|w| TimedWord {
+                text: w.text.trim().to_string(),
+                start_ms: w.start_ms,
+                end_ms: w.end_ms,
+            }));
+        }
+    }
+    words
+}
+
+/// What the client saw after `update`, at `emitted_ms`.
+fn stream_step(update: &StreamUpdate, emitted_ms: f64) -> StreamStep {
+    let mut finals = Vec::new();
+    for segment in &update.finals {
+        if segment.words.is_empty() {
+            finals.extend(segment.text.split_whitespace().map(|text| FinalWord {
+                text: text.to_string(),
+                start_ms: segment.start_ms,
+                end_ms: segment.end_ms,
+            }));
+        } else {
+            finals.extend(segment.words.iter().map(|w| FinalWord {
+                text: w.text.trim().to_string(),
+                start_ms: w.start_ms,
+                end_ms: w.end_ms,
+            }));
+        }
+    }
+    let partial: Vec<&str> = update.partial.iter().map(|s| s.text.trim()).collect();
+    StreamStep {
+        emitted_ms,
+        finals,
+        partial: partial.join(" "),
+    }
+}
@@ -0,0 +1,63 @@
+//! Exit codes, the same for every subcommand, so scripts can tell a bad input
+//! from a broken model without parsing messages.
+
+use std::io;
+use std::process::ExitCode;
+
+use shout_core::ShoutError;
+
+/// Any other failure.
+pub const FAILURE: u8 = 1;
+
+/// Invalid flags or arguments (clap uses the same code for parse errors).
+pub const USAGE: u8 = 2;
+
+/// An input could not be read, decoded or is in an unsupported format.
+pub const INPUT: u8 = 3;
+
+/// The model, its config or the device could not be used.
+pub const MODEL: u8 = 4;
+
+/// Cancelled or timed out.
+pub const INTERRUPTED: u8 = 5;
+
+/// The exit code for `error`, decided by the first error in its chain that
+/// has a known kind.
+pub fn code(error: &anyhow::Error) -> u8 {
+    error
+        .chain()
+        .find_map(|e| {
+            if let Some(e) = e.downcast_ref::<ShoutError>() {
+                return Some(shout_error_code(e));
+            }
+            e.downcast_ref::<io::Error>().map(|_| INPUT)
+        })
+        .unwrap_or(FAILURE)
+}
+
+fn shout_error_code(error: &ShoutError) -> u8 {
+    match error {
+        ShoutError::Io { .. }
+        | ShoutError::UnsupportedFormat(_)
+        | ShoutError::Decode(_)
+        | ShoutError::Resample(_) => INPUT,
+        ShoutError::Model(_)
+        | ShoutError::Tokenizer(_)
+        | ShoutError::Config(_)
+        | ShoutError::Device(_) => MODEL,
+        ShoutError::InvalidArgument(_) => USAGE,
+        ShoutError::Interrupted { .. } => INTERRUPTED,
+        _ => FAILURE,
+    }
+}
+
+/// Print `result`'s error, if any, and turn it into the process exit code.
+pub fn report(result: anyhow::Result<()>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            ExitCode::from(code(&e))
+        }
+    }
+}
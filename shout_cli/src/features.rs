@@ -0,0 +1,200 @@
+//! `shout features`: log-mel features computed once and cached on disk, so
+//! training reads them instead of decoding and transforming the audio every
+//! epoch.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use serde_json::Value;
+
+use shout_core::audio::decoder::decode_to_f32_mono_16k;
+use shout_core::audio::mel::{MelConfig, MelExtractor};
+use shout_core::cache::hash_bytes;
+use shout_core::npy;
+use shout_eval::manifest::{ReferenceEntry, read_references, write_references};
+
+use crate::mel::MelPreset;
+
+#[derive(Args)]
+pub struct FeaturesArgs {
+    #[command(subcommand)]
+    pub command: FeaturesCommand,
+}
+
+#[derive(Subcommand)]
+pub enum FeaturesCommand {
+    /// Compute the features of every manifest entry into a cache directory
+    /// and write a manifest that points to them.
+    Extract(ExtractArgs),
+}
+
+#[derive(Args)]
+pub struct ExtractArgs {
+    /// Manifest of the audio.
+    pub manifest: PathBuf,
+
+    /// Manifest to write: the entries whose features were computed, with
+    /// `features_path` and `n_frames` added.
+    #[arg(long, short)]
+    pub out: PathBuf,
+
+    /// Where the `.npy` files go (default: `features` in the cache directory).
+    #[arg(long)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Window, mel scale and log compression.
+    #[arg(long, value_enum, default_value = "whisper")]
+    pub preset: MelPreset,
+
+    /// Mel bins per frame.
+    #[arg(long, default_value_t = 80)]
+    pub n_mels: usize,
+
+    /// Files computed at once (default: one per CPU core).
+    #[arg(long)]
+    pub jobs: Option<usize>,
+
+    /// Recompute features that are already cached.
+    #[arg(long)]
+    pub force: bool,
+}
+
+pub fn run(args: FeaturesArgs) -> Result<()> {
+    match args.command {
+        FeaturesCommand::Extract(args) => extract(args),
+    }
+}
+
+fn extract(args: ExtractArgs) -> Result<()> {
+    let config = shout_config::get();
+    let mel_config = MelConfig {
+        n_mels: args.n_mels,
+        ..args.preset.config()
+    };
+    let extractor = mel_config.clone().build()?;
+    let cache_dir = args
+        .cache_dir
+        .unwrap_or_else(|| config.cache_dir().join("features"));
+    // Part of every key, so features of another config are never reused.
+    let config_json = serde_json::to_string(&mel_config)?;
+
+    let entries = read_references(&args.manifest, usize::MAX)?;
+    let jobs = args
+        .jobs
+        .unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4)
+        })
+        .clamp(1, entries.len().max(1));
+
+    // Workers take the next entry until none are left.
+    let force = args.force;
+    let next = AtomicUsize::new(0);
+    let cached = AtomicUsize::new(0);
+    let mut results: Vec<Option<Result<(PathBuf, usize), String>>> = vec![None; entries.len()];
+    thread::scope(|scope| {
+        let (next, cached, entries) = (&next, &cached, &entries);
+        let (extractor, cache_dir, config_json) = (&extractor, &cache_dir, &config_json);
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(move || {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(entry) = entries.get(i) else {
+                            return done;
+                        };
+                        let path = cache_path(cache_dir, &entry.audio_path, config_json);
+                        let result = match cached_frames(&path, extractor.config().n_mels) {
+                            Some(n_frames) if !force => {
+                                cached.fetch_add(1, Ordering::Relaxed);
+                                Ok(n_frames)
+                            }
+                            _ => compute(entry, extractor, &path),
+                        };
+                        done.push((i, result.map(|n| (path, n)).map_err(|e| format!("{e:#}"))));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (i, result) in worker.join().expect("feature worker panicked") {
+                results[i] = Some(result);
+            }
+        }
+    });
+
+    let mut extracted = Vec::with_capacity(entries.len());
+    let mut failed = 0usize;
+    for (mut entry, result) in entries.into_iter().zip(results) {
+        match result.expect("every entry is processed") {
+            Ok((path, n_frames)) => {
+                let path = path.display().to_string();
+                entry
+                    .metadata
+                    .insert("features_path".into(), Value::from(path));
+                entry
+                    .metadata
+                    .insert("n_frames".into(), Value::from(n_frames));
+                extracted.push(entry);
+            }
+            Err(e) => {
+                failed += 1;
+                println!("{}: {e}", entry.audio_path);
+            }
+        }
+    }
+    write_references(&args.out, &extracted)?;
+
+    println!(
+        "{} entries ({} already cached), {failed} failed -> {}",
+        extracted.len(),
+        cached.into_inner(),
+        cache_dir.display()
+    );
+    println!("Wrote {}", args.out.display());
+    Ok(())
+}
+
+/// Where the features of `audio_path` under `config_json` are cached: named
+/// by a hash of both, in one of 256 subdirectories so none grows huge.
+fn cache_path(cache_dir: &Path, audio_path: &str, config_json: &str) -> PathBuf {
+    let key = hash_bytes(format!("{audio_path}\n{config_json}").as_bytes());
+    cache_dir.join(&key[..2]).join(format!("{key}.npy"))
+}
+
+/// Decode `entry`'s audio and write its features to `path`; returns the
+/// number of frames.
+fn compute(entry: &ReferenceEntry, extractor: &MelExtractor, path: &Path) -> Result<usize> {
+    let audio = shout_config::get().audio_path(&entry.audio_path);
+    let pcm = decode_to_f32_mono_16k(&audio)
+        .with_context(|| format!("Failed to decode {}", audio.display()))?;
+    let mel = extractor.compute(&pcm);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+
+    // Written under a temporary name, so an interrupted run leaves no
+    // truncated file that looks cached.
+    let partial = path.with_extension("npy.partial");
+    npy::write_matrix(&partial, mel.n_frames, mel.n_mels, mel.data)?;
+    fs::rename(&partial, path)
+        .with_context(|| format!("Failed to move features to {}", path.display()))?;
+    Ok(mel.n_frames)
+}
+
+/// Frames of the cached `(frames, n_mels)` matrix at `path`, if there is one
+/// whose size matches its header.
+fn cached_frames(path: &Path, n_mels: usize) -> Option<usize> {
+    let size = fs::metadata(path).ok()?.len() as usize;
+    let header = npy::load_header(path).ok()?;
+    let [rows, cols] = header.shape[..] else {
+        return None;
+    };
+    (cols == n_mels && header.data_offset + rows * cols * 4 == size).then_some(rows)
+}
@@ -0,0 +1,159 @@
+//! `shout data leakage`: find test utterances whose speaker or recording also
+//! occurs in the training set.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use serde_json::json;
+
+use shout_core::backend::device::DeviceSpec;
+use shout_core::inference::{ShoutModel, Transcriber};
+use shout_eval::leakage::{
+    DEFAULT_THRESHOLD, SimilarPair, shared_audio, shared_speakers, similar_pairs,
+};
+use shout_eval::manifest::{ReferenceEntry, read_references};
+
+use crate::registry::resolve_model;
+use crate::transcribe::load_transcriber;
+
+#[derive(Args)]
+pub struct LeakageArgs {
+    /// Training manifest.
+    #[arg(long)]
+    pub train: PathBuf,
+
+    /// Test manifest.
+    #[arg(long)]
+    pub test: PathBuf,
+
+    /// Model whose encoder embeds the audio.
+    #[arg(long, default_value = "whisper-tiny")]
+    pub model: PathBuf,
+
+    /// Report pairs with at least this cosine similarity (0-1).
+    #[arg(long, default_value_t = DEFAULT_THRESHOLD)]
+    pub threshold: f32,
+
+    /// Use at most this many entries of each manifest.
+    #[arg(long)]
+    pub max_utts: Option<usize>,
+
+    /// Print at most this many pairs.
+    #[arg(long, default_value_t = 20)]
+    pub show: usize,
+
+    /// Write every finding to this JSON file.
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+
+    /// auto, cpu, cuda[:N] or metal[:N].
+    #[arg(long, default_value = shout_config::get().device.as_str())]
+    pub device: DeviceSpec,
+}
+
+pub fn run(args: LeakageArgs) -> Result<()> {
+    let max = args.max_utts.unwrap_or(usize::MAX);
+    let train = read_references(&args.train, max)?;
+    let test = read_references(&args.test, max)?;
+
+    // Owned, since embedding below consumes the entries.
+    let audio: Vec<String> = shared_audio(&train, &test)
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+    let speakers = shared_speakers(&train, &test);
+    for path in audio.iter().take(args.show) {
+        println!("Same file in both splits: {path}");
+    }
+    for (speaker, n) in speakers.iter().take(args.show) {
+        println!("Speaker {speaker} in both splits ({n} test utterances)");
+    }
+
+    let model = resolve_model(&args.model)?;
+    let mut transcriber = load_transcriber(&model, args.device, 1)?;
+    let (train, train_vectors) = embed_all(&mut transcriber, train);
+    let (test, test_vectors) = embed_all(&mut transcriber, test);
+    let pairs = similar_pairs(&train_vectors, &test_vectors, args.threshold, 3);
+    for p in pairs.iter().take(args.show) {
+        println!(
+            "{:.3}  {}  ~  {}",
+            p.similarity, test[p.test].audio_path, train[p.train].audio_path
+        );
+    }
+
+    if let Some(path) = &args.report {
+        write_report(path, &audio, &speakers, &pairs, &train, &test)?;
+    }
+    println!(
+        "{} shared files, {} shared speakers, {} of {} test utterances close to training audio",
+        audio.len(),
+        speakers.len(),
+        distinct_tests(&pairs),
+        test.len()
+    );
+    if !audio.is_empty() || !speakers.is_empty() || !pairs.is_empty() {
+        bail!("The test set leaks into the training set");
+    }
+    Ok(())
+}
+
+/// Embeddings of the entries that could be decoded, and those entries.
+fn embed_all(
+    transcriber: &mut Transcriber<ShoutModel>,
+    entries: Vec<ReferenceEntry>,
+) -> (Vec<ReferenceEntry>, Vec<Vec<f32>>) {
+    let mut kept = Vec::with_capacity(entries.len());
+    let mut vectors = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let audio = shout_config::get().audio_path(&entry.audio_path);
+        match transcriber.embed_file(&audio) {
+            Ok(v) => {
+                vectors.push(v);
+                kept.push(entry);
+            }
+            Err(e) => tracing::warn!("{}: {e:#}", entry.audio_path),
+        }
+    }
+    (kept, vectors)
+}
+
+fn distinct_tests(pairs: &[SimilarPair]) -> usize {
+    let mut tests: Vec<usize> = pairs.iter().map(|p| p.test).collect();
+    tests.sort_unstable();
+    tests.dedup();
+    tests.len()
+}
+
+fn write_report(
+    path: &Path,
+    audio: &[String],
+    speakers: &BTreeMap<String, usize>,
+    pairs: &[SimilarPair],
+    train: &[ReferenceEntry],
+    test: &[ReferenceEntry],
+) -> Result<()> {
+    let pairs: Vec<_> = pairs
+        .iter()
+        .map(|p| {
+            json!({
+                "similarity": p.similarity,
+                "test": test[p.test].audio_path,
+                "train": train[p.train].audio_path,
+            })
+        })
+        .collect();
+    let report = json!({
+        "shared_audio": audio,
+        "shared_speakers": speakers,
+        "similar_pairs": pairs,
+    });
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &report)?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
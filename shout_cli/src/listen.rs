@@ -0,0 +1,192 @@
+//! `shout listen`: live transcription from the microphone.
+//!
+//! [`MicrophoneStream`] downmixes and resamples the device's audio to 16 kHz
+//! as it arrives (with a `StreamResampler`), a streaming session re-decodes
+//! it every `--step-ms`, and each segment is printed on its own line as soon
+//! as it becomes final.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use shout_core::audio::capture::{CAPTURE_SAMPLE_RATE, MicrophoneStream};
+use shout_core::audio::decoder::decode_to_f32_mono_16k;
+use shout_core::backend::device::DeviceSpec;
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::pipeline::WindowTranscriber;
+use shout_core::pipeline::streaming::{
+    Stabilization, StreamUpdate, StreamingOptions, StreamingSession,
+};
+
+use crate::registry::resolve_model;
+use crate::transcribe::load_transcriber;
+
+/// How long to wait for the device before checking the limits again.
+const POLL: Duration = Duration::from_millis(100);
+
+/// Audio per chunk when `--input` plays a file.
+const FILE_CHUNK_MS: usize = 100;
+
+#[derive(Args)]
+pub struct ListenArgs {
+    /// Model directory or name of a pulled model.
+    #[arg(long)]
+    pub model: PathBuf,
+
+    /// auto, cpu, cuda[:N] or metal[:N].
+    #[arg(long, default_value = shout_config::get().device.as_str())]
+    pub device: DeviceSpec,
+
+    /// Spoken language code, or `auto` to detect it.
+    #[arg(long, default_value = shout_config::get().language.as_str())]
+    pub language: LanguageSelection,
+
+    /// Re-decode after this much new audio, in milliseconds.
+    #[arg(long, default_value_t = 1000)]
+    pub step_ms: u64,
+
+    /// When text becomes final: margin, chunks:K or agreement:N.
+    #[arg(long, default_value = "margin")]
+    pub stabilization: Stabilization,
+
+    /// Prefix each line with the segment's start and end in seconds.
+    #[arg(long)]
+    pub timestamps: bool,
+
+    /// Stop after this many seconds of audio (default: until interrupted).
+    #[arg(long)]
+    pub max_secs: Option<f64>,
+
+    /// Play this audio file into the session as if it were being recorded,
+    /// instead of listening to the microphone.
+    #[arg(long, value_name = "PATH")]
+    pub input: Option<PathBuf>,
+}
+
+/// Live 16 kHz mono audio, a chunk at a time: empty while waiting for more,
+/// `None` once the source has ended.
+trait LiveSource {
+    fn next_chunk(&mut self) -> Result<Option<Vec<f32>>>;
+}
+
+impl LiveSource for MicrophoneStream {
+    fn next_chunk(&mut self) -> Result<Option<Vec<f32>>> {
+        Ok(MicrophoneStream::next_chunk(self, POLL)?)
+    }
+}
+
+/// A decoded file handed out in chunks, without waiting between them.
+struct FileSource {
+    pcm: Vec<f32>,
+    pos: usize,
+}
+
+impl LiveSource for FileSource {
+    fn next_chunk(&mut self) -> Result<Option<Vec<f32>>> {
+        if self.pos >= self.pcm.len() {
+            return Ok(None);
+        }
+        let chunk = FILE_CHUNK_MS * CAPTURE_SAMPLE_RATE as usize / 1000;
+        let end = (self.pos + chunk).min(self.pcm.len());
+        let chunk = self.pcm[self.pos..end].to_vec();
+        self.pos = end;
+        Ok(Some(chunk))
+    }
+}
+
+pub fn run(args: ListenArgs) -> Result<()> {
+    let model = resolve_model(&args.model)?;
+    let mut transcriber = load_transcriber(&model, args.device, 1)?;
+    transcriber.options.language = args.language.clone();
+
+    let mut source: Box<dyn LiveSource> = match &args.input {
+        Some(path) => {
+            let pcm = decode_to_f32_mono_16k(path)
+                .with_context(|| format!("Failed to decode {}", path.display()))?;
+            Box::new(FileSource { pcm, pos: 0 })
+        }
+        None => {
+            let microphone = MicrophoneStream::open_default()?;
+            tracing::info!("Listening on {} (Ctrl-C to stop)", microphone.device_name());
+            Box::new(microphone)
+        }
+    };
+
+    let opts = StreamingOptions {
+        sample_rate: CAPTURE_SAMPLE_RATE,
+        step_ms: args.step_ms,
+        stabilization: args.stabilization,
+        ..Default::default()
+    };
+    let mut session = StreamingSession::new(transcriber, opts);
+    listen(
+        source.as_mut(),
+        &mut session,
+        &args,
+        &mut io::stdout().lock(),
+    )
+}
+
+/// Feed `source` into `session` until it ends or `--max-secs` have been
+/// heard, writing final segments to `out` as they come.
+fn listen<T: WindowTranscriber>(
+    source: &mut dyn LiveSource,
+    session: &mut StreamingSession<T>,
+    args: &ListenArgs,
+    out: &mut impl Write,
+) -> Result<()> {
+    let max_samples = args
+        .max_secs
+        .map(|s| (s * CAPTURE_SAMPLE_RATE as f64) as usize);
+    // The partial hypothesis is only shown to someone watching.
+    let partials = io::stderr().is_terminal();
+
+    let mut heard = 0usize;
+    while let Some(chunk) = source.next_chunk()? {
+        heard += chunk.len();
+        if let Some(update) = session.push(&chunk)? {
+            show(&update, args.timestamps, partials, out)?;
+        }
+        if max_samples.is_some_and(|max| heard >= max) {
+            break;
+        }
+    }
+    show(&session.finish()?, args.timestamps, false, out)
+}
+
+fn show(
+    update: &StreamUpdate,
+    timestamps: bool,
+    partials: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    // Clear the partial line before it is overwritten or made final.
+    if io::stderr().is_terminal() {
+        eprint!("\r\x1b[K");
+    }
+    for segment in &update.finals {
+        let text = segment.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        if timestamps {
+            let (start, end) = (
+                segment.start_ms as f64 / 1000.0,
+                segment.end_ms as f64 / 1000.0,
+            );
+            writeln!(out, "[{start:.2} --> {end:.2}] {text}")?;
+        } else {
+            writeln!(out, "{text}")?;
+        }
+    }
+    out.flush()?;
+
+    if partials {
+        let text: Vec<&str> = update.partial.iter().map(|s| s.text.trim()).collect();
+        eprint!("{}", text.join(" "));
+    }
+    Ok(())
+}
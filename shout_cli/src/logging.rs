@@ -0,0 +1,57 @@
+//! Diagnostics on stderr through `tracing`; results stay on stdout.
+
+use clap::ValueEnum;
+use tracing::Level;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+
+use crate::metrics::StageLayer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+/// Install the global subscriber. `verbosity` counts `-v` minus `-q`: 0 logs
+/// shout's own info messages, 1 adds debug messages and stage timings, 2 and
+/// more trace; below 0 only warnings and errors. `RUST_LOG`, if set, replaces
+/// the level selection. With `stage_metrics`, stage timings are also recorded
+/// for [`crate::metrics`], whatever is logged.
+pub fn init(verbosity: i8, format: LogFormat, stage_metrics: bool) {
+    let level = match verbosity {
+        ..=-1 => "warn",
+        0 => "info",
+        1 => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        let crates = ["shout_cli", "shout_config", "shout_core", "shout_eval"];
+        let directives: Vec<String> = crates.iter().map(|c| format!("{c}={level}")).collect();
+        EnvFilter::new(format!("warn,{}", directives.join(",")))
+    });
+    // Span closings carry each stage's duration.
+    let spans = if verbosity >= 1 {
+        FmtSpan::CLOSE
+    } else {
+        FmtSpan::NONE
+    };
+
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_span_events(spans)
+        .with_writer(std::io::stderr);
+    let fmt = match format {
+        LogFormat::Text => fmt.with_target(verbosity >= 1).boxed(),
+        LogFormat::Json => fmt.json().boxed(),
+    };
+    let stages = stage_metrics
+        .then(|| StageLayer.with_filter(Targets::new().with_target("shout_core", Level::DEBUG)));
+    tracing_subscriber::registry()
+        .with(fmt.with_filter(filter))
+        .with(stages)
+        .init();
+}
@@ -1,3 +1,195 @@
-fn main() {
-    println!("Hello, world!");
+mod batch;
+mod bench;
+#[cfg(feature = "tui")]
+mod browse;
+mod calibrate;
+mod compare;
+mod corrections;
+mod data;
+mod decode;
+mod embed;
+mod eval;
+mod exit;
+mod features;
+mod leakage;
+mod listen;
+mod logging;
+mod manifest;
+mod mel;
+mod metrics;
+mod model;
+mod quickstart;
+mod registry;
+mod score;
+mod segment;
+#[cfg(feature = "server")]
+mod serve;
+mod transcribe;
+
+use std::io;
+use std::process::ExitCode;
+
+use anyhow::Result;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+
+use shout_core::backend::device::set_cpu_threads;
+
+/// Defaults of the options below come from `shout.toml` and `SHOUT_*` variables
+/// (see the shout_config crate); flags override both.
+///
+/// Exit codes: 0 success, 1 other failure, 2 invalid usage, 3 unreadable or
+/// unsupported input, 4 unusable model or device, 5 cancelled or timed out.
+#[derive(Parser)]
+#[command(
+    name = "shout",
+    version,
+    about = "Speech recognition with Whisper-style models"
+)]
+struct Cli {
+    /// CPU threads for inference (default: all cores).
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+
+    /// More diagnostics on stderr: -v adds debug messages and stage timings, -vv traces.
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Only warnings and errors on stderr.
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Diagnostics as text or JSON lines.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    log_format: logging::LogFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Transcribe an audio file, or every file of a manifest.
+    Transcribe(transcribe::TranscribeArgs),
+
+    /// Transcribe every file in a manifest.
+    Batch(batch::BatchArgs),
+
+    /// Transcribe the microphone live, printing text as it becomes final.
+    Listen(listen::ListenArgs),
+
+    /// Manage model checkpoints.
+    Model(model::ModelArgs),
+
+    /// Run the HTTP transcription server.
+    #[cfg(feature = "server")]
+    Serve(serve::ServeArgs),
+
+    /// Score a model's transcripts against a manifest (WER/CER).
+    Eval(eval::EvalArgs),
+
+    /// Score CTM hypotheses against STM references (NIST/sclite formats).
+    Score(score::ScoreArgs),
+
+    /// Test whether two eval runs differ significantly (paired bootstrap).
+    Compare(compare::CompareArgs),
+
+    /// Measure how well word confidences match accuracy, and fit a calibration.
+    Calibrate(calibrate::CalibrateArgs),
+
+    /// Measure speed (real-time factor, latency, tokens/s) and memory use.
+    Bench(bench::BenchArgs),
+
+    /// Decode an audio file to a mono WAV file.
+    Decode(decode::DecodeArgs),
+
+    /// Compute the log-mel spectrogram of an audio file.
+    Mel(mel::MelArgs),
+
+    /// Create and inspect JSONL manifests.
+    Manifest(manifest::ManifestArgs),
+
+    /// Cut long recordings at pauses into short chunks, with a manifest.
+    Segment(segment::SegmentArgs),
+
+    /// Prepare datasets and manifests.
+    Data(data::DataArgs),
+
+    /// Precompute and cache features for training.
+    Features(features::FeaturesArgs),
+
+    /// Write an embedding vector per manifest entry (audio search, clustering, dedup).
+    Embed(embed::EmbedArgs),
+
+    /// Train or fine-tune a model.
+    Train,
+
+    /// Download, prepare, fine-tune and evaluate in one go on a small corpus
+    /// subset, to see the whole system work end to end.
+    Quickstart(quickstart::QuickstartArgs),
+
+    /// Print a shell completion script (`shout completions bash > ~/.bash_completion`).
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+impl Command {
+    /// Whether the command reports Prometheus metrics.
+    fn records_metrics(&self) -> bool {
+        match self {
+            #[cfg(feature = "server")]
+            Command::Serve(_) => true,
+            Command::Batch(args) => args.metrics_push.is_some(),
+            _ => false,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    exit::report(run())
+}
+
+fn run() -> Result<()> {
+    // Before parsing: the config supplies the flags' defaults.
+    let config = shout_config::init()?;
+    let cli = Cli::parse();
+    let verbosity = if cli.quiet {
+        -1
+    } else {
+        cli.verbose.min(3) as i8
+    };
+    logging::init(verbosity, cli.log_format, cli.command.records_metrics());
+    if let Some(n) = cli.threads.or(config.threads) {
+        // SAFETY: nothing else has been started yet; we are the only thread.
+        unsafe { set_cpu_threads(n) };
+    }
+
+    match cli.command {
+        Command::Transcribe(args) => transcribe::run(args),
+        Command::Batch(args) => batch::run(args),
+        Command::Listen(args) => listen::run(args),
+        Command::Model(args) => model::run(args),
+        #[cfg(feature = "server")]
+        Command::Serve(args) => serve::run(args),
+        Command::Eval(args) => eval::run(args),
+        Command::Score(args) => score::run(args),
+        Command::Compare(args) => compare::run(args),
+        Command::Calibrate(args) => calibrate::run(args),
+        Command::Bench(args) => bench::run(args),
+        Command::Decode(args) => decode::run(args),
+        Command::Mel(args) => mel::run(args),
+        Command::Manifest(args) => manifest::run(args),
+        Command::Segment(args) => segment::run(args),
+        Command::Data(args) => data::run(args),
+        Command::Embed(args) => embed::run(args),
+        Command::Features(args) => features::run(args),
+        Command::Train => shout_train::train(),
+        Command::Quickstart(args) => quickstart::run(args),
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "shout", &mut io::stdout());
+            Ok(())
+        }
+    }
 }
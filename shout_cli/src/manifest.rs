@@ -0,0 +1,550 @@
+//! `shout manifest`: create and inspect JSONL manifests.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+
+use shout_core::audio::decoder::{self, AudioReport};
+use shout_eval::manifest::{ReferenceEntry, read_references, write_references};
+use shout_eval::manifest_filter::FilterRules;
+use shout_eval::manifest_split::{SplitSize, split};
+use shout_eval::manifest_stats::{DURATION_BUCKETS, Distribution, ManifestStats};
+use shout_tools::append::WriteMode;
+use shout_tools::common_voice::{self, VoteFilter};
+use shout_tools::kaldi;
+use shout_tools::tsv_to_jsonl::{self, ColumnMap, TsvSource};
+
+#[derive(Args)]
+pub struct ManifestArgs {
+    #[command(subcommand)]
+    pub command: ManifestCommand,
+}
+
+#[derive(Subcommand)]
+pub enum ManifestCommand {
+    /// Convert a TSV corpus and its audio directory into a manifest.
+    Convert(ConvertArgs),
+
+    /// Import a Mozilla Common Voice release into a manifest.
+    CommonVoice(CommonVoiceArgs),
+
+    /// Import a Kaldi data directory (`wav.scp`, `text`, `utt2spk`, `utt2dur`).
+    FromKaldi(FromKaldiArgs),
+
+    /// Export a manifest as a Kaldi data directory.
+    ToKaldi(ToKaldiArgs),
+
+    /// Drop entries by duration, speaking rate, text and quality tags,
+    /// writing the rejects with their reasons alongside.
+    Filter(FilterArgs),
+
+    /// Split a manifest into train, dev and test, optionally keeping each
+    /// speaker in one split.
+    Split(SplitArgs),
+
+    /// Decode every file of a manifest and report unreadable, truncated,
+    /// NaN or clipped audio.
+    Verify(VerifyArgs),
+
+    /// Summarize a manifest: hours, durations, text lengths, speaking-rate
+    /// outliers and per-split totals.
+    Stats(StatsArgs),
+}
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// Corpus TSV (default: the corpus under `paths.data_root`).
+    #[arg(long)]
+    pub tsv: Option<PathBuf>,
+
+    /// Directory the TSV's audio paths are relative to (default: the TSV's
+    /// directory, or `audios/` for the default corpus).
+    #[arg(long)]
+    pub audio_dir: Option<PathBuf>,
+
+    /// Which columns hold the fields: `audio=COL,text=COL[,duration=COL][,speaker=COL]`.
+    #[arg(long)]
+    pub columns: Option<ColumnMap>,
+
+    /// Manifest to write (default: `train.jsonl` in `paths.manifests_dir`).
+    #[arg(long, short)]
+    pub out: Option<PathBuf>,
+
+    /// Keep the existing manifest and add only audio it does not list yet.
+    #[arg(long)]
+    pub append: bool,
+}
+
+#[derive(Args)]
+pub struct CommonVoiceArgs {
+    /// Extracted release: the directory holding `clips/` and the TSVs.
+    pub release_dir: PathBuf,
+
+    /// Split to import (`validated`, `train`, `dev`, `test`, ...).
+    #[arg(long, default_value = "validated")]
+    pub split: String,
+
+    /// Skip clips with fewer up votes.
+    #[arg(long, default_value_t = 0)]
+    pub min_up_votes: u32,
+
+    /// Skip clips with more down votes.
+    #[arg(long)]
+    pub max_down_votes: Option<u32>,
+
+    /// Manifest to write (default: `<split>.jsonl` in `paths.manifests_dir`).
+    #[arg(long, short)]
+    pub out: Option<PathBuf>,
+
+    /// Keep the existing manifest and add only audio it does not list yet.
+    #[arg(long)]
+    pub append: bool,
+}
+
+#[derive(Args)]
+pub struct FromKaldiArgs {
+    /// Kaldi data directory.
+    pub data_dir: PathBuf,
+
+    /// Manifest to write.
+    #[arg(long, short)]
+    pub out: PathBuf,
+
+    /// Keep the existing manifest and add only audio it does not list yet.
+    #[arg(long)]
+    pub append: bool,
+}
+
+#[derive(Args)]
+pub struct ToKaldiArgs {
+    /// JSONL manifest.
+    pub manifest: PathBuf,
+
+    /// Data directory to write.
+    pub data_dir: PathBuf,
+}
+
+#[derive(Args)]
+pub struct FilterArgs {
+    /// JSONL manifest to filter.
+    pub manifest: PathBuf,
+
+    /// Manifest of the kept entries.
+    #[arg(long, short)]
+    pub out: PathBuf,
+
+    /// Manifest of the dropped entries, each with a `reject_reason`
+    /// (default: `<out>.rejects.jsonl`).
+    #[arg(long)]
+    pub rejects: Option<PathBuf>,
+
+    /// Drop entries shorter than this many milliseconds.
+    #[arg(long)]
+    pub min_duration_ms: Option<u64>,
+
+    /// Drop entries longer than this many milliseconds.
+    #[arg(long)]
+    pub max_duration_ms: Option<u64>,
+
+    /// Drop entries whose transcript has more characters per second of audio.
+    #[arg(long)]
+    pub max_chars_per_sec: Option<f64>,
+
+    /// Drop transcripts with fewer characters (1 drops empty ones).
+    #[arg(long, default_value_t = 1)]
+    pub min_text_chars: usize,
+
+    /// Drop transcripts containing any of these characters.
+    #[arg(long, default_value = "")]
+    pub disallow_chars: String,
+
+    /// Drop entries with any of these quality tags (comma separated).
+    #[arg(long, value_delimiter = ',')]
+    pub reject_tags: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct SplitArgs {
+    /// JSONL manifest to split.
+    pub manifest: PathBuf,
+
+    /// Directory for `train.jsonl`, `dev.jsonl` and `test.jsonl` (default:
+    /// the manifest's directory).
+    #[arg(long)]
+    pub out_dir: Option<PathBuf>,
+
+    /// Shares of train, dev and test, of audio when every entry has a
+    /// duration and of entries otherwise.
+    #[arg(long, value_delimiter = ',', default_value = "0.9,0.05,0.05")]
+    pub ratio: Vec<f64>,
+
+    /// Hours of audio in dev and test, instead of `--ratio`.
+    #[arg(long, num_args = 2, value_names = ["DEV", "TEST"], conflicts_with = "ratio")]
+    pub hours: Option<Vec<f64>>,
+
+    /// Keep all entries with the same value of this field in one split.
+    #[arg(long, value_name = "FIELD", num_args = 0..=1, default_missing_value = "speaker")]
+    pub by_speaker: Option<String>,
+
+    /// Changes which groups land in which split.
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// JSONL manifest to check.
+    pub manifest: PathBuf,
+
+    /// Files decoded at once (default: one per core).
+    #[arg(long)]
+    pub jobs: Option<usize>,
+
+    /// Largest difference in milliseconds between the header's, the
+    /// manifest's and the decoded duration.
+    #[arg(long, default_value_t = 100)]
+    pub tolerance_ms: u64,
+
+    /// Write the entries without problems to this manifest.
+    #[arg(long)]
+    pub clean: Option<PathBuf>,
+
+    /// Count clipped audio as a problem too.
+    #[arg(long)]
+    pub drop_clipped: bool,
+}
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// JSONL manifest.
+    pub manifest: PathBuf,
+
+    /// Rate outliers to list.
+    #[arg(long, default_value_t = 10)]
+    pub outliers: usize,
+}
+
+pub fn run(args: ManifestArgs) -> Result<()> {
+    match args.command {
+        ManifestCommand::Convert(args) => convert(args),
+        ManifestCommand::CommonVoice(args) => common_voice(args),
+        ManifestCommand::FromKaldi(args) => {
+            let mode = if args.append {
+                WriteMode::Append
+            } else {
+                WriteMode::Overwrite
+            };
+            let written = kaldi::import(&args.data_dir, &args.out, mode)?;
+            println!("Wrote {written} entries to {}", args.out.display());
+            Ok(())
+        }
+        ManifestCommand::ToKaldi(args) => {
+            let written = kaldi::export(&args.manifest, &args.data_dir)?;
+            println!("Wrote {written} utterances to {}", args.data_dir.display());
+            Ok(())
+        }
+        ManifestCommand::Filter(args) => filter(args),
+        ManifestCommand::Split(args) => split_manifest(args),
+        ManifestCommand::Verify(args) => verify(args),
+        ManifestCommand::Stats(args) => stats(args),
+    }
+}
+
+fn convert(args: ConvertArgs) -> Result<()> {
+    let source = TsvSource::from_args(args.tsv, args.audio_dir, args.columns)?;
+    let out = args
+        .out
+        .unwrap_or_else(|| shout_config::get().manifests_dir().join("train.jsonl"));
+    let mode = if args.append {
+        WriteMode::Append
+    } else {
+        WriteMode::Overwrite
+    };
+    let written = tsv_to_jsonl::convert_tsv(&source, &out, mode)?;
+    println!("Wrote {written} entries to {}", out.display());
+    Ok(())
+}
+
+fn common_voice(args: CommonVoiceArgs) -> Result<()> {
+    let out = args.out.unwrap_or_else(|| {
+        shout_config::get()
+            .manifests_dir()
+            .join(format!("{}.jsonl", args.split))
+    });
+    let votes = VoteFilter {
+        min_up_votes: args.min_up_votes,
+        max_down_votes: args.max_down_votes,
+    };
+    let mode = if args.append {
+        WriteMode::Append
+    } else {
+        WriteMode::Overwrite
+    };
+    let written = common_voice::import(&args.release_dir, &args.split, votes, &out, mode)?;
+    println!("Wrote {written} entries to {}", out.display());
+    Ok(())
+}
+
+fn filter(args: FilterArgs) -> Result<()> {
+    let rules = FilterRules {
+        min_duration_ms: args.min_duration_ms,
+        max_duration_ms: args.max_duration_ms,
+        max_chars_per_sec: args.max_chars_per_sec,
+        min_text_chars: args.min_text_chars,
+        disallowed_chars: args.disallow_chars.chars().collect(),
+        rejected_tags: args
+            .reject_tags
+            .into_iter()
+            .map(|t| t.trim().to_string())
+            .collect(),
+    };
+    let entries = read_references(&args.manifest, usize::MAX)?;
+    let total = entries.len();
+
+    let mut kept = Vec::with_capacity(total);
+    let mut rejects = Vec::new();
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for mut entry in entries {
+        match rules.check(&entry) {
+            None => kept.push(entry),
+            Some(reason) => {
+                *counts.entry(reason.kind()).or_default() += 1;
+                entry
+                    .metadata
+                    .insert("reject_reason".into(), reason.to_string().into());
+                rejects.push(entry);
+            }
+        }
+    }
+
+    let rejects_path = args.rejects.unwrap_or_else(|| {
+        let mut path = args.out.clone().into_os_string();
+        path.push(".rejects.jsonl");
+        path.into()
+    });
+    write_references(&args.out, &kept)?;
+    write_references(&rejects_path, &rejects)?;
+
+    println!(
+        "Kept {} of {total} entries -> {}",
+        kept.len(),
+        args.out.display()
+    );
+    println!("Dropped {} -> {}", rejects.len(), rejects_path.display());
+    for (kind, n) in counts {
+        println!("  {kind:<16} {n:>8}");
+    }
+    Ok(())
+}
+
+fn split_manifest(args: SplitArgs) -> Result<()> {
+    let size = match (args.hours.as_deref(), args.ratio.as_slice()) {
+        (Some(&[dev, test]), _) => SplitSize::Hours { dev, test },
+        (_, &[train, dev, test]) => SplitSize::Ratio([train, dev, test]),
+        _ => bail!("--ratio takes three shares: TRAIN,DEV,TEST"),
+    };
+    let out_dir = match args.out_dir {
+        Some(dir) => dir,
+        None => args
+            .manifest
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+    };
+    let entries = read_references(&args.manifest, usize::MAX)?;
+    let splits = split(entries, size, args.by_speaker.as_deref(), args.seed)?;
+
+    std::fs::create_dir_all(&out_dir)
+        .with_context(|| format!("Failed to create {}", out_dir.display()))?;
+    for (name, entries) in ["train", "dev", "test"].into_iter().zip(&splits) {
+        let path = out_dir.join(format!("{name}.jsonl"));
+        write_references(&path, entries)?;
+        let stats = ManifestStats::compute(entries);
+        println!(
+            "{name:<5} {:>8} utterances  {:>8.2} h  {:>6} speakers -> {}",
+            entries.len(),
+            stats.totals.hours(),
+            stats.speakers,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn verify(args: VerifyArgs) -> Result<()> {
+    let config = shout_config::get();
+    let entries = read_references(&args.manifest, usize::MAX)?;
+    let jobs = args
+        .jobs
+        .unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4)
+        })
+        .clamp(1, entries.len().max(1));
+
+    // Workers take the next entry until none are left.
+    let next = AtomicUsize::new(0);
+    let mut reports: Vec<Option<Result<AudioReport, String>>> = vec![None; entries.len()];
+    thread::scope(|scope| {
+        let (next, entries) = (&next, &entries);
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(move || {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(entry) = entries.get(i) else {
+                            return done;
+                        };
+                        let path = config.audio_path(&entry.audio_path);
+                        done.push((i, decoder::inspect(&path).map_err(|e| e.to_string())));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (i, report) in worker.join().expect("verify worker panicked") {
+                reports[i] = Some(report);
+            }
+        }
+    });
+
+    let mut clean = Vec::new();
+    let mut bad = 0usize;
+    for (entry, report) in entries.into_iter().zip(reports) {
+        let report = report.expect("every entry is inspected");
+        let problems = audio_problems(&entry, &report, args.tolerance_ms, args.drop_clipped);
+        if problems.is_empty() {
+            clean.push(entry);
+        } else {
+            bad += 1;
+            println!("{}: {}", entry.audio_path, problems.join("; "));
+        }
+    }
+
+    println!(
+        "{} of {} files OK, {bad} with problems",
+        clean.len(),
+        clean.len() + bad
+    );
+    if let Some(path) = &args.clean {
+        write_references(path, &clean)?;
+        println!("Wrote {} entries to {}", clean.len(), path.display());
+    }
+    Ok(())
+}
+
+/// What is wrong with an entry's audio; empty if nothing is.
+fn audio_problems(
+    entry: &ReferenceEntry,
+    report: &Result<AudioReport, String>,
+    tolerance_ms: u64,
+    clipped_is_bad: bool,
+) -> Vec<String> {
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => return vec![format!("unreadable: {e}")],
+    };
+    let mut problems = Vec::new();
+    let decoded = report.decoded_duration_ms();
+    if report.decoded_frames == 0 {
+        problems.push("no audio".to_string());
+    }
+    if report.skipped_packets > 0 {
+        problems.push(format!("{} corrupt packets", report.skipped_packets));
+    }
+    if let Some(header) = report.header_duration_ms()
+        && header.abs_diff(decoded) > tolerance_ms
+    {
+        problems.push(format!("header says {header} ms, decoded {decoded} ms"));
+    }
+    if let Some(listed) = entry.duration_ms()
+        && listed.abs_diff(decoded) > tolerance_ms
+    {
+        problems.push(format!("manifest says {listed} ms, decoded {decoded} ms"));
+    }
+    if report.nan_samples > 0 {
+        problems.push(format!("{} NaN samples", report.nan_samples));
+    }
+    if clipped_is_bad && report.clipped_samples > 0 {
+        problems.push(format!("{} clipped samples", report.clipped_samples));
+    }
+    problems
+}
+
+fn stats(args: StatsArgs) -> Result<()> {
+    let entries = read_references(&args.manifest, usize::MAX)?;
+    let stats = ManifestStats::compute(&entries);
+    let totals = stats.totals;
+
+    println!("Utterances: {}", totals.utterances);
+    println!("Hours:      {:.2}", totals.hours());
+    if totals.with_duration < totals.utterances {
+        println!(
+            "Unknown duration: {}",
+            totals.utterances - totals.with_duration
+        );
+    }
+    println!("Speakers:   {}", stats.speakers);
+
+    if totals.with_duration > 0 {
+        println!("\nDuration");
+        let mut lower = 0.0;
+        for (i, &count) in stats.duration_histogram.iter().enumerate() {
+            let label = match DURATION_BUCKETS.get(i) {
+                Some(upper) => format!("{lower:>4}-{upper:<4} s"),
+                None => format!("{lower:>4}+     s"),
+            };
+            let bar = "#".repeat((count * 40).div_ceil(totals.with_duration));
+            println!("  {label} {count:>8}  {bar}");
+            lower = DURATION_BUCKETS.get(i).copied().unwrap_or(lower);
+        }
+    }
+    if let Some(d) = stats.text_chars {
+        println!("\nText length (characters)");
+        print_distribution(&d, 0);
+    }
+    if let Some(d) = stats.chars_per_sec {
+        println!("\nCharacters per second");
+        print_distribution(&d, 1);
+    }
+    if !stats.rate_outliers.is_empty() {
+        println!("\nRate outliers: {}", stats.rate_outliers.len());
+        for outlier in stats.rate_outliers.iter().take(args.outliers) {
+            println!(
+                "  line {:>6}  {:>6.1} chars/s  {}",
+                outlier.index + 1,
+                outlier.chars_per_sec,
+                outlier.audio_path
+            );
+        }
+    }
+    if !stats.splits.is_empty() {
+        println!("\nSplits");
+        for (split, totals) in &stats.splits {
+            let hours = totals.hours();
+            println!(
+                "  {split:<12} {:>8} utterances  {hours:>8.2} h",
+                totals.utterances
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_distribution(d: &Distribution, decimals: usize) {
+    println!(
+        "  min {:.p$}  median {:.p$}  p90 {:.p$}  max {:.p$}  mean {:.p$}",
+        d.min,
+        d.median,
+        d.p90,
+        d.max,
+        d.mean,
+        p = decimals
+    );
+}
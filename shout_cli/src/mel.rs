@@ -0,0 +1,136 @@
+//! `shout mel`: the log-mel spectrogram of an audio file as a `.npy` matrix,
+//! to inspect features or feed them to other tools.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+
+use shout_core::audio::decoder::{ChannelMode, DecodeOptions, decode_with};
+use shout_core::audio::mel::MelConfig;
+use shout_core::audio::normalize::Normalization;
+use shout_core::audio::trim::{TrimOptions, trim_silence};
+use shout_core::cancel::CancelToken;
+use shout_core::features::SAMPLE_RATE;
+use shout_core::npy;
+
+use crate::decode::Resampler;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MelPreset {
+    /// OpenAI Whisper's features.
+    Whisper,
+
+    /// Kaldi's `compute-fbank-feats`.
+    Kaldi,
+}
+
+impl MelPreset {
+    pub fn config(self) -> MelConfig {
+        match self {
+            MelPreset::Whisper => MelConfig::whisper(),
+            MelPreset::Kaldi => MelConfig::kaldi(),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct MelArgs {
+    /// Audio file.
+    pub audio: PathBuf,
+
+    /// `.npy` file for the `(frames, n_mels)` matrix.
+    #[arg(long, short)]
+    pub out: PathBuf,
+
+    /// Window, mel scale and log compression to start from.
+    #[arg(long, value_enum, default_value = "whisper")]
+    pub preset: MelPreset,
+
+    /// Mel bins per frame.
+    #[arg(long, default_value_t = 80)]
+    pub n_mels: usize,
+
+    /// Sample rate to compute the features at, in Hz.
+    #[arg(long, default_value_t = SAMPLE_RATE)]
+    pub sample_rate: u32,
+
+    /// How to convert the file's sample rate.
+    #[arg(long, value_enum, default_value = "fft")]
+    pub resampler: Resampler,
+
+    /// Remove the DC offset before computing features.
+    #[arg(long)]
+    pub remove_dc: bool,
+
+    /// Scale the peak to this level in dBFS (e.g. -1).
+    #[arg(long, allow_negative_numbers = true, conflicts_with_all = ["rms", "loudness"])]
+    pub peak: Option<f64>,
+
+    /// Scale the RMS level to this in dBFS (e.g. -20).
+    #[arg(long, allow_negative_numbers = true, conflicts_with = "loudness")]
+    pub rms: Option<f64>,
+
+    /// Normalize to this integrated loudness (EBU R128, e.g. -23).
+    #[arg(long, allow_negative_numbers = true)]
+    pub loudness: Option<f64>,
+
+    /// Cut leading and trailing audio quieter than this in dBFS (e.g. -40),
+    /// keeping 100 ms around the rest.
+    #[arg(long, allow_negative_numbers = true)]
+    pub trim_db: Option<f64>,
+
+    /// FFT size (default: the preset's).
+    #[arg(long)]
+    pub n_fft: Option<usize>,
+
+    /// Samples between frames (default: the preset's).
+    #[arg(long)]
+    pub hop_length: Option<usize>,
+}
+
+pub fn run(args: MelArgs) -> Result<()> {
+    let preset = args.preset.config();
+    let n_fft = args.n_fft.unwrap_or(preset.n_fft);
+    let config = MelConfig {
+        sample_rate: args.sample_rate,
+        n_mels: args.n_mels,
+        n_fft,
+        win_length: preset.win_length.min(n_fft),
+        hop_length: args.hop_length.unwrap_or(preset.hop_length),
+        ..preset
+    };
+    let extractor = config.build()?;
+
+    let normalize = args
+        .peak
+        .map(|dbfs| Normalization::Peak { dbfs })
+        .or(args.rms.map(|dbfs| Normalization::Rms { dbfs }))
+        .or(args.loudness.map(|lufs| Normalization::Loudness { lufs }));
+    let options = DecodeOptions {
+        remove_dc: args.remove_dc,
+        normalize,
+        ..args.resampler.options(args.sample_rate)
+    };
+    let cancel = CancelToken::default();
+    let mut pcm = decode_with(&args.audio, ChannelMode::DownmixMono, &options, &cancel)
+        .with_context(|| format!("Failed to decode {}", args.audio.display()))?
+        .swap_remove(0);
+    if let Some(threshold_db) = args.trim_db {
+        let options = TrimOptions {
+            threshold_db,
+            ..TrimOptions::default()
+        };
+        trim_silence(&mut pcm, args.sample_rate, &options);
+    }
+
+    let mel = extractor.compute(&pcm);
+    npy::write_matrix(&args.out, mel.n_frames, mel.n_mels, mel.data)?;
+    println!(
+        "{} frames x {} mels -> {}",
+        mel.n_frames,
+        mel.n_mels,
+        args.out.display()
+    );
+    Ok(())
+}
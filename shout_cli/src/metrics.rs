@@ -0,0 +1,272 @@
+//! Prometheus metrics of `shout serve` and `shout batch`.
+//!
+//! The server exposes them at `GET /metrics`; `shout batch --metrics-push URL`
+//! sends them to a Pushgateway once the batch is done. Stage latencies come
+//! from the `tracing` spans of shout_core, recorded by [`StageLayer`].
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use prometheus::{
+    Counter, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder, exponential_buckets,
+};
+use tracing::span;
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context as LayerContext;
+use tracing_subscriber::registry::LookupSpan;
+
+use shout_core::backend::memory::peak_process_memory;
+
+type MemoryProbe = Box<dyn Fn() -> Option<u64> + Send + Sync>;
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    jobs: IntCounterVec,
+    queue_depth: IntGauge,
+    jobs_running: IntGauge,
+    audio_seconds: Counter,
+    processing_seconds: Counter,
+    rtf: Histogram,
+    stages: HistogramVec,
+    cache_lookups: IntCounterVec,
+    device_memory_free: IntGauge,
+    peak_memory: IntGauge,
+    memory_probe: OnceLock<MemoryProbe>,
+}
+
+/// The process-wide metrics.
+pub fn get() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("metric definitions are valid"))
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new();
+        let latency = || exponential_buckets(0.005, 2.0, 14);
+        let metrics = Self {
+            http_requests: IntCounterVec::new(
+                Opts::new(
+                    "shout_http_requests_total",
+                    "HTTP requests by route and status.",
+                ),
+                &["method", "route", "status"],
+            )?,
+            http_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "shout_http_request_duration_seconds",
+                    "HTTP request latency.",
+                )
+                .buckets(latency()?),
+                &["method", "route"],
+            )?,
+            jobs: IntCounterVec::new(
+                Opts::new(
+                    "shout_jobs_total",
+                    "Finished transcription jobs (files, in batch runs) by final status.",
+                ),
+                &["status"],
+            )?,
+            queue_depth: IntGauge::new("shout_queue_depth", "Jobs waiting for a worker.")?,
+            jobs_running: IntGauge::new("shout_jobs_running", "Jobs being transcribed.")?,
+            audio_seconds: Counter::new(
+                "shout_audio_seconds_total",
+                "Duration of the audio transcribed.",
+            )?,
+            processing_seconds: Counter::new(
+                "shout_processing_seconds_total",
+                "Time spent transcribing it.",
+            )?,
+            rtf: Histogram::with_opts(
+                HistogramOpts::new(
+                    "shout_real_time_factor",
+                    "Processing time divided by audio duration, per job or batch run.",
+                )
+                .buckets(exponential_buckets(0.01, 2.0, 12)?),
+            )?,
+            stages: HistogramVec::new(
+                HistogramOpts::new(
+                    "shout_stage_duration_seconds",
+                    "Time per pipeline stage: audio decoding per file, the rest per window.",
+                )
+                .buckets(latency()?),
+                &["stage"],
+            )?,
+            cache_lookups: IntCounterVec::new(
+                Opts::new(
+                    "shout_cache_lookups_total",
+                    "Results cache lookups by outcome.",
+                ),
+                &["result"],
+            )?,
+            device_memory_free: IntGauge::new(
+                "shout_device_memory_free_bytes",
+                "Free memory on the device the model runs on.",
+            )?,
+            peak_memory: IntGauge::new(
+                "shout_process_peak_memory_bytes",
+                "Peak resident memory of the process.",
+            )?,
+            memory_probe: OnceLock::new(),
+            registry,
+        };
+
+        let r = &metrics.registry;
+        r.register(Box::new(metrics.http_requests.clone()))?;
+        r.register(Box::new(metrics.http_duration.clone()))?;
+        r.register(Box::new(metrics.jobs.clone()))?;
+        r.register(Box::new(metrics.queue_depth.clone()))?;
+        r.register(Box::new(metrics.jobs_running.clone()))?;
+        r.register(Box::new(metrics.audio_seconds.clone()))?;
+        r.register(Box::new(metrics.processing_seconds.clone()))?;
+        r.register(Box::new(metrics.rtf.clone()))?;
+        r.register(Box::new(metrics.stages.clone()))?;
+        r.register(Box::new(metrics.cache_lookups.clone()))?;
+        r.register(Box::new(metrics.device_memory_free.clone()))?;
+        r.register(Box::new(metrics.peak_memory.clone()))?;
+        Ok(metrics)
+    }
+
+    pub fn http_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn job_queued(&self) {
+        self.queue_depth.inc();
+    }
+
+    /// A queued job did not fit in the queue after all.
+    pub fn job_rejected(&self) {
+        self.queue_depth.dec();
+    }
+
+    pub fn job_started(&self) {
+        self.queue_depth.dec();
+        self.jobs_running.inc();
+    }
+
+    /// A running job went back to the queue to be tried again.
+    pub fn job_retried(&self) {
+        self.jobs_running.dec();
+        self.queue_depth.inc();
+    }
+
+    pub fn job_finished(&self, status: &str) {
+        self.jobs_running.dec();
+        self.jobs.with_label_values(&[status]).inc();
+    }
+
+    /// Count finished jobs that were never queued here, e.g. the files of a batch run.
+    pub fn jobs_finished(&self, status: &str, n: usize) {
+        self.jobs.with_label_values(&[status]).inc_by(n as u64);
+    }
+
+    /// `audio_seconds` of audio took `processing` to transcribe.
+    pub fn transcribed(&self, audio_seconds: f64, processing: Duration) {
+        self.audio_seconds.inc_by(audio_seconds);
+        self.processing_seconds.inc_by(processing.as_secs_f64());
+        if audio_seconds > 0.0 {
+            self.rtf.observe(processing.as_secs_f64() / audio_seconds);
+        }
+    }
+
+    pub fn stage(&self, stage: &str, elapsed: Duration) {
+        self.stages
+            .with_label_values(&[stage])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn cache_lookups(&self, hits: usize, misses: usize) {
+        self.cache_lookups
+            .with_label_values(&["hit"])
+            .inc_by(hits as u64);
+        self.cache_lookups
+            .with_label_values(&["miss"])
+            .inc_by(misses as u64);
+    }
+
+    /// Report `probe()` as the free device memory; only the first probe is kept.
+    pub fn set_memory_probe(&self, probe: impl Fn() -> Option<u64> + Send + Sync + 'static) {
+        let _ = self.memory_probe.set(Box::new(probe));
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn render(&self) -> Result<String> {
+        if let Some(free) = self.memory_probe.get().and_then(|probe| probe()) {
+            self.device_memory_free.set(free as i64);
+        }
+        if let Some(peak) = peak_process_memory() {
+            self.peak_memory.set(peak as i64);
+        }
+
+        let mut out = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut out)
+            .context("Failed to encode metrics")?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    /// Replace the metrics of `job` on the Pushgateway at `gateway`.
+    pub fn push(&self, gateway: &str, job: &str) -> Result<()> {
+        let url = format!("{}/metrics/job/{job}", gateway.trim_end_matches('/'));
+        ureq::put(&url)
+            .header("Content-Type", TextEncoder::new().format_type())
+            .send(self.render()?)
+            .with_context(|| format!("Failed to push metrics to {url}"))?;
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Stage timings
+// -----------------------------------------------------------------------------
+
+/// Records how long the stage spans of shout_core stay open. Needs those spans
+/// enabled, i.e. `shout_core` at debug level for this layer.
+pub struct StageLayer;
+
+/// Metric label of a shout_core span, if it is a stage.
+fn stage_label(span_name: &str) -> Option<&'static str> {
+    Some(match span_name {
+        "decode_cancellable" => "audio_decode",
+        "features" => "features",
+        "encode" => "encode",
+        "decode" => "token_decode",
+        "window" => "window",
+        _ => return None,
+    })
+}
+
+struct Opened(Instant);
+
+impl<S> Layer<S> for StageLayer
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: LayerContext<'_, S>) {
+        if stage_label(attrs.metadata().name()).is_some()
+            && let Some(span) = ctx.span(id)
+        {
+            span.extensions_mut().insert(Opened(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(stage) = stage_label(span.name()) else {
+            return;
+        };
+        if let Some(Opened(opened)) = span.extensions().get::<Opened>() {
+            get().stage(stage, opened.elapsed());
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+use clap::{Args, Subcommand};
+
+use shout_core::backend::device::DeviceSpec;
+use shout_core::model::convert::convert_checkpoint;
+use shout_core::model::quantize::{QuantType, quantize_model_dir};
+use shout_core::model::test_tiny::{TEST_TINY, write_test_tiny};
+use shout_eval::manifest::{ReferenceEntry, read_references};
+use shout_eval::metrics::{self, ErrorCounts};
+use shout_eval::normalize::whisper_basic;
+
+use crate::registry::{self, FileStatus, Layout};
+use crate::transcribe::load_transcriber;
+
+#[derive(Args)]
+pub struct ModelArgs {
+    #[command(subcommand)]
+    pub command: ModelCommand,
+}
+
+#[derive(Subcommand)]
+pub enum ModelCommand {
+    /// Quantize the linear layers of a checkpoint to int8 or int4.
+    Quantize(QuantizeArgs),
+
+    /// Convert a Hugging Face model directory or an OpenAI `.pt` checkpoint.
+    Convert(ConvertArgs),
+
+    /// Download a known model into the models directory.
+    Pull(PullArgs),
+
+    /// List known models and which of them are downloaded.
+    List(ListArgs),
+
+    /// Check downloaded files against their checksums.
+    Verify(PullArgs),
+}
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// Hugging Face model directory, or a `.pt`, `.bin` or `.safetensors` file.
+    pub input: PathBuf,
+
+    /// Output model directory.
+    #[arg(long)]
+    pub out: PathBuf,
+
+    /// tokenizer.json to copy alongside (defaults to the one in the input directory).
+    #[arg(long)]
+    pub tokenizer: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct PullArgs {
+    /// Model name (see `shout model list`).
+    pub name: String,
+
+    /// Download again even if the files are intact.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Registry JSON to use instead of the built-in one.
+    #[arg(long)]
+    pub registry: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    /// Registry JSON to use instead of the built-in one.
+    #[arg(long)]
+    pub registry: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct QuantizeArgs {
+    /// Model directory with model.safetensors.
+    #[arg(long)]
+    pub model: PathBuf,
+
+    /// Output model directory.
+    #[arg(long)]
+    pub out: PathBuf,
+
+    /// int8 or int4.
+    #[arg(long, default_value = "int8")]
+    pub bits: QuantType,
+
+    /// JSONL manifest ({"audio_path", "text"}) to measure the WER change on.
+    #[arg(long)]
+    pub dev_manifest: Option<PathBuf>,
+
+    /// Only score the first N utterances of the dev manifest.
+    #[arg(long, default_value_t = 200)]
+    pub max_utts: usize,
+}
+
+pub fn run(args: ModelArgs) -> Result<()> {
+    match args.command {
+        ModelCommand::Quantize(args) => quantize(args),
+        ModelCommand::Convert(args) => convert(&args.input, &args.out, args.tokenizer.as_deref()),
+        ModelCommand::Pull(args) => pull(args),
+        ModelCommand::List(args) => list(args),
+        ModelCommand::Verify(args) => verify(args),
+    }
+}
+
+pub fn pull(args: PullArgs) -> Result<()> {
+    if args.name == TEST_TINY {
+        let dir = registry::models_dir().join(TEST_TINY);
+        if args.force || !dir.join("model.safetensors").exists() {
+            write_test_tiny(&dir)?;
+        }
+        println!("Model: {}", dir.display());
+        return Ok(());
+    }
+    let registry = registry::load_registry(args.registry.as_deref())?;
+    let entry = registry::find(&registry, &args.name)?;
+    let dir = registry::models_dir().join(&entry.name);
+
+    let download_dir = entry.download_dir(&dir);
+    let downloaded = registry::pull(entry, &download_dir, args.force)?;
+    if entry.layout == Layout::Hf && (downloaded || !dir.join("model.safetensors").exists()) {
+        convert(&download_dir, &dir, None)?;
+    }
+    println!("Model: {}", dir.display());
+    Ok(())
+}
+
+fn convert(input: &Path, out: &Path, tokenizer: Option<&Path>) -> Result<()> {
+    let report = convert_checkpoint(input, out, tokenizer)?;
+    let c = &report.config;
+
+    println!("Wrote: {}", out.display());
+    println!("Tensors: {}", report.tensors);
+    println!(
+        "Encoder: {} layers, {} wide, {} heads; decoder: {} layers, {} wide, {} heads",
+        c.n_audio_layer,
+        c.n_audio_state,
+        c.n_audio_head,
+        c.n_text_layer,
+        c.n_text_state,
+        c.n_text_head
+    );
+    println!("Vocabulary: {}, mel bins: {}", c.n_vocab, c.n_mels);
+    if let Some(v) = c.ctc_vocab {
+        println!("CTC head: {v} outputs");
+    }
+    if !report.dropped.is_empty() {
+        println!("Unused tensors: {}", report.dropped.join(", "));
+    }
+    if report.tokenizer.is_none() {
+        tracing::warn!(
+            "No tokenizer.json found; copy one into {} (or pass --tokenizer)",
+            out.display()
+        );
+    }
+    Ok(())
+}
+
+fn list(args: ListArgs) -> Result<()> {
+    let registry = registry::load_registry(args.registry.as_deref())?;
+    let models_dir = registry::models_dir();
+    println!("Models directory: {}", models_dir.display());
+
+    for entry in &registry {
+        let dir = entry.download_dir(&models_dir.join(&entry.name));
+        let present = entry
+            .files
+            .iter()
+            .filter(|f| dir.join(&f.name).exists())
+            .count();
+        let status = match present {
+            0 => "",
+            n if n == entry.files.len() => "  [downloaded]",
+            _ => "  [partial]",
+        };
+        println!("{:<20} {}{}", entry.name, entry.description, status);
+    }
+    let generated = models_dir
+        .join(TEST_TINY)
+        .join("model.safetensors")
+        .exists();
+    let status = if generated { "  [generated]" } else { "" };
+    println!("{TEST_TINY:<20} Random weights for offline tests, generated on use{status}");
+    Ok(())
+}
+
+fn verify(args: PullArgs) -> Result<()> {
+    let registry = registry::load_registry(args.registry.as_deref())?;
+    let entry = registry::find(&registry, &args.name)?;
+    let dir = entry.download_dir(&registry::models_dir().join(&entry.name));
+
+    let mut bad = 0;
+    for (file, status) in registry::verify(entry, &dir)? {
+        let label = match status {
+            FileStatus::Ok => "ok",
+            FileStatus::Missing => "missing",
+            FileStatus::Mismatch => "CHECKSUM MISMATCH",
+            FileStatus::Unpinned => "no pinned checksum",
+        };
+        bad += usize::from(status != FileStatus::Ok);
+        println!("{file}: {label}");
+    }
+    if bad > 0 {
+        bail!(
+            "{bad} file(s) missing, corrupt or unpinned; run `shout model pull {} --force`",
+            entry.name
+        );
+    }
+    Ok(())
+}
+
+fn quantize(args: QuantizeArgs) -> Result<()> {
+    let model = registry::resolve_model(&args.model)?;
+    let (out_path, report) = quantize_model_dir(&model, &args.out, args.bits)?;
+
+    println!("Wrote: {}", out_path.display());
+    println!("Quantized tensors: {}", report.quantized_tensors);
+    println!("Kept in f32: {}", report.kept_tensors);
+    println!(
+        "Size: {:.1} MB -> {:.1} MB",
+        report.bytes_before as f64 / 1e6,
+        report.bytes_after as f64 / 1e6
+    );
+
+    if let Some(manifest) = &args.dev_manifest {
+        let entries = read_references(manifest, args.max_utts)?;
+        let before = dev_wer(&model, &entries)?;
+        let after = dev_wer(&args.out, &entries)?;
+        println!(
+            "Dev WER ({} utts): {:.2}% -> {:.2}% (delta {:+.2})",
+            entries.len(),
+            before * 100.0,
+            after * 100.0,
+            (after - before) * 100.0
+        );
+    }
+
+    Ok(())
+}
+
+/// Corpus-level WER of the model in `model_dir` over `entries`.
+fn dev_wer(model_dir: &Path, entries: &[ReferenceEntry]) -> Result<f64> {
+    let mut transcriber = load_transcriber(model_dir, DeviceSpec::Auto, 1)?;
+    let mut counts = ErrorCounts::default();
+
+    for entry in entries {
+        let audio = shout_config::get().audio_path(&entry.audio_path);
+        let hyp = transcriber.transcribe_file(audio)?.text();
+        counts += metrics::wer(&whisper_basic(&entry.text), &whisper_basic(&hyp));
+    }
+
+    Ok(counts.rate())
+}
@@ -0,0 +1,217 @@
+//! `shout quickstart`: the whole path from a corpus to an evaluated model in
+//! one command, at a size that finishes within the hour.
+//!
+//! Every intermediate file goes to the work directory, so each step can be
+//! inspected and re-run by hand with the command it stands for:
+//!
+//! 1. download: pull the base model; the corpus itself has to be downloaded
+//!    by hand, since Common Voice asks for its terms to be accepted
+//! 2. import: corpus TSV to a JSONL manifest (`shout data convert`)
+//! 3. normalize: tidy the transcripts and keep the first `--hours` of audio
+//! 4. split: train/dev/test by speaker, so no voice is in two splits
+//! 5. tokenizer: the base model's, which covers German already
+//! 6. features: the base model's front end, computed on the fly during
+//!    training
+//! 7. fine-tune: `shout train` on the train split
+//! 8. eval: `shout eval` on the test split
+//!
+//! Until `shout_train` can fine-tune, the run stops at step 7 with the
+//! manifests prepared; it never evaluates the base model in place of the
+//! fine-tuned one.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use clap::{Args, Parser, ValueEnum};
+
+use shout_core::backend::device::DeviceSpec;
+use shout_core::config::model::ModelConfig;
+use shout_core::features::front_end;
+use shout_eval::manifest::{ReferenceEntry, read_references, write_references};
+use shout_eval::manifest_split::{SplitSize, split};
+use shout_tools::append::WriteMode;
+use shout_tools::tsv_to_jsonl;
+
+use crate::eval::EvalArgs;
+use crate::model::PullArgs;
+
+const STEPS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Corpus {
+    /// Common Voice Spontaneous Speech, German.
+    #[value(name = "common-voice-de")]
+    CommonVoiceDe,
+}
+
+#[derive(Args)]
+pub struct QuickstartArgs {
+    #[arg(long, value_enum, default_value = "common-voice-de")]
+    pub corpus: Corpus,
+
+    /// Hours of audio to use, over all splits.
+    #[arg(long, default_value_t = 10.0)]
+    pub hours: f64,
+
+    /// Extracted corpus (default: under `paths.data_root`).
+    #[arg(long)]
+    pub corpus_dir: Option<PathBuf>,
+
+    /// Registry model to start from.
+    #[arg(long, default_value = "whisper-tiny")]
+    pub base_model: String,
+
+    /// Directory for manifests, the model and the report.
+    #[arg(long, default_value = "quickstart")]
+    pub work_dir: PathBuf,
+
+    /// auto, cpu, cuda[:N] or metal[:N].
+    #[arg(long, default_value = shout_config::get().device.as_str())]
+    pub device: DeviceSpec,
+}
+
+/// Lets the eval step reuse `shout eval`'s flags and their defaults.
+#[derive(Parser)]
+struct EvalCommand {
+    #[command(flatten)]
+    args: EvalArgs,
+}
+
+pub fn run(args: QuickstartArgs) -> Result<()> {
+    let work = &args.work_dir;
+    fs::create_dir_all(work)?;
+
+    step(1, "download");
+    let corpus_dir = args
+        .corpus_dir
+        .clone()
+        .unwrap_or_else(|| match args.corpus {
+            Corpus::CommonVoiceDe => shout_config::get()
+                .data_root()
+                .join(tsv_to_jsonl::CORPUS_DIR),
+        });
+    if !corpus_dir.is_dir() {
+        bail!(
+            "Corpus not found in {}: download it from https://commonvoice.mozilla.org/datasets \
+             (Common Voice asks you to accept its terms first), extract it there or pass \
+             --corpus-dir",
+            corpus_dir.display()
+        );
+    }
+    crate::model::pull(PullArgs {
+        name: args.base_model.clone(),
+        force: false,
+        registry: None,
+    })
+    .with_context(|| format!("Failed to download the base model {}", args.base_model))?;
+    let base_model = crate::registry::models_dir().join(&args.base_model);
+
+    step(2, "import");
+    let imported = work.join("all.jsonl");
+    tsv_to_jsonl::convert_corpus(&corpus_dir, &imported, WriteMode::Overwrite)?;
+
+    step(3, "normalize");
+    let entries = normalize(read_references(&imported, usize::MAX)?, args.hours);
+    let hours = total_ms(&entries) as f64 / 3_600_000.0;
+    println!("{} utterances, {hours:.1} h", entries.len());
+    if entries.is_empty() {
+        bail!("No usable utterances in {}", imported.display());
+    }
+
+    step(4, "split");
+    // About 90/5/5%, with each speaker's utterances in one split.
+    let size = SplitSize::Ratio([0.9, 0.05, 0.05]);
+    let [train, dev, test] = split(entries, size, Some("speaker"), 0)?;
+    for (name, split) in [("train", &train), ("dev", &dev), ("test", &test)] {
+        let path = work.join(format!("{name}.jsonl"));
+        write_references(&path, split)?;
+        println!("{name}: {} utterances -> {}", split.len(), path.display());
+    }
+    if test.is_empty() {
+        bail!("The test split is empty; use more --hours");
+    }
+
+    step(5, "tokenizer");
+    let tokenizer = base_model.join("tokenizer.json");
+    if !tokenizer.is_file() {
+        bail!(
+            "{} has no tokenizer: {} is missing",
+            args.base_model,
+            tokenizer.display()
+        );
+    }
+    println!("Tokenizer: {}", tokenizer.display());
+
+    step(6, "features");
+    let config = ModelConfig::from_file(base_model.join("config.json"))?;
+    let features = front_end(&config)?;
+    println!("{features:?}, {} values per frame", features.feature_dim());
+
+    step(7, "fine-tune");
+    shout_train::train().with_context(|| {
+        format!(
+            "Fine-tuning failed; the manifests are ready in {}",
+            work.display()
+        )
+    })?;
+    let model = work.join("model");
+
+    step(8, "eval");
+    let results = work.join("eval_results.jsonl");
+    let test = work.join("test.jsonl");
+    let device = args.device.to_string();
+    let eval = EvalCommand::try_parse_from([
+        OsStr::new("eval"),
+        OsStr::new("--model"),
+        model.as_os_str(),
+        OsStr::new("--manifest"),
+        test.as_os_str(),
+        OsStr::new("--out"),
+        results.as_os_str(),
+        OsStr::new("--device"),
+        OsStr::new(&device),
+        OsStr::new("--language"),
+        OsStr::new("de"),
+        OsStr::new("--report"),
+    ])?;
+    crate::eval::run(eval.args)?;
+
+    println!();
+    println!("Model: {}", model.display());
+    println!("Per-utterance results: {}", results.display());
+    Ok(())
+}
+
+fn step(n: usize, name: &str) {
+    println!();
+    println!("[{n}/{STEPS}] {name}");
+}
+
+fn total_ms(entries: &[ReferenceEntry]) -> u64 {
+    entries.iter().filter_map(ReferenceEntry::duration_ms).sum()
+}
+
+/// Entries with whitespace collapsed, without empty transcripts or unknown
+/// durations, up to `hours` of audio in manifest order.
+fn normalize(entries: Vec<ReferenceEntry>, hours: f64) -> Vec<ReferenceEntry> {
+    let budget = (hours * 3_600_000.0) as u64;
+    let mut used = 0;
+    let mut out = Vec::new();
+    for mut entry in entries {
+        entry.text = entry.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let Some(ms) = entry.duration_ms() else {
+            continue;
+        };
+        if entry.text.is_empty() {
+            continue;
+        }
+        if used + ms > budget {
+            break;
+        }
+        used += ms;
+        out.push(entry);
+    }
+    out
+}
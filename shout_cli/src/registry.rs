@@ -0,0 +1,196 @@
+//! Known pretrained models and the local models directory they are pulled into.
+//!
+//! Every model lives in `<models dir>/<name>/`. Hugging Face checkpoints are
+//! downloaded into an `hf/` subdirectory and converted into the model directory.
+//!
+//! `test-tiny` is not downloaded but generated on first use (see
+//! `shout_core::model::test_tiny`): random weights for trying things out
+//! offline.
+//!
+//! Every file of a registry entry must pin its SHA-256: an entry with a file
+//! that does not is never downloaded, and a download that does not match its
+//! checksum is discarded.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use shout_core::cache::hash_file;
+use shout_core::model::test_tiny::{TEST_TINY, write_test_tiny};
+
+const BUILTIN: &str = include_str!("../registry.json");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// shout's own config and weight names; loadable as is.
+    #[default]
+    Shout,
+
+    /// A Hugging Face Transformers checkpoint.
+    Hf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryFile {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryEntry {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub layout: Layout,
+    pub files: Vec<RegistryFile>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Ok,
+    Missing,
+    Mismatch,
+
+    /// The registry pins no checksum to check it against.
+    Unpinned,
+}
+
+/// The built-in registry, or the JSON manifest at `path`.
+pub fn load_registry(path: Option<&Path>) -> Result<Vec<RegistryEntry>> {
+    match path {
+        Some(path) => {
+            let raw = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read registry: {}", path.display()))?;
+            serde_json::from_str(&raw)
+                .with_context(|| format!("Invalid registry: {}", path.display()))
+        }
+        None => Ok(serde_json::from_str(BUILTIN).expect("built-in registry is valid JSON")),
+    }
+}
+
+pub fn find<'a>(registry: &'a [RegistryEntry], name: &str) -> Result<&'a RegistryEntry> {
+    match registry.iter().find(|e| e.name == name) {
+        Some(entry) => Ok(entry),
+        None => bail!("Unknown model '{name}' (see `shout model list`)"),
+    }
+}
+
+impl RegistryEntry {
+    /// Where the files of this entry are downloaded for model directory `dir`.
+    pub fn download_dir(&self, dir: &Path) -> PathBuf {
+        match self.layout {
+            Layout::Shout => dir.to_path_buf(),
+            Layout::Hf => dir.join("hf"),
+        }
+    }
+}
+
+/// `paths.models_dir` of the config (`$SHOUT_MODELS_DIR`), else the user cache
+/// directory (`~/.cache/shout/models`).
+pub fn models_dir() -> PathBuf {
+    shout_config::get().models_dir()
+}
+
+/// `--model` accepts a directory, the name of a pulled model or `test-tiny`.
+pub fn resolve_model(model: &Path) -> Result<PathBuf> {
+    if model.exists() || model.components().count() != 1 {
+        return Ok(model.to_path_buf());
+    }
+
+    let pulled = models_dir().join(model);
+    if model == Path::new(TEST_TINY) && !pulled.join("model.safetensors").exists() {
+        tracing::info!("Generating the {TEST_TINY} model in {}", pulled.display());
+        write_test_tiny(&pulled)?;
+    }
+    if pulled.is_dir() {
+        return Ok(pulled);
+    }
+    let name = model.to_string_lossy();
+    if find(&load_registry(None)?, &name).is_ok() {
+        bail!("Model '{name}' is not downloaded yet; run `shout model pull {name}`");
+    }
+    Ok(model.to_path_buf())
+}
+
+/// Check every file of `entry` in `dir` against its pinned checksum.
+pub fn verify(entry: &RegistryEntry, dir: &Path) -> Result<Vec<(String, FileStatus)>> {
+    let mut out = Vec::with_capacity(entry.files.len());
+    for file in &entry.files {
+        let path = dir.join(&file.name);
+        let status = match &file.sha256 {
+            _ if !path.exists() => FileStatus::Missing,
+            None => FileStatus::Unpinned,
+            Some(expected) if !hash_file(&path)?.eq_ignore_ascii_case(expected) => {
+                FileStatus::Mismatch
+            }
+            Some(_) => FileStatus::Ok,
+        };
+        out.push((file.name.clone(), status));
+    }
+    Ok(out)
+}
+
+/// Download the files of `entry` into `dir`, skipping those already present
+/// and intact unless `force` is set. Returns whether anything was downloaded.
+///
+/// Fails before downloading anything if a file of `entry` pins no checksum.
+pub fn pull(entry: &RegistryEntry, dir: &Path, force: bool) -> Result<bool> {
+    let pins = entry
+        .files
+        .iter()
+        .map(|file| match &file.sha256 {
+            Some(sha256) => Ok(sha256),
+            None => bail!(
+                "{}: the registry pins no sha256 for {}; refusing to download it unverified",
+                entry.name,
+                file.name
+            ),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let statuses = verify(entry, dir)?;
+    let mut downloaded = false;
+
+    for ((file, expected), (_, status)) in entry.files.iter().zip(pins).zip(statuses) {
+        if status == FileStatus::Ok && !force {
+            println!("{}: up to date", file.name);
+            continue;
+        }
+
+        let path = dir.join(&file.name);
+        let partial = path.with_extension("part");
+        println!("{}: downloading {}", file.name, file.url);
+        download(&file.url, &partial)?;
+
+        let actual = hash_file(&partial)?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            let _ = fs::remove_file(&partial);
+            bail!(
+                "{}: checksum mismatch (expected {expected}, got {actual})",
+                file.name
+            );
+        }
+        fs::rename(&partial, &path)
+            .with_context(|| format!("Failed to move download to {}", path.display()))?;
+        downloaded = true;
+    }
+    Ok(downloaded)
+}
+
+fn download(url: &str, path: &Path) -> Result<()> {
+    let response = ureq::get(url)
+        .call()
+        .with_context(|| format!("Failed to fetch {url}"))?;
+    let mut file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    io::copy(&mut response.into_body().into_reader(), &mut file)
+        .with_context(|| format!("Failed to download {url}"))?;
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Args;
+
+use shout_eval::metrics::ErrorCounts;
+use shout_eval::nist::{read_ctm, read_stm, score_ctm, write_sclite_reports};
+use shout_eval::normalize::Normalizer;
+
+#[derive(Args)]
+pub struct ScoreArgs {
+    /// Reference segments (NIST STM).
+    #[arg(long)]
+    pub stm: PathBuf,
+
+    /// Hypothesis words (NIST CTM), e.g. from `shout eval --ctm`.
+    #[arg(long)]
+    pub ctm: PathBuf,
+
+    /// Text normalization of both sides: whisper, whisper-en or none.
+    #[arg(long, default_value = "whisper")]
+    pub normalizer: Normalizer,
+
+    /// Write sclite-style `.sys` and `.pra` reports into this directory.
+    #[arg(long)]
+    pub sclite_dir: Option<PathBuf>,
+}
+
+pub fn run(args: ScoreArgs) -> Result<()> {
+    let stm = read_stm(&args.stm)?;
+    let ctm = read_ctm(&args.ctm)?;
+    let scored = score_ctm(&stm, &ctm, args.normalizer);
+
+    let total = scored.iter().fold(ErrorCounts::default(), |acc, utt| {
+        acc + utt.alignment.counts
+    });
+    println!("Segments: {}", scored.len());
+    println!(
+        "WER: {:.2}% (S {} D {} I {} / {} words)",
+        total.rate() * 100.0,
+        total.substitutions,
+        total.deletions,
+        total.insertions,
+        total.reference_len()
+    );
+
+    if let Some(dir) = &args.sclite_dir {
+        let name = args
+            .ctm
+            .file_stem()
+            .map_or("score".into(), |s| s.to_string_lossy());
+        write_sclite_reports(dir, &name, &scored)?;
+        println!("sclite reports: {}", dir.display());
+    }
+    Ok(())
+}
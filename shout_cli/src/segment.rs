@@ -0,0 +1,134 @@
+//! `shout segment`: long recordings (broadcasts, podcasts) cut at pauses into
+//! chunks short enough for the model, with a manifest of the chunks to
+//! transcribe or label.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use serde_json::json;
+
+use shout_core::audio::decoder::decode_to_f32_mono_16k;
+use shout_core::audio::vad::{EnergyVad, SpeechDetector, VadOptions, chunk_speech, detect_speech};
+use shout_core::features::SAMPLE_RATE;
+
+use crate::decode::write_wav;
+
+#[derive(Args)]
+pub struct SegmentArgs {
+    /// Audio files to segment.
+    #[arg(required = true)]
+    pub audio: Vec<PathBuf>,
+
+    /// JSONL manifest to write, one line per chunk with an empty transcript.
+    #[arg(long, short)]
+    pub out: PathBuf,
+
+    /// Write each chunk as a 16 kHz WAV file into this directory. Without it
+    /// the manifest points into the original files with `start_ms` and
+    /// `end_ms`.
+    #[arg(long)]
+    pub audio_dir: Option<PathBuf>,
+
+    /// Longest chunk in seconds.
+    #[arg(long, default_value_t = 30.0)]
+    pub max_secs: f64,
+
+    /// Pauses shorter than this, in milliseconds, never separate speech.
+    #[arg(long, default_value_t = 300)]
+    pub min_silence_ms: u32,
+
+    /// Level in dBFS from which the energy detector hears speech.
+    #[arg(long, default_value_t = -40.0, allow_negative_numbers = true)]
+    pub threshold_db: f32,
+
+    /// Find speech with this Silero VAD model (`silero_vad.onnx`) instead of
+    /// by energy.
+    #[cfg(feature = "vad-silero")]
+    #[arg(long)]
+    pub silero_model: Option<PathBuf>,
+}
+
+pub fn run(args: SegmentArgs) -> Result<()> {
+    let config = shout_config::get();
+    let mut detector = detector(&args)?;
+    let options = VadOptions {
+        min_silence_ms: args.min_silence_ms,
+        ..VadOptions::default()
+    };
+    let max_ms = (args.max_secs * 1000.0) as u64;
+    if let Some(dir) = &args.audio_dir {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+
+    let file = File::create(&args.out)
+        .with_context(|| format!("Failed to create {}", args.out.display()))?;
+    let mut out = BufWriter::new(file);
+    let mut total = 0usize;
+    let mut speech_ms = 0u64;
+    for path in &args.audio {
+        let pcm = decode_to_f32_mono_16k(path)
+            .with_context(|| format!("Failed to decode {}", path.display()))?;
+        let speech = detect_speech(detector.as_mut(), &pcm, &options)?;
+        let chunks = chunk_speech(&speech, max_ms);
+        let source = config.manifest_path(path)?;
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let duration_ms = chunk.end_ms - chunk.start_ms;
+            let line = match &args.audio_dir {
+                Some(dir) => {
+                    let chunk_path = dir.join(format!("{stem}_{i:05}.wav"));
+                    write_wav(&chunk_path, SAMPLE_RATE, &pcm[chunk.samples(SAMPLE_RATE)])?;
+                    json!({
+                        "audio_path": config.manifest_path(&chunk_path)?,
+                        "text": "",
+                        "duration_ms": duration_ms,
+                        "source": source,
+                        "source_start_ms": chunk.start_ms,
+                        "source_end_ms": chunk.end_ms,
+                    })
+                }
+                None => json!({
+                    "audio_path": source,
+                    "text": "",
+                    "duration_ms": duration_ms,
+                    "start_ms": chunk.start_ms,
+                    "end_ms": chunk.end_ms,
+                }),
+            };
+            serde_json::to_writer(&mut out, &line)?;
+            out.write_all(b"\n")?;
+            speech_ms += duration_ms;
+        }
+        println!(
+            "{}: {} chunks, {:.1} of {:.1} min",
+            path.display(),
+            chunks.len(),
+            chunks.iter().map(|c| c.end_ms - c.start_ms).sum::<u64>() as f64 / 60_000.0,
+            pcm.len() as f64 / SAMPLE_RATE as f64 / 60.0
+        );
+        total += chunks.len();
+    }
+    out.flush()?;
+
+    println!(
+        "Wrote {total} chunks ({:.2} h) to {}",
+        speech_ms as f64 / 3_600_000.0,
+        args.out.display()
+    );
+    Ok(())
+}
+
+fn detector(args: &SegmentArgs) -> Result<Box<dyn SpeechDetector>> {
+    #[cfg(feature = "vad-silero")]
+    if let Some(model) = &args.silero_model {
+        return Ok(Box::new(shout_core::audio::vad::SileroVad::load(model)?));
+    }
+    Ok(Box::new(EnergyVad {
+        threshold_db: args.threshold_db,
+        ..EnergyVad::default()
+    }))
+}
@@ -0,0 +1,203 @@
+//! gRPC front end (`shout.v1.Transcription`), sharing the job queue and the
+//! streaming sessions with the HTTP API.
+
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+use shout_core::audio::resample::StreamResampler;
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::decoding::prompt::Task;
+use shout_core::pipeline::streaming::{StreamUpdate, StreamingOptions};
+use shout_core::transcript;
+
+use super::jobs::{AudioSource, Failure, JobQueue, JobRequest, JobStatus, SubmitError};
+use super::stream::{FrameDecoder, StreamPool};
+
+pub mod pb {
+    tonic::include_proto!("shout.v1");
+}
+
+use pb::audio_chunk::Payload;
+use pb::transcription_server::{Transcription, TranscriptionServer};
+
+pub struct TranscriptionService {
+    jobs: Arc<JobQueue>,
+    streams: Arc<StreamPool>,
+}
+
+pub fn service(
+    jobs: Arc<JobQueue>,
+    streams: Arc<StreamPool>,
+    max_message_bytes: usize,
+) -> TranscriptionServer<TranscriptionService> {
+    TranscriptionServer::new(TranscriptionService { jobs, streams })
+        .max_decoding_message_size(max_message_bytes)
+}
+
+fn segment(seg: &transcript::Segment) -> pb::Segment {
+    pb::Segment {
+        start_ms: seg.start_ms,
+        end_ms: seg.end_ms,
+        text: seg.text.trim().to_string(),
+        words: seg
+            .words
+            .iter()
+            .map(|w| pb::Word {
+                text: w.text.clone(),
+                start_ms: w.start_ms,
+                end_ms: w.end_ms,
+                confidence: w.confidence,
+            })
+            .collect(),
+        confidence: seg.confidence(),
+    }
+}
+
+fn stream_response(update: &StreamUpdate, done: bool) -> pb::StreamResponse {
+    pb::StreamResponse {
+        finals: update.finals.iter().map(segment).collect(),
+        partial: update.partial.iter().map(segment).collect(),
+        done,
+    }
+}
+
+fn language(code: &str) -> Result<LanguageSelection, Status> {
+    if code.is_empty() {
+        return Ok(LanguageSelection::Auto);
+    }
+    code.parse()
+        .map_err(|e| Status::invalid_argument(format!("{e}")))
+}
+
+#[tonic::async_trait]
+impl Transcription for TranscriptionService {
+    async fn transcribe(
+        &self,
+        request: Request<pb::TranscribeRequest>,
+    ) -> Result<Response<pb::TranscribeResponse>, Status> {
+        let req = request.into_inner();
+        let task = if req.task.is_empty() {
+            Task::Transcribe
+        } else {
+            req.task
+                .parse()
+                .map_err(|e| Status::invalid_argument(format!("{e}")))?
+        };
+
+        let (notify, done) = oneshot::channel();
+        let id = self
+            .jobs
+            .submit(JobRequest {
+                source: AudioSource::Upload {
+                    bytes: req.audio,
+                    extension: (!req.file_extension.is_empty()).then_some(req.file_extension),
+                },
+                language: language(&req.language)?,
+                task,
+                notify: Some(notify),
+            })
+            .map_err(|e| match e {
+                SubmitError::QueueFull => Status::resource_exhausted("job queue is full"),
+                SubmitError::Storage(e) => Status::internal(e),
+            })?;
+
+        let _ = done.await;
+        let view = self
+            .jobs
+            .view(&id)
+            .ok_or_else(|| Status::internal("job disappeared"))?;
+        let error = view.error.unwrap_or_default();
+        match view.status {
+            JobStatus::Failed => {
+                return Err(match view.failure {
+                    Some(Failure::Input) => Status::invalid_argument(error),
+                    Some(Failure::Unavailable) => Status::unavailable(error),
+                    Some(Failure::Internal) | None => Status::internal(error),
+                });
+            }
+            JobStatus::Cancelled => return Err(Status::cancelled(error)),
+            JobStatus::TimedOut => return Err(Status::deadline_exceeded(error)),
+            _ => {}
+        }
+        let transcript = self
+            .jobs
+            .transcript(&id)
+            .ok_or_else(|| Status::internal("job has no result"))?;
+
+        Ok(Response::new(pb::TranscribeResponse {
+            text: transcript.text(),
+            language: transcript.language.clone().unwrap_or_default(),
+            segments: transcript.segments.iter().map(segment).collect(),
+        }))
+    }
+
+    type StreamTranscribeStream = ReceiverStream<Result<pb::StreamResponse, Status>>;
+
+    async fn stream_transcribe(
+        &self,
+        request: Request<Streaming<pb::AudioChunk>>,
+    ) -> Result<Response<Self::StreamTranscribeStream>, Status> {
+        let mut inbound = request.into_inner();
+
+        let config = match inbound.message().await?.and_then(|c| c.payload) {
+            Some(Payload::Config(config)) => config,
+            _ => {
+                return Err(Status::invalid_argument(
+                    "first message must be a StreamConfig",
+                ));
+            }
+        };
+        let encoding = if config.encoding.is_empty() {
+            "pcm_s16le"
+        } else {
+            config.encoding.as_str()
+        };
+        let sample_rate = match config.sample_rate {
+            0 if encoding == "opus" => 48_000,
+            0 => 16_000,
+            sr => sr,
+        };
+        let mut decoder = FrameDecoder::new(encoding, sample_rate)
+            .map_err(|e| Status::invalid_argument(format!("{e:#}")))?;
+        let mut resampler = StreamResampler::new(sample_rate, 16_000)
+            .map_err(|e| Status::invalid_argument(format!("{e:#}")))?;
+        let language = language(&config.language)?;
+
+        let session = self
+            .streams
+            .checkout(language, StreamingOptions::default())
+            .ok_or_else(|| Status::resource_exhausted("no free streaming session"))?;
+
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let result: anyhow::Result<()> = async {
+                while let Some(chunk) = inbound.message().await? {
+                    let Some(Payload::Audio(data)) = chunk.payload else {
+                        continue;
+                    };
+                    let pcm = resampler.push(&decoder.decode(&data)?)?;
+                    if let Some(update) = session.push(pcm).await?
+                        && tx.send(Ok(stream_response(&update, false))).await.is_err()
+                    {
+                        // The client went away.
+                        return Ok(());
+                    }
+                }
+
+                let update = session.finish(resampler.finish()?).await?;
+                let _ = tx.send(Ok(stream_response(&update, true))).await;
+                Ok(())
+            }
+            .await;
+
+            if let Err(e) = result {
+                let _ = tx.send(Err(Status::internal(format!("{e:#}")))).await;
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
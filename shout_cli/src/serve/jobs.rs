@@ -0,0 +1,613 @@
+//! Job bookkeeping and the worker pool behind the HTTP API.
+//!
+//! With a state directory, jobs are persisted (see [`super::store`]): queued
+//! and interrupted jobs run again after a restart, and results stay available
+//! until they expire. Jobs failing for reasons that may pass (a download, the
+//! device) are retried a few times before they count as failed.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use shout_core::ShoutError;
+use shout_core::backend::device::DeviceSpec;
+use shout_core::backend::memory::available_memory;
+use shout_core::cache::{ResultCache, hash_bytes, model_fingerprint};
+use shout_core::cancel::{CancelToken, Interruption, Timeouts};
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::decoding::prompt::Task;
+use shout_core::model::shout::ShoutModel;
+use shout_core::pipeline::transcribe::Transcriber;
+use shout_core::transcript::Transcript;
+
+use super::store::{JobRecord, JobStore};
+use crate::batch::cache_context;
+use crate::metrics;
+use crate::transcribe::load_transcriber;
+
+/// Finished jobs kept for result retrieval; older ones are forgotten.
+const MAX_FINISHED_JOBS: usize = 1000;
+
+/// Where a job's audio comes from.
+pub enum AudioSource {
+    /// Uploaded bytes plus the original file extension (a hint for the decoder).
+    Upload {
+        bytes: Vec<u8>,
+        extension: Option<String>,
+    },
+    Url(String),
+}
+
+pub struct JobRequest {
+    pub source: AudioSource,
+    pub language: LanguageSelection,
+    pub task: Task,
+
+    /// Signalled when the job is done or failed.
+    pub notify: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+
+    /// Stopped on request; the result holds what was transcribed until then.
+    Cancelled,
+
+    /// Stopped at a time limit; the result holds what was transcribed until then.
+    TimedOut,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::Running)
+    }
+
+    /// The status as serialized, e.g. `timed_out`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::TimedOut => "timed_out",
+        }
+    }
+}
+
+/// Why a job failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Failure {
+    /// The audio or an option is unusable; submitting it again fails again.
+    Input,
+
+    /// Fetching the audio failed.
+    Unavailable,
+
+    /// The model, the device or the server's storage failed.
+    Internal,
+}
+
+impl Failure {
+    fn of(error: &anyhow::Error) -> Self {
+        match error.downcast_ref::<ShoutError>() {
+            Some(
+                ShoutError::UnsupportedFormat(_)
+                | ShoutError::Decode(_)
+                | ShoutError::Resample(_)
+                | ShoutError::InvalidArgument(_),
+            ) => Failure::Input,
+            Some(_) => Failure::Internal,
+            // Downloads and temporary files.
+            None => Failure::Unavailable,
+        }
+    }
+}
+
+/// What the API reports about a job.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobView {
+    pub id: String,
+    pub status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<Failure>,
+
+    /// Times a worker has started the job; above 1 after retries.
+    pub attempts: u32,
+}
+
+struct Job {
+    record: JobRecord,
+    cancel: CancelToken,
+}
+
+/// Bounds on the work one job may cause, and what happens after it.
+#[derive(Debug, Clone, Default)]
+pub struct JobLimits {
+    /// Time from a worker picking the job up until it gives up.
+    pub job_timeout: Option<Duration>,
+
+    /// Limits for decoding the audio and for each window.
+    pub stages: Timeouts,
+
+    /// Largest audio file fetched from a URL.
+    pub max_download_bytes: u64,
+
+    /// Further attempts for a job that failed for a reason that may pass.
+    pub max_retries: u32,
+
+    /// Wait before the first retry; doubled for every further one.
+    pub retry_delay: Duration,
+
+    /// How long finished jobs and their results are kept; forever if `None`.
+    pub result_ttl: Option<Duration>,
+}
+
+pub enum SubmitError {
+    QueueFull,
+
+    /// The job could not be persisted.
+    Storage(String),
+}
+
+/// Shared job table plus the sending side of the bounded work queue.
+pub struct JobQueue {
+    jobs: Mutex<HashMap<String, Job>>,
+    finished: Mutex<VecDeque<String>>,
+    tx: SyncSender<(String, JobRequest)>,
+
+    /// Results cache plus the fingerprint of the served model.
+    cache: Option<(ResultCache, String)>,
+    store: Option<JobStore>,
+    limits: JobLimits,
+}
+
+impl JobQueue {
+    /// Load `concurrency` model instances and start one worker thread per
+    /// instance. Jobs in `store` that had not finished are queued again.
+    pub fn start(
+        model_dir: &Path,
+        device: DeviceSpec,
+        concurrency: usize,
+        queue_depth: usize,
+        cache: Option<ResultCache>,
+        store: Option<JobStore>,
+        limits: JobLimits,
+    ) -> Result<Arc<Self>> {
+        let cache = match cache {
+            Some(cache) => Some((cache, model_fingerprint(model_dir)?)),
+            None => None,
+        };
+        let Recovered {
+            jobs,
+            finished,
+            pending,
+        } = match &store {
+            Some(store) => recover(store, limits.result_ttl)?,
+            None => Recovered::default(),
+        };
+        // Room for the recovered jobs on top of the usual depth.
+        let (tx, rx) = sync_channel(queue_depth + pending.len());
+        for job in pending {
+            metrics::get().job_queued();
+            let _ = tx.try_send(job);
+        }
+        let rx = Arc::new(Mutex::new(rx));
+        let queue = Arc::new(Self {
+            jobs: Mutex::new(jobs),
+            finished: Mutex::new(finished),
+            tx,
+            cache,
+            store,
+            limits,
+        });
+
+        for i in 0..concurrency {
+            let mut transcriber = load_transcriber(model_dir, device, 1)?;
+            transcriber.timeouts = queue.limits.stages;
+            let model_device = transcriber.model().device().clone();
+            metrics::get().set_memory_probe(move || available_memory(&model_device));
+            let queue = Arc::clone(&queue);
+            let rx = Arc::clone(&rx);
+            thread::Builder::new()
+                .name(format!("shout-worker-{i}"))
+                .spawn(move || queue.work(transcriber, rx))
+                .context("Failed to spawn worker")?;
+        }
+        Ok(queue)
+    }
+
+    /// Enqueue a job and return its id, or fail right away if the queue is full.
+    pub fn submit(&self, request: JobRequest) -> Result<String, SubmitError> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let record = JobRecord::new(&id, &request);
+        if let Some(store) = &self.store {
+            let saved = match &request.source {
+                AudioSource::Upload { bytes, .. } => store.save_audio(&id, bytes),
+                AudioSource::Url(_) => Ok(()),
+            };
+            if let Err(e) = saved.and_then(|()| store.save(&record)) {
+                store.remove(&id);
+                return Err(SubmitError::Storage(format!("{e:#}")));
+            }
+        }
+        self.jobs.lock().unwrap().insert(
+            id.clone(),
+            Job {
+                record,
+                cancel: CancelToken::new(),
+            },
+        );
+
+        // Counted before sending, so that a worker never takes the gauge below zero.
+        metrics::get().job_queued();
+        match self.tx.try_send((id.clone(), request)) {
+            Ok(()) => Ok(id),
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+                self.jobs.lock().unwrap().remove(&id);
+                if let Some(store) = &self.store {
+                    store.remove(&id);
+                }
+                metrics::get().job_rejected();
+                Err(SubmitError::QueueFull)
+            }
+        }
+    }
+
+    pub fn view(&self, id: &str) -> Option<JobView> {
+        self.expire();
+        self.jobs.lock().unwrap().get(id).map(|job| JobView {
+            id: id.to_string(),
+            status: job.record.status,
+            error: job.record.error.clone(),
+            failure: job.record.failure,
+            attempts: job.record.attempts,
+        })
+    }
+
+    /// The transcript of a finished job; partial for cancelled and timed-out jobs.
+    pub fn transcript(&self, id: &str) -> Option<Transcript> {
+        self.jobs.lock().unwrap().get(id)?.record.transcript.clone()
+    }
+
+    /// Ask a queued or running job to stop. Running jobs stop at the next
+    /// check, within one decoding step. `None` if there is no such job.
+    pub fn cancel(&self, id: &str) -> Option<JobView> {
+        let view = self.view(id)?;
+        if !view.status.is_finished() {
+            self.jobs.lock().unwrap().get(id)?.cancel.cancel();
+        }
+        Some(view)
+    }
+
+    /// Apply `change` to the job's record and persist it.
+    fn update(&self, id: &str, change: impl FnOnce(&mut JobRecord)) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(job) = jobs.get_mut(id) else {
+            return;
+        };
+        change(&mut job.record);
+        if let Some(store) = &self.store
+            && let Err(e) = store.save(&job.record)
+        {
+            tracing::warn!("Failed to persist job {id}: {e:#}");
+        }
+    }
+
+    /// The job's token, with the job deadline starting now.
+    fn start_job(&self, id: &str) -> CancelToken {
+        metrics::get().job_started();
+        self.update(id, |record| {
+            record.status = JobStatus::Running;
+            record.attempts += 1;
+        });
+        match self.jobs.lock().unwrap().get(id) {
+            Some(job) => job.cancel.with_timeout(self.limits.job_timeout),
+            None => CancelToken::new(),
+        }
+    }
+
+    /// Whether a job that ended with `result` should run again.
+    fn should_retry(&self, id: &str, result: &Result<Transcript>) -> bool {
+        let Err(error) = result else {
+            return false;
+        };
+        let retryable = match error.downcast_ref::<ShoutError>() {
+            // Bad input fails the same way again; interruptions are final.
+            Some(ShoutError::Io { .. } | ShoutError::Model(_) | ShoutError::Device(_)) => true,
+            Some(_) => false,
+            // Downloads and temporary files.
+            None => true,
+        };
+        let jobs = self.jobs.lock().unwrap();
+        retryable
+            && jobs.get(id).is_some_and(|job| {
+                job.record.attempts <= self.limits.max_retries && !job.cancel.is_cancelled()
+            })
+    }
+
+    /// Queue the job again after the retry delay.
+    fn retry(&self, id: String, request: JobRequest, error: &anyhow::Error) {
+        let mut attempts = 0;
+        self.update(&id, |record| {
+            record.status = JobStatus::Queued;
+            record.error = Some(format!("{error:#}"));
+            attempts = record.attempts;
+        });
+        let delay = self.limits.retry_delay * 2u32.saturating_pow(attempts.saturating_sub(1));
+        tracing::warn!("Job {id} failed (attempt {attempts}), retrying in {delay:?}: {error:#}");
+        metrics::get().job_retried();
+
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            thread::sleep(delay);
+            let _ = tx.send((id, request));
+        });
+    }
+
+    fn finish(&self, id: String, result: Result<Transcript>) {
+        let failure = result.as_ref().err().map(Failure::of);
+        let (status, error, transcript) = match result {
+            Ok(transcript) => (JobStatus::Done, None, Some(transcript)),
+            Err(e) => match e.downcast::<ShoutError>() {
+                Ok(ShoutError::Interrupted { reason, partial }) => {
+                    let status = match reason {
+                        Interruption::Cancelled { .. } => JobStatus::Cancelled,
+                        Interruption::TimedOut { .. } => JobStatus::TimedOut,
+                    };
+                    (status, Some(reason.to_string()), Some(*partial))
+                }
+                Ok(e) => (JobStatus::Failed, Some(e.to_string()), None),
+                Err(e) => (JobStatus::Failed, Some(format!("{e:#}")), None),
+            },
+        };
+        metrics::get().job_finished(status.as_str());
+        self.update(&id, |record| {
+            record.finished(status, error, transcript);
+            record.failure = failure.filter(|_| status == JobStatus::Failed);
+        });
+        if let Some(store) = &self.store {
+            store.remove_audio(&id);
+        }
+
+        let mut finished = self.finished.lock().unwrap();
+        finished.push_back(id);
+        while finished.len() > MAX_FINISHED_JOBS {
+            if let Some(old) = finished.pop_front() {
+                self.forget(&old);
+            }
+        }
+        drop(finished);
+        self.expire();
+    }
+
+    fn forget(&self, id: &str) {
+        self.jobs.lock().unwrap().remove(id);
+        if let Some(store) = &self.store {
+            store.remove(id);
+        }
+    }
+
+    /// Forget finished jobs older than the result TTL, oldest first.
+    fn expire(&self) {
+        let now = SystemTime::now();
+        let mut finished = self.finished.lock().unwrap();
+        while let Some(id) = finished.front() {
+            let expired = self
+                .jobs
+                .lock()
+                .unwrap()
+                .get(id)
+                .is_none_or(|job| job.record.expired(self.limits.result_ttl, now));
+            if !expired {
+                break;
+            }
+            if let Some(id) = finished.pop_front() {
+                self.forget(&id);
+            }
+        }
+    }
+
+    fn work(
+        &self,
+        mut transcriber: Transcriber<ShoutModel>,
+        rx: Arc<Mutex<Receiver<(String, JobRequest)>>>,
+    ) {
+        loop {
+            let next = rx.lock().unwrap().recv();
+            let Ok((id, mut request)) = next else {
+                break;
+            };
+
+            let cancel = self.start_job(&id);
+            transcriber.options.cancel = cancel;
+            let result = self.run_job(&mut transcriber, &id, &request);
+            if self.should_retry(&id, &result) {
+                if let Err(e) = &result {
+                    self.retry(id, request, e);
+                }
+                continue;
+            }
+            let notify = request.notify.take();
+            self.finish(id, result);
+            if let Some(notify) = notify {
+                let _ = notify.send(());
+            }
+        }
+    }
+
+    fn run_job(
+        &self,
+        transcriber: &mut Transcriber<ShoutModel>,
+        id: &str,
+        request: &JobRequest,
+    ) -> Result<Transcript> {
+        let (bytes, extension) = match &request.source {
+            AudioSource::Upload { bytes, extension } => (Cow::from(bytes), extension.clone()),
+            AudioSource::Url(url) => {
+                let started = Instant::now();
+                let (bytes, extension) = fetch(
+                    url,
+                    transcriber.options.cancel.deadline(),
+                    self.limits.max_download_bytes,
+                )?;
+                metrics::get().stage("download", started.elapsed());
+                (Cow::from(bytes), extension)
+            }
+        };
+
+        let cached = self.cache.as_ref().map(|(cache, fingerprint)| {
+            let context = cache_context(fingerprint, &request.language, request.task);
+            (cache, ResultCache::key(&hash_bytes(&bytes), &context))
+        });
+        if let Some((cache, key)) = &cached {
+            let hit = cache.get(key);
+            metrics::get().cache_lookups(usize::from(hit.is_some()), usize::from(hit.is_none()));
+            if let Some(transcript) = hit {
+                return Ok(transcript);
+            }
+        }
+
+        let started = Instant::now();
+        let language = request.language.clone();
+        let transcript =
+            transcribe_bytes(transcriber, id, &bytes, extension, language, request.task)?;
+        let audio_ms = transcript.metadata.audio_duration_ms.unwrap_or(0);
+        metrics::get().transcribed(audio_ms as f64 / 1000.0, started.elapsed());
+        if let Some((cache, key)) = &cached
+            && let Err(e) = cache.put(key, &transcript)
+        {
+            tracing::warn!("Failed to cache result of job {id}: {e:#}");
+        }
+        Ok(transcript)
+    }
+}
+
+/// Jobs read back from the state directory.
+#[derive(Default)]
+struct Recovered {
+    jobs: HashMap<String, Job>,
+
+    /// Finished jobs to answer polls for, oldest first.
+    finished: VecDeque<String>,
+
+    /// Jobs to run again.
+    pending: Vec<(String, JobRequest)>,
+}
+
+/// The jobs in `store`. Expired and unrecoverable jobs are deleted.
+fn recover(store: &JobStore, ttl: Option<Duration>) -> Result<Recovered> {
+    let mut records = store.load_all()?;
+    records.sort_by_key(|r| r.finished_at);
+    let now = SystemTime::now();
+
+    let mut jobs = HashMap::new();
+    let mut finished = VecDeque::new();
+    let mut pending = Vec::new();
+    for mut record in records {
+        let id = record.id.clone();
+        if record.status.is_finished() {
+            if record.expired(ttl, now) {
+                store.remove(&id);
+                continue;
+            }
+            finished.push_back(id.clone());
+        } else {
+            match record.request(store.load_audio(&id)) {
+                Ok(request) => pending.push((id.clone(), request)),
+                Err(e) => {
+                    tracing::warn!("Dropping job {id}: {e:#}");
+                    store.remove(&id);
+                    continue;
+                }
+            }
+            // Running jobs were cut off by the restart.
+            record.status = JobStatus::Queued;
+        }
+        let cancel = CancelToken::new();
+        jobs.insert(id, Job { record, cancel });
+    }
+    if !pending.is_empty() {
+        tracing::info!("Resuming {} unfinished job(s)", pending.len());
+    }
+    Ok(Recovered {
+        jobs,
+        finished,
+        pending,
+    })
+}
+
+fn transcribe_bytes(
+    transcriber: &mut Transcriber<ShoutModel>,
+    id: &str,
+    bytes: &[u8],
+    extension: Option<String>,
+    language: LanguageSelection,
+    task: Task,
+) -> Result<Transcript> {
+    // The decoder reads from a path; the extension helps it pick the container.
+    let mut path = std::env::temp_dir().join(format!("shout-{id}"));
+    if let Some(ext) = extension {
+        path.set_extension(ext);
+    }
+    fs::write(&path, bytes)
+        .with_context(|| format!("Failed to write upload: {}", path.display()))?;
+
+    transcriber.options.language = language;
+    transcriber.options.task = task;
+    let mut result = transcriber.transcribe_file(&path);
+
+    let _ = fs::remove_file(&path);
+    match &mut result {
+        Ok(transcript) => transcript.metadata.audio_path = None,
+        Err(ShoutError::Interrupted { partial, .. }) => partial.metadata.audio_path = None,
+        Err(_) => {}
+    }
+    Ok(result?)
+}
+
+/// Download `url`, giving up at `deadline` or beyond `max_bytes`.
+fn fetch(
+    url: &str,
+    deadline: Option<Instant>,
+    max_bytes: u64,
+) -> Result<(Vec<u8>, Option<String>)> {
+    let timeout = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+    let response = ureq::get(url)
+        .config()
+        .timeout_global(timeout)
+        .build()
+        .call()
+        .with_context(|| format!("Failed to fetch {url}"))?;
+
+    let bytes = response
+        .into_body()
+        .with_config()
+        .limit(max_bytes)
+        .read_to_vec()
+        .with_context(|| format!("Failed to read {url}"))?;
+
+    let extension = url
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.split(['?', '#']).next())
+        .and_then(|name| Path::new(name).extension())
+        .map(|e| e.to_string_lossy().to_string());
+    Ok((bytes, extension))
+}
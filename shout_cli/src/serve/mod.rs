@@ -0,0 +1,174 @@
+//! `shout serve`: transcription as an HTTP service.
+//!
+//! Clients submit audio to `POST /v1/jobs`, poll `GET /v1/jobs/{id}` and fetch
+//! the transcript from `GET /v1/jobs/{id}/result`. Jobs wait in a bounded queue
+//! and are processed by a fixed number of workers, each with its own model;
+//! with `--cache-dir`, audio seen before is answered from the results cache.
+//! `DELETE /v1/jobs/{id}` cancels a job, and jobs over `--job-timeout` stop;
+//! either way the result holds what was transcribed until then. With
+//! `--state-dir`, jobs survive restarts; results expire after `--result-ttl`.
+//! Live audio is transcribed over the WebSocket at `/v1/stream`. With
+//! `--grpc-addr` the same functionality is offered as the gRPC service in
+//! `proto/shout.proto`. Prometheus metrics are served at `/metrics`.
+
+mod grpc;
+mod jobs;
+mod routes;
+mod store;
+mod stream;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use shout_core::backend::device::DeviceSpec;
+use shout_core::cache::ResultCache;
+use shout_core::cancel::Timeouts;
+
+use jobs::{JobLimits, JobQueue};
+use store::JobStore;
+use stream::StreamPool;
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Model directory (see `shout transcribe --model`).
+    #[arg(long)]
+    pub model: PathBuf,
+
+    /// auto, cpu, cuda[:N] or metal[:N].
+    #[arg(long, default_value = shout_config::get().device.as_str())]
+    pub device: DeviceSpec,
+
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub addr: SocketAddr,
+
+    /// Also serve the gRPC API on this address.
+    #[arg(long)]
+    pub grpc_addr: Option<SocketAddr>,
+
+    /// Number of jobs transcribed at the same time (one model instance each).
+    #[arg(long, default_value_t = 1)]
+    pub concurrency: usize,
+
+    /// Jobs waiting beyond this are rejected with 503.
+    #[arg(long, default_value_t = 16)]
+    pub queue_depth: usize,
+
+    /// Concurrent WebSocket streams (one model instance each).
+    #[arg(long, default_value_t = 1)]
+    pub stream_sessions: usize,
+
+    /// Largest accepted upload, in MiB.
+    #[arg(long, default_value_t = 200)]
+    pub max_upload_mb: usize,
+
+    /// Answer re-submitted audio from this results cache (default:
+    /// `paths.results_cache_dir` of the config, if set).
+    #[arg(long)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Size limit of the results cache, in MiB; least recently used entries go first.
+    #[arg(long, default_value_t = 1024)]
+    pub cache_max_mb: u64,
+
+    /// Seconds a job may run, download included, before it stops with a
+    /// partial result. 0 for no limit.
+    #[arg(long, default_value_t = 600)]
+    pub job_timeout: u64,
+
+    /// Seconds allowed for decoding one job's audio.
+    #[arg(long)]
+    pub decode_timeout: Option<u64>,
+
+    /// Seconds allowed for the model on one 30-second window.
+    #[arg(long)]
+    pub window_timeout: Option<u64>,
+
+    /// Keep jobs and results in this directory, so that a restart resumes
+    /// unfinished jobs and keeps results available.
+    #[arg(long)]
+    pub state_dir: Option<PathBuf>,
+
+    /// Further attempts for jobs that fail on a download, I/O or the device.
+    #[arg(long, default_value_t = 2)]
+    pub max_retries: u32,
+
+    /// Seconds before the first retry; doubled for each further one.
+    #[arg(long, default_value_t = 5)]
+    pub retry_delay: u64,
+
+    /// Seconds a finished job's result is kept. 0 keeps results until the
+    /// 1000 most recent jobs have pushed them out.
+    #[arg(long, default_value_t = 86_400)]
+    pub result_ttl: u64,
+}
+
+pub fn run(mut args: ServeArgs) -> Result<()> {
+    args.model = crate::registry::resolve_model(&args.model)?;
+    let cache_dir = args
+        .cache_dir
+        .clone()
+        .or_else(|| shout_config::get().paths.results_cache_dir.clone());
+    let cache = match &cache_dir {
+        Some(dir) => Some(ResultCache::open(dir, args.cache_max_mb * 1024 * 1024)?),
+        None => None,
+    };
+    let limits = JobLimits {
+        job_timeout: (args.job_timeout > 0).then(|| Duration::from_secs(args.job_timeout)),
+        stages: Timeouts {
+            decode: args.decode_timeout.map(Duration::from_secs),
+            features: None,
+            inference: args.window_timeout.map(Duration::from_secs),
+        },
+        max_download_bytes: (args.max_upload_mb * 1024 * 1024) as u64,
+        max_retries: args.max_retries,
+        retry_delay: Duration::from_secs(args.retry_delay),
+        result_ttl: (args.result_ttl > 0).then(|| Duration::from_secs(args.result_ttl)),
+    };
+    let store = match &args.state_dir {
+        Some(dir) => Some(JobStore::open(dir)?),
+        None => None,
+    };
+    let queue = JobQueue::start(
+        &args.model,
+        args.device,
+        args.concurrency.max(1),
+        args.queue_depth.max(1),
+        cache,
+        store,
+        limits,
+    )?;
+    let streams = StreamPool::load(&args.model, args.device, args.stream_sessions)?;
+    let app = routes::router(
+        queue.clone(),
+        streams.clone(),
+        args.max_upload_mb * 1024 * 1024,
+    );
+
+    let runtime = tokio::runtime::Runtime::new().context("Failed to start async runtime")?;
+    runtime.block_on(async move {
+        if let Some(addr) = args.grpc_addr {
+            let service = grpc::service(queue, streams, args.max_upload_mb * 1024 * 1024);
+            tracing::info!("gRPC listening on {addr}");
+            tokio::spawn(async move {
+                if let Err(e) = tonic::transport::Server::builder()
+                    .add_service(service)
+                    .serve(addr)
+                    .await
+                {
+                    tracing::error!("gRPC server error: {e}");
+                }
+            });
+        }
+
+        let listener = tokio::net::TcpListener::bind(args.addr)
+            .await
+            .with_context(|| format!("Failed to bind {}", args.addr))?;
+        tracing::info!("Listening on http://{}", args.addr);
+        axum::serve(listener, app).await.context("Server error")
+    })
+}
@@ -0,0 +1,195 @@
+//! HTTP routes.
+
+use std::path::Path as FsPath;
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::{DefaultBodyLimit, MatchedPath, Multipart, Path, Query, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::decoding::prompt::Task;
+use shout_core::output::{OutputFormat, write_transcript};
+
+use super::jobs::{AudioSource, JobQueue, JobRequest, JobStatus, SubmitError};
+use super::stream::{self, StreamPool};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub jobs: Arc<JobQueue>,
+    pub streams: Arc<StreamPool>,
+}
+
+pub fn router(jobs: Arc<JobQueue>, streams: Arc<StreamPool>, max_body_bytes: usize) -> Router {
+    Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/v1/jobs", post(submit))
+        .route("/v1/jobs/{id}", get(status).delete(cancel))
+        .route("/v1/jobs/{id}/result", get(result))
+        .route("/v1/stream", get(stream::stream))
+        .route_layer(middleware::from_fn(track))
+        .route("/metrics", get(metrics))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .with_state(AppState { jobs, streams })
+}
+
+/// Count and time requests to the API routes.
+async fn track(route: MatchedPath, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let status = response.status().as_u16();
+    crate::metrics::get().http_request(&method, route.as_str(), status, started.elapsed());
+    response
+}
+
+/// Prometheus scrape endpoint.
+async fn metrics() -> Response {
+    match crate::metrics::get().render() {
+        Ok(body) => ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response(),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")),
+    }
+}
+
+pub fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+/// Multipart form: `file` (audio upload) or `url`, plus optional `language` and `task`.
+async fn submit(State(state): State<AppState>, mut form: Multipart) -> Response {
+    let mut source = None;
+    let mut language = LanguageSelection::Auto;
+    let mut task = Task::Transcribe;
+
+    loop {
+        let field = match form.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return error(StatusCode::BAD_REQUEST, e.body_text()),
+        };
+
+        let name = field.name().unwrap_or_default().to_string();
+        match name.as_str() {
+            "file" => {
+                let extension = field
+                    .file_name()
+                    .and_then(|n| FsPath::new(n).extension())
+                    .map(|e| e.to_string_lossy().to_string());
+                match field.bytes().await {
+                    Ok(bytes) => {
+                        source = Some(AudioSource::Upload {
+                            bytes: bytes.to_vec(),
+                            extension,
+                        })
+                    }
+                    Err(e) => return error(StatusCode::BAD_REQUEST, e.body_text()),
+                }
+            }
+            "url" | "language" | "task" => {
+                let value = match field.text().await {
+                    Ok(v) => v.trim().to_string(),
+                    Err(e) => return error(StatusCode::BAD_REQUEST, e.body_text()),
+                };
+                let parsed = match name.as_str() {
+                    "url" => {
+                        source = Some(AudioSource::Url(value));
+                        Ok(())
+                    }
+                    "language" => value.parse().map(|l| language = l),
+                    _ => value.parse().map(|t| task = t),
+                };
+                if let Err(e) = parsed {
+                    return error(StatusCode::BAD_REQUEST, format!("{e}"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let Some(source) = source else {
+        return error(StatusCode::BAD_REQUEST, "expected a `file` or `url` field");
+    };
+
+    match state.jobs.submit(JobRequest {
+        source,
+        language,
+        task,
+        notify: None,
+    }) {
+        Ok(id) => (
+            StatusCode::ACCEPTED,
+            Json(json!({ "id": id, "status": JobStatus::Queued })),
+        )
+            .into_response(),
+        Err(SubmitError::QueueFull) => error(StatusCode::SERVICE_UNAVAILABLE, "job queue is full"),
+        Err(SubmitError::Storage(e)) => error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+async fn status(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.jobs.view(&id) {
+        Some(view) => Json(view).into_response(),
+        None => error(StatusCode::NOT_FOUND, "unknown job"),
+    }
+}
+
+/// Stop a queued or running job; a running job keeps what it transcribed so far.
+async fn cancel(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.jobs.cancel(&id) {
+        Some(view) if view.status.is_finished() => {
+            (StatusCode::CONFLICT, Json(view)).into_response()
+        }
+        Some(view) => (StatusCode::ACCEPTED, Json(view)).into_response(),
+        None => error(StatusCode::NOT_FOUND, "unknown job"),
+    }
+}
+
+#[derive(Deserialize)]
+struct ResultQuery {
+    /// txt, srt, vtt, ctm or json (default).
+    format: Option<String>,
+}
+
+async fn result(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ResultQuery>,
+) -> Response {
+    let format = match query.format.as_deref().map(str::parse::<OutputFormat>) {
+        None => OutputFormat::Json,
+        Some(Ok(f)) => f,
+        Some(Err(e)) => return error(StatusCode::BAD_REQUEST, format!("{e}")),
+    };
+
+    let Some(view) = state.jobs.view(&id) else {
+        return error(StatusCode::NOT_FOUND, "unknown job");
+    };
+    match view.status {
+        // Partial transcripts of stopped jobs are returned like complete ones.
+        JobStatus::Done | JobStatus::Cancelled | JobStatus::TimedOut => {}
+        JobStatus::Failed => {
+            return error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                view.error.unwrap_or_default(),
+            );
+        }
+        JobStatus::Queued | JobStatus::Running => {
+            return (StatusCode::CONFLICT, Json(view)).into_response();
+        }
+    }
+
+    let Some(transcript) = state.jobs.transcript(&id) else {
+        return error(StatusCode::NOT_FOUND, "unknown job");
+    };
+    let mut body = Vec::new();
+    if let Err(e) = write_transcript(&mut body, format, &transcript) {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}"));
+    }
+    ([(header::CONTENT_TYPE, format.mime_type())], body).into_response()
+}
@@ -0,0 +1,205 @@
+//! Jobs on disk, so a restarted server neither loses submitted work nor
+//! forgets results clients have yet to fetch.
+//!
+//! Each job is a `<id>.json` record in the state directory, rewritten on
+//! every status change; an uploaded file is kept next to it as `<id>.audio`
+//! until the job finishes. Writes go through a temporary file and a rename,
+//! so a crash leaves either the old or the new record.
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::decoding::prompt::Task;
+use shout_core::transcript::Transcript;
+
+use super::jobs::{AudioSource, Failure, JobRequest, JobStatus};
+
+/// A job as stored: the request (without uploaded bytes) and how it went.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: String,
+    pub status: JobStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<Failure>,
+
+    /// Times a worker has started the job.
+    #[serde(default)]
+    pub attempts: u32,
+    pub source: StoredSource,
+
+    /// Language code or `auto`.
+    pub language: String,
+
+    /// `transcribe` or `translate`.
+    pub task: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript: Option<Transcript>,
+
+    /// When the job finished, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StoredSource {
+    /// Bytes in `<id>.audio`.
+    Upload {
+        extension: Option<String>,
+    },
+    Url {
+        url: String,
+    },
+}
+
+impl JobRecord {
+    /// A queued job for `request`.
+    pub fn new(id: &str, request: &JobRequest) -> Self {
+        let source = match &request.source {
+            AudioSource::Upload { extension, .. } => StoredSource::Upload {
+                extension: extension.clone(),
+            },
+            AudioSource::Url(url) => StoredSource::Url { url: url.clone() },
+        };
+        let language = match &request.language {
+            LanguageSelection::Auto => "auto".to_string(),
+            LanguageSelection::Fixed(code) => code.clone(),
+        };
+        let task = match request.task {
+            Task::Transcribe => "transcribe",
+            Task::Translate => "translate",
+        };
+        Self {
+            id: id.to_string(),
+            status: JobStatus::Queued,
+            error: None,
+            failure: None,
+            attempts: 0,
+            source,
+            language,
+            task: task.to_string(),
+            transcript: None,
+            finished_at: None,
+        }
+    }
+
+    /// The request to run again after a restart; `upload` holds the bytes of
+    /// an uploaded file.
+    pub fn request(&self, upload: Option<Vec<u8>>) -> Result<JobRequest> {
+        let source = match &self.source {
+            StoredSource::Upload { extension } => AudioSource::Upload {
+                bytes: upload.context("uploaded audio is missing")?,
+                extension: extension.clone(),
+            },
+            StoredSource::Url { url } => AudioSource::Url(url.clone()),
+        };
+        Ok(JobRequest {
+            source,
+            language: self.language.parse()?,
+            task: self.task.parse()?,
+            notify: None,
+        })
+    }
+
+    pub fn finished(&mut self, status: JobStatus, error: Option<String>, t: Option<Transcript>) {
+        self.status = status;
+        self.error = error;
+        self.transcript = t;
+        self.finished_at = Some(unix_seconds(SystemTime::now()));
+    }
+
+    /// Whether the result is older than `ttl`.
+    pub fn expired(&self, ttl: Option<Duration>, now: SystemTime) -> bool {
+        match (ttl, self.finished_at) {
+            (Some(ttl), Some(at)) => unix_seconds(now) >= at.saturating_add(ttl.as_secs()),
+            _ => false,
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[derive(Debug, Clone)]
+pub struct JobStore {
+    dir: PathBuf,
+}
+
+impl JobStore {
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create state dir: {}", dir.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    fn record_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    fn audio_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.audio"))
+    }
+
+    pub fn save(&self, record: &JobRecord) -> Result<()> {
+        let path = self.record_path(&record.id);
+        let tmp = path.with_extension("json.tmp");
+        let file = File::create(&tmp)
+            .with_context(|| format!("Failed to write job record: {}", tmp.display()))?;
+        serde_json::to_writer(BufWriter::new(file), record)?;
+        fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to write job record: {}", path.display()))
+    }
+
+    pub fn save_audio(&self, id: &str, bytes: &[u8]) -> Result<()> {
+        let path = self.audio_path(id);
+        fs::write(&path, bytes)
+            .with_context(|| format!("Failed to store upload: {}", path.display()))
+    }
+
+    pub fn load_audio(&self, id: &str) -> Option<Vec<u8>> {
+        fs::read(self.audio_path(id)).ok()
+    }
+
+    pub fn remove_audio(&self, id: &str) {
+        let _ = fs::remove_file(self.audio_path(id));
+    }
+
+    /// Forget a job entirely.
+    pub fn remove(&self, id: &str) {
+        let _ = fs::remove_file(self.record_path(id));
+        self.remove_audio(id);
+    }
+
+    /// Every stored job. Unreadable records are skipped with a warning.
+    pub fn load_all(&self) -> Result<Vec<JobRecord>> {
+        let entries = fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read state dir: {}", self.dir.display()))?;
+        let mut records = Vec::new();
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.extension().is_none_or(|x| x != "json") {
+                continue;
+            }
+            let record = fs::read(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| Ok(serde_json::from_slice::<JobRecord>(&bytes)?));
+            match record {
+                Ok(record) => records.push(record),
+                Err(e) => tracing::warn!("Skipping job record {}: {e:#}", path.display()),
+            }
+        }
+        Ok(records)
+    }
+}
@@ -0,0 +1,251 @@
+//! `GET /v1/stream`: live transcription over a WebSocket.
+//!
+//! Query parameters: `encoding` (`pcm_s16le` (default), `pcm_f32le` or `opus`),
+//! `sample_rate` (default 16000, 48000 for Opus), `language` and `stabilization`
+//! (`margin` (default), `chunks:K` or `agreement:N`, see
+//! [`shout_core::pipeline::streaming::Stabilization`]). The client sends
+//! mono audio as binary messages (one Opus packet per message) and the text
+//! message `end` to flush. The server answers with JSON text messages:
+//! `{"type":"partial","segments":[…]}`, `{"type":"final","segments":[…]}` and
+//! finally `{"type":"done"}`.
+
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, bail};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use shout_core::audio::resample::StreamResampler;
+use shout_core::backend::device::DeviceSpec;
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::model::shout::ShoutModel;
+use shout_core::nonblocking::AsyncStream;
+use shout_core::pipeline::streaming::{
+    Stabilization, StreamUpdate, StreamingOptions, StreamingSession,
+};
+use shout_core::pipeline::transcribe::Transcriber;
+use shout_core::transcript::Segment;
+
+use super::routes::{AppState, error};
+use crate::transcribe::load_transcriber;
+
+/// Model instances reserved for WebSocket sessions.
+pub struct StreamPool {
+    idle: Mutex<Vec<Transcriber<ShoutModel>>>,
+}
+
+impl StreamPool {
+    pub fn load(model_dir: &Path, device: DeviceSpec, sessions: usize) -> Result<Arc<Self>> {
+        let idle = (0..sessions)
+            .map(|_| load_transcriber(model_dir, device, 1))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Arc::new(Self {
+            idle: Mutex::new(idle),
+        }))
+    }
+
+    /// A session on a free instance, or `None` if all are in use.
+    pub(super) fn checkout(
+        self: &Arc<Self>,
+        language: LanguageSelection,
+        opts: StreamingOptions,
+    ) -> Option<PooledSession> {
+        let mut transcriber = self.idle.lock().unwrap().pop()?;
+        transcriber.options.language = language;
+        Some(PooledSession {
+            pool: Arc::clone(self),
+            session: Some(AsyncStream::new(StreamingSession::new(transcriber, opts))),
+        })
+    }
+}
+
+/// A streaming session on an instance from a [`StreamPool`], returned to the
+/// pool when dropped: after the session, a failed upgrade or a panic alike.
+pub(super) struct PooledSession {
+    pool: Arc<StreamPool>,
+    session: Option<AsyncStream<Transcriber<ShoutModel>>>,
+}
+
+impl Deref for PooledSession {
+    type Target = AsyncStream<Transcriber<ShoutModel>>;
+
+    fn deref(&self) -> &Self::Target {
+        self.session.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledSession {
+    fn drop(&mut self) {
+        // `None` while a step whose future was dropped still runs; that
+        // instance is lost to the pool.
+        if let Some(session) = self.session.take().and_then(AsyncStream::into_inner) {
+            self.pool.idle.lock().unwrap().push(session.into_inner());
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    encoding: Option<String>,
+    sample_rate: Option<u32>,
+    language: Option<String>,
+    stabilization: Option<String>,
+}
+
+/// How binary messages are turned into samples.
+pub(super) enum FrameDecoder {
+    S16,
+    F32,
+    Opus(opus::Decoder),
+}
+
+impl FrameDecoder {
+    pub(super) fn new(encoding: &str, sample_rate: u32) -> Result<Self> {
+        Ok(match encoding {
+            "pcm_s16le" => FrameDecoder::S16,
+            "pcm_f32le" => FrameDecoder::F32,
+            "opus" => FrameDecoder::Opus(
+                opus::Decoder::new(sample_rate, opus::Channels::Mono)
+                    .context("Opus supports 8, 12, 16, 24 and 48 kHz")?,
+            ),
+            other => bail!("unknown encoding '{other}' (expected pcm_s16le, pcm_f32le or opus)"),
+        })
+    }
+
+    pub(super) fn decode(&mut self, data: &[u8]) -> Result<Vec<f32>> {
+        Ok(match self {
+            FrameDecoder::S16 => data
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                .collect(),
+            FrameDecoder::F32 => data
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            FrameDecoder::Opus(decoder) => {
+                // 120 ms at 48 kHz is the longest Opus frame.
+                let mut out = vec![0.0f32; 5760];
+                let n = decoder
+                    .decode_float(data, &mut out, false)
+                    .context("invalid Opus packet")?;
+                out.truncate(n);
+                out
+            }
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct SegmentView<'a> {
+    start_ms: u64,
+    end_ms: u64,
+    text: &'a str,
+}
+
+fn segments_json(segments: &[Segment]) -> Vec<SegmentView<'_>> {
+    segments
+        .iter()
+        .map(|s| SegmentView {
+            start_ms: s.start_ms,
+            end_ms: s.end_ms,
+            text: s.text.trim(),
+        })
+        .collect()
+}
+
+pub async fn stream(
+    State(state): State<AppState>,
+    Query(query): Query<StreamQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let encoding = query.encoding.as_deref().unwrap_or("pcm_s16le");
+    let default_rate = if encoding == "opus" { 48_000 } else { 16_000 };
+    let sample_rate = query.sample_rate.unwrap_or(default_rate);
+
+    let decoder = match FrameDecoder::new(encoding, sample_rate) {
+        Ok(d) => d,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("{e:#}")),
+    };
+    let resampler = match StreamResampler::new(sample_rate, 16_000) {
+        Ok(r) => r,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("{e:#}")),
+    };
+    let language = match query
+        .language
+        .as_deref()
+        .map(str::parse::<LanguageSelection>)
+    {
+        None => LanguageSelection::Auto,
+        Some(Ok(l)) => l,
+        Some(Err(e)) => return error(StatusCode::BAD_REQUEST, format!("{e}")),
+    };
+
+    let stabilization = match query
+        .stabilization
+        .as_deref()
+        .map(str::parse::<Stabilization>)
+    {
+        None => Stabilization::default(),
+        Some(Ok(s)) => s,
+        Some(Err(e)) => return error(StatusCode::BAD_REQUEST, format!("{e}")),
+    };
+
+    let opts = StreamingOptions {
+        stabilization,
+        ..Default::default()
+    };
+    let Some(session) = state.streams.checkout(language, opts) else {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "no free streaming session");
+    };
+
+    ws.on_upgrade(move |mut socket| async move {
+        if let Err(e) = run_session(&mut socket, &session, decoder, resampler).await {
+            let msg = json!({ "type": "error", "message": format!("{e:#}") });
+            let _ = socket.send(Message::Text(msg.to_string().into())).await;
+        }
+    })
+}
+
+async fn run_session(
+    socket: &mut WebSocket,
+    session: &AsyncStream<Transcriber<ShoutModel>>,
+    mut decoder: FrameDecoder,
+    mut resampler: StreamResampler,
+) -> Result<()> {
+    while let Some(msg) = socket.recv().await {
+        match msg? {
+            Message::Binary(data) => {
+                let pcm = resampler.push(&decoder.decode(&data)?)?;
+                if let Some(update) = session.push(pcm).await? {
+                    send_update(socket, &update).await?;
+                }
+            }
+            Message::Text(text) if text.trim() == "end" => break,
+            Message::Close(_) => return Ok(()),
+            _ => {}
+        }
+    }
+
+    let update = session.finish(resampler.finish()?).await?;
+    send_update(socket, &update).await?;
+    socket
+        .send(Message::Text(json!({ "type": "done" }).to_string().into()))
+        .await?;
+    Ok(())
+}
+
+async fn send_update(socket: &mut WebSocket, update: &StreamUpdate) -> Result<()> {
+    if !update.finals.is_empty() {
+        let msg = json!({ "type": "final", "segments": segments_json(&update.finals) });
+        socket.send(Message::Text(msg.to_string().into())).await?;
+    }
+    let msg = json!({ "type": "partial", "segments": segments_json(&update.partial) });
+    socket.send(Message::Text(msg.to_string().into())).await?;
+    Ok(())
+}
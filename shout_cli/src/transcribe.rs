@@ -0,0 +1,308 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use shout_core::backend::device::{DeviceSpec, select_device};
+use shout_core::backend::memory::MemoryEstimate;
+use shout_core::confidence::retain_confident;
+use shout_core::decoding::biasing::Hotword;
+use shout_core::decoding::grammar::Grammar;
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::decoding::lm::NgramLm;
+use shout_core::decoding::prompt::Task;
+use shout_core::inference;
+use shout_core::model::shout::ShoutModel;
+use shout_core::output::OutputFormat;
+use shout_core::pipeline::batch::{BatchOptions, run_batch};
+use shout_core::pipeline::transcribe::Transcriber;
+use shout_core::postprocess::PostProcessor;
+use shout_core::postprocess::itn::InverseNormalizer;
+use shout_core::postprocess::punctuation::RulePunctuator;
+use shout_core::postprocess::redact::{RedactOptions, Redactor};
+use shout_core::registry::{self, StageSpec};
+
+use crate::batch::manifest_inputs;
+use crate::registry::resolve_model;
+
+#[derive(Args)]
+pub struct TranscribeArgs {
+    /// Audio file to transcribe, or what `--source` should load.
+    #[arg(required_unless_present = "manifest")]
+    pub audio: Option<PathBuf>,
+
+    /// Transcribe every file of this JSONL manifest (one `{"audio_path": ...}`
+    /// object per line) instead, one output file each in the `--output`
+    /// directory (default: `transcripts`).
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "audio",
+            "source",
+            "augmentations",
+            "punctuate",
+            "itn",
+            "redact_pii",
+            "mask_profanity",
+            "redaction_regions",
+            "min_confidence",
+        ]
+    )]
+    pub manifest: Option<PathBuf>,
+
+    /// With `--manifest`: audio decode worker threads (default: number of CPUs).
+    #[arg(long, requires = "manifest")]
+    pub jobs: Option<usize>,
+
+    /// With `--manifest`: inputs that fit one window go through the encoder
+    /// in forward passes of up to this many.
+    #[arg(long, default_value_t = 8)]
+    pub batch_size: usize,
+
+    /// Model directory (config.json, tokenizer.json and model.safetensors or *.gguf),
+    /// or the name of a model fetched with `shout model pull`.
+    #[arg(long)]
+    pub model: PathBuf,
+
+    /// auto, cpu, cuda[:N] or metal[:N]; falls back to the CPU if the device is
+    /// unavailable or short on memory.
+    #[arg(long, default_value = shout_config::get().device.as_str())]
+    pub device: DeviceSpec,
+
+    /// Output format: txt, srt, vtt, json, ctm or a registered writer, with
+    /// optional JSON parameters (`NAME:{...}`).
+    #[arg(long, default_value = "txt", value_name = "NAME[:PARAMS]")]
+    pub format: StageSpec,
+
+    /// Load the audio with a registered source instead of decoding a file.
+    #[arg(long, value_name = "NAME[:PARAMS]")]
+    pub source: Option<StageSpec>,
+
+    /// Augmentation applied to the audio before transcription, e.g.
+    /// `gain:{"db": -6}`. May be repeated; applied in order.
+    #[arg(long = "augment", value_name = "NAME[:PARAMS]")]
+    pub augmentations: Vec<StageSpec>,
+
+    /// Spoken language code, or `auto` to detect it.
+    #[arg(long, default_value = shout_config::get().language.as_str())]
+    pub language: LanguageSelection,
+
+    /// `transcribe` or `translate` (to English).
+    #[arg(long, default_value = "transcribe")]
+    pub task: Task,
+
+    /// Context for the decoder: names, domain terms or the preferred spelling style.
+    #[arg(long)]
+    pub initial_prompt: Option<String>,
+
+    /// Beam search with this many beams instead of greedy decoding.
+    #[arg(long)]
+    pub beam_size: Option<usize>,
+
+    /// Phrase to favour during beam search, optionally with a per-token boost
+    /// (`--hotword "Grafana:2.0"`). May be repeated.
+    #[arg(long = "hotword", value_name = "PHRASE[:BOOST]")]
+    pub hotwords: Vec<Hotword>,
+
+    /// Word n-gram LM (ARPA) fused into beam search.
+    #[arg(long)]
+    pub lm: Option<PathBuf>,
+
+    /// Weight of the LM log-probability.
+    #[arg(long, default_value_t = 0.3)]
+    pub lm_weight: f32,
+
+    /// Score added per word when the LM is used.
+    #[arg(long, default_value_t = 0.0)]
+    pub lm_bonus: f32,
+
+    /// Only transcribe phrases from this grammar: a phrase list (one per
+    /// line) or a JSGF-like rule file.
+    #[arg(long, value_name = "PATH")]
+    pub grammar: Option<PathBuf>,
+
+    /// Restore punctuation and capitalization on unpunctuated output.
+    #[arg(long)]
+    pub punctuate: bool,
+
+    /// Write numbers, money, times and dates in written form ("23 €" for "twenty three euros").
+    #[arg(long)]
+    pub itn: bool,
+
+    /// Replace e-mail addresses, IBANs and long digit sequences with `[REDACTED]`.
+    #[arg(long)]
+    pub redact_pii: bool,
+
+    /// Mask profanity ("s***").
+    #[arg(long)]
+    pub mask_profanity: bool,
+
+    /// Write the audio regions that were redacted or masked to this JSON file (for bleeping).
+    #[arg(long, value_name = "PATH")]
+    pub redaction_regions: Option<PathBuf>,
+
+    /// Leave out segments whose confidence (0-1) is below this.
+    #[arg(long)]
+    pub min_confidence: Option<f32>,
+
+    /// Write the result here instead of stdout (with `--manifest`: the
+    /// directory for the output files).
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+}
+
+/// Load the model on `device`, checking first that it fits when decoding with `beams` hypotheses.
+pub fn load_transcriber(
+    model_dir: &Path,
+    device: DeviceSpec,
+    beams: usize,
+) -> Result<Transcriber<ShoutModel>> {
+    let required = MemoryEstimate::for_model_dir(model_dir, beams)?.total();
+    let selected = select_device(device, Some(required))?;
+    if let Some(reason) = &selected.fallback {
+        tracing::warn!("Falling back to CPU ({reason})");
+    }
+    Ok(inference::load_transcriber(model_dir, &selected.device)?)
+}
+
+pub fn run(args: TranscribeArgs) -> Result<()> {
+    let model = resolve_model(&args.model)?;
+    let mut beam_size = args.beam_size;
+    let constrained = !args.hotwords.is_empty() || args.lm.is_some() || args.grammar.is_some();
+    if constrained && beam_size.is_none() {
+        beam_size = Some(5);
+    }
+
+    let mut transcriber = load_transcriber(&model, args.device, beam_size.unwrap_or(1))?;
+    transcriber.options.language = args.language.clone();
+    transcriber.options.task = args.task;
+    transcriber.long_form.initial_prompt = args.initial_prompt.clone();
+    transcriber.options.beam_size = beam_size;
+    if !args.hotwords.is_empty() {
+        transcriber.set_hotwords(&args.hotwords)?;
+    }
+    if let Some(path) = &args.lm {
+        let lm = NgramLm::from_arpa(path)?;
+        transcriber.set_language_model(Arc::new(lm), args.lm_weight, args.lm_bonus);
+    }
+    if let Some(path) = &args.grammar {
+        transcriber.set_grammar(&Grammar::from_file(path)?)?;
+    }
+
+    if let Some(manifest) = &args.manifest {
+        return transcribe_manifest(manifest, &mut transcriber, &args);
+    }
+    let audio = args
+        .audio
+        .as_deref()
+        .expect("clap requires the audio without --manifest");
+
+    let writer = registry::global().output_writer(&args.format.name, &args.format.params)?;
+    let mut transcript = if args.source.is_none() && args.augmentations.is_empty() {
+        transcriber.transcribe_file(audio)
+    } else {
+        load_audio(&transcriber, audio, &args).and_then(|pcm| {
+            let mut transcript = transcriber.transcribe_pcm(&pcm)?;
+            transcript.metadata.audio_path = Some(audio.display().to_string());
+            Ok(transcript)
+        })
+    }
+    .with_context(|| format!("failed to transcribe {}", audio.display()))?;
+
+    // Punctuate first: written forms like "March 3, 2024" would otherwise look
+    // like already formatted text.
+    if args.punctuate {
+        RulePunctuator::default().process(&mut transcript);
+    }
+    if args.itn {
+        InverseNormalizer::default().process(&mut transcript);
+    }
+
+    if args.redact_pii || args.mask_profanity {
+        let redactor = Redactor::new(RedactOptions {
+            redact_pii: args.redact_pii,
+            mask_profanity: args.mask_profanity,
+            ..Default::default()
+        });
+        let regions = redactor.redact(&mut transcript);
+        if let Some(path) = &args.redaction_regions {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            serde_json::to_writer_pretty(BufWriter::new(file), &regions)?;
+        }
+    }
+
+    if let Some(min) = args.min_confidence {
+        let dropped = retain_confident(&mut transcript, min, transcriber.calibration.as_ref());
+        if dropped > 0 {
+            tracing::info!("Dropped {dropped} segment(s) below confidence {min}");
+        }
+    }
+
+    match &args.output {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create output: {}", path.display()))?;
+            writer.write(&mut BufWriter::new(file), &transcript)?;
+        }
+        None => writer.write(&mut io::stdout().lock(), &transcript)?,
+    }
+    Ok(())
+}
+
+/// `--manifest`: every file of the manifest through the batch pipeline.
+fn transcribe_manifest(
+    manifest: &Path,
+    transcriber: &mut Transcriber<ShoutModel>,
+    args: &TranscribeArgs,
+) -> Result<()> {
+    let format: OutputFormat = args
+        .format
+        .name
+        .parse()
+        .context("--manifest writes txt, srt, vtt, json or ctm")?;
+    let mut opts = BatchOptions {
+        batch_size: args.batch_size,
+        front_end: transcriber.front_end().clone(),
+        window_ms: transcriber.long_form.window_ms,
+        format,
+        out_dir: args
+            .output
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("transcripts")),
+        ..Default::default()
+    };
+    if let Some(jobs) = args.jobs {
+        opts.jobs = jobs;
+    }
+
+    let inputs = manifest_inputs(manifest)?;
+    let summary = run_batch(&inputs, transcriber, &opts)?;
+    print!("{summary}");
+    Ok(())
+}
+
+/// The audio from `--source` (a decoded file otherwise), augmented.
+fn load_audio(
+    transcriber: &Transcriber<ShoutModel>,
+    audio: &Path,
+    args: &TranscribeArgs,
+) -> shout_core::Result<Vec<f32>> {
+    let mut pcm = match &args.source {
+        Some(spec) => {
+            let source = registry::global().source(&spec.name, &spec.params)?;
+            source.load(&audio.to_string_lossy())?
+        }
+        None => transcriber.decode_file(audio)?,
+    };
+    for spec in &args.augmentations {
+        registry::global()
+            .augmentation(&spec.name, &spec.params)?
+            .apply(&mut pcm)?;
+    }
+    Ok(pcm)
+}
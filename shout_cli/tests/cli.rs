@@ -0,0 +1,251 @@
+//! `shout` end to end on the generated `test-tiny` model: each test runs the
+//! binary as a user would, in a scratch directory with an empty config.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use serde_json::{Value, json};
+
+use shout_core::model::test_tiny::write_test_tiny;
+
+/// A scratch directory with an empty `shout.toml` and the `test-tiny` model
+/// in `model/`, removed on drop.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("shout_cli_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        write_test_tiny(&dir.join("model")).unwrap();
+        fs::write(dir.join("shout.toml"), "").unwrap();
+        Self(dir)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    fn model(&self) -> PathBuf {
+        self.path("model")
+    }
+
+    fn sample(&self) -> PathBuf {
+        self.model().join("sample.wav")
+    }
+
+    /// Run `shout args...`, with the models directory in the scratch directory.
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_shout"))
+            .args(args)
+            .current_dir(&self.0)
+            .env("SHOUT_CONFIG", self.path("shout.toml"))
+            .env("SHOUT_MODELS_DIR", self.path("models"))
+            .env("SHOUT_DEVICE", "cpu")
+            .env("SHOUT_LANGUAGE", "en")
+            .output()
+            .unwrap()
+    }
+
+    /// Run `shout args...`, failing the test unless it succeeds.
+    fn shout(&self, args: &[&str]) -> Output {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "shout {} failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn str(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[test]
+fn calibrate_fits_on_a_transcript() {
+    let scratch = Scratch::new("calibrate");
+    let (model, sample) = (scratch.model(), scratch.sample());
+
+    // The reference of one entry is what the model transcribes, so its words
+    // are correct; the other entry's words are all wrong.
+    let transcript = scratch.path("sample.json");
+    scratch.shout(&[
+        "transcribe",
+        str(&sample),
+        "--model",
+        str(&model),
+        "--format",
+        "json",
+        "--output",
+        str(&transcript),
+    ]);
+    let transcript: Value =
+        serde_json::from_str(&fs::read_to_string(&transcript).unwrap()).unwrap();
+    let words: Vec<&str> = transcript["segments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|s| s["words"].as_array().unwrap())
+        .map(|w| w["text"].as_str().unwrap())
+        .collect();
+    assert!(!words.is_empty(), "no words in {transcript}");
+
+    let manifest = scratch.path("held_out.jsonl");
+    let lines = [
+        json!({"audio_path": str(&sample), "text": words.join(" ")}),
+        json!({"audio_path": str(&sample), "text": "something else entirely"}),
+    ];
+    let lines: Vec<String> = lines.iter().map(Value::to_string).collect();
+    fs::write(&manifest, lines.join("\n") + "\n").unwrap();
+
+    let output = scratch.shout(&[
+        "calibrate",
+        "--model",
+        str(&model),
+        "--manifest",
+        str(&manifest),
+        "--normalizer",
+        "none",
+        "--save",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Slope"), "{stdout}");
+
+    let saved: Value =
+        serde_json::from_str(&fs::read_to_string(model.join("calibration.json")).unwrap()).unwrap();
+    assert!(
+        saved["slope"].is_f64() && saved["intercept"].is_f64(),
+        "{saved}"
+    );
+}
+
+#[test]
+fn listen_prints_final_segments_as_they_come() {
+    let scratch = Scratch::new("listen");
+    let output = scratch.shout(&[
+        "listen",
+        "--model",
+        str(&scratch.model()),
+        "--input",
+        str(&scratch.sample()),
+        "--step-ms",
+        "500",
+        "--timestamps",
+    ]);
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let starts: Vec<f64> = stdout
+        .lines()
+        .map(|line| {
+            let (times, text) = line.split_once("] ").unwrap_or_else(|| panic!("{line:?}"));
+            assert!(!text.trim().is_empty(), "{line:?}");
+            let (start, end) = times.trim_start_matches('[').split_once(" --> ").unwrap();
+            let (start, end): (f64, f64) = (start.parse().unwrap(), end.parse().unwrap());
+            assert!(start <= end, "{line:?}");
+            start
+        })
+        .collect();
+    assert!(!starts.is_empty(), "nothing was transcribed");
+    assert!(starts.windows(2).all(|p| p[0] <= p[1]), "{stdout}");
+}
+
+#[test]
+fn transcribe_writes_one_file_per_manifest_entry() {
+    let scratch = Scratch::new("manifest");
+
+    // Two recordings with the same file name in different directories.
+    let mut lines = Vec::new();
+    for dir in ["a", "b"] {
+        let audio = scratch.path(dir).join("sample.wav");
+        fs::create_dir_all(scratch.path(dir)).unwrap();
+        fs::copy(scratch.sample(), &audio).unwrap();
+        lines.push(json!({"audio_path": str(&audio)}).to_string());
+    }
+    let manifest = scratch.path("files.jsonl");
+    fs::write(&manifest, lines.join("\n") + "\n").unwrap();
+
+    let out = scratch.path("out");
+    scratch.shout(&[
+        "transcribe",
+        "--manifest",
+        str(&manifest),
+        "--model",
+        str(&scratch.model()),
+        "--jobs",
+        "2",
+        "--batch-size",
+        "2",
+        "--format",
+        "json",
+        "--output",
+        str(&out),
+    ]);
+
+    for (name, dir) in [("sample-1.json", "a"), ("sample-2.json", "b")] {
+        let transcript: Value =
+            serde_json::from_str(&fs::read_to_string(out.join(name)).unwrap()).unwrap();
+        let audio_path = transcript["metadata"]["audio_path"].as_str().unwrap();
+        assert!(
+            Path::new(audio_path).starts_with(scratch.path(dir)),
+            "{transcript}"
+        );
+        assert!(
+            !transcript["segments"].as_array().unwrap().is_empty(),
+            "{transcript}"
+        );
+    }
+}
+
+#[test]
+fn pull_refuses_files_without_a_pinned_checksum() {
+    let scratch = Scratch::new("unpinned");
+    let registry = scratch.path("registry.json");
+    let entry = json!([{
+        "name": "unpinned",
+        "description": "A file without a checksum",
+        "files": [
+            {"name": "config.json", "url": "http://127.0.0.1:9/config.json", "sha256": "00"},
+            {"name": "model.safetensors", "url": "http://127.0.0.1:9/model.safetensors"}
+        ]
+    }]);
+    fs::write(&registry, entry.to_string()).unwrap();
+
+    let output = scratch.run(&["model", "pull", "unpinned", "--registry", str(&registry)]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("pins no sha256 for model.safetensors"),
+        "{stderr}"
+    );
+    assert!(
+        !scratch.path("models/unpinned").exists(),
+        "something was downloaded"
+    );
+}
+
+#[test]
+#[ignore = "registry.json still needs sha256s and fixed revisions, computed with network access"]
+fn builtin_registry_pins_every_file() {
+    let is_hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit());
+    let registry: Value = serde_json::from_str(include_str!("../registry.json")).unwrap();
+    for entry in registry.as_array().unwrap() {
+        for file in entry["files"].as_array().unwrap() {
+            let sha256 = file["sha256"].as_str().unwrap_or_default();
+            assert!(is_hex(sha256, 64), "{file}");
+            // A branch such as `main` can move after the checksum was taken.
+            let url = file["url"].as_str().unwrap();
+            let revision = url.split("/resolve/").nth(1).unwrap_or_default();
+            assert!(is_hex(revision.split('/').next().unwrap(), 40), "{file}");
+        }
+    }
+}
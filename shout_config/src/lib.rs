@@ -0,0 +1,266 @@
+//! Settings shared by the shout binaries, layered from lowest to highest
+//! priority:
+//!
+//! 1. built-in defaults;
+//! 2. `shout.toml`: the file named by `$SHOUT_CONFIG`, else `./shout.toml`, else
+//!    `shout/shout.toml` in the user config directory (`~/.config`);
+//! 3. `SHOUT_*` environment variables, named after the setting;
+//! 4. command-line flags, which the binaries apply on top.
+//!
+//! ```toml
+//! device = "cuda:0"
+//! language = "de"
+//! threads = 8
+//!
+//! [paths]
+//! data_root = "/srv/corpora"
+//! audio_root = "/srv/corpora"
+//! models_dir = "/srv/models"
+//! ```
+//!
+//! Values stay strings here (`device = "cuda:0"`); each binary parses them with
+//! the same parser as its flags, so this crate does not depend on the others.
+
+pub mod paths;
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+pub const CONFIG_FILE: &str = "shout.toml";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShoutConfig {
+    /// Compute device: auto, cpu, cuda[:N] or metal[:N] (`$SHOUT_DEVICE`).
+    pub device: String,
+
+    /// Spoken language code, or `auto` to detect it (`$SHOUT_LANGUAGE`).
+    pub language: String,
+
+    /// CPU threads for inference; all cores unless set (`$SHOUT_THREADS`).
+    pub threads: Option<usize>,
+
+    pub paths: Paths,
+}
+
+impl Default for ShoutConfig {
+    fn default() -> Self {
+        Self {
+            device: "auto".into(),
+            language: "auto".into(),
+            threads: None,
+            paths: Paths::default(),
+        }
+    }
+}
+
+/// Where data, manifests, models and caches live. Unset paths fall back to the
+/// defaults of the accessors on [`ShoutConfig`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Paths {
+    /// Corpora the data tools read (`$SHOUT_DATA_ROOT`).
+    pub data_root: Option<PathBuf>,
+
+    /// What relative audio paths in manifests are relative to; the working
+    /// directory unless set (`$SHOUT_AUDIO_ROOT`). See [`paths`].
+    pub audio_root: Option<PathBuf>,
+
+    /// Manifests the data tools write (`$SHOUT_MANIFESTS_DIR`).
+    pub manifests_dir: Option<PathBuf>,
+
+    /// Pulled models (`$SHOUT_MODELS_DIR`).
+    pub models_dir: Option<PathBuf>,
+
+    /// Everything shout caches (`$SHOUT_CACHE_DIR`).
+    pub cache_dir: Option<PathBuf>,
+
+    /// Transcript cache of `batch` and `serve`; off unless set
+    /// (`$SHOUT_RESULTS_CACHE_DIR`).
+    pub results_cache_dir: Option<PathBuf>,
+}
+
+impl ShoutConfig {
+    /// Defaults, overridden by the config file if there is one, overridden by
+    /// the environment.
+    pub fn load() -> Result<Self> {
+        let mut config = match config_file() {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config: {}", path.display()))?;
+        toml::from_str(&raw).with_context(|| format!("Invalid config: {}", path.display()))
+    }
+
+    /// Override settings with the `SHOUT_*` variables `var` returns.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(v) = var("SHOUT_DEVICE") {
+            self.device = v;
+        }
+        if let Some(v) = var("SHOUT_LANGUAGE") {
+            self.language = v;
+        }
+        if let Some(v) = var("SHOUT_THREADS") {
+            self.threads = Some(v.parse().with_context(|| format!("SHOUT_THREADS={v}"))?);
+        }
+
+        let paths = &mut self.paths;
+        for (name, path) in [
+            ("SHOUT_DATA_ROOT", &mut paths.data_root),
+            ("SHOUT_AUDIO_ROOT", &mut paths.audio_root),
+            ("SHOUT_MANIFESTS_DIR", &mut paths.manifests_dir),
+            ("SHOUT_MODELS_DIR", &mut paths.models_dir),
+            ("SHOUT_CACHE_DIR", &mut paths.cache_dir),
+            ("SHOUT_RESULTS_CACHE_DIR", &mut paths.results_cache_dir),
+        ] {
+            if let Some(v) = var(name).filter(|v| !v.is_empty()) {
+                *path = Some(PathBuf::from(v));
+            }
+        }
+        Ok(())
+    }
+
+    /// `data` in the working directory unless set.
+    pub fn data_root(&self) -> PathBuf {
+        self.paths
+            .data_root
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("data"))
+    }
+
+    /// `manifests` in the working directory unless set.
+    pub fn manifests_dir(&self) -> PathBuf {
+        self.paths
+            .manifests_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("manifests"))
+    }
+
+    /// The user cache directory's `shout` (`~/.cache/shout`) unless set.
+    pub fn cache_dir(&self) -> PathBuf {
+        let default = || user_dir(&["XDG_CACHE_HOME", "LOCALAPPDATA"], ".cache").join("shout");
+        self.paths.cache_dir.clone().unwrap_or_else(default)
+    }
+
+    /// The file a manifest's `audio_path` refers to.
+    pub fn audio_path(&self, stored: &str) -> PathBuf {
+        paths::from_manifest(stored, self.paths.audio_root.as_deref())
+    }
+
+    /// `path` as a manifest's `audio_path`.
+    pub fn manifest_path(&self, path: &Path) -> Result<String> {
+        paths::to_manifest(path, self.paths.audio_root.as_deref())
+    }
+
+    /// `models` in [`Self::cache_dir`] unless set.
+    pub fn models_dir(&self) -> PathBuf {
+        self.paths
+            .models_dir
+            .clone()
+            .unwrap_or_else(|| self.cache_dir().join("models"))
+    }
+}
+
+static CONFIG: OnceLock<ShoutConfig> = OnceLock::new();
+
+/// Load the configuration for the rest of the process. Binaries call this
+/// first, so that a broken config file is an error rather than a warning.
+pub fn init() -> Result<&'static ShoutConfig> {
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+    let config = ShoutConfig::load()?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// The process-wide configuration, loaded on first use.
+pub fn get() -> &'static ShoutConfig {
+    CONFIG.get_or_init(|| {
+        ShoutConfig::load().unwrap_or_else(|e| {
+            tracing::warn!("{e:#}; using the default settings");
+            ShoutConfig::default()
+        })
+    })
+}
+
+/// `$SHOUT_CONFIG`, else the first `shout.toml` that exists.
+fn config_file() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("SHOUT_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    [
+        PathBuf::from(CONFIG_FILE),
+        user_dir(&["XDG_CONFIG_HOME", "APPDATA"], ".config")
+            .join("shout")
+            .join(CONFIG_FILE),
+    ]
+    .into_iter()
+    .find(|p| p.is_file())
+}
+
+/// The first of the `vars` that is set, else `home_subdir` in `$HOME`.
+fn user_dir(vars: &[&str], home_subdir: &str) -> PathBuf {
+    vars.iter()
+        .find_map(std::env::var_os)
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(home_subdir)))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment_overrides_file() {
+        let mut config: ShoutConfig = toml::from_str(
+            r#"
+            device = "cuda:1"
+            language = "de"
+
+            [paths]
+            data_root = "/srv/corpora"
+            models_dir = "/srv/models"
+            "#,
+        )
+        .unwrap();
+        config
+            .apply_env(|name| match name {
+                "SHOUT_DEVICE" => Some("cpu".into()),
+                "SHOUT_THREADS" => Some("4".into()),
+                "SHOUT_DATA_ROOT" => Some("/data".into()),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(config.device, "cpu");
+        assert_eq!(config.language, "de");
+        assert_eq!(config.threads, Some(4));
+        assert_eq!(config.data_root(), PathBuf::from("/data"));
+        assert_eq!(config.models_dir(), PathBuf::from("/srv/models"));
+    }
+
+    #[test]
+    fn models_live_in_the_cache_dir_by_default() {
+        let mut config = ShoutConfig::default();
+        config.paths.cache_dir = Some(PathBuf::from("/tmp/shout"));
+        assert_eq!(config.models_dir(), PathBuf::from("/tmp/shout/models"));
+        assert_eq!(config.manifests_dir(), PathBuf::from("manifests"));
+    }
+
+    #[test]
+    fn rejects_unknown_settings_and_bad_numbers() {
+        assert!(toml::from_str::<ShoutConfig>("devcie = \"cpu\"").is_err());
+        let mut config = ShoutConfig::default();
+        assert!(config.apply_env(|_| Some("many".into())).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+//! Audio paths as stored in manifests, portable between Windows and Unix.
+//!
+//! Manifests hold paths with forward slashes, relative to the audio root
+//! (`paths.audio_root`) when the file lies under it. Reading accepts either
+//! separator, so a manifest written on Windows works on Linux as long as its
+//! paths are relative. A backslash inside a Unix file name does not survive
+//! this; such names are rare enough in corpora not to be worth the ambiguity.
+
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// `path` as a manifest should store it: relative to `root` if it lies under
+/// it, with `/` separators. Fails for paths that are not valid UTF-8, which a
+/// JSON string could not hold without losing bytes.
+pub fn to_manifest(path: &Path, root: Option<&Path>) -> Result<String> {
+    let relative = root
+        .and_then(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path);
+    let stored = relative
+        .to_str()
+        .with_context(|| format!("Path is not valid UTF-8: {}", path.display()))?;
+    Ok(stored.replace(MAIN_SEPARATOR, "/"))
+}
+
+/// The file a manifest's `stored` path refers to: separators turned into this
+/// platform's, and relative paths resolved against `root` (the working
+/// directory without one). Windows drive and UNC paths on other platforms
+/// are left absolute-looking rather than joined to `root`; they cannot be
+/// opened either way.
+pub fn from_manifest(stored: &str, root: Option<&Path>) -> PathBuf {
+    let stored = stored.trim();
+    let native: String = stored
+        .chars()
+        .map(|c| {
+            if c == '/' || c == '\\' {
+                MAIN_SEPARATOR
+            } else {
+                c
+            }
+        })
+        .collect();
+    let path = PathBuf::from(native);
+    match root {
+        Some(root) if !path.is_absolute() && !is_windows_absolute(stored) => root.join(path),
+        _ => path,
+    }
+}
+
+/// `C:\...`, `C:/...` or `\\server\share`.
+fn is_windows_absolute(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let drive = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes[2], b'/' | b'\\');
+    drive || path.starts_with(r"\\")
+}
+
+/// `line` without the byte order mark some Windows editors put at the start
+/// of a UTF-8 file.
+pub fn strip_bom(line: &str) -> &str {
+    line.strip_prefix('\u{feff}').unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_and_unix_relative_paths_resolve_alike() {
+        let root = Path::new("corpus");
+        let expected = root.join("clips").join("a b.wav");
+        assert_eq!(from_manifest(r"clips\a b.wav", Some(root)), expected);
+        assert_eq!(from_manifest("clips/a b.wav", Some(root)), expected);
+        assert_eq!(
+            from_manifest("clips/a b.wav", None),
+            Path::new("clips").join("a b.wav")
+        );
+    }
+
+    #[test]
+    fn absolute_paths_ignore_the_root() {
+        let root = Path::new("corpus");
+        assert!(!from_manifest(r"D:\data\x.wav", Some(root)).starts_with(root));
+        assert!(!from_manifest(r"\\nas\share\x.wav", Some(root)).starts_with(root));
+    }
+
+    #[test]
+    fn stored_paths_are_relative_with_forward_slashes() {
+        let root = Path::new("corpus");
+        let path = root.join("clips").join("ü.wav");
+        assert_eq!(to_manifest(&path, Some(root)).unwrap(), "clips/ü.wav");
+        assert_eq!(
+            from_manifest(&to_manifest(&path, Some(root)).unwrap(), Some(root)),
+            path
+        );
+        assert_eq!(to_manifest(&path, None).unwrap(), "corpus/clips/ü.wav");
+        assert_eq!(strip_bom("\u{feff}{}"), "{}");
+    }
+}
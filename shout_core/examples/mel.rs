@@ -0,0 +1,20 @@
+//! Print the shape of the mel spectrogram of an audio file.
+//!
+//! `cargo run -p shout_core --example mel -- <audio file>`
+
+use anyhow::{Context, Result};
+
+use shout_core::audio::decoder::decode_to_f32_mono_16k;
+use shout_core::features::log_mel;
+
+fn main() -> Result<()> {
+    let path = std::env::args().nth(1).context("usage: mel <audio file>")?;
+
+    let pcm = decode_to_f32_mono_16k(&path)?;
+
+    let mel = log_mel(&pcm, 80);
+
+    let (frames, mels) = mel.dim();
+    println!("mel: frames={frames}, mels={mels}, total={}", mel.len());
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use ndarray::{Array2, ArrayView2};
+
+/// Dynamic time warping over a `(tokens, frames)` cost matrix.
+///
+/// Returns the monotonic path from `(0, 0)` to `(tokens - 1, frames - 1)`
+/// as `(token_index, frame_index)` pairs, same as Whisper's `dtw_cpu`.
+pub fn dtw(cost: ArrayView2<f32>) -> Vec<(usize, usize)> {
+    let (n, m) = cost.dim();
+    if n == 0 || m == 0 {
+        return Vec::new();
+    }
+
+    // acc[i][j] = best cost to reach (i - 1, j - 1); row/col 0 are the border.
+    let mut acc = Array2::<f32>::from_elem((n + 1, m + 1), f32::INFINITY);
+    // 0 = diagonal, 1 = from above (next token, same frame), 2 = from left (same token, next frame)
+    let mut trace = Array2::<u8>::zeros((n + 1, m + 1));
+    acc[[0, 0]] = 0.0;
+
+    for j in 1..=m {
+        for i in 1..=n {
+            let c0 = acc[[i - 1, j - 1]];
+            let c1 = acc[[i - 1, j]];
+            let c2 = acc[[i, j - 1]];
+
+            let (best, t) = if c0 < c1 && c0 < c2 {
+                (c0, 0)
+            } else if c1 < c0 && c1 < c2 {
+                (c1, 1)
+            } else {
+                (c2, 2)
+            };
+
+            acc[[i, j]] = cost[[i - 1, j - 1]] + best;
+            trace[[i, j]] = t;
+        }
+    }
+
+    // -------------------------
+    // Backtrace
+    // -------------------------
+    let mut i = n;
+    let mut j = m;
+    let mut path = Vec::with_capacity(n + m);
+
+    while i > 0 && j > 0 {
+        path.push((i - 1, j - 1));
+        match trace[[i, j]] {
+            0 => {
+                i -= 1;
+                j -= 1;
+            }
+            1 => i -= 1,
+            _ => j -= 1,
+        }
+    }
+
+    path.reverse();
+    path
+}
+
+/// Median filter of odd `width` along the frame axis (axis 1), reflect-padded.
+pub fn median_filter(x: &Array2<f32>, width: usize) -> Array2<f32> {
+    let (rows, cols) = x.dim();
+    if width <= 1 || cols == 0 {
+        return x.clone();
+    }
+
+    let half = width / 2;
+    let mut out = Array2::<f32>::zeros((rows, cols));
+    let mut window = Vec::with_capacity(width);
+
+    for r in 0..rows {
+        for c in 0..cols {
+            window.clear();
+            for k in 0..width {
+                let idx = c as isize + k as isize - half as isize;
+                window.push(x[[r, reflect(idx, cols)]]);
+            }
+            window.sort_by(|a, b| a.total_cmp(b));
+            out[[r, c]] = window[half];
+        }
+    }
+
+    out
+}
+
+fn reflect(idx: isize, len: usize) -> usize {
+    if len == 1 {
+        return 0;
+    }
+    let period = 2 * (len as isize - 1);
+    let mut i = idx.rem_euclid(period);
+    if i >= len as isize {
+        i = period - i;
+    }
+    i as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn dtw_follows_cheap_diagonal() {
+        let cost = array![[0.0, 1.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 0.0]];
+        assert_eq!(dtw(cost.view()), vec![(0, 0), (1, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn dtw_stretches_tokens_over_frames() {
+        let cost = array![[0.0, 0.0, 1.0, 1.0], [1.0, 1.0, 0.0, 0.0]];
+        assert_eq!(dtw(cost.view()), vec![(0, 0), (0, 1), (1, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn median_filter_removes_spikes() {
+        let x = array![[0.0, 0.0, 9.0, 0.0, 0.0]];
+        let y = median_filter(&x, 3);
+        assert_eq!(y, array![[0.0, 0.0, 0.0, 0.0, 0.0]]);
+    }
+}
@@ -0,0 +1,174 @@
+//! Word-level timing for decoded segments.
+//!
+//! Uses the same approach as Whisper's `find_alignment`: the decoder's
+//! cross-attention onto the encoder frames is normalized, smoothed with a
+//! median filter and aligned against the text tokens with DTW. The frame at
+//! which each token first appears on the path becomes its start time.
+
+pub mod dtw;
+
+use std::ops::Range;
+
+use ndarray::{Array2, Axis};
+
+use crate::transcript::{Segment, Word};
+
+#[derive(Debug, Clone)]
+pub struct AlignmentOptions {
+    /// Duration of one encoder frame (Whisper: 20 ms, i.e. 50 frames per second).
+    pub frame_ms: u32,
+
+    /// Width of the median filter applied along the time axis. Must be odd.
+    pub median_filter_width: usize,
+}
+
+impl Default for AlignmentOptions {
+    fn default() -> Self {
+        Self {
+            frame_ms: 20,
+            median_filter_width: 7,
+        }
+    }
+}
+
+/// Group decoded token strings into words.
+///
+/// A token starting with whitespace begins a new word; tokens consisting only of
+/// punctuation are attached to the preceding word.
+pub fn group_words(tokens: &[String]) -> Vec<(String, Range<usize>)> {
+    let mut words: Vec<(String, Range<usize>)> = Vec::new();
+
+    for (i, tok) in tokens.iter().enumerate() {
+        let starts_word = tok.starts_with(char::is_whitespace);
+        let is_punct =
+            !tok.trim().is_empty() && tok.trim().chars().all(|c| c.is_ascii_punctuation());
+
+        match words.last_mut() {
+            Some((text, range)) if !starts_word || is_punct => {
+                text.push_str(tok.trim_start());
+                range.end = i + 1;
+            }
+            _ => {
+                if tok.trim().is_empty() {
+                    continue;
+                }
+                words.push((tok.trim_start().to_string(), i..i + 1));
+            }
+        }
+    }
+
+    words
+}
+
+/// Align `tokens` to audio using their cross-attention weights.
+///
+/// `attention` has shape `(tokens, frames)`: the attention of each text token onto
+/// the encoder frames, already averaged over the alignment heads. `offset_ms` is the
+/// start of the window in the source audio and is added to every returned time.
+pub fn align_words(
+    tokens: &[String],
+    attention: &Array2<f32>,
+    offset_ms: u64,
+    opts: &AlignmentOptions,
+) -> Vec<Word> {
+    let (n_tokens, n_frames) = attention.dim();
+    if tokens.is_empty() || n_frames == 0 || n_tokens != tokens.len() {
+        return Vec::new();
+    }
+
+    // -------------------------
+    // 1) Normalize each frame across tokens, then smooth along time
+    // -------------------------
+    let mut weights = attention.clone();
+    let mean = weights.mean_axis(Axis(0)).expect("non-empty token axis");
+    let std = weights.std_axis(Axis(0), 0.0);
+    for mut row in weights.rows_mut() {
+        for ((w, m), s) in row.iter_mut().zip(mean.iter()).zip(std.iter()) {
+            *w = (*w - m) / s.max(1e-8);
+        }
+    }
+    let weights = dtw::median_filter(&weights, opts.median_filter_width);
+
+    // -------------------------
+    // 2) DTW over negative attention (high attention = low cost)
+    // -------------------------
+    let cost = weights.mapv(|w| -w);
+    let path = dtw::dtw(cost.view());
+
+    // First frame of each token on the path, plus the end of the last token.
+    let mut boundaries = vec![0usize; n_tokens + 1];
+    let mut last_token = None;
+    for &(t, f) in &path {
+        if last_token != Some(t) {
+            boundaries[t] = f;
+            last_token = Some(t);
+        }
+    }
+    boundaries[n_tokens] = path.last().map(|&(_, f)| f + 1).unwrap_or(n_frames);
+
+    // -------------------------
+    // 3) Token boundaries -> word timings
+    // -------------------------
+    let frame_ms = opts.frame_ms as u64;
+    group_words(tokens)
+        .into_iter()
+        .map(|(text, range)| Word {
+            text,
+            start_ms: offset_ms + boundaries[range.start] as u64 * frame_ms,
+            end_ms: offset_ms + boundaries[range.end] as u64 * frame_ms,
+            confidence: None,
+        })
+        .collect()
+}
+
+/// Fill `segment.words` from the segment's tokens and their cross-attention.
+///
+/// Word times are clamped to the segment's own start/end.
+pub fn align_segment(
+    segment: &mut Segment,
+    tokens: &[String],
+    attention: &Array2<f32>,
+    opts: &AlignmentOptions,
+) {
+    let mut words = align_words(tokens, attention, segment.start_ms, opts);
+    for w in &mut words {
+        w.start_ms = w.start_ms.clamp(segment.start_ms, segment.end_ms);
+        w.end_ms = w.end_ms.clamp(w.start_ms, segment.end_ms);
+    }
+    segment.words = words;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toks(t: &[&str]) -> Vec<String> {
+        t.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn groups_subword_tokens_and_punctuation() {
+        let words = group_words(&toks(&[" Hal", "lo", " Welt", "!"]));
+        assert_eq!(
+            words,
+            vec![("Hallo".to_string(), 0..2), ("Welt!".to_string(), 2..4)]
+        );
+    }
+
+    #[test]
+    fn aligns_words_to_attention_peaks() {
+        // Token 0 attends to frames 0..2, token 1 to frames 2..4.
+        let attention = ndarray::array![[1.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 1.0]];
+        let opts = AlignmentOptions {
+            frame_ms: 20,
+            median_filter_width: 1,
+        };
+
+        let words = align_words(&toks(&[" ja", " nein"]), &attention, 1000, &opts);
+
+        assert_eq!(words.len(), 2);
+        assert_eq!((words[0].start_ms, words[0].end_ms), (1000, 1040));
+        assert_eq!((words[1].start_ms, words[1].end_ms), (1040, 1080));
+        assert_eq!(words[1].duration_ms(), 40);
+    }
+}
@@ -0,0 +1,119 @@
+//! Live audio capture from the default input device.
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Sample, SampleFormat};
+
+use super::resample::StreamResampler;
+use crate::errors::{Result, ShoutError};
+
+/// Sample rate of the chunks returned by [`MicrophoneStream::next_chunk`].
+pub const CAPTURE_SAMPLE_RATE: u32 = 16_000;
+
+/// Microphone input converted to 16 kHz mono f32.
+///
+/// The cpal callback only copies interleaved samples into a channel; downmixing and
+/// resampling happen on the caller's thread in [`next_chunk`](Self::next_chunk).
+pub struct MicrophoneStream {
+    // Kept alive for the duration of the capture; dropping it stops the device.
+    _stream: cpal::Stream,
+    rx: Receiver<Vec<f32>>,
+    channels: usize,
+    resampler: StreamResampler,
+    device_name: String,
+}
+
+impl MicrophoneStream {
+    /// Open the host's default input device with its default configuration.
+    pub fn open_default() -> Result<Self> {
+        let host = cpal::default_host();
+        let device = host
+            .default_input_device()
+            .ok_or_else(|| ShoutError::Device("no default audio input device".into()))?;
+        let device_name = device.name().unwrap_or_else(|_| "<unknown>".to_string());
+
+        let supported = device.default_input_config().map_err(|e| {
+            ShoutError::Device(format!("failed to query default input config: {e}"))
+        })?;
+        let sample_format = supported.sample_format();
+        let config: cpal::StreamConfig = supported.into();
+
+        let channels = config.channels as usize;
+        let sr_in = config.sample_rate.0;
+
+        let (tx, rx) = mpsc::channel::<Vec<f32>>();
+        let err_fn = |e| tracing::error!("audio input stream error: {e}");
+
+        let stream = match sample_format {
+            SampleFormat::F32 => device.build_input_stream(
+                &config,
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    let _ = tx.send(data.to_vec());
+                },
+                err_fn,
+                None,
+            ),
+            SampleFormat::I16 => device.build_input_stream(
+                &config,
+                move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                    let _ = tx.send(data.iter().map(|s| s.to_sample::<f32>()).collect());
+                },
+                err_fn,
+                None,
+            ),
+            SampleFormat::U16 => device.build_input_stream(
+                &config,
+                move |data: &[u16], _: &cpal::InputCallbackInfo| {
+                    let _ = tx.send(data.iter().map(|s| s.to_sample::<f32>()).collect());
+                },
+                err_fn,
+                None,
+            ),
+            other => {
+                let msg = format!("unsupported input sample format: {other:?}");
+                return Err(ShoutError::UnsupportedFormat(msg));
+            }
+        }
+        .map_err(|e| ShoutError::Device(format!("failed to build input stream: {e}")))?;
+
+        stream
+            .play()
+            .map_err(|e| ShoutError::Device(format!("failed to start input stream: {e}")))?;
+
+        Ok(Self {
+            _stream: stream,
+            rx,
+            channels,
+            resampler: StreamResampler::new(sr_in, CAPTURE_SAMPLE_RATE)?,
+            device_name,
+        })
+    }
+
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+
+    /// Block until the device delivers more audio (or `timeout` passes) and return it
+    /// as 16 kHz mono. Returns an empty chunk on timeout or while the resampler is
+    /// still filling up; `None` once the device has stopped.
+    pub fn next_chunk(&mut self, timeout: Duration) -> Result<Option<Vec<f32>>> {
+        let interleaved = match self.rx.recv_timeout(timeout) {
+            Ok(d) => d,
+            Err(RecvTimeoutError::Timeout) => return Ok(Some(Vec::new())),
+            Err(RecvTimeoutError::Disconnected) => return Ok(None),
+        };
+
+        let mono: Vec<f32> = if self.channels == 1 {
+            interleaved
+        } else {
+            interleaved
+                .chunks_exact(self.channels)
+                .map(|frame| frame.iter().sum::<f32>() / self.channels as f32)
+                .collect()
+        };
+
+        self.resampler.push(&mono).map(Some)
+    }
+}
@@ -1,176 +1,802 @@
-use anyhow::{anyhow, Context, Result};
 use std::path::Path;
 
 use symphonia::core::{
-    audio::{AudioBufferRef, SampleBuffer},
-    codecs::{DecoderOptions, CODEC_TYPE_NULL},
+    audio::SampleBuffer,
+    codecs::{CODEC_TYPE_NULL, CodecParameters, Decoder, DecoderOptions},
     errors::Error as SymphoniaError,
-    formats::FormatOptions,
+    formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
     io::MediaSourceStream,
     meta::MetadataOptions,
     probe::Hint,
+    units::{Time, TimeBase},
 };
 
-use rubato::{Fft, FixedSync, Resampler};
 use audioadapter_buffers::direct::InterleavedSlice;
 
+use super::normalize::{Normalization, remove_dc_offset};
+use super::resample::{
+    DEFAULT_RESAMPLER_CHUNK, ResamplerKind, StreamResampler, new_resampler, resample_error,
+};
+use crate::cancel::{CancelToken, Stage};
+use crate::errors::{IoContext, Result, ShoutError};
+use crate::features::SAMPLE_RATE;
+
+/// Output sample rate, resampling and level of a decode. The default suits
+/// the models: 16 kHz with the FFT resampler, at the file's own level.
+///
+/// The level options need the whole clip, so [`DecodeStream`] ignores them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeOptions {
+    /// Sample rate of the decoded audio in Hz, e.g. 8000 for telephony models.
+    pub target_sr: u32,
+    pub resampler: ResamplerKind,
+    /// Input frames the resampler takes at a time.
+    pub chunk_size: usize,
+    /// Subtract each channel's mean, for recordings with a DC bias.
+    pub remove_dc: bool,
+    /// Level to scale each channel to, after DC removal.
+    pub normalize: Option<Normalization>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            target_sr: SAMPLE_RATE,
+            resampler: ResamplerKind::Fft,
+            chunk_size: DEFAULT_RESAMPLER_CHUNK,
+            remove_dc: false,
+            normalize: None,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// Remove DC and normalize decoded `samples` as configured.
+    fn adjust_level(&self, samples: &mut [f32]) {
+        if self.remove_dc {
+            remove_dc_offset(samples);
+        }
+        if let Some(normalize) = self.normalize {
+            normalize.apply(samples, self.target_sr);
+        }
+    }
+}
+
 /// Decode an audio file to mono f32 samples at 16 kHz.
 ///
 /// Returns: Vec<f32> where each element is one mono sample at 16_000 Hz.
 pub fn decode_to_f32_mono_16k<P: AsRef<Path>>(path: P) -> Result<Vec<f32>> {
+    decode_cancellable(path, &CancelToken::default())
+}
+
+/// [`decode_to_f32_mono_16k`] that checks `cancel` between packets, so a huge
+/// or slow file can be abandoned.
+pub fn decode_cancellable<P: AsRef<Path>>(path: P, cancel: &CancelToken) -> Result<Vec<f32>> {
+    let mut channels = decode_channels(path, ChannelMode::DownmixMono, cancel)?;
+    Ok(channels.swap_remove(0))
+}
+
+/// Which channels of a multichannel file to decode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChannelMode {
+    /// Average all channels into one.
+    #[default]
+    DownmixMono,
+
+    /// Only channel `n` (0-based), e.g. one side of a telephone call.
+    SelectChannel(usize),
+
+    /// Every channel separately.
+    KeepAll,
+}
+
+impl ChannelMode {
+    /// Split a packet's interleaved `channels`-channel samples as asked.
+    fn split(self, interleaved: Vec<f32>, channels: usize) -> Result<Vec<Vec<f32>>> {
+        match self {
+            ChannelMode::DownmixMono => Ok(vec![downmix(interleaved, channels)]),
+            ChannelMode::SelectChannel(n) if n >= channels => Err(ShoutError::InvalidArgument(
+                format!("channel {n} requested, but the audio has {channels} channels"),
+            )),
+            ChannelMode::SelectChannel(n) => Ok(vec![
+                interleaved
+                    .iter()
+                    .skip(n)
+                    .step_by(channels)
+                    .copied()
+                    .collect(),
+            ]),
+            ChannelMode::KeepAll => Ok((0..channels)
+                .map(|c| {
+                    interleaved
+                        .iter()
+                        .skip(c)
+                        .step_by(channels)
+                        .copied()
+                        .collect()
+                })
+                .collect()),
+        }
+    }
+}
+
+/// Decode an audio file to f32 samples at 16 kHz, one `Vec` per channel that
+/// `mode` keeps (a single one unless it is [`ChannelMode::KeepAll`]). Checks
+/// `cancel` between packets.
+pub fn decode_channels<P: AsRef<Path>>(
+    path: P,
+    mode: ChannelMode,
+    cancel: &CancelToken,
+) -> Result<Vec<Vec<f32>>> {
+    decode_with(path, mode, &DecodeOptions::default(), cancel)
+}
+
+/// [`decode_channels`] at the sample rate and with the resampler of
+/// `options`.
+#[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display()))]
+pub fn decode_with<P: AsRef<Path>>(
+    path: P,
+    mode: ChannelMode,
+    options: &DecodeOptions,
+    cancel: &CancelToken,
+) -> Result<Vec<Vec<f32>>> {
     let path = path.as_ref();
 
     // -------------------------
     // 1) Decode with Symphonia
     // -------------------------
-    let file = std::fs::File::open(path)
-        .with_context(|| format!("failed to open audio file: {}", path.display()))?;
+    let mut track = open_track(path)?;
 
-    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    // -------------------------
+    // 2) Split channels, packet by packet
+    // -------------------------
+    // Chained streams may change sample rate or channel count from one link
+    // to the next, so runs of equal rate and channel count are kept apart.
+    let mut runs: Vec<(u32, Vec<Vec<f32>>)> = Vec::new();
 
-    // Hint from extension (optional but helps).
-    let mut hint = Hint::new();
-    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-        hint.with_extension(ext);
+    loop {
+        cancel.check(Stage::Decode)?;
+        let Some(packet) = track.next_packet()? else {
+            break;
+        };
+
+        let split = mode.split(packet.samples, packet.channels)?;
+        match runs.last_mut() {
+            Some((sr, channels)) if *sr == packet.sample_rate && channels.len() == split.len() => {
+                for (channel, samples) in channels.iter_mut().zip(split) {
+                    channel.extend(samples);
+                }
+            }
+            _ => runs.push((packet.sample_rate, split)),
+        }
     }
 
-    let probed = symphonia::default::get_probe()
-        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
-        .context("unsupported format or failed to probe container")?;
+    if runs
+        .iter()
+        .all(|(_, channels)| channels.iter().all(Vec::is_empty))
+    {
+        return Err(ShoutError::Decode("decoded audio was empty".into()));
+    }
+    let n_channels = runs[0].1.len();
+    if runs
+        .iter()
+        .any(|(_, channels)| channels.len() != n_channels)
+    {
+        return Err(ShoutError::Decode(
+            "the channel count changes within the file".into(),
+        ));
+    }
 
-    let mut format = probed.format;
+    // -------------------------
+    // 3) Resample to the target rate (if needed) using rubato v1.0.0
+    // -------------------------
+    let mut out = vec![Vec::new(); n_channels];
+    for (sr_in, channels) in runs {
+        for (channel, samples) in out.iter_mut().zip(channels) {
+            cancel.check(Stage::Decode)?;
+            channel.extend(resample_clip(samples, sr_in, options)?);
+        }
+    }
+    for channel in &mut out {
+        options.adjust_level(channel);
+    }
+    Ok(out)
+}
 
-    let track = format
-        .tracks()
-        .iter()
-        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-        .ok_or_else(|| anyhow!("no supported audio tracks found"))?;
+/// Resample a whole mono clip from `sr_in` to the rate of `options`.
+fn resample_clip(mono: Vec<f32>, sr_in: u32, options: &DecodeOptions) -> Result<Vec<f32>> {
+    if sr_in == options.target_sr || mono.is_empty() {
+        return Ok(mono);
+    }
 
-    let track_id = track.id;
+    // rubato's `process_all_into_buffer` is perfect for full clips.
+    let mut resampler = new_resampler(
+        options.resampler,
+        sr_in,
+        options.target_sr,
+        options.chunk_size,
+    )?;
+
+    let input_len_frames = mono.len(); // mono => 1 sample per frame
 
-    let mut decoder = symphonia::default::get_codecs()
-        .make(&track.codec_params, &DecoderOptions::default())
-        .context("failed to create decoder for selected track")?;
+    // Determine minimal output size (frames) needed. :contentReference[oaicite:3]{index=3}
+    let out_len_frames = resampler.process_all_needed_output_len(input_len_frames);
 
-    // We'll accumulate decoded interleaved f32 here.
-    let mut interleaved_f32: Vec<f32> = Vec::new();
+    let mut out = vec![0.0f32; out_len_frames];
 
-    // Determine input sample rate. Prefer codec params, but fall back to decoded buffer spec later.
-    let mut input_sample_rate: Option<u32> = track.codec_params.sample_rate;
+    // Adapters: (interleaved) with 1 channel => same as plain slice
+    let input_adapter = InterleavedSlice::new(&mono, 1, input_len_frames)
+        .map_err(resample_error("bad input adapter"))?;
 
-    // We also need to know channel count for downmixing.
-    let mut input_channels: Option<usize> = None;
+    let mut output_adapter = InterleavedSlice::new_mut(&mut out, 1, out_len_frames)
+        .map_err(resample_error("bad output adapter"))?;
 
-    loop {
-        let packet = match format.next_packet() {
-            Ok(p) => p,
-            Err(SymphoniaError::ResetRequired) => {
-                return Err(anyhow!(
-                    "decoder reset required (chained streams). handle by recreating decoder."
-                ));
-            }
-            Err(SymphoniaError::IoError(_)) => break, // end of file
-            Err(e) => return Err(e).context("error reading next packet"),
-        };
+    // Resample whole clip into preallocated buffer. :contentReference[oaicite:4]{index=4}
+    let (_frames_read, frames_written) = resampler
+        .process_all_into_buffer(&input_adapter, &mut output_adapter, input_len_frames, None)
+        .map_err(resample_error("resampling failed"))?;
+
+    out.truncate(frames_written);
+    Ok(out)
+}
+
+/// Decode the range `start_ms..end_ms` of an audio file to mono f32 samples
+/// at 16 kHz.
+pub fn decode_segment_to_f32_mono_16k<P: AsRef<Path>>(
+    path: P,
+    start_ms: u64,
+    end_ms: u64,
+) -> Result<Vec<f32>> {
+    decode_segment(path, start_ms, end_ms, &DecodeOptions::default())
+}
+
+/// Decode the range `start_ms..end_ms` of an audio file to mono f32 samples
+/// at the sample rate of `options`.
+///
+/// Seeks to the start instead of decoding everything before it, so a short
+/// window out of a multi-hour recording is cheap; formats that cannot seek
+/// are decoded from the beginning. The range is cut at the file's own sample
+/// rate using packet timestamps, so the result starts at the requested
+/// sample. A range reaching past the end of the file is cut short.
+pub fn decode_segment<P: AsRef<Path>>(
+    path: P,
+    start_ms: u64,
+    end_ms: u64,
+    options: &DecodeOptions,
+) -> Result<Vec<f32>> {
+    let path = path.as_ref();
+    if end_ms <= start_ms {
+        return Err(ShoutError::InvalidArgument(format!(
+            "segment end ({end_ms} ms) must be after its start ({start_ms} ms)"
+        )));
+    }
+    let mut track = open_track(path)?;
+
+    let start = Time::new(start_ms / 1000, (start_ms % 1000) as f64 / 1000.0);
+    let seek = track.format.seek(
+        SeekMode::Accurate,
+        SeekTo::Time {
+            time: start,
+            track_id: Some(track.track_id),
+        },
+    );
+    match seek {
+        Ok(_) => track.decoder.reset(),
+        Err(e) => {
+            tracing::debug!(
+                "cannot seek in {}, decoding from the start: {e}",
+                path.display()
+            );
+        }
+    }
 
-        if packet.track_id() != track_id {
+    // Frame positions at the file's sample rate, known from the first packet.
+    let mut mono = Vec::new();
+    let mut sample_rate = None;
+    let mut range = 0..0u64;
+    let mut position = None;
+    while let Some(packet) = track.next_packet()? {
+        let sr = *sample_rate.get_or_insert_with(|| {
+            let sr = track.sample_rate.unwrap_or(packet.sample_rate) as u64;
+            range = start_ms * sr / 1000..end_ms * sr / 1000;
+            sr
+        });
+        let first = *position.get_or_insert_with(|| track.frame_of(packet.ts, sr));
+        let frames = (packet.samples.len() / packet.channels.max(1)) as u64;
+        position = Some(first + frames);
+        if first + frames <= range.start {
             continue;
         }
 
-        let decoded = match decoder.decode(&packet) {
-            Ok(d) => d,
-            Err(SymphoniaError::IoError(_)) => continue,
-            Err(SymphoniaError::DecodeError(_)) => continue,
-            Err(SymphoniaError::ResetRequired) => {
-                return Err(anyhow!(
-                    "decoder reset required mid-stream. handle by recreating decoder."
-                ));
-            }
-            Err(e) => return Err(e).context("unrecoverable decode error"),
-        };
+        let packet_mono = downmix(packet.samples, packet.channels);
+        let from = range.start.saturating_sub(first) as usize;
+        let to = range.end.saturating_sub(first).min(frames) as usize;
+        mono.extend_from_slice(&packet_mono[from..to]);
+        if first + frames >= range.end {
+            break;
+        }
+    }
 
-        // Update fallback info from decoded spec.
-        input_sample_rate.get_or_insert(decoded.spec().rate);
-        input_channels.get_or_insert(decoded.spec().channels.count());
+    let Some(sr) = sample_rate else {
+        return Err(ShoutError::Decode("decoded audio was empty".into()));
+    };
+    if mono.is_empty() {
+        return Err(ShoutError::Decode(format!(
+            "{}: the segment starts after the end of the file",
+            path.display()
+        )));
+    }
+    let mut resampler = stream_resampler(sr as u32, options)?;
+    let mut out = resampler.push(&mono)?;
+    out.extend(resampler.finish()?);
+    out.truncate(((end_ms - start_ms) * options.target_sr as u64 / 1000) as usize);
+    options.adjust_level(&mut out);
+    Ok(out)
+}
 
-        // Convert decoded buffer to interleaved f32
-        let mut sbuf = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
-        sbuf.copy_interleaved_ref(decoded);
+/// Samples per chunk of [`decode_stream`] that suit the mel front end: one
+/// second at 16 kHz, a whole number of 10 ms hops.
+pub const DEFAULT_CHUNK_SIZE: usize = 16_000;
 
-        interleaved_f32.extend_from_slice(sbuf.samples());
+/// Decode an audio file incrementally, yielding mono 16 kHz samples in chunks
+/// of `chunk_size` (the last one may be shorter); see
+/// [`DecodeStream::with_options`] for other rates.
+///
+/// Unlike [`decode_to_f32_mono_16k`], memory use does not grow with the length
+/// of the file, so hour-long recordings can be fed to the mel front end as
+/// they are decoded. Concatenated, the chunks have the same length as the
+/// whole-file decode, though resampled samples may differ in the last digits.
+pub fn decode_stream<P: AsRef<Path>>(path: P, chunk_size: usize) -> Result<DecodeStream> {
+    if chunk_size == 0 {
+        return Err(ShoutError::InvalidArgument(
+            "chunk size must be positive".into(),
+        ));
     }
+    Ok(DecodeStream {
+        track: open_track(path.as_ref())?,
+        resampler: None,
+        options: DecodeOptions::default(),
+        chunk_size,
+        pending: Vec::new(),
+        cancel: CancelToken::default(),
+        produced: 0,
+        at_end: false,
+        done: false,
+    })
+}
 
-    let sr_in = input_sample_rate.ok_or_else(|| anyhow!("could not determine input sample rate"))?;
-    let ch_in = input_channels.ok_or_else(|| anyhow!("could not determine channel count"))?;
+/// Iterator over the chunks of [`decode_stream`].
+pub struct DecodeStream {
+    track: Track,
+
+    /// Created with the first packet, once the input rate is certain, and
+    /// again whenever a chained stream changes it. Keyed by that rate.
+    resampler: Option<(u32, StreamResampler)>,
+    options: DecodeOptions,
+    chunk_size: usize,
+
+    /// Resampled samples not yet yielded.
+    pending: Vec<f32>,
+    cancel: CancelToken,
+    produced: usize,
+    at_end: bool,
+    done: bool,
+}
 
-    if interleaved_f32.is_empty() {
-        return Err(anyhow!("decoded audio was empty"));
+impl DecodeStream {
+    /// Check `cancel` between packets.
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
     }
 
-    // -------------------------
-    // 2) Downmix to mono
-    // -------------------------
-    let mono: Vec<f32> = if ch_in == 1 {
-        interleaved_f32
-    } else {
-        let frames = interleaved_f32.len() / ch_in;
-        let mut out = Vec::with_capacity(frames);
-
-        for f in 0..frames {
-            let mut sum = 0.0f32;
-            let base = f * ch_in;
-            for c in 0..ch_in {
-                sum += interleaved_f32[base + c];
+    /// Resample to the rate and with the resampler of `options`; chunks keep
+    /// their length in samples.
+    pub fn with_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sample rate of the file, if the container states it.
+    pub fn source_sample_rate(&self) -> Option<u32> {
+        self.track.sample_rate
+    }
+
+    /// Decode until a full chunk is pending or the file ends.
+    fn fill(&mut self) -> Result<()> {
+        while !self.at_end && self.pending.len() < self.chunk_size {
+            self.cancel.check(Stage::Decode)?;
+            let Some(packet) = self.track.next_packet()? else {
+                self.at_end = true;
+                if let Some((_, resampler)) = &mut self.resampler {
+                    self.pending.extend(resampler.finish()?);
+                }
+                break;
+            };
+
+            let resampler = match self.resampler.take() {
+                Some((sr_in, resampler)) if sr_in == packet.sample_rate => (sr_in, resampler),
+                previous => {
+                    if let Some((_, mut resampler)) = previous {
+                        self.pending.extend(resampler.finish()?);
+                    }
+                    let sr_in = packet.sample_rate;
+                    (sr_in, stream_resampler(sr_in, &self.options)?)
+                }
+            };
+            let (_, resampler) = self.resampler.insert(resampler);
+            let mono = downmix(packet.samples, packet.channels);
+            self.pending.extend(resampler.push(&mono)?);
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for DecodeStream {
+    type Item = Result<Vec<f32>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Err(e) = self.fill() {
+            self.done = true;
+            return Some(Err(e));
+        }
+        if self.pending.is_empty() {
+            if self.produced == 0 {
+                self.done = true;
+                return Some(Err(ShoutError::Decode("decoded audio was empty".into())));
             }
-            out.push(sum / ch_in as f32);
+            return None;
+        }
+
+        let n = self.chunk_size.min(self.pending.len());
+        let chunk: Vec<f32> = self.pending.drain(..n).collect();
+        self.produced += chunk.len();
+        Some(Ok(chunk))
+    }
+}
+
+fn stream_resampler(sr_in: u32, options: &DecodeOptions) -> Result<StreamResampler> {
+    StreamResampler::with_kind(
+        sr_in,
+        options.target_sr,
+        options.resampler,
+        options.chunk_size,
+    )
+}
+
+/// What decoding a whole file found, for checking a corpus before training.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AudioReport {
+    pub sample_rate: u32,
+    pub channels: usize,
+
+    /// Frames the container header announces, if it does.
+    pub header_frames: Option<u64>,
+    pub decoded_frames: u64,
+
+    /// Packets the codec could not decode and skipped.
+    pub skipped_packets: usize,
+    pub nan_samples: u64,
+
+    /// Samples at or beyond full scale.
+    pub clipped_samples: u64,
+}
+
+impl AudioReport {
+    pub fn header_duration_ms(&self) -> Option<u64> {
+        let frames = self.header_frames?;
+        (self.sample_rate > 0).then(|| frames * 1000 / self.sample_rate as u64)
+    }
+
+    pub fn decoded_duration_ms(&self) -> u64 {
+        if self.sample_rate == 0 {
+            return 0;
         }
-        out
+        self.decoded_frames * 1000 / self.sample_rate as u64
+    }
+}
+
+/// Decode all of `path` at its own sample rate without keeping the samples,
+/// counting what is wrong with it. Fails if the file cannot be opened or
+/// decoding stops early.
+pub fn inspect<P: AsRef<Path>>(path: P) -> Result<AudioReport> {
+    let mut track = open_track(path.as_ref())?;
+    let mut report = AudioReport {
+        sample_rate: track.sample_rate.unwrap_or(0),
+        header_frames: track.n_frames,
+        ..AudioReport::default()
     };
+    while let Some(packet) = track.next_packet()? {
+        if report.sample_rate == 0 {
+            report.sample_rate = packet.sample_rate;
+        }
+        report.channels = packet.channels;
+        report.decoded_frames += (packet.samples.len() / packet.channels.max(1)) as u64;
+        for s in &packet.samples {
+            if s.is_nan() {
+                report.nan_samples += 1;
+            } else if s.abs() >= 1.0 {
+                report.clipped_samples += 1;
+            }
+        }
+    }
+    report.skipped_packets = track.skipped_packets;
+    if track.resets > 0 {
+        // The header only describes the first link of a chained stream.
+        report.header_frames = None;
+    }
+    Ok(report)
+}
 
-    // -------------------------
-    // 3) Resample to 16 kHz (if needed) using rubato v1.0.0
-    // -------------------------
-    const SR_OUT: usize = 16_000;
+/// An opened container with a decoder for its first audio track.
+struct Track {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
 
-    if sr_in as usize == SR_OUT {
-        return Ok(mono);
+    /// From the codec parameters; some containers only tell once decoding.
+    sample_rate: Option<u32>,
+
+    /// Length in frames, if the container states it.
+    n_frames: Option<u64>,
+
+    /// Unit of packet timestamps.
+    time_base: Option<TimeBase>,
+
+    /// Packets skipped because the codec could not decode them.
+    skipped_packets: usize,
+
+    /// Times a chained stream started a new link.
+    resets: usize,
+}
+
+/// The container of `path`, probed from its contents and extension.
+pub(super) fn open_format(path: &Path) -> Result<Box<dyn FormatReader>> {
+    let file = std::fs::File::open(path)
+        .io_context(|| format!("failed to open audio file: {}", path.display()))?;
+
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    // Hint from extension (optional but helps).
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
     }
 
-    // Choose a chunk size for FFT resampler.
-    // For offline processing, 1024 is a fine starting point.
-    let chunk_size: usize = 1024;
-    let sub_chunks: usize = 1;
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| {
+            ShoutError::UnsupportedFormat(format!(
+                "unsupported format or failed to probe container: {}: {e}",
+                path.display()
+            ))
+        })?;
+    Ok(probed.format)
+}
 
-    // Create FFT resampler (sync) for mono (1 channel).
-    // rubato::Fft supports `process_all_into_buffer` which is perfect for full clips. :contentReference[oaicite:2]{index=2}
-    let mut resampler = Fft::<f32>::new(
-        sr_in as usize,
-        SR_OUT,
-        chunk_size,
-        sub_chunks,
-        1,                // mono
-        FixedSync::Input, // fixed input chunking, output varies
-    )
-        .context("failed to construct FFT resampler")?;
+fn open_track(path: &Path) -> Result<Track> {
+    let format = open_format(path)?;
 
-    let input_len_frames = mono.len(); // mono => 1 sample per frame
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| ShoutError::UnsupportedFormat("no supported audio tracks found".into()))?;
 
-    // Determine minimal output size (frames) needed. :contentReference[oaicite:3]{index=3}
-    let out_len_frames = resampler.process_all_needed_output_len(input_len_frames);
+    let track_id = track.id;
 
-    let mut out = vec![0.0f32; out_len_frames];
+    let decoder = make_decoder(&track.codec_params)?;
+
+    let sample_rate = track.codec_params.sample_rate;
+    let n_frames = track.codec_params.n_frames;
+    let time_base = track.codec_params.time_base;
+    Ok(Track {
+        format,
+        decoder,
+        track_id,
+        sample_rate,
+        n_frames,
+        time_base,
+        skipped_packets: 0,
+        resets: 0,
+    })
+}
 
-    // Adapters: (interleaved) with 1 channel => same as plain slice
-    let input_adapter =
-        InterleavedSlice::new(&mono, 1, input_len_frames).context("bad input adapter")?;
+/// A decoder for a track with `params`.
+fn make_decoder(params: &CodecParameters) -> Result<Box<dyn Decoder>> {
+    symphonia::default::get_codecs()
+        .make(params, &DecoderOptions::default())
+        .map_err(|e| {
+            let msg = format!("failed to create decoder for selected track: {e}");
+            ShoutError::UnsupportedFormat(msg)
+        })
+}
 
-    let mut output_adapter =
-        InterleavedSlice::new_mut(&mut out, 1, out_len_frames).context("bad output adapter")?;
+impl Track {
+    /// Start over on the container's current first audio track with a fresh
+    /// decoder. A chained stream (Ogg podcasts, radio recordings) starts each
+    /// link with new tracks and asks for this with `ResetRequired`.
+    fn reset(&mut self) -> Result<()> {
+        let no_track = || ShoutError::UnsupportedFormat("no supported audio tracks found".into());
+        let track = self
+            .format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(no_track)?;
+        self.decoder = make_decoder(&track.codec_params)?;
+        self.track_id = track.id;
+        self.sample_rate = track.codec_params.sample_rate.or(self.sample_rate);
+        self.time_base = track.codec_params.time_base;
+        self.resets += 1;
+        Ok(())
+    }
 
-    // Resample whole clip into preallocated buffer. :contentReference[oaicite:4]{index=4}
-    let (_frames_read, frames_written) =
-        resampler.process_all_into_buffer(&input_adapter, &mut output_adapter, input_len_frames, None)?;
+    /// The frame at `sample_rate` that the packet timestamp `ts` falls on.
+    fn frame_of(&self, ts: u64, sample_rate: u64) -> u64 {
+        match self.time_base {
+            Some(tb) => {
+                let time = tb.calc_time(ts);
+                time.seconds * sample_rate + (time.frac * sample_rate as f64).round() as u64
+            }
+            None => ts,
+        }
+    }
 
-    out.truncate(frames_written);
-    Ok(out)
+    /// The next packet of the track as interleaved f32, or `None` at the end
+    /// of the file. Packets the codec cannot decode are skipped; chained
+    /// streams continue with the next link.
+    fn next_packet(&mut self) -> Result<Option<Packet>> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(p) => p,
+                Err(SymphoniaError::ResetRequired) => {
+                    self.reset()?;
+                    continue;
+                }
+                Err(SymphoniaError::IoError(_)) => return Ok(None), // end of file
+                Err(e) => {
+                    return Err(ShoutError::Decode(format!(
+                        "error reading next packet: {e}"
+                    )));
+                }
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            // A decoder that asks for a reset mid-stream is rebuilt and given
+            // the packet again.
+            let mut result = self.decoder.decode(&packet);
+            if matches!(result, Err(SymphoniaError::ResetRequired)) {
+                self.reset()?;
+                result = self.decoder.decode(&packet);
+            }
+            let decoded = match result {
+                Ok(d) => d,
+                Err(SymphoniaError::IoError(_) | SymphoniaError::DecodeError(_)) => {
+                    self.skipped_packets += 1;
+                    continue;
+                }
+                Err(e) => {
+                    return Err(ShoutError::Decode(format!(
+                        "unrecoverable decode error: {e}"
+                    )));
+                }
+            };
+
+            // Convert decoded buffer to interleaved f32
+            let spec = *decoded.spec();
+            let mut sbuf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            sbuf.copy_interleaved_ref(decoded);
+
+            return Ok(Some(Packet {
+                ts: packet.ts(),
+                sample_rate: spec.rate,
+                channels: spec.channels.count(),
+                samples: sbuf.samples().to_vec(),
+            }));
+        }
+    }
+}
+
+/// One decoded packet.
+struct Packet {
+    /// Timestamp of the first frame, in the track's time base.
+    ts: u64,
+    sample_rate: u32,
+    channels: usize,
+
+    /// Interleaved, `channels` samples per frame.
+    samples: Vec<f32>,
+}
+
+/// Average interleaved frames of `channels` samples into one.
+fn downmix(interleaved: Vec<f32>, channels: usize) -> Vec<f32> {
+    if channels == 1 {
+        return interleaved;
+    }
+    interleaved
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+#[cfg(all(test, feature = "codec-wav"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_chunks_add_up_to_the_whole_file() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden/tones.wav");
+        let whole = decode_to_f32_mono_16k(&path).unwrap();
+        let chunks: Vec<Vec<f32>> = decode_stream(&path, 5000)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert!(chunks[..chunks.len() - 1].iter().all(|c| c.len() == 5000));
+        assert_eq!(chunks.concat(), whole);
+        assert!(decode_stream(&path, 0).is_err());
+    }
+
+    #[test]
+    fn segment_matches_the_slice_of_the_whole_file() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden/tones.wav");
+        let whole = decode_to_f32_mono_16k(&path).unwrap();
+        let segment = decode_segment_to_f32_mono_16k(&path, 250, 750).unwrap();
+        assert_eq!(segment, whole[4000..12000]);
+        assert!(decode_segment_to_f32_mono_16k(&path, 750, 250).is_err());
+    }
+
+    #[test]
+    fn channel_modes_split_interleaved_samples() {
+        let stereo = vec![1.0, -1.0, 0.5, 0.0];
+        let split = |mode: ChannelMode| mode.split(stereo.clone(), 2).unwrap();
+        assert_eq!(split(ChannelMode::DownmixMono), [vec![0.0, 0.25]]);
+        assert_eq!(split(ChannelMode::SelectChannel(1)), [vec![-1.0, 0.0]]);
+        assert_eq!(
+            split(ChannelMode::KeepAll),
+            [vec![1.0, 0.5], vec![-1.0, 0.0]]
+        );
+        assert!(
+            ChannelMode::SelectChannel(2)
+                .split(stereo.clone(), 2)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn decodes_at_the_configured_rate() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden/tones.wav");
+        let whole = decode_to_f32_mono_16k(&path).unwrap();
+        let options = DecodeOptions {
+            target_sr: 8000,
+            resampler: ResamplerKind::SincFixed {
+                quality: Default::default(),
+            },
+            ..DecodeOptions::default()
+        };
+        let cancel = CancelToken::default();
+        let narrow = decode_with(&path, ChannelMode::DownmixMono, &options, &cancel).unwrap();
+        assert!(narrow[0].len().abs_diff(whole.len() / 2) <= 1);
+    }
+
+    #[test]
+    fn inspect_counts_frames() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden/tones.wav");
+        let report = inspect(&path).unwrap();
+        assert_eq!(report.header_frames, Some(report.decoded_frames));
+        assert_eq!(report.nan_samples, 0);
+        assert_eq!(report.skipped_packets, 0);
+    }
 }
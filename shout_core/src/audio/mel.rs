@@ -1,4 +1,13 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
 use mel_spec::prelude::*;
+use ndarray::Array2;
+use rustfft::num_complex::Complex32;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{Result, ShoutError};
 
 #[derive(Debug, Clone)]
 pub struct MelSpec {
@@ -13,12 +22,57 @@ pub struct MelSpec {
     pub data: Vec<f32>,
 }
 
+impl From<Array2<f32>> for MelSpec {
+    /// From a `(frames, n_mels)` matrix.
+    fn from(features: Array2<f32>) -> Self {
+        let (n_frames, n_mels) = features.dim();
+        Self {
+            n_frames,
+            n_mels,
+            data: features.iter().copied().collect(),
+        }
+    }
+}
+
+impl MelSpec {
+    /// The `(n_frames, n_mels)` matrix.
+    pub fn to_array(&self) -> Array2<f32> {
+        Array2::from_shape_vec((self.n_frames, self.n_mels), self.data.clone())
+            .expect("mel buffer has n_frames * n_mels values")
+    }
+}
 
-/// Convert mono 16k PCM samples into a mel spectrogram matrix.
-///
-/// Returns an ndarray matrix with shape either (n_mels, frames) or (frames, n_mels)
-/// depending on what `interleave_frames` returns in this crate.
-pub fn pcm_to_mel_frames_flat(pcm_16k_mono: &[f32], n_mels: usize) -> MelSpec{
+/// Flattening of `(frames, n_mels)` feature matrices for APIs that take one
+/// buffer.
+pub trait TimeMajor {
+    /// The values frame after frame, `out[t * n_mels + m] == features[[t, m]]`,
+    /// whatever the matrix's memory layout.
+    fn to_flat_time_major(&self) -> Vec<f32>;
+}
+
+impl TimeMajor for Array2<f32> {
+    fn to_flat_time_major(&self) -> Vec<f32> {
+        self.iter().copied().collect()
+    }
+}
+
+/// Log-mel spectrogram of mono 16 kHz samples (25 ms window, 10 ms hop, via
+/// the `mel_spec` crate) as a `(frames, n_mels)` matrix: row `t` holds the
+/// mel bins of frame `t`.
+pub fn log_mel(pcm_16k_mono: &[f32], n_mels: usize) -> Array2<f32> {
+    let mut mel = mel_spec_frames(pcm_16k_mono, n_mels);
+    mel.data.truncate(mel.n_frames * mel.n_mels);
+    Array2::from_shape_vec((mel.n_frames, mel.n_mels), mel.data)
+        .expect("mel buffer has n_frames * n_mels values")
+}
+
+/// Convert mono 16k PCM samples into a flat mel spectrogram, frame after frame.
+#[deprecated(note = "use `log_mel`, which returns a `(frames, n_mels)` matrix")]
+pub fn pcm_to_mel_frames_flat(pcm_16k_mono: &[f32], n_mels: usize) -> MelSpec {
+    mel_spec_frames(pcm_16k_mono, n_mels)
+}
+
+fn mel_spec_frames(pcm_16k_mono: &[f32], n_mels: usize) -> MelSpec {
     let fft_size = 400;
     let hop_size = 160;
     let sampling_rate = 16000.0;
@@ -51,3 +105,616 @@ pub fn pcm_to_mel_frames_flat(pcm_16k_mono: &[f32], n_mels: usize) -> MelSpec{
         data: flat,
     }
 }
+
+// -----------------------------------------------------------------------------
+// Configurable log-mel
+// -----------------------------------------------------------------------------
+
+/// Analysis window applied to each frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Window {
+    /// Periodic Hann, as PyTorch's `hann_window`.
+    Hann,
+
+    /// Periodic Hamming.
+    Hamming,
+
+    /// Kaldi's default: a symmetric Hann window raised to the power 0.85.
+    Povey,
+
+    Rectangular,
+}
+
+impl Window {
+    fn coefficients(self, len: usize) -> Vec<f32> {
+        let periodic = |n: usize| 2.0 * PI * n as f64 / len as f64;
+        let symmetric = |n: usize| 2.0 * PI * n as f64 / (len.max(2) - 1) as f64;
+        (0..len)
+            .map(|n| {
+                let w = match self {
+                    Window::Hann => 0.5 - 0.5 * periodic(n).cos(),
+                    Window::Hamming => 0.54 - 0.46 * periodic(n).cos(),
+                    Window::Povey => (0.5 - 0.5 * symmetric(n).cos()).powf(0.85),
+                    Window::Rectangular => 1.0,
+                };
+                w as f32
+            })
+            .collect()
+    }
+}
+
+/// Mel scale and filter shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MelScale {
+    /// Slaney's scale (linear below 1 kHz) with triangles in Hz normalized to
+    /// equal area: librosa's default and Whisper's filterbank.
+    Slaney,
+
+    /// HTK's `1127 ln(1 + f / 700)` with triangles in mel of peak 1, as Kaldi.
+    Htk,
+}
+
+/// How mel energies become features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogScale {
+    /// Whisper's: `log10` clamped at 1e-10, floored at 8 below the maximum
+    /// of the clip, then scaled to `(x + 4) / 4`.
+    Whisper,
+
+    /// Natural log floored at `f32::EPSILON`, as Kaldi's `fbank`.
+    Natural,
+}
+
+/// Everything that determines a log-mel spectrogram. Start from a preset and
+/// change fields with struct update syntax, then [`MelConfig::build`] the
+/// extractor once and reuse it:
+///
+/// ```
+/// use shout_core::audio::mel::MelConfig;
+///
+/// let mel = MelConfig { n_mels: 128, ..MelConfig::whisper() }.build().unwrap();
+/// assert_eq!(mel.compute(&[0.0; 16_000]).n_frames, 100);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MelConfig {
+    pub sample_rate: u32,
+
+    /// FFT length; frames shorter than this are zero padded.
+    pub n_fft: usize,
+
+    /// Samples per frame, at most `n_fft`.
+    pub win_length: usize,
+
+    /// Samples between frame starts, at most `win_length`.
+    pub hop_length: usize,
+    pub n_mels: usize,
+
+    /// Lowest frequency of the filterbank, in Hz.
+    pub f_min: f32,
+
+    /// Highest frequency of the filterbank; half the sample rate if `None`.
+    pub f_max: Option<f32>,
+    pub window: Window,
+    pub mel_scale: MelScale,
+    pub log: LogScale,
+
+    /// Center frames on multiples of the hop, reflecting the signal at its
+    /// ends, giving `len / hop_length` frames (Whisper). Otherwise frames
+    /// start at multiples of the hop and only whole frames count (Kaldi's
+    /// `snip-edges`).
+    pub center: bool,
+
+    /// Subtract each frame's mean before windowing.
+    pub remove_dc: bool,
+
+    /// Pre-emphasis coefficient; 0 for none.
+    pub preemphasis: f32,
+}
+
+impl MelConfig {
+    /// OpenAI Whisper's front end: 25 ms frames every 10 ms at 16 kHz, 80
+    /// Slaney mel bins (128 for large-v3).
+    pub fn whisper() -> Self {
+        Self {
+            sample_rate: 16_000,
+            n_fft: 400,
+            win_length: 400,
+            hop_length: 160,
+            n_mels: 80,
+            f_min: 0.0,
+            f_max: None,
+            window: Window::Hann,
+            mel_scale: MelScale::Slaney,
+            log: LogScale::Whisper,
+            center: true,
+            remove_dc: false,
+            preemphasis: 0.0,
+        }
+    }
+
+    /// Kaldi's `compute-fbank-feats` defaults without dither, with 80 bins.
+    pub fn kaldi() -> Self {
+        Self {
+            sample_rate: 16_000,
+            n_fft: 512,
+            win_length: 400,
+            hop_length: 160,
+            n_mels: 80,
+            f_min: 20.0,
+            f_max: None,
+            window: Window::Povey,
+            mel_scale: MelScale::Htk,
+            log: LogScale::Natural,
+            center: false,
+            remove_dc: true,
+            preemphasis: 0.97,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let nyquist = self.sample_rate as f32 / 2.0;
+        let f_max = self.f_max.unwrap_or(nyquist);
+        let problem = if self.sample_rate == 0 {
+            "sample rate must be positive".to_string()
+        } else if self.n_mels == 0 {
+            "n_mels must be positive".to_string()
+        } else if self.win_length == 0 || self.win_length > self.n_fft {
+            format!(
+                "window length {} must be in 1..={}",
+                self.win_length, self.n_fft
+            )
+        } else if self.hop_length == 0 || self.hop_length > self.win_length {
+            format!(
+                "hop length {} must be in 1..={}",
+                self.hop_length, self.win_length
+            )
+        } else if self.f_min < 0.0 || self.f_min >= f_max || f_max > nyquist {
+            format!(
+                "need 0 <= f_min < f_max <= {nyquist} Hz, got {} and {f_max}",
+                self.f_min
+            )
+        } else if !(0.0..1.0).contains(&self.preemphasis) {
+            format!("pre-emphasis {} must be in [0, 1)", self.preemphasis)
+        } else {
+            return Ok(());
+        };
+        Err(ShoutError::InvalidArgument(format!(
+            "invalid mel config: {problem}"
+        )))
+    }
+
+    /// Check the config and precompute the window and filterbank.
+    pub fn build(self) -> Result<MelExtractor> {
+        self.validate()?;
+        Ok(MelExtractor {
+            window: self.window.coefficients(self.win_length),
+            filters: filterbank(&self),
+            fft: FftPlanner::<f32>::new().plan_fft_forward(self.n_fft),
+            config: self,
+        })
+    }
+}
+
+/// Computes log-mel spectrograms for one [`MelConfig`].
+pub struct MelExtractor {
+    config: MelConfig,
+    window: Vec<f32>,
+
+    /// `(n_mels, n_fft / 2 + 1)`.
+    filters: Array2<f32>,
+    fft: Arc<dyn Fft<f32>>,
+}
+
+impl std::fmt::Debug for MelExtractor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MelExtractor")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl MelExtractor {
+    pub fn config(&self) -> &MelConfig {
+        &self.config
+    }
+
+    /// Frames [`Self::compute`] gives for `n_samples` samples.
+    pub fn n_frames(&self, n_samples: usize) -> usize {
+        let c = &self.config;
+        if c.center {
+            n_samples / c.hop_length
+        } else if n_samples < c.win_length {
+            0
+        } else {
+            1 + (n_samples - c.win_length) / c.hop_length
+        }
+    }
+
+    /// [`Self::compute`] as a `(frames, n_mels)` matrix.
+    pub fn features(&self, pcm: &[f32]) -> Array2<f32> {
+        self.compute(pcm).to_array()
+    }
+
+    /// Log-mel spectrogram of `pcm`, sampled at the config's rate.
+    pub fn compute(&self, pcm: &[f32]) -> MelSpec {
+        let c = &self.config;
+        let n_frames = self.n_frames(pcm.len());
+        let offset = if c.center {
+            c.win_length as isize / 2
+        } else {
+            0
+        };
+
+        let mut frame = vec![0.0f32; c.win_length];
+        let mut scratch = Vec::new();
+        let mut data = Vec::with_capacity(n_frames * c.n_mels);
+        for t in 0..n_frames {
+            let start = (t * c.hop_length) as isize - offset;
+            for (i, x) in frame.iter_mut().enumerate() {
+                *x = reflect(pcm, start + i as isize);
+            }
+            self.log_mel_frame(&mut frame, &mut scratch, &mut data);
+        }
+
+        if c.log == LogScale::Whisper {
+            let max = data.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            for v in &mut data {
+                *v = ((*v).max(max - 8.0) + 4.0) / 4.0;
+            }
+        }
+        MelSpec {
+            n_frames,
+            n_mels: c.n_mels,
+            data,
+        }
+    }
+
+    /// Append the log mel energies of one frame of `win_length` samples (which
+    /// it modifies); clip-level normalization is left to the caller.
+    fn log_mel_frame(&self, frame: &mut [f32], scratch: &mut Vec<Complex32>, out: &mut Vec<f32>) {
+        let c = &self.config;
+        if c.remove_dc {
+            let mean = frame.iter().sum::<f32>() / frame.len() as f32;
+            frame.iter_mut().for_each(|x| *x -= mean);
+        }
+        if c.preemphasis > 0.0 {
+            for i in (1..frame.len()).rev() {
+                frame[i] -= c.preemphasis * frame[i - 1];
+            }
+            frame[0] -= c.preemphasis * frame[0];
+        }
+
+        scratch.clear();
+        scratch.extend(
+            frame
+                .iter()
+                .zip(&self.window)
+                .map(|(x, w)| Complex32::new(x * w, 0.0)),
+        );
+        scratch.resize(c.n_fft, Complex32::default());
+        self.fft.process(scratch);
+
+        let power: Vec<f32> = scratch[..c.n_fft / 2 + 1]
+            .iter()
+            .map(|z| z.norm_sqr())
+            .collect();
+        for filter in self.filters.rows() {
+            let energy: f32 = filter.iter().zip(&power).map(|(w, p)| w * p).sum();
+            out.push(match c.log {
+                LogScale::Whisper => energy.max(1e-10).log10(),
+                LogScale::Natural => energy.max(f32::EPSILON).ln(),
+            });
+        }
+    }
+}
+
+/// Incremental [`MelExtractor`]: takes audio in chunks of any length, as it
+/// arrives from a microphone or [`decode_stream`](crate::audio::decoder), and
+/// returns each frame once the samples it covers are in.
+///
+/// The frames equal those of [`MelExtractor::compute`] on the whole signal,
+/// except with [`LogScale::Whisper`]: its floor follows the maximum seen so
+/// far instead of the clip's, so frames before the loudest one may differ.
+/// Only the samples of pending frames are kept.
+#[derive(Debug)]
+pub struct StreamingMel {
+    extractor: MelExtractor,
+
+    /// The first `win_length` samples, for reflecting at the start.
+    head: Vec<f32>,
+
+    /// Samples from `buffer_start` on.
+    buffer: Vec<f32>,
+    buffer_start: usize,
+    received: usize,
+    next_frame: usize,
+    running_max: f32,
+    frame: Vec<f32>,
+    scratch: Vec<Complex32>,
+}
+
+impl StreamingMel {
+    pub fn new(config: MelConfig) -> Result<Self> {
+        let extractor = config.build()?;
+        let win_length = extractor.config.win_length;
+        Ok(Self {
+            extractor,
+            head: Vec::with_capacity(win_length),
+            buffer: Vec::new(),
+            buffer_start: 0,
+            received: 0,
+            next_frame: 0,
+            running_max: f32::NEG_INFINITY,
+            frame: vec![0.0; win_length],
+            scratch: Vec::new(),
+        })
+    }
+
+    pub fn config(&self) -> &MelConfig {
+        &self.extractor.config
+    }
+
+    /// Frames returned so far.
+    pub fn frames_emitted(&self) -> usize {
+        self.next_frame
+    }
+
+    /// Feed samples; returns the frames they complete, `(frames, n_mels)`.
+    pub fn push(&mut self, pcm: &[f32]) -> Array2<f32> {
+        let missing = self.frame.len() - self.head.len();
+        self.head.extend_from_slice(&pcm[..missing.min(pcm.len())]);
+        self.buffer.extend_from_slice(pcm);
+        self.received += pcm.len();
+
+        let (win, hop) = (self.config().win_length, self.config().hop_length);
+        let offset = self.offset();
+        let ready = |t: usize| t * hop + win <= self.received + offset;
+        let mut n = 0;
+        while ready(self.next_frame + n) {
+            n += 1;
+        }
+        self.emit(n, None)
+    }
+
+    /// The remaining frames at the end of the stream. With centered frames
+    /// these reflect the signal at its end; otherwise a partial last frame
+    /// is dropped.
+    pub fn finish(&mut self) -> Array2<f32> {
+        let total = self.extractor.n_frames(self.received);
+        let n = total.saturating_sub(self.next_frame);
+        self.emit(n, Some(self.received))
+    }
+
+    fn offset(&self) -> usize {
+        let c = self.config();
+        if c.center { c.win_length / 2 } else { 0 }
+    }
+
+    /// Compute the next `n` frames; `end` is the stream length once known.
+    fn emit(&mut self, n: usize, end: Option<usize>) -> Array2<f32> {
+        let c = &self.extractor.config;
+        let (n_mels, hop) = (c.n_mels, c.hop_length);
+        let offset = self.offset() as isize;
+
+        let mut data = Vec::with_capacity(n * n_mels);
+        for _ in 0..n {
+            let start = (self.next_frame * hop) as isize - offset;
+            for i in 0..self.frame.len() {
+                let mut j = start + i as isize;
+                if let Some(end) = end {
+                    j = reflect_index(end, j);
+                } else if j < 0 {
+                    j = -j;
+                }
+                let j = j as usize;
+                self.frame[i] = match j.checked_sub(self.buffer_start) {
+                    Some(k) => self.buffer[k],
+                    None => self.head[j],
+                };
+            }
+            self.extractor
+                .log_mel_frame(&mut self.frame, &mut self.scratch, &mut data);
+            self.next_frame += 1;
+        }
+
+        if self.extractor.config.log == LogScale::Whisper {
+            for frame in data.chunks_exact_mut(n_mels) {
+                let max = frame.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                self.running_max = self.running_max.max(max);
+                for v in frame {
+                    *v = ((*v).max(self.running_max - 8.0) + 4.0) / 4.0;
+                }
+            }
+        }
+
+        // Samples before the next frame's start are only needed for reflection,
+        // which `head` covers.
+        let keep_from = (self.next_frame * hop).saturating_sub(offset as usize);
+        if keep_from > self.buffer_start {
+            let drop = (keep_from - self.buffer_start).min(self.buffer.len());
+            self.buffer.drain(..drop);
+            self.buffer_start += drop;
+        }
+
+        Array2::from_shape_vec((n, n_mels), data).expect("n frames of n_mels values")
+    }
+}
+
+/// Log-mel spectrogram computed the way OpenAI Whisper's
+/// `log_mel_spectrogram` does ([`MelConfig::whisper`]), so checkpoints trained
+/// on it see the features they expect. `n_mels` is 80, or 128 for large-v3.
+/// Gives `pcm.len() / 160` frames.
+pub fn whisper_mel(pcm: &[f32], n_mels: usize) -> MelSpec {
+    MelConfig {
+        n_mels,
+        ..MelConfig::whisper()
+    }
+    .build()
+    .expect("the Whisper preset is valid for any positive n_mels")
+    .compute(pcm)
+}
+
+/// `pcm[i]`, mirrored at both ends without repeating the edge sample (NumPy's
+/// and PyTorch's `reflect`).
+fn reflect(pcm: &[f32], i: isize) -> f32 {
+    pcm[reflect_index(pcm.len(), i) as usize]
+}
+
+/// Index `i` mirrored into `0..n` as [`reflect`] does.
+fn reflect_index(n: usize, i: isize) -> isize {
+    let n = n as isize;
+    if n == 1 {
+        return 0;
+    }
+    let period = 2 * (n - 1);
+    let i = i.rem_euclid(period);
+    if i < n { i } else { period - i }
+}
+
+/// The mel filters of `config`, `(n_mels, n_fft / 2 + 1)`.
+fn filterbank(config: &MelConfig) -> Array2<f32> {
+    let sample_rate = config.sample_rate as f64;
+    let f_min = config.f_min as f64;
+    let f_max = config.f_max.map_or(sample_rate / 2.0, f64::from);
+    let scale = config.mel_scale;
+    let (low, high) = (scale.to_mel(f_min), scale.to_mel(f_max));
+    let edges: Vec<f64> = (0..config.n_mels + 2)
+        .map(|i| low + (high - low) * i as f64 / (config.n_mels + 1) as f64)
+        .collect();
+
+    Array2::from_shape_fn((config.n_mels, config.n_fft / 2 + 1), |(m, k)| {
+        let freq = k as f64 * sample_rate / config.n_fft as f64;
+        let (lower, center, upper) = (edges[m], edges[m + 1], edges[m + 2]);
+        let weight = match config.mel_scale {
+            // Triangles in Hz, each of area 1.
+            MelScale::Slaney => {
+                let (lower, center, upper) =
+                    (scale.to_hz(lower), scale.to_hz(center), scale.to_hz(upper));
+                let rising = (freq - lower) / (center - lower);
+                let falling = (upper - freq) / (upper - center);
+                rising.min(falling).max(0.0) * 2.0 / (upper - lower)
+            }
+            // Triangles in mel, of height 1.
+            MelScale::Htk => {
+                let mel = scale.to_mel(freq);
+                let rising = (mel - lower) / (center - lower);
+                let falling = (upper - mel) / (upper - center);
+                rising.min(falling).max(0.0)
+            }
+        };
+        weight as f32
+    })
+}
+
+/// Slaney's mel scale: linear below 1 kHz, logarithmic above.
+fn hz_to_mel(hz: f64) -> f64 {
+    let log_step = 6.4f64.ln() / 27.0;
+    if hz < 1000.0 {
+        hz * 3.0 / 200.0
+    } else {
+        15.0 + (hz / 1000.0).ln() / log_step
+    }
+}
+
+fn mel_to_hz(mel: f64) -> f64 {
+    let log_step = 6.4f64.ln() / 27.0;
+    if mel < 15.0 {
+        mel * 200.0 / 3.0
+    } else {
+        1000.0 * ((mel - 15.0) * log_step).exp()
+    }
+}
+
+fn hz_to_htk_mel(hz: f64) -> f64 {
+    1127.0 * (1.0 + hz / 700.0).ln()
+}
+
+impl MelScale {
+    fn to_mel(self, hz: f64) -> f64 {
+        match self {
+            MelScale::Slaney => hz_to_mel(hz),
+            MelScale::Htk => hz_to_htk_mel(hz),
+        }
+    }
+
+    fn to_hz(self, mel: f64) -> f64 {
+        match self {
+            MelScale::Slaney => mel_to_hz(mel),
+            MelScale::Htk => 700.0 * ((mel / 1127.0).exp() - 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whisper_mel_matches_the_reference_layout() {
+        // One second of a 440 Hz tone.
+        let pcm: Vec<f32> = (0..16_000)
+            .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16_000.0).sin())
+            .collect();
+        let mel = whisper_mel(&pcm, 80);
+        assert_eq!((mel.n_frames, mel.n_mels), (100, 80));
+
+        // Nothing lies more than 8 (in log10) below the clip maximum, and the
+        // loudest band of a middle frame is one that passes 440 Hz.
+        let max = mel.data.iter().copied().fold(f32::MIN, f32::max);
+        assert!(mel.data.iter().all(|&v| v >= max - 2.0 - 1e-6));
+        let frame = &mel.data[50 * 80..51 * 80];
+        let loudest = (0..80)
+            .max_by(|&a, &b| frame[a].total_cmp(&frame[b]))
+            .unwrap();
+        let filters = filterbank(&MelConfig::whisper());
+        assert!(filters[[loudest, 11]] > 0.0); // 440 Hz at 40 Hz per FFT bin
+    }
+
+    #[test]
+    fn slaney_scale_is_continuous_at_one_khz() {
+        assert!((hz_to_mel(1000.0) - 15.0).abs() < 1e-12);
+        assert!((mel_to_hz(hz_to_mel(3456.0)) - 3456.0).abs() < 1e-9);
+        assert!((hz_to_mel(200.0) - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn presets_validate_and_count_frames() {
+        let kaldi = MelConfig::kaldi().build().unwrap();
+        // 25 ms frames every 10 ms without padding: 98 in a second.
+        assert_eq!(kaldi.compute(&[0.1; 16_000]).n_frames, 98);
+        assert_eq!(kaldi.n_frames(399), 0);
+
+        let bad = MelConfig {
+            hop_length: 500,
+            ..MelConfig::whisper()
+        };
+        assert!(bad.build().is_err());
+    }
+
+    #[test]
+    fn streaming_matches_whole_clip() {
+        let pcm: Vec<f32> = (0..7_321)
+            .map(|i| ((i * 7919) % 1000) as f32 / 1000.0 - 0.5)
+            .collect();
+        let centered = MelConfig {
+            log: LogScale::Natural,
+            ..MelConfig::whisper()
+        };
+        for config in [MelConfig::kaldi(), centered] {
+            let whole = config.clone().build().unwrap().features(&pcm);
+            let mut streaming = StreamingMel::new(config).unwrap();
+            let mut frames = Vec::new();
+            for chunk in pcm.chunks(333) {
+                frames.extend(streaming.push(chunk).to_flat_time_major());
+            }
+            frames.extend(streaming.finish().to_flat_time_major());
+
+            assert_eq!(streaming.frames_emitted(), whole.nrows());
+            let whole = whole.to_flat_time_major();
+            assert!(frames.iter().zip(&whole).all(|(a, b)| (a - b).abs() < 1e-4));
+        }
+    }
+}
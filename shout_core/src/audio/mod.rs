@@ -1,2 +1,14 @@
+#[cfg(feature = "capture")]
+pub mod capture;
+#[cfg(feature = "decode")]
 pub mod decoder;
-pub mod mel;
\ No newline at end of file
+pub mod mel;
+pub mod normalize;
+#[cfg(feature = "playback")]
+pub mod playback;
+#[cfg(feature = "decode")]
+pub mod probe;
+#[cfg(feature = "resample")]
+pub mod resample;
+pub mod trim;
+pub mod vad;
@@ -0,0 +1,241 @@
+//! Per-clip level normalization, so corpus recordings made at very different
+//! levels reach the front end at the same one: to a peak, an RMS level, or a
+//! loudness measured after EBU R128 (ITU-R BS.1770); and DC offset removal.
+
+/// Loudness broadcast audio is normalized to under EBU R128, in LUFS.
+pub const EBU_R128_TARGET_LUFS: f64 = -23.0;
+
+/// Peak level that leaves headroom for resampling and codecs, in dBFS.
+pub const DEFAULT_PEAK_DBFS: f64 = -1.0;
+
+/// Level to scale a clip to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Largest absolute sample at `dbfs`.
+    Peak { dbfs: f64 },
+
+    /// Root mean square at `dbfs`.
+    Rms { dbfs: f64 },
+
+    /// Integrated loudness at `lufs`, e.g. [`EBU_R128_TARGET_LUFS`].
+    Loudness { lufs: f64 },
+}
+
+impl Normalization {
+    /// Scale `samples` to the level; returns the gain applied in dB, or `None`
+    /// (leaving `samples` as they are) if the clip is silent or too short to
+    /// measure. Only [`Normalization::Peak`] keeps samples within ±1.0.
+    pub fn apply(self, samples: &mut [f32], sample_rate: u32) -> Option<f64> {
+        let gain_db = match self {
+            Normalization::Peak { dbfs } => dbfs - db(peak(samples)?),
+            Normalization::Rms { dbfs } => dbfs - db(rms(samples)?),
+            Normalization::Loudness { lufs } => lufs - integrated_loudness(samples, sample_rate)?,
+        };
+        apply_gain(samples, gain_db);
+        Some(gain_db)
+    }
+}
+
+/// Subtract the mean of `samples` from each; returns the offset removed.
+pub fn remove_dc_offset(samples: &mut [f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let offset = (samples.iter().map(|&s| s as f64).sum::<f64>() / samples.len() as f64) as f32;
+    samples.iter_mut().for_each(|s| *s -= offset);
+    offset
+}
+
+/// Largest absolute sample; `None` if all are zero.
+fn peak(samples: &[f32]) -> Option<f64> {
+    let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    (peak > 0.0).then_some(peak as f64)
+}
+
+/// Root mean square; `None` if all samples are zero.
+fn rms(samples: &[f32]) -> Option<f64> {
+    let sum: f64 = samples.iter().map(|&s| s as f64 * s as f64).sum();
+    (sum > 0.0).then(|| (sum / samples.len() as f64).sqrt())
+}
+
+fn db(amplitude: f64) -> f64 {
+    20.0 * amplitude.log10()
+}
+
+fn apply_gain(samples: &mut [f32], gain_db: f64) {
+    let gain = 10f64.powf(gain_db / 20.0) as f32;
+    samples.iter_mut().for_each(|s| *s *= gain);
+}
+
+/// Blocks quieter than this never count towards the integrated loudness.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+
+/// Blocks more than this many LU below the ungated loudness are dropped.
+const RELATIVE_GATE_LU: f64 = 10.0;
+
+const BLOCK_MS: usize = 400;
+const STEP_MS: usize = 100;
+
+/// Integrated loudness of mono `samples` in LUFS: the mean power of the
+/// K-weighted signal over gated 400 ms blocks. `None` if the clip is shorter
+/// than one block or silent.
+pub fn integrated_loudness(samples: &[f32], sample_rate: u32) -> Option<f64> {
+    let block = sample_rate as usize * BLOCK_MS / 1000;
+    let step = sample_rate as usize * STEP_MS / 1000;
+    if block == 0 || samples.len() < block {
+        return None;
+    }
+
+    let mut shelf = Biquad::high_shelf(sample_rate as f64);
+    let mut high_pass = Biquad::high_pass(sample_rate as f64);
+    let weighted: Vec<f64> = samples
+        .iter()
+        .map(|&s| high_pass.process(shelf.process(s as f64)))
+        .collect();
+
+    // Mean square per block, from a running sum of squares.
+    let mut squares = Vec::with_capacity(weighted.len() + 1);
+    squares.push(0.0);
+    for (i, s) in weighted.iter().enumerate() {
+        squares.push(squares[i] + s * s);
+    }
+    let powers: Vec<f64> = (0..=(weighted.len() - block) / step)
+        .map(|i| (squares[i * step + block] - squares[i * step]) / block as f64)
+        .filter(|&z| loudness(z) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    if powers.is_empty() {
+        return None;
+    }
+
+    let gate = loudness(mean(&powers)) - RELATIVE_GATE_LU;
+    let gated: Vec<f64> = powers.into_iter().filter(|&z| loudness(z) > gate).collect();
+    Some(loudness(mean(&gated)))
+}
+
+/// Scale `samples` so their integrated loudness is `target_lufs`; returns the
+/// gain applied in dB, or `None` (leaving `samples` as they are) if the
+/// loudness cannot be measured. Peaks are not limited, so loud targets can
+/// push samples past ±1.0.
+pub fn normalize_loudness(samples: &mut [f32], sample_rate: u32, target_lufs: f64) -> Option<f64> {
+    Normalization::Loudness { lufs: target_lufs }.apply(samples, sample_rate)
+}
+
+fn loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// A second-order IIR section (direct form I).
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    /// BS.1770's first K-weighting stage, a +4 dB shelf modelling the head,
+    /// for any sample rate (the coefficients of libebur128).
+    fn high_shelf(sample_rate: f64) -> Self {
+        let f0 = 1681.974450955533;
+        let gain_db = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let b = [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ];
+        Self::new(b, [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0])
+    }
+
+    /// BS.1770's second stage, the RLB high-pass around 38 Hz.
+    fn high_pass(sample_rate: f64) -> Self {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Self::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    }
+
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, sample_rate: u32, secs: usize) -> Vec<f32> {
+        let n = sample_rate as usize * secs;
+        (0..n)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                amplitude * (2.0 * std::f32::consts::PI * 997.0 * t).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn measures_and_normalizes_a_tone() {
+        // A full-scale 997 Hz sine in one channel is -3.01 LUFS.
+        for sample_rate in [16_000, 48_000] {
+            let mut tone = sine(0.1, sample_rate, 3);
+            let lufs = integrated_loudness(&tone, sample_rate).unwrap();
+            assert!((lufs + 23.01).abs() < 0.1, "{sample_rate} Hz: {lufs}");
+
+            let gain_db = normalize_loudness(&mut tone, sample_rate, -18.0).unwrap();
+            assert!((gain_db - 5.01).abs() < 0.1, "{gain_db}");
+            let lufs = integrated_loudness(&tone, sample_rate).unwrap();
+            assert!((lufs + 18.0).abs() < 0.01, "{lufs}");
+        }
+
+        assert_eq!(integrated_loudness(&[0.0; 16_000], 16_000), None);
+        assert_eq!(integrated_loudness(&[0.5; 100], 16_000), None);
+    }
+
+    #[test]
+    fn removes_dc_and_scales_peak_and_rms() {
+        let mut clip: Vec<f32> = sine(0.2, 16_000, 1).iter().map(|s| s + 0.3).collect();
+        assert!((remove_dc_offset(&mut clip) - 0.3).abs() < 1e-4);
+
+        let gain_db = Normalization::Peak { dbfs: -1.0 }
+            .apply(&mut clip, 16_000)
+            .unwrap();
+        assert!((gain_db - 20.0 * (0.891 / 0.2f64).log10()).abs() < 0.01);
+        assert!((peak(&clip).unwrap() - 0.891).abs() < 1e-3);
+
+        Normalization::Rms { dbfs: -20.0 }
+            .apply(&mut clip, 16_000)
+            .unwrap();
+        assert!((rms(&clip).unwrap() - 0.1).abs() < 1e-4);
+        assert_eq!(
+            Normalization::Peak { dbfs: -1.0 }.apply(&mut [0.0; 8], 16_000),
+            None
+        );
+    }
+}
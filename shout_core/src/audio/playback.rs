@@ -0,0 +1,93 @@
+//! Playing audio on the default output device, for listening to dataset
+//! entries.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+
+use super::resample::StreamResampler;
+use crate::cancel::CancelToken;
+use crate::errors::{Result, ShoutError};
+
+/// Play mono `pcm` at `sample_rate` and block until it has been played. The
+/// [`CancelToken`] stops playback early; that is not an error.
+pub fn play(pcm: &[f32], sample_rate: u32, cancel: &CancelToken) -> Result<()> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| ShoutError::Device("no default audio output device".into()))?;
+    let supported = device
+        .default_output_config()
+        .map_err(|e| ShoutError::Device(format!("failed to query default output config: {e}")))?;
+    let sample_format = supported.sample_format();
+    let config: cpal::StreamConfig = supported.into();
+
+    let mut resampler = StreamResampler::new(sample_rate, config.sample_rate.0)?;
+    let mut samples = resampler.push(pcm)?;
+    samples.extend(resampler.finish()?);
+    let samples: Arc<[f32]> = samples.into();
+
+    let (done_tx, done_rx) = mpsc::channel();
+    let stream = match sample_format {
+        SampleFormat::F32 => build::<f32>(&device, &config, samples, done_tx),
+        SampleFormat::I16 => build::<i16>(&device, &config, samples, done_tx),
+        SampleFormat::U16 => build::<u16>(&device, &config, samples, done_tx),
+        other => {
+            let msg = format!("unsupported output sample format: {other:?}");
+            return Err(ShoutError::UnsupportedFormat(msg));
+        }
+    }?;
+    stream
+        .play()
+        .map_err(|e| ShoutError::Device(format!("failed to start output stream: {e}")))?;
+
+    loop {
+        match done_rx.recv_timeout(Duration::from_millis(50)) {
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) if cancel.is_cancelled() => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+    // Let the device drain its buffer before the stream is dropped.
+    std::thread::sleep(Duration::from_millis(100));
+    Ok(())
+}
+
+/// An output stream writing `samples` to every channel, signalling `done`
+/// once they have all been handed to the device.
+fn build<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    samples: Arc<[f32]>,
+    done: mpsc::Sender<()>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let position = AtomicUsize::new(0);
+    let err_fn = |e| tracing::error!("audio output stream error: {e}");
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                let start = position.load(Ordering::Relaxed);
+                for (i, frame) in data.chunks_mut(channels).enumerate() {
+                    let s = samples.get(start + i).copied().unwrap_or(0.0);
+                    frame.fill(T::from_sample(s));
+                }
+                let end = start + data.len() / channels;
+                position.store(end, Ordering::Relaxed);
+                if end >= samples.len() {
+                    let _ = done.send(());
+                }
+            },
+            err_fn,
+            None,
+        )
+        .map_err(|e| ShoutError::Device(format!("failed to build output stream: {e}")))
+}
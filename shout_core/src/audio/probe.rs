@@ -0,0 +1,62 @@
+//! Audio length without decoding: from the container header when it states
+//! the number of frames, otherwise by adding up packet durations, which
+//! needs no codec work. Either is far cheaper than a full decode when
+//! filling in durations for a large corpus.
+
+use std::path::Path;
+
+use symphonia::core::codecs::CODEC_TYPE_NULL;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::units::TimeBase;
+
+use super::decoder::open_format;
+use crate::errors::{Result, ShoutError};
+
+/// Duration of the first audio track of `path` in milliseconds.
+pub fn duration_ms<P: AsRef<Path>>(path: P) -> Result<u64> {
+    let path = path.as_ref();
+    let mut format = open_format(path)?;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| ShoutError::UnsupportedFormat("no supported audio tracks found".into()))?;
+    let params = &track.codec_params;
+    if let (Some(frames), Some(rate)) = (params.n_frames, params.sample_rate)
+        && rate > 0
+    {
+        return Ok(frames * 1000 / rate as u64);
+    }
+
+    let track_id = track.id;
+    let time_base = params
+        .time_base
+        .or_else(|| params.sample_rate.map(|rate| TimeBase::new(1, rate)))
+        .ok_or_else(|| {
+            ShoutError::Decode(format!("{}: no time base to measure by", path.display()))
+        })?;
+    let mut ticks = 0u64;
+    loop {
+        match format.next_packet() {
+            Ok(packet) if packet.track_id() == track_id => ticks += packet.dur(),
+            Ok(_) => {}
+            Err(SymphoniaError::IoError(_)) => break, // end of file
+            Err(e) => return Err(ShoutError::Decode(format!("{}: {e}", path.display()))),
+        }
+    }
+    let time = time_base.calc_time(ticks);
+    Ok(time.seconds * 1000 + (time.frac * 1000.0).round() as u64)
+}
+
+#[cfg(all(test, feature = "codec-wav"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_decoded_length() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden/tones.wav");
+        let decoded = crate::audio::decoder::decode_to_f32_mono_16k(&path).unwrap();
+        let expected = decoded.len() as u64 * 1000 / 16_000;
+        assert!(duration_ms(&path).unwrap().abs_diff(expected) <= 1);
+    }
+}
@@ -0,0 +1,207 @@
+use std::fmt::Display;
+
+use audioadapter_buffers::direct::InterleavedSlice;
+use rubato::{
+    Async, Fft, FixedAsync, FixedSync, Resampler, SincInterpolationParameters,
+    SincInterpolationType, WindowFunction, calculate_cutoff,
+};
+
+use crate::errors::{Result, ShoutError};
+
+/// Input frames per resampler chunk unless configured otherwise.
+pub const DEFAULT_RESAMPLER_CHUNK: usize = 1024;
+
+/// Which rubato resampler converts between sample rates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResamplerKind {
+    /// FFT-based; fast and good enough for speech.
+    #[default]
+    Fft,
+
+    /// Band-limited sinc interpolation at a fixed ratio; slower, with less
+    /// aliasing near the new Nyquist frequency.
+    SincFixed { quality: SincQuality },
+}
+
+/// Length and precision of the sinc filter, trading speed for accuracy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SincQuality {
+    Fast,
+    #[default]
+    Balanced,
+    Best,
+}
+
+impl SincQuality {
+    fn parameters(self) -> SincInterpolationParameters {
+        let (sinc_len, oversampling_factor, interpolation) = match self {
+            SincQuality::Fast => (64, 128, SincInterpolationType::Linear),
+            SincQuality::Balanced => (128, 256, SincInterpolationType::Cubic),
+            SincQuality::Best => (256, 512, SincInterpolationType::Cubic),
+        };
+        let window = WindowFunction::BlackmanHarris2;
+        SincInterpolationParameters {
+            sinc_len,
+            f_cutoff: calculate_cutoff(sinc_len, window),
+            oversampling_factor,
+            interpolation,
+            window,
+        }
+    }
+}
+
+/// A mono `kind` resampler from `sr_in` to `sr_out` that takes `chunk_size`
+/// input frames at a time.
+pub(crate) fn new_resampler(
+    kind: ResamplerKind,
+    sr_in: u32,
+    sr_out: u32,
+    chunk_size: usize,
+) -> Result<Box<dyn Resampler<f32>>> {
+    if sr_in == 0 || sr_out == 0 || chunk_size == 0 {
+        return Err(ShoutError::InvalidArgument(format!(
+            "cannot resample {sr_in} Hz to {sr_out} Hz in chunks of {chunk_size}"
+        )));
+    }
+    let (sr_in, sr_out) = (sr_in as usize, sr_out as usize);
+    Ok(match kind {
+        ResamplerKind::Fft => Box::new(
+            Fft::<f32>::new(sr_in, sr_out, chunk_size, 1, 1, FixedSync::Input)
+                .map_err(resample_error("failed to construct FFT resampler"))?,
+        ),
+        ResamplerKind::SincFixed { quality } => Box::new(
+            Async::<f32>::new_sinc(
+                sr_out as f64 / sr_in as f64,
+                1.0,
+                &quality.parameters(),
+                chunk_size,
+                1,
+                FixedAsync::Input,
+            )
+            .map_err(resample_error("failed to construct sinc resampler"))?,
+        ),
+    })
+}
+
+/// Incremental mono resampler for audio that arrives in pieces (microphone, streaming decode).
+///
+/// Input of any length is buffered until a full resampler chunk is available;
+/// `finish` flushes the tail at end of stream.
+pub struct StreamResampler {
+    /// `None` when input and output rates match and samples pass straight through.
+    resampler: Option<Box<dyn Resampler<f32>>>,
+    pending: Vec<f32>,
+    out_buf: Vec<f32>,
+
+    /// Output samples still to drop to compensate the resampler's delay.
+    to_skip: usize,
+
+    sr_in: usize,
+    sr_out: usize,
+    consumed_in: usize,
+    produced_out: usize,
+}
+
+impl StreamResampler {
+    pub fn new(sr_in: u32, sr_out: u32) -> Result<Self> {
+        Self::with_kind(sr_in, sr_out, ResamplerKind::Fft, DEFAULT_RESAMPLER_CHUNK)
+    }
+
+    /// A `kind` resampler that takes `chunk_size` input frames at a time.
+    pub fn with_kind(
+        sr_in: u32,
+        sr_out: u32,
+        kind: ResamplerKind,
+        chunk_size: usize,
+    ) -> Result<Self> {
+        let resampler = if sr_in == sr_out {
+            None
+        } else {
+            Some(new_resampler(kind, sr_in, sr_out, chunk_size)?)
+        };
+        let (sr_in, sr_out) = (sr_in as usize, sr_out as usize);
+
+        let out_len = resampler
+            .as_ref()
+            .map(|r| r.output_frames_max())
+            .unwrap_or(0);
+        let to_skip = resampler.as_ref().map(|r| r.output_delay()).unwrap_or(0);
+
+        Ok(Self {
+            resampler,
+            pending: Vec::new(),
+            out_buf: vec![0.0; out_len],
+            to_skip,
+            sr_in,
+            sr_out,
+            consumed_in: 0,
+            produced_out: 0,
+        })
+    }
+
+    /// Feed mono samples and return every output sample that is complete so far.
+    pub fn push(&mut self, input: &[f32]) -> Result<Vec<f32>> {
+        let Some(resampler) = self.resampler.as_mut() else {
+            return Ok(input.to_vec());
+        };
+
+        self.pending.extend_from_slice(input);
+        let mut out = Vec::new();
+
+        loop {
+            let needed = resampler.input_frames_next();
+            if self.pending.len() < needed {
+                break;
+            }
+
+            let input_adapter = InterleavedSlice::new(&self.pending[..needed], 1, needed)
+                .map_err(resample_error("bad input adapter"))?;
+            let out_len = self.out_buf.len();
+            let mut output_adapter = InterleavedSlice::new_mut(&mut self.out_buf, 1, out_len)
+                .map_err(resample_error("bad output adapter"))?;
+
+            let (read, written) = resampler
+                .process_into_buffer(&input_adapter, &mut output_adapter, None)
+                .map_err(resample_error("resampling failed"))?;
+
+            self.pending.drain(..read);
+            self.consumed_in += read;
+
+            let skip = self.to_skip.min(written);
+            self.to_skip -= skip;
+            out.extend_from_slice(&self.out_buf[skip..written]);
+        }
+
+        self.produced_out += out.len();
+        Ok(out)
+    }
+
+    /// Flush buffered input at end of stream. Output is trimmed so the total length
+    /// matches the input duration.
+    pub fn finish(&mut self) -> Result<Vec<f32>> {
+        if self.resampler.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let total_in = self.consumed_in + self.pending.len();
+        let expected_out = total_in * self.sr_out / self.sr_in;
+        let already_out = self.produced_out;
+
+        // Feed silence until everything (including the resampler delay) has come out.
+        let mut out = Vec::new();
+        while already_out + out.len() < expected_out {
+            let needed = self.resampler.as_ref().map_or(1, |r| r.input_frames_next());
+            out.extend(self.push(&vec![0.0f32; needed])?);
+        }
+
+        out.truncate(expected_out.saturating_sub(already_out));
+        self.produced_out = already_out + out.len();
+        self.pending.clear();
+        Ok(out)
+    }
+}
+
+/// Maps a rubato or buffer adapter error to [`ShoutError::Resample`].
+pub(crate) fn resample_error<E: Display>(what: &'static str) -> impl Fn(E) -> ShoutError {
+    move |e| ShoutError::Resample(format!("{what}: {e}"))
+}
@@ -0,0 +1,89 @@
+//! Cutting leading and trailing silence off decoded clips, which otherwise
+//! spend mel frames and context length on seconds of dead air.
+
+use std::ops::Range;
+
+/// How quiet silence is and how much of it to keep around the sound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrimOptions {
+    /// Frames whose RMS level is below this, in dBFS, are silent.
+    pub threshold_db: f64,
+    /// Length of the frames the level is measured over.
+    pub frame_ms: u32,
+    /// Silence kept before the first loud frame.
+    pub lead_margin_ms: u32,
+    /// Silence kept after the last loud frame.
+    pub trail_margin_ms: u32,
+}
+
+impl Default for TrimOptions {
+    fn default() -> Self {
+        Self {
+            threshold_db: -40.0,
+            frame_ms: 20,
+            lead_margin_ms: 100,
+            trail_margin_ms: 100,
+        }
+    }
+}
+
+/// The samples of `samples` from the first to the last frame at or above the
+/// threshold, widened by the margins; `None` if every frame is silent.
+pub fn sound_bounds(
+    samples: &[f32],
+    sample_rate: u32,
+    options: &TrimOptions,
+) -> Option<Range<usize>> {
+    let per_ms = |ms: u32| sample_rate as usize * ms as usize / 1000;
+    let frame = per_ms(options.frame_ms).max(1);
+    let threshold = 10f64.powf(options.threshold_db / 20.0);
+    let loud = |chunk: &[f32]| {
+        let sum: f64 = chunk.iter().map(|&s| s as f64 * s as f64).sum();
+        (sum / chunk.len() as f64).sqrt() >= threshold
+    };
+
+    let first = samples.chunks(frame).position(loud)?;
+    let last = samples.chunks(frame).rposition(loud)?;
+    let start = (first * frame).saturating_sub(per_ms(options.lead_margin_ms));
+    let end = ((last + 1) * frame + per_ms(options.trail_margin_ms)).min(samples.len());
+    Some(start..end)
+}
+
+/// Cut leading and trailing silence off `samples`; returns the range of the
+/// original samples that was kept. An all-silent clip is left whole.
+pub fn trim_silence(
+    samples: &mut Vec<f32>,
+    sample_rate: u32,
+    options: &TrimOptions,
+) -> Range<usize> {
+    let Some(bounds) = sound_bounds(samples, sample_rate, options) else {
+        return 0..samples.len();
+    };
+    samples.truncate(bounds.end);
+    samples.drain(..bounds.start);
+    bounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_sound_and_its_margins() {
+        // 1 s of silence, 0.5 s of tone, 2 s of near-silence at 16 kHz.
+        let mut clip = vec![0.0f32; 16_000];
+        clip.extend((0..8000).map(|i| 0.3 * (i as f32 * 0.2).sin()));
+        clip.extend(std::iter::repeat_n(0.001, 32_000));
+
+        let kept = trim_silence(&mut clip, 16_000, &TrimOptions::default());
+        assert_eq!(kept, 16_000 - 1600..24_000 + 1600);
+        assert_eq!(clip.len(), 8000 + 3200);
+
+        let mut silent = vec![0.0f32; 1000];
+        assert_eq!(
+            trim_silence(&mut silent, 16_000, &TrimOptions::default()),
+            0..1000
+        );
+        assert_eq!(silent.len(), 1000);
+    }
+}
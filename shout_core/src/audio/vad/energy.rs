@@ -0,0 +1,52 @@
+use super::SpeechDetector;
+use crate::errors::Result;
+
+/// A baseline detector from frame energy and zero-crossing rate: frames
+/// above `threshold_db` are speech, and so are somewhat quieter frames that
+/// cross zero as often as fricatives (s, f, sh) do. Fine for clean read
+/// speech; noise at speech level counts as speech.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnergyVad {
+    /// RMS level in dBFS from which a frame is speech.
+    pub threshold_db: f32,
+    /// How far below `threshold_db` fricative-like frames still count.
+    pub fricative_range_db: f32,
+    /// Share of consecutive samples changing sign from which a quieter
+    /// frame sounds like a fricative.
+    pub fricative_zcr: f32,
+    /// Frame length in samples at 16 kHz.
+    pub frame_len: usize,
+}
+
+impl Default for EnergyVad {
+    fn default() -> Self {
+        Self {
+            threshold_db: -40.0,
+            fricative_range_db: 10.0,
+            fricative_zcr: 0.3,
+            frame_len: 480,
+        }
+    }
+}
+
+impl SpeechDetector for EnergyVad {
+    fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    fn speech_probability(&mut self, frame: &[f32]) -> Result<f32> {
+        let mean_square = frame.iter().map(|s| s * s).sum::<f32>() / frame.len().max(1) as f32;
+        let db = 10.0 * mean_square.max(1e-12).log10();
+        let crossings = frame
+            .windows(2)
+            .filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0))
+            .count();
+        let zcr = crossings as f32 / frame.len().saturating_sub(1).max(1) as f32;
+
+        let speech = db >= self.threshold_db
+            || (db >= self.threshold_db - self.fricative_range_db && zcr >= self.fricative_zcr);
+        Ok(if speech { 1.0 } else { 0.0 })
+    }
+
+    fn reset(&mut self) {}
+}
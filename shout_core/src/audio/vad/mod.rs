@@ -0,0 +1,231 @@
+//! Voice activity detection: where in a clip someone speaks, for cutting
+//! silence and noise out of training data and for chunking long recordings
+//! at pauses at inference time.
+//!
+//! A [`SpeechDetector`] scores fixed-length frames of 16 kHz audio, and
+//! [`detect_speech`] turns the scores into [`SpeechSegment`]s. [`EnergyVad`]
+//! needs nothing but the audio; [`SileroVad`] (feature `vad-silero`) runs the
+//! Silero VAD model and copes far better with noise, music and quiet speech.
+
+mod energy;
+#[cfg(feature = "vad-silero")]
+mod silero;
+
+use std::ops::Range;
+
+pub use energy::EnergyVad;
+#[cfg(feature = "vad-silero")]
+pub use silero::SileroVad;
+
+use crate::errors::Result;
+use crate::features::SAMPLE_RATE;
+
+/// Scores frames of 16 kHz mono audio for speech.
+pub trait SpeechDetector {
+    /// Samples per frame.
+    fn frame_len(&self) -> usize;
+
+    /// Probability in `0.0..=1.0` that `frame`, exactly
+    /// [`frame_len`](Self::frame_len) samples long, holds speech.
+    fn speech_probability(&mut self, frame: &[f32]) -> Result<f32>;
+
+    /// Forget what earlier frames left behind, before a new clip.
+    fn reset(&mut self);
+}
+
+/// A stretch of speech, in milliseconds from the start of the clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeechSegment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+impl SpeechSegment {
+    /// The segment's samples in a clip at `sample_rate`.
+    pub fn samples(&self, sample_rate: u32) -> Range<usize> {
+        let at = |ms: u64| (ms * sample_rate as u64 / 1000) as usize;
+        at(self.start_ms)..at(self.end_ms)
+    }
+}
+
+/// How frame scores become segments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VadOptions {
+    /// Frames scoring at least this start or continue speech. Speech ends
+    /// only on frames 0.15 below it, so scores hovering around the threshold
+    /// do not chop a segment up.
+    pub threshold: f32,
+    /// Shorter stretches of speech are dropped as clicks and coughs.
+    pub min_speech_ms: u32,
+    /// Pauses shorter than this stay inside a segment.
+    pub min_silence_ms: u32,
+    /// Audio kept on both sides of each segment; segments that then overlap
+    /// are merged.
+    pub speech_pad_ms: u32,
+}
+
+impl Default for VadOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 0.5,
+            min_speech_ms: 250,
+            min_silence_ms: 100,
+            speech_pad_ms: 30,
+        }
+    }
+}
+
+/// The speech in `samples` (16 kHz mono) according to `detector`, in order.
+pub fn detect_speech(
+    detector: &mut dyn SpeechDetector,
+    samples: &[f32],
+    options: &VadOptions,
+) -> Result<Vec<SpeechSegment>> {
+    detector.reset();
+    let frame = detector.frame_len();
+    let per_ms = |ms: u32| SAMPLE_RATE as usize * ms as usize / 1000;
+    let release = (options.threshold - 0.15).max(0.0);
+
+    let mut speech: Vec<Range<usize>> = Vec::new();
+    let mut keep = |range: Range<usize>| {
+        if range.len() >= per_ms(options.min_speech_ms) {
+            speech.push(range);
+        }
+    };
+    let mut start = None;
+    let mut silent_since = None;
+    let mut padded = vec![0.0; frame];
+    for (i, chunk) in samples.chunks(frame).enumerate() {
+        let position = i * frame;
+        let probability = if chunk.len() == frame {
+            detector.speech_probability(chunk)?
+        } else {
+            padded[..chunk.len()].copy_from_slice(chunk);
+            padded[chunk.len()..].fill(0.0);
+            detector.speech_probability(&padded)?
+        };
+
+        if probability >= options.threshold {
+            start.get_or_insert(position);
+            silent_since = None;
+        } else if probability < release
+            && let Some(begin) = start
+        {
+            let since = *silent_since.get_or_insert(position);
+            if position + frame - since >= per_ms(options.min_silence_ms) {
+                keep(begin..since);
+                start = None;
+                silent_since = None;
+            }
+        }
+    }
+    if let Some(begin) = start {
+        keep(begin..silent_since.unwrap_or(samples.len()));
+    }
+
+    let pad = per_ms(options.speech_pad_ms);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(speech.len());
+    for range in speech {
+        let (begin, end) = (
+            range.start.saturating_sub(pad),
+            (range.end + pad).min(samples.len()),
+        );
+        match merged.last_mut() {
+            Some(last) if begin <= last.end => last.end = end,
+            _ => merged.push(begin..end),
+        }
+    }
+    let ms = |sample: usize| sample as u64 * 1000 / SAMPLE_RATE as u64;
+    Ok(merged
+        .into_iter()
+        .map(|range| SpeechSegment {
+            start_ms: ms(range.start),
+            end_ms: ms(range.end),
+        })
+        .collect())
+}
+
+/// Consecutive `segments` grouped into chunks of at most `max_ms`, each
+/// running from the start of its first segment to the end of its last, so
+/// long recordings are cut only in pauses. A segment longer than `max_ms` is
+/// cut into equal parts.
+pub fn chunk_speech(segments: &[SpeechSegment], max_ms: u64) -> Vec<SpeechSegment> {
+    let max_ms = max_ms.max(1);
+    let mut chunks: Vec<SpeechSegment> = Vec::new();
+    for segment in segments {
+        let length = segment.end_ms - segment.start_ms;
+        if length > max_ms {
+            let parts = length.div_ceil(max_ms);
+            chunks.extend((0..parts).map(|i| SpeechSegment {
+                start_ms: segment.start_ms + length * i / parts,
+                end_ms: segment.start_ms + length * (i + 1) / parts,
+            }));
+            continue;
+        }
+        match chunks.last_mut() {
+            Some(chunk) if segment.end_ms - chunk.start_ms <= max_ms => {
+                chunk.end_ms = segment.end_ms
+            }
+            _ => chunks.push(*segment),
+        }
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_two_utterances_with_energy() {
+        let tone =
+            |secs: f32| (0..(secs * 16_000.0) as usize).map(|i| 0.2 * (i as f32 * 0.3).sin());
+        let silence = |secs: f32| std::iter::repeat_n(0.0, (secs * 16_000.0) as usize);
+        let clip: Vec<f32> = silence(1.0)
+            .chain(tone(1.0))
+            .chain(silence(1.0))
+            .chain(tone(0.5))
+            .chain(silence(0.1))
+            .chain(tone(0.1))
+            .collect();
+
+        let segments = detect_speech(&mut EnergyVad::default(), &clip, &VadOptions::default());
+        let segments = segments.unwrap();
+        assert_eq!(
+            segments,
+            [
+                SpeechSegment {
+                    start_ms: 960,
+                    end_ms: 2040
+                },
+                // The 100 ms pause is too short to end the second one.
+                SpeechSegment {
+                    start_ms: 2970,
+                    end_ms: 3700
+                },
+            ]
+        );
+        assert_eq!(segments[0].samples(8000), 7680..16_320);
+    }
+
+    #[test]
+    fn chunks_cut_only_between_segments() {
+        let at = |start_ms, end_ms| SpeechSegment { start_ms, end_ms };
+        let speech = [
+            at(0, 10_000),
+            at(12_000, 25_000),
+            at(26_000, 40_000),
+            at(41_000, 111_000),
+        ];
+        assert_eq!(
+            chunk_speech(&speech, 30_000),
+            [
+                at(0, 25_000),
+                at(26_000, 40_000),
+                at(41_000, 64_333),
+                at(64_333, 87_666),
+                at(87_666, 111_000),
+            ]
+        );
+    }
+}
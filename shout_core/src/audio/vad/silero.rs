@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use ort::session::Session;
+use ort::value::Tensor;
+
+use super::SpeechDetector;
+use crate::errors::{Result, ShoutError};
+use crate::features::SAMPLE_RATE;
+
+/// Samples the model scores at a time at 16 kHz.
+const WINDOW: usize = 512;
+
+/// Samples of the previous window the model sees in front of each one.
+const CONTEXT: usize = 64;
+
+/// Shape of the recurrent state carried between windows.
+const STATE_SHAPE: [usize; 3] = [2, 1, 128];
+
+/// The Silero VAD v5 model (`silero_vad.onnx` from
+/// <https://github.com/snakers4/silero-vad>) run with ONNX Runtime.
+pub struct SileroVad {
+    session: Session,
+    state: Vec<f32>,
+    context: Vec<f32>,
+}
+
+impl SileroVad {
+    pub fn load(path: &Path) -> Result<Self> {
+        let session = Session::builder()
+            .and_then(|builder| builder.commit_from_file(path))
+            .map_err(|e| {
+                ShoutError::Model(format!("failed to load Silero VAD {}: {e}", path.display()))
+            })?;
+        Ok(Self {
+            session,
+            state: vec![0.0; STATE_SHAPE.iter().product()],
+            context: vec![0.0; CONTEXT],
+        })
+    }
+}
+
+impl SpeechDetector for SileroVad {
+    fn frame_len(&self) -> usize {
+        WINDOW
+    }
+
+    fn speech_probability(&mut self, frame: &[f32]) -> Result<f32> {
+        let mut input = Vec::with_capacity(CONTEXT + WINDOW);
+        input.extend_from_slice(&self.context);
+        input.extend_from_slice(frame);
+        self.context.copy_from_slice(&frame[WINDOW - CONTEXT..]);
+
+        let inputs = ort::inputs![
+            "input" => Tensor::from_array(([1, CONTEXT + WINDOW], input)).map_err(model_error)?,
+            "state" => Tensor::from_array((STATE_SHAPE, self.state.clone())).map_err(model_error)?,
+            "sr" => Tensor::from_array(([0usize; 0], vec![SAMPLE_RATE as i64]))
+                .map_err(model_error)?,
+        ];
+        let outputs = self.session.run(inputs).map_err(model_error)?;
+        let (_, probability) = outputs["output"]
+            .try_extract_tensor::<f32>()
+            .map_err(model_error)?;
+        let (_, state) = outputs["stateN"]
+            .try_extract_tensor::<f32>()
+            .map_err(model_error)?;
+        self.state.copy_from_slice(state);
+        Ok(probability[0])
+    }
+
+    fn reset(&mut self) {
+        self.state.fill(0.0);
+        self.context.fill(0.0);
+    }
+}
+
+fn model_error(error: ort::Error) -> ShoutError {
+    ShoutError::Model(format!("Silero VAD: {error}"))
+}
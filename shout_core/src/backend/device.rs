@@ -0,0 +1,165 @@
+use std::fmt;
+use std::str::FromStr;
+
+use candle_core::Device;
+
+use super::memory::available_memory;
+use crate::errors::{Result, ShoutError};
+
+/// CUDA if compiled in and present, then Metal, then CPU.
+pub fn best_device() -> Result<Device> {
+    let device_error = |e: candle_core::Error| ShoutError::Device(e.to_string());
+    if candle_core::utils::cuda_is_available() {
+        return Device::new_cuda(0).map_err(device_error);
+    }
+    if candle_core::utils::metal_is_available() {
+        return Device::new_metal(0).map_err(device_error);
+    }
+    Ok(Device::Cpu)
+}
+
+/// Device requested on the command line: `auto`, `cpu`, `cuda[:N]` or `metal[:N]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceSpec {
+    #[default]
+    Auto,
+    Cpu,
+    Cuda(usize),
+    Metal(usize),
+}
+
+impl FromStr for DeviceSpec {
+    type Err = ShoutError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, ordinal) = match s.split_once(':') {
+            Some((kind, n)) => match n.parse() {
+                Ok(n) => (kind, n),
+                Err(_) => {
+                    let msg = format!("invalid device ordinal in '{s}'");
+                    return Err(ShoutError::InvalidArgument(msg));
+                }
+            },
+            None => (s, 0),
+        };
+        match kind.to_ascii_lowercase().as_str() {
+            "auto" => Ok(DeviceSpec::Auto),
+            "cpu" => Ok(DeviceSpec::Cpu),
+            "cuda" | "gpu" => Ok(DeviceSpec::Cuda(ordinal)),
+            "metal" | "mps" => Ok(DeviceSpec::Metal(ordinal)),
+            _ => Err(ShoutError::InvalidArgument(format!(
+                "unknown device '{s}' (expected auto, cpu, cuda[:N] or metal[:N])"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for DeviceSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceSpec::Auto => write!(f, "auto"),
+            DeviceSpec::Cpu => write!(f, "cpu"),
+            DeviceSpec::Cuda(n) => write!(f, "cuda:{n}"),
+            DeviceSpec::Metal(n) => write!(f, "metal:{n}"),
+        }
+    }
+}
+
+/// The device a model will run on.
+pub struct SelectedDevice {
+    pub device: Device,
+
+    /// Why the requested accelerator was not used, if the CPU was chosen instead.
+    pub fallback: Option<String>,
+}
+
+/// Open the device for `spec`, falling back to the CPU when the accelerator is
+/// missing, fails to initialize or has less than `required_bytes` free.
+pub fn select_device(spec: DeviceSpec, required_bytes: Option<u64>) -> Result<SelectedDevice> {
+    let candidates = match spec {
+        DeviceSpec::Cpu => vec![],
+        DeviceSpec::Cuda(_) | DeviceSpec::Metal(_) => vec![spec],
+        DeviceSpec::Auto => {
+            let mut c = Vec::new();
+            if candle_core::utils::cuda_is_available() {
+                c.push(DeviceSpec::Cuda(0));
+            }
+            if candle_core::utils::metal_is_available() {
+                c.push(DeviceSpec::Metal(0));
+            }
+            c
+        }
+    };
+
+    let mut reasons = Vec::new();
+    for candidate in candidates {
+        let opened = match candidate {
+            DeviceSpec::Cuda(n) => Device::new_cuda(n),
+            DeviceSpec::Metal(n) => Device::new_metal(n),
+            DeviceSpec::Auto | DeviceSpec::Cpu => unreachable!("not an accelerator"),
+        };
+        let device = match opened {
+            Ok(device) => device,
+            Err(e) => {
+                reasons.push(format!("{candidate}: {e}"));
+                continue;
+            }
+        };
+
+        if let (Some(required), Some(free)) = (required_bytes, available_memory(&device))
+            && required > free
+        {
+            reasons.push(format!(
+                "{candidate}: needs ~{} MiB, {} MiB free",
+                required >> 20,
+                free >> 20
+            ));
+            continue;
+        }
+        return Ok(SelectedDevice {
+            device,
+            fallback: None,
+        });
+    }
+
+    // `auto` on a machine without accelerators tried nothing and reports nothing.
+    Ok(SelectedDevice {
+        device: Device::Cpu,
+        fallback: (!reasons.is_empty()).then(|| reasons.join("; ")),
+    })
+}
+
+/// Limit the CPU threads used by the inference kernels.
+///
+/// # Safety
+///
+/// Sets the `RAYON_NUM_THREADS` environment variable read by candle, so it must
+/// be called before any other thread is started.
+pub unsafe fn set_cpu_threads(n: usize) {
+    // SAFETY: the caller guarantees no other thread reads the environment concurrently.
+    unsafe { std::env::set_var("RAYON_NUM_THREADS", n.max(1).to_string()) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_device_specs() {
+        assert_eq!("auto".parse::<DeviceSpec>().unwrap(), DeviceSpec::Auto);
+        assert_eq!("CPU".parse::<DeviceSpec>().unwrap(), DeviceSpec::Cpu);
+        assert_eq!("cuda".parse::<DeviceSpec>().unwrap(), DeviceSpec::Cuda(0));
+        assert_eq!("cuda:1".parse::<DeviceSpec>().unwrap(), DeviceSpec::Cuda(1));
+        assert_eq!("metal".parse::<DeviceSpec>().unwrap(), DeviceSpec::Metal(0));
+        assert!("tpu".parse::<DeviceSpec>().is_err());
+        assert!("cuda:x".parse::<DeviceSpec>().is_err());
+        assert_eq!(DeviceSpec::Cuda(1).to_string(), "cuda:1");
+    }
+
+    #[test]
+    fn cpu_request_never_reports_a_fallback() {
+        let selected = select_device(DeviceSpec::Cpu, Some(u64::MAX)).unwrap();
+        assert!(selected.device.is_cpu());
+        assert!(selected.fallback.is_none());
+    }
+}
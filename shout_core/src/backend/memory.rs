@@ -0,0 +1,141 @@
+//! Rough memory requirements of a model, checked against what a device has free
+//! before loading.
+
+use std::path::Path;
+
+use candle_core::Device;
+
+use crate::config::model::ModelConfig;
+use crate::errors::Result;
+
+const F32_BYTES: u64 = 4;
+
+/// Estimated bytes needed to run a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    /// Weights as held in memory after loading.
+    pub weights: u64,
+
+    /// Peak activations and KV caches while decoding one window.
+    pub activations: u64,
+}
+
+impl MemoryEstimate {
+    pub fn total(&self) -> u64 {
+        self.weights + self.activations
+    }
+
+    /// Estimate for f32 weights, decoding with `beams` hypotheses.
+    pub fn for_config(config: &ModelConfig, beams: usize) -> Self {
+        Self {
+            weights: parameter_count(config) * F32_BYTES,
+            activations: activation_floats(config, beams.max(1) as u64) * F32_BYTES,
+        }
+    }
+
+    /// Estimate for the checkpoint in a model directory. GGUF weights stay
+    /// quantized, so their file size stands in for the loaded size.
+    pub fn for_model_dir<P: AsRef<Path>>(dir: P, beams: usize) -> Result<Self> {
+        let dir = dir.as_ref();
+        let config = ModelConfig::from_file(dir.join("config.json"))?;
+        let mut estimate = Self::for_config(&config, beams);
+
+        if !dir.join("model.safetensors").exists() {
+            let gguf = std::fs::read_dir(dir)?
+                .filter_map(|e| e.ok())
+                .find(|e| e.path().extension().is_some_and(|x| x == "gguf"));
+            if let Some(entry) = gguf {
+                estimate.weights = entry.metadata()?.len();
+            }
+        }
+        Ok(estimate)
+    }
+}
+
+/// Number of parameters of an encoder-decoder model with this configuration.
+pub fn parameter_count(c: &ModelConfig) -> u64 {
+    let (d_audio, d_text) = (c.n_audio_state as u64, c.n_text_state as u64);
+
+    // Per layer: attention (q, k, v, out; k without bias), a 4x MLP and layer norms.
+    let audio_layer = 12 * d_audio * d_audio + 12 * d_audio;
+    let encoder = c.n_mels as u64 * d_audio * 3
+        + d_audio * d_audio * 3
+        + 2 * d_audio
+        + c.n_audio_ctx as u64 * d_audio
+        + c.n_audio_layer as u64 * audio_layer
+        + 2 * d_audio;
+
+    // Self- and cross-attention, MLP and three layer norms.
+    let text_layer = 16 * d_text * d_text + 17 * d_text;
+    let decoder = (c.n_vocab + c.n_text_ctx) as u64 * d_text
+        + c.n_text_layer as u64 * text_layer
+        + 2 * d_text;
+
+    let ctc = c.ctc_vocab.map_or(0, |v| (d_audio + 1) * v as u64);
+    encoder + decoder + ctc
+}
+
+fn activation_floats(c: &ModelConfig, beams: u64) -> u64 {
+    let audio_ctx = c.n_audio_ctx as u64;
+    let d_audio = c.n_audio_state as u64;
+    let d_text = c.n_text_state as u64;
+
+    // Attention scores of one encoder layer plus the MLP's hidden states.
+    let encoder = c.n_audio_head as u64 * audio_ctx * audio_ctx + 8 * audio_ctx * d_audio;
+
+    // Cross- and self-attention key/value caches for every hypothesis.
+    let kv = c.n_text_layer as u64 * 2 * (audio_ctx + c.n_text_ctx as u64) * d_text;
+    let logits = c.n_vocab as u64;
+
+    encoder + beams * (kv + logits)
+}
+
+/// Free memory on `device` in bytes, if it can be queried.
+pub fn available_memory(device: &Device) -> Option<u64> {
+    match device {
+        Device::Cpu => cpu_available_memory(),
+        #[cfg(feature = "cuda")]
+        Device::Cuda(_) => candle_core::cuda_backend::cudarc::driver::result::mem_get_info()
+            .ok()
+            .map(|(free, _total)| free as u64),
+        // Metal shares system memory; the OS pages rather than failing allocations.
+        _ => None,
+    }
+}
+
+/// `MemAvailable` from `/proc/meminfo` (Linux only).
+fn cpu_available_memory() -> Option<u64> {
+    proc_kib("/proc/meminfo", "MemAvailable:")
+}
+
+/// Peak resident memory of this process so far (`VmHWM`, Linux only).
+pub fn peak_process_memory() -> Option<u64> {
+    proc_kib("/proc/self/status", "VmHWM:")
+}
+
+/// A `<key> <n> kB` line of a procfs file, in bytes.
+fn proc_kib(path: &str, key: &str) -> Option<u64> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let line = contents.lines().find(|l| l.starts_with(key))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiny_has_about_38m_parameters() {
+        let params = parameter_count(&ModelConfig::tiny());
+        assert!((37_000_000..39_000_000).contains(&params), "{params}");
+    }
+
+    #[test]
+    fn beams_grow_the_activation_estimate() {
+        let greedy = MemoryEstimate::for_config(&ModelConfig::tiny(), 1);
+        let beam = MemoryEstimate::for_config(&ModelConfig::tiny(), 5);
+        assert_eq!(greedy.weights, beam.weights);
+        assert!(beam.activations > greedy.activations);
+    }
+}
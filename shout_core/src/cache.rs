@@ -0,0 +1,180 @@
+//! On-disk cache of finished transcripts.
+//!
+//! Entries are keyed by a hash of the audio bytes, a fingerprint of the model
+//! directory and a caller-provided description of the decoding settings, so a
+//! different model or different settings never hit an old entry. Stale entries
+//! are not deleted eagerly; they age out of the size-bounded, least recently
+//! used store.
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+use crate::errors::{IoContext, Result};
+use crate::transcript::Transcript;
+
+/// Files of a model directory whose contents are hashed; everything else
+/// (weights) is identified by name, size and modification time.
+const HASHED_MODEL_FILES: &[&str] = &["config.json", "tokenizer.json"];
+
+#[derive(Debug, Clone)]
+pub struct ResultCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// SHA-256 of `bytes`, hex encoded.
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+/// SHA-256 of a file's contents, hex encoded.
+pub fn hash_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Fingerprint of a model directory that changes whenever the model does.
+pub fn model_fingerprint<P: AsRef<Path>>(model_dir: P) -> Result<String> {
+    let model_dir = model_dir.as_ref();
+    let mut entries: Vec<_> = fs::read_dir(model_dir)
+        .io_context(|| format!("Failed to read model dir: {}", model_dir.display()))?
+        .collect::<std::io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        let meta = entry.metadata()?;
+        if !meta.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        hasher.update(name.as_bytes());
+        if HASHED_MODEL_FILES.contains(&name.as_str()) {
+            hasher.update(fs::read(entry.path())?);
+        } else {
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos());
+            hasher.update(meta.len().to_le_bytes());
+            hasher.update(modified.to_le_bytes());
+        }
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+impl ResultCache {
+    /// Cache in `dir`, holding at most `max_bytes` of entries.
+    pub fn open<P: Into<PathBuf>>(dir: P, max_bytes: u64) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .io_context(|| format!("Failed to create cache dir: {}", dir.display()))?;
+        Ok(Self { dir, max_bytes })
+    }
+
+    /// Cache key for audio with hash `audio_hash`, decoded with the model and
+    /// settings described by `context`.
+    pub fn key(audio_hash: &str, context: &str) -> String {
+        hash_bytes(format!("{audio_hash}\n{context}").as_bytes())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(key).with_extension("json")
+    }
+
+    /// The cached transcript for `key`, if any. A hit refreshes the entry's age.
+    pub fn get(&self, key: &str) -> Option<Transcript> {
+        let path = self.entry_path(key);
+        let file = File::options().read(true).write(true).open(&path).ok()?;
+        let transcript = serde_json::from_reader(BufReader::new(&file)).ok()?;
+        let _ = file.set_modified(SystemTime::now());
+        Some(transcript)
+    }
+
+    /// Store `transcript` under `key` and evict old entries beyond the size limit.
+    pub fn put(&self, key: &str, transcript: &Transcript) -> Result<()> {
+        let path = self.entry_path(key);
+        let tmp = path.with_extension("json.tmp");
+        let file = File::create(&tmp)
+            .io_context(|| format!("Failed to write cache entry: {}", tmp.display()))?;
+        serde_json::to_writer(BufWriter::new(file), transcript)?;
+        fs::rename(&tmp, &path)?;
+        self.evict()
+    }
+
+    /// Delete least recently used entries until the cache fits in `max_bytes`.
+    pub fn evict(&self) -> Result<()> {
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|x| x == "json"))
+            .filter_map(|e| {
+                let meta = e.metadata().ok()?;
+                Some((meta.modified().ok()?, meta.len(), e.path()))
+            })
+            .collect();
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort_by_key(|(modified, _, _)| *modified);
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            // Another process may have removed it already.
+            let _ = fs::remove_file(&path);
+            total = total.saturating_sub(len);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::Segment;
+
+    #[test]
+    fn round_trips_and_evicts_beyond_the_limit() {
+        let dir = std::env::temp_dir().join(format!("shout_cache_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let transcript = Transcript {
+            segments: vec![Segment {
+                text: " Hallo".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let cache = ResultCache::open(&dir, 1 << 20).unwrap();
+        let key = ResultCache::key(&hash_bytes(b"audio"), "model-a");
+        assert!(cache.get(&key).is_none());
+        cache.put(&key, &transcript).unwrap();
+        assert_eq!(cache.get(&key), Some(transcript.clone()));
+        assert_ne!(key, ResultCache::key(&hash_bytes(b"audio"), "model-b"));
+
+        let tiny = ResultCache::open(&dir, 0).unwrap();
+        tiny.evict().unwrap();
+        assert!(tiny.get(&key).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
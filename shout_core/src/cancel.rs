@@ -0,0 +1,194 @@
+//! Cooperative cancellation and timeouts.
+//!
+//! A [`CancelToken`] is checked between units of work: decoded audio packets,
+//! windows and decoding steps. Cancelling it, or passing its deadline, makes
+//! the next check fail with [`ShoutError::Interrupted`]. Work already done is
+//! kept: long-form transcription returns the windows finished so far.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use shout_core::cancel::CancelToken;
+//!
+//! let token = CancelToken::new();
+//! let job = token.with_timeout(Some(Duration::from_secs(60)));
+//! token.cancel();
+//! assert!(job.is_cancelled());
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::errors::{Result, ShoutError};
+#[cfg(feature = "inference")]
+use crate::transcript::Transcript;
+
+/// The pipeline stage that was running when work stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Reading and resampling the audio.
+    Decode,
+    Features,
+
+    /// Running the model: encoder and token decoding.
+    Inference,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Decode => "audio decoding",
+            Stage::Features => "feature extraction",
+            Stage::Inference => "inference",
+        })
+    }
+}
+
+/// Why work stopped early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interruption {
+    Cancelled { stage: Stage },
+    TimedOut { stage: Stage },
+}
+
+impl Interruption {
+    pub fn stage(&self) -> Stage {
+        match *self {
+            Interruption::Cancelled { stage } | Interruption::TimedOut { stage } => stage,
+        }
+    }
+}
+
+impl fmt::Display for Interruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Interruption::Cancelled { stage } => write!(f, "cancelled during {stage}"),
+            Interruption::TimedOut { stage } => write!(f, "timed out during {stage}"),
+        }
+    }
+}
+
+/// A cancellation flag shared by all clones, plus an optional deadline.
+///
+/// The default token is never cancelled and has no deadline.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel this token, its clones and every token derived from them.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// A token cancelled together with this one that also expires `timeout`
+    /// from now, or at this token's deadline if that comes first. `None` adds
+    /// no deadline.
+    pub fn with_timeout(&self, timeout: Option<Duration>) -> Self {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        Self {
+            cancelled: Arc::clone(&self.cancelled),
+            deadline: match (self.deadline, deadline) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+
+    /// Why work in `stage` should stop, if it should.
+    pub fn interruption(&self, stage: Stage) -> Option<Interruption> {
+        if self.is_cancelled() {
+            Some(Interruption::Cancelled { stage })
+        } else if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            Some(Interruption::TimedOut { stage })
+        } else {
+            None
+        }
+    }
+
+    /// Fail with [`ShoutError::Interrupted`] if work in `stage` should stop.
+    pub fn check(&self, stage: Stage) -> Result<()> {
+        match self.interruption(stage) {
+            Some(reason) => Err(ShoutError::Interrupted {
+                reason,
+                partial: Box::default(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Per-stage time limits; `None` means no limit. Limits apply per input file
+/// ([`Stage::Decode`]) or per model window (the other stages) and combine with
+/// the deadline of the [`CancelToken`] in use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    pub decode: Option<Duration>,
+    pub features: Option<Duration>,
+    pub inference: Option<Duration>,
+}
+
+/// Put `partial` into an interruption error; other errors pass unchanged.
+#[cfg(feature = "inference")]
+pub(crate) fn with_partial(error: ShoutError, partial: Transcript) -> ShoutError {
+    match error {
+        ShoutError::Interrupted { reason, .. } => ShoutError::Interrupted {
+            reason,
+            partial: Box::new(partial),
+        },
+        e => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derived_tokens_share_cancellation_and_keep_the_earlier_deadline() {
+        let token = CancelToken::new().with_timeout(Some(Duration::from_secs(3600)));
+        let child = token.with_timeout(Some(Duration::from_secs(7200)));
+        assert_eq!(child.deadline(), token.deadline());
+        assert!(child.check(Stage::Features).is_ok());
+
+        token.cancel();
+        assert_eq!(
+            child.interruption(Stage::Inference),
+            Some(Interruption::Cancelled {
+                stage: Stage::Inference
+            })
+        );
+    }
+
+    #[test]
+    fn expired_deadline_times_out() {
+        let token = CancelToken::new().with_timeout(Some(Duration::ZERO));
+        let err = token.check(Stage::Decode).unwrap_err();
+        assert!(matches!(
+            err,
+            ShoutError::Interrupted {
+                reason: Interruption::TimedOut {
+                    stage: Stage::Decode
+                },
+                ..
+            }
+        ));
+        assert_eq!(err.to_string(), "timed out during audio decoding");
+    }
+}
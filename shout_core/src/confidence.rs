@@ -0,0 +1,147 @@
+//! Confidence scores derived from token posteriors.
+//!
+//! A word's raw score is the mean log-probability of its tokens. Without a
+//! calibration this is mapped to `exp(mean)` (the geometric mean of the token
+//! probabilities); with one it goes through a fitted logistic curve so that a
+//! score of 0.8 means roughly 80 % of such words are correct. Calibrations
+//! are fitted by `shout calibrate` and stored next to the model weights.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::alignment::group_words;
+use crate::errors::{IoContext, Result, ShoutError};
+use crate::transcript::{Segment, TokenScore, Transcript};
+
+/// File name of a model's calibration, in the model directory.
+pub const CALIBRATION_FILE: &str = "calibration.json";
+
+/// Platt scaling: `p = sigmoid(slope * mean_logprob + intercept)`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    pub slope: f32,
+    pub intercept: f32,
+}
+
+impl Calibration {
+    /// The calibration stored in `model_dir`, if there is one.
+    pub fn from_model_dir(model_dir: &Path) -> Result<Option<Self>> {
+        let path = model_dir.join(CALIBRATION_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let raw = std::fs::read_to_string(&path)
+            .io_context(|| format!("failed to read calibration: {}", path.display()))?;
+        let calibration = serde_json::from_str(&raw).map_err(|e| {
+            ShoutError::Config(format!("invalid calibration: {}: {e}", path.display()))
+        })?;
+        Ok(Some(calibration))
+    }
+
+    /// Store as the calibration of the model in `model_dir`.
+    pub fn save_to_model_dir(&self, model_dir: &Path) -> Result<()> {
+        let path = model_dir.join(CALIBRATION_FILE);
+        std::fs::write(&path, serde_json::to_string_pretty(self)? + "\n")
+            .io_context(|| format!("failed to write calibration: {}", path.display()))
+    }
+
+    pub fn apply(&self, mean_logprob: f32) -> f32 {
+        1.0 / (1.0 + (-(self.slope * mean_logprob + self.intercept)).exp())
+    }
+}
+
+/// Confidence in `[0, 1]` for a group of tokens.
+pub fn score(tokens: &[TokenScore], calibration: Option<&Calibration>) -> Option<f32> {
+    if tokens.is_empty() {
+        return None;
+    }
+    let mean = tokens.iter().map(|t| t.logprob).sum::<f32>() / tokens.len() as f32;
+    Some(match calibration {
+        Some(c) => c.apply(mean),
+        None => mean.exp(),
+    })
+}
+
+/// Confidence of a whole segment.
+pub fn segment_confidence(segment: &Segment, calibration: Option<&Calibration>) -> Option<f32> {
+    score(&segment.tokens, calibration)
+}
+
+/// Fill `Word::confidence` for every word of `segment`, grouping its tokens into
+/// words the same way alignment does. Does nothing if the words don't match the
+/// token grouping (e.g. words from an external aligner).
+pub fn annotate_words(segment: &mut Segment, calibration: Option<&Calibration>) {
+    let texts: Vec<String> = segment.tokens.iter().map(|t| t.text.clone()).collect();
+    let groups = group_words(&texts);
+    if groups.len() != segment.words.len() {
+        return;
+    }
+
+    for (word, (_, range)) in segment.words.iter_mut().zip(groups) {
+        word.confidence = score(&segment.tokens[range], calibration);
+    }
+}
+
+/// Drop segments whose confidence is below `min_confidence`. Returns how many were dropped.
+pub fn retain_confident(
+    transcript: &mut Transcript,
+    min_confidence: f32,
+    calibration: Option<&Calibration>,
+) -> usize {
+    let before = transcript.segments.len();
+    transcript
+        .segments
+        .retain(|s| segment_confidence(s, calibration).is_none_or(|c| c >= min_confidence));
+    before - transcript.segments.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::Word;
+
+    fn token(text: &str, logprob: f32) -> TokenScore {
+        TokenScore {
+            id: 0,
+            text: text.into(),
+            logprob,
+        }
+    }
+
+    #[test]
+    fn scores_words_from_their_tokens() {
+        let word = |text: &str| Word {
+            text: text.into(),
+            start_ms: 0,
+            end_ms: 0,
+            confidence: None,
+        };
+        let mut seg = Segment {
+            text: " Hallo Welt!".into(),
+            words: vec![word("Hallo"), word("Welt!")],
+            tokens: vec![
+                token(" Hal", -0.1),
+                token("lo", -0.3),
+                token(" Welt", -2.0),
+                token("!", 0.0),
+            ],
+            ..Default::default()
+        };
+
+        annotate_words(&mut seg, None);
+
+        assert!((seg.words[0].confidence.unwrap() - (-0.2f32).exp()).abs() < 1e-6);
+        assert!((seg.words[1].confidence.unwrap() - (-1.0f32).exp()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn calibration_is_monotonic() {
+        let c = Calibration {
+            slope: 3.0,
+            intercept: 2.0,
+        };
+        assert!(c.apply(-2.0) < c.apply(-0.5));
+        assert!((c.apply(-2.0 / 3.0) - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{IoContext, Result, ShoutError};
+
+/// Model hyperparameters, stored as `config.json` next to the weights.
+///
+/// Field names follow OpenAI's `ModelDimensions` so Whisper configs map one to one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelConfig {
+    pub n_mels: usize,
+    pub n_audio_ctx: usize,
+    pub n_audio_state: usize,
+    pub n_audio_head: usize,
+    pub n_audio_layer: usize,
+    pub n_vocab: usize,
+    pub n_text_ctx: usize,
+    pub n_text_state: usize,
+    pub n_text_head: usize,
+    pub n_text_layer: usize,
+
+    /// Output size of the optional CTC head on top of the encoder.
+    #[serde(default)]
+    pub ctc_vocab: Option<usize>,
+
+    /// Features the encoder takes; `n_mels` is their size per frame.
+    #[serde(default)]
+    pub front_end: FrontEndConfig,
+
+    /// `(layer, head)` of the decoder cross-attention heads that follow the
+    /// audio closely enough to time words; see [`Self::word_alignment_heads`].
+    #[serde(default)]
+    pub alignment_heads: Option<Vec<(usize, usize)>>,
+}
+
+/// Which [`crate::features`] front end computes the encoder input.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontEndConfig {
+    #[default]
+    LogMel,
+
+    /// OpenAI Whisper's log-mel (Slaney filterbank, clip-level normalization).
+    WhisperLogMel,
+
+    /// Cepstra of a log-mel spectrogram with `n_filters` bins.
+    Mfcc { n_filters: usize },
+
+    /// Frames of raw samples, `n_mels` long.
+    RawWaveform,
+
+    /// A front end registered under `name` in [`crate::registry`].
+    Plugin {
+        name: String,
+        #[serde(default)]
+        params: serde_json::Value,
+    },
+}
+
+impl ModelConfig {
+    /// Whisper tiny (39M).
+    pub fn tiny() -> Self {
+        Self {
+            n_mels: 80,
+            n_audio_ctx: 1500,
+            n_audio_state: 384,
+            n_audio_head: 6,
+            n_audio_layer: 4,
+            n_vocab: 51865,
+            n_text_ctx: 448,
+            n_text_state: 384,
+            n_text_head: 6,
+            n_text_layer: 4,
+            ctc_vocab: None,
+            front_end: FrontEndConfig::LogMel,
+            alignment_heads: None,
+        }
+    }
+
+    /// The randomly initialized `test-tiny` model (see
+    /// `model::test_tiny`): Whisper's input and vocabulary with one narrow
+    /// layer on each side.
+    pub fn test_tiny() -> Self {
+        Self {
+            n_mels: 80,
+            n_audio_ctx: 1500,
+            n_audio_state: 16,
+            n_audio_head: 2,
+            n_audio_layer: 1,
+            n_vocab: 51865,
+            n_text_ctx: 448,
+            n_text_state: 16,
+            n_text_head: 2,
+            n_text_layer: 1,
+            ctc_vocab: None,
+            front_end: FrontEndConfig::LogMel,
+            alignment_heads: None,
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .io_context(|| format!("failed to read model config: {}", path.display()))?;
+        serde_json::from_str(&raw).map_err(|e| {
+            ShoutError::Config(format!("invalid model config: {}: {e}", path.display()))
+        })
+    }
+
+    /// The heads word alignment averages: `alignment_heads`, or like Whisper
+    /// without a list every head of the upper half of the decoder layers.
+    pub fn word_alignment_heads(&self) -> Vec<(usize, usize)> {
+        match &self.alignment_heads {
+            Some(heads) => heads.clone(),
+            None => (self.n_text_layer / 2..self.n_text_layer)
+                .flat_map(|layer| (0..self.n_text_head).map(move |head| (layer, head)))
+                .collect(),
+        }
+    }
+
+    /// Number of mel frames in one window (the encoder halves this with its stride-2 conv).
+    pub fn n_frames(&self) -> usize {
+        self.n_audio_ctx * 2
+    }
+}
@@ -0,0 +1,257 @@
+//! Beam search over one window, with optional contextual biasing, n-gram
+//! shallow fusion and a grammar constraint.
+
+use super::biasing::BiasState;
+use super::grammar::GrammarState;
+use super::greedy::{DecodeResult, no_speech_prob, suppress_special};
+use super::lm::LmState;
+use super::repetition::block_repeated_ngrams;
+use super::timestamps::apply_timestamp_rules;
+use super::{DecodeOptions, SpeechModel, log_softmax};
+use crate::cancel::Stage;
+use crate::errors::Result;
+use crate::tokenizer::special_tokens::SpecialTokens;
+
+#[derive(Debug, Clone, Default)]
+struct Hypothesis {
+    tokens: Vec<u32>,
+    logprobs: Vec<f32>,
+
+    /// Model log-probability plus biasing and LM bonuses; used for ranking only.
+    score: f32,
+
+    /// Log-probability of the end-of-text token, once finished.
+    eot_logprob: f32,
+
+    bias: BiasState,
+    lm: LmState,
+    grammar: GrammarState,
+}
+
+/// One possible extension of a beam.
+struct Candidate {
+    beam: usize,
+    token: u32,
+    logprob: f32,
+    score: f32,
+    bias: BiasState,
+    lm: LmState,
+}
+
+impl Hypothesis {
+    fn sum_logprob(&self) -> f32 {
+        self.logprobs.iter().sum::<f32>() + self.eot_logprob
+    }
+
+    /// Length-normalized score, as in Whisper without a length penalty.
+    fn final_score(&self) -> f32 {
+        self.score / (self.tokens.len() + 1) as f32
+    }
+}
+
+/// Beam search with `beam_size` hypotheses, stopping once `beam_size` of them
+/// have produced end-of-text. Hotwords in `opts.biasing` and the LM in `opts.lm`
+/// add to the ranking score; the recorded log-probabilities stay the model's own.
+/// `opts.grammar` rules out every token that would leave its phrases.
+pub fn beam_decode<M: SpeechModel + ?Sized>(
+    model: &mut M,
+    encoded: &M::Encoded,
+    prompt: &[u32],
+    special: &SpecialTokens,
+    opts: &DecodeOptions,
+    beam_size: usize,
+) -> Result<DecodeResult> {
+    let beam_size = beam_size.max(1);
+    let no_speech_prob = no_speech_prob(model, encoded, prompt, special)?;
+
+    let mut beams = vec![Hypothesis {
+        lm: opts
+            .lm
+            .as_ref()
+            .map(|f| f.start_state())
+            .unwrap_or_default(),
+        ..Default::default()
+    }];
+    let mut finished: Vec<Hypothesis> = Vec::new();
+    let mut truncated = true;
+
+    for _ in 0..opts.max_tokens {
+        opts.cancel.check(Stage::Inference)?;
+        let mut candidates: Vec<Candidate> = Vec::new();
+
+        for (b, hyp) in beams.iter().enumerate() {
+            let tokens = [prompt, &hyp.tokens].concat();
+            let mut logits = model.next_token_logits(encoded, &tokens)?;
+            suppress_special(&mut logits, special);
+            if opts.with_timestamps {
+                apply_timestamp_rules(&mut logits, &hyp.tokens, special);
+            }
+            block_repeated_ngrams(&mut logits, &hyp.tokens, opts.no_repeat_ngram_size, special);
+
+            // Masked after the softmax so the recorded log-probabilities stay
+            // the model's own.
+            let mut logprobs = log_softmax(&logits);
+            if let Some(grammar) = &opts.grammar {
+                grammar.mask(hyp.grammar, &mut logprobs, special);
+            }
+            let mut ranked: Vec<(usize, f32)> = logprobs
+                .iter()
+                .copied()
+                .enumerate()
+                .filter(|(_, lp)| lp.is_finite())
+                .collect();
+            ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+            for &(token, lp) in ranked.iter().take(beam_size + 1) {
+                let token = token as u32;
+                let (bias_bonus, bias) = match &opts.biasing {
+                    Some(trie) if token < special.eot => trie.advance(hyp.bias, token),
+                    _ => (0.0, hyp.bias),
+                };
+                let (lm_bonus, lm) = match &opts.lm {
+                    Some(fusion) if token == special.eot => {
+                        (fusion.finish(&hyp.lm), hyp.lm.clone())
+                    }
+                    Some(fusion) if token < special.eot => fusion.advance(&hyp.lm, token),
+                    _ => (0.0, hyp.lm.clone()),
+                };
+                candidates.push(Candidate {
+                    beam: b,
+                    token,
+                    logprob: lp,
+                    score: hyp.score + lp + bias_bonus + lm_bonus,
+                    bias,
+                    lm,
+                });
+            }
+        }
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        let mut next = Vec::with_capacity(beam_size);
+        for c in candidates {
+            let parent = &beams[c.beam];
+            if c.token == special.eot {
+                if finished.len() < beam_size {
+                    // An unfinished phrase match doesn't count towards the final score.
+                    finished.push(Hypothesis {
+                        score: c.score - c.bias.pending(),
+                        eot_logprob: c.logprob,
+                        ..parent.clone()
+                    });
+                }
+            } else if next.len() < beam_size {
+                let mut hyp = parent.clone();
+                hyp.tokens.push(c.token);
+                hyp.logprobs.push(c.logprob);
+                hyp.score = c.score;
+                hyp.bias = c.bias;
+                hyp.lm = c.lm;
+                if let Some(grammar) = &opts.grammar {
+                    hyp.grammar = grammar.advance(hyp.grammar, c.token, special);
+                }
+                next.push(hyp);
+            }
+            if next.len() >= beam_size && finished.len() >= beam_size {
+                break;
+            }
+        }
+
+        beams = next;
+        if finished.len() >= beam_size || beams.is_empty() {
+            truncated = false;
+            break;
+        }
+    }
+
+    if finished.is_empty() {
+        finished = beams;
+    } else {
+        truncated = false;
+    }
+
+    let best = finished
+        .into_iter()
+        .max_by(|a, b| a.final_score().total_cmp(&b.final_score()))
+        .unwrap_or_default();
+
+    Ok(DecodeResult {
+        avg_logprob: best.sum_logprob() / (best.tokens.len() + 1) as f32,
+        tokens: best.tokens,
+        logprobs: best.logprobs,
+        no_speech_prob,
+        truncated,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::mel::MelSpec;
+    use crate::decoding::biasing::BiasingTrie;
+    use crate::decoding::grammar::GrammarConstraint;
+
+    /// After the prompt, prefers token 10 slightly over token 20, then ends.
+    struct TwoWay;
+
+    impl SpeechModel for TwoWay {
+        type Encoded = usize;
+
+        fn encode(&mut self, _mel: &MelSpec) -> Result<usize> {
+            Ok(0)
+        }
+
+        fn next_token_logits(&mut self, prompt_len: &usize, tokens: &[u32]) -> Result<Vec<f32>> {
+            let special = SpecialTokens::whisper_multilingual(51865);
+            let mut logits = vec![-10.0; 51865];
+            if tokens.len() == *prompt_len {
+                logits[10] = 5.0;
+                logits[20] = 4.5;
+            } else {
+                logits[special.eot as usize] = 10.0;
+            }
+            Ok(logits)
+        }
+    }
+
+    #[test]
+    fn hotwords_change_the_winning_hypothesis() {
+        let special = SpecialTokens::whisper_multilingual(51865);
+        let prompt = vec![
+            special.sot,
+            50261,
+            special.transcribe,
+            special.no_timestamps,
+        ];
+        let mut opts = DecodeOptions {
+            with_timestamps: false,
+            ..Default::default()
+        };
+
+        let plain = beam_decode(&mut TwoWay, &prompt.len(), &prompt, &special, &opts, 3).unwrap();
+        assert_eq!(plain.tokens, vec![10]);
+
+        opts.biasing = Some(BiasingTrie::new([(vec![20], 2.0)]));
+        let biased = beam_decode(&mut TwoWay, &prompt.len(), &prompt, &special, &opts, 3).unwrap();
+        assert_eq!(biased.tokens, vec![20]);
+        assert!(biased.avg_logprob < plain.avg_logprob);
+    }
+
+    #[test]
+    fn grammar_rules_out_the_likelier_token() {
+        let special = SpecialTokens::whisper_multilingual(51865);
+        let prompt = vec![
+            special.sot,
+            50261,
+            special.transcribe,
+            special.no_timestamps,
+        ];
+        let opts = DecodeOptions {
+            with_timestamps: false,
+            grammar: Some(GrammarConstraint::new([vec![20]])),
+            ..Default::default()
+        };
+        let out = beam_decode(&mut TwoWay, &prompt.len(), &prompt, &special, &opts, 3).unwrap();
+        assert_eq!(out.tokens, vec![20]);
+    }
+}
@@ -0,0 +1,202 @@
+//! Contextual biasing: a trie of boosted phrases whose tokens earn a bonus while a
+//! beam is inside a match.
+//!
+//! A partial match that is abandoned gives its accumulated bonus back, so boosting
+//! only changes the ranking of hypotheses that actually complete a phrase and the
+//! model is free to ignore a hotword that isn't there.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::errors::{Result, ShoutError};
+
+/// Bonus per matched token when a hotword has no explicit boost.
+pub const DEFAULT_BOOST: f32 = 1.5;
+
+/// A phrase to favour, e.g. from `--hotword "Kubernetes:2.0"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hotword {
+    pub phrase: String,
+
+    /// Log-probability bonus added per matched token.
+    pub boost: f32,
+}
+
+impl FromStr for Hotword {
+    type Err = ShoutError;
+
+    /// `phrase` or `phrase:boost`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (phrase, boost) = match s.rsplit_once(':') {
+            Some((phrase, boost)) => match boost.trim().parse::<f32>() {
+                Ok(boost) => (phrase, boost),
+                Err(_) => (s, DEFAULT_BOOST),
+            },
+            None => (s, DEFAULT_BOOST),
+        };
+
+        let phrase = phrase.trim();
+        if phrase.is_empty() {
+            return Err(ShoutError::InvalidArgument("empty hotword".into()));
+        }
+        Ok(Self {
+            phrase: phrase.to_string(),
+            boost,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    children: HashMap<u32, usize>,
+    boost: f32,
+    is_end: bool,
+}
+
+/// Position of one hypothesis in the trie.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BiasState {
+    node: usize,
+
+    /// Bonus collected by the current partial match, refunded if it breaks off.
+    pending: f32,
+}
+
+impl BiasState {
+    /// Bonus of the unfinished match, which a hypothesis ending here must give back.
+    pub fn pending(&self) -> f32 {
+        self.pending
+    }
+}
+
+/// Token trie over all boosted phrases.
+#[derive(Debug, Clone)]
+pub struct BiasingTrie {
+    nodes: Vec<Node>,
+}
+
+impl Default for BiasingTrie {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+        }
+    }
+}
+
+impl BiasingTrie {
+    /// Build from tokenized phrases and their per-token boosts.
+    pub fn new<I: IntoIterator<Item = (Vec<u32>, f32)>>(phrases: I) -> Self {
+        let mut trie = Self::default();
+        for (tokens, boost) in phrases {
+            trie.insert(&tokens, boost);
+        }
+        trie
+    }
+
+    pub fn insert(&mut self, tokens: &[u32], boost: f32) {
+        if tokens.is_empty() {
+            return;
+        }
+
+        let mut node = 0;
+        for &t in tokens {
+            node = match self.nodes[node].children.get(&t) {
+                Some(&child) => child,
+                None => {
+                    self.nodes.push(Node::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.insert(t, child);
+                    child
+                }
+            };
+            let n = &mut self.nodes[node];
+            n.boost = n.boost.max(boost);
+        }
+        self.nodes[node].is_end = true;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    /// Score adjustment for appending `token` in `state`, and the state afterwards.
+    pub fn advance(&self, state: BiasState, token: u32) -> (f32, BiasState) {
+        if let Some(&child) = self.nodes[state.node].children.get(&token) {
+            let node = &self.nodes[child];
+            return if node.is_end && node.children.is_empty() {
+                // Completed phrase: keep the bonus and start over.
+                (node.boost, BiasState::default())
+            } else if node.is_end {
+                (
+                    node.boost,
+                    BiasState {
+                        node: child,
+                        pending: 0.0,
+                    },
+                )
+            } else {
+                (
+                    node.boost,
+                    BiasState {
+                        node: child,
+                        pending: state.pending + node.boost,
+                    },
+                )
+            };
+        }
+
+        // The match broke off: refund it and try to start a new one at this token.
+        let refund = -state.pending;
+        if state.node == 0 {
+            return (0.0, BiasState::default());
+        }
+        let (bonus, next) = self.advance(BiasState::default(), token);
+        (refund + bonus, next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hotword_with_optional_boost() {
+        assert_eq!(
+            "Grafana:2.5".parse::<Hotword>().unwrap(),
+            Hotword {
+                phrase: "Grafana".into(),
+                boost: 2.5
+            }
+        );
+        assert_eq!(
+            "Dr. Müller".parse::<Hotword>().unwrap().boost,
+            DEFAULT_BOOST
+        );
+        assert_eq!(
+            "10:30 meeting".parse::<Hotword>().unwrap().phrase,
+            "10:30 meeting"
+        );
+        assert!(" :3".parse::<Hotword>().is_err());
+    }
+
+    #[test]
+    fn completed_phrases_keep_bonus_and_broken_ones_refund_it() {
+        let trie = BiasingTrie::new([(vec![1, 2, 3], 1.0)]);
+
+        let mut state = BiasState::default();
+        let mut total = 0.0;
+        for t in [1, 2, 3] {
+            let (b, s) = trie.advance(state, t);
+            total += b;
+            state = s;
+        }
+        assert_eq!(total, 3.0);
+        assert_eq!(state, BiasState::default());
+
+        let (b1, s) = trie.advance(BiasState::default(), 1);
+        let (b2, s) = trie.advance(s, 2);
+        let (b3, s) = trie.advance(s, 9);
+        assert_eq!(b1 + b2 + b3, 0.0);
+        assert_eq!(s, BiasState::default());
+    }
+}
@@ -0,0 +1,235 @@
+//! CTC prefix beam search over per-frame log-probabilities, with optional
+//! n-gram shallow fusion and a lexicon restricting the output to known words.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ndarray::Array2;
+
+use super::lexicon::{Lexicon, LexiconState};
+use super::lm::{LmFusion, LmState};
+
+/// Settings for [`ctc_prefix_beam_search`].
+#[derive(Debug, Clone)]
+pub struct CtcBeamOptions {
+    pub beam_size: usize,
+
+    /// Index of the blank symbol.
+    pub blank: u32,
+
+    /// Only the this many most likely tokens per frame are expanded.
+    pub token_beam: usize,
+
+    /// Language model fused at word boundaries.
+    pub lm: Option<LmFusion>,
+
+    /// Only spell out words of this lexicon.
+    pub lexicon: Option<Arc<Lexicon>>,
+}
+
+impl Default for CtcBeamOptions {
+    fn default() -> Self {
+        Self {
+            beam_size: 8,
+            blank: 0,
+            token_beam: 16,
+            lm: None,
+            lexicon: None,
+        }
+    }
+}
+
+/// One decoded label sequence (blanks and repeats collapsed).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CtcHypothesis {
+    pub tokens: Vec<u32>,
+
+    /// Acoustic log-probability of the prefix, summed over alignments.
+    pub acoustic: f32,
+
+    /// Acoustic plus LM score, used for ranking.
+    pub score: f32,
+}
+
+#[derive(Debug, Clone)]
+struct Prefix {
+    /// Log-probability of the prefix with its last frame blank / non-blank.
+    blank: f32,
+    non_blank: f32,
+    lm_score: f32,
+    lm: LmState,
+    lexicon: LexiconState,
+}
+
+impl Prefix {
+    fn acoustic(&self) -> f32 {
+        log_add(self.blank, self.non_blank)
+    }
+
+    fn total(&self) -> f32 {
+        self.acoustic() + self.lm_score
+    }
+}
+
+fn log_add(a: f32, b: f32) -> f32 {
+    if a == f32::NEG_INFINITY {
+        return b;
+    }
+    if b == f32::NEG_INFINITY {
+        return a;
+    }
+    let max = a.max(b);
+    max + ((a - max).exp() + (b - max).exp()).ln()
+}
+
+/// Prefix beam search over `log_probs` (`frames x vocab`). Returns the final
+/// beams, best first. With a lexicon, beams that stop inside a word are
+/// dropped unless no beam ends on a word boundary.
+pub fn ctc_prefix_beam_search(
+    log_probs: &Array2<f32>,
+    opts: &CtcBeamOptions,
+) -> Vec<CtcHypothesis> {
+    let start = Prefix {
+        blank: 0.0,
+        non_blank: f32::NEG_INFINITY,
+        lm_score: 0.0,
+        lm: opts
+            .lm
+            .as_ref()
+            .map(LmFusion::start_state)
+            .unwrap_or_default(),
+        lexicon: opts.lexicon.as_ref().map(|l| l.start()).unwrap_or_default(),
+    };
+    let mut beams: Vec<(Vec<u32>, Prefix)> = vec![(Vec::new(), start)];
+
+    for frame in log_probs.rows() {
+        let mut ranked: Vec<(u32, f32)> = frame
+            .iter()
+            .enumerate()
+            .map(|(i, &lp)| (i as u32, lp))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(opts.token_beam.max(1));
+
+        let blank_lp = frame
+            .get(opts.blank as usize)
+            .copied()
+            .unwrap_or(f32::NEG_INFINITY);
+        let mut next: HashMap<Vec<u32>, Prefix> = HashMap::new();
+
+        for (tokens, beam) in &beams {
+            // Stay on the same prefix with a blank.
+            let stay = next.entry(tokens.clone()).or_insert_with(|| Prefix {
+                blank: f32::NEG_INFINITY,
+                non_blank: f32::NEG_INFINITY,
+                ..beam.clone()
+            });
+            stay.blank = log_add(stay.blank, beam.acoustic() + blank_lp);
+
+            for &(token, lp) in &ranked {
+                if token == opts.blank {
+                    continue;
+                }
+
+                if tokens.last() == Some(&token) {
+                    // A repeat without a blank in between collapses into the same prefix.
+                    let stay = next.get_mut(tokens).expect("inserted above");
+                    stay.non_blank = log_add(stay.non_blank, beam.non_blank + lp);
+                }
+
+                let lexicon = match &opts.lexicon {
+                    Some(lexicon) => match lexicon.advance(beam.lexicon, token) {
+                        Some(state) => state,
+                        None => continue,
+                    },
+                    None => beam.lexicon,
+                };
+                let mut extended = tokens.clone();
+                extended.push(token);
+                let entry = next.entry(extended).or_insert_with(|| {
+                    let (bonus, lm) = match &opts.lm {
+                        Some(fusion) => fusion.advance(&beam.lm, token),
+                        None => (0.0, beam.lm.clone()),
+                    };
+                    Prefix {
+                        blank: f32::NEG_INFINITY,
+                        non_blank: f32::NEG_INFINITY,
+                        lm_score: beam.lm_score + bonus,
+                        lm,
+                        lexicon,
+                    }
+                });
+                let from = if tokens.last() == Some(&token) {
+                    beam.blank
+                } else {
+                    beam.acoustic()
+                };
+                entry.non_blank = log_add(entry.non_blank, from + lp);
+            }
+        }
+
+        let mut pruned: Vec<(Vec<u32>, Prefix)> = next.into_iter().collect();
+        pruned.sort_by(|a, b| b.1.total().total_cmp(&a.1.total()));
+        pruned.truncate(opts.beam_size.max(1));
+        beams = pruned;
+    }
+
+    if let Some(lexicon) = &opts.lexicon
+        && beams.iter().any(|(_, p)| lexicon.is_complete(p.lexicon))
+    {
+        beams.retain(|(_, p)| lexicon.is_complete(p.lexicon));
+    }
+    let mut out: Vec<CtcHypothesis> = beams
+        .into_iter()
+        .map(|(tokens, p)| {
+            let finish = opts.lm.as_ref().map_or(0.0, |f| f.finish(&p.lm));
+            CtcHypothesis {
+                acoustic: p.acoustic(),
+                score: p.total() + finish,
+                tokens,
+            }
+        })
+        .collect();
+    out.sort_by(|a, b| b.score.total_cmp(&a.score));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoding::log_softmax;
+
+    fn frames(rows: &[[f32; 3]]) -> Array2<f32> {
+        let flat: Vec<f32> = rows.iter().flat_map(|r| log_softmax(r)).collect();
+        Array2::from_shape_vec((rows.len(), 3), flat).unwrap()
+    }
+
+    #[test]
+    fn collapses_repeats_and_blanks() {
+        // blank=0; frames: a a _ a b
+        let lp = frames(&[
+            [0.0, 5.0, 0.0],
+            [0.0, 5.0, 0.0],
+            [5.0, 0.0, 0.0],
+            [0.0, 5.0, 0.0],
+            [0.0, 0.0, 5.0],
+        ]);
+        let best = &ctc_prefix_beam_search(&lp, &CtcBeamOptions::default())[0];
+        assert_eq!(best.tokens, vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn lexicon_overrides_a_likelier_spelling() {
+        // blank=0; the acoustics favour "a b", the lexicon only knows "a a".
+        let lp = frames(&[[0.0, 5.0, 0.0], [5.0, 0.0, 0.0], [0.0, 2.0, 3.0]]);
+        let opts = CtcBeamOptions {
+            lexicon: Some(Arc::new(Lexicon::new([("aa".to_string(), vec![1, 1])]))),
+            ..Default::default()
+        };
+        assert_eq!(
+            ctc_prefix_beam_search(&lp, &CtcBeamOptions::default())[0].tokens,
+            [1, 2]
+        );
+        assert_eq!(ctc_prefix_beam_search(&lp, &opts)[0].tokens, [1, 1]);
+    }
+}
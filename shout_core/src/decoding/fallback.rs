@@ -0,0 +1,158 @@
+//! Whisper's temperature fallback: decode greedily first and retry at increasing
+//! temperatures while the output looks degenerate (too repetitive or too unlikely).
+
+use std::io::Write;
+
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use super::beam::beam_decode;
+use super::greedy::{DecodeResult, sample_decode};
+use super::repetition::token_budget;
+use super::{DecodeOptions, SpeechModel};
+use crate::errors::Result;
+use crate::tokenizer::special_tokens::SpecialTokens;
+
+/// `len(text) / len(zlib(text))`. Repetitive text compresses well and scores high.
+pub fn compression_ratio(text: &str) -> f32 {
+    let bytes = text.as_bytes();
+    if bytes.is_empty() {
+        return 0.0;
+    }
+
+    let mut enc = ZlibEncoder::new(Vec::new(), Compression::default());
+    let compressed = enc
+        .write_all(bytes)
+        .and_then(|_| enc.finish())
+        .map(|c| c.len())
+        .unwrap_or(bytes.len());
+    bytes.len() as f32 / compressed.max(1) as f32
+}
+
+/// Whether a window is silence: high `<|nospeech|>` probability and a low-confidence decode.
+pub fn is_silence(result: &DecodeResult, opts: &DecodeOptions) -> bool {
+    let Some(no_speech) = opts.no_speech_threshold else {
+        return false;
+    };
+    let low_logprob = opts
+        .logprob_threshold
+        .is_none_or(|t| result.avg_logprob < t);
+    result.no_speech_prob > no_speech && low_logprob
+}
+
+/// Whether `result` should be retried at the next temperature.
+pub fn needs_fallback(result: &DecodeResult, opts: &DecodeOptions) -> bool {
+    if is_silence(result, opts) {
+        return false;
+    }
+    let too_repetitive = opts
+        .compression_ratio_threshold
+        .is_some_and(|t| result.compression_ratio > t);
+    let too_unlikely = opts
+        .logprob_threshold
+        .is_some_and(|t| result.avg_logprob < t);
+    too_repetitive || too_unlikely
+}
+
+/// Decode one window, falling back through `opts.temperatures`. Returns the first
+/// result that passes the checks, or the one at the highest temperature.
+/// With `opts.beam_size` set, temperature 0 uses beam search.
+///
+/// `audio_ms` is the window's audio length, used for `max_tokens_per_second`, and
+/// `detokenize` turns generated tokens into text for the compression check.
+pub fn decode_with_fallback<M, F>(
+    model: &mut M,
+    encoded: &M::Encoded,
+    prompt: &[u32],
+    special: &SpecialTokens,
+    opts: &DecodeOptions,
+    audio_ms: u64,
+    detokenize: F,
+) -> Result<DecodeResult>
+where
+    M: SpeechModel + ?Sized,
+    F: Fn(&[u32]) -> Result<String>,
+{
+    let budget = opts
+        .max_tokens_per_second
+        .map(|rate| token_budget(audio_ms, rate))
+        .filter(|&b| b < opts.max_tokens);
+    let capped;
+    let opts = match budget {
+        Some(max_tokens) => {
+            capped = DecodeOptions {
+                max_tokens,
+                ..opts.clone()
+            };
+            &capped
+        }
+        None => opts,
+    };
+
+    let mut rng = StdRng::seed_from_u64(opts.seed);
+    let temperatures = if opts.temperatures.is_empty() {
+        &[0.0][..]
+    } else {
+        &opts.temperatures[..]
+    };
+
+    let mut last = None;
+    for &t in temperatures {
+        let mut result = match opts.beam_size {
+            Some(beam_size) if t == 0.0 => {
+                beam_decode(model, encoded, prompt, special, opts, beam_size)?
+            }
+            _ => sample_decode(model, encoded, prompt, special, opts, t, &mut rng)?,
+        };
+        result.compression_ratio = compression_ratio(&detokenize(&result.tokens)?);
+
+        let runaway = budget.is_some() && result.truncated;
+        if !runaway && !needs_fallback(&result, opts) {
+            return Ok(result);
+        }
+        tracing::debug!(
+            temperature = t,
+            compression_ratio = result.compression_ratio,
+            avg_logprob = result.avg_logprob,
+            runaway,
+            "decode failed the quality checks"
+        );
+        last = Some(result);
+    }
+
+    Ok(last.expect("at least one temperature"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repetitive_text_has_high_compression_ratio() {
+        let normal = "Der schnelle braune Fuchs springt über den faulen Hund.";
+        let looped = "Danke. ".repeat(30);
+        assert!(compression_ratio(normal) < 2.4);
+        assert!(compression_ratio(&looped) > 2.4);
+    }
+
+    #[test]
+    fn silence_is_not_retried() {
+        let opts = DecodeOptions::default();
+        let silent = DecodeResult {
+            no_speech_prob: 0.9,
+            avg_logprob: -2.0,
+            ..Default::default()
+        };
+        let unsure = DecodeResult {
+            no_speech_prob: 0.1,
+            avg_logprob: -2.0,
+            ..Default::default()
+        };
+
+        assert!(is_silence(&silent, &opts));
+        assert!(!needs_fallback(&silent, &opts));
+        assert!(needs_fallback(&unsure, &opts));
+    }
+}
@@ -0,0 +1,429 @@
+//! Constrained decoding: the output of a window must be one of the phrases a
+//! small grammar allows, as in IVR menus or form fields.
+//!
+//! A grammar is either a plain phrase list (one per line, `#` comments) or a
+//! JSGF-like rule set:
+//!
+//! ```text
+//! #JSGF V1.0;
+//! public <answer> = <yes> | <no> | [ich möchte] (kündigen | bestellen);
+//! <yes> = ja [bitte];
+//! <no> = nein [danke];
+//! ```
+//!
+//! Rules combine words with `|` (alternatives), `( )` (grouping), `[ ]`
+//! (optional parts) and `<rule>` references; the first public rule (else the
+//! first rule) is the root. Weights, tags and recursion are not supported.
+//! The grammar is expanded into its phrases and tokenized into a trie, which
+//! masks every text token that would leave it; end-of-text is only allowed
+//! once a phrase is complete. Timestamp tokens are not constrained.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::errors::{IoContext, Result, ShoutError};
+use crate::tokenizer::special_tokens::SpecialTokens;
+
+/// Grammars expanding into more phrases than this are rejected; the trie is
+/// meant for menus, not open vocabularies.
+pub const MAX_PHRASES: usize = 10_000;
+
+/// The phrases a grammar accepts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grammar {
+    phrases: Vec<String>,
+}
+
+impl Grammar {
+    /// A plain list of phrases.
+    pub fn from_phrases<I: IntoIterator<Item = S>, S: AsRef<str>>(phrases: I) -> Self {
+        let mut out: Vec<String> = Vec::new();
+        for phrase in phrases {
+            let phrase = phrase
+                .as_ref()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            if !phrase.is_empty() && !out.contains(&phrase) {
+                out.push(phrase);
+            }
+        }
+        Self { phrases: out }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .io_context(|| format!("Failed to read grammar: {}", path.display()))?;
+        Self::parse(&text).map_err(|e| e.context(format!("Invalid grammar: {}", path.display())))
+    }
+
+    /// A JSGF-like rule set if `text` defines any rule, else a phrase list.
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let is_jsgf = text.lines().any(|l| {
+            let l = l.trim_start();
+            let rule = (l.starts_with("public") || l.starts_with('<')) && l.contains('=');
+            l.starts_with("#JSGF") || rule
+        });
+        if is_jsgf {
+            return parse_jsgf(text);
+        }
+        let lines = text.lines().filter(|l| !l.trim_start().starts_with('#'));
+        let grammar = Self::from_phrases(lines);
+        if grammar.phrases.is_empty() {
+            return Err(ShoutError::Config("the grammar has no phrases".into()));
+        }
+        Ok(grammar)
+    }
+
+    pub fn phrases(&self) -> &[String] {
+        &self.phrases
+    }
+}
+
+// -----------------------------------------------------------------------------
+// JSGF
+// -----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expansion {
+    Word(String),
+    Rule(String),
+    Sequence(Vec<Expansion>),
+    Alternatives(Vec<Expansion>),
+    Optional(Box<Expansion>),
+}
+
+fn parse_jsgf(text: &str) -> Result<Grammar> {
+    let body: String = text
+        .lines()
+        .map(|l| l.split("//").next().unwrap_or(""))
+        .filter(|l| {
+            let l = l.trim_start();
+            !l.starts_with("#JSGF") && !l.starts_with("grammar ") && !l.starts_with("import ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut rules = HashMap::new();
+    let mut root = None;
+    let mut first = None;
+    for statement in body.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+        let (head, expansion) = statement.split_once('=').ok_or_else(|| {
+            ShoutError::Config(format!("expected `<rule> = ...` in '{statement}'"))
+        })?;
+        let head = head.trim();
+        let public = head.starts_with("public");
+        let name = head
+            .trim_start_matches("public")
+            .trim()
+            .strip_prefix('<')
+            .and_then(|n| n.strip_suffix('>'))
+            .ok_or_else(|| ShoutError::Config(format!("invalid rule name '{head}'")))?
+            .to_string();
+        let tokens = tokenize(expansion);
+        let mut pos = 0;
+        let expansion = parse_alternatives(&tokens, &mut pos)?;
+        if pos != tokens.len() {
+            return Err(ShoutError::Config(format!(
+                "unexpected '{}' in <{name}>",
+                tokens[pos]
+            )));
+        }
+        if public && root.is_none() {
+            root = Some(name.clone());
+        }
+        first.get_or_insert_with(|| name.clone());
+        rules.insert(name, expansion);
+    }
+
+    let root = root
+        .or(first)
+        .ok_or_else(|| ShoutError::Config("the grammar has no rules".into()))?;
+    let phrases = expand(
+        &Expansion::Rule(root),
+        &rules,
+        &mut vec![Vec::new()],
+        &mut Vec::new(),
+    )?;
+    Ok(Grammar::from_phrases(
+        phrases.iter().map(|words| words.join(" ")),
+    ))
+}
+
+fn tokenize(s: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut word = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '|' | '(' | ')' | '[' | ']' => {
+                out.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+                out.push(c.to_string());
+            }
+            '<' => {
+                out.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+                let mut rule = String::from("<");
+                for c in chars.by_ref() {
+                    rule.push(c);
+                    if c == '>' {
+                        break;
+                    }
+                }
+                out.push(rule);
+            }
+            c if c.is_whitespace() => {
+                out.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+            }
+            c => word.push(c),
+        }
+    }
+    out.extend((!word.is_empty()).then_some(word));
+    out
+}
+
+fn parse_alternatives(tokens: &[String], pos: &mut usize) -> Result<Expansion> {
+    let mut alternatives = vec![parse_sequence(tokens, pos)?];
+    while tokens.get(*pos).is_some_and(|t| t == "|") {
+        *pos += 1;
+        alternatives.push(parse_sequence(tokens, pos)?);
+    }
+    Ok(match alternatives.len() {
+        1 => alternatives.pop().expect("one alternative"),
+        _ => Expansion::Alternatives(alternatives),
+    })
+}
+
+fn parse_sequence(tokens: &[String], pos: &mut usize) -> Result<Expansion> {
+    let mut items = Vec::new();
+    while let Some(token) = tokens.get(*pos) {
+        let item = match token.as_str() {
+            "|" | ")" | "]" => break,
+            "(" | "[" => {
+                *pos += 1;
+                let inner = parse_alternatives(tokens, pos)?;
+                let close = if token == "(" { ")" } else { "]" };
+                if tokens.get(*pos).map(String::as_str) != Some(close) {
+                    return Err(ShoutError::Config(format!("missing '{close}'")));
+                }
+                if token == "(" {
+                    inner
+                } else {
+                    Expansion::Optional(Box::new(inner))
+                }
+            }
+            t if t.starts_with('<') => {
+                let name = t.trim_start_matches('<').trim_end_matches('>');
+                Expansion::Rule(name.to_string())
+            }
+            t => Expansion::Word(t.to_string()),
+        };
+        *pos += 1;
+        items.push(item);
+    }
+    if items.is_empty() {
+        return Err(ShoutError::Config("empty alternative".into()));
+    }
+    Ok(Expansion::Sequence(items))
+}
+
+/// Every word sequence of `expansion` appended to each of `prefixes`.
+fn expand(
+    expansion: &Expansion,
+    rules: &HashMap<String, Expansion>,
+    prefixes: &mut Vec<Vec<String>>,
+    active: &mut Vec<String>,
+) -> Result<Vec<Vec<String>>> {
+    let out = match expansion {
+        Expansion::Word(word) => prefixes
+            .iter()
+            .map(|p| [p.as_slice(), std::slice::from_ref(word)].concat())
+            .collect(),
+        Expansion::Rule(name) => {
+            if active.contains(name) {
+                return Err(ShoutError::Config(format!("rule <{name}> is recursive")));
+            }
+            let rule = rules
+                .get(name)
+                .ok_or_else(|| ShoutError::Config(format!("undefined rule <{name}>")))?;
+            active.push(name.clone());
+            let out = expand(rule, rules, prefixes, active)?;
+            active.pop();
+            out
+        }
+        Expansion::Sequence(items) => {
+            let mut current = std::mem::take(prefixes);
+            for item in items {
+                current = expand(item, rules, &mut current, active)?;
+            }
+            current
+        }
+        Expansion::Alternatives(alternatives) => {
+            let mut out = Vec::new();
+            for alternative in alternatives {
+                out.extend(expand(alternative, rules, &mut prefixes.clone(), active)?);
+            }
+            out
+        }
+        Expansion::Optional(inner) => {
+            let mut out = prefixes.clone();
+            out.extend(expand(inner, rules, &mut prefixes.clone(), active)?);
+            out
+        }
+    };
+    if out.len() > MAX_PHRASES {
+        return Err(ShoutError::Config(format!(
+            "the grammar allows more than {MAX_PHRASES} phrases"
+        )));
+    }
+    Ok(out)
+}
+
+// -----------------------------------------------------------------------------
+// Constraint
+// -----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    children: HashMap<u32, usize>,
+    is_end: bool,
+}
+
+/// Position of one hypothesis in the [`GrammarConstraint`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GrammarState {
+    node: usize,
+}
+
+/// Token trie over the tokenized phrases of a grammar.
+#[derive(Debug, Clone)]
+pub struct GrammarConstraint {
+    nodes: Vec<Node>,
+}
+
+impl Default for GrammarConstraint {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+        }
+    }
+}
+
+impl GrammarConstraint {
+    /// Build from the token sequences of the allowed outputs.
+    pub fn new<I: IntoIterator<Item = Vec<u32>>>(sequences: I) -> Self {
+        let mut trie = Self::default();
+        for tokens in sequences {
+            trie.insert(&tokens);
+        }
+        trie
+    }
+
+    pub fn insert(&mut self, tokens: &[u32]) {
+        if tokens.is_empty() {
+            return;
+        }
+
+        let mut node = 0;
+        for &t in tokens {
+            node = match self.nodes[node].children.get(&t) {
+                Some(&child) => child,
+                None => {
+                    self.nodes.push(Node::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.insert(t, child);
+                    child
+                }
+            };
+        }
+        self.nodes[node].is_end = true;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    /// Set the scores of text tokens that leave the grammar in `state`, and of
+    /// end-of-text before a phrase is complete, to `-inf`.
+    pub fn mask(&self, state: GrammarState, scores: &mut [f32], special: &SpecialTokens) {
+        let node = &self.nodes[state.node];
+        let eot = special.eot as usize;
+        for (token, score) in scores.iter_mut().enumerate().take(eot) {
+            if !node.children.contains_key(&(token as u32)) {
+                *score = f32::NEG_INFINITY;
+            }
+        }
+        if !node.is_end
+            && let Some(score) = scores.get_mut(eot)
+        {
+            *score = f32::NEG_INFINITY;
+        }
+    }
+
+    /// The state after `token`; tokens outside the text vocabulary leave it
+    /// unchanged.
+    pub fn advance(
+        &self,
+        state: GrammarState,
+        token: u32,
+        special: &SpecialTokens,
+    ) -> GrammarState {
+        if token >= special.eot {
+            return state;
+        }
+        match self.nodes[state.node].children.get(&token) {
+            Some(&node) => GrammarState { node },
+            None => state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_jsgf_rules_and_phrase_lists() {
+        let jsgf = "#JSGF V1.0;\n\
+                    public <answer> = <yes> | [ich möchte] (kündigen | bestellen);\n\
+                    <yes> = ja [bitte]; // polite\n";
+        let grammar = Grammar::parse(jsgf).unwrap();
+        let expected = [
+            "ja",
+            "ja bitte",
+            "kündigen",
+            "ich möchte kündigen",
+            "bestellen",
+            "ich möchte bestellen",
+        ];
+        assert_eq!(grammar.phrases(), expected);
+
+        let list = Grammar::parse("# menu\nKonto\n  Karte  sperren\nKonto\n").unwrap();
+        assert_eq!(list.phrases(), ["Konto", "Karte sperren"]);
+
+        assert!(Grammar::parse("public <a> = x <a>;").is_err());
+        assert!(Grammar::parse("public <a> = (x | y;").is_err());
+    }
+
+    #[test]
+    fn masks_tokens_outside_the_phrases() {
+        let special = SpecialTokens::whisper_multilingual(51865);
+        let trie = GrammarConstraint::new([vec![1, 2], vec![1, 3, 4]]);
+        let eot = special.eot as usize;
+
+        let mut scores = vec![0.0; eot + 1];
+        trie.mask(GrammarState::default(), &mut scores, &special);
+        assert!(scores[1].is_finite() && scores[2].is_infinite() && scores[eot].is_infinite());
+
+        let state = trie.advance(
+            trie.advance(GrammarState::default(), 1, &special),
+            2,
+            &special,
+        );
+        let mut scores = vec![0.0; eot + 1];
+        trie.mask(state, &mut scores, &special);
+        assert!(scores[..eot].iter().all(|s| s.is_infinite()) && scores[eot].is_finite());
+    }
+}
@@ -0,0 +1,211 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::grammar::GrammarState;
+use super::repetition::block_repeated_ngrams;
+use super::timestamps::apply_timestamp_rules;
+use super::{DecodeOptions, SpeechModel, log_softmax};
+use crate::cancel::Stage;
+use crate::errors::Result;
+use crate::tokenizer::special_tokens::SpecialTokens;
+
+/// Output of decoding one window.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeResult {
+    /// Generated tokens after the prompt, end-of-text excluded.
+    pub tokens: Vec<u32>,
+
+    /// Log-probability of each generated token.
+    pub logprobs: Vec<f32>,
+
+    /// Sum of `logprobs` divided by the number of tokens (end-of-text included).
+    pub avg_logprob: f32,
+
+    /// Probability of `<|nospeech|>` right after `<|startoftranscript|>`.
+    pub no_speech_prob: f32,
+
+    /// Temperature this result was decoded at.
+    pub temperature: f32,
+
+    /// Compression ratio of the decoded text (filled in by the fallback logic).
+    pub compression_ratio: f32,
+
+    /// Decoding stopped at `max_tokens` instead of end-of-text.
+    pub truncated: bool,
+}
+
+/// Mask tokens that must never be generated: the prompt/control special tokens
+/// between end-of-text and the first timestamp.
+pub fn suppress_special(logits: &mut [f32], special: &SpecialTokens) {
+    let start = (special.eot as usize + 1).min(logits.len());
+    let end = (special.timestamp_begin as usize).min(logits.len());
+    logits[start..end].fill(f32::NEG_INFINITY);
+}
+
+/// Probability of `<|nospeech|>` as the token following `<|startoftranscript|>` in `prompt`.
+pub fn no_speech_prob<M: SpeechModel + ?Sized>(
+    model: &mut M,
+    encoded: &M::Encoded,
+    prompt: &[u32],
+    special: &SpecialTokens,
+) -> Result<f32> {
+    let Some(sot_index) = prompt.iter().position(|&t| t == special.sot) else {
+        return Ok(0.0);
+    };
+    let logits = model.next_token_logits(encoded, &prompt[..=sot_index])?;
+    Ok(log_softmax(&logits)
+        .get(special.no_speech as usize)
+        .map_or(0.0, |lp| lp.exp()))
+}
+
+/// Pick the most likely token at every step until end-of-text or `max_tokens`.
+pub fn greedy_decode<M: SpeechModel + ?Sized>(
+    model: &mut M,
+    encoded: &M::Encoded,
+    prompt: &[u32],
+    special: &SpecialTokens,
+    opts: &DecodeOptions,
+) -> Result<DecodeResult> {
+    let mut rng = StdRng::seed_from_u64(opts.seed);
+    sample_decode(model, encoded, prompt, special, opts, 0.0, &mut rng)
+}
+
+/// Decode one window at `temperature`: greedy at `0.0`, otherwise sampling from the
+/// tempered distribution. Recorded log-probabilities are always untempered.
+pub fn sample_decode<M: SpeechModel + ?Sized>(
+    model: &mut M,
+    encoded: &M::Encoded,
+    prompt: &[u32],
+    special: &SpecialTokens,
+    opts: &DecodeOptions,
+    temperature: f32,
+    rng: &mut StdRng,
+) -> Result<DecodeResult> {
+    let no_speech_prob = no_speech_prob(model, encoded, prompt, special)?;
+
+    let mut tokens = prompt.to_vec();
+    let mut result = DecodeResult {
+        no_speech_prob,
+        temperature,
+        ..Default::default()
+    };
+    let mut sum_logprob = 0.0f32;
+    let mut grammar = GrammarState::default();
+    result.truncated = true;
+
+    for _ in 0..opts.max_tokens {
+        opts.cancel.check(Stage::Inference)?;
+        let mut logits = model.next_token_logits(encoded, &tokens)?;
+        suppress_special(&mut logits, special);
+        if opts.with_timestamps {
+            apply_timestamp_rules(&mut logits, &result.tokens, special);
+        }
+        block_repeated_ngrams(
+            &mut logits,
+            &result.tokens,
+            opts.no_repeat_ngram_size,
+            special,
+        );
+
+        // Recorded log-probabilities ignore the grammar mask.
+        let logprobs = log_softmax(&logits);
+        if let Some(constraint) = &opts.grammar {
+            constraint.mask(grammar, &mut logits, special);
+        }
+        let next = if temperature > 0.0 {
+            sample(&logits, temperature, rng)
+        } else {
+            argmax(&logits, special.eot as usize)
+        };
+        let lp = logprobs[next];
+        if let Some(constraint) = &opts.grammar {
+            grammar = constraint.advance(grammar, next as u32, special);
+        }
+
+        sum_logprob += lp;
+        if next as u32 == special.eot {
+            result.truncated = false;
+            break;
+        }
+
+        tokens.push(next as u32);
+        result.tokens.push(next as u32);
+        result.logprobs.push(lp);
+    }
+
+    result.avg_logprob = sum_logprob / (result.tokens.len() + 1) as f32;
+    Ok(result)
+}
+
+fn argmax(values: &[f32], default: usize) -> usize {
+    values
+        .iter()
+        .copied()
+        .enumerate()
+        .fold((default, f32::NEG_INFINITY), |best, (i, v)| {
+            if v > best.1 { (i, v) } else { best }
+        })
+        .0
+}
+
+/// Draw a token from `softmax(logits / temperature)`.
+fn sample(logits: &[f32], temperature: f32, rng: &mut StdRng) -> usize {
+    let scaled: Vec<f32> = logits.iter().map(|&l| l / temperature).collect();
+    let probs: Vec<f32> = log_softmax(&scaled).into_iter().map(f32::exp).collect();
+
+    let mut r: f32 = rng.random();
+    for (i, p) in probs.iter().enumerate() {
+        if r < *p {
+            return i;
+        }
+        r -= p;
+    }
+    argmax(&probs, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::mel::MelSpec;
+
+    /// Emits a fixed script of tokens, one per step.
+    struct Scripted(Vec<u32>);
+
+    impl SpeechModel for Scripted {
+        type Encoded = usize;
+
+        fn encode(&mut self, _mel: &MelSpec) -> Result<usize> {
+            Ok(0)
+        }
+
+        fn next_token_logits(&mut self, prompt_len: &usize, tokens: &[u32]) -> Result<Vec<f32>> {
+            let mut logits = vec![0.0; 51865];
+            let step = tokens.len().saturating_sub(*prompt_len);
+            if let Some(&t) = self.0.get(step) {
+                logits[t as usize] = 20.0;
+            }
+            Ok(logits)
+        }
+    }
+
+    #[test]
+    fn stops_at_end_of_text() {
+        let special = SpecialTokens::whisper_multilingual(51865);
+        let prompt = vec![
+            special.sot,
+            50261,
+            special.transcribe,
+            special.no_timestamps,
+        ];
+        let mut model = Scripted(vec![10, 11, special.eot, 12]);
+        let opts = DecodeOptions {
+            with_timestamps: false,
+            ..Default::default()
+        };
+
+        let out = greedy_decode(&mut model, &prompt.len(), &prompt, &special, &opts).unwrap();
+
+        assert_eq!(out.tokens, vec![10, 11]);
+        assert!(out.avg_logprob > -0.01);
+    }
+}
@@ -0,0 +1,140 @@
+//! Spoken language identification from the model's language tokens.
+
+use std::str::FromStr;
+
+use super::{SpeechModel, log_softmax};
+use crate::errors::{Result, ShoutError};
+use crate::tokenizer::special_tokens::SpecialTokens;
+
+/// Probability of every supported language for one encoded window, most likely first.
+///
+/// Feeds `<|startoftranscript|>` to the decoder and renormalizes the next-token
+/// distribution over the language tokens only, as Whisper's `detect_language` does.
+pub fn detect_language<M: SpeechModel + ?Sized>(
+    model: &mut M,
+    encoded: &M::Encoded,
+    special: &SpecialTokens,
+) -> Result<Vec<(String, f32)>> {
+    let logits = model.next_token_logits(encoded, &[special.sot])?;
+
+    let begin = special.language_begin as usize;
+    let end = begin + special.n_languages;
+    if logits.len() < end {
+        return Err(ShoutError::Model(format!(
+            "model vocabulary ({}) too small for {} language tokens",
+            logits.len(),
+            special.n_languages
+        )));
+    }
+
+    let logprobs = log_softmax(&logits[begin..end]);
+    let mut probs: Vec<(String, f32)> = logprobs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, lp)| {
+            special
+                .language_code(special.language_begin + i as u32)
+                .map(|code| (code.to_string(), lp.exp()))
+        })
+        .collect();
+
+    probs.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(probs)
+}
+
+/// Value of `transcribe --language`: a fixed language code or `auto`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum LanguageSelection {
+    #[default]
+    Auto,
+    Fixed(String),
+}
+
+impl FromStr for LanguageSelection {
+    type Err = ShoutError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        if s == "auto" {
+            return Ok(LanguageSelection::Auto);
+        }
+        if SpecialTokens::whisper_multilingual(51866)
+            .language_token(&s)
+            .is_none()
+        {
+            return Err(ShoutError::InvalidArgument(format!(
+                "unknown language code '{s}'"
+            )));
+        }
+        Ok(LanguageSelection::Fixed(s))
+    }
+}
+
+/// Resolve the decoding language for one window: the fixed language, or the most
+/// likely detected one for [`LanguageSelection::Auto`].
+pub fn resolve_language<M: SpeechModel + ?Sized>(
+    selection: &LanguageSelection,
+    model: &mut M,
+    encoded: &M::Encoded,
+    special: &SpecialTokens,
+) -> Result<String> {
+    match selection {
+        LanguageSelection::Fixed(code) => Ok(code.clone()),
+        LanguageSelection::Auto => detect_language(model, encoded, special)?
+            .into_iter()
+            .next()
+            .map(|(code, _)| code)
+            .ok_or_else(|| ShoutError::Model("language detection returned no candidates".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::mel::MelSpec;
+
+    /// Always strongly prefers one token after <|startoftranscript|>.
+    struct Prefers(u32);
+
+    impl SpeechModel for Prefers {
+        type Encoded = ();
+
+        fn encode(&mut self, _mel: &MelSpec) -> Result<()> {
+            Ok(())
+        }
+
+        fn next_token_logits(&mut self, _encoded: &(), _tokens: &[u32]) -> Result<Vec<f32>> {
+            let mut logits = vec![0.0; 51865];
+            logits[self.0 as usize] = 10.0;
+            Ok(logits)
+        }
+    }
+
+    #[test]
+    fn detects_most_likely_language() {
+        let special = SpecialTokens::whisper_multilingual(51865);
+        let mut model = Prefers(special.language_token("de").unwrap());
+
+        let probs = detect_language(&mut model, &(), &special).unwrap();
+
+        assert_eq!(probs[0].0, "de");
+        assert_eq!(probs.len(), 99);
+        assert!((probs.iter().map(|p| p.1).sum::<f32>() - 1.0).abs() < 1e-4);
+
+        let lang = resolve_language(&LanguageSelection::Auto, &mut model, &(), &special).unwrap();
+        assert_eq!(lang, "de");
+    }
+
+    #[test]
+    fn parses_language_selection() {
+        assert_eq!(
+            "auto".parse::<LanguageSelection>().unwrap(),
+            LanguageSelection::Auto
+        );
+        assert_eq!(
+            "DE".parse::<LanguageSelection>().unwrap(),
+            LanguageSelection::Fixed("de".into())
+        );
+        assert!("xx".parse::<LanguageSelection>().is_err());
+    }
+}
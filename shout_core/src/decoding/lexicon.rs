@@ -0,0 +1,213 @@
+//! Lexicon-constrained CTC decoding: a prefix tree over the unit sequences of
+//! the allowed words, so the beam search can only spell out words from the
+//! list. Meant for command-and-control vocabularies of a few hundred words.
+//!
+//! A lexicon file has one word per line, optionally followed by its
+//! pronunciation as space-separated CTC units (`licht l i c h t |`). Words
+//! without one are spelled with their characters, plus the `|` word delimiter
+//! when the CTC vocabulary has one (wav2vec2 style). A word may be listed
+//! several times with different pronunciations.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::errors::{IoContext, Result, ShoutError};
+
+/// Word delimiter unit of character-level CTC vocabularies.
+pub const WORD_DELIMITER: &str = "|";
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    children: HashMap<u32, usize>,
+
+    /// Index into `words` of the word ending here.
+    word: Option<usize>,
+}
+
+/// Prefix tree over the unit sequences of every allowed word.
+#[derive(Debug, Clone)]
+pub struct Lexicon {
+    nodes: Vec<Node>,
+    words: Vec<String>,
+}
+
+impl Default for Lexicon {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+            words: Vec::new(),
+        }
+    }
+}
+
+impl Lexicon {
+    /// Build from words and their unit sequences.
+    pub fn new<I: IntoIterator<Item = (String, Vec<u32>)>>(entries: I) -> Self {
+        let mut lexicon = Self::default();
+        for (word, units) in entries {
+            lexicon.insert(word, &units);
+        }
+        lexicon
+    }
+
+    /// Read a lexicon file; `units` holds the text of every CTC output.
+    pub fn from_file<P: AsRef<Path>>(path: P, units: &[String]) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .io_context(|| format!("Failed to open lexicon: {}", path.display()))?;
+        Self::read(BufReader::new(file), units)
+            .map_err(|e| e.context(format!("Failed to read lexicon: {}", path.display())))
+    }
+
+    pub fn read<R: BufRead>(reader: R, units: &[String]) -> Result<Self> {
+        let ids: HashMap<&str, u32> = units
+            .iter()
+            .enumerate()
+            .map(|(i, u)| (u.as_str(), i as u32))
+            .collect();
+        let delimiter = ids.get(WORD_DELIMITER).copied();
+
+        let mut lexicon = Self::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let mut fields = line.split_whitespace();
+            let Some(word) = fields.next() else {
+                continue;
+            };
+            let unit = |u: &str| {
+                ids.get(u).copied().ok_or_else(|| {
+                    ShoutError::Config(format!("line {}: unknown unit '{u}' in '{word}'", i + 1))
+                })
+            };
+
+            let pronunciation: Vec<&str> = fields.collect();
+            let units = if pronunciation.is_empty() {
+                let mut spelled: Vec<u32> = word
+                    .chars()
+                    .map(|c| unit(c.to_string().as_str()))
+                    .collect::<Result<_>>()?;
+                spelled.extend(delimiter);
+                spelled
+            } else {
+                pronunciation.into_iter().map(unit).collect::<Result<_>>()?
+            };
+            lexicon.insert(word.to_string(), &units);
+        }
+        Ok(lexicon)
+    }
+
+    pub fn insert(&mut self, word: String, units: &[u32]) {
+        if units.is_empty() {
+            return;
+        }
+
+        let mut node = 0;
+        for &u in units {
+            node = match self.nodes[node].children.get(&u) {
+                Some(&child) => child,
+                None => {
+                    self.nodes.push(Node::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.insert(u, child);
+                    child
+                }
+            };
+        }
+        let index = match self.words.iter().position(|w| *w == word) {
+            Some(index) => index,
+            None => {
+                self.words.push(word);
+                self.words.len() - 1
+            }
+        };
+        self.nodes[node].word = Some(index);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Number of distinct words.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Position of a hypothesis that has not emitted anything yet.
+    pub fn start(&self) -> LexiconState {
+        LexiconState { node: 0 }
+    }
+
+    /// The position after emitting `unit`, or `None` if no word continues
+    /// with it. At the end of a word, the next word may start right away.
+    pub fn advance(&self, state: LexiconState, unit: u32) -> Option<LexiconState> {
+        let node = &self.nodes[state.node];
+        let next = node.children.get(&unit).or_else(|| {
+            node.word?;
+            self.nodes[0].children.get(&unit)
+        })?;
+        Some(LexiconState { node: *next })
+    }
+
+    /// Whether a hypothesis may end here: nothing emitted yet, or at the end
+    /// of a word.
+    pub fn is_complete(&self, state: LexiconState) -> bool {
+        state.node == 0 || self.nodes[state.node].word.is_some()
+    }
+
+    /// The words a unit sequence accepted by [`Lexicon::advance`] spells.
+    /// Where one word's units are a prefix of another's, the longer word wins.
+    pub fn words(&self, units: &[u32]) -> Vec<&str> {
+        let mut out = Vec::new();
+        let mut node = 0;
+        for &u in units {
+            node = match self.nodes[node].children.get(&u) {
+                Some(&child) => child,
+                None => {
+                    if let Some(w) = self.nodes[node].word {
+                        out.push(self.words[w].as_str());
+                    }
+                    match self.nodes[0].children.get(&u) {
+                        Some(&child) => child,
+                        None => return out,
+                    }
+                }
+            };
+        }
+        if let Some(w) = self.nodes[node].word {
+            out.push(self.words[w].as_str());
+        }
+        out
+    }
+}
+
+/// Position of one hypothesis in the [`Lexicon`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LexiconState {
+    node: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spells_words_and_follows_pronunciations() {
+        let units: Vec<String> = ["_", "|", "a", "n", "u", "s"].map(String::from).to_vec();
+        let text = "an\naus\nnass n a s |\n";
+        let lexicon = Lexicon::read(text.as_bytes(), &units).unwrap();
+        assert_eq!(lexicon.len(), 3);
+
+        // "an|aus|"
+        let path = [2, 3, 1, 2, 4, 5, 1];
+        let mut state = lexicon.start();
+        for (i, &u) in path.iter().enumerate() {
+            state = lexicon.advance(state, u).unwrap();
+            assert_eq!(lexicon.is_complete(state), i == 2 || i == 6);
+        }
+        assert_eq!(lexicon.words(&path), ["an", "aus"]);
+        assert!(lexicon.advance(lexicon.start(), 5).is_none());
+        assert!(Lexicon::read("ab".as_bytes(), &units).is_err());
+    }
+}
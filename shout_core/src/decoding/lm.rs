@@ -0,0 +1,307 @@
+//! External word n-gram language model (ARPA format) for shallow fusion.
+//!
+//! Hypotheses are scored as `log p_model + weight * log p_lm + insertion_bonus`
+//! per completed word. Sub-word tokens are joined into words on the fly: a token
+//! starting with a space (or `▁`) closes the previous word.
+//!
+//! Only the ARPA text format is read. KenLM's binary format is not supported;
+//! keep the `.arpa` file `build_binary` was run on.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::errors::{IoContext, Result, ShoutError};
+
+/// log10 probability used for words missing from the LM when it has no `<unk>`.
+const UNK_LOG10: f32 = -10.0;
+
+/// Error for line `i` (0-based) of an ARPA file.
+fn bad_line(i: usize, what: &str) -> ShoutError {
+    ShoutError::Config(format!("line {}: {what}", i + 1))
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Entry {
+    log10_prob: f32,
+    log10_backoff: f32,
+}
+
+/// Backoff n-gram model.
+#[derive(Debug, Default)]
+pub struct NgramLm {
+    order: usize,
+    vocab: HashMap<String, u32>,
+
+    /// `ngrams[n - 1]` holds the n-grams, keyed by word ids.
+    ngrams: Vec<HashMap<Vec<u32>, Entry>>,
+}
+
+impl NgramLm {
+    /// Load an ARPA file.
+    pub fn from_arpa<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).io_context(|| format!("Failed to open LM: {}", path.display()))?;
+        Self::read_arpa(BufReader::new(file))
+            .map_err(|e| e.context(format!("Failed to read ARPA LM: {}", path.display())))
+    }
+
+    pub fn read_arpa<R: BufRead>(reader: R) -> Result<Self> {
+        let mut lm = NgramLm::default();
+        let mut section: Option<usize> = None;
+        let mut seen_data = false;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line == "\\data\\" {
+                seen_data = true;
+                continue;
+            }
+            if line == "\\end\\" {
+                break;
+            }
+            if let Some(n) = line
+                .strip_prefix('\\')
+                .and_then(|l| l.strip_suffix("-grams:"))
+            {
+                let n: usize = n.parse().map_err(|_| bad_line(i, "bad section"))?;
+                lm.order = lm.order.max(n);
+                while lm.ngrams.len() < n {
+                    lm.ngrams.push(HashMap::new());
+                }
+                section = Some(n);
+                continue;
+            }
+
+            let Some(n) = section else {
+                // `ngram N=count` lines in the header.
+                continue;
+            };
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < n + 1 {
+                return Err(bad_line(i, &format!("expected {n} words")));
+            }
+            let log10_prob: f32 = fields[0]
+                .parse()
+                .map_err(|_| bad_line(i, "bad probability"))?;
+            let log10_backoff = match fields.get(n + 1) {
+                Some(b) => b.parse().map_err(|_| bad_line(i, "bad backoff"))?,
+                None => 0.0,
+            };
+
+            let key: Vec<u32> = fields[1..=n].iter().map(|w| lm.intern(w)).collect();
+            lm.ngrams[n - 1].insert(
+                key,
+                Entry {
+                    log10_prob,
+                    log10_backoff,
+                },
+            );
+        }
+
+        if !seen_data || lm.order == 0 {
+            return Err(ShoutError::UnsupportedFormat(
+                "not an ARPA file (KenLM binaries are not supported)".into(),
+            ));
+        }
+        Ok(lm)
+    }
+
+    fn intern(&mut self, word: &str) -> u32 {
+        let next = self.vocab.len() as u32;
+        *self.vocab.entry(word.to_string()).or_insert(next)
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn word_id(&self, word: &str) -> Option<u32> {
+        self.vocab.get(word).copied()
+    }
+
+    /// Natural-log probability of `word` after `context` (oldest word first), with backoff.
+    pub fn log_prob(&self, context: &[u32], word: Option<u32>) -> f32 {
+        let word = match word.or_else(|| self.word_id("<unk>")) {
+            Some(w) => w,
+            None => return UNK_LOG10 * std::f32::consts::LN_10,
+        };
+
+        let ctx = &context[context.len().saturating_sub(self.order - 1)..];
+        let mut backoff = 0.0;
+        for start in 0..=ctx.len() {
+            let history = &ctx[start..];
+            let key = [history, &[word]].concat();
+            if let Some(e) = self.ngrams[key.len() - 1].get(&key) {
+                return (backoff + e.log10_prob) * std::f32::consts::LN_10;
+            }
+            if let Some(e) = history
+                .len()
+                .checked_sub(1)
+                .and_then(|n| self.ngrams[n].get(history))
+            {
+                backoff += e.log10_backoff;
+            }
+        }
+        (backoff + UNK_LOG10) * std::f32::consts::LN_10
+    }
+}
+
+/// Word-level state of one hypothesis.
+#[derive(Debug, Clone, Default)]
+pub struct LmState {
+    /// Last `order - 1` completed words.
+    history: Vec<u32>,
+
+    /// Text of the word still being spelled out.
+    partial: String,
+}
+
+/// An LM plus how it is combined with the acoustic model.
+#[derive(Debug, Clone)]
+pub struct LmFusion {
+    pub lm: Arc<NgramLm>,
+
+    /// Text of every token id the search can emit; ids past the end score nothing.
+    pub token_texts: Arc<Vec<String>>,
+
+    /// Scale of the LM log-probability.
+    pub weight: f32,
+
+    /// Added per word, counteracting the LM's bias towards short outputs.
+    pub insertion_bonus: f32,
+}
+
+impl LmFusion {
+    pub fn new(
+        lm: Arc<NgramLm>,
+        token_texts: Vec<String>,
+        weight: f32,
+        insertion_bonus: f32,
+    ) -> Self {
+        Self {
+            lm,
+            token_texts: Arc::new(token_texts),
+            weight,
+            insertion_bonus,
+        }
+    }
+
+    /// State at the beginning of a sentence.
+    pub fn start_state(&self) -> LmState {
+        LmState {
+            history: self.lm.word_id("<s>").into_iter().collect(),
+            partial: String::new(),
+        }
+    }
+
+    /// Score adjustment for appending `token`, and the state afterwards.
+    pub fn advance(&self, state: &LmState, token: u32) -> (f32, LmState) {
+        let Some(text) = self.token_texts.get(token as usize) else {
+            return (0.0, state.clone());
+        };
+
+        let mut next = state.clone();
+        let starts_word = text.starts_with([' ', '▁']) || text == "|";
+        if !starts_word {
+            next.partial.push_str(text);
+            return (0.0, next);
+        }
+
+        let score = self.complete_word(&mut next);
+        next.partial = text.trim_start_matches([' ', '▁', '|']).to_string();
+        (score, next)
+    }
+
+    /// Score of the unfinished word plus end-of-sentence.
+    pub fn finish(&self, state: &LmState) -> f32 {
+        let mut state = state.clone();
+        let word = self.complete_word(&mut state);
+        let eos = self.lm.log_prob(&state.history, self.lm.word_id("</s>"));
+        word + self.weight * eos
+    }
+
+    fn complete_word(&self, state: &mut LmState) -> f32 {
+        let word: String = state
+            .partial
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '\'')
+            .flat_map(char::to_lowercase)
+            .collect();
+        state.partial.clear();
+        if word.is_empty() {
+            return 0.0;
+        }
+
+        let id = self.lm.word_id(&word);
+        let score = self.weight * self.lm.log_prob(&state.history, id) + self.insertion_bonus;
+
+        state
+            .history
+            .push(id.or_else(|| self.lm.word_id("<unk>")).unwrap_or(u32::MAX));
+        let keep = self.lm.order().saturating_sub(1);
+        if state.history.len() > keep {
+            state.history.drain(..state.history.len() - keep);
+        }
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARPA: &str = "\\data\\
+ngram 1=4
+ngram 2=2
+
+\\1-grams:
+-1.0\t<s>\t-0.5
+-1.0\t</s>
+-0.5\thello\t-0.3
+-2.0\tworld
+
+\\2-grams:
+-0.1\t<s> hello
+-0.2\thello world
+
+\\end\\
+";
+
+    #[test]
+    fn scores_with_backoff() {
+        let lm = NgramLm::read_arpa(ARPA.as_bytes()).unwrap();
+        let id = |w| lm.word_id(w).unwrap();
+        let ln = |x: f32| x * std::f32::consts::LN_10;
+
+        assert!((lm.log_prob(&[id("hello")], Some(id("world"))) - ln(-0.2)).abs() < 1e-5);
+        // "world hello" is unseen: backoff(world)=0 + p(hello).
+        assert!((lm.log_prob(&[id("world")], Some(id("hello"))) - ln(-0.5)).abs() < 1e-5);
+        // "<s> world": backoff(<s>) + p(world).
+        assert!((lm.log_prob(&[id("<s>")], Some(id("world"))) - ln(-2.5)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn joins_subword_tokens_into_words() {
+        let lm = Arc::new(NgramLm::read_arpa(ARPA.as_bytes()).unwrap());
+        let texts = vec![" hel".to_string(), "lo".to_string(), " world".to_string()];
+        let fusion = LmFusion::new(lm, texts, 1.0, 0.0);
+
+        let (s0, st) = fusion.advance(&fusion.start_state(), 0);
+        let (s1, st) = fusion.advance(&st, 1);
+        let (s2, st) = fusion.advance(&st, 2);
+        assert_eq!(s0, 0.0);
+        assert_eq!(s1, 0.0);
+        assert!((s2 - (-0.1 * std::f32::consts::LN_10)).abs() < 1e-5);
+        assert!(fusion.finish(&st) < 0.0);
+    }
+}
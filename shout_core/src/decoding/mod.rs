@@ -0,0 +1,155 @@
+pub mod beam;
+pub mod biasing;
+pub mod ctc;
+pub mod fallback;
+pub mod grammar;
+pub mod greedy;
+pub mod language;
+pub mod lexicon;
+pub mod lm;
+pub mod prompt;
+pub mod repetition;
+pub mod timestamps;
+
+use ndarray::Array2;
+
+use crate::audio::mel::MelSpec;
+use crate::cancel::CancelToken;
+use crate::errors::Result;
+
+use biasing::BiasingTrie;
+use grammar::GrammarConstraint;
+use language::LanguageSelection;
+use lm::LmFusion;
+use prompt::Task;
+
+/// User-facing decoding settings shared by every decoding strategy.
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    pub task: Task,
+
+    /// Language to decode in, or `Auto` to detect it per window.
+    pub language: LanguageSelection,
+
+    /// Predict timestamp tokens (needed for segment times).
+    pub with_timestamps: bool,
+
+    /// Upper bound on generated tokens per window (Whisper: 224).
+    pub max_tokens: usize,
+
+    /// Use beam search with this many beams at temperature 0 instead of greedy decoding.
+    pub beam_size: Option<usize>,
+
+    /// Hotwords favoured during beam search.
+    pub biasing: Option<BiasingTrie>,
+
+    /// External language model fused into beam search.
+    pub lm: Option<LmFusion>,
+
+    /// Only phrases of this grammar may be decoded.
+    pub grammar: Option<GrammarConstraint>,
+
+    /// Temperatures tried in order until an output passes the checks below.
+    /// `0.0` is greedy decoding; higher values sample.
+    pub temperatures: Vec<f32>,
+
+    /// Retry when the gzip compression ratio of the text exceeds this (repetitive output).
+    pub compression_ratio_threshold: Option<f32>,
+
+    /// Retry when the average token log-probability falls below this.
+    pub logprob_threshold: Option<f32>,
+
+    /// A window whose `<|nospeech|>` probability exceeds this, and whose output also
+    /// fails `logprob_threshold`, is treated as silence instead of retried.
+    pub no_speech_threshold: Option<f32>,
+
+    /// Seed for sampling at non-zero temperatures.
+    pub seed: u64,
+
+    /// Never generate the same text `n`-gram twice within a window. `0` disables.
+    pub no_repeat_ngram_size: usize,
+
+    /// Cap generated tokens at this rate relative to the window's audio length;
+    /// a window that runs into the cap is treated as a loop and retried.
+    pub max_tokens_per_second: Option<f32>,
+
+    /// Segments matching one of these phrases are dropped when the window looks silent.
+    pub hallucination_phrases: Vec<String>,
+
+    /// `<|nospeech|>` probability above which a window counts as silent for
+    /// `hallucination_phrases`.
+    pub hallucination_no_speech_threshold: f32,
+
+    /// Checked before every decoding step; see [`crate::cancel`].
+    pub cancel: CancelToken,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            task: Task::Transcribe,
+            language: LanguageSelection::Auto,
+            with_timestamps: true,
+            max_tokens: 224,
+            beam_size: None,
+            biasing: None,
+            lm: None,
+            grammar: None,
+            temperatures: vec![0.0, 0.2, 0.4, 0.6, 0.8, 1.0],
+            compression_ratio_threshold: Some(2.4),
+            logprob_threshold: Some(-1.0),
+            no_speech_threshold: Some(0.6),
+            seed: 0,
+            no_repeat_ngram_size: 0,
+            max_tokens_per_second: Some(15.0),
+            hallucination_phrases: repetition::DEFAULT_HALLUCINATIONS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            hallucination_no_speech_threshold: 0.2,
+            cancel: CancelToken::default(),
+        }
+    }
+}
+
+/// The parts of a Whisper-style encoder/decoder model the decoding algorithms need.
+///
+/// Implemented by the inference backends; the decoding code itself never touches tensors.
+pub trait SpeechModel {
+    /// Encoder output for one window, reused for every decoding step.
+    type Encoded;
+
+    /// Run the encoder over one window of log-mel features.
+    fn encode(&mut self, mel: &MelSpec) -> Result<Self::Encoded>;
+
+    /// Run the encoder over several windows in one forward pass, one output
+    /// per window. The default encodes them one after another.
+    fn encode_batch(&mut self, mels: &[&MelSpec]) -> Result<Vec<Self::Encoded>> {
+        mels.iter().map(|mel| self.encode(mel)).collect()
+    }
+
+    /// Logits over the vocabulary for the token following `tokens`.
+    fn next_token_logits(&mut self, encoded: &Self::Encoded, tokens: &[u32]) -> Result<Vec<f32>>;
+
+    /// Cross-attention of the alignment heads while reading `tokens`, averaged
+    /// over the heads: one row per token, one column per encoder frame. Row
+    /// `i` is the attention of the step that predicts `tokens[i + 1]`.
+    ///
+    /// `None` (the default) if the model cannot provide it; its segments then
+    /// carry no word timings.
+    fn alignment_attention(
+        &mut self,
+        _encoded: &Self::Encoded,
+        _tokens: &[u32],
+    ) -> Result<Option<Array2<f32>>> {
+        Ok(None)
+    }
+}
+
+/// Numerically stable log-softmax.
+pub fn log_softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let sum: f32 = logits.iter().map(|&l| (l - max).exp()).sum();
+    let log_z = max + sum.ln();
+    logits.iter().map(|&l| l - log_z).collect()
+}
@@ -0,0 +1,117 @@
+//! Construction of the decoder prompt (the token prefix before the first text token).
+
+use std::str::FromStr;
+
+use crate::errors::{Result, ShoutError};
+use crate::tokenizer::special_tokens::SpecialTokens;
+
+/// What the decoder is asked to produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Task {
+    /// Text in the spoken language.
+    #[default]
+    Transcribe,
+
+    /// English text, whatever the spoken language.
+    Translate,
+}
+
+impl Task {
+    pub fn token(self, special: &SpecialTokens) -> u32 {
+        match self {
+            Task::Transcribe => special.transcribe,
+            Task::Translate => special.translate,
+        }
+    }
+
+    /// Language of the produced text for audio in `spoken`. Evaluation uses this to
+    /// pick the reference text and normalizer.
+    pub fn output_language(self, spoken: &str) -> &str {
+        match self {
+            Task::Transcribe => spoken,
+            Task::Translate => "en",
+        }
+    }
+}
+
+impl FromStr for Task {
+    type Err = ShoutError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "transcribe" => Ok(Task::Transcribe),
+            "translate" => Ok(Task::Translate),
+            other => Err(ShoutError::InvalidArgument(format!(
+                "unknown task '{other}' (expected transcribe or translate)"
+            ))),
+        }
+    }
+}
+
+/// `<|startoftranscript|> <|lang|> <|task|> [<|notimestamps|>]`.
+pub fn sot_sequence(
+    special: &SpecialTokens,
+    language: &str,
+    task: Task,
+    with_timestamps: bool,
+) -> Result<Vec<u32>> {
+    let lang = special.language_token(language).ok_or_else(|| {
+        ShoutError::Tokenizer(format!(
+            "language '{language}' has no token in this vocabulary"
+        ))
+    })?;
+
+    let mut tokens = vec![special.sot, lang, task.token(special)];
+    if !with_timestamps {
+        tokens.push(special.no_timestamps);
+    }
+    Ok(tokens)
+}
+
+/// Full prompt including previous-context tokens:
+/// `<|startofprev|> previous... <|startoftranscript|> <|lang|> <|task|> ...`.
+///
+/// `previous` is truncated from the front so it takes at most `max_previous` tokens
+/// (Whisper uses half the text context, 223 tokens).
+pub fn build_prompt(
+    special: &SpecialTokens,
+    previous: &[u32],
+    max_previous: usize,
+    language: &str,
+    task: Task,
+    with_timestamps: bool,
+) -> Result<Vec<u32>> {
+    let mut tokens = Vec::new();
+    if !previous.is_empty() && max_previous > 0 {
+        let keep = previous.len().min(max_previous);
+        tokens.push(special.sot_prev);
+        tokens.extend_from_slice(&previous[previous.len() - keep..]);
+    }
+    tokens.extend(sot_sequence(special, language, task, with_timestamps)?);
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_translate_prompt_with_context() {
+        let special = SpecialTokens::whisper_multilingual(51865);
+        let prompt = build_prompt(&special, &[1, 2, 3], 2, "de", Task::Translate, false).unwrap();
+
+        assert_eq!(
+            prompt,
+            vec![
+                special.sot_prev,
+                2,
+                3,
+                special.sot,
+                50261,
+                special.translate,
+                special.no_timestamps
+            ]
+        );
+        assert_eq!(Task::Translate.output_language("de"), "en");
+    }
+}
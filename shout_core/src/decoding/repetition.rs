@@ -0,0 +1,114 @@
+//! Guards against the decoder getting stuck: repeated n-grams, runaway token
+//! counts and the stock phrases Whisper-style models produce on silence.
+
+use crate::tokenizer::special_tokens::SpecialTokens;
+
+/// Fewest tokens a window may generate regardless of `max_tokens_per_second`,
+/// so very short windows still fit a timestamped segment.
+pub const MIN_TOKEN_BUDGET: usize = 16;
+
+/// Phrases the model is known to emit on silence or music (subtitle credits
+/// from its training data).
+pub const DEFAULT_HALLUCINATIONS: &[&str] = &[
+    "Thank you for watching.",
+    "Thanks for watching!",
+    "Please subscribe to my channel.",
+    "Subtitles by the Amara.org community",
+    "Untertitel der Amara.org-Community",
+    "Untertitel im Auftrag des ZDF, 2017",
+    "Untertitelung des ZDF, 2020",
+    "Untertitel von Stephanie Geiges",
+    "Vielen Dank fürs Zuschauen!",
+    "Bis zum nächsten Mal.",
+    "Sous-titres réalisés par la communauté d'Amara.org",
+    "Subtítulos realizados por la comunidad de Amara.org",
+];
+
+/// Mask every text token that would complete an `n`-gram already present in
+/// the generated text tokens. Timestamps and special tokens are ignored.
+pub fn block_repeated_ngrams(
+    logits: &mut [f32],
+    generated: &[u32],
+    n: usize,
+    special: &SpecialTokens,
+) {
+    if n == 0 {
+        return;
+    }
+    let text: Vec<u32> = generated
+        .iter()
+        .copied()
+        .filter(|&t| t < special.eot)
+        .collect();
+    if text.len() < n {
+        return;
+    }
+
+    let prefix = &text[text.len() + 1 - n..];
+    for window in text.windows(n) {
+        if window[..n - 1] == *prefix
+            && let Some(l) = logits.get_mut(window[n - 1] as usize)
+        {
+            *l = f32::NEG_INFINITY;
+        }
+    }
+}
+
+/// Token budget for `audio_ms` of audio at `max_per_second` tokens per second.
+pub fn token_budget(audio_ms: u64, max_per_second: f32) -> usize {
+    let budget = (audio_ms as f32 / 1000.0 * max_per_second).ceil() as usize;
+    budget.max(MIN_TOKEN_BUDGET)
+}
+
+/// Lowercased text with punctuation and whitespace removed, for phrase matching.
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Whether `text` is one of `phrases`, ignoring case, punctuation and spacing.
+pub fn is_hallucination(text: &str, phrases: &[String]) -> bool {
+    let text = normalize(text);
+    !text.is_empty() && phrases.iter().any(|p| normalize(p) == text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_only_the_repeating_continuation() {
+        let special = SpecialTokens::whisper_multilingual(51865);
+        let ts = special.timestamp_begin;
+        let mut logits = vec![0.0; 51865];
+
+        // "1 2 3 | 1 2" -> 3 would repeat the trigram "1 2 3".
+        block_repeated_ngrams(&mut logits, &[ts, 1, 2, 3, ts + 5, 1, 2], 3, &special);
+
+        assert_eq!(logits[3], f32::NEG_INFINITY);
+        assert_eq!(logits[4], 0.0);
+        assert_eq!(logits[special.eot as usize], 0.0);
+    }
+
+    #[test]
+    fn budget_scales_with_duration() {
+        assert_eq!(token_budget(30_000, 10.0), 300);
+        assert_eq!(token_budget(200, 10.0), MIN_TOKEN_BUDGET);
+    }
+
+    #[test]
+    fn matches_phrases_loosely() {
+        let phrases: Vec<String> = DEFAULT_HALLUCINATIONS
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(is_hallucination(
+            " Untertitel der Amara.org-Community",
+            &phrases
+        ));
+        assert!(is_hallucination("thanks for watching", &phrases));
+        assert!(!is_hallucination("Thanks for watching the kids.", &phrases));
+    }
+}
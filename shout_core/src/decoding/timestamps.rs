@@ -0,0 +1,129 @@
+//! Timestamp-token rules during decoding and segment splitting afterwards.
+
+use crate::errors::Result;
+use crate::tokenizer::bpe::Tokenizer;
+use crate::tokenizer::special_tokens::SpecialTokens;
+use crate::transcript::{Segment, TokenScore};
+
+/// Largest timestamp allowed as the very first token (Whisper: 1.0 s).
+const MAX_INITIAL_TIMESTAMP_MS: u64 = 1000;
+
+/// Constrain `logits` so timestamps come in well-formed pairs, as in Whisper's
+/// `ApplyTimestampRules`. `sampled` are the tokens generated after the prompt.
+pub fn apply_timestamp_rules(logits: &mut [f32], sampled: &[u32], special: &SpecialTokens) {
+    let ts_begin = special.timestamp_begin as usize;
+    let eot = special.eot as usize;
+    let mask = |logits: &mut [f32], range: std::ops::Range<usize>| {
+        let end = range.end.min(logits.len());
+        logits[range.start.min(end)..end].fill(f32::NEG_INFINITY);
+    };
+
+    logits[special.no_timestamps as usize] = f32::NEG_INFINITY;
+
+    let last_was_ts = sampled.last().is_some_and(|&t| special.is_timestamp(t));
+    let penultimate_was_ts = sampled.len() < 2 || special.is_timestamp(sampled[sampled.len() - 2]);
+
+    if last_was_ts {
+        if penultimate_was_ts {
+            // A pair just closed: text (or end) must follow.
+            mask(logits, ts_begin..logits.len());
+        } else {
+            // Inside a pair: only a timestamp or end-of-text may follow.
+            mask(logits, 0..eot);
+        }
+    }
+
+    // Timestamps never go backwards.
+    if let Some(&last_ts) = sampled.iter().rev().find(|&&t| special.is_timestamp(t)) {
+        let floor = if last_was_ts && !penultimate_was_ts {
+            last_ts as usize
+        } else {
+            last_ts as usize + 1
+        };
+        mask(logits, ts_begin..floor);
+    }
+
+    if sampled.is_empty() {
+        // The first token must be a timestamp, and not too far in.
+        mask(logits, 0..ts_begin);
+        let max_initial = ts_begin + (MAX_INITIAL_TIMESTAMP_MS / 20) as usize;
+        mask(logits, max_initial + 1..logits.len());
+    }
+
+    // If the total probability of all timestamps beats any single text token, force a timestamp.
+    let logprobs = super::log_softmax(logits);
+    let ts_logprob = log_sum_exp(&logprobs[ts_begin.min(logprobs.len())..]);
+    let max_text = logprobs[..ts_begin.min(logprobs.len())]
+        .iter()
+        .copied()
+        .fold(f32::NEG_INFINITY, f32::max);
+    if ts_logprob > max_text {
+        mask(logits, 0..ts_begin);
+    }
+}
+
+fn log_sum_exp(xs: &[f32]) -> f32 {
+    let max = xs.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    if max == f32::NEG_INFINITY {
+        return max;
+    }
+    max + xs.iter().map(|&x| (x - max).exp()).sum::<f32>().ln()
+}
+
+/// Split decoded tokens into segments at timestamp pairs.
+///
+/// `tokens`/`logprobs` are the generated tokens after the prompt (end-of-text
+/// excluded); `window_end_ms` closes a final segment that has no end timestamp.
+/// Times are relative to the window start.
+pub fn split_segments(
+    tokens: &[u32],
+    logprobs: &[f32],
+    tokenizer: &Tokenizer,
+    window_end_ms: u64,
+) -> Result<Vec<Segment>> {
+    let special = &tokenizer.special;
+    let mut segments = Vec::new();
+    let mut start_ms: Option<u64> = None;
+    let mut text: Vec<(u32, f32)> = Vec::new();
+
+    let mut close = |start: u64, end: u64, text: &mut Vec<(u32, f32)>| -> Result<()> {
+        let ids: Vec<u32> = text.iter().map(|&(t, _)| t).collect();
+        segments.push(Segment {
+            start_ms: start,
+            end_ms: end.max(start),
+            text: tokenizer.decode(&ids)?,
+            words: Vec::new(),
+            tokens: text
+                .iter()
+                .map(|&(id, logprob)| TokenScore {
+                    id,
+                    text: tokenizer.token_text(id),
+                    logprob,
+                })
+                .collect(),
+        });
+        text.clear();
+        Ok(())
+    };
+
+    for (&tok, &lp) in tokens.iter().zip(logprobs) {
+        if let Some(ts) = special.timestamp_ms(tok) {
+            match start_ms {
+                Some(start) if !text.is_empty() => {
+                    close(start, ts, &mut text)?;
+                    start_ms = None;
+                }
+                _ => start_ms = Some(ts),
+            }
+        } else if tok < special.eot {
+            start_ms.get_or_insert(0);
+            text.push((tok, lp));
+        }
+    }
+
+    if !text.is_empty() {
+        close(start_ms.unwrap_or(0), window_end_ms, &mut text)?;
+    }
+
+    Ok(segments)
+}
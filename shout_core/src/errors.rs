@@ -0,0 +1,129 @@
+//! The library's error type.
+//!
+//! Every fallible public function returns [`Result`], whose error says which
+//! stage failed so callers can react to, say, an unsupported file differently
+//! from a broken model. Messages carry the details (paths, line numbers) and
+//! include the underlying error, so printing one is enough.
+
+use std::fmt::Display;
+use std::io;
+
+use thiserror::Error;
+
+use crate::cancel::Interruption;
+use crate::transcript::Transcript;
+
+pub type Result<T, E = ShoutError> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ShoutError {
+    /// Reading or writing a file or stream failed.
+    #[error("{context}: {error}")]
+    Io { context: String, error: io::Error },
+
+    /// The audio container, codec or sample format, or the model file format,
+    /// is not supported.
+    #[error("{0}")]
+    UnsupportedFormat(String),
+
+    /// The audio could not be decoded.
+    #[error("{0}")]
+    Decode(String),
+
+    #[error("{0}")]
+    Resample(String),
+
+    /// Computing features from the audio failed.
+    #[error("{0}")]
+    Feature(String),
+
+    /// Loading or running the model failed, including tensor errors.
+    #[error("{0}")]
+    Model(String),
+
+    #[error("{0}")]
+    Tokenizer(String),
+
+    /// A file (model config, manifest, language model) has invalid contents.
+    #[error("{0}")]
+    Config(String),
+
+    /// No usable compute or audio device.
+    #[error("{0}")]
+    Device(String),
+
+    /// An option or argument has an invalid value.
+    #[error("{0}")]
+    InvalidArgument(String),
+
+    /// A [`CancelToken`](crate::cancel::CancelToken) was cancelled or expired.
+    /// `partial` holds the segments transcribed before that, often none.
+    #[error("{reason}")]
+    Interrupted {
+        reason: Interruption,
+        partial: Box<Transcript>,
+    },
+}
+
+impl ShoutError {
+    pub fn io(context: impl Display, error: io::Error) -> Self {
+        ShoutError::Io {
+            context: context.to_string(),
+            error,
+        }
+    }
+
+    /// The same kind of error, with `context` put in front of its message.
+    pub fn context(self, context: impl Display) -> Self {
+        use ShoutError::*;
+        match self {
+            Io { context: c, error } => Io {
+                context: format!("{context}: {c}"),
+                error,
+            },
+            UnsupportedFormat(m) => UnsupportedFormat(format!("{context}: {m}")),
+            Decode(m) => Decode(format!("{context}: {m}")),
+            Resample(m) => Resample(format!("{context}: {m}")),
+            Feature(m) => Feature(format!("{context}: {m}")),
+            Model(m) => Model(format!("{context}: {m}")),
+            Tokenizer(m) => Tokenizer(format!("{context}: {m}")),
+            Config(m) => Config(format!("{context}: {m}")),
+            Device(m) => Device(format!("{context}: {m}")),
+            InvalidArgument(m) => InvalidArgument(format!("{context}: {m}")),
+            interrupted @ Interrupted { .. } => interrupted,
+        }
+    }
+}
+
+impl From<io::Error> for ShoutError {
+    fn from(error: io::Error) -> Self {
+        ShoutError::io("I/O error", error)
+    }
+}
+
+#[cfg(feature = "inference")]
+impl From<candle_core::Error> for ShoutError {
+    fn from(error: candle_core::Error) -> Self {
+        ShoutError::Model(error.to_string())
+    }
+}
+
+/// Only for writing JSON, where errors are I/O errors; invalid JSON input is
+/// reported as [`ShoutError::Config`] with the file it came from.
+impl From<serde_json::Error> for ShoutError {
+    fn from(error: serde_json::Error) -> Self {
+        ShoutError::io("failed to write JSON", error.into())
+    }
+}
+
+/// `anyhow::Context`-like helpers for I/O results.
+pub(crate) trait IoContext<T> {
+    fn io_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T>;
+}
+
+impl<T> IoContext<T> for std::result::Result<T, io::Error> {
+    fn io_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|e| ShoutError::io(context(), e))
+    }
+}
@@ -0,0 +1,229 @@
+//! Acoustic features: what the encoder sees of 16 kHz mono audio.
+//!
+//! A [`FeatureExtractor`] turns samples into a `(frames, dim)` matrix at 100
+//! frames per second; an [`AudioFrontEnd`] is the fallible, model-facing side
+//! that the transcriber and batch pipeline call. Every extractor is a front end;
+//! learned front ends that run a network implement [`AudioFrontEnd`] directly.
+//! Which one a checkpoint uses is part of its `config.json` (see
+//! [`FrontEndConfig`]), log-mel unless it says otherwise.
+
+use std::f32::consts::PI;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use ndarray::Array2;
+
+#[allow(deprecated)]
+pub use crate::audio::mel::pcm_to_mel_frames_flat;
+pub use crate::audio::mel::{MelSpec, TimeMajor, log_mel, whisper_mel};
+pub use crate::config::model::FrontEndConfig;
+use crate::config::model::ModelConfig;
+use crate::errors::Result;
+use crate::registry;
+
+/// Sample rate all features are computed at.
+pub const SAMPLE_RATE: u32 = 16_000;
+
+/// Samples between two feature frames (10 ms).
+pub const HOP_LENGTH: usize = 160;
+
+/// Computes a feature matrix from 16 kHz mono samples.
+pub trait FeatureExtractor: Debug + Send + Sync {
+    /// Values per frame.
+    fn dim(&self) -> usize;
+
+    /// Features of `pcm`, shape `(frames, dim)`.
+    fn extract(&self, pcm: &[f32]) -> Array2<f32>;
+}
+
+/// Turns 16 kHz mono samples into the encoder's input.
+pub trait AudioFrontEnd: Debug + Send + Sync {
+    /// Values per frame; the model's `n_mels`.
+    fn feature_dim(&self) -> usize;
+
+    fn features(&self, pcm: &[f32]) -> Result<MelSpec>;
+}
+
+impl<T: FeatureExtractor> AudioFrontEnd for T {
+    fn feature_dim(&self) -> usize {
+        self.dim()
+    }
+
+    fn features(&self, pcm: &[f32]) -> Result<MelSpec> {
+        Ok(MelSpec::from(self.extract(pcm)))
+    }
+}
+
+/// The front end a model with `config` was trained with. Fails if it names a
+/// plugin that is not registered or that computes features of another size.
+pub fn front_end(config: &ModelConfig) -> Result<Arc<dyn AudioFrontEnd>> {
+    Ok(match &config.front_end {
+        FrontEndConfig::LogMel => Arc::new(LogMel::new(config.n_mels)),
+        FrontEndConfig::WhisperLogMel => Arc::new(WhisperLogMel::new(config.n_mels)),
+        FrontEndConfig::Mfcc { n_filters } => Arc::new(Mfcc::new(*n_filters, config.n_mels)),
+        FrontEndConfig::RawWaveform => Arc::new(RawWaveform::new(config.n_mels)),
+        FrontEndConfig::Plugin { name, params } => {
+            return registry::global().feature_extractor(name, config.n_mels, params);
+        }
+    })
+}
+
+/// Log-mel spectrogram (25 ms window, 10 ms hop).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogMel {
+    pub n_mels: usize,
+}
+
+impl LogMel {
+    pub fn new(n_mels: usize) -> Self {
+        Self { n_mels }
+    }
+}
+
+impl FeatureExtractor for LogMel {
+    fn dim(&self) -> usize {
+        self.n_mels
+    }
+
+    fn extract(&self, pcm: &[f32]) -> Array2<f32> {
+        log_mel(pcm, self.n_mels)
+    }
+}
+
+/// Log-mel spectrogram exactly as OpenAI Whisper computes it (see
+/// [`whisper_mel`]), for checkpoints trained by OpenAI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhisperLogMel {
+    pub n_mels: usize,
+}
+
+impl WhisperLogMel {
+    pub fn new(n_mels: usize) -> Self {
+        Self { n_mels }
+    }
+}
+
+impl FeatureExtractor for WhisperLogMel {
+    fn dim(&self) -> usize {
+        self.n_mels
+    }
+
+    fn extract(&self, pcm: &[f32]) -> Array2<f32> {
+        whisper_mel(pcm, self.n_mels).to_array()
+    }
+}
+
+/// Mel-frequency cepstral coefficients: the orthonormal DCT-II of a log-mel
+/// spectrogram with `n_filters` bins, keeping the first `n_coeffs`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mfcc {
+    log_mel: LogMel,
+
+    /// `(n_filters, n_coeffs)`.
+    dct: Array2<f32>,
+}
+
+impl Mfcc {
+    pub fn new(n_filters: usize, n_coeffs: usize) -> Self {
+        let n = n_filters as f32;
+        let dct = Array2::from_shape_fn((n_filters, n_coeffs), |(i, k)| {
+            let scale = if k == 0 {
+                (1.0 / n).sqrt()
+            } else {
+                (2.0 / n).sqrt()
+            };
+            scale * (PI * k as f32 * (i as f32 + 0.5) / n).cos()
+        });
+        Self {
+            log_mel: LogMel::new(n_filters),
+            dct,
+        }
+    }
+
+    /// Cepstra of a `(frames, n_filters)` log-mel matrix.
+    pub fn cepstra(&self, log_mel: &Array2<f32>) -> Array2<f32> {
+        log_mel.dot(&self.dct)
+    }
+}
+
+impl FeatureExtractor for Mfcc {
+    fn dim(&self) -> usize {
+        self.dct.ncols()
+    }
+
+    fn extract(&self, pcm: &[f32]) -> Array2<f32> {
+        self.cepstra(&self.log_mel.extract(pcm))
+    }
+}
+
+/// The samples themselves: a frame of `frame_len` samples every 10 ms, zero
+/// padded at the end, for encoders that learn their own filterbank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawWaveform {
+    pub frame_len: usize,
+}
+
+impl RawWaveform {
+    pub fn new(frame_len: usize) -> Self {
+        Self { frame_len }
+    }
+}
+
+impl FeatureExtractor for RawWaveform {
+    fn dim(&self) -> usize {
+        self.frame_len
+    }
+
+    fn extract(&self, pcm: &[f32]) -> Array2<f32> {
+        let n_frames = pcm.len().div_ceil(HOP_LENGTH);
+        Array2::from_shape_fn((n_frames, self.frame_len), |(t, i)| {
+            pcm.get(t * HOP_LENGTH + i).copied().unwrap_or(0.0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mfcc_of_flat_spectrum_is_all_energy() {
+        let mfcc = Mfcc::new(40, 13);
+        let cepstra = mfcc.cepstra(&Array2::from_elem((3, 40), 2.0));
+        assert_eq!(cepstra.dim(), (3, 13));
+        // c0 of a constant is its value times sqrt(n); the rest vanish.
+        assert!((cepstra[[0, 0]] - 2.0 * 40f32.sqrt()).abs() < 1e-4);
+        assert!(cepstra.row(1).iter().skip(1).all(|c| c.abs() < 1e-4));
+    }
+
+    #[test]
+    fn raw_waveform_frames_overlap_and_pad() {
+        let pcm: Vec<f32> = (0..400).map(|i| i as f32).collect();
+        let frames = RawWaveform::new(200).extract(&pcm);
+        assert_eq!(frames.dim(), (3, 200));
+        assert_eq!(frames[[1, 0]], 160.0);
+        assert_eq!(frames[[1, 199]], 359.0);
+        assert_eq!(frames[[2, 100]], 0.0);
+    }
+
+    #[test]
+    fn front_end_follows_config() {
+        let mut config = ModelConfig::tiny();
+        assert_eq!(front_end(&config).unwrap().feature_dim(), 80);
+        config.front_end = FrontEndConfig::Mfcc { n_filters: 40 };
+        config.n_mels = 13;
+        let features = front_end(&config).unwrap().features(&[0.1; 1600]).unwrap();
+        assert_eq!(features.n_mels, 13);
+
+        config.front_end = FrontEndConfig::Plugin {
+            name: "raw_waveform".into(),
+            params: Default::default(),
+        };
+        assert_eq!(front_end(&config).unwrap().feature_dim(), 13);
+        config.front_end = FrontEndConfig::Plugin {
+            name: "missing".into(),
+            params: Default::default(),
+        };
+        assert!(front_end(&config).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+//! Loading a model and transcribing with it: the types most users of the
+//! crate need, re-exported from the modules that define them.
+
+use std::path::Path;
+
+use candle_core::Device;
+
+use crate::errors::Result;
+use crate::features;
+
+pub use crate::confidence::Calibration;
+pub use crate::decoding::DecodeOptions;
+pub use crate::decoding::language::LanguageSelection;
+pub use crate::model::shout::ShoutModel;
+pub use crate::pipeline::transcribe::Transcriber;
+pub use crate::tokenizer::bpe::Tokenizer;
+pub use crate::transcript::{Segment, Transcript, Word};
+
+/// Load the model in `model_dir` (`config.json`, weights, `tokenizer.json`
+/// and, if present, `calibration.json`) onto `device`.
+#[tracing::instrument(level = "debug", skip_all, fields(model_dir = %model_dir.display()))]
+pub fn load_transcriber(model_dir: &Path, device: &Device) -> Result<Transcriber<ShoutModel>> {
+    let model = ShoutModel::load_dir(model_dir, device)?;
+    let tokenizer = Tokenizer::from_file(model_dir.join("tokenizer.json"))?;
+
+    let front_end = features::front_end(&model.config)?;
+    let mut transcriber = Transcriber::new(model, tokenizer, front_end.feature_dim());
+    transcriber.set_front_end(front_end);
+    transcriber.model_name = model_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string());
+    transcriber.calibration = Calibration::from_model_dir(model_dir)?;
+    tracing::info!(
+        model = transcriber.model_name.as_deref().unwrap_or(""),
+        device = ?device.location(),
+        calibrated = transcriber.calibration.is_some(),
+        "loaded model"
+    );
+    Ok(transcriber)
+}
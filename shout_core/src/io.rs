@@ -0,0 +1,423 @@
+//! Named tensors in the safetensors format, for feature batches and model
+//! weights that other tools (PyTorch, candle, Hugging Face) read directly.
+//!
+//! A file is an 8-byte little-endian header length, a JSON header naming
+//! each tensor's dtype, shape and byte range plus string `__metadata__`, and
+//! the tensor data. Feature files record the sample rate and a hash of the
+//! [`MelConfig`] they were computed with, and [`load_features`] refuses ones
+//! from another config, so a cache never serves features of an old front
+//! end.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use ndarray::{Array2, ArrayView2};
+use serde_json::{Map, Value, json};
+
+use crate::audio::mel::MelConfig;
+use crate::errors::{IoContext, Result, ShoutError};
+
+/// Headers larger than this are taken for corruption rather than read.
+const MAX_HEADER_LEN: u64 = 100 << 20;
+
+/// Metadata key of the sample rate features were computed at.
+pub const SAMPLE_RATE_KEY: &str = "sample_rate";
+
+/// Metadata key of [`mel_config_hash`].
+pub const MEL_CONFIG_HASH_KEY: &str = "mel_config_hash";
+
+/// Element types safetensors names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtype {
+    U8,
+    I32,
+    I64,
+    F16,
+    BF16,
+    F32,
+    F64,
+}
+
+impl Dtype {
+    pub fn size(self) -> usize {
+        match self {
+            Dtype::U8 => 1,
+            Dtype::F16 | Dtype::BF16 => 2,
+            Dtype::I32 | Dtype::F32 => 4,
+            Dtype::I64 | Dtype::F64 => 8,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Dtype::U8 => "U8",
+            Dtype::I32 => "I32",
+            Dtype::I64 => "I64",
+            Dtype::F16 => "F16",
+            Dtype::BF16 => "BF16",
+            Dtype::F32 => "F32",
+            Dtype::F64 => "F64",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        [
+            Dtype::U8,
+            Dtype::I32,
+            Dtype::I64,
+            Dtype::F16,
+            Dtype::BF16,
+            Dtype::F32,
+            Dtype::F64,
+        ]
+        .into_iter()
+        .find(|d| d.name() == name)
+    }
+}
+
+/// A tensor's raw little-endian bytes with their type and shape.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    pub dtype: Dtype,
+    pub shape: Vec<usize>,
+    pub data: Vec<u8>,
+}
+
+impl Tensor {
+    /// A float32 tensor of `shape` from row-major `values`.
+    pub fn from_f32(shape: Vec<usize>, values: &[f32]) -> Result<Self> {
+        let tensor = Self {
+            dtype: Dtype::F32,
+            data: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            shape,
+        };
+        tensor.check()?;
+        Ok(tensor)
+    }
+
+    pub fn from_matrix(matrix: ArrayView2<f32>) -> Self {
+        let (rows, cols) = matrix.dim();
+        Self {
+            dtype: Dtype::F32,
+            shape: vec![rows, cols],
+            data: matrix.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    /// The values as float32, converting from the other float types.
+    pub fn to_f32(&self) -> Result<Vec<f32>> {
+        let values = self.data.chunks_exact(self.dtype.size());
+        Ok(match self.dtype {
+            Dtype::F32 => values
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect(),
+            Dtype::F64 => values
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32)
+                .collect(),
+            Dtype::BF16 => values
+                .map(|b| f32::from_bits((u16::from_le_bytes([b[0], b[1]]) as u32) << 16))
+                .collect(),
+            Dtype::F16 => values
+                .map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]])))
+                .collect(),
+            other => {
+                return Err(ShoutError::UnsupportedFormat(format!(
+                    "{} tensor is not floating point",
+                    other.name()
+                )));
+            }
+        })
+    }
+
+    /// The tensor as a float32 matrix; it must have two dimensions.
+    pub fn to_matrix(&self) -> Result<Array2<f32>> {
+        let &[rows, cols] = self.shape.as_slice() else {
+            return Err(ShoutError::UnsupportedFormat(format!(
+                "expected a 2-D tensor, found shape {:?}",
+                self.shape
+            )));
+        };
+        Array2::from_shape_vec((rows, cols), self.to_f32()?)
+            .map_err(|e| ShoutError::Config(e.to_string()))
+    }
+
+    /// Fails unless `data` holds exactly the values `shape` calls for.
+    fn check(&self) -> Result<()> {
+        let expected = self.shape.iter().product::<usize>() * self.dtype.size();
+        if self.data.len() != expected {
+            return Err(ShoutError::InvalidArgument(format!(
+                "{} bytes for a {} tensor of shape {:?}, which needs {expected}",
+                self.data.len(),
+                self.dtype.name(),
+                self.shape
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The tensors and metadata of a safetensors file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TensorFile {
+    pub tensors: BTreeMap<String, Tensor>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl TensorFile {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file =
+            File::create(path).io_context(|| format!("Failed to create {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        self.write(&mut out)
+            .and_then(|()| out.flush().map_err(ShoutError::from))
+            .map_err(|e| e.context(path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+        Self::read(&mut BufReader::new(file)).map_err(|e| e.context(path.display()))
+    }
+
+    /// Only the metadata of the file at `path`, without reading the tensors.
+    pub fn load_metadata(path: &Path) -> Result<BTreeMap<String, String>> {
+        let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+        let (header, _) =
+            read_header(&mut BufReader::new(file)).map_err(|e| e.context(path.display()))?;
+        Ok(header.metadata)
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> Result<()> {
+        let mut header = Map::new();
+        if !self.metadata.is_empty() {
+            header.insert("__metadata__".into(), json!(self.metadata));
+        }
+        let mut offset = 0usize;
+        for (name, tensor) in &self.tensors {
+            tensor.check().map_err(|e| e.context(name))?;
+            let end = offset + tensor.data.len();
+            header.insert(
+                name.clone(),
+                json!({
+                    "dtype": tensor.dtype.name(),
+                    "shape": tensor.shape,
+                    "data_offsets": [offset, end],
+                }),
+            );
+            offset = end;
+        }
+
+        // Padded with spaces so the data starts 8-byte aligned.
+        let mut header = serde_json::to_string(&Value::Object(header))?;
+        let padding = header.len().next_multiple_of(8) - header.len();
+        header.extend(std::iter::repeat_n(' ', padding));
+        out.write_all(&(header.len() as u64).to_le_bytes())?;
+        out.write_all(header.as_bytes())?;
+        for tensor in self.tensors.values() {
+            out.write_all(&tensor.data)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let (header, entries) = read_header(reader)?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let mut tensors = BTreeMap::new();
+        for (name, dtype, shape, [begin, end]) in entries {
+            let bytes = data.get(begin..end).ok_or_else(|| {
+                ShoutError::Config(format!("{name}: data_offsets {begin}..{end} out of range"))
+            })?;
+            let tensor = Tensor {
+                dtype,
+                shape,
+                data: bytes.to_vec(),
+            };
+            tensor
+                .check()
+                .map_err(|e| ShoutError::Config(format!("{name}: {e}")))?;
+            tensors.insert(name, tensor);
+        }
+        Ok(Self {
+            tensors,
+            metadata: header.metadata,
+        })
+    }
+}
+
+struct Header {
+    metadata: BTreeMap<String, String>,
+}
+
+type Entry = (String, Dtype, Vec<usize>, [usize; 2]);
+
+/// The header's metadata and tensor entries, leaving `reader` at the data.
+fn read_header<R: Read>(reader: &mut R) -> Result<(Header, Vec<Entry>)> {
+    let invalid = |what: String| ShoutError::Config(format!("not a safetensors file: {what}"));
+    let mut len = [0u8; 8];
+    reader
+        .read_exact(&mut len)
+        .map_err(|_| invalid("too short".into()))?;
+    let len = u64::from_le_bytes(len);
+    if len > MAX_HEADER_LEN {
+        return Err(invalid(format!("header of {len} bytes")));
+    }
+    let mut header = vec![0u8; len as usize];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| invalid("header cut short".into()))?;
+    let header: Map<String, Value> =
+        serde_json::from_slice(&header).map_err(|e| invalid(format!("bad header: {e}")))?;
+
+    let mut metadata = BTreeMap::new();
+    let mut entries = Vec::new();
+    for (name, value) in header {
+        if name == "__metadata__" {
+            metadata = serde_json::from_value(value)
+                .map_err(|e| invalid(format!("bad __metadata__: {e}")))?;
+            continue;
+        }
+        let dtype = value["dtype"].as_str().unwrap_or_default();
+        let dtype = Dtype::parse(dtype)
+            .ok_or_else(|| ShoutError::UnsupportedFormat(format!("{name}: dtype {dtype:?}")))?;
+        let shape: Vec<usize> = serde_json::from_value(value["shape"].clone())
+            .map_err(|e| invalid(format!("{name}: bad shape: {e}")))?;
+        let offsets: [usize; 2] = serde_json::from_value(value["data_offsets"].clone())
+            .map_err(|e| invalid(format!("{name}: bad data_offsets: {e}")))?;
+        entries.push((name, dtype, shape, offsets));
+    }
+    Ok((Header { metadata }, entries))
+}
+
+/// A short hash of everything in `config`, stable across runs and
+/// platforms, that changes whenever the features would.
+pub fn mel_config_hash(config: &MelConfig) -> String {
+    let json = serde_json::to_string(config).expect("a mel config serializes");
+    let hash = json.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// Save named feature matrices to `path`, recording the sample rate and
+/// [`mel_config_hash`] of `config`.
+pub fn save_features(
+    path: &Path,
+    features: &[(&str, ArrayView2<f32>)],
+    config: &MelConfig,
+) -> Result<()> {
+    let file = TensorFile {
+        tensors: features
+            .iter()
+            .map(|(name, matrix)| (name.to_string(), Tensor::from_matrix(matrix.view())))
+            .collect(),
+        metadata: BTreeMap::from([
+            (SAMPLE_RATE_KEY.to_string(), config.sample_rate.to_string()),
+            (MEL_CONFIG_HASH_KEY.to_string(), mel_config_hash(config)),
+        ]),
+    };
+    file.save(path)
+}
+
+/// Whether the features at `path` were computed with `config`.
+pub fn features_current(path: &Path, config: &MelConfig) -> Result<bool> {
+    let metadata = TensorFile::load_metadata(path)?;
+    Ok(metadata.get(MEL_CONFIG_HASH_KEY) == Some(&mel_config_hash(config)))
+}
+
+/// The feature matrices at `path` by name, or `None` if they were computed
+/// with another config than `config` (or without one recorded) and must be
+/// recomputed.
+pub fn load_features(
+    path: &Path,
+    config: &MelConfig,
+) -> Result<Option<BTreeMap<String, Array2<f32>>>> {
+    let file = TensorFile::load(path)?;
+    if file.metadata.get(MEL_CONFIG_HASH_KEY) != Some(&mel_config_hash(config)) {
+        return Ok(None);
+    }
+    let matrices = file
+        .tensors
+        .iter()
+        .map(|(name, tensor)| {
+            Ok((
+                name.clone(),
+                tensor.to_matrix().map_err(|e| e.context(name))?,
+            ))
+        })
+        .collect::<Result<_>>()?;
+    Ok(Some(matrices))
+}
+
+/// IEEE half precision to single.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits >> 15) as u32) << 31;
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as u32;
+    let magnitude = match exponent {
+        0 => mantissa as f32 * 2f32.powi(-24),
+        0x1f if mantissa == 0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => f32::from_bits((((exponent + 112) as u32) << 23) | (mantissa << 13)),
+    };
+    if sign != 0 { -magnitude } else { magnitude }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn round_trips_tensors_and_metadata() {
+        let file = TensorFile {
+            tensors: BTreeMap::from([
+                (
+                    "a".to_string(),
+                    Tensor::from_f32(vec![3], &[1.0, -2.0, 0.5]).unwrap(),
+                ),
+                (
+                    "b".to_string(),
+                    Tensor {
+                        dtype: Dtype::F16,
+                        shape: vec![2],
+                        data: vec![0, 0x3c, 0, 0xc0],
+                    },
+                ),
+            ]),
+            metadata: BTreeMap::from([("k".to_string(), "v".to_string())]),
+        };
+        let mut bytes = Vec::new();
+        file.write(&mut bytes).unwrap();
+        let header_len = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        assert_eq!(header_len % 8, 0);
+
+        let read = TensorFile::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, file);
+        assert_eq!(read.tensors["b"].to_f32().unwrap(), [1.0, -2.0]);
+        assert!(Tensor::from_f32(vec![2, 2], &[1.0]).is_err());
+    }
+
+    #[test]
+    fn features_of_another_config_are_stale() {
+        let path =
+            std::env::temp_dir().join(format!("shout_io_{}.safetensors", std::process::id()));
+        let whisper = MelConfig::whisper();
+        let mel = array![[0.0f32, 1.0], [2.0, 3.0]];
+        save_features(&path, &[("utt1", mel.view())], &whisper).unwrap();
+
+        assert!(features_current(&path, &whisper).unwrap());
+        assert_eq!(
+            load_features(&path, &whisper).unwrap().unwrap()["utt1"],
+            mel
+        );
+        let other = MelConfig {
+            n_mels: 128,
+            ..MelConfig::whisper()
+        };
+        assert!(load_features(&path, &other).unwrap().is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+}
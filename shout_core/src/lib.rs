@@ -1,18 +1,71 @@
-mod errors;
-mod audio;
-mod model;
+//! Speech recognition with Whisper-style models.
+//!
+//! The entry points are grouped by stage:
+//!
+//! - [`audio`]: decoding files to 16 kHz mono PCM, resampling and capture;
+//! - [`features`]: log-mel spectrograms;
+//! - [`inference`]: loading a model and transcribing audio with it;
+//! - [`PipelineBuilder`]: all of the above, configured in one place;
+//! - [`registry`]: custom sources, augmentations, front ends and output
+//!   formats, selected by name;
+//! - `nonblocking` (feature `tokio`): `async` versions for tokio applications.
+//!
+//! Transcribing a file (feature `native`):
+//!
+#![cfg_attr(feature = "native", doc = "```no_run")]
+#![cfg_attr(not(feature = "native"), doc = "```ignore")]
+//! use std::path::Path;
+//!
+//! use candle_core::Device;
+//! use shout_core::inference::load_transcriber;
+//!
+//! # fn main() -> shout_core::Result<()> {
+//! let mut transcriber = load_transcriber(Path::new("models/base"), &Device::Cpu)?;
+//! let transcript = transcriber.transcribe_file("speech.wav")?;
+//! println!("{}", transcript.text());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The remaining modules hold the building blocks (decoding strategies,
+//! model layers, output formats, post-processing) for finer control.
+//!
+//! By default only the feature front end is built (`frontend-only`): mel and
+//! MFCC features, configs, transcripts and output formats. `inference` adds
+//! the model and pipeline, `decode` and the `codec-*` features audio files,
+//! `capture` microphones; `native` enables all of them, `wasm` what builds
+//! for `wasm32`.
 
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+pub mod alignment;
+pub mod audio;
+#[cfg(feature = "inference")]
+pub mod backend;
+#[cfg(feature = "native")]
+pub mod cache;
+pub mod cancel;
+pub mod confidence;
+pub mod config;
+#[cfg(feature = "inference")]
+pub mod decoding;
+pub mod errors;
+pub mod features;
+#[cfg(feature = "inference")]
+pub mod inference;
+pub mod io;
+#[cfg(feature = "inference")]
+pub mod model;
+#[cfg(feature = "tokio")]
+pub mod nonblocking;
+pub mod npy;
+pub mod output;
+#[cfg(feature = "inference")]
+pub mod pipeline;
+pub mod postprocess;
+pub mod registry;
+#[cfg(feature = "inference")]
+pub mod tokenizer;
+pub mod transcript;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use errors::{Result, ShoutError};
+#[cfg(feature = "inference")]
+pub use pipeline::builder::{Pipeline, PipelineBuilder};
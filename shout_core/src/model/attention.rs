@@ -0,0 +1,224 @@
+use candle_core::{Module, Result, Tensor};
+use candle_nn::LayerNorm;
+
+use super::linear::QLinear;
+use super::weights::Weights;
+
+/// Keys and values of one attention layer, `(batch, n_ctx, n_state)` each.
+pub type KeyValue = (Tensor, Tensor);
+
+/// Multi-head attention with OpenAI Whisper parameter names
+/// (`query`, `key` (no bias), `value`, `out`).
+#[derive(Debug, Clone)]
+pub struct MultiHeadAttention {
+    query: QLinear,
+    key: QLinear,
+    value: QLinear,
+    out: QLinear,
+    n_head: usize,
+}
+
+impl MultiHeadAttention {
+    pub fn load(n_state: usize, n_head: usize, w: Weights) -> Result<Self> {
+        Ok(Self {
+            query: w.pp("query").linear(n_state, n_state, true)?,
+            key: w.pp("key").linear(n_state, n_state, false)?,
+            value: w.pp("value").linear(n_state, n_state, true)?,
+            out: w.pp("out").linear(n_state, n_state, true)?,
+            n_head,
+        })
+    }
+
+    /// Self-attention when `xa` is `None`, cross-attention onto `xa` otherwise. No caching.
+    pub fn forward(
+        &self,
+        x: &Tensor,
+        xa: Option<&Tensor>,
+        mask: Option<&Tensor>,
+    ) -> Result<Tensor> {
+        let (k, v) = self.key_value(xa.unwrap_or(x))?;
+        self.attend(x, &k, &v, mask)
+    }
+
+    /// Self-attention over the cached keys/values plus those of `x`; the cache is
+    /// extended with `x`.
+    pub fn forward_self_cached(
+        &self,
+        x: &Tensor,
+        mask: Option<&Tensor>,
+        cache: &mut Option<KeyValue>,
+    ) -> Result<Tensor> {
+        let (k, v) = self.key_value(x)?;
+        let (k, v) = match cache.take() {
+            Some((pk, pv)) => (Tensor::cat(&[&pk, &k], 1)?, Tensor::cat(&[&pv, &v], 1)?),
+            None => (k, v),
+        };
+
+        let out = self.attend(x, &k, &v, mask)?;
+        *cache = Some((k, v));
+        Ok(out)
+    }
+
+    /// Attention onto precomputed keys/values (cross-attention with a cached encoder output).
+    pub fn forward_with_kv(&self, x: &Tensor, kv: &KeyValue) -> Result<Tensor> {
+        self.attend(x, &kv.0, &kv.1, None)
+    }
+
+    /// Like [`Self::forward_with_kv`], also returning the attention weights
+    /// `(batch, n_head, q_len, k_len)`.
+    pub fn forward_with_kv_weights(&self, x: &Tensor, kv: &KeyValue) -> Result<(Tensor, Tensor)> {
+        let q = self.query.forward(x)?;
+        let (wv, w) = self.qkv_attention(&q, &kv.0, &kv.1, None)?;
+        Ok((self.out.forward(&wv)?, w))
+    }
+
+    pub fn key_value(&self, x: &Tensor) -> Result<KeyValue> {
+        Ok((self.key.forward(x)?, self.value.forward(x)?))
+    }
+
+    fn attend(&self, x: &Tensor, k: &Tensor, v: &Tensor, mask: Option<&Tensor>) -> Result<Tensor> {
+        let q = self.query.forward(x)?;
+        let (wv, _) = self.qkv_attention(&q, k, v, mask)?;
+        self.out.forward(&wv)
+    }
+
+    fn reshape_head(&self, x: &Tensor) -> Result<Tensor> {
+        let (b, n_ctx, n_state) = x.dims3()?;
+        x.reshape((b, n_ctx, self.n_head, n_state / self.n_head))?
+            .transpose(1, 2)
+    }
+
+    /// The attention output and its weights. `mask`, if given, must already be
+    /// `(q_len, k_len)`.
+    fn qkv_attention(
+        &self,
+        q: &Tensor,
+        k: &Tensor,
+        v: &Tensor,
+        mask: Option<&Tensor>,
+    ) -> Result<(Tensor, Tensor)> {
+        let (_, _, n_state) = q.dims3()?;
+        let scale = ((n_state / self.n_head) as f64).powf(-0.25);
+
+        let q = (self.reshape_head(q)? * scale)?;
+        let k = (self.reshape_head(k)?.transpose(2, 3)? * scale)?;
+        let v = self.reshape_head(v)?.contiguous()?;
+
+        let mut qk = q.matmul(&k)?;
+        if let Some(mask) = mask {
+            qk = qk.broadcast_add(mask)?;
+        }
+
+        let w = candle_nn::ops::softmax_last_dim(&qk)?;
+        let wv = w.matmul(&v)?.transpose(1, 2)?.flatten_from(2)?;
+        Ok((wv, w))
+    }
+}
+
+/// Pre-norm transformer block: self-attention, optional cross-attention, MLP.
+#[derive(Debug, Clone)]
+pub struct ResidualAttentionBlock {
+    attn: MultiHeadAttention,
+    attn_ln: LayerNorm,
+    cross_attn: Option<(MultiHeadAttention, LayerNorm)>,
+    mlp_fc1: QLinear,
+    mlp_fc2: QLinear,
+    mlp_ln: LayerNorm,
+}
+
+impl ResidualAttentionBlock {
+    pub fn load(n_state: usize, n_head: usize, cross_attention: bool, w: Weights) -> Result<Self> {
+        let cross_attn = if cross_attention {
+            Some((
+                MultiHeadAttention::load(n_state, n_head, w.pp("cross_attn"))?,
+                w.pp("cross_attn_ln").layer_norm(n_state)?,
+            ))
+        } else {
+            None
+        };
+
+        let n_mlp = n_state * 4;
+        Ok(Self {
+            attn: MultiHeadAttention::load(n_state, n_head, w.pp("attn"))?,
+            attn_ln: w.pp("attn_ln").layer_norm(n_state)?,
+            cross_attn,
+            mlp_fc1: w.pp("mlp.0").linear(n_state, n_mlp, true)?,
+            mlp_fc2: w.pp("mlp.2").linear(n_mlp, n_state, true)?,
+            mlp_ln: w.pp("mlp_ln").layer_norm(n_state)?,
+        })
+    }
+
+    /// Uncached forward pass (encoder).
+    pub fn forward(
+        &self,
+        x: &Tensor,
+        xa: Option<&Tensor>,
+        mask: Option<&Tensor>,
+    ) -> Result<Tensor> {
+        let attn = self.attn.forward(&self.attn_ln.forward(x)?, None, mask)?;
+        let mut x = (x + attn)?;
+
+        if let Some((cross_attn, ln)) = &self.cross_attn {
+            x = (&x + cross_attn.forward(&ln.forward(&x)?, xa, None)?)?;
+        }
+
+        self.mlp(&x)
+    }
+
+    /// Cross-attention keys/values for encoder output `xa` (`None` for encoder blocks).
+    pub fn cross_key_value(&self, xa: &Tensor) -> Result<Option<KeyValue>> {
+        match &self.cross_attn {
+            Some((cross_attn, _)) => Ok(Some(cross_attn.key_value(xa)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Incremental forward pass (decoder): self-attention uses and extends `self_cache`,
+    /// cross-attention uses the precomputed `cross_kv`.
+    pub fn forward_cached(
+        &self,
+        x: &Tensor,
+        mask: Option<&Tensor>,
+        self_cache: &mut Option<KeyValue>,
+        cross_kv: Option<&KeyValue>,
+    ) -> Result<Tensor> {
+        let attn = self
+            .attn
+            .forward_self_cached(&self.attn_ln.forward(x)?, mask, self_cache)?;
+        let mut x = (x + attn)?;
+
+        if let (Some((cross_attn, ln)), Some(kv)) = (&self.cross_attn, cross_kv) {
+            x = (&x + cross_attn.forward_with_kv(&ln.forward(&x)?, kv)?)?;
+        }
+
+        self.mlp(&x)
+    }
+
+    /// Uncached forward pass (decoder) that also returns the cross-attention
+    /// weights, `(batch, n_head, len, n_audio_ctx)`, for word alignment.
+    pub fn forward_cross_weights(
+        &self,
+        x: &Tensor,
+        mask: Option<&Tensor>,
+        cross_kv: Option<&KeyValue>,
+    ) -> Result<(Tensor, Option<Tensor>)> {
+        let attn = self.attn.forward(&self.attn_ln.forward(x)?, None, mask)?;
+        let mut x = (x + attn)?;
+
+        let mut weights = None;
+        if let (Some((cross_attn, ln)), Some(kv)) = (&self.cross_attn, cross_kv) {
+            let (out, w) = cross_attn.forward_with_kv_weights(&ln.forward(&x)?, kv)?;
+            x = (&x + out)?;
+            weights = Some(w);
+        }
+
+        Ok((self.mlp(&x)?, weights))
+    }
+
+    fn mlp(&self, x: &Tensor) -> Result<Tensor> {
+        let h = self
+            .mlp_fc2
+            .forward(&self.mlp_fc1.forward(&self.mlp_ln.forward(x)?)?.gelu_erf()?)?;
+        x + h
+    }
+}
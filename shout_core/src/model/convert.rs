@@ -0,0 +1,387 @@
+//! Conversion of public Whisper checkpoints into shout's layout.
+//!
+//! shout uses OpenAI's parameter names. Hugging Face Transformers checkpoints
+//! (`model.encoder.layers.0.self_attn.q_proj.weight`, ...) are renamed; OpenAI
+//! `.pt` files only need their state dict extracted. CTC heads exported as
+//! `ctc.ctc_lo` (ESPnet style) become `ctc_head`. The configuration is derived
+//! from the tensor shapes, and every tensor the model loads is checked against
+//! the shape that configuration implies.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use candle_core::{Device, Tensor};
+use serde::Deserialize;
+
+use crate::config::model::{FrontEndConfig, ModelConfig};
+use crate::errors::{IoContext, Result, ShoutError};
+
+/// Width of one attention head in every Whisper size.
+const HEAD_DIM: usize = 64;
+
+/// Hugging Face name fragments and their OpenAI counterparts, applied in order.
+const RENAMES: &[(&str, &str)] = &[
+    (".layers.", ".blocks."),
+    (
+        "encoder.embed_positions.weight",
+        "encoder.positional_embedding",
+    ),
+    (
+        "decoder.embed_positions.weight",
+        "decoder.positional_embedding",
+    ),
+    ("decoder.embed_tokens", "decoder.token_embedding"),
+    (".self_attn_layer_norm", ".attn_ln"),
+    (".encoder_attn_layer_norm", ".cross_attn_ln"),
+    (".final_layer_norm", ".mlp_ln"),
+    (".self_attn.", ".attn."),
+    (".encoder_attn.", ".cross_attn."),
+    (".q_proj", ".query"),
+    (".k_proj", ".key"),
+    (".v_proj", ".value"),
+    (".out_proj", ".out"),
+    (".fc1", ".mlp.0"),
+    (".fc2", ".mlp.2"),
+    ("encoder.layer_norm", "encoder.ln_post"),
+    ("decoder.layer_norm", "decoder.ln"),
+    ("ctc.ctc_lo", "ctc_head"),
+];
+
+#[derive(Debug)]
+pub struct ConvertReport {
+    pub config: ModelConfig,
+    pub tensors: usize,
+
+    /// Source tensors the model does not use (tied output projection, the
+    /// encoder's fixed positional embedding, ...).
+    pub dropped: Vec<String>,
+    pub tokenizer: Option<PathBuf>,
+}
+
+/// The parts of a Hugging Face `config.json` that shapes cannot tell.
+#[derive(Deserialize)]
+struct HfConfig {
+    encoder_attention_heads: usize,
+    decoder_attention_heads: usize,
+}
+
+/// OpenAI name for a checkpoint tensor; `None` for tensors shout never loads.
+pub fn map_name(name: &str) -> Option<String> {
+    let name = name.strip_prefix("model.").unwrap_or(name);
+    if name.starts_with("proj_out.") {
+        // Tied to the token embedding.
+        return None;
+    }
+    let mut out = name.to_string();
+    for (from, to) in RENAMES {
+        out = out.replace(from, to);
+    }
+    Some(out)
+}
+
+fn linear(
+    out: &mut Vec<(String, Vec<usize>)>,
+    prefix: &str,
+    n_in: usize,
+    n_out: usize,
+    bias: bool,
+) {
+    out.push((format!("{prefix}.weight"), vec![n_out, n_in]));
+    if bias {
+        out.push((format!("{prefix}.bias"), vec![n_out]));
+    }
+}
+
+fn norm(out: &mut Vec<(String, Vec<usize>)>, prefix: &str, d: usize) {
+    out.push((format!("{prefix}.weight"), vec![d]));
+    out.push((format!("{prefix}.bias"), vec![d]));
+}
+
+/// Every tensor the model loads for `c`, with its shape.
+pub fn expected_tensors(c: &ModelConfig) -> Vec<(String, Vec<usize>)> {
+    let (da, dt) = (c.n_audio_state, c.n_text_state);
+    let mut out = vec![
+        ("encoder.conv1.weight".to_string(), vec![da, c.n_mels, 3]),
+        ("encoder.conv1.bias".to_string(), vec![da]),
+        ("encoder.conv2.weight".to_string(), vec![da, da, 3]),
+        ("encoder.conv2.bias".to_string(), vec![da]),
+        (
+            "decoder.token_embedding.weight".to_string(),
+            vec![c.n_vocab, dt],
+        ),
+        (
+            "decoder.positional_embedding".to_string(),
+            vec![c.n_text_ctx, dt],
+        ),
+    ];
+
+    for (prefix, d, layers, cross) in [
+        ("encoder", da, c.n_audio_layer, false),
+        ("decoder", dt, c.n_text_layer, true),
+    ] {
+        for i in 0..layers {
+            let block = format!("{prefix}.blocks.{i}");
+            let attns: &[&str] = if cross {
+                &["attn", "cross_attn"]
+            } else {
+                &["attn"]
+            };
+            for attn in attns {
+                linear(&mut out, &format!("{block}.{attn}.query"), d, d, true);
+                linear(&mut out, &format!("{block}.{attn}.key"), d, d, false);
+                linear(&mut out, &format!("{block}.{attn}.value"), d, d, true);
+                linear(&mut out, &format!("{block}.{attn}.out"), d, d, true);
+                norm(&mut out, &format!("{block}.{attn}_ln"), d);
+            }
+            linear(&mut out, &format!("{block}.mlp.0"), d, 4 * d, true);
+            linear(&mut out, &format!("{block}.mlp.2"), 4 * d, d, true);
+            norm(&mut out, &format!("{block}.mlp_ln"), d);
+        }
+    }
+    norm(&mut out, "encoder.ln_post", da);
+    norm(&mut out, "decoder.ln", dt);
+    if let Some(v) = c.ctc_vocab {
+        linear(&mut out, "ctc_head", da, v, true);
+    }
+    out
+}
+
+fn dims<'a>(
+    shapes: &'a HashMap<String, Vec<usize>>,
+    name: &str,
+    rank: usize,
+) -> Result<&'a [usize]> {
+    match shapes.get(name) {
+        Some(d) if d.len() == rank => Ok(d),
+        Some(d) => {
+            let msg = format!("{name}: expected rank {rank}, found shape {d:?}");
+            Err(ShoutError::Model(msg))
+        }
+        None => Err(ShoutError::Model(format!("missing tensor {name}"))),
+    }
+}
+
+fn count_blocks(shapes: &HashMap<String, Vec<usize>>, prefix: &str) -> usize {
+    shapes
+        .keys()
+        .filter_map(|n| {
+            n.strip_prefix(prefix)?
+                .split('.')
+                .next()?
+                .parse::<usize>()
+                .ok()
+        })
+        .map(|i| i + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Model configuration implied by tensor shapes (OpenAI names). Head counts
+/// are not visible in the shapes; without `heads` Whisper's 64-wide heads are assumed.
+pub fn infer_config(
+    shapes: &HashMap<String, Vec<usize>>,
+    heads: Option<(usize, usize)>,
+) -> Result<ModelConfig> {
+    let conv1 = dims(shapes, "encoder.conv1.weight", 3)?;
+    let (n_audio_state, n_mels) = (conv1[0], conv1[1]);
+    let embedding = dims(shapes, "decoder.token_embedding.weight", 2)?;
+    let (n_vocab, n_text_state) = (embedding[0], embedding[1]);
+    let n_text_ctx = dims(shapes, "decoder.positional_embedding", 2)?[0];
+    let n_audio_ctx = match shapes.get("encoder.positional_embedding") {
+        Some(d) => d[0],
+        None => 1500,
+    };
+    let (n_audio_head, n_text_head) =
+        heads.unwrap_or((n_audio_state / HEAD_DIM, n_text_state / HEAD_DIM));
+    let ctc_vocab = match shapes.get("ctc_head.weight") {
+        Some(_) => Some(dims(shapes, "ctc_head.weight", 2)?[0]),
+        None => None,
+    };
+
+    Ok(ModelConfig {
+        n_mels,
+        n_audio_ctx,
+        n_audio_state,
+        n_audio_head,
+        n_audio_layer: count_blocks(shapes, "encoder.blocks."),
+        n_vocab,
+        n_text_ctx,
+        n_text_state,
+        n_text_head,
+        n_text_layer: count_blocks(shapes, "decoder.blocks."),
+        ctc_vocab,
+        front_end: FrontEndConfig::WhisperLogMel,
+        alignment_heads: None,
+    })
+}
+
+fn load_tensors(path: &Path) -> Result<Vec<(String, Tensor)>> {
+    if path.extension().is_some_and(|e| e == "safetensors") {
+        let tensors = candle_core::safetensors::load(path, &Device::Cpu)?;
+        return Ok(tensors.into_iter().collect());
+    }
+    // OpenAI's `.pt` wraps the weights in `model_state_dict`; HF's `.bin` is the dict itself.
+    candle_core::pickle::read_all_with_key(path, Some("model_state_dict"))
+        .or_else(|_| candle_core::pickle::read_all(path))
+        .map_err(Into::into)
+}
+
+/// Convert a Hugging Face model directory or a single checkpoint file into
+/// `out_dir` (`config.json`, `model.safetensors` and, if found, `tokenizer.json`).
+pub fn convert_checkpoint(
+    input: &Path,
+    out_dir: &Path,
+    tokenizer: Option<&Path>,
+) -> Result<ConvertReport> {
+    let (weights, heads, default_tokenizer) = if input.is_dir() {
+        let weights = ["model.safetensors", "pytorch_model.bin"]
+            .iter()
+            .map(|f| input.join(f))
+            .find(|p| p.exists())
+            .ok_or_else(|| {
+                let dir = input.display();
+                ShoutError::Model(format!(
+                    "no model.safetensors or pytorch_model.bin in {dir}"
+                ))
+            })?;
+        let heads = std::fs::read_to_string(input.join("config.json"))
+            .ok()
+            .and_then(|raw| serde_json::from_str::<HfConfig>(&raw).ok())
+            .map(|c| (c.encoder_attention_heads, c.decoder_attention_heads));
+        (weights, heads, Some(input.join("tokenizer.json")))
+    } else {
+        (input.to_path_buf(), None, None)
+    };
+
+    let source = load_tensors(&weights)
+        .map_err(|e| e.context(format!("failed to read checkpoint: {}", weights.display())))?;
+
+    let mut tensors = HashMap::with_capacity(source.len());
+    let mut dropped = Vec::new();
+    for (name, tensor) in source {
+        match map_name(&name) {
+            Some(mapped) => {
+                tensors.insert(mapped, tensor);
+            }
+            None => dropped.push(name),
+        }
+    }
+
+    let shapes = tensors
+        .iter()
+        .map(|(n, t)| (n.clone(), t.dims().to_vec()))
+        .collect();
+    let config = infer_config(&shapes, heads)?;
+
+    let expected = expected_tensors(&config);
+    for (name, shape) in &expected {
+        match tensors.get(name) {
+            Some(t) if t.dims() == shape.as_slice() => {}
+            Some(t) => {
+                let msg = format!("{name}: expected shape {shape:?}, found {:?}", t.dims());
+                return Err(ShoutError::Model(msg));
+            }
+            None => return Err(ShoutError::Model(format!("missing tensor {name}"))),
+        }
+    }
+    let keep: HashSet<&str> = expected.iter().map(|(n, _)| n.as_str()).collect();
+    let mut unused: Vec<String> = tensors
+        .keys()
+        .filter(|n| !keep.contains(n.as_str()))
+        .cloned()
+        .collect();
+    unused.sort();
+    dropped.extend(unused);
+    tensors.retain(|n, _| keep.contains(n.as_str()));
+
+    std::fs::create_dir_all(out_dir)
+        .io_context(|| format!("Failed to create output dir: {}", out_dir.display()))?;
+    candle_core::safetensors::save(&tensors, out_dir.join("model.safetensors"))?;
+    std::fs::write(
+        out_dir.join("config.json"),
+        serde_json::to_string_pretty(&config)?,
+    )?;
+
+    let tokenizer = tokenizer
+        .map(Path::to_path_buf)
+        .or(default_tokenizer)
+        .filter(|p| p.exists());
+    if let Some(src) = &tokenizer {
+        std::fs::copy(src, out_dir.join("tokenizer.json"))
+            .io_context(|| format!("failed to copy {}", src.display()))?;
+    }
+
+    Ok(ConvertReport {
+        config,
+        tensors: tensors.len(),
+        dropped,
+        tokenizer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_hugging_face_names() {
+        let cases = [
+            (
+                "model.encoder.layers.3.self_attn.q_proj.weight",
+                "encoder.blocks.3.attn.query.weight",
+            ),
+            (
+                "model.decoder.layers.0.encoder_attn.out_proj.bias",
+                "decoder.blocks.0.cross_attn.out.bias",
+            ),
+            (
+                "model.decoder.layers.1.encoder_attn_layer_norm.weight",
+                "decoder.blocks.1.cross_attn_ln.weight",
+            ),
+            (
+                "model.encoder.layers.0.fc2.weight",
+                "encoder.blocks.0.mlp.2.weight",
+            ),
+            (
+                "model.encoder.layers.0.final_layer_norm.bias",
+                "encoder.blocks.0.mlp_ln.bias",
+            ),
+            ("model.encoder.layer_norm.weight", "encoder.ln_post.weight"),
+            (
+                "model.decoder.embed_tokens.weight",
+                "decoder.token_embedding.weight",
+            ),
+            (
+                "model.decoder.embed_positions.weight",
+                "decoder.positional_embedding",
+            ),
+            ("ctc.ctc_lo.weight", "ctc_head.weight"),
+            // OpenAI names are already in place.
+            (
+                "encoder.blocks.0.attn.key.weight",
+                "encoder.blocks.0.attn.key.weight",
+            ),
+        ];
+        for (hf, openai) in cases {
+            assert_eq!(map_name(hf).as_deref(), Some(openai), "{hf}");
+        }
+        assert_eq!(map_name("proj_out.weight"), None);
+    }
+
+    #[test]
+    fn infers_tiny_from_its_shapes() {
+        let tiny = ModelConfig::tiny();
+        let mut shapes: HashMap<String, Vec<usize>> = expected_tensors(&tiny).into_iter().collect();
+        shapes.insert("encoder.positional_embedding".into(), vec![1500, 384]);
+
+        // Converted OpenAI checkpoints expect OpenAI's front end.
+        let expected = ModelConfig {
+            front_end: FrontEndConfig::WhisperLogMel,
+            ..tiny
+        };
+        assert_eq!(infer_config(&shapes, None).unwrap(), expected);
+        assert_eq!(shapes["encoder.conv1.weight"], vec![384, 80, 3]);
+        assert_eq!(shapes["decoder.blocks.3.mlp.0.weight"], vec![1536, 384]);
+        assert_eq!(shapes["decoder.blocks.3.cross_attn_ln.bias"], vec![384]);
+    }
+}
@@ -0,0 +1,38 @@
+use candle_core::{Module, Result, Tensor};
+use candle_nn::{Conv1d, Conv1dConfig};
+
+use super::weights::Weights;
+
+/// Convolutional stem of the audio encoder: two 3-wide 1-D convolutions with GELU,
+/// the second with stride 2, so 3000 mel frames become 1500 encoder positions.
+#[derive(Debug, Clone)]
+pub struct ConvStem {
+    conv1: Conv1d,
+    conv2: Conv1d,
+}
+
+impl ConvStem {
+    pub fn load(n_mels: usize, n_state: usize, w: Weights) -> Result<Self> {
+        let cfg1 = Conv1dConfig {
+            padding: 1,
+            ..Default::default()
+        };
+        let cfg2 = Conv1dConfig {
+            padding: 1,
+            stride: 2,
+            ..Default::default()
+        };
+
+        Ok(Self {
+            conv1: w.pp("conv1").conv1d(n_mels, n_state, 3, cfg1)?,
+            conv2: w.pp("conv2").conv1d(n_state, n_state, 3, cfg2)?,
+        })
+    }
+
+    /// `(batch, n_mels, frames)` -> `(batch, frames / 2, n_state)`.
+    pub fn forward(&self, mel: &Tensor) -> Result<Tensor> {
+        let x = self.conv1.forward(mel)?.gelu_erf()?;
+        let x = self.conv2.forward(&x)?.gelu_erf()?;
+        x.transpose(1, 2)
+    }
+}
@@ -0,0 +1,146 @@
+use candle_core::{Device, Module, Result, Tensor};
+use candle_nn::{Embedding, LayerNorm};
+
+use super::attention::{KeyValue, ResidualAttentionBlock};
+use super::weights::Weights;
+use crate::config::model::ModelConfig;
+
+/// Self-attention keys/values of every decoder layer for one token prefix.
+///
+/// Cloning is cheap (tensors are reference counted), so a hypothesis can branch
+/// off its parent's cache during beam search.
+#[derive(Debug, Clone, Default)]
+pub struct KvCache {
+    layers: Vec<Option<KeyValue>>,
+
+    /// Number of tokens already in the cache.
+    len: usize,
+}
+
+impl KvCache {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Transformer text decoder with cross-attention onto the encoder output.
+#[derive(Debug, Clone)]
+pub struct TextDecoder {
+    token_embedding: Embedding,
+    positional_embedding: Tensor,
+    blocks: Vec<ResidualAttentionBlock>,
+    ln: LayerNorm,
+    mask: Tensor,
+}
+
+impl TextDecoder {
+    pub fn load(cfg: &ModelConfig, w: Weights) -> Result<Self> {
+        let blocks = (0..cfg.n_text_layer)
+            .map(|i| {
+                ResidualAttentionBlock::load(
+                    cfg.n_text_state,
+                    cfg.n_text_head,
+                    true,
+                    w.pp(format!("blocks.{i}")),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            token_embedding: w
+                .pp("token_embedding")
+                .embedding(cfg.n_vocab, cfg.n_text_state)?,
+            positional_embedding: w
+                .get((cfg.n_text_ctx, cfg.n_text_state), "positional_embedding")?,
+            blocks,
+            ln: w.pp("ln").layer_norm(cfg.n_text_state)?,
+            mask: causal_mask(cfg.n_text_ctx, w.device())?,
+        })
+    }
+
+    /// Cross-attention keys/values of every layer for encoder output `xa`.
+    /// Computed once per window and shared by all decoding steps and hypotheses.
+    pub fn cross_key_values(&self, xa: &Tensor) -> Result<Vec<Option<KeyValue>>> {
+        self.blocks.iter().map(|b| b.cross_key_value(xa)).collect()
+    }
+
+    /// Uncached forward pass over the whole sequence.
+    ///
+    /// `tokens: (batch, len)`, `xa: (batch, n_audio_ctx, n_state)` -> logits `(batch, len, n_vocab)`.
+    pub fn forward(&self, tokens: &Tensor, xa: &Tensor) -> Result<Tensor> {
+        let cross = self.cross_key_values(xa)?;
+        self.forward_cached(tokens, &cross, &mut KvCache::default())
+    }
+
+    /// Forward pass over `tokens` that follow the `cache.len()` tokens already in
+    /// `cache`; the cache is extended in place. Returns logits for the new tokens only.
+    pub fn forward_cached(
+        &self,
+        tokens: &Tensor,
+        cross: &[Option<KeyValue>],
+        cache: &mut KvCache,
+    ) -> Result<Tensor> {
+        let (_, len) = tokens.dims2()?;
+        let offset = cache.len;
+        if cache.layers.len() != self.blocks.len() {
+            cache.layers = vec![None; self.blocks.len()];
+        }
+
+        let pos = self.positional_embedding.narrow(0, offset, len)?;
+        let mask = if len > 1 {
+            Some(
+                self.mask
+                    .narrow(0, offset, len)?
+                    .narrow(1, 0, offset + len)?,
+            )
+        } else {
+            None
+        };
+
+        let mut x = self.token_embedding.forward(tokens)?.broadcast_add(&pos)?;
+        for ((block, layer_cache), cross_kv) in self.blocks.iter().zip(&mut cache.layers).zip(cross)
+        {
+            x = block.forward_cached(&x, mask.as_ref(), layer_cache, cross_kv.as_ref())?;
+        }
+        cache.len += len;
+
+        let x = self.ln.forward(&x)?;
+
+        // Output projection is tied to the token embedding.
+        let w = self.token_embedding.embeddings().t()?;
+        x.broadcast_matmul(&w)
+    }
+
+    /// Uncached forward pass over `tokens` returning every layer's
+    /// cross-attention weights `(batch, n_head, len, n_audio_ctx)` instead of
+    /// logits, for word alignment.
+    pub fn cross_attention_weights(
+        &self,
+        tokens: &Tensor,
+        cross: &[Option<KeyValue>],
+    ) -> Result<Vec<Tensor>> {
+        let (_, len) = tokens.dims2()?;
+        let pos = self.positional_embedding.narrow(0, 0, len)?;
+        let mask = self.mask.narrow(0, 0, len)?.narrow(1, 0, len)?;
+
+        let mut x = self.token_embedding.forward(tokens)?.broadcast_add(&pos)?;
+        let mut weights = Vec::with_capacity(self.blocks.len());
+        for (block, cross_kv) in self.blocks.iter().zip(cross) {
+            let (out, w) = block.forward_cross_weights(&x, Some(&mask), cross_kv.as_ref())?;
+            x = out;
+            weights.extend(w);
+        }
+        Ok(weights)
+    }
+}
+
+fn causal_mask(n: usize, device: &Device) -> Result<Tensor> {
+    let mask: Vec<f32> = (0..n)
+        .flat_map(|i| (0..n).map(move |j| if j > i { f32::NEG_INFINITY } else { 0.0 }))
+        .collect();
+    Tensor::from_slice(&mask, (n, n), device)
+}
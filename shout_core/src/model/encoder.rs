@@ -0,0 +1,68 @@
+use candle_core::{DType, Device, Module, Result, Tensor};
+use candle_nn::LayerNorm;
+
+use super::attention::ResidualAttentionBlock;
+use super::convolutional::ConvStem;
+use super::weights::Weights;
+use crate::config::model::ModelConfig;
+
+/// Conv stem + transformer encoder over log-mel features.
+#[derive(Debug, Clone)]
+pub struct AudioEncoder {
+    stem: ConvStem,
+    positional_embedding: Tensor,
+    blocks: Vec<ResidualAttentionBlock>,
+    ln_post: LayerNorm,
+}
+
+impl AudioEncoder {
+    pub fn load(cfg: &ModelConfig, w: Weights) -> Result<Self> {
+        let blocks = (0..cfg.n_audio_layer)
+            .map(|i| {
+                ResidualAttentionBlock::load(
+                    cfg.n_audio_state,
+                    cfg.n_audio_head,
+                    false,
+                    w.pp(format!("blocks.{i}")),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            stem: ConvStem::load(cfg.n_mels, cfg.n_audio_state, w.clone())?,
+            positional_embedding: sinusoids(cfg.n_audio_ctx, cfg.n_audio_state, w.device())?,
+            blocks,
+            ln_post: w.pp("ln_post").layer_norm(cfg.n_audio_state)?,
+        })
+    }
+
+    /// `(batch, n_mels, frames)` -> `(batch, frames / 2, n_audio_state)`.
+    pub fn forward(&self, mel: &Tensor) -> Result<Tensor> {
+        let x = self.stem.forward(mel)?;
+        let (_, n_ctx, _) = x.dims3()?;
+        let pos = self.positional_embedding.narrow(0, 0, n_ctx)?;
+
+        let mut x = x.broadcast_add(&pos)?;
+        for block in &self.blocks {
+            x = block.forward(&x, None, None)?;
+        }
+        self.ln_post.forward(&x)
+    }
+}
+
+/// Fixed sinusoidal position embedding, as in Whisper's `sinusoids`.
+fn sinusoids(length: usize, channels: usize, device: &Device) -> Result<Tensor> {
+    let half = channels / 2;
+    let log_timescale_increment = (10_000f64).ln() / (half as f64 - 1.0);
+
+    let inv_timescales: Vec<f32> = (0..half)
+        .map(|i| (-log_timescale_increment * i as f64).exp() as f32)
+        .collect();
+    let inv_timescales = Tensor::new(inv_timescales.as_slice(), device)?.unsqueeze(0)?;
+    let positions = Tensor::arange(0u32, length as u32, device)?
+        .to_dtype(DType::F32)?
+        .unsqueeze(1)?;
+
+    let scaled = positions.broadcast_mul(&inv_timescales)?;
+    Tensor::cat(&[scaled.sin()?, scaled.cos()?], 1)
+}
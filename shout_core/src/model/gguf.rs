@@ -0,0 +1,459 @@
+//! Reader for GGUF checkpoints (the format used by whisper.cpp / ggml).
+//!
+//! Parses the header, metadata and tensor directory and dequantizes tensors to f32
+//! so they can be loaded into shout's own model. Supported tensor types: F32, F16,
+//! Q4_0, Q4_1 and Q8_0.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::errors::{IoContext, Result, ShoutError};
+
+const MAGIC: &[u8; 4] = b"GGUF";
+const DEFAULT_ALIGNMENT: u64 = 32;
+
+/// ggml tensor types we know how to dequantize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GgmlType {
+    F32,
+    F16,
+    Q4_0,
+    Q4_1,
+    Q8_0,
+}
+
+impl GgmlType {
+    fn from_u32(v: u32) -> Result<Self> {
+        Ok(match v {
+            0 => GgmlType::F32,
+            1 => GgmlType::F16,
+            2 => GgmlType::Q4_0,
+            3 => GgmlType::Q4_1,
+            8 => GgmlType::Q8_0,
+            other => {
+                let msg = format!("unsupported ggml tensor type {other}");
+                return Err(ShoutError::UnsupportedFormat(msg));
+            }
+        })
+    }
+
+    /// (elements per block, bytes per block)
+    fn block_layout(self) -> (usize, usize) {
+        match self {
+            GgmlType::F32 => (1, 4),
+            GgmlType::F16 => (1, 2),
+            GgmlType::Q4_0 => (32, 2 + 16),
+            GgmlType::Q4_1 => (32, 2 + 2 + 16),
+            GgmlType::Q8_0 => (32, 2 + 32),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+    String(String),
+    Array(Vec<GgufValue>),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl GgufValue {
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            GgufValue::U8(v) => Some(v as u64),
+            GgufValue::U16(v) => Some(v as u64),
+            GgufValue::U32(v) => Some(v as u64),
+            GgufValue::U64(v) => Some(v),
+            GgufValue::I8(v) if v >= 0 => Some(v as u64),
+            GgufValue::I16(v) if v >= 0 => Some(v as u64),
+            GgufValue::I32(v) if v >= 0 => Some(v as u64),
+            GgufValue::I64(v) if v >= 0 => Some(v as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            GgufValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GgufTensorInfo {
+    pub name: String,
+
+    /// Row-major shape (outermost dimension first, i.e. PyTorch order).
+    /// GGUF stores dimensions innermost-first; they are reversed on load.
+    pub shape: Vec<usize>,
+    pub dtype: GgmlType,
+
+    /// Offset relative to the start of the tensor data section.
+    pub offset: u64,
+}
+
+impl GgufTensorInfo {
+    pub fn n_elements(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    fn n_bytes(&self) -> usize {
+        let (block, bytes) = self.dtype.block_layout();
+        self.n_elements().div_ceil(block) * bytes
+    }
+}
+
+/// Parsed GGUF header. Tensor data is read lazily with [`GgufFile::read_tensor`].
+#[derive(Debug, Clone)]
+pub struct GgufFile {
+    pub version: u32,
+    pub metadata: HashMap<String, GgufValue>,
+    pub tensors: Vec<GgufTensorInfo>,
+
+    /// Absolute file offset of the tensor data section.
+    pub data_offset: u64,
+}
+
+impl GgufFile {
+    pub fn read<R: Read + Seek>(r: &mut R) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)
+            .io_context(|| "failed to read GGUF magic")?;
+        if &magic != MAGIC {
+            return Err(ShoutError::UnsupportedFormat(
+                "not a GGUF file (bad magic)".into(),
+            ));
+        }
+
+        let version = read_u32(r)?;
+        if !(2..=3).contains(&version) {
+            let msg = format!("unsupported GGUF version {version}");
+            return Err(ShoutError::UnsupportedFormat(msg));
+        }
+
+        let n_tensors = read_u64(r)?;
+        let n_kv = read_u64(r)?;
+
+        let mut metadata = HashMap::new();
+        for _ in 0..n_kv {
+            let key = read_string(r)?;
+            let ty = read_u32(r)?;
+            let value = read_value(r, ty)
+                .map_err(|e| e.context(format!("bad metadata value for '{key}'")))?;
+            metadata.insert(key, value);
+        }
+
+        let mut tensors = Vec::with_capacity(n_tensors as usize);
+        for _ in 0..n_tensors {
+            let name = read_string(r)?;
+            let n_dims = read_u32(r)? as usize;
+            let mut shape = Vec::with_capacity(n_dims);
+            for _ in 0..n_dims {
+                shape.push(read_u64(r)? as usize);
+            }
+            shape.reverse();
+            let dtype = GgmlType::from_u32(read_u32(r)?)
+                .map_err(|e| e.context(format!("tensor '{name}'")))?;
+            let offset = read_u64(r)?;
+            tensors.push(GgufTensorInfo {
+                name,
+                shape,
+                dtype,
+                offset,
+            });
+        }
+
+        let alignment = metadata
+            .get("general.alignment")
+            .and_then(GgufValue::as_u64)
+            .unwrap_or(DEFAULT_ALIGNMENT);
+        let pos = r.stream_position()?;
+        let data_offset = pos.div_ceil(alignment) * alignment;
+
+        Ok(Self {
+            version,
+            metadata,
+            tensors,
+            data_offset,
+        })
+    }
+
+    pub fn tensor(&self, name: &str) -> Option<&GgufTensorInfo> {
+        self.tensors.iter().find(|t| t.name == name)
+    }
+
+    /// Read and dequantize one tensor to f32 (row-major, see [`GgufTensorInfo::shape`]).
+    pub fn read_tensor<R: Read + Seek>(
+        &self,
+        r: &mut R,
+        info: &GgufTensorInfo,
+    ) -> Result<Vec<f32>> {
+        r.seek(SeekFrom::Start(self.data_offset + info.offset))?;
+        let mut raw = vec![0u8; info.n_bytes()];
+        r.read_exact(&mut raw)
+            .io_context(|| format!("truncated data for tensor '{}'", info.name))?;
+
+        let mut out = dequantize(info.dtype, &raw);
+        out.truncate(info.n_elements());
+        Ok(out)
+    }
+}
+
+/// Dense f32 tensor loaded from a checkpoint.
+#[derive(Debug, Clone)]
+pub struct WeightTensor {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+/// Metadata and dequantized tensors of a whole checkpoint.
+#[derive(Debug, Clone)]
+pub struct GgufCheckpoint {
+    pub metadata: HashMap<String, GgufValue>,
+    pub weights: HashMap<String, WeightTensor>,
+}
+
+/// Load every tensor of a GGUF checkpoint, dequantized to f32.
+pub fn load_gguf<P: AsRef<Path>>(path: P) -> Result<GgufCheckpoint> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).io_context(|| format!("failed to open model: {}", path.display()))?;
+    let mut r = BufReader::new(file);
+
+    let gguf = GgufFile::read(&mut r)
+        .map_err(|e| e.context(format!("failed to parse GGUF: {}", path.display())))?;
+
+    let mut weights = HashMap::with_capacity(gguf.tensors.len());
+    for info in &gguf.tensors {
+        let data = gguf.read_tensor(&mut r, info)?;
+        weights.insert(
+            info.name.clone(),
+            WeightTensor {
+                shape: info.shape.clone(),
+                data,
+            },
+        );
+    }
+
+    Ok(GgufCheckpoint {
+        metadata: gguf.metadata,
+        weights,
+    })
+}
+
+// -------------------------
+// Dequantization
+// -------------------------
+
+fn dequantize(dtype: GgmlType, raw: &[u8]) -> Vec<f32> {
+    let (block, bytes) = dtype.block_layout();
+    let mut out = Vec::with_capacity(raw.len() / bytes * block);
+
+    for b in raw.chunks_exact(bytes) {
+        match dtype {
+            GgmlType::F32 => out.push(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            GgmlType::F16 => out.push(f16_to_f32(u16::from_le_bytes([b[0], b[1]]))),
+            GgmlType::Q8_0 => {
+                let d = f16_to_f32(u16::from_le_bytes([b[0], b[1]]));
+                out.extend(b[2..].iter().map(|&q| q as i8 as f32 * d));
+            }
+            GgmlType::Q4_0 => {
+                let d = f16_to_f32(u16::from_le_bytes([b[0], b[1]]));
+                let qs = &b[2..];
+                out.extend(qs.iter().map(|&q| ((q & 0x0f) as i32 - 8) as f32 * d));
+                out.extend(qs.iter().map(|&q| ((q >> 4) as i32 - 8) as f32 * d));
+            }
+            GgmlType::Q4_1 => {
+                let d = f16_to_f32(u16::from_le_bytes([b[0], b[1]]));
+                let m = f16_to_f32(u16::from_le_bytes([b[2], b[3]]));
+                let qs = &b[4..];
+                out.extend(qs.iter().map(|&q| (q & 0x0f) as f32 * d + m));
+                out.extend(qs.iter().map(|&q| (q >> 4) as f32 * d + m));
+            }
+        }
+    }
+    out
+}
+
+/// IEEE 754 half -> single precision.
+pub fn f16_to_f32(h: u16) -> f32 {
+    let sign = ((h >> 15) as u32) << 31;
+    let exp = ((h >> 10) & 0x1f) as u32;
+    let frac = (h & 0x3ff) as u32;
+
+    let bits = match (exp, frac) {
+        (0, 0) => sign,
+        (0, _) => {
+            // Subnormal: renormalize.
+            let mut e = 127 - 15 + 1;
+            let mut f = frac;
+            while f & 0x400 == 0 {
+                f <<= 1;
+                e -= 1;
+            }
+            sign | (e << 23) | ((f & 0x3ff) << 13)
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | (frac << 13),
+        _ => sign | ((exp + 127 - 15) << 23) | (frac << 13),
+    };
+    f32::from_bits(bits)
+}
+
+// -------------------------
+// Primitive readers
+// -------------------------
+
+fn read_u32<R: Read>(r: &mut R) -> Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> Result<u64> {
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_string<R: Read>(r: &mut R) -> Result<String> {
+    let len = read_u64(r)? as usize;
+    let mut buf = vec![0u8; len];
+    r.read_exact(&mut buf)?;
+    String::from_utf8(buf)
+        .map_err(|e| ShoutError::Model(format!("invalid UTF-8 string in GGUF: {e}")))
+}
+
+fn read_value<R: Read>(r: &mut R, ty: u32) -> Result<GgufValue> {
+    let mut b8 = [0u8; 8];
+    Ok(match ty {
+        0 => {
+            r.read_exact(&mut b8[..1])?;
+            GgufValue::U8(b8[0])
+        }
+        1 => {
+            r.read_exact(&mut b8[..1])?;
+            GgufValue::I8(b8[0] as i8)
+        }
+        2 => {
+            r.read_exact(&mut b8[..2])?;
+            GgufValue::U16(u16::from_le_bytes([b8[0], b8[1]]))
+        }
+        3 => {
+            r.read_exact(&mut b8[..2])?;
+            GgufValue::I16(i16::from_le_bytes([b8[0], b8[1]]))
+        }
+        4 => GgufValue::U32(read_u32(r)?),
+        5 => GgufValue::I32(read_u32(r)? as i32),
+        6 => GgufValue::F32(f32::from_bits(read_u32(r)?)),
+        7 => {
+            r.read_exact(&mut b8[..1])?;
+            GgufValue::Bool(b8[0] != 0)
+        }
+        8 => GgufValue::String(read_string(r)?),
+        9 => {
+            let elem_ty = read_u32(r)?;
+            let n = read_u64(r)?;
+            let mut items = Vec::with_capacity(n.min(1 << 20) as usize);
+            for _ in 0..n {
+                items.push(read_value(r, elem_ty)?);
+            }
+            GgufValue::Array(items)
+        }
+        10 => GgufValue::U64(read_u64(r)?),
+        11 => GgufValue::I64(read_u64(r)? as i64),
+        12 => GgufValue::F64(f64::from_bits(read_u64(r)?)),
+        other => {
+            return Err(ShoutError::Model(format!(
+                "unknown GGUF value type {other}"
+            )));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn put_str(buf: &mut Vec<u8>, s: &str) {
+        buf.extend((s.len() as u64).to_le_bytes());
+        buf.extend(s.as_bytes());
+    }
+
+    #[test]
+    fn reads_f32_and_q8_0_tensors() {
+        let mut buf = Vec::new();
+        buf.extend(MAGIC);
+        buf.extend(3u32.to_le_bytes());
+        buf.extend(2u64.to_le_bytes()); // tensors
+        buf.extend(1u64.to_le_bytes()); // kv
+
+        put_str(&mut buf, "general.architecture");
+        buf.extend(8u32.to_le_bytes());
+        put_str(&mut buf, "whisper");
+
+        // 2x3 f32 tensor (ne = [3, 2]) at offset 0
+        put_str(&mut buf, "a");
+        buf.extend(2u32.to_le_bytes());
+        buf.extend(3u64.to_le_bytes());
+        buf.extend(2u64.to_le_bytes());
+        buf.extend(0u32.to_le_bytes());
+        buf.extend(0u64.to_le_bytes());
+
+        // 32-element Q8_0 tensor right after (24 bytes of f32 padded to 32)
+        put_str(&mut buf, "b");
+        buf.extend(1u32.to_le_bytes());
+        buf.extend(32u64.to_le_bytes());
+        buf.extend(8u32.to_le_bytes());
+        buf.extend(32u64.to_le_bytes());
+
+        while buf.len() % 32 != 0 {
+            buf.push(0);
+        }
+        for v in [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0] {
+            buf.extend(v.to_le_bytes());
+        }
+        buf.extend([0u8; 8]);
+        buf.extend(0x3800u16.to_le_bytes()); // d = 0.5 in f16
+        buf.extend((0..32).map(|i| (i as i8 - 16) as u8));
+
+        let mut cur = Cursor::new(buf);
+        let gguf = GgufFile::read(&mut cur).unwrap();
+        assert_eq!(
+            gguf.metadata["general.architecture"].as_str(),
+            Some("whisper")
+        );
+
+        let a = gguf.tensor("a").unwrap().clone();
+        assert_eq!(a.shape, vec![2, 3]);
+        assert_eq!(
+            gguf.read_tensor(&mut cur, &a).unwrap(),
+            vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]
+        );
+
+        let b = gguf.tensor("b").unwrap().clone();
+        let data = gguf.read_tensor(&mut cur, &b).unwrap();
+        assert_eq!(data.len(), 32);
+        assert_eq!(data[0], -8.0);
+        assert_eq!(data[31], 7.5);
+    }
+
+    #[test]
+    fn converts_half_floats() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+    }
+}
@@ -0,0 +1,28 @@
+use candle_core::quantized::QMatMul;
+use candle_core::{Module, Result, Tensor};
+use candle_nn::Linear;
+
+/// Linear layer backed either by f32 weights or by a quantized matmul kernel.
+#[derive(Debug, Clone)]
+pub enum QLinear {
+    Float(Linear),
+    Quantized {
+        weight: QMatMul,
+        bias: Option<Tensor>,
+    },
+}
+
+impl Module for QLinear {
+    fn forward(&self, x: &Tensor) -> Result<Tensor> {
+        match self {
+            QLinear::Float(l) => l.forward(x),
+            QLinear::Quantized { weight, bias } => {
+                let y = weight.forward(x)?;
+                match bias {
+                    Some(b) => y.broadcast_add(b),
+                    None => Ok(y),
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,11 @@
+pub mod attention;
+pub mod convert;
 pub mod convolutional;
 pub mod decoder;
 pub mod encoder;
-pub mod shout;
\ No newline at end of file
+pub mod gguf;
+pub mod linear;
+pub mod quantize;
+pub mod shout;
+pub mod test_tiny;
+pub mod weights;
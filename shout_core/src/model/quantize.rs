@@ -0,0 +1,173 @@
+//! Post-training weight quantization of linear layers.
+//!
+//! Linear weights are quantized to ggml's Q8_0 (int8) or Q4_0 (int4) block formats:
+//! every group of 32 consecutive input weights of an output channel gets its own
+//! scale, which is at least as fine-grained as per-channel scaling and is what
+//! candle's quantized matmul kernels (CPU, CUDA, Metal) operate on. Embeddings,
+//! norms and convolutions are kept in f32.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use candle_core::quantized::{GgmlDType, QTensor, gguf_file};
+use candle_core::{DType, Device};
+
+use crate::confidence::CALIBRATION_FILE;
+use crate::errors::{IoContext, Result, ShoutError};
+
+/// Quantized block size; input dimensions must be a multiple of this.
+const BLOCK: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantType {
+    Int8,
+    Int4,
+}
+
+impl QuantType {
+    fn ggml(self) -> GgmlDType {
+        match self {
+            QuantType::Int8 => GgmlDType::Q8_0,
+            QuantType::Int4 => GgmlDType::Q4_0,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            QuantType::Int8 => "q8_0",
+            QuantType::Int4 => "q4_0",
+        }
+    }
+}
+
+impl FromStr for QuantType {
+    type Err = ShoutError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "8" | "int8" | "q8_0" => Ok(QuantType::Int8),
+            "4" | "int4" | "q4_0" => Ok(QuantType::Int4),
+            other => Err(ShoutError::InvalidArgument(format!(
+                "unknown quantization '{other}' (expected int8 or int4)"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct QuantizeReport {
+    pub quantized_tensors: usize,
+    pub kept_tensors: usize,
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+}
+
+/// Linear weights (rank 2, `.weight`, input dim divisible by the block size).
+/// The token embedding doubles as output projection and is kept in f32.
+fn is_linear_weight(name: &str, dims: &[usize]) -> bool {
+    name.ends_with(".weight")
+        && dims.len() == 2
+        && dims[1].is_multiple_of(BLOCK)
+        && !name.contains("token_embedding")
+        && !name.contains("positional_embedding")
+}
+
+/// Quantize a safetensors checkpoint into a GGUF file.
+pub fn quantize_safetensors(
+    input: &Path,
+    output: &Path,
+    qtype: QuantType,
+) -> Result<QuantizeReport> {
+    let tensors = candle_core::safetensors::load(input, &Device::Cpu).map_err(|e| {
+        ShoutError::Model(format!(
+            "failed to read checkpoint: {}: {e}",
+            input.display()
+        ))
+    })?;
+
+    let mut names: Vec<&String> = tensors.keys().collect();
+    names.sort();
+
+    let mut report = QuantizeReport::default();
+    let mut qtensors = Vec::with_capacity(names.len());
+
+    for name in names {
+        let t = tensors[name].to_dtype(DType::F32)?;
+        let (dtype, quantized) = if is_linear_weight(name, t.dims()) {
+            (qtype.ggml(), true)
+        } else {
+            (GgmlDType::F32, false)
+        };
+
+        let q = QTensor::quantize(&t, dtype)
+            .map_err(|e| ShoutError::Model(format!("failed to quantize {name}: {e}")))?;
+        report.bytes_before += t.elem_count() * 4;
+        report.bytes_after += q.storage_size_in_bytes();
+        if quantized {
+            report.quantized_tensors += 1;
+        } else {
+            report.kept_tensors += 1;
+        }
+        qtensors.push((name.as_str(), q));
+    }
+
+    let arch = gguf_file::Value::String("whisper".to_string());
+    let quant = gguf_file::Value::String(qtype.name().to_string());
+    let metadata = [
+        ("general.architecture", &arch),
+        ("shout.quantization", &quant),
+    ];
+    let tensor_refs: Vec<(&str, &QTensor)> = qtensors.iter().map(|(n, q)| (*n, q)).collect();
+
+    let file = File::create(output)
+        .io_context(|| format!("Failed to create output: {}", output.display()))?;
+    let mut writer = BufWriter::new(file);
+    gguf_file::write(&mut writer, &metadata, &tensor_refs)?;
+
+    Ok(report)
+}
+
+/// Quantize the model in `model_dir` into `out_dir`, copying config, tokenizer and calibration.
+/// Returns the path of the written GGUF file.
+pub fn quantize_model_dir(
+    model_dir: &Path,
+    out_dir: &Path,
+    qtype: QuantType,
+) -> Result<(PathBuf, QuantizeReport)> {
+    std::fs::create_dir_all(out_dir)?;
+    for file in ["config.json", "tokenizer.json", CALIBRATION_FILE] {
+        let src = model_dir.join(file);
+        if src.exists() {
+            std::fs::copy(&src, out_dir.join(file))
+                .io_context(|| format!("failed to copy {}", src.display()))?;
+        }
+    }
+
+    let out_path = out_dir.join(format!("model-{}.gguf", qtype.name()));
+    let report = quantize_safetensors(&model_dir.join("model.safetensors"), &out_path, qtype)?;
+    Ok((out_path, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_linear_weights_are_quantized() {
+        assert!(is_linear_weight(
+            "encoder.blocks.0.attn.query.weight",
+            &[384, 384]
+        ));
+        assert!(!is_linear_weight(
+            "encoder.blocks.0.attn.query.bias",
+            &[384]
+        ));
+        assert!(!is_linear_weight(
+            "decoder.token_embedding.weight",
+            &[51865, 384]
+        ));
+        assert!(!is_linear_weight("encoder.conv1.weight", &[384, 80, 3]));
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use candle_core::quantized::gguf_file;
+use candle_core::{D, DType, Device, Module, Tensor, TensorId};
+use candle_nn::VarBuilder;
+use ndarray::Array2;
+
+use super::attention::KeyValue;
+use super::decoder::{KvCache, TextDecoder};
+use super::encoder::AudioEncoder;
+use super::linear::QLinear;
+use super::weights::{QuantizedWeights, Weights};
+use crate::audio::mel::MelSpec;
+use crate::config::model::ModelConfig;
+use crate::decoding::SpeechModel;
+use crate::errors::{IoContext, Result, ShoutError};
+
+/// Whisper-style encoder/decoder running on candle (CPU, CUDA or Metal).
+///
+/// Weights use OpenAI's parameter names (`encoder.blocks.0.attn.query.weight`, ...),
+/// which is also the layout shout_train writes and whisper.cpp uses in GGUF files.
+pub struct ShoutModel {
+    pub config: ModelConfig,
+    encoder: AudioEncoder,
+    decoder: TextDecoder,
+
+    /// Optional `ctc_head` linear layer for CTC-trained checkpoints.
+    ctc_head: Option<QLinear>,
+    device: Device,
+
+    /// Cross-attention keys/values of the most recently seen encoder output.
+    cross_cache: Option<(TensorId, Vec<Option<KeyValue>>)>,
+
+    /// Self-attention caches of recently decoded token sequences. A new request
+    /// continues from the longest cached prefix, so greedy decoding and every beam
+    /// of a beam search only run the decoder on their newest token.
+    prefix_cache: Vec<(Vec<u32>, KvCache)>,
+}
+
+/// Number of token prefixes kept in the cache (enough for beam sizes up to 8 with
+/// one step of history).
+const MAX_CACHED_PREFIXES: usize = 16;
+
+impl ShoutModel {
+    pub fn new(config: ModelConfig, w: Weights) -> Result<Self> {
+        let ctc_head = match config.ctc_vocab {
+            Some(n) => Some(w.pp("ctc_head").linear(config.n_audio_state, n, true)?),
+            None => None,
+        };
+
+        Ok(Self {
+            encoder: AudioEncoder::load(&config, w.pp("encoder"))?,
+            decoder: TextDecoder::load(&config, w.pp("decoder"))?,
+            ctc_head,
+            device: w.device().clone(),
+            config,
+            cross_cache: None,
+            prefix_cache: Vec::new(),
+        })
+    }
+
+    /// Load a model directory: `config.json` plus `model.safetensors` or a `*.gguf` file.
+    pub fn load_dir<P: AsRef<Path>>(dir: P, device: &Device) -> Result<Self> {
+        let dir = dir.as_ref();
+        let config = ModelConfig::from_file(dir.join("config.json"))?;
+
+        let safetensors = dir.join("model.safetensors");
+        if safetensors.exists() {
+            return Self::load_safetensors(config, &safetensors, device);
+        }
+
+        let gguf = std::fs::read_dir(dir)
+            .io_context(|| format!("failed to read model dir: {}", dir.display()))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .find(|p| p.extension().is_some_and(|e| e == "gguf"));
+        match gguf {
+            Some(path) => Self::load_gguf(config, &path, device),
+            None => Err(ShoutError::Model(format!(
+                "no model.safetensors or *.gguf found in {}",
+                dir.display()
+            ))),
+        }
+    }
+
+    pub fn load_safetensors(config: ModelConfig, path: &Path, device: &Device) -> Result<Self> {
+        // SAFETY: the file is memory-mapped read-only and not modified while the model lives.
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[path], DType::F32, device)? };
+        Self::new(config, Weights::Float(vb))
+            .map_err(|e| e.context(format!("failed to load weights: {}", path.display())))
+    }
+
+    /// Load a GGUF checkpoint. Quantized linear layers run on candle's quantized
+    /// matmul kernels; all other tensors are dequantized to f32.
+    pub fn load_gguf(config: ModelConfig, path: &Path, device: &Device) -> Result<Self> {
+        let mut file =
+            File::open(path).io_context(|| format!("failed to open model: {}", path.display()))?;
+        Self::read_gguf(config, &mut file, device)
+            .map_err(|e| e.context(format!("failed to load GGUF: {}", path.display())))
+    }
+
+    /// Load a GGUF checkpoint held in memory, for targets without a filesystem (wasm).
+    pub fn load_gguf_bytes(config: ModelConfig, bytes: &[u8], device: &Device) -> Result<Self> {
+        Self::read_gguf(config, &mut std::io::Cursor::new(bytes), device)
+    }
+
+    fn read_gguf<R: Read + Seek>(
+        config: ModelConfig,
+        reader: &mut R,
+        device: &Device,
+    ) -> Result<Self> {
+        let content = gguf_file::Content::read(reader)
+            .map_err(|e| ShoutError::Model(format!("failed to parse GGUF: {e}")))?;
+
+        let mut tensors = HashMap::with_capacity(content.tensor_infos.len());
+        for name in content.tensor_infos.keys() {
+            tensors.insert(name.clone(), content.tensor(reader, name, device)?);
+        }
+
+        let w = Weights::Quantized(QuantizedWeights::new(tensors, device));
+        Self::new(config, w).map_err(|e| e.context("failed to load weights"))
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    /// `MelSpec` (frames, n_mels) -> `(1, n_mels, n_frames)`, padded or cut to one window.
+    pub fn mel_tensor(&self, mel: &MelSpec) -> Result<Tensor> {
+        if mel.n_mels != self.config.n_mels {
+            return Err(ShoutError::Feature(format!(
+                "model expects {} mel bins, got {}",
+                self.config.n_mels, mel.n_mels
+            )));
+        }
+
+        let target = self.config.n_frames();
+        let pad_value = mel.data.iter().copied().fold(f32::INFINITY, f32::min);
+        let pad_value = if pad_value.is_finite() {
+            pad_value
+        } else {
+            0.0
+        };
+
+        let mut data = vec![pad_value; target * mel.n_mels];
+        let n = mel.n_frames.min(target) * mel.n_mels;
+        data[..n].copy_from_slice(&mel.data[..n]);
+
+        let t = Tensor::from_vec(data, (target, mel.n_mels), &self.device)?;
+        Ok(t.t()?.unsqueeze(0)?)
+    }
+
+    /// Mean encoder state over the part of the window `mel` covers, leaving
+    /// out the padding: one `n_audio_state` vector.
+    pub fn embed(&mut self, mel: &MelSpec) -> Result<Vec<f32>> {
+        let encoded = self.encode(mel)?.squeeze(0)?;
+        // The encoder halves the frame rate.
+        let covered = mel.n_frames.div_ceil(2).clamp(1, encoded.dim(0)?);
+        Ok(encoded.narrow(0, 0, covered)?.mean(0)?.to_vec1::<f32>()?)
+    }
+
+    /// Cross-attention keys/values for `encoded`, computed once per window. A
+    /// new window also drops the self-attention caches of the last one.
+    fn cross_key_values(&mut self, encoded: &Tensor) -> Result<Vec<Option<KeyValue>>> {
+        match &self.cross_cache {
+            Some((id, kv)) if *id == encoded.id() => Ok(kv.clone()),
+            _ => {
+                let kv = self.decoder.cross_key_values(encoded)?;
+                self.cross_cache = Some((encoded.id(), kv.clone()));
+                self.prefix_cache.clear();
+                Ok(kv)
+            }
+        }
+    }
+
+    /// Per-frame CTC log-probabilities `(frames, ctc_vocab)`, if the checkpoint has a CTC head.
+    pub fn ctc_log_probs(&self, encoded: &Tensor) -> Result<Option<Array2<f32>>> {
+        let Some(head) = &self.ctc_head else {
+            return Ok(None);
+        };
+
+        let logits = head.forward(encoded)?.squeeze(0)?;
+        let logp = candle_nn::ops::log_softmax(&logits, D::Minus1)?;
+        let (frames, vocab) = logp.dims2()?;
+        let flat = logp.flatten_all()?.to_vec1::<f32>()?;
+        let log_probs = Array2::from_shape_vec((frames, vocab), flat)
+            .map_err(|e| ShoutError::Model(format!("CTC log-probabilities: {e}")))?;
+        Ok(Some(log_probs))
+    }
+}
+
+impl SpeechModel for ShoutModel {
+    type Encoded = Tensor;
+
+    fn encode(&mut self, mel: &MelSpec) -> Result<Tensor> {
+        let mel = self.mel_tensor(mel)?;
+        Ok(self.encoder.forward(&mel)?)
+    }
+
+    fn encode_batch(&mut self, mels: &[&MelSpec]) -> Result<Vec<Tensor>> {
+        if mels.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mels = mels
+            .iter()
+            .map(|mel| self.mel_tensor(mel))
+            .collect::<Result<Vec<_>>>()?;
+        let encoded = self.encoder.forward(&Tensor::cat(&mels, 0)?)?;
+        let mut out = Vec::with_capacity(mels.len());
+        for i in 0..mels.len() {
+            out.push(encoded.narrow(0, i, 1)?);
+        }
+        Ok(out)
+    }
+
+    fn next_token_logits(&mut self, encoded: &Tensor, tokens: &[u32]) -> Result<Vec<f32>> {
+        if tokens.is_empty() {
+            return Err(ShoutError::InvalidArgument(
+                "decoder needs at least one prompt token".into(),
+            ));
+        }
+
+        let cross = self.cross_key_values(encoded)?;
+
+        // Continue from the longest cached strict prefix of `tokens`.
+        let mut cache = self
+            .prefix_cache
+            .iter()
+            .filter(|(prefix, _)| prefix.len() < tokens.len() && tokens.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, cache)| cache.clone())
+            .unwrap_or_default();
+
+        let new_tokens = &tokens[cache.len()..];
+        let input = Tensor::new(new_tokens, &self.device)?.unsqueeze(0)?;
+        let logits = self.decoder.forward_cached(&input, &cross, &mut cache)?;
+
+        if self.prefix_cache.len() >= MAX_CACHED_PREFIXES {
+            self.prefix_cache.remove(0);
+        }
+        self.prefix_cache.push((tokens.to_vec(), cache));
+
+        Ok(logits
+            .squeeze(0)?
+            .get(new_tokens.len() - 1)?
+            .to_vec1::<f32>()?)
+    }
+
+    fn alignment_attention(
+        &mut self,
+        encoded: &Tensor,
+        tokens: &[u32],
+    ) -> Result<Option<Array2<f32>>> {
+        let heads = self.config.word_alignment_heads();
+        if tokens.is_empty() || heads.is_empty() {
+            return Ok(None);
+        }
+
+        let cross = self.cross_key_values(encoded)?;
+        let input = Tensor::new(tokens, &self.device)?.unsqueeze(0)?;
+        let layers = self.decoder.cross_attention_weights(&input, &cross)?;
+        let mut sum: Option<Tensor> = None;
+        for &(layer, head) in &heads {
+            let weights = layers
+                .get(layer)
+                .ok_or_else(|| {
+                    ShoutError::Model(format!("alignment head in missing layer {layer}"))
+                })?
+                .get(0)?
+                .get(head)?;
+            sum = Some(match sum {
+                Some(sum) => (sum + weights)?,
+                None => weights,
+            });
+        }
+        let mean = (sum.expect("at least one head") / heads.len() as f64)?;
+
+        let (rows, frames) = mean.dims2()?;
+        let attention = Array2::from_shape_vec((rows, frames), mean.flatten_all()?.to_vec1()?)
+            .map_err(|e| ShoutError::Model(format!("alignment attention: {e}")))?;
+        Ok(Some(attention))
+    }
+}
@@ -0,0 +1,254 @@
+//! `test-tiny`: a randomly initialized model small enough to generate on the
+//! fly, for tests and demos that need the whole pipeline but not its accuracy.
+//!
+//! The directory written by [`write_test_tiny`] loads like any other model:
+//! `config.json`, `model.safetensors` (about 4 MB) and a `tokenizer.json` with
+//! the layout of the multilingual Whisper vocabulary, so special tokens,
+//! language detection and timestamps work as with real weights. The text
+//! vocabulary holds single bytes and byte pairs without merges. `sample.wav`
+//! has a few seconds of synthetic voiced sound to transcribe.
+//!
+//! Everything is generated from a fixed seed, so two calls produce identical
+//! files and the model's (meaningless) output is reproducible.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use candle_core::{Device, Tensor};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::{Map, Value, json};
+
+use super::convert::expected_tensors;
+use crate::config::model::ModelConfig;
+use crate::errors::{IoContext, Result};
+use crate::features::SAMPLE_RATE;
+use crate::tokenizer::special_tokens::{LANGUAGES, N_TIMESTAMPS, SpecialTokens};
+
+/// Name under which the CLIs accept the model.
+pub const TEST_TINY: &str = "test-tiny";
+
+/// Length of `sample.wav`.
+pub const SAMPLE_SECONDS: f32 = 3.0;
+
+const SEED: u64 = 0x5407;
+
+/// Write the `test-tiny` model and `sample.wav` into `dir`.
+pub fn write_test_tiny(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)
+        .io_context(|| format!("failed to create model dir: {}", dir.display()))?;
+    let config = ModelConfig::test_tiny();
+    let mut rng = StdRng::seed_from_u64(SEED);
+
+    let mut tensors = HashMap::new();
+    for (name, shape) in expected_tensors(&config) {
+        let n: usize = shape.iter().product();
+        let values: Vec<f32> = if is_norm_scale(&name) {
+            vec![1.0; n]
+        } else if name.ends_with(".bias") {
+            vec![0.0; n]
+        } else {
+            (0..n).map(|_| rng.random_range(-0.1..0.1)).collect()
+        };
+        tensors.insert(name, Tensor::from_vec(values, shape, &Device::Cpu)?);
+    }
+    candle_core::safetensors::save(&tensors, dir.join("model.safetensors"))?;
+
+    let write = |name: &str, bytes: &[u8]| {
+        let path = dir.join(name);
+        fs::write(&path, bytes).io_context(|| format!("failed to write {}", path.display()))
+    };
+    write(
+        "config.json",
+        serde_json::to_string_pretty(&config)?.as_bytes(),
+    )?;
+    write(
+        "tokenizer.json",
+        serde_json::to_string(&tokenizer_json())?.as_bytes(),
+    )?;
+    write(
+        "sample.wav",
+        &wav_bytes(&synthetic_speech(SAMPLE_SECONDS), SAMPLE_RATE),
+    )?;
+    Ok(())
+}
+
+/// Layer norm gains, which start at one.
+fn is_norm_scale(name: &str) -> bool {
+    let Some(layer) = name.strip_suffix(".weight") else {
+        return false;
+    };
+    layer.ends_with("_ln") || layer.ends_with(".ln") || layer.ends_with(".ln_post")
+}
+
+/// A Hugging Face `tokenizer.json`: byte-level BPE whose ids line up with
+/// [`SpecialTokens::whisper_multilingual`].
+fn tokenizer_json() -> Value {
+    let special = SpecialTokens::whisper_multilingual(ModelConfig::test_tiny().n_vocab);
+    let bytes = byte_chars();
+
+    // Single bytes first, then byte pairs up to the first special token.
+    let mut vocab = Map::new();
+    for (id, c) in bytes.iter().enumerate() {
+        vocab.insert(c.to_string(), json!(id));
+    }
+    for id in bytes.len()..special.eot as usize {
+        let pair = id - bytes.len();
+        let token: String = [bytes[pair / 256], bytes[pair % 256]].iter().collect();
+        vocab.insert(token, json!(id));
+    }
+
+    let mut added = vec![
+        "<|endoftext|>".to_string(),
+        "<|startoftranscript|>".to_string(),
+    ];
+    added.extend(
+        LANGUAGES[..special.n_languages]
+            .iter()
+            .map(|l| format!("<|{l}|>")),
+    );
+    added.extend(
+        [
+            "<|translate|>",
+            "<|transcribe|>",
+            "<|startoflm|>",
+            "<|startofprev|>",
+            "<|nospeech|>",
+            "<|notimestamps|>",
+        ]
+        .map(String::from),
+    );
+    added.extend((0..N_TIMESTAMPS).map(|i| format!("<|{:.2}|>", i as f32 * 0.02)));
+    let added: Vec<Value> = added
+        .iter()
+        .enumerate()
+        .map(|(i, content)| {
+            json!({
+                "id": special.eot as usize + i,
+                "content": content,
+                "single_word": false,
+                "lstrip": false,
+                "rstrip": false,
+                "normalized": false,
+                "special": true,
+            })
+        })
+        .collect();
+
+    let byte_level = json!({
+        "type": "ByteLevel",
+        "add_prefix_space": false,
+        "trim_offsets": true,
+        "use_regex": true,
+    });
+    json!({
+        "version": "1.0",
+        "truncation": null,
+        "padding": null,
+        "added_tokens": added,
+        "normalizer": null,
+        "pre_tokenizer": byte_level,
+        "post_processor": null,
+        "decoder": byte_level,
+        "model": {
+            "type": "BPE",
+            "dropout": null,
+            "unk_token": null,
+            "continuing_subword_prefix": "",
+            "end_of_word_suffix": "",
+            "fuse_unk": false,
+            "byte_fallback": false,
+            "vocab": vocab,
+            "merges": [],
+        },
+    })
+}
+
+/// The printable characters GPT-2's byte-level BPE stands in for each byte.
+fn byte_chars() -> Vec<char> {
+    let printable = |b: u32| (0x21..=0x7e).contains(&b) || (0xa1..=0xac).contains(&b) || b >= 0xae;
+    let mut shifted = 0;
+    (0..256u32)
+        .map(|b| {
+            let code = if printable(b) {
+                b
+            } else {
+                shifted += 1;
+                255 + shifted
+            };
+            char::from_u32(code).expect("below the surrogate range")
+        })
+        .collect()
+}
+
+/// `seconds` of a voiced sound at 16 kHz: a gliding 120-180 Hz pitch with
+/// harmonics shaped by two formants, in syllable-like bursts.
+pub fn synthetic_speech(seconds: f32) -> Vec<f32> {
+    let n = (seconds * SAMPLE_RATE as f32) as usize;
+    let mut phase = 0.0f32;
+    (0..n)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let pitch = 150.0 + 30.0 * (2.0 * PI * 0.7 * t).sin();
+            phase += 2.0 * PI * pitch / SAMPLE_RATE as f32;
+            let voiced: f32 = (1..=20)
+                .map(|h| {
+                    let f = h as f32 * pitch;
+                    let formants = (-((f - 700.0) / 200.0).powi(2)).exp()
+                        + 0.6 * (-((f - 1200.0) / 300.0).powi(2)).exp();
+                    formants * (h as f32 * phase).sin()
+                })
+                .sum();
+            let syllables = (PI * 4.0 * t).sin().max(0.0);
+            0.3 * syllables * voiced
+        })
+        .collect()
+}
+
+/// `pcm` as a 16-bit mono WAV file.
+pub fn wav_bytes(pcm: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_len = (pcm.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // mono
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for &s in pcm {
+        out.extend_from_slice(&((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::bpe::Tokenizer;
+
+    #[test]
+    fn tokenizer_matches_the_whisper_layout() {
+        let dir = std::env::temp_dir().join(format!("shout_test_tiny_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tokenizer.json"), tokenizer_json().to_string()).unwrap();
+        let tokenizer = Tokenizer::from_file(dir.join("tokenizer.json")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let n_vocab = ModelConfig::test_tiny().n_vocab;
+        assert_eq!(
+            tokenizer.special,
+            SpecialTokens::whisper_multilingual(n_vocab)
+        );
+        let tokens = tokenizer.encode("Grüß Gott").unwrap();
+        assert!(tokens.iter().all(|&t| t < 256));
+        assert_eq!(tokenizer.decode(&tokens).unwrap(), "Grüß Gott");
+    }
+}
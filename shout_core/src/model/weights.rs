@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use candle_core::quantized::{GgmlDType, QMatMul, QTensor};
+use candle_core::{Device, Result, Shape, Tensor};
+use candle_nn::{Conv1d, Conv1dConfig, Embedding, LayerNorm, Linear, VarBuilder};
+
+use super::linear::QLinear;
+
+/// Where model parameters come from: f32 tensors (safetensors) or a quantized GGUF
+/// checkpoint. Linear layers of a quantized checkpoint stay quantized; everything
+/// else (norms, embeddings, convolutions) is dequantized on load.
+#[derive(Clone)]
+pub enum Weights<'a> {
+    Float(VarBuilder<'a>),
+    Quantized(QuantizedWeights),
+}
+
+#[derive(Clone)]
+pub struct QuantizedWeights {
+    tensors: Arc<HashMap<String, Arc<QTensor>>>,
+    prefix: String,
+    device: Device,
+}
+
+impl QuantizedWeights {
+    pub fn new(tensors: HashMap<String, QTensor>, device: &Device) -> Self {
+        Self {
+            tensors: Arc::new(tensors.into_iter().map(|(k, v)| (k, Arc::new(v))).collect()),
+            prefix: String::new(),
+            device: device.clone(),
+        }
+    }
+
+    fn path(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", self.prefix, name)
+        }
+    }
+
+    fn qtensor(&self, name: &str) -> Result<Arc<QTensor>> {
+        let path = self.path(name);
+        self.tensors
+            .get(&path)
+            .cloned()
+            .ok_or_else(|| candle_core::Error::CannotFindTensor { path }.bt())
+    }
+}
+
+impl Weights<'_> {
+    pub fn pp<S: ToString>(&self, s: S) -> Self {
+        match self {
+            Weights::Float(vb) => Weights::Float(vb.pp(s)),
+            Weights::Quantized(q) => Weights::Quantized(QuantizedWeights {
+                prefix: q.path(&s.to_string()),
+                ..q.clone()
+            }),
+        }
+    }
+
+    pub fn device(&self) -> &Device {
+        match self {
+            Weights::Float(vb) => vb.device(),
+            Weights::Quantized(q) => &q.device,
+        }
+    }
+
+    pub fn get<S: Into<Shape>>(&self, shape: S, name: &str) -> Result<Tensor> {
+        match self {
+            Weights::Float(vb) => vb.get(shape, name),
+            Weights::Quantized(q) => {
+                let shape = shape.into();
+                let t = q.qtensor(name)?.dequantize(&q.device)?;
+                if t.shape() != &shape {
+                    return Err(candle_core::Error::UnexpectedShape {
+                        msg: format!("shape mismatch for {}", q.path(name)),
+                        expected: shape,
+                        got: t.shape().clone(),
+                    }
+                    .bt());
+                }
+                Ok(t)
+            }
+        }
+    }
+
+    pub fn linear(&self, in_dim: usize, out_dim: usize, bias: bool) -> Result<QLinear> {
+        let b = if bias {
+            Some(self.get(out_dim, "bias")?)
+        } else {
+            None
+        };
+
+        match self {
+            Weights::Quantized(q) => {
+                let w = q.qtensor("weight")?;
+                if w.dtype() == GgmlDType::F32 || w.dtype() == GgmlDType::F16 {
+                    let w = w.dequantize(&q.device)?;
+                    return Ok(QLinear::Float(Linear::new(w, b)));
+                }
+                Ok(QLinear::Quantized {
+                    weight: QMatMul::from_arc(w)?,
+                    bias: b,
+                })
+            }
+            Weights::Float(_) => {
+                let w = self.get((out_dim, in_dim), "weight")?;
+                Ok(QLinear::Float(Linear::new(w, b)))
+            }
+        }
+    }
+
+    pub fn layer_norm(&self, size: usize) -> Result<LayerNorm> {
+        Ok(LayerNorm::new(
+            self.get(size, "weight")?,
+            self.get(size, "bias")?,
+            1e-5,
+        ))
+    }
+
+    pub fn conv1d(
+        &self,
+        in_c: usize,
+        out_c: usize,
+        kernel: usize,
+        cfg: Conv1dConfig,
+    ) -> Result<Conv1d> {
+        let w = self.get((out_c, in_c, kernel), "weight")?;
+        let b = self.get(out_c, "bias")?;
+        Ok(Conv1d::new(w, Some(b), cfg))
+    }
+
+    pub fn embedding(&self, n: usize, dim: usize) -> Result<Embedding> {
+        Ok(Embedding::new(self.get((n, dim), "weight")?, dim))
+    }
+}
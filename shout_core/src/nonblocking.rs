@@ -0,0 +1,174 @@
+//! `async` versions of the blocking entry points, for tokio applications.
+//!
+//! Decoding and transcription are CPU/GPU bound; each call here runs on
+//! tokio's blocking thread pool and the caller awaits the result, so the async
+//! workers stay free. Dropping a future does not stop the work it started:
+//! cancel the transcriber's [`CancelToken`](crate::cancel::CancelToken) for that.
+//!
+//! ```no_run
+//! use candle_core::Device;
+//! use shout_core::nonblocking::AsyncTranscriber;
+//!
+//! # async fn run() -> shout_core::Result<()> {
+//! let transcriber = AsyncTranscriber::load("models/base", Device::Cpu).await?;
+//! let transcript = transcriber.transcribe_file("speech.wav").await?;
+//! println!("{}", transcript.text());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use candle_core::Device;
+
+use crate::audio::decoder::decode_to_f32_mono_16k;
+use crate::decoding::SpeechModel;
+use crate::errors::{Result, ShoutError};
+use crate::inference::load_transcriber;
+use crate::model::shout::ShoutModel;
+use crate::pipeline::WindowTranscriber;
+use crate::pipeline::streaming::{StreamUpdate, StreamingSession};
+use crate::pipeline::transcribe::Transcriber;
+use crate::transcript::Transcript;
+
+/// Run `f` on the blocking thread pool. A panic in `f` resumes in the caller.
+pub async fn spawn_blocking<R, F>(f: F) -> Result<R>
+where
+    F: FnOnce() -> Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(ShoutError::io(
+            "blocking task did not run",
+            io::Error::other(e),
+        )),
+    }
+}
+
+/// Decode an audio file to mono f32 samples at 16 kHz.
+pub async fn decode_file(path: impl Into<PathBuf>) -> Result<Vec<f32>> {
+    let path = path.into();
+    spawn_blocking(move || decode_to_f32_mono_16k(path)).await
+}
+
+/// A panic in one call should not make the transcriber unusable.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// -----------------------------------------------------------------------------
+// Transcriber
+// -----------------------------------------------------------------------------
+
+/// A [`Transcriber`] shared between tasks. Clones share the model; calls on it
+/// run one at a time.
+pub struct AsyncTranscriber<M: SpeechModel = ShoutModel> {
+    inner: Arc<Mutex<Transcriber<M>>>,
+}
+
+impl<M: SpeechModel> Clone for AsyncTranscriber<M> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl AsyncTranscriber<ShoutModel> {
+    /// Load the model in `model_dir`; see [`load_transcriber`].
+    pub async fn load(model_dir: impl Into<PathBuf>, device: Device) -> Result<Self> {
+        let model_dir = model_dir.into();
+        let transcriber = spawn_blocking(move || load_transcriber(&model_dir, &device)).await?;
+        Ok(Self::new(transcriber))
+    }
+}
+
+impl<M> AsyncTranscriber<M>
+where
+    M: SpeechModel + Send + 'static,
+{
+    pub fn new(transcriber: Transcriber<M>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(transcriber)),
+        }
+    }
+
+    /// Read or change settings. Waits while a transcription is running, so
+    /// keep `f` short.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Transcriber<M>) -> R) -> R {
+        f(&mut lock(&self.inner))
+    }
+
+    /// Decode an audio file and transcribe it.
+    pub async fn transcribe_file(&self, path: impl Into<PathBuf>) -> Result<Transcript> {
+        let path = path.into();
+        self.run(move |t| t.transcribe_file(path)).await
+    }
+
+    /// Transcribe 16 kHz mono samples of any length.
+    pub async fn transcribe_pcm(&self, pcm: Vec<f32>) -> Result<Transcript> {
+        self.run(move |t| t.transcribe_pcm(&pcm)).await
+    }
+
+    async fn run<R, F>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Transcriber<M>) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        spawn_blocking(move || f(&mut lock(&inner))).await
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Streaming
+// -----------------------------------------------------------------------------
+
+/// A [`StreamingSession`] whose decoding steps run on the blocking pool.
+/// Await each call before the next so samples stay in order.
+pub struct AsyncStream<T> {
+    session: Arc<Mutex<StreamingSession<T>>>,
+}
+
+impl<T> AsyncStream<T>
+where
+    T: WindowTranscriber + Send + 'static,
+{
+    pub fn new(session: StreamingSession<T>) -> Self {
+        Self {
+            session: Arc::new(Mutex::new(session)),
+        }
+    }
+
+    /// See [`StreamingSession::push`].
+    pub async fn push(&self, pcm: Vec<f32>) -> Result<Option<StreamUpdate>> {
+        let session = Arc::clone(&self.session);
+        spawn_blocking(move || lock(&session).push(&pcm)).await
+    }
+
+    /// Push the last samples and finish the stream; the update holds
+    /// everything that became final in both steps.
+    pub async fn finish(&self, tail: Vec<f32>) -> Result<StreamUpdate> {
+        let session = Arc::clone(&self.session);
+        spawn_blocking(move || {
+            let mut session = lock(&session);
+            let mut update = session.push(&tail)?.unwrap_or_default();
+            let last = session.finish()?;
+            update.finals.extend(last.finals);
+            update.partial = last.partial;
+            Ok(update)
+        })
+        .await
+    }
+
+    /// The session back, or `None` while a step whose future was dropped is
+    /// still running.
+    pub fn into_inner(self) -> Option<StreamingSession<T>> {
+        let mutex = Arc::try_unwrap(self.session).ok()?;
+        Some(mutex.into_inner().unwrap_or_else(|e| e.into_inner()))
+    }
+}
@@ -0,0 +1,446 @@
+//! NumPy `.npy` and `.npz` files of feature matrices, so features computed
+//! here can be inspected with `numpy.load` and features computed elsewhere
+//! can be read back for training.
+//!
+//! Matrices are written as little-endian float32 in C order, the layout
+//! `numpy.save` uses for `float32` arrays. Reading accepts float32 and
+//! float64 of either byte order and either memory order, converted to a
+//! float32 matrix in standard layout; anything else is refused with an error
+//! naming the file and what it holds. `.npz` archives must be stored
+//! uncompressed (`numpy.savez`, not `numpy.savez_compressed`).
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Write};
+use std::path::Path;
+
+use ndarray::{Array2, ArrayView2, ShapeBuilder};
+
+use crate::errors::{IoContext, Result, ShoutError};
+
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// What the header of a `.npy` file says about its array.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// NumPy type string, e.g. `<f4`.
+    pub descr: String,
+    pub fortran_order: bool,
+    pub shape: Vec<usize>,
+
+    /// Bytes before the data.
+    pub data_offset: usize,
+}
+
+impl Header {
+    /// Bytes per value, for the float types this module reads.
+    fn item_size(&self) -> Option<usize> {
+        match self.descr.as_str() {
+            "<f4" | ">f4" => Some(4),
+            "<f8" | ">f8" => Some(8),
+            _ => None,
+        }
+    }
+}
+
+/// Write `matrix` to `path` as a `.npy` file.
+pub fn save(path: &Path, matrix: ArrayView2<f32>) -> Result<()> {
+    let (rows, cols) = matrix.dim();
+    write_matrix(path, rows, cols, matrix.iter().copied())
+}
+
+/// Write `values`, `rows * cols` of them in row-major order, to `path` as a
+/// `.npy` matrix, without collecting them first.
+pub fn write_matrix<I>(path: &Path, rows: usize, cols: usize, values: I) -> Result<()>
+where
+    I: IntoIterator<Item = f32>,
+{
+    let file = File::create(path).io_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    write(&mut out, rows, cols, values)
+        .and_then(|()| out.flush().map_err(ShoutError::from))
+        .map_err(|e| e.context(path.display()))
+}
+
+/// Write a `.npy` matrix of `rows * cols` row-major `values` to `out`.
+pub fn write<W, I>(out: &mut W, rows: usize, cols: usize, values: I) -> Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = f32>,
+{
+    // Header: magic, version 1.0, length, then a dict padded so the data
+    // starts at a multiple of 64 bytes.
+    let mut header =
+        format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({rows}, {cols}), }}");
+    let unpadded = 10 + header.len() + 1;
+    header.extend(std::iter::repeat_n(
+        ' ',
+        unpadded.next_multiple_of(64) - unpadded,
+    ));
+    header.push('\n');
+    out.write_all(MAGIC)?;
+    out.write_all(&[1, 0])?;
+    out.write_all(&(header.len() as u16).to_le_bytes())?;
+    out.write_all(header.as_bytes())?;
+
+    let mut written = 0usize;
+    for v in values {
+        out.write_all(&v.to_le_bytes())?;
+        written += 1;
+    }
+    if written != rows * cols {
+        return Err(ShoutError::InvalidArgument(format!(
+            "{written} values for a {rows} x {cols} matrix"
+        )));
+    }
+    Ok(())
+}
+
+/// Read the `.npy` matrix at `path`.
+pub fn load(path: &Path) -> Result<Array2<f32>> {
+    let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    read(&mut BufReader::new(file)).map_err(|e| e.context(path.display()))
+}
+
+/// Read the header of the `.npy` file at `path`.
+pub fn load_header(path: &Path) -> Result<Header> {
+    let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    read_header(&mut BufReader::new(file)).map_err(|e| e.context(path.display()))
+}
+
+/// Read a `.npy` matrix from `reader`.
+pub fn read<R: Read>(reader: &mut R) -> Result<Array2<f32>> {
+    let header = read_header(reader)?;
+    let Some(item_size) = header.item_size() else {
+        return Err(ShoutError::UnsupportedFormat(format!(
+            "dtype '{}' is not float32 or float64",
+            header.descr
+        )));
+    };
+    let &[rows, cols] = header.shape.as_slice() else {
+        return Err(ShoutError::UnsupportedFormat(format!(
+            "expected a 2-D matrix, found shape {:?}",
+            header.shape
+        )));
+    };
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() != rows * cols * item_size {
+        return Err(ShoutError::Config(format!(
+            "{} bytes of data, but a {rows} x {cols} '{}' matrix has {}",
+            bytes.len(),
+            header.descr,
+            rows * cols * item_size
+        )));
+    }
+    let big_endian = header.descr.starts_with('>');
+    let values: Vec<f32> = bytes
+        .chunks_exact(item_size)
+        .map(|b| match (b.len(), big_endian) {
+            (4, false) => f32::from_le_bytes(b.try_into().unwrap()),
+            (4, true) => f32::from_be_bytes(b.try_into().unwrap()),
+            (_, false) => f64::from_le_bytes(b.try_into().unwrap()) as f32,
+            (_, true) => f64::from_be_bytes(b.try_into().unwrap()) as f32,
+        })
+        .collect();
+
+    let matrix = if header.fortran_order {
+        Array2::from_shape_vec((rows, cols).f(), values)
+            .map(|m| m.as_standard_layout().into_owned())
+    } else {
+        Array2::from_shape_vec((rows, cols), values)
+    };
+    matrix.map_err(|e| ShoutError::Config(e.to_string()))
+}
+
+/// Read the header of a `.npy` file from `reader`, leaving it at the data.
+pub fn read_header<R: Read>(reader: &mut R) -> Result<Header> {
+    let invalid = |what: &str| ShoutError::Config(format!("not a .npy file: {what}"));
+    let mut prefix = [0u8; 8];
+    reader
+        .read_exact(&mut prefix)
+        .map_err(|_| invalid("too short"))?;
+    if &prefix[..6] != MAGIC {
+        return Err(invalid("no NumPy magic"));
+    }
+    let (len, len_bytes) = match prefix[6] {
+        1 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            (u16::from_le_bytes(len) as usize, 2)
+        }
+        2 | 3 => {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len)?;
+            (u32::from_le_bytes(len) as usize, 4)
+        }
+        version => {
+            return Err(ShoutError::UnsupportedFormat(format!(
+                ".npy format version {version} is not supported"
+            )));
+        }
+    };
+    let mut dict = vec![0u8; len];
+    reader
+        .read_exact(&mut dict)
+        .map_err(|_| invalid("header cut short"))?;
+    let dict = String::from_utf8(dict).map_err(|_| invalid("header is not text"))?;
+
+    let value = |key: &str| dict_value(&dict, key);
+    let descr = value("descr")
+        .and_then(|v| v.strip_prefix('\''))
+        .and_then(|v| v.split_once('\''))
+        .ok_or_else(|| invalid("no descr"))?
+        .0
+        .to_string();
+    let fortran_order = match value("fortran_order") {
+        Some(v) if v.starts_with("True") => true,
+        Some(v) if v.starts_with("False") => false,
+        _ => return Err(invalid("no fortran_order")),
+    };
+    let shape = value("shape")
+        .and_then(|v| v.strip_prefix('('))
+        .and_then(|v| v.split_once(')'))
+        .ok_or_else(|| invalid("no shape"))?
+        .0
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| d.parse().map_err(|_| invalid("bad shape")))
+        .collect::<Result<_>>()?;
+    Ok(Header {
+        descr,
+        fortran_order,
+        shape,
+        data_offset: 8 + len_bytes + len,
+    })
+}
+
+/// What follows `'key':` in the header's dict.
+fn dict_value<'a>(dict: &'a str, key: &str) -> Option<&'a str> {
+    let (_, rest) = dict.split_once(&format!("'{key}':"))?;
+    Some(rest.trim_start())
+}
+
+/// Write `arrays` to `path` as an uncompressed `.npz` archive, each under its
+/// name (`numpy.load(path)[name]`).
+pub fn save_npz(path: &Path, arrays: &[(&str, ArrayView2<f32>)]) -> Result<()> {
+    let file = File::create(path).io_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    write_npz(&mut out, arrays)
+        .and_then(|()| out.flush().map_err(ShoutError::from))
+        .map_err(|e| e.context(path.display()))
+}
+
+fn write_npz<W: Write>(out: &mut W, arrays: &[(&str, ArrayView2<f32>)]) -> Result<()> {
+    let too_large = || ShoutError::InvalidArgument("the .npz archive exceeds 4 GiB".into());
+    let mut central = Vec::new();
+    let mut offset = 0u32;
+    for (name, matrix) in arrays {
+        let name = format!("{name}.npy");
+        let (rows, cols) = matrix.dim();
+        let mut data = Vec::new();
+        write(&mut data, rows, cols, matrix.iter().copied())?;
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let crc = crc32(&data);
+
+        // Local header, then the data; stored, dated 1980-01-01.
+        let mut local = Vec::with_capacity(30 + name.len());
+        local.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        local.extend_from_slice(&entry_fields(crc, size, name.len()));
+        local.extend_from_slice(&0u16.to_le_bytes());
+        local.extend_from_slice(name.as_bytes());
+        out.write_all(&local)?;
+        out.write_all(&data)?;
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes());
+        central.extend_from_slice(&entry_fields(crc, size, name.len()));
+        // Extra field, comment, disk, internal and external attributes.
+        central.extend_from_slice(&[0; 12]);
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+
+        let entry_len = local.len() as u64 + data.len() as u64;
+        offset = u32::try_from(offset as u64 + entry_len).map_err(|_| too_large())?;
+    }
+
+    let count = u16::try_from(arrays.len())
+        .map_err(|_| ShoutError::InvalidArgument("too many arrays for one .npz".into()))?;
+    out.write_all(&central)?;
+    out.write_all(&0x0605_4b50u32.to_le_bytes())?;
+    out.write_all(&[0; 4])?;
+    out.write_all(&count.to_le_bytes())?;
+    out.write_all(&count.to_le_bytes())?;
+    out.write_all(&(central.len() as u32).to_le_bytes())?;
+    out.write_all(&offset.to_le_bytes())?;
+    out.write_all(&0u16.to_le_bytes())?;
+    Ok(())
+}
+
+/// The fields local and central zip headers share, from the version needed
+/// to the file name's length.
+fn entry_fields(crc: u32, size: u32, name_len: usize) -> Vec<u8> {
+    let mut fields = Vec::with_capacity(24);
+    fields.extend_from_slice(&20u16.to_le_bytes()); // version needed: 2.0
+    fields.extend_from_slice(&0u16.to_le_bytes()); // flags
+    fields.extend_from_slice(&0u16.to_le_bytes()); // stored
+    fields.extend_from_slice(&0u16.to_le_bytes()); // time
+    fields.extend_from_slice(&0x21u16.to_le_bytes()); // date
+    fields.extend_from_slice(&crc.to_le_bytes());
+    fields.extend_from_slice(&size.to_le_bytes());
+    fields.extend_from_slice(&size.to_le_bytes());
+    fields.extend_from_slice(&(name_len as u16).to_le_bytes());
+    fields
+}
+
+/// Read every matrix of the `.npz` archive at `path`, with its name, in
+/// archive order.
+pub fn load_npz(path: &Path) -> Result<Vec<(String, Array2<f32>)>> {
+    let bytes = std::fs::read(path).io_context(|| format!("Failed to read {}", path.display()))?;
+    read_npz(&bytes).map_err(|e| e.context(path.display()))
+}
+
+fn read_npz(bytes: &[u8]) -> Result<Vec<(String, Array2<f32>)>> {
+    let invalid = |what: &str| ShoutError::Config(format!("not a .npz archive: {what}"));
+    let u16_at = |at: usize| {
+        bytes
+            .get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u32_at = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    };
+
+    // The end of central directory record is the last thing in the file,
+    // followed by a comment of at most 64 KiB.
+    let search_from = bytes.len().saturating_sub(22 + 0xffff);
+    let end = (search_from..bytes.len().saturating_sub(21))
+        .rev()
+        .find(|&at| u32_at(at) == Some(0x0605_4b50))
+        .ok_or_else(|| invalid("no end of central directory"))?;
+    let count = u16_at(end + 10).ok_or_else(|| invalid("truncated"))? as usize;
+    if u32_at(end + 16) == Some(u32::MAX) {
+        return Err(ShoutError::UnsupportedFormat(
+            "zip64 .npz archives are not supported".into(),
+        ));
+    }
+    let mut at = u32_at(end + 16).ok_or_else(|| invalid("truncated"))? as usize;
+
+    let mut arrays = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(at) != Some(0x0201_4b50) {
+            return Err(invalid("bad central directory"));
+        }
+        let field = |offset: usize| u16_at(at + offset).ok_or_else(|| invalid("truncated"));
+        let method = field(10)?;
+        let crc = u32_at(at + 16).ok_or_else(|| invalid("truncated"))?;
+        let size = u32_at(at + 20).ok_or_else(|| invalid("truncated"))? as usize;
+        let (name_len, extra_len, comment_len) = (field(28)?, field(30)?, field(32)?);
+        let local = u32_at(at + 42).ok_or_else(|| invalid("truncated"))? as usize;
+        let name = bytes
+            .get(at + 46..at + 46 + name_len as usize)
+            .ok_or_else(|| invalid("truncated"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        at += 46 + name_len as usize + extra_len as usize + comment_len as usize;
+
+        if method != 0 {
+            return Err(ShoutError::UnsupportedFormat(format!(
+                "{name} is compressed; save with numpy.savez, not numpy.savez_compressed"
+            )));
+        }
+        let local_name = u16_at(local + 26).ok_or_else(|| invalid("truncated"))? as usize;
+        let local_extra = u16_at(local + 28).ok_or_else(|| invalid("truncated"))? as usize;
+        let start = local + 30 + local_name + local_extra;
+        let data = bytes
+            .get(start..start + size)
+            .ok_or_else(|| invalid("truncated"))?;
+        if crc32(data) != crc {
+            return Err(ShoutError::Config(format!("{name}: checksum mismatch")));
+        }
+        let matrix = read(&mut Cursor::new(data)).map_err(|e| e.context(&name))?;
+        let name = name.strip_suffix(".npy").unwrap_or(&name).to_string();
+        arrays.push((name, matrix));
+    }
+    Ok(arrays)
+}
+
+/// CRC-32 (IEEE), as zip archives use.
+fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut c = i as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 {
+                    0xedb8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                k += 1;
+            }
+            table[i] = c;
+            i += 1;
+        }
+        table
+    };
+    !bytes.iter().fold(!0u32, |c, &b| {
+        TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn round_trips_npy_and_npz() {
+        let matrix = array![[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        let mut bytes = Vec::new();
+        write(&mut bytes, 2, 3, matrix.iter().copied()).unwrap();
+        assert_eq!(
+            read_header(&mut bytes.as_slice()).unwrap().data_offset % 64,
+            0
+        );
+        assert_eq!(read(&mut bytes.as_slice()).unwrap(), matrix);
+
+        let mut archive = Vec::new();
+        write_npz(&mut archive, &[("a", matrix.view()), ("b", matrix.t())]).unwrap();
+        let arrays = read_npz(&archive).unwrap();
+        assert_eq!(arrays[0], ("a".to_string(), matrix.clone()));
+        assert_eq!(arrays[1], ("b".to_string(), matrix.t().to_owned()));
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn reads_fortran_order_float64_and_refuses_other_dtypes() {
+        let npy = |descr: &str, fortran: &str, data: &[u8]| {
+            let header =
+                format!("{{'descr': '{descr}', 'fortran_order': {fortran}, 'shape': (2, 2), }}\n");
+            let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+            bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(header.as_bytes());
+            bytes.extend_from_slice(data);
+            bytes
+        };
+        let column_major: Vec<u8> = [1.0f64, 3.0, 2.0, 4.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let matrix = read(&mut npy("<f8", "True", &column_major).as_slice()).unwrap();
+        assert_eq!(matrix, array![[1.0, 2.0], [3.0, 4.0]]);
+
+        let error = read(&mut npy("<i8", "False", &column_major).as_slice()).unwrap_err();
+        assert!(
+            error.to_string().contains("'<i8' is not float32"),
+            "{error}"
+        );
+        let error = read(&mut npy("<f4", "False", &column_major).as_slice()).unwrap_err();
+        assert!(error.to_string().contains("bytes of data"), "{error}");
+    }
+}
@@ -0,0 +1,124 @@
+//! NIST CTM (time-marked conversation) output, one word per line:
+//! `<file> <channel> <start> <duration> <word> [<confidence>]`, times in seconds.
+//!
+//! Word times come from alignment (see [`crate::alignment`]). Segments
+//! without aligned words, e.g. with `Transcriber::alignment` off, are written
+//! with their duration split evenly between their words: those times are
+//! only approximate, and a warning says so.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::errors::Result;
+use crate::transcript::{Transcript, Word};
+
+/// Write `transcript` as CTM lines for recording `file_id`, channel `channel`.
+pub fn write_ctm<W: Write>(
+    mut w: W,
+    transcript: &Transcript,
+    file_id: &str,
+    channel: &str,
+) -> Result<()> {
+    let mut spread = 0usize;
+    for segment in &transcript.segments {
+        let words = if segment.words.is_empty() {
+            spread += 1;
+            spread_words(&segment.text, segment.start_ms, segment.end_ms)
+        } else {
+            segment.words.clone()
+        };
+        for word in words {
+            let text = word.text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            write!(
+                w,
+                "{file_id} {channel} {:.3} {:.3} {text}",
+                word.start_ms as f64 / 1000.0,
+                word.duration_ms() as f64 / 1000.0
+            )?;
+            if let Some(confidence) = word.confidence {
+                write!(w, " {confidence:.4}")?;
+            }
+            writeln!(w)?;
+        }
+    }
+    if spread > 0 {
+        tracing::warn!(
+            file_id,
+            segments = spread,
+            "CTM word times of unaligned segments are approximate (segment time split evenly)"
+        );
+    }
+    Ok(())
+}
+
+/// Recording id of a transcript: the audio file name without extension.
+pub fn file_id(transcript: &Transcript) -> String {
+    transcript
+        .metadata
+        .audio_path
+        .as_deref()
+        .and_then(|p| Path::new(p).file_stem())
+        .map_or_else(|| "audio".to_string(), |s| s.to_string_lossy().into_owned())
+}
+
+/// Words of an unaligned segment, with its duration split evenly between
+/// them. Not real word timings: a stand-in so every word gets a line.
+fn spread_words(text: &str, start_ms: u64, end_ms: u64) -> Vec<Word> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let step = end_ms.saturating_sub(start_ms) / tokens.len().max(1) as u64;
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(i, t)| Word {
+            text: t.to_string(),
+            start_ms: start_ms + i as u64 * step,
+            end_ms: start_ms + (i as u64 + 1) * step,
+            confidence: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::Segment;
+
+    #[test]
+    fn writes_aligned_and_unaligned_segments() {
+        let transcript = Transcript {
+            segments: vec![
+                Segment {
+                    start_ms: 0,
+                    end_ms: 1000,
+                    text: "Hallo Welt".into(),
+                    words: vec![Word {
+                        text: " Hallo".into(),
+                        start_ms: 120,
+                        end_ms: 480,
+                        confidence: Some(0.9),
+                    }],
+                    ..Default::default()
+                },
+                Segment {
+                    start_ms: 2000,
+                    end_ms: 3000,
+                    text: "wie geht's".into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        write_ctm(&mut out, &transcript, "rec1", "1").unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "rec1 1 0.120 0.360 Hallo 0.9000\n\
+             rec1 1 2.000 0.500 wie\n\
+             rec1 1 2.500 0.500 geht's\n"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Structured JSON output for programmatic consumers.
+//!
+//! The document mirrors [`Transcript`] and adds the derived scores
+//! (`avg_logprob`, `confidence`) so consumers don't have to recompute them.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::errors::Result;
+use crate::transcript::{Segment, TokenScore, Transcript, TranscriptMetadata, Word};
+
+#[derive(Serialize)]
+struct JsonTranscript<'a> {
+    text: String,
+    language: Option<&'a str>,
+    confidence: Option<f32>,
+    metadata: &'a TranscriptMetadata,
+    segments: Vec<JsonSegment<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonSegment<'a> {
+    id: usize,
+    start_ms: u64,
+    end_ms: u64,
+    text: &'a str,
+    avg_logprob: Option<f32>,
+    confidence: Option<f32>,
+    words: &'a [Word],
+    tokens: &'a [TokenScore],
+}
+
+impl<'a> JsonSegment<'a> {
+    fn new(id: usize, seg: &'a Segment) -> Self {
+        Self {
+            id,
+            start_ms: seg.start_ms,
+            end_ms: seg.end_ms,
+            text: seg.text.trim(),
+            avg_logprob: seg.avg_logprob(),
+            confidence: seg.confidence(),
+            words: &seg.words,
+            tokens: &seg.tokens,
+        }
+    }
+}
+
+/// Write `transcript` as a single pretty-printed JSON document.
+pub fn write_json<W: Write>(mut w: W, transcript: &Transcript) -> Result<()> {
+    let doc = JsonTranscript {
+        text: transcript.text(),
+        language: transcript.language.as_deref(),
+        confidence: transcript.confidence(),
+        metadata: &transcript.metadata,
+        segments: transcript
+            .segments
+            .iter()
+            .enumerate()
+            .map(|(i, s)| JsonSegment::new(i, s))
+            .collect(),
+    };
+
+    serde_json::to_writer_pretty(&mut w, &doc)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_scores_and_metadata() {
+        let transcript = Transcript {
+            language: Some("de".into()),
+            segments: vec![Segment {
+                start_ms: 0,
+                end_ms: 1000,
+                text: " Hallo".into(),
+                tokens: vec![TokenScore {
+                    id: 7,
+                    text: " Hallo".into(),
+                    logprob: 0.0,
+                }],
+                ..Default::default()
+            }],
+            metadata: TranscriptMetadata {
+                model: Some("tiny".into()),
+                ..Default::default()
+            },
+        };
+
+        let mut out = Vec::new();
+        write_json(&mut out, &transcript).unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+        assert_eq!(v["text"], "Hallo");
+        assert_eq!(v["language"], "de");
+        assert_eq!(v["confidence"], 1.0);
+        assert_eq!(v["metadata"]["model"], "tiny");
+        assert_eq!(v["segments"][0]["tokens"][0]["id"], 7);
+    }
+}
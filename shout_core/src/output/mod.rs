@@ -0,0 +1,86 @@
+pub mod ctm;
+pub mod json;
+pub mod subtitles;
+
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::errors::{Result, ShoutError};
+use crate::transcript::Transcript;
+
+/// Output format of a transcription, as selected with `transcribe --format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Plain text, one segment per line.
+    #[default]
+    Text,
+    Srt,
+    Vtt,
+    /// Structured JSON with words, token log-probs, confidences and metadata.
+    Json,
+    /// NIST CTM, one timed word per line.
+    Ctm,
+}
+
+impl OutputFormat {
+    /// File extension conventionally used for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Srt => "srt",
+            OutputFormat::Vtt => "vtt",
+            OutputFormat::Json => "json",
+            OutputFormat::Ctm => "ctm",
+        }
+    }
+
+    /// MIME type for serving this format over HTTP.
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "text/plain; charset=utf-8",
+            OutputFormat::Srt => "application/x-subrip; charset=utf-8",
+            OutputFormat::Vtt => "text/vtt; charset=utf-8",
+            OutputFormat::Json => "application/json",
+            OutputFormat::Ctm => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = ShoutError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "txt" | "text" => Ok(OutputFormat::Text),
+            "srt" => Ok(OutputFormat::Srt),
+            "vtt" | "webvtt" => Ok(OutputFormat::Vtt),
+            "json" => Ok(OutputFormat::Json),
+            "ctm" => Ok(OutputFormat::Ctm),
+            other => Err(ShoutError::InvalidArgument(format!(
+                "unknown output format '{other}' (expected txt, srt, vtt, json or ctm)"
+            ))),
+        }
+    }
+}
+
+/// Render `transcript` in the given format.
+pub fn write_transcript<W: Write>(
+    mut w: W,
+    format: OutputFormat,
+    transcript: &Transcript,
+) -> Result<()> {
+    let opts = subtitles::SubtitleOptions::default();
+    let segments = &transcript.segments;
+    match format {
+        OutputFormat::Text => {
+            for seg in segments {
+                writeln!(w, "{}", seg.text.trim())?;
+            }
+            Ok(())
+        }
+        OutputFormat::Srt => subtitles::write_srt(w, segments, &opts),
+        OutputFormat::Vtt => subtitles::write_vtt(w, segments, &opts),
+        OutputFormat::Json => json::write_json(w, transcript),
+        OutputFormat::Ctm => ctm::write_ctm(w, transcript, &ctm::file_id(transcript), "1"),
+    }
+}
@@ -0,0 +1,257 @@
+//! SRT and WebVTT rendering of timestamped segments.
+
+use std::io::Write;
+
+use crate::errors::Result;
+use crate::transcript::{Segment, Word};
+
+#[derive(Debug, Clone)]
+pub struct SubtitleOptions {
+    /// Maximum characters per rendered line (42 is the usual broadcast limit).
+    pub max_line_chars: usize,
+
+    /// Maximum lines per cue. Longer cues are split into several cues.
+    pub max_lines: usize,
+
+    /// Cues shorter than this are merged with the following one when possible.
+    pub min_cue_ms: u64,
+
+    /// Never merge across a pause longer than this.
+    pub max_merge_gap_ms: u64,
+}
+
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        Self {
+            max_line_chars: 42,
+            max_lines: 2,
+            min_cue_ms: 1000,
+            max_merge_gap_ms: 500,
+        }
+    }
+}
+
+/// One subtitle cue after merging/splitting, with its lines already wrapped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub lines: Vec<String>,
+}
+
+/// Turn segments into cues: merge very short neighbours, then split anything that
+/// does not fit into `max_lines` wrapped lines.
+pub fn build_cues(segments: &[Segment], opts: &SubtitleOptions) -> Vec<Cue> {
+    let max_chars = opts.max_line_chars * opts.max_lines.max(1);
+
+    // -------------------------
+    // 1) Merge short cues
+    // -------------------------
+    let mut merged: Vec<Segment> = Vec::new();
+    for seg in segments.iter().filter(|s| !s.text.trim().is_empty()) {
+        if let Some(prev) = merged.last_mut() {
+            let gap = seg.start_ms.saturating_sub(prev.end_ms);
+            let combined_len =
+                prev.text.trim().chars().count() + 1 + seg.text.trim().chars().count();
+
+            if prev.duration_ms() < opts.min_cue_ms
+                && gap <= opts.max_merge_gap_ms
+                && combined_len <= max_chars
+            {
+                prev.text = format!("{} {}", prev.text.trim(), seg.text.trim());
+                prev.end_ms = seg.end_ms;
+                prev.words.extend(seg.words.iter().cloned());
+                continue;
+            }
+        }
+        merged.push(seg.clone());
+    }
+
+    // -------------------------
+    // 2) Wrap and split long cues
+    // -------------------------
+    let mut cues = Vec::new();
+    for seg in &merged {
+        let lines = wrap_text(seg.text.trim(), opts.max_line_chars);
+        if lines.len() <= opts.max_lines.max(1) {
+            cues.push(Cue {
+                start_ms: seg.start_ms,
+                end_ms: seg.end_ms,
+                lines,
+            });
+            continue;
+        }
+
+        let per_cue = opts.max_lines.max(1);
+        for (i, chunk) in lines.chunks(per_cue).enumerate() {
+            let (start_ms, end_ms) = chunk_times(seg, &lines, i * per_cue, chunk.len());
+            cues.push(Cue {
+                start_ms,
+                end_ms,
+                lines: chunk.to_vec(),
+            });
+        }
+    }
+
+    cues
+}
+
+/// Time span for `n_lines` lines starting at `first_line` of a split segment: taken
+/// from the word timings when the segment has been aligned, otherwise interpolated
+/// by character count.
+fn chunk_times(seg: &Segment, lines: &[String], first_line: usize, n_lines: usize) -> (u64, u64) {
+    let before = &lines[..first_line];
+    let chunk = &lines[first_line..first_line + n_lines];
+
+    let words_before: usize = before.iter().map(|l| l.split_whitespace().count()).sum();
+    let words_in_chunk: usize = chunk.iter().map(|l| l.split_whitespace().count()).sum();
+
+    if !seg.words.is_empty() && seg.words.len() == seg.text.split_whitespace().count() {
+        let words: &[Word] = &seg.words[words_before..words_before + words_in_chunk];
+        if let (Some(first), Some(last)) = (words.first(), words.last()) {
+            return (first.start_ms, last.end_ms);
+        }
+    }
+
+    let char_count = |ls: &[String]| ls.iter().map(|l| l.chars().count()).sum::<usize>() as u64;
+    let total = char_count(lines).max(1);
+    let chars_before = char_count(before);
+    let chunk_chars = char_count(chunk);
+
+    let dur = seg.duration_ms();
+    let start = seg.start_ms + dur * chars_before / total;
+    let end = seg.start_ms + dur * (chars_before + chunk_chars) / total;
+    (start, end)
+}
+
+/// Greedy word wrap. Words longer than `max_chars` get a line of their own.
+pub fn wrap_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let needed = if current.is_empty() {
+            word.chars().count()
+        } else {
+            current.chars().count() + 1 + word.chars().count()
+        };
+
+        if needed > max_chars && !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// `HH:MM:SS<sep>mmm` — SRT uses `,`, WebVTT uses `.`.
+pub fn format_timestamp(ms: u64, sep: char) -> String {
+    let hours = ms / 3_600_000;
+    let minutes = (ms / 60_000) % 60;
+    let seconds = (ms / 1000) % 60;
+    let millis = ms % 1000;
+    format!("{hours:02}:{minutes:02}:{seconds:02}{sep}{millis:03}")
+}
+
+/// SRT has no escaping; only `-->` needs neutralizing so it is not read as a timing line.
+fn escape_srt(line: &str) -> String {
+    line.replace("-->", "->")
+}
+
+/// WebVTT cue text is HTML-like: `&`, `<` and `>` must be escaped (which also rules out `-->`).
+fn escape_vtt(line: &str) -> String {
+    line.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub fn write_srt<W: Write>(mut w: W, segments: &[Segment], opts: &SubtitleOptions) -> Result<()> {
+    for (i, cue) in build_cues(segments, opts).iter().enumerate() {
+        writeln!(w, "{}", i + 1)?;
+        writeln!(
+            w,
+            "{} --> {}",
+            format_timestamp(cue.start_ms, ','),
+            format_timestamp(cue.end_ms, ',')
+        )?;
+        for line in &cue.lines {
+            writeln!(w, "{}", escape_srt(line))?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+pub fn write_vtt<W: Write>(mut w: W, segments: &[Segment], opts: &SubtitleOptions) -> Result<()> {
+    writeln!(w, "WEBVTT")?;
+    writeln!(w)?;
+    for cue in build_cues(segments, opts) {
+        writeln!(
+            w,
+            "{} --> {}",
+            format_timestamp(cue.start_ms, '.'),
+            format_timestamp(cue.end_ms, '.')
+        )?;
+        for line in &cue.lines {
+            writeln!(w, "{}", escape_vtt(line))?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(start_ms: u64, end_ms: u64, text: &str) -> Segment {
+        Segment {
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(format_timestamp(3_723_004, ','), "01:02:03,004");
+        assert_eq!(format_timestamp(59_999, '.'), "00:00:59.999");
+    }
+
+    #[test]
+    fn writes_srt_and_merges_short_cues() {
+        let segments = vec![seg(0, 400, "Hallo"), seg(500, 2000, "wie geht's?")];
+        let mut out = Vec::new();
+        write_srt(&mut out, &segments, &SubtitleOptions::default()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1\n00:00:00,000 --> 00:00:02,000\nHallo wie geht's?\n\n"
+        );
+    }
+
+    #[test]
+    fn escapes_vtt_and_splits_long_cues() {
+        let opts = SubtitleOptions {
+            max_line_chars: 10,
+            max_lines: 1,
+            ..Default::default()
+        };
+        let segments = vec![seg(0, 2000, "a<b & c>d eins zwei")];
+        let mut out = Vec::new();
+        write_vtt(&mut out, &segments, &opts).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("WEBVTT\n\n"));
+        assert!(out.contains("a&lt;b &amp; c&gt;d\n"));
+        assert_eq!(out.matches(" --> ").count(), 2);
+    }
+}
@@ -0,0 +1,646 @@
+//! Many-file transcription as a staged pipeline.
+//!
+//! Inputs flow through four stages connected by bounded channels, so CPU
+//! preprocessing for later inputs overlaps with model compute for earlier ones:
+//!
+//! 1. `jobs` decode workers read and resample the audio,
+//! 2. `feature_jobs` feature workers compute the features of inputs that fit
+//!    one model window; longer inputs are cut into windows by the model stage,
+//!    which computes their features window by window,
+//! 3. the calling thread owns the model and transcribes batches of up to
+//!    `batch_size` inputs,
+//! 4. a writer thread fills in metadata, updates the results cache and writes
+//!    one output file per input.
+//!
+//! With a [`ResultCache`], inputs transcribed before with the same model and
+//! settings skip straight from the decode stage to the writer.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::audio::decoder::decode_cancellable;
+use crate::audio::mel::MelSpec;
+use crate::cache::{ResultCache, hash_file};
+use crate::cancel::{CancelToken, Stage};
+use crate::errors::{IoContext, Result, ShoutError};
+use crate::features::{AudioFrontEnd, LogMel, SAMPLE_RATE};
+use crate::output::{OutputFormat, write_transcript};
+use crate::transcript::Transcript;
+
+/// One decoded input, ready for the model.
+pub struct PreparedAudio {
+    /// Position in the inputs of the batch run.
+    pub index: usize,
+    pub path: PathBuf,
+
+    /// 16 kHz mono samples.
+    pub pcm: Vec<f32>,
+
+    /// Features of the whole input, if it fits one window (`window_ms`).
+    pub mel: Option<MelSpec>,
+
+    /// Where the transcript goes in the results cache, if one is used.
+    pub cache_key: Option<String>,
+}
+
+/// Output of the decode stage.
+enum Decoded {
+    Audio {
+        pcm: Vec<f32>,
+        cache_key: Option<String>,
+    },
+    Cached(Transcript),
+}
+
+/// Output of the feature stage.
+enum Prepared {
+    Audio(PreparedAudio),
+    Cached(Transcript),
+}
+
+/// What the model stage hands to the writer.
+struct Finished {
+    index: usize,
+    result: Result<Transcript>,
+
+    /// Known for freshly decoded audio; cached transcripts carry their own.
+    duration_ms: Option<u64>,
+    cache_key: Option<String>,
+    cached: bool,
+}
+
+/// A model that can transcribe several prepared inputs in one forward pass.
+pub trait BatchTranscriber {
+    /// One result per input, in input order.
+    fn transcribe_batch(&mut self, batch: &[PreparedAudio]) -> Vec<Result<Transcript>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Number of audio decode worker threads.
+    pub jobs: usize,
+
+    /// Number of feature extraction worker threads.
+    pub feature_jobs: usize,
+
+    /// Maximum inputs per model call.
+    pub batch_size: usize,
+
+    /// Inputs buffered between two stages (bounds memory use).
+    pub queue_depth: usize,
+
+    /// Computes `PreparedAudio::mel`; the model's front end.
+    pub front_end: Arc<dyn AudioFrontEnd>,
+
+    /// Length of one model window; only inputs up to this long get their
+    /// features from the feature stage.
+    pub window_ms: u64,
+    pub format: OutputFormat,
+    pub out_dir: PathBuf,
+
+    /// Reuse transcripts of inputs seen before.
+    pub cache: Option<ResultCache>,
+
+    /// Model fingerprint and decoding settings; part of every cache key.
+    pub cache_context: String,
+
+    /// Checked by the decode and feature stages; the model stage checks the
+    /// transcriber's own token. Once it fires, the remaining inputs fail.
+    pub cancel: CancelToken,
+
+    /// Time limit for decoding one input.
+    pub decode_timeout: Option<Duration>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            jobs: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            feature_jobs: 2,
+            batch_size: 8,
+            queue_depth: 16,
+            front_end: Arc::new(LogMel::new(80)),
+            window_ms: 30_000,
+            format: OutputFormat::Text,
+            out_dir: PathBuf::from("transcripts"),
+            cache: None,
+            cache_context: String::new(),
+            cancel: CancelToken::default(),
+            decode_timeout: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub succeeded: usize,
+
+    /// Of the succeeded inputs, those served from the results cache.
+    pub cached: usize,
+    pub failed: Vec<(PathBuf, String)>,
+
+    /// Total duration of successfully decoded audio.
+    pub audio_seconds: f64,
+    pub wall_time: Duration,
+}
+
+impl BatchSummary {
+    /// Real-time factor: processing time divided by audio duration (lower is faster).
+    pub fn rtf(&self) -> f64 {
+        if self.audio_seconds > 0.0 {
+            self.wall_time.as_secs_f64() / self.audio_seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Transcribed: {} ({} cached)",
+            self.succeeded, self.cached
+        )?;
+        writeln!(f, "Failed: {}", self.failed.len())?;
+        writeln!(
+            f,
+            "Audio: {:.1} s in {:.1} s (RTF {:.3})",
+            self.audio_seconds,
+            self.wall_time.as_secs_f64(),
+            self.rtf()
+        )?;
+        for (path, err) in &self.failed {
+            writeln!(f, "  {}: {}", path.display(), err)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct ManifestEntry {
+    audio_path: String,
+}
+
+/// Audio paths from a JSONL manifest (one `{"audio_path": ...}` object per line).
+pub fn read_manifest_paths<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).io_context(|| format!("Failed to open manifest: {}", path.display()))?;
+
+    let mut out = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let line = line.strip_prefix('\u{feff}').unwrap_or(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: ManifestEntry = serde_json::from_str(line).map_err(|e| {
+            ShoutError::Config(format!(
+                "{}:{}: invalid manifest line: {e}",
+                path.display(),
+                i + 1
+            ))
+        })?;
+        out.push(PathBuf::from(entry.audio_path));
+    }
+    Ok(out)
+}
+
+/// Output file of every input: `<out_dir>/<file stem>.<format extension>`.
+/// Inputs whose file stem is shared with another input get their (1-based)
+/// position appended, `<file stem>-<n>.<format extension>`, so that no two
+/// inputs write the same file.
+pub fn output_paths(inputs: &[PathBuf], opts: &BatchOptions) -> Vec<PathBuf> {
+    let stem = |input: &PathBuf| -> OsString {
+        input
+            .file_stem()
+            .map(|s| s.to_os_string())
+            .unwrap_or_else(|| "output".into())
+    };
+    let mut counts: HashMap<OsString, usize> = HashMap::new();
+    for input in inputs {
+        *counts.entry(stem(input)).or_default() += 1;
+    }
+
+    inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            let mut name = stem(input);
+            if counts[&name] > 1 {
+                name.push(format!("-{}", i + 1));
+            }
+            name.push(".");
+            name.push(opts.format.extension());
+            opts.out_dir.join(name)
+        })
+        .collect()
+}
+
+/// Transcribe every input, writing one output file each.
+pub fn run_batch<T: BatchTranscriber + ?Sized>(
+    inputs: &[PathBuf],
+    transcriber: &mut T,
+    opts: &BatchOptions,
+) -> Result<BatchSummary> {
+    std::fs::create_dir_all(&opts.out_dir)
+        .io_context(|| format!("Failed to create output dir: {}", opts.out_dir.display()))?;
+
+    let started = Instant::now();
+    let depth = opts.queue_depth.max(1);
+    let next = AtomicUsize::new(0);
+    let outputs = output_paths(inputs, opts);
+    // Shared by the feature workers, so it must outlive the scope.
+    let (decoded_tx, decoded_rx) = mpsc::sync_channel::<(usize, Result<Decoded>)>(depth);
+    let decoded_rx = Mutex::new(decoded_rx);
+
+    let mut summary = std::thread::scope(|scope| {
+        let (prepared_tx, prepared_rx) = mpsc::sync_channel::<(usize, Result<Prepared>)>(depth);
+        let (finished_tx, finished_rx) = mpsc::sync_channel::<Finished>(depth);
+
+        // -------------------------
+        // Stage 1: decode workers
+        // -------------------------
+        for _ in 0..opts.jobs.max(1) {
+            let tx = decoded_tx.clone();
+            let next = &next;
+            scope.spawn(move || {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = inputs.get(i) else { break };
+                    if tx.send((i, decode(path, opts))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(decoded_tx);
+
+        // -------------------------
+        // Stage 2: feature workers
+        // -------------------------
+        for _ in 0..opts.feature_jobs.max(1) {
+            let tx = prepared_tx.clone();
+            let rx = &decoded_rx;
+            scope.spawn(move || feature_worker(rx, tx, inputs, opts));
+        }
+        drop(prepared_tx);
+
+        // -------------------------
+        // Stage 4: writer
+        // -------------------------
+        let outputs = &outputs;
+        let writer = scope.spawn(move || write_outputs(finished_rx, inputs, outputs, opts));
+
+        // -------------------------
+        // Stage 3: model (this thread)
+        // -------------------------
+        run_model(
+            transcriber,
+            prepared_rx,
+            &finished_tx,
+            opts.batch_size.max(1),
+        );
+        drop(finished_tx);
+
+        writer.join().expect("batch writer panicked")
+    });
+
+    summary.wall_time = started.elapsed();
+    Ok(summary)
+}
+
+fn decode(path: &Path, opts: &BatchOptions) -> Result<Decoded> {
+    let mut cache_key = None;
+    if let Some(cache) = &opts.cache {
+        let key = ResultCache::key(&hash_file(path)?, &opts.cache_context);
+        if let Some(transcript) = cache.get(&key) {
+            return Ok(Decoded::Cached(transcript));
+        }
+        cache_key = Some(key);
+    }
+
+    let pcm = decode_cancellable(path, &opts.cancel.with_timeout(opts.decode_timeout))?;
+    Ok(Decoded::Audio { pcm, cache_key })
+}
+
+fn feature_worker(
+    rx: &Mutex<Receiver<(usize, Result<Decoded>)>>,
+    tx: SyncSender<(usize, Result<Prepared>)>,
+    inputs: &[PathBuf],
+    opts: &BatchOptions,
+) {
+    let max_samples = (opts.window_ms * SAMPLE_RATE as u64 / 1000) as usize;
+    loop {
+        // Holding the lock only while receiving lets the other workers compute.
+        let next = rx.lock().unwrap().recv();
+        let Ok((index, decoded)) = next else { break };
+        let path = &inputs[index];
+
+        let prepared = decoded.and_then(|decoded| match decoded {
+            Decoded::Audio { pcm, cache_key } => {
+                opts.cancel.check(Stage::Features)?;
+                let mel = if pcm.len() <= max_samples {
+                    let span = tracing::debug_span!("features", path = %path.display());
+                    Some(span.in_scope(|| opts.front_end.features(&pcm))?)
+                } else {
+                    None
+                };
+                Ok(Prepared::Audio(PreparedAudio {
+                    index,
+                    path: path.clone(),
+                    pcm,
+                    mel,
+                    cache_key,
+                }))
+            }
+            Decoded::Cached(transcript) => Ok(Prepared::Cached(transcript)),
+        });
+        if tx.send((index, prepared)).is_err() {
+            break;
+        }
+    }
+}
+
+fn run_model<T: BatchTranscriber + ?Sized>(
+    transcriber: &mut T,
+    rx: Receiver<(usize, Result<Prepared>)>,
+    tx: &SyncSender<Finished>,
+    batch_size: usize,
+) {
+    let mut batch: Vec<PreparedAudio> = Vec::with_capacity(batch_size);
+    let mut receiving = true;
+
+    while receiving || !batch.is_empty() {
+        if receiving {
+            // Block for the first item, then take whatever else is already waiting;
+            // the model never stalls waiting for a full batch.
+            match rx.recv() {
+                Ok(item) => accept(item, &mut batch, tx),
+                Err(_) => receiving = false,
+            }
+            while batch.len() < batch_size {
+                match rx.try_recv() {
+                    Ok(item) => accept(item, &mut batch, tx),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        receiving = false;
+                        break;
+                    }
+                }
+            }
+        }
+
+        if batch.is_empty() {
+            continue;
+        }
+
+        let mut results = transcriber.transcribe_batch(&batch);
+        if results.len() != batch.len() {
+            let message = format!(
+                "transcriber returned {} results for a batch of {} inputs",
+                results.len(),
+                batch.len()
+            );
+            results = batch
+                .iter()
+                .map(|_| Err(ShoutError::Model(message.clone())))
+                .collect();
+        }
+        for (item, result) in batch.drain(..).zip(results) {
+            let finished = Finished {
+                index: item.index,
+                result,
+                duration_ms: Some(item.pcm.len() as u64 * 1000 / SAMPLE_RATE as u64),
+                cache_key: item.cache_key,
+                cached: false,
+            };
+            if tx.send(finished).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Queue a prepared input for the model, or pass it on to the writer if it
+/// failed or came from the cache.
+fn accept(
+    (index, prepared): (usize, Result<Prepared>),
+    batch: &mut Vec<PreparedAudio>,
+    tx: &SyncSender<Finished>,
+) {
+    let (result, cached) = match prepared {
+        Ok(Prepared::Audio(p)) => return batch.push(p),
+        Ok(Prepared::Cached(t)) => (Ok(t), true),
+        Err(e) => (Err(e), false),
+    };
+    let _ = tx.send(Finished {
+        index,
+        result,
+        duration_ms: None,
+        cache_key: None,
+        cached,
+    });
+}
+
+fn write_outputs(
+    rx: Receiver<Finished>,
+    inputs: &[PathBuf],
+    outputs: &[PathBuf],
+    opts: &BatchOptions,
+) -> BatchSummary {
+    let mut summary = BatchSummary::default();
+
+    for item in rx {
+        let path = &inputs[item.index];
+        let written = item.result.and_then(|mut transcript| {
+            if item.duration_ms.is_some() {
+                transcript.metadata.audio_duration_ms = item.duration_ms;
+            }
+            if let (Some(cache), Some(key)) = (&opts.cache, &item.cache_key) {
+                // A cache that cannot be written only costs a re-run later.
+                let _ = cache.put(key, &transcript);
+            }
+            let duration_ms = transcript.metadata.audio_duration_ms.unwrap_or(0);
+            write_output(path, &outputs[item.index], transcript, opts).map(|()| duration_ms)
+        });
+
+        match written {
+            Ok(duration_ms) => {
+                summary.succeeded += 1;
+                summary.cached += usize::from(item.cached);
+                summary.audio_seconds += duration_ms as f64 / 1000.0;
+            }
+            Err(e) => summary.failed.push((path.clone(), format!("{e:#}"))),
+        }
+    }
+    summary
+}
+
+fn write_output(
+    path: &Path,
+    out_path: &Path,
+    mut transcript: Transcript,
+    opts: &BatchOptions,
+) -> Result<()> {
+    transcript.metadata.audio_path = Some(path.to_string_lossy().to_string());
+
+    let file = File::create(out_path)
+        .io_context(|| format!("Failed to create output: {}", out_path.display()))?;
+    write_transcript(BufWriter::new(file), opts.format, &transcript)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::test_tiny::{synthetic_speech, wav_bytes};
+
+    struct Unreachable;
+
+    impl BatchTranscriber for Unreachable {
+        fn transcribe_batch(&mut self, _batch: &[PreparedAudio]) -> Vec<Result<Transcript>> {
+            unreachable!("no input decodes successfully")
+        }
+    }
+
+    /// Loses the result of the first input of every batch.
+    struct Lossy;
+
+    impl BatchTranscriber for Lossy {
+        fn transcribe_batch(&mut self, batch: &[PreparedAudio]) -> Vec<Result<Transcript>> {
+            batch
+                .iter()
+                .skip(1)
+                .map(|_| Ok(Transcript::default()))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn reports_unreadable_inputs_as_failures() {
+        let opts = BatchOptions {
+            jobs: 2,
+            out_dir: std::env::temp_dir().join("shout_batch_test"),
+            ..Default::default()
+        };
+        let inputs = vec![
+            PathBuf::from("does/not/exist.wav"),
+            PathBuf::from("neither.mp3"),
+        ];
+
+        let summary = run_batch(&inputs, &mut Unreachable, &opts).unwrap();
+
+        assert_eq!(summary.succeeded, 0);
+        assert_eq!(summary.failed.len(), 2);
+    }
+
+    #[test]
+    fn batches_with_missing_results_fail() {
+        let dir = std::env::temp_dir().join(format!("shout_batch_lossy_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let inputs = [dir.join("a.wav"), dir.join("b.wav")];
+        for input in &inputs {
+            std::fs::write(input, wav_bytes(&synthetic_speech(0.5), SAMPLE_RATE)).unwrap();
+        }
+
+        let opts = BatchOptions {
+            out_dir: dir.join("out"),
+            ..Default::default()
+        };
+        let summary = run_batch(&inputs, &mut Lossy, &opts).unwrap();
+
+        assert_eq!(summary.succeeded, 0);
+        assert_eq!(summary.failed.len(), 2);
+        assert!(
+            summary.failed.iter().all(|(_, e)| e.contains("results")),
+            "{:?}",
+            summary.failed
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn summary_lists_the_failures() {
+        let summary = BatchSummary {
+            succeeded: 3,
+            cached: 1,
+            failed: vec![(PathBuf::from("bad.wav"), "not audio".into())],
+            audio_seconds: 20.0,
+            wall_time: Duration::from_secs(2),
+        };
+        assert_eq!(
+            summary.to_string(),
+            "Transcribed: 3 (1 cached)\nFailed: 1\nAudio: 20.0 s in 2.0 s (RTF 0.100)\n  \
+             bad.wav: not audio\n"
+        );
+    }
+
+    #[test]
+    fn cached_inputs_skip_the_model() {
+        let dir = std::env::temp_dir().join(format!("shout_batch_cache_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("clip.wav");
+        std::fs::write(&input, b"not really audio").unwrap();
+
+        let cache = ResultCache::open(dir.join("cache"), 1 << 20).unwrap();
+        let key = ResultCache::key(&hash_file(&input).unwrap(), "ctx");
+        cache.put(&key, &Transcript::default()).unwrap();
+
+        let opts = BatchOptions {
+            out_dir: dir.join("out"),
+            cache: Some(cache),
+            cache_context: "ctx".into(),
+            ..Default::default()
+        };
+        let summary = run_batch(&[input], &mut Unreachable, &opts).unwrap();
+
+        assert_eq!((summary.succeeded, summary.cached), (1, 1));
+        assert!(dir.join("out/clip.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn output_paths_use_format_extension() {
+        let opts = BatchOptions {
+            format: OutputFormat::Srt,
+            out_dir: PathBuf::from("out"),
+            ..Default::default()
+        };
+        let inputs = [PathBuf::from("a/b/clip.mp3"), PathBuf::from("talk.v2.wav")];
+        assert_eq!(
+            output_paths(&inputs, &opts),
+            [
+                PathBuf::from("out/clip.srt"),
+                PathBuf::from("out/talk.v2.srt")
+            ]
+        );
+    }
+
+    #[test]
+    fn output_paths_of_inputs_with_the_same_stem_are_numbered() {
+        let opts = BatchOptions {
+            out_dir: PathBuf::from("out"),
+            ..Default::default()
+        };
+        let inputs = ["a/clip.mp3", "b/other.wav", "b/clip.wav"].map(PathBuf::from);
+        assert_eq!(
+            output_paths(&inputs, &opts),
+            ["out/clip-1.txt", "out/other.txt", "out/clip-3.txt"].map(PathBuf::from)
+        );
+    }
+}
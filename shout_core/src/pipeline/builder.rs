@@ -0,0 +1,209 @@
+//! One entry point for applications: a [`Pipeline`] configured stage by stage
+//! with [`PipelineBuilder`] and then used on files, samples or live streams.
+//!
+//! Transcribing a file (feature `native`):
+//!
+#![cfg_attr(feature = "native", doc = "```no_run")]
+#![cfg_attr(not(feature = "native"), doc = "```ignore")]
+//! use shout_core::pipeline::builder::PipelineBuilder;
+//!
+//! # fn main() -> shout_core::Result<()> {
+//! let mut pipeline = PipelineBuilder::new().model("models/base").build()?;
+//! let transcript = pipeline.transcribe_file("speech.wav")?;
+//! println!("{}", transcript.text());
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "native")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use candle_core::Device;
+
+use super::SpeechDetector;
+use super::gating::GateOptions;
+use super::longform::LongFormOptions;
+use super::streaming::{StreamingOptions, StreamingSession};
+use super::transcribe::Transcriber;
+#[cfg(feature = "native")]
+use super::transcribe::with_audio_path;
+use crate::cancel::{CancelToken, Timeouts};
+use crate::decoding::DecodeOptions;
+use crate::errors::{Result, ShoutError};
+use crate::features::AudioFrontEnd;
+use crate::inference::load_transcriber;
+use crate::model::shout::ShoutModel;
+use crate::transcript::Transcript;
+
+/// A speech detector the pipeline can own.
+pub type BoxedDetector = Box<dyn SpeechDetector + Send>;
+
+/// Settings for every stage of a [`Pipeline`]; only [`Self::model`] is required.
+#[derive(Default)]
+pub struct PipelineBuilder {
+    model_dir: Option<PathBuf>,
+    device: Option<Device>,
+    front_end: Option<Arc<dyn AudioFrontEnd>>,
+    decoder: Option<DecodeOptions>,
+    long_form: Option<LongFormOptions>,
+    vad: Option<(BoxedDetector, GateOptions)>,
+    streaming: StreamingOptions,
+    cancel: Option<CancelToken>,
+    timeouts: Timeouts,
+}
+
+impl PipelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Model directory with `config.json`, weights and `tokenizer.json`.
+    pub fn model(mut self, model_dir: impl Into<PathBuf>) -> Self {
+        self.model_dir = Some(model_dir.into());
+        self
+    }
+
+    /// Where the model runs; the CPU unless set.
+    pub fn device(mut self, device: Device) -> Self {
+        self.device = Some(device);
+        self
+    }
+
+    /// Replace the feature front end the model's config asks for.
+    pub fn features(mut self, front_end: Arc<dyn AudioFrontEnd>) -> Self {
+        self.front_end = Some(front_end);
+        self
+    }
+
+    /// Task, language, beam size, fallback and the other decoding settings.
+    pub fn decoder(mut self, options: DecodeOptions) -> Self {
+        self.decoder = Some(options);
+        self
+    }
+
+    /// How audio longer than one window is split.
+    pub fn long_form(mut self, options: LongFormOptions) -> Self {
+        self.long_form = Some(options);
+        self
+    }
+
+    /// Transcribe only what `detector` marks as speech.
+    pub fn vad(mut self, detector: BoxedDetector, gate: GateOptions) -> Self {
+        self.vad = Some((detector, gate));
+        self
+    }
+
+    /// Step and finalization settings of [`Pipeline::transcribe_stream`].
+    pub fn streaming(mut self, options: StreamingOptions) -> Self {
+        self.streaming = options;
+        self
+    }
+
+    /// Stop work once `cancel` is cancelled or expires; applies to every call.
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Time limits for decoding a file and for each window's features and inference.
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Load the model and assemble the pipeline.
+    pub fn build(self) -> Result<Pipeline> {
+        let model_dir = self.model_dir.ok_or_else(|| {
+            ShoutError::InvalidArgument("pipeline needs a model directory".into())
+        })?;
+        let device = self.device.unwrap_or(Device::Cpu);
+
+        let mut transcriber = load_transcriber(&model_dir, &device)?;
+        if let Some(front_end) = self.front_end {
+            if front_end.feature_dim() != transcriber.n_mels() {
+                return Err(ShoutError::Feature(format!(
+                    "front end computes {} values per frame, model expects {}",
+                    front_end.feature_dim(),
+                    transcriber.n_mels()
+                )));
+            }
+            transcriber.set_front_end(front_end);
+        }
+        if let Some(options) = self.decoder {
+            transcriber.options = options;
+        }
+        if let Some(options) = self.long_form {
+            transcriber.long_form = options;
+        }
+        if let Some(cancel) = self.cancel {
+            transcriber.options.cancel = cancel;
+        }
+        transcriber.timeouts = self.timeouts;
+
+        Ok(Pipeline {
+            transcriber,
+            vad: self.vad,
+            streaming: self.streaming,
+        })
+    }
+}
+
+/// A loaded model with its audio, feature and decoding settings.
+pub struct Pipeline {
+    transcriber: Transcriber<ShoutModel>,
+    vad: Option<(BoxedDetector, GateOptions)>,
+    streaming: StreamingOptions,
+}
+
+impl Pipeline {
+    pub fn transcriber(&self) -> &Transcriber<ShoutModel> {
+        &self.transcriber
+    }
+
+    /// For settings the builder does not cover, such as hotwords or a language model.
+    pub fn transcriber_mut(&mut self) -> &mut Transcriber<ShoutModel> {
+        &mut self.transcriber
+    }
+
+    /// Transcribe 16 kHz mono samples of any length.
+    pub fn transcribe_pcm(&mut self, pcm: &[f32]) -> Result<Transcript> {
+        match &mut self.vad {
+            Some((detector, gate)) => {
+                self.transcriber
+                    .transcribe_pcm_gated(pcm, detector.as_mut(), gate)
+            }
+            None => self.transcriber.transcribe_pcm(pcm),
+        }
+    }
+
+    /// Decode an audio file and transcribe it.
+    #[cfg(feature = "native")]
+    pub fn transcribe_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Transcript> {
+        let path = path.as_ref();
+        let pcm = self.transcriber.decode_file(path)?;
+        let result = self.transcribe_pcm(&pcm);
+        with_audio_path(result, path)
+    }
+
+    /// Start a live stream: push samples at the streaming sample rate to the
+    /// session as they arrive and call `finish` at the end. The speech detector
+    /// is not used while streaming.
+    pub fn transcribe_stream(&mut self) -> StreamingSession<&mut Transcriber<ShoutModel>> {
+        StreamingSession::new(&mut self.transcriber, self.streaming.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_requires_a_model() {
+        let result = PipelineBuilder::new()
+            .decoder(DecodeOptions::default())
+            .build();
+        assert!(matches!(result, Err(ShoutError::InvalidArgument(_))));
+    }
+}
@@ -0,0 +1,54 @@
+//! Utterance embeddings: the encoder states of a model pooled into one vector
+//! per recording, for audio search, clustering and finding duplicates.
+
+#[cfg(feature = "native")]
+use std::path::Path;
+
+use super::transcribe::Transcriber;
+use crate::errors::{Result, ShoutError};
+use crate::features::HOP_LENGTH;
+use crate::model::shout::ShoutModel;
+
+impl Transcriber<ShoutModel> {
+    /// Length of [`Self::embed`]'s vectors.
+    pub fn embedding_dim(&self) -> usize {
+        self.model().config.n_audio_state
+    }
+
+    /// Embedding of 16 kHz mono samples of any length: the mean encoder state
+    /// of every model window, averaged weighted by the window's length and
+    /// scaled to unit length, so the dot product of two embeddings is their
+    /// cosine similarity.
+    pub fn embed(&mut self, pcm: &[f32]) -> Result<Vec<f32>> {
+        if pcm.is_empty() {
+            return Err(ShoutError::InvalidArgument(
+                "cannot embed empty audio".into(),
+            ));
+        }
+
+        let window = self.model().config.n_frames() * HOP_LENGTH;
+        let mut sum = vec![0.0f32; self.embedding_dim()];
+        for chunk in pcm.chunks(window) {
+            let mel = self.front_end().features(chunk)?;
+            let weight = chunk.len() as f32 / pcm.len() as f32;
+            for (s, v) in sum.iter_mut().zip(self.model_mut().embed(&mel)?) {
+                *s += weight * v;
+            }
+        }
+
+        let norm = sum.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            sum.iter_mut().for_each(|v| *v /= norm);
+        }
+        Ok(sum)
+    }
+
+    /// [`Self::embed`] for an audio file.
+    #[cfg(feature = "native")]
+    pub fn embed_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<f32>> {
+        let path = path.as_ref();
+        let pcm = self.decode_file(path)?;
+        self.embed(&pcm)
+            .map_err(|e| e.context(format!("failed to embed {}", path.display())))
+    }
+}
@@ -0,0 +1,157 @@
+//! VAD-gated transcription: only regions a [`SpeechDetector`] marks as speech are
+//! sent to the model. Silence and music are skipped, which saves compute and avoids
+//! the text Whisper-style models tend to hallucinate on non-speech input.
+
+use super::longform::{Chunking, LongFormOptions, transcribe_long};
+use super::{SpeechDetector, WindowTranscriber};
+use crate::errors::Result;
+use crate::transcript::Segment;
+
+#[derive(Debug, Clone)]
+pub struct GateOptions {
+    /// When false, the whole input is transcribed with fixed windows.
+    pub enabled: bool,
+
+    /// Padding added on both sides of every speech region so word onsets and
+    /// trailing consonants are not clipped.
+    pub pad_ms: u64,
+
+    /// Regions separated by less than this are merged into one.
+    pub merge_gap_ms: u64,
+
+    /// Regions shorter than this (after padding and merging) are dropped.
+    pub min_speech_ms: u64,
+}
+
+impl Default for GateOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pad_ms: 200,
+            merge_gap_ms: 300,
+            min_speech_ms: 250,
+        }
+    }
+}
+
+/// Pad, merge and filter raw detector output. Input need not be sorted.
+pub fn clean_regions(
+    mut regions: Vec<(u64, u64)>,
+    total_ms: u64,
+    opts: &GateOptions,
+) -> Vec<(u64, u64)> {
+    regions.sort_unstable();
+
+    let mut out: Vec<(u64, u64)> = Vec::new();
+    for (start, end) in regions {
+        let start = start.saturating_sub(opts.pad_ms);
+        let end = (end + opts.pad_ms).min(total_ms);
+        if end <= start {
+            continue;
+        }
+
+        match out.last_mut() {
+            Some(last) if start <= last.1 + opts.merge_gap_ms => last.1 = last.1.max(end),
+            _ => out.push((start, end)),
+        }
+    }
+
+    out.retain(|&(s, e)| e - s >= opts.min_speech_ms);
+    out
+}
+
+/// Transcribe `pcm`, skipping everything the detector does not classify as speech.
+///
+/// With `detector == None` or `gate.enabled == false` this is plain fixed-window
+/// long-form transcription.
+pub fn transcribe_gated<T, D>(
+    transcriber: &mut T,
+    detector: Option<&mut D>,
+    pcm: &[f32],
+    gate: &GateOptions,
+    opts: &LongFormOptions,
+) -> Result<Vec<Segment>>
+where
+    T: WindowTranscriber + ?Sized,
+    D: SpeechDetector + ?Sized,
+{
+    let detector = match detector {
+        Some(d) if gate.enabled => d,
+        _ => return transcribe_long(transcriber, pcm, &Chunking::Fixed, opts),
+    };
+
+    let total_ms = pcm.len() as u64 * 1000 / opts.sample_rate as u64;
+    let regions = clean_regions(
+        detector.speech_regions(pcm, opts.sample_rate)?,
+        total_ms,
+        gate,
+    );
+    if regions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut segments =
+        transcribe_long(transcriber, pcm, &Chunking::Regions(regions.clone()), opts)?;
+
+    // Windows may still contain short gaps between packed regions; drop anything
+    // the model produced there.
+    segments.retain(|seg| {
+        let mid = seg.start_ms + seg.duration_ms() / 2;
+        regions.iter().any(|&(s, e)| mid >= s && mid < e)
+    });
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_merges_and_drops_short_regions() {
+        let opts = GateOptions {
+            pad_ms: 100,
+            merge_gap_ms: 200,
+            min_speech_ms: 500,
+            ..Default::default()
+        };
+        let regions = vec![(2_000, 2_100), (500, 1_000), (1_250, 1_500)];
+
+        assert_eq!(clean_regions(regions, 10_000, &opts), vec![(400, 1_600)]);
+    }
+
+    struct Nothing;
+
+    impl SpeechDetector for Nothing {
+        fn speech_regions(&mut self, _pcm: &[f32], _sample_rate: u32) -> Result<Vec<(u64, u64)>> {
+            Ok(Vec::new())
+        }
+    }
+
+    struct Hallucinate;
+
+    impl WindowTranscriber for Hallucinate {
+        fn transcribe_window(&mut self, _pcm: &[f32], _prompt: &str) -> Result<Vec<Segment>> {
+            Ok(vec![Segment {
+                start_ms: 0,
+                end_ms: 1_000,
+                text: "Vielen Dank fürs Zuschauen!".into(),
+                ..Default::default()
+            }])
+        }
+    }
+
+    #[test]
+    fn silence_is_never_transcribed() {
+        let pcm = vec![0.0f32; 16_000 * 5];
+        let segments = transcribe_gated(
+            &mut Hallucinate,
+            Some(&mut Nothing),
+            &pcm,
+            &GateOptions::default(),
+            &LongFormOptions::default(),
+        )
+        .unwrap();
+
+        assert!(segments.is_empty());
+    }
+}
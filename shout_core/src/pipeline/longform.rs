@@ -0,0 +1,457 @@
+//! Transcription of audio longer than one model window.
+//!
+//! The input is cut into windows (fixed, overlapping windows or packed speech
+//! regions), each window is transcribed with the previous text as prompt, and the
+//! per-window hypotheses are stitched back together on a single timeline.
+
+use super::WindowTranscriber;
+use crate::cancel::with_partial;
+use crate::errors::Result;
+use crate::transcript::{Segment, Transcript, Word};
+
+#[derive(Debug, Clone)]
+pub struct LongFormOptions {
+    /// Sample rate of the PCM passed to [`transcribe_long`].
+    pub sample_rate: u32,
+
+    /// Length of one model window.
+    pub window_ms: u64,
+
+    /// Overlap between consecutive fixed windows. Hypotheses in the overlap are
+    /// reconciled at its midpoint.
+    pub overlap_ms: u64,
+
+    /// Pass the text decoded so far as prompt to the next window.
+    pub condition_on_previous_text: bool,
+
+    /// Upper bound on the prompt length (the tail of the previous text is kept).
+    pub max_prompt_chars: usize,
+
+    /// User context (names, jargon, spelling) put at the front of every window's
+    /// prompt. It takes precedence over the previous text within `max_prompt_chars`.
+    pub initial_prompt: Option<String>,
+}
+
+impl Default for LongFormOptions {
+    fn default() -> Self {
+        Self {
+            sample_rate: 16_000,
+            window_ms: 30_000,
+            overlap_ms: 5_000,
+            condition_on_previous_text: true,
+            max_prompt_chars: 600,
+            initial_prompt: None,
+        }
+    }
+}
+
+/// How the input is cut into windows.
+#[derive(Debug, Clone)]
+pub enum Chunking {
+    /// Fixed windows of `window_ms` with `overlap_ms` overlap.
+    Fixed,
+
+    /// Speech regions `(start_ms, end_ms)`, e.g. from a VAD. Adjacent regions are
+    /// packed into windows of at most `window_ms`; longer regions are split into
+    /// fixed windows. Audio outside the regions is never transcribed.
+    Regions(Vec<(u64, u64)>),
+}
+
+/// Window boundaries `(start_ms, end_ms)` for `total_ms` of audio.
+pub fn plan_windows(total_ms: u64, chunking: &Chunking, opts: &LongFormOptions) -> Vec<(u64, u64)> {
+    match chunking {
+        Chunking::Fixed => fixed_windows(0, total_ms, opts),
+        Chunking::Regions(regions) => {
+            let mut windows: Vec<(u64, u64)> = Vec::new();
+            for &(start, end) in regions {
+                let (start, end) = (start.min(total_ms), end.min(total_ms));
+                if end <= start {
+                    continue;
+                }
+
+                match windows.last_mut() {
+                    Some(last) if end - last.0 <= opts.window_ms && start >= last.1 => last.1 = end,
+                    _ if end - start > opts.window_ms => {
+                        windows.extend(fixed_windows(start, end, opts))
+                    }
+                    _ => windows.push((start, end)),
+                }
+            }
+            windows
+        }
+    }
+}
+
+fn fixed_windows(start: u64, end: u64, opts: &LongFormOptions) -> Vec<(u64, u64)> {
+    let step = opts.window_ms.saturating_sub(opts.overlap_ms).max(1);
+    let mut windows = Vec::new();
+    let mut s = start;
+
+    while s < end {
+        let e = (s + opts.window_ms).min(end);
+        windows.push((s, e));
+        if e == end {
+            break;
+        }
+        s += step;
+    }
+    windows
+}
+
+/// Transcribe `pcm` of arbitrary length window by window and stitch the results.
+/// When a window is interrupted, the error carries the windows before it.
+pub fn transcribe_long<T: WindowTranscriber + ?Sized>(
+    transcriber: &mut T,
+    pcm: &[f32],
+    chunking: &Chunking,
+    opts: &LongFormOptions,
+) -> Result<Vec<Segment>> {
+    let sr = opts.sample_rate as u64;
+    let total_ms = pcm.len() as u64 * 1000 / sr;
+    let windows = plan_windows(total_ms, chunking, opts);
+
+    let mut out: Vec<Segment> = Vec::new();
+
+    for (i, &(start_ms, end_ms)) in windows.iter().enumerate() {
+        let a = (start_ms * sr / 1000) as usize;
+        let b = ((end_ms * sr / 1000) as usize).min(pcm.len());
+
+        let prompt = window_prompt(&out, opts);
+        let mut segments = match transcriber.transcribe_window(&pcm[a..b], &prompt) {
+            Ok(segments) => segments,
+            Err(e) => {
+                let partial = Transcript {
+                    segments: out,
+                    ..Default::default()
+                };
+                return Err(with_partial(e, partial));
+            }
+        };
+        for seg in &mut segments {
+            shift(seg, start_ms, end_ms);
+        }
+
+        // Everything before the midpoint of the overlap with the previous window
+        // belongs to the previous window, everything after it to this one.
+        let cut_ms = match i.checked_sub(1).map(|p| windows[p]) {
+            Some((_, prev_end)) if prev_end > start_ms => start_ms + (prev_end - start_ms) / 2,
+            _ => start_ms,
+        };
+        stitch(&mut out, segments, cut_ms);
+    }
+
+    Ok(out)
+}
+
+/// [`transcribe_long`] of `total_ms` of audio that fits one window, with
+/// `transcribe` decoding that window given its prompt.
+pub(crate) fn transcribe_single(
+    total_ms: u64,
+    opts: &LongFormOptions,
+    transcribe: impl FnOnce(&str) -> Result<Vec<Segment>>,
+) -> Result<Vec<Segment>> {
+    let mut segments = transcribe(&window_prompt(&[], opts))
+        .map_err(|e| with_partial(e, Transcript::default()))?;
+    for seg in &mut segments {
+        shift(seg, 0, total_ms);
+    }
+
+    let mut out = Vec::new();
+    stitch(&mut out, segments, 0);
+    Ok(out)
+}
+
+/// Move window-relative times onto the global timeline, clamped to the window.
+fn shift(seg: &mut Segment, start_ms: u64, end_ms: u64) {
+    let clamp = |t: u64| (t + start_ms).min(end_ms);
+    seg.start_ms = clamp(seg.start_ms);
+    seg.end_ms = clamp(seg.end_ms).max(seg.start_ms);
+    for w in &mut seg.words {
+        w.start_ms = clamp(w.start_ms);
+        w.end_ms = clamp(w.end_ms).max(w.start_ms);
+    }
+}
+
+/// Append `next` to `out`, keeping only what lies on the correct side of `cut_ms`
+/// and keeping timestamps monotonic.
+fn stitch(out: &mut Vec<Segment>, next: Vec<Segment>, cut_ms: u64) {
+    // Drop (or shorten) what the previous windows produced after the cut.
+    while let Some(last) = out.last_mut() {
+        if midpoint(last.start_ms, last.end_ms) >= cut_ms && last.words.is_empty() {
+            out.pop();
+            continue;
+        }
+        if !last.words.is_empty() {
+            let before = last.words.len();
+            last.words
+                .retain(|w| midpoint(w.start_ms, w.end_ms) < cut_ms);
+            if last.words.is_empty() {
+                out.pop();
+                continue;
+            }
+            if last.words.len() < before {
+                rebuild_from_words(last);
+            }
+        }
+        break;
+    }
+
+    let prev_end = out.last().map(|s| s.end_ms).unwrap_or(0);
+    let prev_words: Vec<String> = out
+        .iter()
+        .rev()
+        .flat_map(|s| s.words.iter().rev())
+        .take(MAX_DUPLICATE_WORDS)
+        .map(|w| normalize(&w.text))
+        .collect();
+
+    let mut first = true;
+    for mut seg in next {
+        if !seg.words.is_empty() {
+            let before = seg.words.len();
+            seg.words
+                .retain(|w| midpoint(w.start_ms, w.end_ms) >= cut_ms);
+            if first {
+                drop_repeated_prefix(&mut seg.words, &prev_words);
+            }
+            if seg.words.is_empty() {
+                continue;
+            }
+            // Untouched segments keep their text and token scores.
+            if seg.words.len() < before {
+                rebuild_from_words(&mut seg);
+            }
+        } else if midpoint(seg.start_ms, seg.end_ms) < cut_ms {
+            continue;
+        }
+
+        seg.start_ms = seg.start_ms.max(prev_end);
+        seg.end_ms = seg.end_ms.max(seg.start_ms);
+        out.push(seg);
+        first = false;
+    }
+}
+
+/// Longest run of words that is checked for duplication across a window boundary.
+const MAX_DUPLICATE_WORDS: usize = 8;
+
+/// Drop leading words of `words` that repeat the end of the previous output
+/// (`prev_rev` holds the previous words in reverse order).
+fn drop_repeated_prefix(words: &mut Vec<Word>, prev_rev: &[String]) {
+    let max = prev_rev.len().min(words.len());
+    for n in (1..=max).rev() {
+        let tail = prev_rev[..n].iter().rev();
+        let head = words[..n].iter().map(|w| normalize(&w.text));
+        if tail.cloned().eq(head) {
+            words.drain(..n);
+            return;
+        }
+    }
+}
+
+fn rebuild_from_words(seg: &mut Segment) {
+    seg.text = seg
+        .words
+        .iter()
+        .map(|w| w.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let (Some(first), Some(last)) = (seg.words.first(), seg.words.last()) {
+        seg.start_ms = first.start_ms;
+        seg.end_ms = last.end_ms;
+    }
+    // Token scores no longer line up with the shortened text.
+    seg.tokens.clear();
+}
+
+/// The initial prompt followed by as much of the previous text as still fits.
+fn window_prompt(out: &[Segment], opts: &LongFormOptions) -> String {
+    let initial = opts.initial_prompt.as_deref().map_or("", str::trim);
+    let previous = if opts.condition_on_previous_text {
+        prompt_tail(
+            out,
+            opts.max_prompt_chars
+                .saturating_sub(initial.chars().count()),
+        )
+    } else {
+        String::new()
+    };
+
+    match (initial.is_empty(), previous.is_empty()) {
+        (true, _) => previous,
+        (false, true) => initial.to_string(),
+        (false, false) => format!("{initial} {previous}"),
+    }
+}
+
+fn prompt_tail(segments: &[Segment], max_chars: usize) -> String {
+    let text = segments
+        .iter()
+        .map(|s| s.text.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let n = text.chars().count();
+    if n <= max_chars {
+        return text;
+    }
+
+    let tail: String = text.chars().skip(n - max_chars).collect();
+    // Don't start the prompt in the middle of a word.
+    match tail.find(char::is_whitespace) {
+        Some(i) => tail[i..].trim_start().to_string(),
+        None => tail,
+    }
+}
+
+fn midpoint(a: u64, b: u64) -> u64 {
+    a + (b.saturating_sub(a)) / 2
+}
+
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::{CancelToken, Stage};
+    use crate::errors::ShoutError;
+
+    #[test]
+    fn plans_overlapping_fixed_windows() {
+        let opts = LongFormOptions {
+            window_ms: 30_000,
+            overlap_ms: 5_000,
+            ..Default::default()
+        };
+        assert_eq!(
+            plan_windows(60_000, &Chunking::Fixed, &opts),
+            vec![(0, 30_000), (25_000, 55_000), (50_000, 60_000)]
+        );
+    }
+
+    #[test]
+    fn packs_speech_regions_into_windows() {
+        let opts = LongFormOptions::default();
+        let regions = Chunking::Regions(vec![(1_000, 5_000), (6_000, 20_000), (40_000, 45_000)]);
+        assert_eq!(
+            plan_windows(60_000, &regions, &opts),
+            vec![(1_000, 20_000), (40_000, 45_000)]
+        );
+    }
+
+    /// Emits one word per second of audio, named after its absolute second.
+    struct Counter {
+        prompts: Vec<String>,
+        offset_s: u64,
+    }
+
+    impl WindowTranscriber for Counter {
+        fn transcribe_window(&mut self, pcm: &[f32], prompt: &str) -> Result<Vec<Segment>> {
+            self.prompts.push(prompt.to_string());
+            let secs = pcm.len() as u64 / 10;
+            let words: Vec<Word> = (0..secs)
+                .map(|s| Word {
+                    text: format!("w{}", self.offset_s + s),
+                    start_ms: s * 1000,
+                    end_ms: s * 1000 + 1000,
+                    confidence: None,
+                })
+                .collect();
+            // Next window starts 3 s later (window 5 s, overlap 2 s).
+            self.offset_s += 3;
+
+            let mut seg = Segment {
+                words,
+                ..Default::default()
+            };
+            rebuild_from_words(&mut seg);
+            Ok(vec![seg])
+        }
+    }
+
+    #[test]
+    fn stitches_overlapping_hypotheses_without_duplicates() {
+        let opts = LongFormOptions {
+            sample_rate: 10,
+            window_ms: 5_000,
+            overlap_ms: 2_000,
+            ..Default::default()
+        };
+        let pcm = vec![0.0f32; 110]; // 11 s
+        let mut t = Counter {
+            prompts: Vec::new(),
+            offset_s: 0,
+        };
+
+        let segments = transcribe_long(&mut t, &pcm, &Chunking::Fixed, &opts).unwrap();
+        let words: Vec<&str> = segments
+            .iter()
+            .flat_map(|s| s.words.iter().map(|w| w.text.as_str()))
+            .collect();
+
+        assert_eq!(words, (0..11).map(|i| format!("w{i}")).collect::<Vec<_>>());
+        assert_eq!(t.prompts[1], "w0 w1 w2 w3 w4");
+        assert!(segments.windows(2).all(|p| p[0].end_ms <= p[1].start_ms));
+    }
+
+    #[test]
+    fn interrupted_window_keeps_the_earlier_windows() {
+        /// One segment per window until the token is cancelled after the first.
+        struct Stopping(CancelToken);
+
+        impl WindowTranscriber for Stopping {
+            fn transcribe_window(&mut self, _pcm: &[f32], _prompt: &str) -> Result<Vec<Segment>> {
+                self.0.check(Stage::Inference)?;
+                self.0.cancel();
+                Ok(vec![Segment {
+                    end_ms: 1000,
+                    text: "first".into(),
+                    ..Default::default()
+                }])
+            }
+        }
+
+        let opts = LongFormOptions {
+            sample_rate: 10,
+            window_ms: 5_000,
+            overlap_ms: 0,
+            ..Default::default()
+        };
+        let mut t = Stopping(CancelToken::new());
+        let err = transcribe_long(&mut t, &[0.0; 110], &Chunking::Fixed, &opts).unwrap_err();
+
+        let ShoutError::Interrupted { reason, partial } = err else {
+            panic!("expected an interruption, got {err}");
+        };
+        assert_eq!(reason.stage(), Stage::Inference);
+        assert_eq!(partial.text(), "first");
+    }
+
+    #[test]
+    fn initial_prompt_leads_every_window() {
+        let opts = LongFormOptions {
+            max_prompt_chars: 19,
+            initial_prompt: Some(" Kubernetes, Grafana ".into()),
+            ..Default::default()
+        };
+        let out = vec![Segment {
+            text: "we deployed it yesterday".into(),
+            ..Default::default()
+        }];
+
+        assert_eq!(window_prompt(&[], &opts), "Kubernetes, Grafana");
+        assert_eq!(window_prompt(&out, &opts), "Kubernetes, Grafana");
+
+        let opts = LongFormOptions {
+            max_prompt_chars: 40,
+            ..opts
+        };
+        assert_eq!(
+            window_prompt(&out, &opts),
+            "Kubernetes, Grafana it yesterday"
+        );
+    }
+}
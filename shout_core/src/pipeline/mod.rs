@@ -0,0 +1,31 @@
+#[cfg(feature = "native")]
+pub mod batch;
+pub mod builder;
+pub mod embed;
+pub mod gating;
+pub mod longform;
+pub mod streaming;
+pub mod transcribe;
+
+use crate::errors::Result;
+use crate::transcript::Segment;
+
+/// Anything that can transcribe up to one model window (30 s for Whisper) of 16 kHz mono audio.
+pub trait WindowTranscriber {
+    /// Transcribe `pcm`, conditioning the decoder on `prompt` (text of the preceding
+    /// context, may be empty). Returned segment and word times are relative to the
+    /// start of `pcm`.
+    fn transcribe_window(&mut self, pcm: &[f32], prompt: &str) -> Result<Vec<Segment>>;
+}
+
+impl<T: WindowTranscriber + ?Sized> WindowTranscriber for &mut T {
+    fn transcribe_window(&mut self, pcm: &[f32], prompt: &str) -> Result<Vec<Segment>> {
+        (**self).transcribe_window(pcm, prompt)
+    }
+}
+
+/// Voice activity detection as seen by the pipeline.
+pub trait SpeechDetector {
+    /// Speech regions `(start_ms, end_ms)` in `pcm`.
+    fn speech_regions(&mut self, pcm: &[f32], sample_rate: u32) -> Result<Vec<(u64, u64)>>;
+}
@@ -0,0 +1,391 @@
+//! Chunkwise transcription of audio that arrives in real time.
+//!
+//! Incoming samples are collected in a buffer that starts at the first
+//! not-yet-final audio. Every `step_ms` the whole buffer is re-decoded; segments
+//! the [`Stabilization`] policy considers settled are reported as final and cut
+//! off the buffer, the rest as the current partial hypothesis. Policies trade
+//! how soon text becomes final against how often partial text is rewritten.
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use super::WindowTranscriber;
+use crate::errors::{Result, ShoutError};
+use crate::transcript::Segment;
+
+/// When a segment of the running hypothesis becomes final.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Stabilization {
+    /// Once `finalize_margin_ms` of audio follows it.
+    #[default]
+    Margin,
+
+    /// Once `k` further steps (chunks) of audio follow it.
+    AfterChunks(usize),
+
+    /// LocalAgreement-n: once the last `n` hypotheses agree on all of its words.
+    /// Needs no timing margin, so stable speech finalizes after `n` steps.
+    LocalAgreement(usize),
+}
+
+impl FromStr for Stabilization {
+    type Err = ShoutError;
+
+    /// `margin`, `chunks:K` or `agreement:N`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, arg) = s.split_once(':').unwrap_or((s, ""));
+        let count = || -> Result<usize> {
+            match arg.parse() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(ShoutError::InvalidArgument(format!(
+                    "'{s}' needs a positive count, e.g. '{kind}:2'"
+                ))),
+            }
+        };
+        match kind {
+            "margin" => Ok(Stabilization::Margin),
+            "chunks" => Ok(Stabilization::AfterChunks(count()?)),
+            "agreement" => Ok(Stabilization::LocalAgreement(count()?)),
+            _ => Err(ShoutError::InvalidArgument(format!(
+                "unknown stabilization '{s}' (expected margin, chunks:K or agreement:N)"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamingOptions {
+    pub sample_rate: u32,
+
+    /// Re-decode after this much new audio.
+    pub step_ms: u64,
+
+    /// Longest buffer decoded at once (one model window). When it is full, all
+    /// but the last segment are finalized regardless of the margin.
+    pub max_buffer_ms: u64,
+
+    /// A segment is final once this much audio follows it (with [`Stabilization::Margin`]).
+    pub finalize_margin_ms: u64,
+
+    pub stabilization: Stabilization,
+
+    /// Upper bound on the final text passed as prompt.
+    pub max_prompt_chars: usize,
+}
+
+impl Default for StreamingOptions {
+    fn default() -> Self {
+        Self {
+            sample_rate: 16_000,
+            step_ms: 1_000,
+            max_buffer_ms: 30_000,
+            finalize_margin_ms: 2_000,
+            stabilization: Stabilization::Margin,
+            max_prompt_chars: 600,
+        }
+    }
+}
+
+/// Result of one decoding step. Times are relative to the start of the stream.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StreamUpdate {
+    /// Segments that will not change any more.
+    pub finals: Vec<Segment>,
+
+    /// Current hypothesis for the audio after the last final segment.
+    pub partial: Vec<Segment>,
+}
+
+/// One live stream, owning its transcriber for the duration of the session.
+pub struct StreamingSession<T> {
+    transcriber: T,
+    opts: StreamingOptions,
+    buffer: Vec<f32>,
+    buffer_start_ms: u64,
+    unprocessed: usize,
+    committed: String,
+
+    /// Words of the most recent hypotheses for the current buffer (local agreement).
+    history: VecDeque<Vec<String>>,
+}
+
+impl<T: WindowTranscriber> StreamingSession<T> {
+    pub fn new(transcriber: T, opts: StreamingOptions) -> Self {
+        Self {
+            transcriber,
+            opts,
+            buffer: Vec::new(),
+            buffer_start_ms: 0,
+            unprocessed: 0,
+            committed: String::new(),
+            history: VecDeque::new(),
+        }
+    }
+
+    /// End the session and hand the transcriber back.
+    pub fn into_inner(self) -> T {
+        self.transcriber
+    }
+
+    /// Add samples at `opts.sample_rate`. Returns an update whenever a decoding
+    /// step ran, `None` while waiting for more audio.
+    pub fn push(&mut self, pcm: &[f32]) -> Result<Option<StreamUpdate>> {
+        self.buffer.extend_from_slice(pcm);
+        self.unprocessed += pcm.len();
+        if self.unprocessed < self.samples(self.opts.step_ms) {
+            return Ok(None);
+        }
+        self.unprocessed = 0;
+        self.step(false).map(Some)
+    }
+
+    /// Decode whatever is left and finalize it.
+    pub fn finish(&mut self) -> Result<StreamUpdate> {
+        self.unprocessed = 0;
+        self.step(true)
+    }
+
+    fn samples(&self, ms: u64) -> usize {
+        (ms * self.opts.sample_rate as u64 / 1000) as usize
+    }
+
+    fn step(&mut self, flush: bool) -> Result<StreamUpdate> {
+        if self.buffer.is_empty() {
+            return Ok(StreamUpdate::default());
+        }
+
+        let buffer_ms = self.buffer.len() as u64 * 1000 / self.opts.sample_rate as u64;
+        let prompt = tail(&self.committed, self.opts.max_prompt_chars);
+        let mut segments = self.transcriber.transcribe_window(&self.buffer, prompt)?;
+
+        let full = buffer_ms >= self.opts.max_buffer_ms;
+        let behind = |margin_ms: u64| {
+            segments
+                .iter()
+                .take_while(|s| s.end_ms + margin_ms <= buffer_ms)
+                .count()
+        };
+        let stable = match self.opts.stabilization {
+            Stabilization::Margin => behind(self.opts.finalize_margin_ms),
+            Stabilization::AfterChunks(k) => behind(k as u64 * self.opts.step_ms),
+            Stabilization::LocalAgreement(n) => self.agreed(&segments, n),
+        };
+        let n_final = if flush {
+            segments.len()
+        } else if full {
+            stable
+                .max(segments.len().saturating_sub(1))
+                .max(1)
+                .min(segments.len())
+        } else {
+            stable
+        };
+
+        // Keep the buffer from the end of the last final segment on; a full buffer
+        // without any recognized speech is dropped entirely.
+        let cut_ms = match n_final.checked_sub(1).map(|i| segments[i].end_ms) {
+            _ if flush => buffer_ms,
+            Some(end) => end.min(buffer_ms),
+            None if full => buffer_ms,
+            None => 0,
+        };
+
+        for seg in &mut segments {
+            seg.start_ms += self.buffer_start_ms;
+            seg.end_ms += self.buffer_start_ms;
+            for w in &mut seg.words {
+                w.start_ms += self.buffer_start_ms;
+                w.end_ms += self.buffer_start_ms;
+            }
+        }
+        let partial = segments.split_off(n_final);
+        let finals = segments;
+        self.forget_words(&finals.iter().flat_map(segment_words).collect::<Vec<_>>());
+
+        for seg in &finals {
+            if !self.committed.is_empty() {
+                self.committed.push(' ');
+            }
+            self.committed.push_str(seg.text.trim());
+        }
+
+        let cut = self.samples(cut_ms).min(self.buffer.len());
+        self.buffer.drain(..cut);
+        self.buffer_start_ms += cut_ms;
+
+        Ok(StreamUpdate { finals, partial })
+    }
+
+    /// Number of leading segments whose words the last `n` hypotheses
+    /// (including `segments`) agree on.
+    fn agreed(&mut self, segments: &[Segment], n: usize) -> usize {
+        self.history
+            .push_back(segments.iter().flat_map(segment_words).collect());
+        while self.history.len() > n {
+            self.history.pop_front();
+        }
+        if self.history.len() < n {
+            return 0;
+        }
+
+        let latest = &self.history[self.history.len() - 1];
+        let prefix = (0..latest.len())
+            .take_while(|&i| self.history.iter().all(|h| h.get(i) == Some(&latest[i])))
+            .count();
+
+        let mut covered = 0;
+        segments
+            .iter()
+            .take_while(|s| {
+                covered += segment_words(s).len();
+                covered <= prefix
+            })
+            .count()
+    }
+
+    /// Drop the `finalized` words from the front of every remembered hypothesis,
+    /// or the whole history if they were finalized without agreement.
+    fn forget_words(&mut self, finalized: &[String]) {
+        if self.history.iter().all(|h| h.starts_with(finalized)) {
+            for h in &mut self.history {
+                h.drain(..finalized.len());
+            }
+        } else {
+            self.history.clear();
+        }
+    }
+}
+
+/// Words of a segment as compared between hypotheses.
+fn segment_words(segment: &Segment) -> Vec<String> {
+    if segment.words.is_empty() {
+        segment
+            .text
+            .split_whitespace()
+            .map(str::to_string)
+            .collect()
+    } else {
+        segment
+            .words
+            .iter()
+            .map(|w| w.text.trim().to_string())
+            .collect()
+    }
+}
+
+/// Last `max_chars` characters of `text`, starting at a word boundary.
+fn tail(text: &str, max_chars: usize) -> &str {
+    let n = text.chars().count();
+    if n <= max_chars {
+        return text;
+    }
+    let start = text.char_indices().nth(n - max_chars).map_or(0, |(i, _)| i);
+    let tail = &text[start..];
+    match tail.find(char::is_whitespace) {
+        Some(i) => tail[i..].trim_start(),
+        None => tail,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One segment per full second of the window.
+    struct PerSecond;
+
+    impl WindowTranscriber for PerSecond {
+        fn transcribe_window(&mut self, pcm: &[f32], _prompt: &str) -> Result<Vec<Segment>> {
+            Ok((0..pcm.len() as u64 / 10)
+                .map(|s| Segment {
+                    start_ms: s * 1000,
+                    end_ms: s * 1000 + 1000,
+                    text: format!(" s{s}"),
+                    ..Default::default()
+                })
+                .collect())
+        }
+    }
+
+    /// Returns one prepared hypothesis per call, one second per segment.
+    struct Scripted(VecDeque<Vec<&'static str>>);
+
+    impl WindowTranscriber for Scripted {
+        fn transcribe_window(&mut self, _pcm: &[f32], _prompt: &str) -> Result<Vec<Segment>> {
+            let texts = self.0.pop_front().unwrap_or_default();
+            Ok((0u64..)
+                .zip(texts)
+                .map(|(i, text)| Segment {
+                    start_ms: i * 1000,
+                    end_ms: i * 1000 + 1000,
+                    text: format!(" {text}"),
+                    ..Default::default()
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn local_agreement_finalizes_what_successive_hypotheses_share() {
+        let script = vec![vec!["a", "b"], vec!["a", "c"], vec!["c", "d"]];
+        let opts = StreamingOptions {
+            sample_rate: 10,
+            stabilization: Stabilization::LocalAgreement(2),
+            ..Default::default()
+        };
+        let mut session = StreamingSession::new(Scripted(script.into()), opts);
+
+        let first = session.push(&[0.0; 20]).unwrap().unwrap();
+        assert!(first.finals.is_empty());
+
+        let second = session.push(&[0.0; 20]).unwrap().unwrap();
+        assert_eq!(second.finals.len(), 1);
+        assert_eq!(second.finals[0].text, " a");
+        assert_eq!(second.partial[0].text, " c");
+
+        let third = session.push(&[0.0; 20]).unwrap().unwrap();
+        assert_eq!(third.finals.len(), 1);
+        assert_eq!(third.finals[0].text, " c");
+        assert_eq!(third.finals[0].start_ms, 1000);
+    }
+
+    #[test]
+    fn parses_stabilization_policies() {
+        assert_eq!(
+            "margin".parse::<Stabilization>().unwrap(),
+            Stabilization::Margin
+        );
+        assert_eq!(
+            "chunks:3".parse::<Stabilization>().unwrap(),
+            Stabilization::AfterChunks(3)
+        );
+        assert_eq!(
+            "agreement:2".parse::<Stabilization>().unwrap(),
+            Stabilization::LocalAgreement(2)
+        );
+        assert!("agreement:0".parse::<Stabilization>().is_err());
+        assert!("eager".parse::<Stabilization>().is_err());
+    }
+
+    #[test]
+    fn finalizes_segments_behind_the_margin() {
+        let opts = StreamingOptions {
+            sample_rate: 10,
+            ..Default::default()
+        };
+        let mut session = StreamingSession::new(PerSecond, opts);
+
+        assert!(session.push(&[0.0; 5]).unwrap().is_none());
+        let update = session.push(&[0.0; 45]).unwrap().unwrap();
+        assert_eq!(update.finals.len(), 3);
+        assert_eq!(update.partial.len(), 2);
+        assert_eq!(update.partial[0].start_ms, 3000);
+
+        let last = session.finish().unwrap();
+        let ends: Vec<u64> = last.finals.iter().map(|s| s.end_ms).collect();
+        assert_eq!(ends, vec![4000, 5000]);
+        assert!(last.partial.is_empty());
+    }
+}
@@ -0,0 +1,461 @@
+//! End-to-end transcription: audio -> features -> tokens -> text.
+
+#[cfg(feature = "native")]
+use std::path::Path;
+use std::sync::Arc;
+
+use super::gating::{GateOptions, transcribe_gated};
+use super::longform::{Chunking, LongFormOptions, transcribe_long, transcribe_single};
+use super::{SpeechDetector, WindowTranscriber};
+use crate::alignment::{AlignmentOptions, align_segment};
+#[cfg(feature = "native")]
+use crate::audio::decoder::decode_cancellable;
+use crate::cancel::{CancelToken, Stage, Timeouts};
+use crate::confidence::{Calibration, annotate_words};
+use crate::decoding::biasing::{BiasingTrie, Hotword};
+use crate::decoding::fallback::{decode_with_fallback, is_silence};
+use crate::decoding::grammar::{Grammar, GrammarConstraint};
+use crate::decoding::language::{LanguageSelection, resolve_language};
+use crate::decoding::lm::{LmFusion, NgramLm};
+use crate::decoding::prompt::build_prompt;
+use crate::decoding::repetition::is_hallucination;
+use crate::decoding::timestamps::split_segments;
+use crate::decoding::{DecodeOptions, SpeechModel};
+use ndarray::{Axis, s};
+use tracing::debug_span;
+
+use crate::errors::{Result, ShoutError};
+use crate::features::{AudioFrontEnd, LogMel, MelSpec};
+use crate::tokenizer::bpe::Tokenizer;
+use crate::transcript::{Segment, Transcript, TranscriptMetadata};
+
+/// Maximum number of previous-text tokens in the prompt (half of Whisper's 448 text context).
+const MAX_PREVIOUS_TOKENS: usize = 223;
+
+const SAMPLE_RATE: u32 = 16_000;
+
+/// A model plus tokenizer plus decoding settings, usable on whole files or single windows.
+pub struct Transcriber<M: SpeechModel> {
+    model: M,
+    tokenizer: Tokenizer,
+    front_end: Arc<dyn AudioFrontEnd>,
+
+    pub options: DecodeOptions,
+    pub long_form: LongFormOptions,
+
+    /// Limits per file and window, on top of the deadline of `options.cancel`.
+    pub timeouts: Timeouts,
+
+    /// With `--language auto`, detect the language on every window instead of once per file.
+    pub detect_language_per_window: bool,
+
+    /// Recorded in the transcript metadata.
+    pub model_name: Option<String>,
+
+    /// Maps token log-probabilities to word confidences; `None` uses `exp(mean logprob)`.
+    pub calibration: Option<Calibration>,
+
+    /// Time the words of every segment from the decoder's cross-attention, at
+    /// the cost of one more decoder pass per window; `None` leaves
+    /// `Segment::words` empty.
+    pub alignment: Option<AlignmentOptions>,
+
+    /// Language of the most recently decoded window.
+    language: Option<String>,
+}
+
+impl<M: SpeechModel> Transcriber<M> {
+    /// A transcriber computing `n_mels`-bin log-mel features; see [`Self::set_front_end`].
+    pub fn new(model: M, tokenizer: Tokenizer, n_mels: usize) -> Self {
+        Self {
+            model,
+            tokenizer,
+            front_end: Arc::new(LogMel::new(n_mels)),
+            options: DecodeOptions::default(),
+            long_form: LongFormOptions::default(),
+            timeouts: Timeouts::default(),
+            detect_language_per_window: false,
+            model_name: None,
+            calibration: None,
+            alignment: Some(AlignmentOptions::default()),
+            language: None,
+        }
+    }
+
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    pub fn model_mut(&mut self) -> &mut M {
+        &mut self.model
+    }
+
+    /// Favour `hotwords` during beam search. Each phrase is boosted both at the
+    /// start of a word (with a leading space) and glued to the previous token.
+    pub fn set_hotwords(&mut self, hotwords: &[Hotword]) -> Result<()> {
+        let mut trie = BiasingTrie::default();
+        for h in hotwords {
+            trie.insert(&self.tokenizer.encode(&format!(" {}", h.phrase))?, h.boost);
+            trie.insert(&self.tokenizer.encode(&h.phrase)?, h.boost);
+        }
+        self.options.biasing = (!trie.is_empty()).then_some(trie);
+        Ok(())
+    }
+
+    /// Restrict every window's output to one of the phrases of `grammar`.
+    pub fn set_grammar(&mut self, grammar: &Grammar) -> Result<()> {
+        let mut constraint = GrammarConstraint::default();
+        for phrase in grammar.phrases() {
+            constraint.insert(&self.tokenizer.encode(&format!(" {phrase}"))?);
+            constraint.insert(&self.tokenizer.encode(phrase)?);
+        }
+        self.options.grammar = (!constraint.is_empty()).then_some(constraint);
+        Ok(())
+    }
+
+    /// Fuse `lm` into beam search with the given weight and per-word insertion bonus.
+    pub fn set_language_model(&mut self, lm: Arc<NgramLm>, weight: f32, insertion_bonus: f32) {
+        let token_texts = (0..self.tokenizer.special.eot)
+            .map(|t| self.tokenizer.token_text(t))
+            .collect();
+        self.options.lm = Some(LmFusion::new(lm, token_texts, weight, insertion_bonus));
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    /// Mel bins (feature values per frame) the model expects.
+    pub fn n_mels(&self) -> usize {
+        self.front_end.feature_dim()
+    }
+
+    pub fn front_end(&self) -> &Arc<dyn AudioFrontEnd> {
+        &self.front_end
+    }
+
+    /// Compute the model input with `front_end` instead of log-mel features.
+    pub fn set_front_end(&mut self, front_end: Arc<dyn AudioFrontEnd>) {
+        self.front_end = front_end;
+    }
+
+    /// Transcribe 16 kHz mono samples of any length.
+    ///
+    /// Stops early with [`ShoutError::Interrupted`] when `options.cancel` is
+    /// cancelled or a timeout passes; the error holds the windows finished so far.
+    pub fn transcribe_pcm(&mut self, pcm: &[f32]) -> Result<Transcript> {
+        self.language = None;
+        let long_form = self.long_form.clone();
+        let segments = transcribe_long(self, pcm, &Chunking::Fixed, &long_form)
+            .map_err(|e| self.interrupted(e, pcm))?;
+        Ok(self.transcript(segments, pcm))
+    }
+
+    /// Like [`Self::transcribe_pcm`], but only what `detector` marks as speech
+    /// is sent to the model.
+    pub fn transcribe_pcm_gated(
+        &mut self,
+        pcm: &[f32],
+        detector: &mut dyn SpeechDetector,
+        gate: &GateOptions,
+    ) -> Result<Transcript> {
+        self.language = None;
+        let long_form = self.long_form.clone();
+        let segments = transcribe_gated(self, Some(detector), pcm, gate, &long_form)
+            .map_err(|e| self.interrupted(e, pcm))?;
+        Ok(self.transcript(segments, pcm))
+    }
+
+    /// Like [`Self::transcribe_pcm`] for `pcm` that fits one window, from its
+    /// already computed features (those of [`Self::front_end`]) instead of
+    /// computing them again.
+    pub fn transcribe_features(&mut self, pcm: &[f32], mel: &MelSpec) -> Result<Transcript> {
+        self.transcribe_one_window(pcm, |this, window_ms, prompt| {
+            this.window_from_features(mel, window_ms, prompt)
+        })
+    }
+
+    /// Like [`Self::transcribe_features`], from the encoder output of the features.
+    #[cfg(feature = "native")]
+    fn transcribe_encoded(&mut self, pcm: &[f32], encoded: &M::Encoded) -> Result<Transcript> {
+        self.transcribe_one_window(pcm, |this, window_ms, prompt| {
+            let inference = this.options.cancel.with_timeout(this.timeouts.inference);
+            inference.check(Stage::Inference)?;
+            this.decode_window(encoded, inference, window_ms, prompt)
+        })
+    }
+
+    /// Transcribe `pcm` that fits one window with `window`, which gets the
+    /// window's length and prompt.
+    fn transcribe_one_window(
+        &mut self,
+        pcm: &[f32],
+        window: impl FnOnce(&mut Self, u64, &str) -> Result<Vec<Segment>>,
+    ) -> Result<Transcript> {
+        let total_ms = pcm.len() as u64 * 1000 / SAMPLE_RATE as u64;
+        if total_ms > self.long_form.window_ms {
+            return Err(ShoutError::InvalidArgument(format!(
+                "{total_ms} ms of audio do not fit one {} ms window",
+                self.long_form.window_ms
+            )));
+        }
+
+        self.language = None;
+        let long_form = self.long_form.clone();
+        let segments = transcribe_single(total_ms, &long_form, |prompt| {
+            window(self, total_ms, prompt)
+        })
+        .map_err(|e| self.interrupted(e, pcm))?;
+        Ok(self.transcript(segments, pcm))
+    }
+
+    /// Give the partial transcript of an interruption the usual metadata.
+    fn interrupted(&self, error: ShoutError, pcm: &[f32]) -> ShoutError {
+        match error {
+            ShoutError::Interrupted { reason, partial } => ShoutError::Interrupted {
+                reason,
+                partial: Box::new(self.transcript(partial.segments, pcm)),
+            },
+            e => e,
+        }
+    }
+
+    fn transcript(&self, segments: Vec<Segment>, pcm: &[f32]) -> Transcript {
+        Transcript {
+            language: self.language.clone(),
+            segments,
+            metadata: TranscriptMetadata {
+                model: self.model_name.clone(),
+                audio_duration_ms: Some(pcm.len() as u64 * 1000 / SAMPLE_RATE as u64),
+                sample_rate: Some(SAMPLE_RATE),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[cfg(feature = "native")]
+    pub fn transcribe_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Transcript> {
+        let path = path.as_ref();
+        let pcm = self.decode_file(path)?;
+        let result = self.transcribe_pcm(&pcm);
+        with_audio_path(result, path)
+    }
+
+    /// Decode `path` within the decode timeout.
+    #[cfg(feature = "native")]
+    pub fn decode_file(&self, path: &Path) -> Result<Vec<f32>> {
+        decode_cancellable(
+            path,
+            &self.options.cancel.with_timeout(self.timeouts.decode),
+        )
+    }
+
+    /// Fill the words of `segments`, split from the decoded `tokens`, from the
+    /// cross-attention of the decoder reading `prompt` and `tokens` again, and
+    /// score them with `calibration`.
+    fn align(
+        &mut self,
+        encoded: &M::Encoded,
+        prompt: &[u32],
+        tokens: &[u32],
+        segments: &mut [Segment],
+        opts: &AlignmentOptions,
+    ) -> Result<()> {
+        let sequence = [prompt, tokens].concat();
+        let Some(attention) = self.model.alignment_attention(encoded, &sequence)? else {
+            return Ok(());
+        };
+        let n_frames = attention.ncols();
+        if n_frames == 0 {
+            return Ok(());
+        }
+
+        // The segments hold the text tokens in order; each one is predicted by
+        // the row of the position before it.
+        let eot = self.tokenizer.special.eot;
+        let mut rows = (0..tokens.len())
+            .filter(|&i| tokens[i] < eot)
+            .map(|i| prompt.len() + i - 1);
+        let frame_ms = opts.frame_ms.max(1) as u64;
+        for segment in segments {
+            let seg_rows: Vec<usize> = rows.by_ref().take(segment.tokens.len()).collect();
+            if seg_rows.len() != segment.tokens.len() || seg_rows.is_empty() {
+                continue;
+            }
+            let first = ((segment.start_ms / frame_ms) as usize).min(n_frames - 1);
+            let last = (segment.end_ms.div_ceil(frame_ms) as usize).clamp(first + 1, n_frames);
+            let attention = attention.select(Axis(0), &seg_rows);
+            let texts: Vec<String> = segment.tokens.iter().map(|t| t.text.clone()).collect();
+            align_segment(
+                segment,
+                &texts,
+                &attention.slice(s![.., first..last]).to_owned(),
+                opts,
+            );
+            // Scored here, while the segment still has the tokens of its words.
+            annotate_words(segment, self.calibration.as_ref());
+        }
+        Ok(())
+    }
+
+    fn window_language(&mut self, encoded: &M::Encoded) -> Result<String> {
+        if let (LanguageSelection::Auto, Some(lang), false) = (
+            &self.options.language,
+            &self.language,
+            self.detect_language_per_window,
+        ) {
+            return Ok(lang.clone());
+        }
+        resolve_language(
+            &self.options.language,
+            &mut self.model,
+            encoded,
+            &self.tokenizer.special,
+        )
+    }
+
+    /// Encode and decode one window of `window_ms` from its features.
+    fn window_from_features(
+        &mut self,
+        mel: &MelSpec,
+        window_ms: u64,
+        prompt: &str,
+    ) -> Result<Vec<Segment>> {
+        let inference = self.options.cancel.with_timeout(self.timeouts.inference);
+        inference.check(Stage::Inference)?;
+        let encoded = debug_span!("encode").in_scope(|| self.model.encode(mel))?;
+        self.decode_window(&encoded, inference, window_ms, prompt)
+    }
+
+    /// Decode one window of `window_ms` from its encoder output, within the
+    /// deadline of `inference`.
+    fn decode_window(
+        &mut self,
+        encoded: &M::Encoded,
+        inference: CancelToken,
+        window_ms: u64,
+        prompt: &str,
+    ) -> Result<Vec<Segment>> {
+        let language = self.window_language(encoded)?;
+        let previous = if prompt.trim().is_empty() {
+            Vec::new()
+        } else {
+            self.tokenizer.encode(&format!(" {}", prompt.trim()))?
+        };
+        let prompt_tokens = build_prompt(
+            &self.tokenizer.special,
+            &previous,
+            MAX_PREVIOUS_TOKENS,
+            &language,
+            self.options.task,
+            self.options.with_timestamps,
+        )?;
+
+        let limited;
+        let options = match self.timeouts.inference {
+            Some(_) => {
+                limited = DecodeOptions {
+                    cancel: inference,
+                    ..self.options.clone()
+                };
+                &limited
+            }
+            None => &self.options,
+        };
+        let tokenizer = &self.tokenizer;
+        let _decode = debug_span!("decode", language = %language).entered();
+        let result = decode_with_fallback(
+            &mut self.model,
+            encoded,
+            &prompt_tokens,
+            &tokenizer.special,
+            options,
+            window_ms,
+            |tokens| tokenizer.decode(tokens),
+        )?;
+        self.language = Some(language);
+
+        if is_silence(&result, &self.options) {
+            return Ok(Vec::new());
+        }
+
+        let mut segments =
+            split_segments(&result.tokens, &result.logprobs, &self.tokenizer, window_ms)?;
+        if let Some(alignment) = self.alignment.clone() {
+            let _align = debug_span!("align").entered();
+            self.align(
+                encoded,
+                &prompt_tokens,
+                &result.tokens,
+                &mut segments,
+                &alignment,
+            )?;
+        }
+        if result.no_speech_prob > self.options.hallucination_no_speech_threshold {
+            segments.retain(|s| !is_hallucination(&s.text, &self.options.hallucination_phrases));
+        }
+        Ok(segments)
+    }
+}
+
+impl<M: SpeechModel> WindowTranscriber for Transcriber<M> {
+    #[tracing::instrument(level = "debug", name = "window", skip_all, fields(samples = pcm.len()))]
+    fn transcribe_window(&mut self, pcm: &[f32], prompt: &str) -> Result<Vec<Segment>> {
+        // Feature extraction cannot stop midway; a window over the limit fails afterwards.
+        let features = self.options.cancel.with_timeout(self.timeouts.features);
+        features.check(Stage::Features)?;
+        let mel = debug_span!("features").in_scope(|| self.front_end.features(pcm))?;
+        features.check(Stage::Features)?;
+
+        let window_ms = pcm.len() as u64 * 1000 / SAMPLE_RATE as u64;
+        self.window_from_features(&mel, window_ms, prompt)
+    }
+}
+
+/// Set the audio path of a transcript, or of the partial transcript of an
+/// interruption.
+#[cfg(feature = "native")]
+pub(crate) fn with_audio_path(mut result: Result<Transcript>, path: &Path) -> Result<Transcript> {
+    let transcript = match &mut result {
+        Ok(transcript) => transcript,
+        Err(ShoutError::Interrupted { partial, .. }) => partial.as_mut(),
+        Err(_) => return result,
+    };
+    transcript.metadata.audio_path = Some(path.to_string_lossy().to_string());
+    result
+}
+
+/// Inputs the feature stage computed features for (one window or shorter) go
+/// through the encoder in one forward pass and are then decoded one after
+/// another; longer ones are transcribed window by window from their samples.
+#[cfg(feature = "native")]
+impl<M: SpeechModel> super::batch::BatchTranscriber for Transcriber<M> {
+    fn transcribe_batch(
+        &mut self,
+        batch: &[super::batch::PreparedAudio],
+    ) -> Vec<Result<Transcript>> {
+        let mels: Vec<&MelSpec> = batch.iter().filter_map(|item| item.mel.as_ref()).collect();
+        let encoded =
+            debug_span!("encode", windows = mels.len()).in_scope(|| self.model.encode_batch(&mels));
+        let encoded = match encoded {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                // Encoded one by one instead, so that only a faulty input fails.
+                tracing::warn!("Batched encoding failed, encoding inputs one by one: {e}");
+                Vec::new()
+            }
+        };
+
+        let mut encoded = encoded.into_iter();
+        batch
+            .iter()
+            .map(|item| match &item.mel {
+                Some(mel) => match encoded.next() {
+                    Some(encoded) => self.transcribe_encoded(&item.pcm, &encoded),
+                    None => self.transcribe_features(&item.pcm, mel),
+                },
+                None => self.transcribe_pcm(&item.pcm),
+            })
+            .collect()
+    }
+}
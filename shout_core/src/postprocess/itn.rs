@@ -0,0 +1,614 @@
+//! Inverse text normalization: spoken forms back to written forms.
+//!
+//! Rule-based and per language (English and German, other languages pass
+//! through). Handles cardinals, decimals, money, percentages, clock times and
+//! dates:
+//!
+//! - "twenty three euros" → "23 €", "fifteen percent" → "15%"
+//! - "three thirty pm" → "3:30 pm", "drei uhr dreißig" → "3:30 Uhr"
+//! - "march third twenty twenty four" → "March 3, 2024",
+//!   "dritten märz zweitausendvierundzwanzig" → "3. März 2024"
+//!
+//! Small standalone numbers ("one of them") stay words.
+
+use std::ops::Range;
+
+use super::{PostProcessor, segment_words, set_segment_spans};
+use crate::transcript::Segment;
+
+#[derive(Debug, Clone)]
+pub struct ItnOptions {
+    /// Standalone cardinals below this are kept as words.
+    pub min_standalone: u64,
+}
+
+impl Default for ItnOptions {
+    fn default() -> Self {
+        Self { min_standalone: 10 }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InverseNormalizer {
+    pub opts: ItnOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lang {
+    En,
+    De,
+}
+
+impl Lang {
+    fn from_code(code: Option<&str>) -> Option<Self> {
+        match code {
+            None | Some("en") => Some(Lang::En),
+            Some("de") => Some(Lang::De),
+            _ => None,
+        }
+    }
+}
+
+const MONTHS_EN: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+const MONTHS_DE: [&str; 12] = [
+    "januar",
+    "februar",
+    "märz",
+    "april",
+    "mai",
+    "juni",
+    "juli",
+    "august",
+    "september",
+    "oktober",
+    "november",
+    "dezember",
+];
+
+// -------------------------
+// English numbers
+// -------------------------
+
+fn en_atom(word: &str) -> Option<u64> {
+    let v = match word {
+        "zero" => 0,
+        "one" => 1,
+        "two" => 2,
+        "three" => 3,
+        "four" => 4,
+        "five" => 5,
+        "six" => 6,
+        "seven" => 7,
+        "eight" => 8,
+        "nine" => 9,
+        "ten" => 10,
+        "eleven" => 11,
+        "twelve" => 12,
+        "thirteen" => 13,
+        "fourteen" => 14,
+        "fifteen" => 15,
+        "sixteen" => 16,
+        "seventeen" => 17,
+        "eighteen" => 18,
+        "nineteen" => 19,
+        "twenty" => 20,
+        "thirty" => 30,
+        "forty" => 40,
+        "fifty" => 50,
+        "sixty" => 60,
+        "seventy" => 70,
+        "eighty" => 80,
+        "ninety" => 90,
+        "hundred" => 100,
+        "thousand" => 1_000,
+        "million" => 1_000_000,
+        "billion" => 1_000_000_000,
+        _ => return None,
+    };
+    Some(v)
+}
+
+/// Cardinal form of an English ordinal word ("third" → "three", "twentieth" → "twenty").
+fn en_ordinal_base(word: &str) -> Option<String> {
+    let base = match word {
+        "first" => "one".to_string(),
+        "second" => "two".to_string(),
+        "third" => "three".to_string(),
+        "fifth" => "five".to_string(),
+        "eighth" => "eight".to_string(),
+        "ninth" => "nine".to_string(),
+        "twelfth" => "twelve".to_string(),
+        w if w.ends_with("ieth") => format!("{}y", &w[..w.len() - 4]),
+        w if w.ends_with("th") => w[..w.len() - 2].to_string(),
+        _ => return None,
+    };
+    en_atom(&base).map(|_| base)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Last {
+    None,
+    Unit,
+    Teen,
+    Tens,
+    Hundred,
+    Scale,
+}
+
+/// Longest English cardinal at the start of `words`: `(value, words consumed)`.
+/// With `ordinal`, the number must end in an ordinal word ("twenty first").
+fn en_number(words: &[&str], ordinal: bool) -> Option<(u64, usize)> {
+    let (mut total, mut current) = (0u64, 0u64);
+    let mut last = Last::None;
+    let mut last_scale = u64::MAX;
+    let mut best = None;
+
+    for (i, word) in words.iter().enumerate() {
+        if *word == "and" && matches!(last, Last::Hundred | Last::Scale) {
+            continue;
+        }
+
+        // "third" → "three", "twenty-first" → "twenty" "one".
+        let mut parts: Vec<String> = word.split('-').map(str::to_string).collect();
+        let mut is_ordinal = false;
+        if ordinal && let Some(base) = parts.last().and_then(|p| en_ordinal_base(p)) {
+            *parts.last_mut().expect("non-empty split") = base;
+            is_ordinal = true;
+        }
+
+        for part in &parts {
+            let Some(v) = en_atom(part) else {
+                return best;
+            };
+            let after_scale = matches!(last, Last::None | Last::Hundred | Last::Scale);
+            last = match v {
+                0 if last == Last::None => Last::Unit,
+                1..=9 if after_scale || last == Last::Tens => {
+                    current += v;
+                    Last::Unit
+                }
+                10..=19 if after_scale => {
+                    current += v;
+                    Last::Teen
+                }
+                20..=90 if after_scale => {
+                    current += v;
+                    Last::Tens
+                }
+                100 if matches!(last, Last::Unit | Last::Teen) && current < 100 => {
+                    current *= 100;
+                    Last::Hundred
+                }
+                s if s >= 1000 && s < last_scale && !matches!(last, Last::None | Last::Scale) => {
+                    total += current * s;
+                    current = 0;
+                    last_scale = s;
+                    Last::Scale
+                }
+                _ => return best,
+            };
+        }
+
+        if is_ordinal {
+            return Some((total + current, i + 1));
+        }
+        if !ordinal {
+            best = Some((total + current, i + 1));
+        }
+        if total + current == 0 {
+            // "zero" stands alone.
+            break;
+        }
+    }
+    best
+}
+
+// -------------------------
+// German numbers
+// -------------------------
+
+fn de_atom(word: &str) -> Option<u64> {
+    let v = match word {
+        "null" => 0,
+        "ein" | "eins" | "eine" | "einen" | "einem" | "einer" => 1,
+        "zwei" => 2,
+        "drei" => 3,
+        "vier" => 4,
+        "fünf" => 5,
+        "sechs" => 6,
+        "sieben" => 7,
+        "acht" => 8,
+        "neun" => 9,
+        "zehn" => 10,
+        "elf" => 11,
+        "zwölf" => 12,
+        "dreizehn" => 13,
+        "vierzehn" => 14,
+        "fünfzehn" => 15,
+        "sechzehn" => 16,
+        "siebzehn" => 17,
+        "achtzehn" => 18,
+        "neunzehn" => 19,
+        "zwanzig" => 20,
+        "dreißig" => 30,
+        "vierzig" => 40,
+        "fünfzig" => 50,
+        "sechzig" => 60,
+        "siebzig" => 70,
+        "achtzig" => 80,
+        "neunzig" => 90,
+        _ => return None,
+    };
+    Some(v)
+}
+
+/// A German number written as one word ("zweitausendvierundzwanzig").
+fn de_word(word: &str) -> Option<u64> {
+    if word.is_empty() {
+        return None;
+    }
+    // An empty side of "tausend"/"hundert" means 1 on the left and 0 on the right.
+    let part = |s: &str, empty: u64, max: u64| -> Option<u64> {
+        if s.is_empty() {
+            return Some(empty);
+        }
+        de_word(s).filter(|&v| v < max)
+    };
+
+    if let Some((left, right)) = word.split_once("tausend") {
+        return Some(part(left, 1, 1000)? * 1000 + part(right, 0, 1000)?);
+    }
+    if let Some((left, right)) = word.split_once("hundert") {
+        return Some(part(left, 1, 10)? * 100 + part(right, 0, 100)?);
+    }
+    if let Some((unit, tens)) = word.split_once("und") {
+        let unit = de_atom(unit).filter(|&v| (1..10).contains(&v))?;
+        let tens = de_atom(tens).filter(|&v| v >= 20)?;
+        return Some(tens + unit);
+    }
+    de_atom(word)
+}
+
+/// German ordinal ("dritten", "einundzwanzigste") → value.
+fn de_ordinal(word: &str) -> Option<u64> {
+    let stem = ["en", "er", "es", "em", "e"]
+        .iter()
+        .find_map(|s| word.strip_suffix(s))?;
+    match stem {
+        "erst" => return Some(1),
+        "dritt" => return Some(3),
+        "siebt" => return Some(7),
+        "acht" => return Some(8),
+        _ => {}
+    }
+    if let Some(base) = stem.strip_suffix("st") {
+        return de_word(base).filter(|&v| v >= 20);
+    }
+    let base = stem.strip_suffix('t')?;
+    de_word(base).filter(|&v| v < 20)
+}
+
+/// Longest German cardinal at the start of `words`, including "n millionen m".
+fn de_number(words: &[&str]) -> Option<(u64, usize)> {
+    let first = de_word(words.first()?)?;
+    let scale = match words.get(1).copied() {
+        Some("million" | "millionen") => 1_000_000,
+        Some("milliarde" | "milliarden") => 1_000_000_000,
+        _ => return Some((first, 1)),
+    };
+    let value = first * scale;
+    match words.get(2).and_then(|w| de_word(w)) {
+        Some(rest) if rest < scale => Some((value + rest, 3)),
+        _ => Some((value, 2)),
+    }
+}
+
+// -------------------------
+// Matchers
+// -------------------------
+
+fn number(words: &[&str], lang: Lang) -> Option<(u64, usize)> {
+    match lang {
+        Lang::En => en_number(words, false),
+        Lang::De => de_number(words),
+    }
+}
+
+/// "<n> point/komma <digit> <digit>…".
+fn decimal(words: &[&str], lang: Lang) -> Option<(String, usize)> {
+    let (int, n) = number(words, lang)?;
+    let (sep_word, sep) = match lang {
+        Lang::En => ("point", '.'),
+        Lang::De => ("komma", ','),
+    };
+    if words.get(n) != Some(&sep_word) {
+        return None;
+    }
+
+    let digits: String = words[n + 1..]
+        .iter()
+        .map_while(|w| {
+            let d = match (lang, *w) {
+                (Lang::En, "oh") => Some(0),
+                (Lang::En, w) => en_atom(w),
+                (Lang::De, w) => de_atom(w),
+            }?;
+            (d < 10).then(|| char::from_digit(d as u32, 10).expect("single digit"))
+        })
+        .collect();
+    if digits.is_empty() {
+        return None;
+    }
+    Some((
+        format!("{int}{sep}{digits}"),
+        n + 1 + digits.chars().count(),
+    ))
+}
+
+/// Number followed by a currency or percent word.
+fn with_unit(words: &[&str], lang: Lang) -> Option<(String, usize)> {
+    let (value, n) =
+        decimal(words, lang).or_else(|| number(words, lang).map(|(v, n)| (v.to_string(), n)))?;
+    let unit = words.get(n)?;
+    let text = match (lang, *unit) {
+        (_, "euro" | "euros") => format!("{value} €"),
+        (Lang::En, "dollar" | "dollars") => format!("${value}"),
+        (Lang::En, "pound" | "pounds") => format!("£{value}"),
+        (Lang::En, "percent") => format!("{value}%"),
+        (Lang::En, "per") if words.get(n + 1) == Some(&"cent") => {
+            return Some((format!("{value}%"), n + 2));
+        }
+        (Lang::De, "dollar") => format!("{value} $"),
+        (Lang::De, "pfund") => format!("{value} £"),
+        (Lang::De, "prozent") => format!("{value} %"),
+        _ => return None,
+    };
+    Some((text, n + 1))
+}
+
+/// Clock times.
+fn time(words: &[&str], lang: Lang) -> Option<(String, usize)> {
+    let (hour, n) = number(words, lang)?;
+    if hour > 24 {
+        return None;
+    }
+
+    match lang {
+        Lang::En => {
+            if words.get(n) == Some(&"o'clock") {
+                return Some((format!("{hour}:00"), n + 1));
+            }
+            let meridiem = |w: Option<&&str>| match w.copied() {
+                Some("am" | "a.m.") => Some("am"),
+                Some("pm" | "p.m.") => Some("pm"),
+                _ => None,
+            };
+            if hour > 12 {
+                return None;
+            }
+            if let Some(m) = meridiem(words.get(n)) {
+                return Some((format!("{hour} {m}"), n + 1));
+            }
+            let (minute, k) = match words.get(n) {
+                Some(&"oh") => {
+                    let (m, k) = en_number(&words[n + 1..], false).filter(|&(m, _)| m < 10)?;
+                    (m, k + 1)
+                }
+                _ => en_number(&words[n..], false)?,
+            };
+            let m = meridiem(words.get(n + k))?;
+            (minute < 60).then(|| (format!("{hour}:{minute:02} {m}"), n + k + 1))
+        }
+        Lang::De => {
+            if words.get(n) != Some(&"uhr") {
+                return None;
+            }
+            match words.get(n + 1).and_then(|w| de_word(w)) {
+                Some(minute) if minute < 60 => Some((format!("{hour}:{minute:02} Uhr"), n + 2)),
+                _ => Some((format!("{hour} Uhr"), n + 1)),
+            }
+        }
+    }
+}
+
+/// Years: a cardinal ≥ 1000, or English pairs ("nineteen eighty four").
+fn year(words: &[&str], lang: Lang) -> Option<(u64, usize)> {
+    if let Some((y, n)) = number(words, lang).filter(|&(y, _)| (1000..3000).contains(&y)) {
+        return Some((y, n));
+    }
+    if lang != Lang::En {
+        return None;
+    }
+
+    let (century, n) = en_number(words, false).filter(|&(c, _)| (10..100).contains(&c))?;
+    let rest = &words[n..];
+    let (tail, k) = match rest.first() {
+        Some(&"hundred") => (0, 1),
+        Some(&"oh") => {
+            let (d, k) = en_number(&rest[1..], false).filter(|&(d, _)| d < 10)?;
+            (d, k + 1)
+        }
+        _ => en_number(rest, false).filter(|&(t, _)| (10..100).contains(&t))?,
+    };
+    Some((century * 100 + tail, n + k))
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// "march third [twenty twenty four]" / "[der] dritte[n] märz [zweitausend…]".
+fn date(words: &[&str], lang: Lang) -> Option<(String, usize)> {
+    match lang {
+        Lang::En => {
+            let month = MONTHS_EN.iter().position(|m| Some(m) == words.first())?;
+            let (day, n) = en_number(&words[1..], true).filter(|&(d, _)| (1..=31).contains(&d))?;
+            let name = capitalize(MONTHS_EN[month]);
+            match year(&words[1 + n..], lang) {
+                Some((y, k)) => Some((format!("{name} {day}, {y}"), 1 + n + k)),
+                None => Some((format!("{name} {day}"), 1 + n)),
+            }
+        }
+        Lang::De => {
+            let day = de_ordinal(words.first()?).filter(|d| (1..=31).contains(d))?;
+            let month = MONTHS_DE.iter().position(|m| words.get(1) == Some(m))?;
+            let name = capitalize(MONTHS_DE[month]);
+            match year(&words[2..], lang) {
+                Some((y, k)) => Some((format!("{day}. {name} {y}"), 2 + k)),
+                None => Some((format!("{day}. {name}"), 2)),
+            }
+        }
+    }
+}
+
+/// Split trailing punctuation off a word: `"euros."` → `("euros", ".")`.
+fn split_punct(word: &str) -> (&str, &str) {
+    let end = word
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| matches!(c, '.' | ',' | '?' | '!' | ';' | ':'))
+        .last()
+        .map_or(word.len(), |(i, _)| i);
+    word.split_at(end)
+}
+
+impl InverseNormalizer {
+    pub fn new(opts: ItnOptions) -> Self {
+        Self { opts }
+    }
+
+    /// Rewrite `words` into written forms. Each output item carries the range of
+    /// input words it replaces.
+    pub fn normalize(
+        &self,
+        words: &[String],
+        language: Option<&str>,
+    ) -> Vec<(String, Range<usize>)> {
+        let Some(lang) = Lang::from_code(language) else {
+            return words
+                .iter()
+                .enumerate()
+                .map(|(i, w)| (w.clone(), i..i + 1))
+                .collect();
+        };
+
+        let keys: Vec<String> = words
+            .iter()
+            .map(|w| split_punct(w).0.to_lowercase())
+            .collect();
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < words.len() {
+            // Punctuation inside a span would be lost, so spans stop at the first punctuated word.
+            let span_end = (i..words.len())
+                .find(|&j| !split_punct(&words[j]).1.is_empty())
+                .map_or(words.len(), |j| j + 1);
+            let rest = &keys[i..span_end];
+
+            let found = date(rest, lang)
+                .or_else(|| time(rest, lang))
+                .or_else(|| with_unit(rest, lang))
+                .or_else(|| decimal(rest, lang))
+                .or_else(|| {
+                    number(rest, lang)
+                        .filter(|&(v, _)| v >= self.opts.min_standalone)
+                        .map(|(v, n)| (v.to_string(), n))
+                });
+
+            match found {
+                Some((text, n)) => {
+                    let punct = split_punct(&words[i + n - 1]).1;
+                    out.push((format!("{text}{punct}"), i..i + n));
+                    i += n;
+                }
+                None => {
+                    out.push((words[i].clone(), i..i + 1));
+                    i += 1;
+                }
+            }
+        }
+        out
+    }
+}
+
+impl PostProcessor for InverseNormalizer {
+    fn process_segment(&self, segment: &mut Segment, language: Option<&str>) {
+        let (words, _) = segment_words(segment);
+        let spans = self.normalize(&words, language);
+        if spans.len() != words.len() {
+            set_segment_spans(segment, spans);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn itn(text: &str, lang: &str) -> String {
+        let words: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+        InverseNormalizer::default()
+            .normalize(&words, Some(lang))
+            .into_iter()
+            .map(|(w, _)| w)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn english_written_forms() {
+        assert_eq!(itn("it costs twenty three euros.", "en"), "it costs 23 €.");
+        assert_eq!(itn("one hundred and five percent", "en"), "105%");
+        assert_eq!(itn("one of the two cats", "en"), "one of the two cats");
+        assert_eq!(itn("meet at three thirty pm", "en"), "meet at 3:30 pm");
+        assert_eq!(
+            itn("on march third twenty twenty four", "en"),
+            "on March 3, 2024"
+        );
+        assert_eq!(itn("pi is three point one four", "en"), "pi is 3.14");
+        assert_eq!(itn("two thousand nineteen people", "en"), "2019 people");
+    }
+
+    #[test]
+    fn german_written_forms() {
+        assert_eq!(
+            itn("das kostet dreiundzwanzig euro", "de"),
+            "das kostet 23 €"
+        );
+        assert_eq!(
+            itn("am dritten märz zweitausendvierundzwanzig", "de"),
+            "am 3. März 2024"
+        );
+        assert_eq!(itn("um drei uhr dreißig", "de"), "um 3:30 Uhr");
+        assert_eq!(itn("zweihundertfünf prozent", "de"), "205 %");
+        assert_eq!(itn("eine katze", "de"), "eine katze");
+    }
+
+    #[test]
+    fn keeps_word_spans() {
+        let words: Vec<String> = ["about", "twenty", "three", "euros"]
+            .map(String::from)
+            .to_vec();
+        let spans = InverseNormalizer::default().normalize(&words, Some("en"));
+        assert_eq!(spans[1], ("23 €".to_string(), 1..4));
+    }
+}
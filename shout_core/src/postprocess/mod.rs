@@ -0,0 +1,101 @@
+//! Text post-processing applied to finished transcripts (punctuation, written
+//! forms, redaction).
+//!
+//! Stages rewrite segment text and, where a segment has word timings, the words
+//! themselves, so word-level output stays consistent with the text.
+
+pub mod itn;
+pub mod punctuation;
+pub mod redact;
+
+use std::ops::Range;
+
+use crate::transcript::{Segment, Transcript, Word};
+
+/// One post-processing stage.
+pub trait PostProcessor {
+    /// Rewrite `segment` in place. `language` is the transcript's language code, if known.
+    fn process_segment(&self, segment: &mut Segment, language: Option<&str>);
+
+    fn process(&self, transcript: &mut Transcript) {
+        let language = transcript.language.clone();
+        for seg in &mut transcript.segments {
+            self.process_segment(seg, language.as_deref());
+        }
+    }
+}
+
+/// The segment's words as plain strings, with the pause before each word when
+/// timings are known. Falls back to splitting the text on whitespace.
+pub(crate) fn segment_words(segment: &Segment) -> (Vec<String>, Vec<Option<u64>>) {
+    if segment.words.is_empty() {
+        let words: Vec<String> = segment
+            .text
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        let gaps = vec![None; words.len()];
+        return (words, gaps);
+    }
+
+    let words = segment
+        .words
+        .iter()
+        .map(|w| w.text.trim().to_string())
+        .collect();
+    let gaps = segment
+        .words
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            i.checked_sub(1)
+                .map(|p| w.start_ms.saturating_sub(segment.words[p].end_ms))
+        })
+        .collect();
+    (words, gaps)
+}
+
+/// Write rewritten `words` back into `segment`, one output word per input word.
+pub(crate) fn set_segment_words(segment: &mut Segment, words: Vec<String>) {
+    let spans = words
+        .into_iter()
+        .enumerate()
+        .map(|(i, w)| (w, i..i + 1))
+        .collect();
+    set_segment_spans(segment, spans);
+}
+
+/// Write rewritten words back into `segment`. Each item replaces the given range
+/// of the words returned by [`segment_words`]; merged words span the timings of
+/// the words they replace. Empty items are dropped.
+pub(crate) fn set_segment_spans(segment: &mut Segment, spans: Vec<(String, Range<usize>)>) {
+    if !segment.words.is_empty() {
+        let old = std::mem::take(&mut segment.words);
+        segment.words = spans
+            .iter()
+            .filter(|(text, range)| !text.is_empty() && range.end <= old.len())
+            .map(|(text, range)| Word {
+                text: text.clone(),
+                start_ms: old[range.start].start_ms,
+                end_ms: old[range.end - 1].end_ms,
+                confidence: old[range.clone()]
+                    .iter()
+                    .filter_map(|w| w.confidence)
+                    .reduce(f32::min),
+            })
+            .collect();
+    }
+
+    let text = spans
+        .iter()
+        .map(|(text, _)| text.as_str())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    // Keep Whisper's leading-space convention.
+    segment.text = if segment.text.starts_with(' ') {
+        format!(" {text}")
+    } else {
+        text
+    };
+}
@@ -0,0 +1,196 @@
+//! Rule-based punctuation and capitalization restoration.
+//!
+//! Meant for output without any (CTC heads, models trained on normalized text).
+//! Sentences are split at segment ends and long pauses, short pauses become
+//! commas, sentences are capitalized and those opening with a question word end
+//! in `?`. Text that already carries punctuation or capitals is left alone
+//! unless `force` is set.
+
+use super::{PostProcessor, segment_words, set_segment_words};
+use crate::transcript::Segment;
+
+#[derive(Debug, Clone)]
+pub struct PunctuationOptions {
+    /// A pause at least this long ends a sentence.
+    pub sentence_gap_ms: u64,
+
+    /// A pause at least this long (but shorter than a sentence gap) becomes a comma.
+    pub comma_gap_ms: u64,
+
+    /// Also process segments that already contain punctuation or capitals.
+    pub force: bool,
+}
+
+impl Default for PunctuationOptions {
+    fn default() -> Self {
+        Self {
+            sentence_gap_ms: 800,
+            comma_gap_ms: 350,
+            force: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RulePunctuator {
+    pub opts: PunctuationOptions,
+}
+
+fn question_words(language: Option<&str>) -> &'static [&'static str] {
+    match language {
+        Some("de") => &[
+            "was", "warum", "wieso", "weshalb", "wie", "wer", "wen", "wem", "wo", "wohin", "woher",
+            "wann", "welche", "welcher", "welches", "ist", "sind", "hast", "hat", "habt", "kannst",
+            "kann", "können", "willst", "darf", "soll", "gibt",
+        ],
+        Some("es") => &[
+            "qué", "que", "por", "cómo", "como", "quién", "dónde", "cuándo", "cuál", "cuánto",
+        ],
+        Some("fr") => &[
+            "qu'est-ce",
+            "pourquoi",
+            "comment",
+            "qui",
+            "où",
+            "quand",
+            "quel",
+            "quelle",
+            "est-ce",
+        ],
+        _ => &[
+            "what", "why", "how", "who", "whom", "where", "when", "which", "is", "are", "am", "do",
+            "does", "did", "can", "could", "would", "will", "should", "shall", "have", "has",
+            "may",
+        ],
+    }
+}
+
+fn is_terminal(c: char) -> bool {
+    matches!(c, '.' | '?' | '!' | '…')
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Whether `words` already look punctuated or cased (and should be left alone).
+fn is_formatted(words: &[String]) -> bool {
+    words.iter().any(|w| {
+        w.chars()
+            .any(|c| c.is_uppercase() || matches!(c, '.' | ',' | '?' | '!' | ';' | ':'))
+    })
+}
+
+impl RulePunctuator {
+    pub fn new(opts: PunctuationOptions) -> Self {
+        Self { opts }
+    }
+
+    /// Punctuate `words`, where `gaps[i]` is the pause before word `i` if known.
+    pub fn punctuate(&self, words: &mut [String], gaps: &[Option<u64>], language: Option<&str>) {
+        if words.is_empty() || (!self.opts.force && is_formatted(words)) {
+            return;
+        }
+        let questions = question_words(language);
+
+        let mut sentence_start = 0;
+        for i in 0..words.len() {
+            let next_gap = gaps.get(i + 1).copied().flatten();
+            let last = i + 1 == words.len();
+            let ends_sentence = last || next_gap.is_some_and(|g| g >= self.opts.sentence_gap_ms);
+
+            if ends_sentence {
+                let first = words[sentence_start].to_lowercase();
+                let mark = if questions.contains(&first.as_str()) {
+                    '?'
+                } else {
+                    '.'
+                };
+                if !words[i].ends_with(is_terminal) {
+                    words[i].push(mark);
+                }
+                words[sentence_start] = capitalize(&words[sentence_start]);
+                sentence_start = i + 1;
+            } else if next_gap.is_some_and(|g| g >= self.opts.comma_gap_ms)
+                && !words[i].ends_with([',', ';', ':'])
+            {
+                words[i].push(',');
+            }
+
+            if language.is_none_or(|l| l == "en") && words[i] == "i" {
+                words[i] = "I".to_string();
+            }
+        }
+    }
+}
+
+impl PostProcessor for RulePunctuator {
+    fn process_segment(&self, segment: &mut Segment, language: Option<&str>) {
+        let (mut words, gaps) = segment_words(segment);
+        if words.is_empty() || (!self.opts.force && is_formatted(&words)) {
+            return;
+        }
+        self.punctuate(&mut words, &gaps, language);
+        set_segment_words(segment, words);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::Word;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn splits_on_pauses_and_marks_questions() {
+        let p = RulePunctuator::default();
+        let mut w = words("where are you i am here");
+        let gaps = [None, Some(0), Some(0), Some(1200), Some(0), Some(400)];
+
+        p.punctuate(&mut w, &gaps, Some("en"));
+
+        assert_eq!(w.join(" "), "Where are you? I am, here.");
+    }
+
+    #[test]
+    fn leaves_formatted_text_alone() {
+        let p = RulePunctuator::default();
+        let mut seg = Segment {
+            text: " Hallo, Welt.".into(),
+            ..Default::default()
+        };
+        p.process_segment(&mut seg, Some("de"));
+        assert_eq!(seg.text, " Hallo, Welt.");
+    }
+
+    #[test]
+    fn rewrites_words_and_text_together() {
+        let word = |text: &str, start_ms, end_ms| Word {
+            text: text.into(),
+            start_ms,
+            end_ms,
+            confidence: None,
+        };
+        let mut seg = Segment {
+            text: " wie geht es".into(),
+            words: vec![
+                word("wie", 0, 200),
+                word("geht", 250, 500),
+                word("es", 520, 700),
+            ],
+            ..Default::default()
+        };
+
+        RulePunctuator::default().process_segment(&mut seg, Some("de"));
+
+        assert_eq!(seg.text, " Wie geht es?");
+        assert_eq!(seg.words[2].text, "es?");
+    }
+}
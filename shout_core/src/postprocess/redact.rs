@@ -0,0 +1,291 @@
+//! Profanity masking and PII redaction.
+//!
+//! Works on words, so redacted spans keep their timings: [`Redactor::redact`]
+//! returns the affected audio regions for bleeping alongside the rewritten text.
+//! Detected PII: e-mail addresses, IBANs and runs of at least `min_pii_digits`
+//! digits (phone, card and account numbers), whether written as numbers or
+//! spoken digit by digit.
+
+use std::ops::Range;
+
+use serde::Serialize;
+
+use super::{PostProcessor, segment_words, set_segment_spans};
+use crate::transcript::{Segment, Transcript};
+
+/// Masked by default. Matching ignores case and surrounding punctuation.
+pub const DEFAULT_PROFANITY: &[&str] = &[
+    "fuck",
+    "fucking",
+    "fucked",
+    "shit",
+    "bullshit",
+    "bitch",
+    "bastard",
+    "asshole",
+    "dick",
+    "cunt",
+    "scheiße",
+    "scheisse",
+    "arschloch",
+    "fotze",
+    "wichser",
+    "hurensohn",
+    "fick",
+    "ficken",
+    "verdammt",
+];
+
+#[derive(Debug, Clone)]
+pub struct RedactOptions {
+    pub mask_profanity: bool,
+    pub redact_pii: bool,
+    pub profanity: Vec<String>,
+
+    /// Shortest digit run treated as PII.
+    pub min_pii_digits: usize,
+
+    /// Replacement text for a PII span.
+    pub placeholder: String,
+}
+
+impl Default for RedactOptions {
+    fn default() -> Self {
+        Self {
+            mask_profanity: true,
+            redact_pii: true,
+            profanity: DEFAULT_PROFANITY.iter().map(|s| s.to_string()).collect(),
+            min_pii_digits: 7,
+            placeholder: "[REDACTED]".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionKind {
+    Profanity,
+    Pii,
+}
+
+/// Audio region whose text was masked.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RedactedRegion {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub kind: RedactionKind,
+}
+
+/// A rewritten word, the input words it replaces and why it was changed.
+pub type RedactedWord = (String, Range<usize>, Option<RedactionKind>);
+
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    pub opts: RedactOptions,
+}
+
+fn bare(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Digits contributed by `word`: written digits ("555-0199") or a spoken digit.
+fn digits(word: &str) -> Option<usize> {
+    let w = word.trim_matches(|c: char| matches!(c, '.' | ',' | '?' | '!' | ';' | ':'));
+    if !w.is_empty()
+        && w.chars().any(|c| c.is_ascii_digit())
+        && w.chars()
+            .all(|c| c.is_ascii_digit() || "+-()/ ".contains(c))
+    {
+        return Some(w.chars().filter(char::is_ascii_digit).count());
+    }
+    match bare(word).as_str() {
+        "zero" | "oh" | "one" | "two" | "three" | "four" | "five" | "six" | "seven" | "eight"
+        | "nine" | "null" | "eins" | "zwei" | "zwo" | "drei" | "vier" | "fünf" | "sechs"
+        | "sieben" | "acht" | "neun" => Some(1),
+        _ => None,
+    }
+}
+
+fn is_email(word: &str) -> bool {
+    let w = word.trim_matches(|c: char| !c.is_alphanumeric());
+    match w.split_once('@') {
+        Some((user, domain)) => !user.is_empty() && domain.contains('.') && !domain.ends_with('.'),
+        None => false,
+    }
+}
+
+fn is_iban(word: &str) -> bool {
+    let w: String = word.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+    let b = w.as_bytes();
+    (15..=34).contains(&b.len())
+        && b[..2].iter().all(u8::is_ascii_uppercase)
+        && b[2..4].iter().all(u8::is_ascii_digit)
+        && w == word.trim_matches(|c: char| !c.is_alphanumeric())
+}
+
+impl Redactor {
+    pub fn new(opts: RedactOptions) -> Self {
+        Self { opts }
+    }
+
+    /// Rewrite `words`; returns the new words with the input ranges they replace
+    /// and which of them were redacted.
+    pub fn redact_words(&self, words: &[String]) -> Vec<RedactedWord> {
+        let mut out = Vec::new();
+        let mut i = 0;
+
+        while i < words.len() {
+            if self.opts.redact_pii {
+                let run = words[i..]
+                    .iter()
+                    .map_while(|w| digits(w))
+                    .collect::<Vec<_>>();
+                let n = if run.iter().sum::<usize>() >= self.opts.min_pii_digits {
+                    run.len()
+                } else if is_email(&words[i]) || is_iban(&words[i]) {
+                    1
+                } else {
+                    0
+                };
+                if n > 0 {
+                    let placeholder = self.opts.placeholder.clone();
+                    out.push((placeholder, i..i + n, Some(RedactionKind::Pii)));
+                    i += n;
+                    continue;
+                }
+            }
+
+            let word = &words[i];
+            let key = bare(word);
+            if self.opts.mask_profanity && !key.is_empty() && self.opts.profanity.contains(&key) {
+                // Keep the first letter and any punctuation: "shit," → "s***,".
+                let mut seen = 0;
+                let masked: String = word
+                    .chars()
+                    .map(|c| {
+                        if !c.is_alphanumeric() {
+                            return c;
+                        }
+                        seen += 1;
+                        if seen == 1 { c } else { '*' }
+                    })
+                    .collect();
+                out.push((masked, i..i + 1, Some(RedactionKind::Profanity)));
+            } else {
+                out.push((word.clone(), i..i + 1, None));
+            }
+            i += 1;
+        }
+        out
+    }
+
+    /// Redact `segment` and return the audio regions that were masked.
+    pub fn redact_segment(&self, segment: &mut Segment) -> Vec<RedactedRegion> {
+        let (words, _) = segment_words(segment);
+        let out = self.redact_words(&words);
+
+        let regions = out
+            .iter()
+            .filter_map(|(_, range, kind)| {
+                let kind = (*kind)?;
+                let (start_ms, end_ms) = match segment.words.get(range.start..range.end) {
+                    Some(ws) if !ws.is_empty() => (ws[0].start_ms, ws[ws.len() - 1].end_ms),
+                    _ => (segment.start_ms, segment.end_ms),
+                };
+                Some(RedactedRegion {
+                    start_ms,
+                    end_ms,
+                    kind,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if !regions.is_empty() {
+            set_segment_spans(segment, out.into_iter().map(|(w, r, _)| (w, r)).collect());
+        }
+        regions
+    }
+
+    /// Redact the whole transcript and return all masked regions in order.
+    pub fn redact(&self, transcript: &mut Transcript) -> Vec<RedactedRegion> {
+        transcript
+            .segments
+            .iter_mut()
+            .flat_map(|seg| self.redact_segment(seg))
+            .collect()
+    }
+}
+
+impl PostProcessor for Redactor {
+    fn process_segment(&self, segment: &mut Segment, _language: Option<&str>) {
+        self.redact_segment(segment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::Word;
+
+    fn text(r: &Redactor, s: &str) -> String {
+        let words: Vec<String> = s.split_whitespace().map(str::to_string).collect();
+        r.redact_words(&words)
+            .into_iter()
+            .map(|(w, _, _)| w)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn masks_profanity_and_pii() {
+        let r = Redactor::default();
+        assert_eq!(text(&r, "Oh shit, that hurt"), "Oh s***, that hurt");
+        assert_eq!(
+            text(&r, "call me at 555 0199 23 or mail jane@example.org."),
+            "call me at [REDACTED] or mail [REDACTED]"
+        );
+        assert_eq!(
+            text(
+                &r,
+                "meine Nummer ist null eins sieben zwei drei vier fünf danke"
+            ),
+            "meine Nummer ist [REDACTED] danke"
+        );
+        assert_eq!(text(&r, "we met 3 times in 2024"), "we met 3 times in 2024");
+    }
+
+    #[test]
+    fn reports_regions_with_word_timings() {
+        let word = |text: &str, start_ms, end_ms| Word {
+            text: text.into(),
+            start_ms,
+            end_ms,
+            confidence: None,
+        };
+        let mut seg = Segment {
+            start_ms: 0,
+            end_ms: 3000,
+            text: " IBAN DE89370400440532013000 bitte".into(),
+            words: vec![
+                word("IBAN", 0, 400),
+                word("DE89370400440532013000", 500, 2200),
+                word("bitte", 2300, 2600),
+            ],
+            ..Default::default()
+        };
+
+        let regions = Redactor::default().redact_segment(&mut seg);
+
+        assert_eq!(
+            regions,
+            vec![RedactedRegion {
+                start_ms: 500,
+                end_ms: 2200,
+                kind: RedactionKind::Pii
+            }]
+        );
+        assert_eq!(seg.text, " IBAN [REDACTED] bitte");
+        assert_eq!(seg.words[1].start_ms, 500);
+    }
+}
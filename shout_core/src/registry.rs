@@ -0,0 +1,404 @@
+//! Pipeline stages registered by name, so model configs and command lines can
+//! refer to implementations from other crates without changes to shout.
+//!
+//! Four kinds of stage can be registered: [`AudioSource`]s that load audio,
+//! [`Augmentation`]s that alter it, feature extractors (any
+//! [`AudioFrontEnd`]) and [`OutputWriter`]s. Each is registered as a factory
+//! taking JSON parameters, so the same name can be configured differently.
+//! A downstream binary registers its stages with [`global`] at startup:
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use shout_core::features::LogMel;
+//! use shout_core::registry;
+//!
+//! registry::global().register_feature_extractor("wide_mel", |_dim, _params| {
+//!     Ok(Arc::new(LogMel::new(128)))
+//! });
+//! let front_end = registry::global().feature_extractor("wide_mel", 128, &Default::default());
+//! assert_eq!(front_end.unwrap().feature_dim(), 128);
+//! ```
+//!
+//! A model then selects it in `config.json` with
+//! `"front_end": {"type": "plugin", "name": "wide_mel"}`, and the CLI takes
+//! `--format`, `--source` and `--augment` values of the form
+//! `NAME` or `NAME:{"json": "params"}` (see [`StageSpec`]).
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use serde_json::Value;
+
+use crate::errors::{Result, ShoutError};
+use crate::features::{AudioFrontEnd, LogMel, Mfcc, RawWaveform, WhisperLogMel};
+use crate::output::{OutputFormat, write_transcript};
+use crate::transcript::Transcript;
+
+/// Parameters of a stage, e.g. `{"n_filters": 40}`; `null` when none are given.
+pub type Params = Value;
+
+/// Loads audio from a location whose meaning the source defines: a path, a
+/// URL, a database key.
+pub trait AudioSource: Send + Sync {
+    /// 16 kHz mono samples.
+    fn load(&self, location: &str) -> Result<Vec<f32>>;
+}
+
+/// Alters 16 kHz mono audio, e.g. adds noise or reverberation.
+pub trait Augmentation: Send + Sync {
+    fn apply(&self, pcm: &mut Vec<f32>) -> Result<()>;
+}
+
+/// Renders a transcript in some format.
+pub trait OutputWriter: Send + Sync {
+    /// File extension for outputs of this writer.
+    fn extension(&self) -> &str;
+
+    fn mime_type(&self) -> &str {
+        "text/plain; charset=utf-8"
+    }
+
+    fn write(&self, w: &mut dyn Write, transcript: &Transcript) -> Result<()>;
+}
+
+pub type SourceFactory = dyn Fn(&Params) -> Result<Arc<dyn AudioSource>> + Send + Sync;
+pub type AugmentationFactory = dyn Fn(&Params) -> Result<Arc<dyn Augmentation>> + Send + Sync;
+pub type OutputFactory = dyn Fn(&Params) -> Result<Arc<dyn OutputWriter>> + Send + Sync;
+
+/// Builds a front end computing the given number of values per frame.
+pub type FeatureFactory = dyn Fn(usize, &Params) -> Result<Arc<dyn AudioFrontEnd>> + Send + Sync;
+
+/// Factories of one kind of stage, by lower-case name.
+struct Table<F: ?Sized> {
+    kind: &'static str,
+    entries: RwLock<BTreeMap<String, Arc<F>>>,
+}
+
+impl<F: ?Sized> Table<F> {
+    fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            entries: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    fn insert(&self, name: &str, factory: Arc<F>) {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.insert(name.to_ascii_lowercase(), factory);
+    }
+
+    fn get(&self, name: &str) -> Result<Arc<F>> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&name.to_ascii_lowercase())
+            .cloned()
+            .ok_or_else(|| {
+                let known: Vec<&str> = entries.keys().map(String::as_str).collect();
+                ShoutError::InvalidArgument(format!(
+                    "unknown {} '{name}' (registered: {})",
+                    self.kind,
+                    known.join(", ")
+                ))
+            })
+    }
+
+    fn names(&self) -> Vec<String> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        entries.keys().cloned().collect()
+    }
+}
+
+/// Named stage factories. Registering a name again replaces the earlier entry,
+/// built-ins included.
+pub struct Registry {
+    sources: Table<SourceFactory>,
+    augmentations: Table<AugmentationFactory>,
+    features: Table<FeatureFactory>,
+    outputs: Table<OutputFactory>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::with_builtins()
+    }
+}
+
+impl Registry {
+    /// A registry without any entries.
+    pub fn empty() -> Self {
+        Self {
+            sources: Table::new("audio source"),
+            augmentations: Table::new("augmentation"),
+            features: Table::new("feature extractor"),
+            outputs: Table::new("output format"),
+        }
+    }
+
+    /// The stages shout ships with: the `file` source (with the `decode`
+    /// feature), the `gain` augmentation, the `log_mel`, `whisper_log_mel`,
+    /// `mfcc` and `raw_waveform` front ends and the txt, srt, vtt, json and ctm
+    /// writers.
+    pub fn with_builtins() -> Self {
+        let registry = Self::empty();
+
+        #[cfg(feature = "decode")]
+        registry.register_source("file", |_| Ok(Arc::new(FileSource)));
+        registry.register_augmentation("gain", |params| {
+            let db = param(params, "db")?.unwrap_or(0.0);
+            Ok(Arc::new(Gain { db }))
+        });
+
+        registry.register_feature_extractor("log_mel", |dim, _| Ok(Arc::new(LogMel::new(dim))));
+        registry.register_feature_extractor("whisper_log_mel", |dim, _| {
+            Ok(Arc::new(WhisperLogMel::new(dim)))
+        });
+        registry.register_feature_extractor("mfcc", |dim, params| {
+            let n_filters = param(params, "n_filters")?.unwrap_or(40);
+            Ok(Arc::new(Mfcc::new(n_filters, dim)))
+        });
+        registry.register_feature_extractor("raw_waveform", |dim, _| {
+            Ok(Arc::new(RawWaveform::new(dim)))
+        });
+
+        for (name, format) in [
+            ("txt", OutputFormat::Text),
+            ("text", OutputFormat::Text),
+            ("srt", OutputFormat::Srt),
+            ("vtt", OutputFormat::Vtt),
+            ("webvtt", OutputFormat::Vtt),
+            ("json", OutputFormat::Json),
+            ("ctm", OutputFormat::Ctm),
+        ] {
+            registry.register_output_writer(name, move |_| Ok(Arc::new(BuiltinWriter(format))));
+        }
+        registry
+    }
+
+    pub fn register_source<F>(&self, name: &str, factory: F)
+    where
+        F: Fn(&Params) -> Result<Arc<dyn AudioSource>> + Send + Sync + 'static,
+    {
+        self.sources.insert(name, Arc::new(factory));
+    }
+
+    pub fn register_augmentation<F>(&self, name: &str, factory: F)
+    where
+        F: Fn(&Params) -> Result<Arc<dyn Augmentation>> + Send + Sync + 'static,
+    {
+        self.augmentations.insert(name, Arc::new(factory));
+    }
+
+    /// `factory` gets the values per frame the model expects and the parameters.
+    pub fn register_feature_extractor<F>(&self, name: &str, factory: F)
+    where
+        F: Fn(usize, &Params) -> Result<Arc<dyn AudioFrontEnd>> + Send + Sync + 'static,
+    {
+        self.features.insert(name, Arc::new(factory));
+    }
+
+    pub fn register_output_writer<F>(&self, name: &str, factory: F)
+    where
+        F: Fn(&Params) -> Result<Arc<dyn OutputWriter>> + Send + Sync + 'static,
+    {
+        self.outputs.insert(name, Arc::new(factory));
+    }
+
+    pub fn source(&self, name: &str, params: &Params) -> Result<Arc<dyn AudioSource>> {
+        (self.sources.get(name)?)(params)
+    }
+
+    pub fn augmentation(&self, name: &str, params: &Params) -> Result<Arc<dyn Augmentation>> {
+        (self.augmentations.get(name)?)(params)
+    }
+
+    /// The front end registered as `name`, checked to produce `dim` values per frame.
+    pub fn feature_extractor(
+        &self,
+        name: &str,
+        dim: usize,
+        params: &Params,
+    ) -> Result<Arc<dyn AudioFrontEnd>> {
+        let front_end = (self.features.get(name)?)(dim, params)?;
+        if front_end.feature_dim() != dim {
+            return Err(ShoutError::Feature(format!(
+                "front end '{name}' computes {} values per frame, model expects {dim}",
+                front_end.feature_dim()
+            )));
+        }
+        Ok(front_end)
+    }
+
+    pub fn output_writer(&self, name: &str, params: &Params) -> Result<Arc<dyn OutputWriter>> {
+        (self.outputs.get(name)?)(params)
+    }
+
+    pub fn source_names(&self) -> Vec<String> {
+        self.sources.names()
+    }
+
+    pub fn augmentation_names(&self) -> Vec<String> {
+        self.augmentations.names()
+    }
+
+    pub fn feature_extractor_names(&self) -> Vec<String> {
+        self.features.names()
+    }
+
+    pub fn output_writer_names(&self) -> Vec<String> {
+        self.outputs.names()
+    }
+}
+
+/// The process-wide registry, with the built-ins.
+pub fn global() -> &'static Registry {
+    static GLOBAL: OnceLock<Registry> = OnceLock::new();
+    GLOBAL.get_or_init(Registry::with_builtins)
+}
+
+/// `params[key]` as a `T`, `None` if it is not set.
+pub fn param<T: serde::de::DeserializeOwned>(params: &Params, key: &str) -> Result<Option<T>> {
+    match params.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => T::deserialize(v)
+            .map(Some)
+            .map_err(|e| ShoutError::InvalidArgument(format!("parameter '{key}': {e}"))),
+    }
+}
+
+/// A stage as written on the command line: `NAME` or `NAME:{JSON params}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageSpec {
+    pub name: String,
+    pub params: Params,
+}
+
+impl FromStr for StageSpec {
+    type Err = ShoutError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, params) = match s.split_once(':') {
+            Some((name, json)) => {
+                let params = serde_json::from_str(json).map_err(|e| {
+                    ShoutError::InvalidArgument(format!("invalid parameters for '{name}': {e}"))
+                })?;
+                (name, params)
+            }
+            None => (s, Value::Null),
+        };
+        if name.trim().is_empty() {
+            return Err(ShoutError::InvalidArgument(format!(
+                "missing stage name in '{s}'"
+            )));
+        }
+        Ok(Self {
+            name: name.trim().to_string(),
+            params,
+        })
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Built-ins
+// -----------------------------------------------------------------------------
+
+/// Audio files in any format the decoder supports.
+#[cfg(feature = "decode")]
+struct FileSource;
+
+#[cfg(feature = "decode")]
+impl AudioSource for FileSource {
+    fn load(&self, location: &str) -> Result<Vec<f32>> {
+        crate::audio::decoder::decode_to_f32_mono_16k(location)
+    }
+}
+
+/// Scales the audio by `db` decibels.
+struct Gain {
+    db: f32,
+}
+
+impl Augmentation for Gain {
+    fn apply(&self, pcm: &mut Vec<f32>) -> Result<()> {
+        let factor = 10f32.powf(self.db / 20.0);
+        pcm.iter_mut().for_each(|s| *s *= factor);
+        Ok(())
+    }
+}
+
+struct BuiltinWriter(OutputFormat);
+
+impl OutputWriter for BuiltinWriter {
+    fn extension(&self) -> &str {
+        self.0.extension()
+    }
+
+    fn mime_type(&self) -> &str {
+        self.0.mime_type()
+    }
+
+    fn write(&self, w: &mut dyn Write, transcript: &Transcript) -> Result<()> {
+        write_transcript(w, self.0, transcript)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtins_resolve_case_insensitively() {
+        let registry = Registry::with_builtins();
+        assert_eq!(
+            registry
+                .output_writer("SRT", &Value::Null)
+                .unwrap()
+                .extension(),
+            "srt"
+        );
+
+        let mfcc = registry.feature_extractor("mfcc", 13, &serde_json::json!({"n_filters": 40}));
+        assert_eq!(mfcc.unwrap().feature_dim(), 13);
+
+        let err = registry.augmentation("reverb", &Value::Null).err().unwrap();
+        assert!(err.to_string().contains("registered: gain"));
+    }
+
+    #[test]
+    fn registered_stages_replace_builtins() {
+        struct Shout;
+        impl OutputWriter for Shout {
+            fn extension(&self) -> &str {
+                "txt"
+            }
+            fn write(&self, w: &mut dyn Write, transcript: &Transcript) -> Result<()> {
+                Ok(write!(w, "{}", transcript.text().to_uppercase())?)
+            }
+        }
+
+        let registry = Registry::with_builtins();
+        registry.register_output_writer("txt", |_| Ok(Arc::new(Shout)));
+        let transcript = Transcript {
+            segments: vec![crate::transcript::Segment {
+                text: "hallo".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let writer = registry.output_writer("txt", &Value::Null).unwrap();
+        writer.write(&mut out, &transcript).unwrap();
+        assert_eq!(out, b"HALLO");
+    }
+
+    #[test]
+    fn parses_stage_specs() {
+        let spec: StageSpec = r#"gain:{"db": -6}"#.parse().unwrap();
+        assert_eq!(spec.name, "gain");
+        assert_eq!(param::<f32>(&spec.params, "db").unwrap(), Some(-6.0));
+        assert_eq!("json".parse::<StageSpec>().unwrap().params, Value::Null);
+        assert!(":{}".parse::<StageSpec>().is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use super::special_tokens::SpecialTokens;
+use crate::errors::{Result, ShoutError};
+
+/// Byte-level BPE tokenizer (HuggingFace `tokenizer.json`) plus Whisper's special tokens.
+pub struct Tokenizer {
+    inner: tokenizers::Tokenizer,
+    pub special: SpecialTokens,
+}
+
+impl Tokenizer {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let inner = tokenizers::Tokenizer::from_file(path).map_err(|e| {
+            ShoutError::Tokenizer(format!("failed to load tokenizer {}: {e}", path.display()))
+        })?;
+        let special = SpecialTokens::whisper_multilingual(inner.get_vocab_size(true));
+        Ok(Self { inner, special })
+    }
+
+    /// Load from the contents of a `tokenizer.json` (e.g. fetched by a browser).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let inner = tokenizers::Tokenizer::from_bytes(bytes)
+            .map_err(|e| ShoutError::Tokenizer(format!("failed to load tokenizer: {e}")))?;
+        let special = SpecialTokens::whisper_multilingual(inner.get_vocab_size(true));
+        Ok(Self { inner, special })
+    }
+
+    /// Text tokens for `text` (no special tokens added).
+    pub fn encode(&self, text: &str) -> Result<Vec<u32>> {
+        let enc = self
+            .inner
+            .encode(text, false)
+            .map_err(|e| ShoutError::Tokenizer(format!("tokenization failed: {e}")))?;
+        Ok(enc.get_ids().to_vec())
+    }
+
+    /// Text of `tokens`, ignoring special and timestamp tokens.
+    pub fn decode(&self, tokens: &[u32]) -> Result<String> {
+        let text: Vec<u32> = tokens
+            .iter()
+            .copied()
+            .filter(|&t| t < self.special.eot)
+            .collect();
+        self.inner
+            .decode(&text, true)
+            .map_err(|e| ShoutError::Tokenizer(format!("detokenization failed: {e}")))
+    }
+
+    /// Text of a single token (with its leading space, if any).
+    pub fn token_text(&self, token: u32) -> String {
+        self.decode(&[token]).unwrap_or_default()
+    }
+}
@@ -0,0 +1,2 @@
+pub mod bpe;
+pub mod special_tokens;
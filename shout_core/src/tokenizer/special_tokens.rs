@@ -0,0 +1,106 @@
+//! Whisper's special tokens: <|startoftranscript|>, <|de|>, <|transcribe|>,
+//! <|translate|>, <|notimestamps|>, timestamps, etc.
+
+/// Language codes in vocabulary order. The first 99 exist in every multilingual
+/// Whisper vocabulary, `yue` only from large-v3 on.
+pub const LANGUAGES: [&str; 100] = [
+    "en", "zh", "de", "es", "ru", "ko", "fr", "ja", "pt", "tr", "pl", "ca", "nl", "ar", "sv", "it",
+    "id", "hi", "fi", "vi", "he", "uk", "el", "ms", "cs", "ro", "da", "hu", "ta", "no", "th", "ur",
+    "hr", "bg", "lt", "la", "mi", "ml", "cy", "sk", "te", "fa", "lv", "bn", "sr", "az", "sl", "kn",
+    "et", "mk", "br", "eu", "is", "hy", "ne", "mn", "bs", "kk", "sq", "sw", "gl", "mr", "pa", "si",
+    "km", "sn", "yo", "so", "af", "oc", "ka", "be", "tg", "sd", "gu", "am", "yi", "lo", "uz", "fo",
+    "ht", "ps", "tk", "nn", "mt", "sa", "lb", "my", "bo", "tl", "mg", "as", "tt", "haw", "ln",
+    "ha", "ba", "jw", "su", "yue",
+];
+
+/// Number of timestamp tokens (0.00 .. 30.00 s in 20 ms steps).
+pub const N_TIMESTAMPS: u32 = 1501;
+
+/// Ids of the special tokens for one multilingual vocabulary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecialTokens {
+    pub eot: u32,
+    pub sot: u32,
+    pub language_begin: u32,
+    pub n_languages: usize,
+    pub translate: u32,
+    pub transcribe: u32,
+    pub sot_lm: u32,
+    pub sot_prev: u32,
+    pub no_speech: u32,
+    pub no_timestamps: u32,
+    pub timestamp_begin: u32,
+}
+
+impl SpecialTokens {
+    /// Special tokens of the multilingual Whisper vocabulary of size `n_vocab`
+    /// (51865 for v1/v2, 51866 for large-v3 which adds `yue`).
+    pub fn whisper_multilingual(n_vocab: usize) -> Self {
+        let n_languages = (n_vocab + 99)
+            .saturating_sub(51865)
+            .clamp(1, LANGUAGES.len());
+
+        let eot = 50257;
+        let sot = eot + 1;
+        let language_begin = sot + 1;
+        let translate = language_begin + n_languages as u32;
+
+        Self {
+            eot,
+            sot,
+            language_begin,
+            n_languages,
+            translate,
+            transcribe: translate + 1,
+            sot_lm: translate + 2,
+            sot_prev: translate + 3,
+            no_speech: translate + 4,
+            no_timestamps: translate + 5,
+            timestamp_begin: translate + 6,
+        }
+    }
+
+    pub fn language_token(&self, code: &str) -> Option<u32> {
+        LANGUAGES[..self.n_languages]
+            .iter()
+            .position(|&l| l == code)
+            .map(|i| self.language_begin + i as u32)
+    }
+
+    pub fn language_code(&self, token: u32) -> Option<&'static str> {
+        let i = token.checked_sub(self.language_begin)? as usize;
+        LANGUAGES[..self.n_languages].get(i).copied()
+    }
+
+    pub fn is_timestamp(&self, token: u32) -> bool {
+        token >= self.timestamp_begin
+    }
+
+    /// Timestamp token -> offset in milliseconds within the window.
+    pub fn timestamp_ms(&self, token: u32) -> Option<u64> {
+        if self.is_timestamp(token) {
+            Some((token - self.timestamp_begin) as u64 * 20)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_whisper_v2_and_v3_layouts() {
+        let v2 = SpecialTokens::whisper_multilingual(51865);
+        assert_eq!(v2.language_token("de"), Some(50261));
+        assert_eq!(v2.transcribe, 50359);
+        assert_eq!(v2.no_timestamps, 50363);
+        assert_eq!(v2.timestamp_begin + N_TIMESTAMPS, 51865);
+        assert_eq!(v2.language_token("yue"), None);
+
+        let v3 = SpecialTokens::whisper_multilingual(51866);
+        assert_eq!(v3.language_token("yue"), Some(50358));
+        assert_eq!(v3.timestamp_begin + N_TIMESTAMPS, 51866);
+    }
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+/// A single word with its position in the source audio.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Word {
+    pub text: String,
+
+    /// Start of the word in milliseconds from the beginning of the audio.
+    pub start_ms: u64,
+
+    /// End of the word in milliseconds from the beginning of the audio.
+    pub end_ms: u64,
+
+    /// Probability in `[0, 1]` that the word is correct, see [`crate::confidence`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+}
+
+impl Word {
+    pub fn duration_ms(&self) -> u64 {
+        self.end_ms.saturating_sub(self.start_ms)
+    }
+}
+
+/// One decoded token with the log-probability the model assigned to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenScore {
+    pub id: u32,
+    pub text: String,
+    pub logprob: f32,
+}
+
+/// One transcribed segment (usually one decoder window or one timestamp pair).
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Segment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+
+    /// Per-word timings. Empty until the segment has been aligned.
+    #[serde(default)]
+    pub words: Vec<Word>,
+
+    /// Text tokens of this segment (special and timestamp tokens excluded).
+    #[serde(default)]
+    pub tokens: Vec<TokenScore>,
+}
+
+impl Segment {
+    pub fn duration_ms(&self) -> u64 {
+        self.end_ms.saturating_sub(self.start_ms)
+    }
+
+    /// Mean token log-probability, `None` if the segment carries no token scores.
+    pub fn avg_logprob(&self) -> Option<f32> {
+        if self.tokens.is_empty() {
+            return None;
+        }
+        Some(self.tokens.iter().map(|t| t.logprob).sum::<f32>() / self.tokens.len() as f32)
+    }
+
+    /// Geometric mean of the token probabilities, in `[0, 1]`.
+    pub fn confidence(&self) -> Option<f32> {
+        self.avg_logprob().map(f32::exp)
+    }
+}
+
+/// Where a transcript came from.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TranscriptMetadata {
+    pub model: Option<String>,
+    pub audio_path: Option<String>,
+    pub audio_duration_ms: Option<u64>,
+    pub sample_rate: Option<u32>,
+}
+
+/// Full result of transcribing one input.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Transcript {
+    /// Language code the segments were decoded in (e.g. `"de"`).
+    pub language: Option<String>,
+    pub segments: Vec<Segment>,
+    #[serde(default)]
+    pub metadata: TranscriptMetadata,
+}
+
+impl Transcript {
+    /// All segment texts joined with single spaces.
+    pub fn text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|s| s.text.trim())
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Confidence over all tokens of all segments (geometric mean of token probabilities).
+    pub fn confidence(&self) -> Option<f32> {
+        let (sum, n) = self
+            .segments
+            .iter()
+            .flat_map(|s| s.tokens.iter())
+            .fold((0.0f32, 0usize), |(sum, n), t| (sum + t.logprob, n + 1));
+        (n > 0).then(|| (sum / n as f32).exp())
+    }
+}
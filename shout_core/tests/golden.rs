@@ -0,0 +1,216 @@
+//! Golden-output regression tests: the mel spectrogram and transcript of each
+//! fixture in `tests/fixtures/golden/` must match the recorded expectations
+//! within tolerance.
+//!
+//! After an intended change of results, re-record with
+//! `SHOUT_BLESS=1 cargo test -p shout_core --test golden` and commit the
+//! updated JSON files. Missing expectations are recorded on the first run.
+//!
+//! The transcript test needs a model: `SHOUT_GOLDEN_MODEL=<dir>`, or the
+//! directory `tests/fixtures/golden/model`. It is skipped without one.
+#![cfg(feature = "native")]
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use candle_core::Device;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use shout_core::audio::decoder::decode_to_f32_mono_16k;
+use shout_core::features::{MelSpec, log_mel};
+use shout_core::inference::load_transcriber;
+
+/// Largest allowed difference of any mel summary value.
+const MEL_TOLERANCE: f32 = 1e-3;
+
+/// Largest allowed difference of the transcript confidence.
+const CONFIDENCE_TOLERANCE: f32 = 0.02;
+
+const N_MELS: usize = 80;
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden")
+}
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(fixtures_dir())
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "wav"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+fn bless() -> bool {
+    std::env::var_os("SHOUT_BLESS").is_some_and(|v| v != "0")
+}
+
+/// Compare `actual` with the expectations in `file`, recording them instead
+/// if blessing or if there are none yet.
+fn check<T, F>(file: &str, actual: &BTreeMap<String, T>, compare: F) -> Result<()>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+    F: Fn(&str, &T, &T) -> Vec<String>,
+{
+    let path = fixtures_dir().join(file);
+    if bless() || !path.exists() {
+        fs::write(&path, serde_json::to_string_pretty(actual)? + "\n")?;
+        eprintln!("Recorded {}", path.display());
+        return Ok(());
+    }
+
+    let text = fs::read_to_string(&path)?;
+    let expected: BTreeMap<String, T> =
+        serde_json::from_str(&text).with_context(|| format!("Invalid {}", path.display()))?;
+    let mut problems = Vec::new();
+    for (key, actual) in actual {
+        match expected.get(key) {
+            Some(expected) => problems.extend(compare(key, expected, actual)),
+            None => problems.push(format!("{key}: no expectation (re-run with SHOUT_BLESS=1)")),
+        }
+    }
+    assert!(
+        problems.is_empty(),
+        "golden mismatch in {file}:\n{}",
+        problems.join("\n")
+    );
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MelSummary {
+    n_frames: usize,
+    /// Mean of each frame over all mel bins.
+    frame_means: Vec<f32>,
+    /// Mean of each mel bin over all frames.
+    bin_means: Vec<f32>,
+    /// Of the raw f32 values; informational, a mismatch alone is not an error.
+    sha256: String,
+}
+
+fn summarize(path: &Path) -> Result<MelSummary> {
+    let pcm = decode_to_f32_mono_16k(path)?;
+    let mel = MelSpec::from(log_mel(&pcm, N_MELS));
+    let frames: Vec<&[f32]> = mel.data.chunks(mel.n_mels).collect();
+
+    let frame_means = frames
+        .iter()
+        .map(|f| f.iter().sum::<f32>() / f.len() as f32)
+        .collect();
+    let bin_means = (0..mel.n_mels)
+        .map(|b| frames.iter().map(|f| f[b]).sum::<f32>() / frames.len().max(1) as f32)
+        .collect();
+    let bytes: Vec<u8> = mel.data.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+    Ok(MelSummary {
+        n_frames: mel.n_frames,
+        frame_means,
+        bin_means,
+        sha256: format!("{:x}", Sha256::digest(&bytes)),
+    })
+}
+
+fn max_difference(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y).abs())
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn mel_spectrograms_match() -> Result<()> {
+    let mut actual = BTreeMap::new();
+    for path in fixtures() {
+        actual.insert(name(&path), summarize(&path)?);
+    }
+
+    check("mel.json", &actual, |key, expected: &MelSummary, actual| {
+        if expected.n_frames != actual.n_frames {
+            let (a, e) = (actual.n_frames, expected.n_frames);
+            return vec![format!("{key}: {a} frames, expected {e}")];
+        }
+        let mut problems = Vec::new();
+        for (what, e, a) in [
+            ("frame means", &expected.frame_means, &actual.frame_means),
+            ("bin means", &expected.bin_means, &actual.bin_means),
+        ] {
+            let diff = max_difference(e, a);
+            if diff > MEL_TOLERANCE {
+                problems.push(format!("{key}: {what} differ by up to {diff}"));
+            }
+        }
+        if problems.is_empty() && expected.sha256 != actual.sha256 {
+            eprintln!("{key}: mel values changed within tolerance");
+        }
+        problems
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TranscriptSummary {
+    text: String,
+    confidence: Option<f32>,
+}
+
+fn golden_model() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("SHOUT_GOLDEN_MODEL") {
+        return Some(PathBuf::from(dir));
+    }
+    let bundled = fixtures_dir().join("model");
+    bundled.join("config.json").exists().then_some(bundled)
+}
+
+#[test]
+fn transcripts_match() -> Result<()> {
+    let Some(model_dir) = golden_model() else {
+        eprintln!("No golden model (set SHOUT_GOLDEN_MODEL); skipping transcript test");
+        return Ok(());
+    };
+    let mut transcriber = load_transcriber(&model_dir, &Device::Cpu)?;
+
+    let mut actual = BTreeMap::new();
+    for path in fixtures() {
+        let transcript = transcriber.transcribe_file(&path)?;
+        let summary = TranscriptSummary {
+            text: transcript.text(),
+            confidence: transcript.confidence(),
+        };
+        actual.insert(name(&path), summary);
+    }
+
+    // Expectations are per model, since a different model legitimately
+    // transcribes differently.
+    let model_name = model_dir
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned();
+    let file = format!("transcripts.{model_name}.json");
+    check(
+        &file,
+        &actual,
+        |key, expected: &TranscriptSummary, actual| {
+            let mut problems = Vec::new();
+            if expected.text != actual.text {
+                problems.push(format!(
+                    "{key}: text {:?}, expected {:?}",
+                    actual.text, expected.text
+                ));
+            }
+            if let (Some(e), Some(a)) = (expected.confidence, actual.confidence)
+                && (e - a).abs() > CONFIDENCE_TOLERANCE
+            {
+                problems.push(format!("{key}: confidence {a:.3}, expected {e:.3}"));
+            }
+            problems
+        },
+    )
+}
@@ -0,0 +1,23 @@
+//! `shout_core` used as a library, the way an application embedding it
+//! would: decoding a file and computing its features through the public
+//! `audio` modules.
+#![cfg(feature = "native")]
+
+use shout_core::audio::decoder::decode_to_f32_mono_16k;
+use shout_core::audio::mel::log_mel;
+use shout_core::model::test_tiny::{synthetic_speech, wav_bytes};
+
+#[test]
+fn decodes_a_file_and_computes_its_log_mel() {
+    let path = std::env::temp_dir().join(format!("shout_library_{}.wav", std::process::id()));
+    let pcm = synthetic_speech(1.0);
+    std::fs::write(&path, wav_bytes(&pcm, 16_000)).unwrap();
+
+    let decoded = decode_to_f32_mono_16k(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(decoded.len(), pcm.len());
+
+    let mel = log_mel(&decoded, 80);
+    assert_eq!(mel.ncols(), 80);
+    assert!(mel.nrows() > 0 && mel.iter().all(|v| v.is_finite()));
+}
@@ -0,0 +1,179 @@
+//! The whole pipeline on the generated `test-tiny` model. Its text is
+//! meaningless, but everything derived from the decoder's output (word
+//! timings, confidences, output formats) must still be there.
+#![cfg(feature = "native")]
+
+use std::fs;
+use std::path::PathBuf;
+
+use candle_core::Device;
+
+use shout_core::inference::{
+    Calibration, DecodeOptions, ShoutModel, Transcriber, load_transcriber,
+};
+use shout_core::model::test_tiny::write_test_tiny;
+use shout_core::output::ctm::write_ctm;
+use shout_core::output::json::write_json;
+use shout_core::transcript::Transcript;
+
+/// A fresh `test-tiny` model in a directory of its own, removed on drop.
+struct TestTiny(PathBuf);
+
+impl TestTiny {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("shout_{name}_{}", std::process::id()));
+        write_test_tiny(&dir).unwrap();
+        Self(dir)
+    }
+
+    /// A transcriber that keeps whatever the random model decodes: one greedy
+    /// pass with the quality checks that would reject it turned off.
+    fn transcriber(&self) -> Transcriber<ShoutModel> {
+        let mut transcriber = load_transcriber(&self.0, &Device::Cpu).unwrap();
+        transcriber.options = DecodeOptions {
+            temperatures: vec![0.0],
+            max_tokens: 32,
+            compression_ratio_threshold: None,
+            logprob_threshold: None,
+            no_speech_threshold: None,
+            max_tokens_per_second: None,
+            hallucination_phrases: Vec::new(),
+            ..DecodeOptions::default()
+        };
+        transcriber
+    }
+
+    fn transcribe(&self) -> Transcript {
+        self.transcriber()
+            .transcribe_file(self.0.join("sample.wav"))
+            .unwrap()
+    }
+}
+
+impl Drop for TestTiny {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn segments_carry_timed_words() {
+    let transcript = TestTiny::new("timed_words").transcribe();
+
+    let duration_ms = transcript.metadata.audio_duration_ms.unwrap();
+    assert!(!transcript.segments.is_empty(), "test-tiny decoded nothing");
+    assert!(
+        transcript.segments.iter().any(|s| !s.words.is_empty()),
+        "no segment was aligned"
+    );
+    for segment in &transcript.segments {
+        for word in &segment.words {
+            assert!(!word.text.is_empty());
+            assert!(
+                word.start_ms <= word.end_ms && word.end_ms <= duration_ms,
+                "{word:?}"
+            );
+        }
+        assert!(
+            segment
+                .words
+                .windows(2)
+                .all(|w| w[0].start_ms <= w[1].start_ms)
+        );
+    }
+}
+
+#[test]
+fn words_carry_calibrated_confidences() {
+    let model = TestTiny::new("confidences");
+    let mut transcriber = model.transcriber();
+    transcriber.calibration = Some(Calibration {
+        slope: 2.0,
+        intercept: 1.0,
+    });
+    let transcript = transcriber
+        .transcribe_file(model.0.join("sample.wav"))
+        .unwrap();
+
+    let words: Vec<_> = transcript.segments.iter().flat_map(|s| &s.words).collect();
+    assert!(!words.is_empty(), "no segment was aligned");
+    for word in words {
+        let confidence = word
+            .confidence
+            .unwrap_or_else(|| panic!("{word:?} has no confidence"));
+        assert!((0.0..=1.0).contains(&confidence));
+    }
+}
+
+#[test]
+fn json_output_lists_the_timed_words() {
+    let transcript = TestTiny::new("json").transcribe();
+    let mut out = Vec::new();
+    write_json(&mut out, &transcript).unwrap();
+    let doc: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    let words: Vec<&serde_json::Value> = doc["segments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|s| s["words"].as_array().unwrap())
+        .collect();
+    assert!(!words.is_empty(), "no words in {doc}");
+    for word in words {
+        assert!(word["start_ms"].as_u64().unwrap() <= word["end_ms"].as_u64().unwrap());
+        assert!(word["confidence"].is_f64(), "{word}");
+    }
+}
+
+#[test]
+fn ctm_uses_the_aligned_word_times() {
+    let transcript = TestTiny::new("ctm").transcribe();
+    let mut out = Vec::new();
+    write_ctm(&mut out, &transcript, "sample", "1").unwrap();
+
+    let expected: Vec<String> = transcript
+        .segments
+        .iter()
+        .flat_map(|s| &s.words)
+        .filter(|w| !w.text.trim().is_empty())
+        .map(|w| {
+            let seconds = |ms: u64| ms as f64 / 1000.0;
+            format!("{:.3} {:.3}", seconds(w.start_ms), seconds(w.duration_ms()))
+        })
+        .collect();
+    let times: Vec<String> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            line.split(' ')
+                .skip(2)
+                .take(2)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(times, expected);
+}
+
+#[test]
+fn precomputed_features_transcribe_like_the_samples() {
+    let model = TestTiny::new("features");
+    let mut transcriber = model.transcriber();
+    let pcm = transcriber
+        .decode_file(&model.0.join("sample.wav"))
+        .unwrap();
+    let mel = transcriber.front_end().features(&pcm).unwrap();
+
+    let spans = |transcript: Transcript| -> Vec<(u64, u64, String)> {
+        transcript
+            .segments
+            .into_iter()
+            .map(|s| (s.start_ms, s.end_ms, s.text))
+            .collect()
+    };
+    let from_features = spans(transcriber.transcribe_features(&pcm, &mel).unwrap());
+    let from_samples = spans(transcriber.transcribe_pcm(&pcm).unwrap());
+    assert!(!from_samples.is_empty());
+    assert_eq!(from_features, from_samples);
+}
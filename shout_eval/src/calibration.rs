@@ -0,0 +1,301 @@
+//! Calibration of word confidences: whether words scored 0.8 are right about
+//! 80 % of the time, measured as a reliability curve and the expected
+//! calibration error (ECE), and a Platt scaler fitted to make it so.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::{EditOp, align};
+use crate::normalize::Normalizer;
+
+/// Floor of confidences before taking their log, so that 0 stays finite.
+const MIN_CONFIDENCE: f64 = 1e-12;
+
+/// A hypothesis word's confidence and whether the word was right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceSample {
+    pub confidence: f64,
+    pub correct: bool,
+}
+
+/// Samples of the hypothesis `words` (text and confidence) of one utterance.
+/// A word is correct if all of its normalized pieces align as hits; words that
+/// normalize to nothing are left out.
+pub fn word_samples(
+    reference: &str,
+    words: &[(&str, f64)],
+    normalizer: Normalizer,
+) -> Vec<ConfidenceSample> {
+    let reference: Vec<String> = normalizer
+        .apply(reference)
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    let mut pieces = Vec::new();
+    let mut owner = Vec::new();
+    for (i, (text, _)) in words.iter().enumerate() {
+        for piece in normalizer.apply(text).split_whitespace() {
+            pieces.push(piece.to_string());
+            owner.push(i);
+        }
+    }
+
+    let mut correct: Vec<Option<bool>> = vec![None; words.len()];
+    let hypothesis_ops = align(&reference, &pieces)
+        .pairs
+        .into_iter()
+        .filter(|p| p.hypothesis.is_some())
+        .map(|p| p.op);
+    for (op, &i) in hypothesis_ops.zip(&owner) {
+        correct[i] = Some(correct[i].unwrap_or(true) && op == EditOp::Hit);
+    }
+    words
+        .iter()
+        .zip(correct)
+        .filter_map(|(&(_, confidence), correct)| {
+            Some(ConfidenceSample {
+                confidence,
+                correct: correct?,
+            })
+        })
+        .collect()
+}
+
+/// Words whose confidence falls in `[lower, upper)`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReliabilityBin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+    pub mean_confidence: f64,
+    pub accuracy: f64,
+}
+
+/// Reliability curve and calibration errors of a set of samples.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CalibrationReport {
+    pub samples: usize,
+    pub bins: Vec<ReliabilityBin>,
+    /// Mean gap between confidence and accuracy, weighted by bin size.
+    pub ece: f64,
+    /// Largest gap of any non-empty bin.
+    pub mce: f64,
+}
+
+impl CalibrationReport {
+    /// Bin the samples into `n_bins` equally wide confidence bins.
+    pub fn new(samples: &[ConfidenceSample], n_bins: usize) -> Self {
+        let n_bins = n_bins.max(1);
+        let mut sums = vec![(0usize, 0.0f64, 0usize); n_bins];
+        for s in samples {
+            let bin = ((s.confidence.clamp(0.0, 1.0) * n_bins as f64) as usize).min(n_bins - 1);
+            sums[bin].0 += 1;
+            sums[bin].1 += s.confidence;
+            sums[bin].2 += usize::from(s.correct);
+        }
+
+        let (mut ece, mut mce) = (0.0f64, 0.0f64);
+        let bins = sums
+            .into_iter()
+            .enumerate()
+            .map(|(i, (count, confidence, correct))| {
+                let d = count.max(1) as f64;
+                let bin = ReliabilityBin {
+                    lower: i as f64 / n_bins as f64,
+                    upper: (i + 1) as f64 / n_bins as f64,
+                    count,
+                    mean_confidence: confidence / d,
+                    accuracy: correct as f64 / d,
+                };
+                if count > 0 {
+                    let gap = (bin.accuracy - bin.mean_confidence).abs();
+                    ece += gap * count as f64 / samples.len() as f64;
+                    mce = mce.max(gap);
+                }
+                bin
+            })
+            .collect();
+        Self {
+            samples: samples.len(),
+            bins,
+            ece,
+            mce,
+        }
+    }
+}
+
+impl fmt::Display for CalibrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} words, ECE {:.4}, MCE {:.4}",
+            self.samples, self.ece, self.mce
+        )?;
+        writeln!(
+            f,
+            "  {:<11} {:>7} {:>10} {:>9}",
+            "confidence", "words", "mean conf", "accuracy"
+        )?;
+        for bin in self.bins.iter().filter(|b| b.count > 0) {
+            writeln!(
+                f,
+                "  {:.2}-{:.2}   {:>7} {:>10.3} {:>9.3}",
+                bin.lower, bin.upper, bin.count, bin.mean_confidence, bin.accuracy
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `p = sigmoid(slope * ln(confidence) + intercept)`, fitted on uncalibrated
+/// confidences (`exp` of the mean token log-probability), so that it maps the
+/// mean log-probability directly, like `shout_core::confidence::Calibration`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlattScaler {
+    pub slope: f64,
+    pub intercept: f64,
+}
+
+impl PlattScaler {
+    /// Fit by maximum likelihood with Platt's smoothed targets. `None` unless
+    /// the samples contain both correct and incorrect words.
+    pub fn fit(samples: &[ConfidenceSample]) -> Option<Self> {
+        let positives = samples.iter().filter(|s| s.correct).count() as f64;
+        let negatives = samples.len() as f64 - positives;
+        if positives == 0.0 || negatives == 0.0 {
+            return None;
+        }
+        let (hi, lo) = (
+            (positives + 1.0) / (positives + 2.0),
+            1.0 / (negatives + 2.0),
+        );
+        let data: Vec<(f64, f64)> = samples
+            .iter()
+            .map(|s| (log_score(s.confidence), if s.correct { hi } else { lo }))
+            .collect();
+
+        let loss = |a: f64, b: f64| -> f64 {
+            data.iter()
+                .map(|&(x, t)| {
+                    let z = a * x + b;
+                    t * softplus(-z) + (1.0 - t) * softplus(z)
+                })
+                .sum()
+        };
+
+        // Newton's method with step halving.
+        let (mut a, mut b) = (0.0, ((positives + 1.0) / (negatives + 1.0)).ln());
+        let mut current = loss(a, b);
+        for _ in 0..100 {
+            let (mut ga, mut gb, mut haa, mut hab, mut hbb) = (0.0, 0.0, 1e-12, 0.0, 1e-12);
+            for &(x, t) in &data {
+                let p = sigmoid(a * x + b);
+                let w = p * (1.0 - p);
+                ga += (p - t) * x;
+                gb += p - t;
+                haa += w * x * x;
+                hab += w * x;
+                hbb += w;
+            }
+            let det = haa * hbb - hab * hab;
+            let (da, db) = ((hbb * ga - hab * gb) / det, (haa * gb - hab * ga) / det);
+
+            let mut step = 1.0;
+            while step > 1e-10 {
+                let next = loss(a - step * da, b - step * db);
+                if next <= current {
+                    a -= step * da;
+                    b -= step * db;
+                    current = next;
+                    break;
+                }
+                step /= 2.0;
+            }
+            if step <= 1e-10 || (step * da).abs().max((step * db).abs()) < 1e-10 {
+                break;
+            }
+        }
+        Some(Self {
+            slope: a,
+            intercept: b,
+        })
+    }
+
+    /// Calibrated confidence of an uncalibrated one.
+    pub fn apply(&self, confidence: f64) -> f64 {
+        sigmoid(self.slope * log_score(confidence) + self.intercept)
+    }
+}
+
+fn log_score(confidence: f64) -> f64 {
+    confidence.max(MIN_CONFIDENCE).ln()
+}
+
+fn sigmoid(z: f64) -> f64 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+/// `ln(1 + e^z)` without overflow.
+fn softplus(z: f64) -> f64 {
+    z.max(0.0) + (-z.abs()).exp().ln_1p()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(confidence: f64, correct: bool) -> ConfidenceSample {
+        ConfidenceSample {
+            confidence,
+            correct,
+        }
+    }
+
+    #[test]
+    fn marks_words_correct_by_alignment() {
+        let words = [("Guten", 0.9), ("Morgan,", 0.4), ("Welt", 0.8), ("—", 0.1)];
+        let samples = word_samples("guten morgen welt", &words, Normalizer::Whisper);
+        let correct: Vec<bool> = samples.iter().map(|s| s.correct).collect();
+        assert_eq!(correct, vec![true, false, true]);
+    }
+
+    #[test]
+    fn measures_calibration_error() {
+        let samples = [
+            sample(0.95, true),
+            sample(0.95, true),
+            sample(0.25, false),
+            sample(0.25, true),
+        ];
+        let report = CalibrationReport::new(&samples, 10);
+        assert_eq!(report.bins[9].count, 2);
+        assert!((report.bins[2].accuracy - 0.5).abs() < 1e-9);
+        // Half the words are off by 0.05, half by 0.25.
+        assert!((report.ece - 0.15).abs() < 1e-9);
+        assert!((report.mce - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fit_recovers_logistic_relation() {
+        // Per score, the share of correct words follows sigmoid(2 x + 1).
+        let mut samples = Vec::new();
+        for i in 0..40 {
+            let x = -4.0 + i as f64 * 0.1;
+            let n_correct = (sigmoid(2.0 * x + 1.0) * 200.0).round() as usize;
+            for j in 0..200 {
+                samples.push(sample(x.exp(), j < n_correct));
+            }
+        }
+        let fit = PlattScaler::fit(&samples).unwrap();
+        assert!((fit.slope - 2.0).abs() < 0.05, "{fit:?}");
+        assert!((fit.intercept - 1.0).abs() < 0.05, "{fit:?}");
+
+        let calibrated: Vec<_> = samples
+            .iter()
+            .map(|s| sample(fit.apply(s.confidence), s.correct))
+            .collect();
+        let before = CalibrationReport::new(&samples, 10).ece;
+        assert!(CalibrationReport::new(&calibrated, 10).ece < before);
+    }
+}
@@ -0,0 +1,159 @@
+//! WER sliced by manifest metadata (speaker, gender, age, source, ...), so
+//! a group doing much worse than the rest is not hidden in the aggregate.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::metrics::ErrorCounts;
+use crate::report::UtteranceResult;
+
+/// Pseudo-key bucketing utterances by the `duration_ms` metadata field.
+pub const DURATION: &str = "duration";
+
+const DURATION_BUCKETS: &[(u64, &str)] = &[
+    (5_000, "<5s"),
+    (10_000, "5-10s"),
+    (20_000, "10-20s"),
+    (30_000, "20-30s"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupScore {
+    pub value: String,
+    pub utterances: usize,
+    pub words: ErrorCounts,
+}
+
+impl GroupScore {
+    pub fn wer(&self) -> f64 {
+        self.words.rate()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupBreakdown {
+    pub key: String,
+    /// Groups with at least the minimum count, worst WER first.
+    pub groups: Vec<GroupScore>,
+    /// Groups left out for having too few utterances.
+    pub below_threshold: usize,
+}
+
+/// The group of `result` under `key`, if it has one.
+pub fn group_of(result: &UtteranceResult, key: &str) -> Option<String> {
+    if key == DURATION {
+        let ms: f64 = result.metadata.get("duration_ms")?.parse().ok()?;
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .find(|(limit, _)| ms < *limit as f64)
+            .map_or(">=30s", |(_, name)| name);
+        return Some(bucket.to_string());
+    }
+    result
+        .metadata
+        .get(key)
+        .filter(|v| !v.trim().is_empty())
+        .cloned()
+}
+
+/// Breakdown of `results` by `key`; `None` if no utterance has that field.
+pub fn breakdown(
+    results: &[UtteranceResult],
+    key: &str,
+    min_count: usize,
+) -> Option<GroupBreakdown> {
+    let mut by_value: BTreeMap<String, GroupScore> = BTreeMap::new();
+    for result in results {
+        let Some(value) = group_of(result, key) else {
+            continue;
+        };
+        let score = by_value.entry(value.clone()).or_insert_with(|| GroupScore {
+            value,
+            utterances: 0,
+            words: ErrorCounts::default(),
+        });
+        score.utterances += 1;
+        score.words += result.words;
+    }
+    if by_value.is_empty() {
+        return None;
+    }
+
+    let total = by_value.len();
+    let mut groups: Vec<GroupScore> = by_value
+        .into_values()
+        .filter(|g| g.utterances >= min_count)
+        .collect();
+    groups.sort_by(|a, b| b.wer().total_cmp(&a.wer()));
+
+    Some(GroupBreakdown {
+        key: key.to_string(),
+        below_threshold: total - groups.len(),
+        groups,
+    })
+}
+
+impl fmt::Display for GroupBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "WER by {}:", self.key)?;
+        for group in &self.groups {
+            writeln!(
+                f,
+                "  {:<20} {:>6.2}%  ({} utts, {} words)",
+                group.value,
+                group.wer() * 100.0,
+                group.utterances,
+                group.words.reference_len()
+            )?;
+        }
+        if self.below_threshold > 0 {
+            writeln!(
+                f,
+                "  ({} group(s) below the minimum size left out)",
+                self.below_threshold
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(metadata: &[(&str, &str)], errors: usize) -> UtteranceResult {
+        let mut r = UtteranceResult::score("a.wav", "a b c d", "a b c d");
+        r.words.hits -= errors;
+        r.words.substitutions += errors;
+        r.metadata = metadata
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        r
+    }
+
+    #[test]
+    fn groups_sorted_worst_first_with_threshold() {
+        let results = vec![
+            result(&[("gender", "female")], 0),
+            result(&[("gender", "female")], 1),
+            result(&[("gender", "male")], 2),
+            result(&[("gender", "male")], 2),
+            result(&[("gender", "other")], 4),
+            result(&[], 4),
+        ];
+        let b = breakdown(&results, "gender", 2).unwrap();
+        let values: Vec<&str> = b.groups.iter().map(|g| g.value.as_str()).collect();
+        assert_eq!(values, ["male", "female"]);
+        assert_eq!(b.below_threshold, 1);
+        assert!(breakdown(&results, "speaker", 1).is_none());
+    }
+
+    #[test]
+    fn duration_buckets() {
+        let r = result(&[("duration_ms", "7300")], 0);
+        assert_eq!(group_of(&r, DURATION).as_deref(), Some("5-10s"));
+        let r = result(&[("duration_ms", "45000")], 0);
+        assert_eq!(group_of(&r, DURATION).as_deref(), Some(">=30s"));
+    }
+}
@@ -0,0 +1,285 @@
+//! Recall and precision of a list of keywords (product names, entities,
+//! domain terms), for when getting those right matters more than the WER.
+//!
+//! Keywords may be phrases. Both keywords and texts are normalized with the
+//! evaluation's normalizer and matched as whole word sequences.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::normalize::Normalizer;
+
+/// A hypothesis word with its time in the audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedWord {
+    pub text: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// A keyword found in a hypothesis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Detection {
+    pub keyword: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// The reference contains this keyword at least as often as the
+    /// hypothesis did up to here.
+    pub correct: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct KeywordCounts {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+}
+
+impl KeywordCounts {
+    pub fn recall(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+
+    pub fn precision(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    fn add(&mut self, other: &KeywordCounts) {
+        self.true_positives += other.true_positives;
+        self.false_positives += other.false_positives;
+        self.false_negatives += other.false_negatives;
+    }
+}
+
+/// 1 for 0/0: no occurrences means nothing was missed (or wrongly found).
+fn ratio(n: usize, d: usize) -> f64 {
+    if d == 0 { 1.0 } else { n as f64 / d as f64 }
+}
+
+pub struct Keywords {
+    normalizer: Normalizer,
+    /// Keyword as given, and its normalized words.
+    phrases: Vec<(String, Vec<String>)>,
+}
+
+impl Keywords {
+    pub fn new<I, S>(keywords: I, normalizer: Normalizer) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let phrases = keywords
+            .into_iter()
+            .map(Into::into)
+            .filter_map(|k: String| {
+                let words = tokens(&normalizer.apply(&k));
+                (!words.is_empty()).then_some((k, words))
+            })
+            .collect();
+        Self {
+            normalizer,
+            phrases,
+        }
+    }
+
+    /// One keyword or phrase per line; blank lines and `#` comments are skipped.
+    pub fn load<P: AsRef<Path>>(path: P, normalizer: Normalizer) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read keywords: {}", path.display()))?;
+        let lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_string);
+        Ok(Self::new(lines, normalizer))
+    }
+
+    pub fn len(&self) -> usize {
+        self.phrases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.phrases.is_empty()
+    }
+
+    /// Occurrences of each keyword in `text`, in keyword order.
+    pub fn count(&self, text: &str) -> Vec<usize> {
+        let words = tokens(&self.normalizer.apply(text));
+        self.phrases
+            .iter()
+            .map(|(_, phrase)| occurrences(&words, phrase).len())
+            .collect()
+    }
+
+    /// Keywords in the timed hypothesis `words`, checked against `reference`.
+    pub fn detect(&self, reference: &str, words: &[TimedWord]) -> Vec<Detection> {
+        // Normalizing may split or drop words; keep each piece's timing.
+        let mut pieces: Vec<(String, u64, u64)> = Vec::new();
+        for word in words {
+            for piece in tokens(&self.normalizer.apply(&word.text)) {
+                pieces.push((piece, word.start_ms, word.end_ms));
+            }
+        }
+        let texts: Vec<String> = pieces.iter().map(|p| p.0.clone()).collect();
+        let in_reference = self.count(reference);
+
+        let mut detections = Vec::new();
+        for ((keyword, phrase), &allowed) in self.phrases.iter().zip(&in_reference) {
+            for (n, start) in occurrences(&texts, phrase).into_iter().enumerate() {
+                detections.push(Detection {
+                    keyword: keyword.clone(),
+                    start_ms: pieces[start].1,
+                    end_ms: pieces[start + phrase.len() - 1].2,
+                    correct: n < allowed,
+                });
+            }
+        }
+        detections.sort_by_key(|d| d.start_ms);
+        detections
+    }
+}
+
+fn tokens(text: &str) -> Vec<String> {
+    text.split_whitespace().map(str::to_string).collect()
+}
+
+/// Start indices of non-overlapping occurrences of `phrase` in `words`.
+fn occurrences(words: &[String], phrase: &[String]) -> Vec<usize> {
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i + phrase.len() <= words.len() {
+        if words[i..i + phrase.len()] == *phrase {
+            found.push(i);
+            i += phrase.len();
+        } else {
+            i += 1;
+        }
+    }
+    found
+}
+
+/// Keyword counts summed over an evaluation.
+#[derive(Debug, Clone, Default)]
+pub struct KeywordReport {
+    pub per_keyword: BTreeMap<String, KeywordCounts>,
+}
+
+impl KeywordReport {
+    /// Add one utterance: occurrences in the reference vs. the hypothesis.
+    pub fn add(&mut self, keywords: &Keywords, reference: &str, hypothesis: &str) {
+        let expected = keywords.count(reference);
+        let found = keywords.count(hypothesis);
+        for (((keyword, _), &r), &h) in keywords.phrases.iter().zip(&expected).zip(&found) {
+            let hits = r.min(h);
+            self.per_keyword
+                .entry(keyword.clone())
+                .or_default()
+                .add(&KeywordCounts {
+                    true_positives: hits,
+                    false_positives: h - hits,
+                    false_negatives: r - hits,
+                });
+        }
+    }
+
+    /// Counts over all keywords (micro-averaged).
+    pub fn total(&self) -> KeywordCounts {
+        let mut total = KeywordCounts::default();
+        for counts in self.per_keyword.values() {
+            total.add(counts);
+        }
+        total
+    }
+}
+
+impl fmt::Display for KeywordReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Keywords:")?;
+        writeln!(
+            f,
+            "  {:<24} {:>5} {:>5} {:>5} {:>8} {:>9}",
+            "keyword", "TP", "FP", "FN", "recall", "precision"
+        )?;
+        let (all, total) = ("(all)".to_string(), self.total());
+        for (keyword, c) in self.per_keyword.iter().chain([(&all, &total)]) {
+            writeln!(
+                f,
+                "  {:<24} {:>5} {:>5} {:>5} {:>7.1}% {:>8.1}%",
+                keyword,
+                c.true_positives,
+                c.false_positives,
+                c.false_negatives,
+                c.recall() * 100.0,
+                c.precision() * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timed(text: &str) -> Vec<TimedWord> {
+        text.split_whitespace()
+            .enumerate()
+            .map(|(i, w)| TimedWord {
+                text: w.to_string(),
+                start_ms: i as u64 * 500,
+                end_ms: i as u64 * 500 + 400,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_recall_and_precision() {
+        let keywords = Keywords::new(["Shout Pro", "Berlin"], Normalizer::Whisper);
+        let mut report = KeywordReport::default();
+        report.add(
+            &keywords,
+            "Buy Shout Pro in Berlin.",
+            "buy shout pro in burling",
+        );
+        report.add(&keywords, "Berlin calling", "Berlin, Berlin calling");
+
+        let berlin = &report.per_keyword["Berlin"];
+        assert_eq!(
+            (
+                berlin.true_positives,
+                berlin.false_positives,
+                berlin.false_negatives
+            ),
+            (1, 1, 1)
+        );
+        assert_eq!(report.per_keyword["Shout Pro"].recall(), 1.0);
+        assert_eq!(report.total().recall(), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn detections_carry_timestamps() {
+        let keywords = Keywords::new(["shout pro"], Normalizer::Whisper);
+        let detections = keywords.detect("no keyword here", &timed("try Shout Pro today"));
+        assert_eq!(
+            detections,
+            vec![Detection {
+                keyword: "shout pro".into(),
+                start_ms: 500,
+                end_ms: 1400,
+                correct: false,
+            }]
+        );
+    }
+}
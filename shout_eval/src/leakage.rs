@@ -0,0 +1,102 @@
+//! Train/test leakage: utterances of the test set whose voice or recording
+//! also occurs in the training set.
+//!
+//! Two signals are checked. Manifests that name speakers must not share any
+//! across the splits. Independently, every test utterance is compared with
+//! every training utterance by the cosine similarity of their embeddings
+//! (see `shout embed`); a pair above the threshold is most likely the same
+//! recording, possibly re-encoded or trimmed, or the same speaker in the
+//! same conditions.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::manifest::ReferenceEntry;
+
+/// Default similarity above which a pair is reported.
+pub const DEFAULT_THRESHOLD: f32 = 0.95;
+
+/// A training and a test utterance that are suspiciously alike.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimilarPair {
+    /// Index into the training entries.
+    pub train: usize,
+
+    /// Index into the test entries.
+    pub test: usize,
+    pub similarity: f32,
+}
+
+/// Pairs of unit-length embeddings across the splits with at least
+/// `threshold` cosine similarity, most similar first. Each test utterance is
+/// reported with at most its `per_test` closest training utterances.
+pub fn similar_pairs(
+    train: &[Vec<f32>],
+    test: &[Vec<f32>],
+    threshold: f32,
+    per_test: usize,
+) -> Vec<SimilarPair> {
+    let mut pairs = Vec::new();
+    for (j, t) in test.iter().enumerate() {
+        let mut close: Vec<SimilarPair> = train
+            .iter()
+            .enumerate()
+            .map(|(i, r)| SimilarPair {
+                train: i,
+                test: j,
+                similarity: dot(r, t),
+            })
+            .filter(|p| p.similarity >= threshold)
+            .collect();
+        close.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        close.truncate(per_test);
+        pairs.extend(close);
+    }
+    pairs.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    pairs
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Speakers (the `speaker` field) present in both splits, with their number
+/// of test utterances.
+pub fn shared_speakers(
+    train: &[ReferenceEntry],
+    test: &[ReferenceEntry],
+) -> BTreeMap<String, usize> {
+    let train: BTreeSet<String> = train.iter().filter_map(|e| e.field("speaker")).collect();
+    let mut shared = BTreeMap::new();
+    for speaker in test.iter().filter_map(|e| e.field("speaker")) {
+        if train.contains(&speaker) {
+            *shared.entry(speaker).or_insert(0) += 1;
+        }
+    }
+    shared
+}
+
+/// Audio files listed in both splits.
+pub fn shared_audio<'a>(train: &[ReferenceEntry], test: &'a [ReferenceEntry]) -> Vec<&'a str> {
+    let train: BTreeSet<&str> = train.iter().map(|e| e.audio_path.as_str()).collect();
+    test.iter()
+        .map(|e| e.audio_path.as_str())
+        .filter(|p| train.contains(p))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_close_pairs_most_similar_first() {
+        let train = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.6, 0.8]];
+        let test = vec![vec![0.0, 1.0], vec![-1.0, 0.0], vec![0.8, 0.6]];
+        let pairs = similar_pairs(&train, &test, 0.9, 1);
+
+        assert_eq!(pairs.len(), 2);
+        assert_eq!((pairs[0].train, pairs[0].test), (1, 0));
+        assert_eq!((pairs[1].train, pairs[1].test), (2, 2));
+        assert!((pairs[1].similarity - 0.96).abs() < 1e-6);
+    }
+}
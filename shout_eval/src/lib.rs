@@ -0,0 +1,20 @@
+//! Evaluation of transcripts against references. Deliberately light on
+//! dependencies so the training loop can link it as well as the CLI.
+
+pub mod calibration;
+pub mod groups;
+pub mod keywords;
+pub mod leakage;
+pub mod manifest;
+pub mod manifest_filter;
+pub mod manifest_split;
+pub mod manifest_stats;
+pub mod metrics;
+pub mod nist;
+pub mod normalize;
+pub mod report;
+pub mod review;
+pub mod stats;
+pub mod streaming;
+pub mod taxonomy;
+pub mod visualize;
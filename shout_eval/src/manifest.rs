@@ -0,0 +1,217 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Error, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One line of a test manifest: audio and its reference transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferenceEntry {
+    pub audio_path: String,
+    pub text: String,
+
+    /// Every other field of the line: `duration_ms`, `speaker`, `language`,
+    /// `gender`, `age`, a free-form `tags` object, ...
+    #[serde(flatten)]
+    pub metadata: BTreeMap<String, Value>,
+}
+
+impl ReferenceEntry {
+    /// A metadata field, or failing that an entry of `tags`.
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        self.metadata
+            .get(name)
+            .or_else(|| self.metadata.get("tags")?.as_object()?.get(name))
+    }
+
+    /// A field as a string: `audio_path`, `text`, a metadata field or a tag
+    /// (`None` if missing or null).
+    pub fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "audio_path" => Some(self.audio_path.clone()),
+            "text" => Some(self.text.clone()),
+            _ => value_string(self.value(name)?),
+        }
+    }
+
+    pub fn duration_ms(&self) -> Option<u64> {
+        self.metadata.get("duration_ms")?.as_u64()
+    }
+
+    /// Metadata values as plain strings, with the tags alongside the other
+    /// fields (a field wins over a tag of the same name); nulls are dropped.
+    pub fn metadata_strings(&self) -> BTreeMap<String, String> {
+        let tags = self
+            .metadata
+            .get("tags")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten();
+        let fields = self.metadata.iter().filter(|&(key, _)| key != "tags");
+        tags.chain(fields)
+            .filter_map(|(key, value)| Some((key.clone(), value_string(value)?)))
+            .collect()
+    }
+}
+
+fn value_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Entries of a JSONL manifest (`{"audio_path": ..., "text": ...}` per line),
+/// at most `max` of them. Audio paths are returned as stored; resolve them with
+/// `shout_config`'s `audio_path`.
+pub fn read_references<P: AsRef<Path>>(path: P, max: usize) -> Result<Vec<ReferenceEntry>> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).with_context(|| format!("Failed to open manifest: {}", path.display()))?;
+
+    let mut out = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        if out.len() == max {
+            break;
+        }
+        let line =
+            line.with_context(|| format!("Failed to read line {} of {}", i + 1, path.display()))?;
+        // Manifests saved by Windows editors may start with a byte order mark.
+        let line = line.strip_prefix('\u{feff}').unwrap_or(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(line)
+            .with_context(|| format!("Invalid manifest line {} in {}", i + 1, path.display()))?;
+        out.push(entry);
+    }
+    Ok(out)
+}
+
+/// Write `entries` as a JSONL manifest, replacing `path` only once the whole
+/// file has been written. Metadata fields are kept as they were read.
+pub fn write_references<P: AsRef<Path>>(path: P, entries: &[ReferenceEntry]) -> Result<()> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let file = File::create(&tmp)
+        .with_context(|| format!("Failed to create manifest: {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    for entry in entries {
+        serde_json::to_writer(&mut out, entry)?;
+        writeln!(out)?;
+    }
+    out.flush()?;
+    drop(out);
+    fs::rename(&tmp, path)
+        .with_context(|| format!("Failed to replace manifest: {}", path.display()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+/// A condition on one field of a manifest entry, written `FIELD OP VALUE`
+/// with OP one of `=`, `!=`, `<`, `<=`, `>`, `>=` or `~` (contains), e.g.
+/// `duration_ms>15000` or `speaker=spk_12`. Values that both parse as numbers
+/// compare numerically, others as strings. Entries without the field never match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryFilter {
+    field: String,
+    op: FilterOp,
+    value: String,
+}
+
+impl EntryFilter {
+    pub fn matches(&self, entry: &ReferenceEntry) -> bool {
+        let Some(actual) = entry.field(&self.field) else {
+            return false;
+        };
+        if self.op == FilterOp::Contains {
+            return actual.contains(&self.value);
+        }
+        let ordering = match (actual.trim().parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.total_cmp(&b),
+            _ => actual.as_str().cmp(self.value.as_str()),
+        };
+        match self.op {
+            FilterOp::Eq => ordering == Ordering::Equal,
+            FilterOp::Ne => ordering != Ordering::Equal,
+            FilterOp::Lt => ordering == Ordering::Less,
+            FilterOp::Le => ordering != Ordering::Greater,
+            FilterOp::Gt => ordering == Ordering::Greater,
+            FilterOp::Ge => ordering != Ordering::Less,
+            FilterOp::Contains => unreachable!("handled above"),
+        }
+    }
+}
+
+impl FromStr for EntryFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        const OPS: [(&str, FilterOp); 7] = [
+            ("!=", FilterOp::Ne),
+            ("<=", FilterOp::Le),
+            (">=", FilterOp::Ge),
+            ("=", FilterOp::Eq),
+            ("<", FilterOp::Lt),
+            (">", FilterOp::Gt),
+            ("~", FilterOp::Contains),
+        ];
+        // The leftmost operator; at the same position the two-character one.
+        let found = OPS
+            .iter()
+            .filter_map(|&(token, op)| s.find(token).map(|at| (at, token, op)))
+            .min_by_key(|&(at, token, _)| (at, std::cmp::Reverse(token.len())));
+        let Some((at, token, op)) = found else {
+            bail!("invalid filter '{s}' (expected FIELD OP VALUE, e.g. duration_ms>15000)");
+        };
+        let field = s[..at].trim();
+        if field.is_empty() {
+            bail!("invalid filter '{s}': missing field name");
+        }
+        Ok(Self {
+            field: field.to_string(),
+            op,
+            value: s[at + token.len()..].trim().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_compare_numbers_and_strings() {
+        let entry: ReferenceEntry = serde_json::from_str(
+            r#"{"audio_path": "a.wav", "text": "guten Tag", "duration_ms": 9000, "speaker": "s2",
+                "tags": {"accent": "bayerisch"}}"#,
+        )
+        .unwrap();
+        let matches = |f: &str| f.parse::<EntryFilter>().unwrap().matches(&entry);
+
+        assert!(matches("duration_ms<10000"));
+        assert!(matches("duration_ms >= 9000"));
+        assert!(!matches("duration_ms>9000"));
+        assert!(matches("speaker!=s1"));
+        assert!(matches("text~Tag"));
+        assert!(!matches("snr<10"));
+        assert!(matches("accent=bayerisch"));
+        assert!("<5".parse::<EntryFilter>().is_err());
+    }
+}
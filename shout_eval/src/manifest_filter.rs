@@ -0,0 +1,180 @@
+//! Cleaning a manifest before training: dropping entries whose audio is too
+//! short or long, whose transcript is empty, too short or implausibly dense
+//! for the audio, contains characters the model should not learn, or carries
+//! a rejected quality tag.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::manifest::ReferenceEntry;
+
+/// Which entries to keep. Rules that need a duration pass entries without
+/// one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterRules {
+    pub min_duration_ms: Option<u64>,
+    pub max_duration_ms: Option<u64>,
+    pub max_chars_per_sec: Option<f64>,
+    /// Shortest transcript in characters; 1 drops empty transcripts.
+    pub min_text_chars: usize,
+    pub disallowed_chars: BTreeSet<char>,
+    /// Entries with any of these in `quality_tags` are dropped.
+    pub rejected_tags: BTreeSet<String>,
+}
+
+impl Default for FilterRules {
+    fn default() -> Self {
+        Self {
+            min_duration_ms: None,
+            max_duration_ms: None,
+            max_chars_per_sec: None,
+            min_text_chars: 1,
+            disallowed_chars: BTreeSet::new(),
+            rejected_tags: BTreeSet::new(),
+        }
+    }
+}
+
+/// Why an entry was dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    TooShort { duration_ms: u64 },
+    TooLong { duration_ms: u64 },
+    TooFast { chars_per_sec: f64 },
+    ShortText { chars: usize },
+    DisallowedChar(char),
+    QualityTag(String),
+}
+
+impl Rejection {
+    /// The rule that dropped the entry, for counting.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Rejection::TooShort { .. } => "too_short",
+            Rejection::TooLong { .. } => "too_long",
+            Rejection::TooFast { .. } => "too_fast",
+            Rejection::ShortText { .. } => "short_text",
+            Rejection::DisallowedChar(_) => "disallowed_char",
+            Rejection::QualityTag(_) => "quality_tag",
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::TooShort { duration_ms } => write!(f, "too short ({duration_ms} ms)"),
+            Rejection::TooLong { duration_ms } => write!(f, "too long ({duration_ms} ms)"),
+            Rejection::TooFast { chars_per_sec } => {
+                write!(f, "too fast ({chars_per_sec:.1} chars/s)")
+            }
+            Rejection::ShortText { chars } => write!(f, "text too short ({chars} chars)"),
+            Rejection::DisallowedChar(c) => write!(f, "disallowed character {c:?}"),
+            Rejection::QualityTag(tag) => write!(f, "quality tag {tag:?}"),
+        }
+    }
+}
+
+impl FilterRules {
+    /// The first rule `entry` breaks, or `None` to keep it.
+    pub fn check(&self, entry: &ReferenceEntry) -> Option<Rejection> {
+        let text = entry.text.trim();
+        let chars = text.chars().count();
+        if chars < self.min_text_chars {
+            return Some(Rejection::ShortText { chars });
+        }
+        if let Some(c) = text.chars().find(|c| self.disallowed_chars.contains(c)) {
+            return Some(Rejection::DisallowedChar(c));
+        }
+        if let Some(tag) = quality_tags(entry).find(|t| self.rejected_tags.contains(*t)) {
+            return Some(Rejection::QualityTag(tag.to_string()));
+        }
+
+        let duration_ms = entry.duration_ms()?;
+        if self.min_duration_ms.is_some_and(|min| duration_ms < min) {
+            return Some(Rejection::TooShort { duration_ms });
+        }
+        if self.max_duration_ms.is_some_and(|max| duration_ms > max) {
+            return Some(Rejection::TooLong { duration_ms });
+        }
+        if duration_ms > 0 {
+            let chars_per_sec = chars as f64 / (duration_ms as f64 / 1000.0);
+            if self
+                .max_chars_per_sec
+                .is_some_and(|max| chars_per_sec > max)
+            {
+                return Some(Rejection::TooFast { chars_per_sec });
+            }
+        }
+        None
+    }
+}
+
+/// The entry's `quality_tags`: a list, or a string separated by commas,
+/// semicolons, `|` or whitespace.
+fn quality_tags(entry: &ReferenceEntry) -> impl Iterator<Item = &str> {
+    let (list, text) = match entry.value("quality_tags") {
+        Some(Value::Array(items)) => (items.as_slice(), ""),
+        Some(Value::String(s)) => (&[][..], s.as_str()),
+        _ => (&[][..], ""),
+    };
+    let separators = |c: char| c == ',' || c == ';' || c == '|' || c.is_whitespace();
+    list.iter()
+        .filter_map(Value::as_str)
+        .chain(text.split(separators))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(text: &str, duration_ms: u64, tags: &str) -> ReferenceEntry {
+        serde_json::from_value(serde_json::json!({
+            "audio_path": "a.wav",
+            "text": text,
+            "duration_ms": duration_ms,
+            "quality_tags": tags,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reports_the_first_broken_rule() {
+        let rules = FilterRules {
+            min_duration_ms: Some(500),
+            max_duration_ms: Some(30_000),
+            max_chars_per_sec: Some(25.0),
+            disallowed_chars: "<>".chars().collect(),
+            rejected_tags: ["noisy".to_string()].into(),
+            ..FilterRules::default()
+        };
+        assert_eq!(rules.check(&entry("hallo welt", 2000, "")), None);
+        assert_eq!(
+            rules.check(&entry(" ", 2000, "")),
+            Some(Rejection::ShortText { chars: 0 })
+        );
+        assert_eq!(
+            rules.check(&entry("a <b>", 2000, "")),
+            Some(Rejection::DisallowedChar('<'))
+        );
+        assert_eq!(
+            rules.check(&entry("hallo", 2000, "clipping, noisy")),
+            Some(Rejection::QualityTag("noisy".into()))
+        );
+        assert_eq!(
+            rules.check(&entry("hallo", 100, "")),
+            Some(Rejection::TooShort { duration_ms: 100 })
+        );
+        assert_eq!(
+            rules
+                .check(&entry(&"a".repeat(60), 2000, ""))
+                .unwrap()
+                .kind(),
+            "too_fast"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Partitioning a manifest into train, dev and test splits, optionally with
+//! every speaker in exactly one split so test scores are not flattered by
+//! voices the model was trained on.
+
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+
+use crate::manifest::ReferenceEntry;
+
+/// How large dev and test are; train gets the rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitSize {
+    /// Shares of train, dev and test, normalized to their sum. Measured in
+    /// audio when every entry has a `duration_ms`, in entries otherwise.
+    Ratio([f64; 3]),
+
+    /// Hours of audio in dev and test.
+    Hours { dev: f64, test: f64 },
+}
+
+/// Train, dev and test. Entries are grouped by the field `group_by` (e.g.
+/// `speaker`) and each group goes to one split; entries without the field
+/// form groups of their own. Groups are placed in an order that follows
+/// from a hash of their key and `seed`, so a split is the same on every run
+/// and adding entries moves few of the old ones.
+pub fn split(
+    entries: Vec<ReferenceEntry>,
+    size: SplitSize,
+    group_by: Option<&str>,
+    seed: u64,
+) -> Result<[Vec<ReferenceEntry>; 3]> {
+    let durations: Vec<Option<u64>> = entries.iter().map(ReferenceEntry::duration_ms).collect();
+    let timed = durations.iter().all(Option::is_some);
+    let weight = |i: usize| {
+        if timed {
+            durations[i].unwrap_or(0) as f64
+        } else {
+            1.0
+        }
+    };
+    let total: f64 = (0..entries.len()).map(weight).sum();
+
+    let [dev_target, test_target] = match size {
+        SplitSize::Ratio(shares) => {
+            let sum: f64 = shares.iter().sum();
+            if shares.iter().any(|&s| s < 0.0) || sum <= 0.0 {
+                bail!("Split ratios must be non-negative and not all zero");
+            }
+            [total * shares[1] / sum, total * shares[2] / sum]
+        }
+        SplitSize::Hours { dev, test } => {
+            if !timed {
+                bail!("Splitting by hours needs a duration_ms on every entry");
+            }
+            [dev * 3_600_000.0, test * 3_600_000.0]
+        }
+    };
+
+    let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let key = group_by
+            .and_then(|field| entry.field(field))
+            .unwrap_or_else(|| format!("\0{}", entry.audio_path));
+        groups.entry(key).or_default().push(i);
+    }
+    let mut groups: Vec<(u64, Vec<usize>)> = groups
+        .into_iter()
+        .map(|(key, members)| (fnv1a(seed, &key), members))
+        .collect();
+    groups.sort_unstable_by_key(|(hash, members)| (*hash, members[0]));
+
+    // Test first, then dev, each until it reaches its target.
+    let mut assignment = vec![0usize; entries.len()];
+    let mut filled = [0.0f64; 3];
+    for (_, members) in &groups {
+        let split = if filled[2] < test_target {
+            2
+        } else if filled[1] < dev_target {
+            1
+        } else {
+            0
+        };
+        for &i in members {
+            assignment[i] = split;
+            filled[split] += weight(i);
+        }
+    }
+
+    let mut splits: [Vec<ReferenceEntry>; 3] = Default::default();
+    for (entry, split) in entries.into_iter().zip(assignment) {
+        splits[split].push(entry);
+    }
+    Ok(splits)
+}
+
+/// FNV-1a of `seed` and `s`, stable across platforms and Rust versions
+/// unlike `DefaultHasher`.
+fn fnv1a(seed: u64, s: &str) -> u64 {
+    seed.to_le_bytes()
+        .iter()
+        .chain(s.as_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+            (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_speakers_in_one_split() {
+        let entries: Vec<ReferenceEntry> = (0..200)
+            .map(|i| {
+                serde_json::from_value(serde_json::json!({
+                    "audio_path": format!("{i}.wav"),
+                    "text": "hallo",
+                    "duration_ms": 1000,
+                    "speaker": format!("spk{}", i % 40),
+                }))
+                .unwrap()
+            })
+            .collect();
+
+        let [train, dev, test] = split(
+            entries,
+            SplitSize::Ratio([0.8, 0.1, 0.1]),
+            Some("speaker"),
+            7,
+        )
+        .unwrap();
+        assert_eq!(train.len() + dev.len() + test.len(), 200);
+        assert_eq!((dev.len(), test.len()), (20, 20));
+
+        let speakers = |split: &[ReferenceEntry]| -> Vec<String> {
+            split.iter().filter_map(|e| e.field("speaker")).collect()
+        };
+        let train_speakers = speakers(&train);
+        assert!(speakers(&test).iter().all(|s| !train_speakers.contains(s)));
+        assert!(speakers(&dev).iter().all(|s| !train_speakers.contains(s)));
+    }
+}
@@ -0,0 +1,201 @@
+//! Summaries of a manifest for sanity checks after an import: how much audio
+//! there is, how long the utterances and transcripts are, and which entries
+//! speak implausibly fast or slow for their transcript (usually a wrong
+//! transcript or a truncated file).
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::manifest::ReferenceEntry;
+
+/// Upper bounds in seconds of the duration histogram's buckets; the last
+/// bucket is open-ended.
+pub const DURATION_BUCKETS: [f64; 7] = [1.0, 2.0, 5.0, 10.0, 15.0, 20.0, 30.0];
+
+/// Modified z-score beyond which a characters-per-second rate is an outlier
+/// (Iglewicz and Hoaglin's 3.5).
+pub const CPS_OUTLIER_Z: f64 = 3.5;
+
+/// Utterance count and audio length of a group of entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Totals {
+    pub utterances: usize,
+    /// Entries with a `duration_ms`.
+    pub with_duration: usize,
+    pub duration_ms: u64,
+}
+
+impl Totals {
+    fn add(&mut self, duration_ms: Option<u64>) {
+        self.utterances += 1;
+        if let Some(ms) = duration_ms {
+            self.with_duration += 1;
+            self.duration_ms += ms;
+        }
+    }
+
+    pub fn hours(&self) -> f64 {
+        self.duration_ms as f64 / 3_600_000.0
+    }
+}
+
+/// Spread of a quantity over the entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Distribution {
+    pub min: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl Distribution {
+    /// `None` for no values.
+    pub fn of(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        Some(Self {
+            min: sorted[0],
+            median: quantile(&sorted, 0.5),
+            p90: quantile(&sorted, 0.9),
+            max: sorted[sorted.len() - 1],
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+        })
+    }
+}
+
+/// An entry whose speaking rate is far from the rest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateOutlier {
+    /// Index in the manifest.
+    pub index: usize,
+    pub audio_path: String,
+    pub chars_per_sec: f64,
+    /// Modified z-score; negative for slow entries.
+    pub z: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ManifestStats {
+    pub totals: Totals,
+    pub speakers: usize,
+    /// Entries per bucket of [`DURATION_BUCKETS`], plus one for longer ones.
+    pub duration_histogram: Vec<usize>,
+    /// Transcript length in characters.
+    pub text_chars: Option<Distribution>,
+    pub chars_per_sec: Option<Distribution>,
+    /// Most extreme first.
+    pub rate_outliers: Vec<RateOutlier>,
+    /// Totals by the `split` field; empty if no entry has one.
+    pub splits: BTreeMap<String, Totals>,
+}
+
+impl ManifestStats {
+    pub fn compute(entries: &[ReferenceEntry]) -> Self {
+        let mut stats = Self {
+            duration_histogram: vec![0; DURATION_BUCKETS.len() + 1],
+            ..Self::default()
+        };
+        let mut speakers = BTreeSet::new();
+        let mut text_chars = Vec::with_capacity(entries.len());
+        let mut rates = Vec::new();
+
+        for (index, entry) in entries.iter().enumerate() {
+            let duration_ms = entry.duration_ms();
+            stats.totals.add(duration_ms);
+            if let Some(split) = entry.field("split") {
+                stats.splits.entry(split).or_default().add(duration_ms);
+            }
+            if let Some(speaker) = entry.field("speaker") {
+                speakers.insert(speaker);
+            }
+
+            let chars = entry.text.trim().chars().count();
+            text_chars.push(chars as f64);
+            if let Some(ms) = duration_ms.filter(|&ms| ms > 0) {
+                let secs = ms as f64 / 1000.0;
+                let bucket = DURATION_BUCKETS.iter().position(|&b| secs < b);
+                stats.duration_histogram[bucket.unwrap_or(DURATION_BUCKETS.len())] += 1;
+                rates.push((index, chars as f64 / secs));
+            }
+        }
+
+        stats.speakers = speakers.len();
+        stats.text_chars = Distribution::of(&text_chars);
+        let rate_values: Vec<f64> = rates.iter().map(|&(_, r)| r).collect();
+        stats.chars_per_sec = Distribution::of(&rate_values);
+        stats.rate_outliers = rate_outliers(&rates, entries);
+        stats
+    }
+}
+
+/// Rates whose modified z-score (deviation from the median in units of the
+/// median absolute deviation) exceeds [`CPS_OUTLIER_Z`].
+fn rate_outliers(rates: &[(usize, f64)], entries: &[ReferenceEntry]) -> Vec<RateOutlier> {
+    let mut sorted: Vec<f64> = rates.iter().map(|&(_, r)| r).collect();
+    if sorted.is_empty() {
+        return Vec::new();
+    }
+    sorted.sort_by(f64::total_cmp);
+    let median = quantile(&sorted, 0.5);
+    let mut deviations: Vec<f64> = sorted.iter().map(|r| (r - median).abs()).collect();
+    deviations.sort_by(f64::total_cmp);
+    let mad = quantile(&deviations, 0.5);
+    if mad == 0.0 {
+        return Vec::new();
+    }
+
+    let mut outliers: Vec<RateOutlier> = rates
+        .iter()
+        .map(|&(index, rate)| (index, rate, 0.6745 * (rate - median) / mad))
+        .filter(|&(_, _, z)| z.abs() > CPS_OUTLIER_Z)
+        .map(|(index, chars_per_sec, z)| RateOutlier {
+            index,
+            audio_path: entries[index].audio_path.clone(),
+            chars_per_sec,
+            z,
+        })
+        .collect();
+    outliers.sort_by(|a, b| b.z.abs().total_cmp(&a.z.abs()));
+    outliers
+}
+
+/// Linear interpolation between the closest ranks of sorted `values`.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(text: &str, duration_ms: u64, split: &str) -> ReferenceEntry {
+        serde_json::from_value(serde_json::json!({
+            "audio_path": format!("{text}.wav"),
+            "text": text,
+            "duration_ms": duration_ms,
+            "split": split,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn summarizes_and_flags_fast_speech() {
+        let mut entries: Vec<ReferenceEntry> = (0..20)
+            .map(|i| entry(&"a".repeat(14 + i % 3), 1000 + (i as u64 % 3) * 50, "train"))
+            .collect();
+        entries.push(entry(&"b".repeat(300), 1500, "dev"));
+
+        let stats = ManifestStats::compute(&entries);
+        assert_eq!(stats.totals.utterances, 21);
+        assert_eq!(stats.duration_histogram[1], 21);
+        assert_eq!(stats.splits["dev"].utterances, 1);
+        assert_eq!(stats.rate_outliers.len(), 1);
+        assert_eq!(stats.rate_outliers[0].index, 20);
+        assert!(stats.rate_outliers[0].z > 0.0);
+    }
+}
@@ -0,0 +1,250 @@
+//! Word and character error rates via Levenshtein alignment.
+//!
+//! Texts are compared as given (split on whitespace for words); normalize
+//! case and punctuation beforehand if they should not count as errors.
+
+use std::ops::{Add, AddAssign};
+
+use serde::{Deserialize, Serialize};
+
+/// Edit counts of one alignment, or summed over a corpus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ErrorCounts {
+    pub hits: usize,
+    pub substitutions: usize,
+    pub deletions: usize,
+    pub insertions: usize,
+}
+
+impl ErrorCounts {
+    pub fn errors(&self) -> usize {
+        self.substitutions + self.deletions + self.insertions
+    }
+
+    /// Length of the reference.
+    pub fn reference_len(&self) -> usize {
+        self.hits + self.substitutions + self.deletions
+    }
+
+    /// Length of the hypothesis.
+    pub fn hypothesis_len(&self) -> usize {
+        self.hits + self.substitutions + self.insertions
+    }
+
+    /// Errors per reference unit. An empty reference counts as one unit, so
+    /// any insertion against it is an error rate of at least 1.
+    pub fn rate(&self) -> f64 {
+        self.errors() as f64 / self.reference_len().max(1) as f64
+    }
+}
+
+impl Add for ErrorCounts {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            hits: self.hits + other.hits,
+            substitutions: self.substitutions + other.substitutions,
+            deletions: self.deletions + other.deletions,
+            insertions: self.insertions + other.insertions,
+        }
+    }
+}
+
+impl AddAssign for ErrorCounts {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditOp {
+    Hit,
+    Substitution,
+    /// A reference unit missing from the hypothesis.
+    Deletion,
+    /// A hypothesis unit not in the reference.
+    Insertion,
+}
+
+/// One column of an alignment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AlignedPair<T> {
+    pub op: EditOp,
+    pub reference: Option<T>,
+    pub hypothesis: Option<T>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Alignment<T> {
+    pub pairs: Vec<AlignedPair<T>>,
+    pub counts: ErrorCounts,
+}
+
+/// Minimum edit alignment of `hypothesis` to `reference` (unit costs). Among
+/// equally cheap alignments, substitutions are preferred over a deletion plus
+/// an insertion.
+pub fn align<T: PartialEq + Clone>(reference: &[T], hypothesis: &[T]) -> Alignment<T> {
+    let (n, m) = (reference.len(), hypothesis.len());
+
+    // cost[i][j]: edits to turn reference[..i] into hypothesis[..j].
+    let mut cost = vec![vec![0usize; m + 1]; n + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in cost[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let diagonal = cost[i - 1][j - 1] + usize::from(reference[i - 1] != hypothesis[j - 1]);
+            cost[i][j] = diagonal.min(cost[i - 1][j] + 1).min(cost[i][j - 1] + 1);
+        }
+    }
+
+    let mut pairs = Vec::with_capacity(n.max(m));
+    let mut counts = ErrorCounts::default();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        let op = if i > 0 && j > 0 {
+            let same = reference[i - 1] == hypothesis[j - 1];
+            if cost[i][j] == cost[i - 1][j - 1] + usize::from(!same) {
+                if same {
+                    EditOp::Hit
+                } else {
+                    EditOp::Substitution
+                }
+            } else if cost[i][j] == cost[i - 1][j] + 1 {
+                EditOp::Deletion
+            } else {
+                EditOp::Insertion
+            }
+        } else if i > 0 {
+            EditOp::Deletion
+        } else {
+            EditOp::Insertion
+        };
+
+        let (r, h) = match op {
+            EditOp::Hit | EditOp::Substitution => {
+                i -= 1;
+                j -= 1;
+                (Some(reference[i].clone()), Some(hypothesis[j].clone()))
+            }
+            EditOp::Deletion => {
+                i -= 1;
+                (Some(reference[i].clone()), None)
+            }
+            EditOp::Insertion => {
+                j -= 1;
+                (None, Some(hypothesis[j].clone()))
+            }
+        };
+        match op {
+            EditOp::Hit => counts.hits += 1,
+            EditOp::Substitution => counts.substitutions += 1,
+            EditOp::Deletion => counts.deletions += 1,
+            EditOp::Insertion => counts.insertions += 1,
+        }
+        pairs.push(AlignedPair {
+            op,
+            reference: r,
+            hypothesis: h,
+        });
+    }
+    pairs.reverse();
+
+    Alignment { pairs, counts }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace().map(str::to_string).collect()
+}
+
+/// Characters with runs of whitespace collapsed to a single space.
+fn chars(text: &str) -> Vec<char> {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect()
+}
+
+pub fn word_alignment(reference: &str, hypothesis: &str) -> Alignment<String> {
+    align(&words(reference), &words(hypothesis))
+}
+
+pub fn char_alignment(reference: &str, hypothesis: &str) -> Alignment<char> {
+    align(&chars(reference), &chars(hypothesis))
+}
+
+/// Word error counts; `.rate()` is the WER.
+pub fn wer(reference: &str, hypothesis: &str) -> ErrorCounts {
+    word_alignment(reference, hypothesis).counts
+}
+
+/// Character error counts; `.rate()` is the CER.
+pub fn cer(reference: &str, hypothesis: &str) -> ErrorCounts {
+    char_alignment(reference, hypothesis).counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_each_kind_of_error() {
+        // "the mat" / "mat today": two substitutions, preferred over a
+        // deletion plus an insertion.
+        let counts = wer("the cat sat on the mat", "the cat sat on mat today");
+        assert_eq!(counts.hits, 4);
+        assert_eq!(counts.substitutions, 2);
+        assert_eq!((counts.deletions, counts.insertions), (0, 0));
+        assert!((counts.rate() - 2.0 / 6.0).abs() < 1e-9);
+
+        let counts = wer("guten morgen", "guten abend");
+        assert_eq!((counts.hits, counts.substitutions), (1, 1));
+    }
+
+    #[test]
+    fn alignment_pairs_reference_and_hypothesis() {
+        let alignment = word_alignment("a b c", "a x c d");
+        let ops: Vec<EditOp> = alignment.pairs.iter().map(|p| p.op).collect();
+        assert_eq!(
+            ops,
+            vec![
+                EditOp::Hit,
+                EditOp::Substitution,
+                EditOp::Hit,
+                EditOp::Insertion
+            ]
+        );
+        assert_eq!(alignment.pairs[1].reference.as_deref(), Some("b"));
+        assert_eq!(alignment.pairs[1].hypothesis.as_deref(), Some("x"));
+        assert_eq!(alignment.pairs[3].reference, None);
+    }
+
+    #[test]
+    fn character_error_rate_ignores_extra_whitespace() {
+        let counts = cer("haus  maus", "haus laus");
+        assert_eq!(counts.errors(), 1);
+        assert_eq!(counts.reference_len(), 9);
+    }
+
+    #[test]
+    fn empty_inputs() {
+        assert_eq!(wer("", "").rate(), 0.0);
+        assert_eq!(wer("", "hallo").rate(), 1.0);
+        assert_eq!(wer("hallo", "").deletions, 1);
+    }
+
+    #[test]
+    fn corpus_counts_add_up() {
+        let mut total = ErrorCounts::default();
+        total += wer("a b", "a c");
+        total += wer("d", "d");
+        assert_eq!(total.reference_len(), 3);
+        assert_eq!(total.errors(), 1);
+    }
+}
@@ -0,0 +1,350 @@
+//! NIST formats for interop with sclite-based workflows: CTM hypotheses,
+//! STM references, and sclite-style `.sys` (per-speaker summary) and `.pra`
+//! (per-utterance alignment) reports.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use crate::metrics::{Alignment, ErrorCounts, align, word_alignment};
+use crate::normalize::Normalizer;
+use crate::report::UtteranceResult;
+use crate::visualize::render;
+
+/// STM transcript of regions sclite leaves out of scoring.
+const IGNORE_SEGMENT: &str = "ignore_time_segment_in_scoring";
+
+/// One word of a CTM file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CtmWord {
+    pub file: String,
+    pub channel: String,
+    pub start: f64,
+    pub duration: f64,
+    pub word: String,
+    pub confidence: Option<f64>,
+}
+
+/// One reference segment of an STM file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StmSegment {
+    pub file: String,
+    pub channel: String,
+    pub speaker: String,
+    pub start: f64,
+    pub end: f64,
+    /// The optional `<...>` label field, without the brackets.
+    pub label: Option<String>,
+    pub text: String,
+}
+
+fn read_lines(path: &Path) -> Result<Vec<(usize, String)>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut out = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && !trimmed.starts_with(";;") {
+            out.push((i + 1, trimmed.to_string()));
+        }
+    }
+    Ok(out)
+}
+
+fn parse_time(field: &str, line: usize, path: &Path) -> Result<f64> {
+    field.parse().with_context(|| {
+        format!(
+            "Invalid time '{field}' on line {line} of {}",
+            path.display()
+        )
+    })
+}
+
+pub fn read_ctm<P: AsRef<Path>>(path: P) -> Result<Vec<CtmWord>> {
+    let path = path.as_ref();
+    let mut out = Vec::new();
+    for (n, line) in read_lines(path)? {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 5 {
+            bail!(
+                "Expected at least 5 fields on line {n} of {}",
+                path.display()
+            );
+        }
+        out.push(CtmWord {
+            file: fields[0].to_string(),
+            channel: fields[1].to_string(),
+            start: parse_time(fields[2], n, path)?,
+            duration: parse_time(fields[3], n, path)?,
+            word: fields[4].to_string(),
+            confidence: fields.get(5).and_then(|c| c.parse().ok()),
+        });
+    }
+    Ok(out)
+}
+
+pub fn read_stm<P: AsRef<Path>>(path: P) -> Result<Vec<StmSegment>> {
+    let path = path.as_ref();
+    let mut out = Vec::new();
+    for (n, line) in read_lines(path)? {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 5 {
+            bail!(
+                "Expected at least 5 fields on line {n} of {}",
+                path.display()
+            );
+        }
+        let mut rest = &fields[5..];
+        let label = rest
+            .first()
+            .and_then(|f| f.strip_prefix('<')?.strip_suffix('>'));
+        if label.is_some() {
+            rest = &rest[1..];
+        }
+        out.push(StmSegment {
+            file: fields[0].to_string(),
+            channel: fields[1].to_string(),
+            speaker: fields[2].to_string(),
+            start: parse_time(fields[3], n, path)?,
+            end: parse_time(fields[4], n, path)?,
+            label: label.map(str::to_string),
+            text: rest.join(" "),
+        });
+    }
+    Ok(out)
+}
+
+/// A scored utterance, the unit of the sclite reports.
+#[derive(Debug, Clone)]
+pub struct ScoredUtterance {
+    pub id: String,
+    pub speaker: String,
+    pub alignment: Alignment<String>,
+}
+
+impl ScoredUtterance {
+    /// From an eval result; the speaker comes from the `speaker` metadata field.
+    pub fn from_result(result: &UtteranceResult) -> Self {
+        let id = Path::new(&result.audio_path).file_stem().map_or_else(
+            || result.audio_path.clone(),
+            |s| s.to_string_lossy().into_owned(),
+        );
+        Self {
+            speaker: result
+                .metadata
+                .get("speaker")
+                .cloned()
+                .unwrap_or_else(|| id.clone()),
+            alignment: word_alignment(&result.reference, &result.hypothesis),
+            id,
+        }
+    }
+}
+
+/// Score CTM hypotheses against STM references. Each hypothesis word goes to
+/// the segment of its file and channel that contains its midpoint, or else
+/// the nearest one; words of files without references are ignored. Both
+/// sides are normalized with `normalizer` first.
+pub fn score_ctm(
+    stm: &[StmSegment],
+    ctm: &[CtmWord],
+    normalizer: Normalizer,
+) -> Vec<ScoredUtterance> {
+    let mut by_recording: BTreeMap<(&str, &str), Vec<usize>> = BTreeMap::new();
+    for (i, segment) in stm.iter().enumerate() {
+        by_recording
+            .entry((segment.file.as_str(), segment.channel.as_str()))
+            .or_default()
+            .push(i);
+    }
+
+    let mut hypotheses: Vec<Vec<&CtmWord>> = vec![Vec::new(); stm.len()];
+    for word in ctm {
+        let Some(candidates) = by_recording.get(&(word.file.as_str(), word.channel.as_str()))
+        else {
+            continue;
+        };
+        let mid = word.start + word.duration / 2.0;
+        let distance = |s: &StmSegment| (s.start - mid).max(mid - s.end).max(0.0);
+        let nearest = candidates
+            .iter()
+            .copied()
+            .min_by(|&a, &b| distance(&stm[a]).total_cmp(&distance(&stm[b])));
+        if let Some(i) = nearest {
+            hypotheses[i].push(word);
+        }
+    }
+
+    let words = |text: &str| -> Vec<String> {
+        normalizer
+            .apply(text)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect()
+    };
+    stm.iter()
+        .zip(hypotheses)
+        .filter(|(segment, _)| !segment.text.eq_ignore_ascii_case(IGNORE_SEGMENT))
+        .map(|(segment, mut hyp)| {
+            hyp.sort_by(|a, b| a.start.total_cmp(&b.start));
+            let hyp_text: Vec<&str> = hyp.iter().map(|w| w.word.as_str()).collect();
+            ScoredUtterance {
+                id: format!("{}-{}-{:.2}", segment.file, segment.channel, segment.start),
+                speaker: segment.speaker.clone(),
+                alignment: align(&words(&segment.text), &words(&hyp_text.join(" "))),
+            }
+        })
+        .collect()
+}
+
+/// sclite-style per-speaker summary (percentages of reference words).
+pub fn write_sys<W: Write>(mut w: W, title: &str, utterances: &[ScoredUtterance]) -> Result<()> {
+    let mut speakers: BTreeMap<&str, (usize, usize, ErrorCounts)> = BTreeMap::new();
+    for utt in utterances {
+        let entry = speakers.entry(&utt.speaker).or_default();
+        entry.0 += 1;
+        entry.1 += usize::from(utt.alignment.counts.errors() > 0);
+        entry.2 += utt.alignment.counts;
+    }
+    let sum = speakers
+        .values()
+        .fold((0, 0, ErrorCounts::default()), |acc, s| {
+            (acc.0 + s.0, acc.1 + s.1, acc.2 + s.2)
+        });
+
+    writeln!(w, "SYSTEM SUMMARY PERCENTAGES by SPEAKER")?;
+    writeln!(w, "{title}")?;
+    let rule = format!("|{:-<22}+{:-<14}+{:-<42}|", "", "", "");
+    writeln!(w, "{rule}")?;
+    writeln!(
+        w,
+        "| {:<20} | {:>5} {:>6} | {:>6} {:>6} {:>6} {:>6} {:>6} {:>6} |",
+        "SPKR", "# Snt", "# Wrd", "Corr", "Sub", "Del", "Ins", "Err", "S.Err"
+    )?;
+    writeln!(w, "{rule}")?;
+    for (speaker, row) in speakers
+        .iter()
+        .map(|(s, r)| (*s, *r))
+        .chain([("Sum/Avg", sum)])
+    {
+        let (sentences, sentence_errors, counts) = row;
+        let words = counts.reference_len().max(1) as f64;
+        let pct = |n: usize| n as f64 * 100.0 / words;
+        writeln!(
+            w,
+            "| {:<20} | {:>5} {:>6} | {:>6.1} {:>6.1} {:>6.1} {:>6.1} {:>6.1} {:>6.1} |",
+            speaker,
+            sentences,
+            counts.reference_len(),
+            pct(counts.hits),
+            pct(counts.substitutions),
+            pct(counts.deletions),
+            pct(counts.insertions),
+            pct(counts.errors()),
+            sentence_errors as f64 * 100.0 / sentences.max(1) as f64
+        )?;
+        if speaker == "Sum/Avg" {
+            writeln!(w, "{rule}")?;
+        }
+    }
+    Ok(())
+}
+
+/// sclite-style alignment of every utterance: counts, then REF/HYP/Eval lines.
+pub fn write_pra<W: Write>(mut w: W, utterances: &[ScoredUtterance]) -> Result<()> {
+    for utt in utterances {
+        let c = utt.alignment.counts;
+        let [reference, hypothesis, markers] = render(&utt.alignment, false);
+        writeln!(w, "id: ({}-{})", utt.speaker, utt.id)?;
+        writeln!(
+            w,
+            "Scores: (#C #S #D #I) {} {} {} {}",
+            c.hits, c.substitutions, c.deletions, c.insertions
+        )?;
+        writeln!(w, "REF:  {reference}")?;
+        writeln!(w, "HYP:  {hypothesis}")?;
+        writeln!(w, "Eval: {markers}")?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// Write `<dir>/<name>.sys` and `<dir>/<name>.pra`.
+pub fn write_sclite_reports(dir: &Path, name: &str, utterances: &[ScoredUtterance]) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let create = |ext: &str| {
+        let path = dir.join(format!("{name}.{ext}"));
+        File::create(&path).with_context(|| format!("Failed to create {}", path.display()))
+    };
+    write_sys(create("sys")?, name, utterances)?;
+    write_pra(create("pra")?, utterances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stm(start: f64, end: f64, text: &str) -> StmSegment {
+        StmSegment {
+            file: "rec".into(),
+            channel: "1".into(),
+            speaker: "spk1".into(),
+            start,
+            end,
+            label: None,
+            text: text.into(),
+        }
+    }
+
+    fn ctm(start: f64, word: &str) -> CtmWord {
+        CtmWord {
+            file: "rec".into(),
+            channel: "1".into(),
+            start,
+            duration: 0.2,
+            word: word.into(),
+            confidence: None,
+        }
+    }
+
+    #[test]
+    fn assigns_words_to_segments_by_time() {
+        let refs = [stm(0.0, 2.0, "guten morgen"), stm(3.0, 5.0, "wie geht es")];
+        let hyps = [
+            ctm(0.1, "guten"),
+            ctm(0.8, "Morgen"),
+            ctm(2.2, "äh"),
+            ctm(3.2, "wie"),
+            ctm(4.0, "steht"),
+            ctm(9.0, "tschüss"),
+        ];
+        let scored = score_ctm(&refs, &hyps, Normalizer::Whisper);
+
+        assert_eq!(scored[0].alignment.counts.errors(), 1);
+        assert_eq!(scored[0].alignment.counts.insertions, 1);
+        let second = scored[1].alignment.counts;
+        assert_eq!(
+            (second.hits, second.substitutions, second.errors()),
+            (1, 2, 2)
+        );
+    }
+
+    #[test]
+    fn pra_lists_counts_and_alignment() {
+        let utt = ScoredUtterance {
+            id: "rec-1-0.00".into(),
+            speaker: "spk1".into(),
+            alignment: word_alignment("a b c", "a x c"),
+        };
+        let mut out = Vec::new();
+        write_pra(&mut out, &[utt]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "id: (spk1-rec-1-0.00)\nScores: (#C #S #D #I) 2 1 0 0\n\
+             REF:  a b c\nHYP:  a x c\nEval:   S\n\n"
+        );
+    }
+}
@@ -0,0 +1,489 @@
+//! Text normalization applied to both sides before scoring.
+//!
+//! The `whisper` normalizers port the ones published with Whisper, so scores
+//! can be compared with its reported WERs. Two parts of the English one are
+//! not replicated: British spellings are not mapped to American ones, and
+//! the number normalizer handles cardinals, ordinals, decimals and percentages
+//! but not currency amounts.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use anyhow::{Error, bail};
+use regex::Regex;
+use unicode_categories::UnicodeCategories;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Normalizer {
+    /// Score the texts as they are (whitespace is still collapsed).
+    None,
+    /// Whisper's `BasicTextNormalizer`, for any language.
+    #[default]
+    Whisper,
+    /// Whisper's `EnglishTextNormalizer`.
+    WhisperEnglish,
+}
+
+impl Normalizer {
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Normalizer::None => collapse_whitespace(text),
+            Normalizer::Whisper => whisper_basic(text),
+            Normalizer::WhisperEnglish => whisper_english(text),
+        }
+    }
+}
+
+impl FromStr for Normalizer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" | "raw" => Ok(Normalizer::None),
+            "whisper" | "basic" => Ok(Normalizer::Whisper),
+            "whisper-en" | "english" => Ok(Normalizer::WhisperEnglish),
+            other => bail!("unknown normalizer '{other}' (expected none, whisper or whisper-en)"),
+        }
+    }
+}
+
+impl fmt::Display for Normalizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Normalizer::None => "none",
+            Normalizer::Whisper => "whisper",
+            Normalizer::WhisperEnglish => "whisper-en",
+        })
+    }
+}
+
+fn regex(pattern: &str) -> Regex {
+    Regex::new(pattern).expect("valid normalizer pattern")
+}
+
+static BRACKETED: LazyLock<Regex> = LazyLock::new(|| regex(r"[<\[][^>\]]*[>\]]"));
+static PARENTHESIZED: LazyLock<Regex> = LazyLock::new(|| regex(r"\(([^)]+?)\)"));
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whisper's `BasicTextNormalizer`: lowercase, drop bracketed and
+/// parenthesized spans, and replace marks, symbols and punctuation with
+/// spaces.
+pub fn whisper_basic(text: &str) -> String {
+    let s = text.to_lowercase();
+    let s = BRACKETED.replace_all(&s, "");
+    let s = PARENTHESIZED.replace_all(&s, "");
+    let s: String = s
+        .nfkc()
+        .map(|c| {
+            if c.is_mark() || c.is_symbol() || c.is_punctuation() {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect();
+    collapse_whitespace(&s.to_lowercase())
+}
+
+/// Letters NFKD does not decompose, spelled out the way Whisper does.
+fn additional_diacritic(c: char) -> Option<&'static str> {
+    Some(match c {
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ø' => "o",
+        'Ø' => "O",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'ß' => "ss",
+        'ẞ' => "SS",
+        'đ' | 'ð' => "d",
+        'Đ' | 'Ð' => "D",
+        'þ' | 'Þ' => "th",
+        'ł' => "l",
+        'Ł' => "L",
+        _ => return None,
+    })
+}
+
+/// Strip diacritics and replace other marks, symbols and punctuation (except
+/// the characters in `keep`) with spaces.
+fn remove_symbols_and_diacritics(text: &str, keep: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.nfkd() {
+        if keep.contains(c) {
+            out.push(c);
+        } else if let Some(spelled) = additional_diacritic(c) {
+            out.push_str(spelled);
+        } else if c.is_mark_nonspacing() {
+            continue;
+        } else if c.is_mark() || c.is_symbol() || c.is_punctuation() {
+            out.push(' ');
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+static FILLERS: LazyLock<Regex> = LazyLock::new(|| regex(r"\b(hmm|mm|mhm|mmm|uh|um)\b"));
+static SPACE_BEFORE_APOSTROPHE: LazyLock<Regex> = LazyLock::new(|| regex(r"\s+'"));
+static DIGIT_COMMA: LazyLock<Regex> = LazyLock::new(|| regex(r"(\d),(\d)"));
+static PERIOD: LazyLock<Regex> = LazyLock::new(|| regex(r"\.([^0-9]|$)"));
+static CURRENCY_PREFIX: LazyLock<Regex> = LazyLock::new(|| regex(r"[.$¢€£]([^0-9])"));
+static PERCENT_SUFFIX: LazyLock<Regex> = LazyLock::new(|| regex(r"([^0-9])%"));
+
+/// Contractions, titles and abbreviations, applied in order.
+const REPLACERS: &[(&str, &str)] = &[
+    (r"\bwon't\b", "will not"),
+    (r"\bcan't\b", "can not"),
+    (r"\blet's\b", "let us"),
+    (r"\bain't\b", "aint"),
+    (r"\by'all\b", "you all"),
+    (r"\bwanna\b", "want to"),
+    (r"\bgotta\b", "got to"),
+    (r"\bgonna\b", "going to"),
+    (r"\bi'ma\b", "i am going to"),
+    (r"\bimma\b", "i am going to"),
+    (r"\bwoulda\b", "would have"),
+    (r"\bcoulda\b", "could have"),
+    (r"\bshoulda\b", "should have"),
+    (r"\bma'am\b", "madam"),
+    (r"\bmr\b", "mister "),
+    (r"\bmrs\b", "missus "),
+    (r"\bst\b", "saint "),
+    (r"\bdr\b", "doctor "),
+    (r"\bprof\b", "professor "),
+    (r"\bcapt\b", "captain "),
+    (r"\bgov\b", "governor "),
+    (r"\bald\b", "alderman "),
+    (r"\bgen\b", "general "),
+    (r"\bsen\b", "senator "),
+    (r"\brep\b", "representative "),
+    (r"\bpres\b", "president "),
+    (r"\brev\b", "reverend "),
+    (r"\bhon\b", "honorable "),
+    (r"\basst\b", "assistant "),
+    (r"\bassoc\b", "associate "),
+    (r"\blt\b", "lieutenant "),
+    (r"\bcol\b", "colonel "),
+    (r"\bjr\b", "junior "),
+    (r"\bsr\b", "senior "),
+    (r"\besq\b", "esquire "),
+    (r"'d been\b", " had been"),
+    (r"'s been\b", " has been"),
+    (r"'d gone\b", " had gone"),
+    (r"'s gone\b", " has gone"),
+    (r"'d done\b", " had done"),
+    (r"'s got\b", " has got"),
+    (r"n't\b", " not"),
+    (r"'re\b", " are"),
+    (r"'s\b", " is"),
+    (r"'d\b", " would"),
+    (r"'ll\b", " will"),
+    (r"'t\b", " not"),
+    (r"'ve\b", " have"),
+    (r"'m\b", " am"),
+];
+
+static REPLACER_PATTERNS: LazyLock<Vec<(Regex, &'static str)>> =
+    LazyLock::new(|| REPLACERS.iter().map(|&(p, r)| (regex(p), r)).collect());
+
+/// Whisper's `EnglishTextNormalizer` (see the module docs for what differs).
+pub fn whisper_english(text: &str) -> String {
+    let s = text.to_lowercase();
+    let s = BRACKETED.replace_all(&s, "");
+    let s = PARENTHESIZED.replace_all(&s, "");
+    let s = FILLERS.replace_all(&s, "");
+    let mut s = SPACE_BEFORE_APOSTROPHE.replace_all(&s, "'").into_owned();
+    for (pattern, replacement) in REPLACER_PATTERNS.iter() {
+        s = pattern.replace_all(&s, *replacement).into_owned();
+    }
+    let s = DIGIT_COMMA.replace_all(&s, "${1}${2}");
+    let s = PERIOD.replace_all(&s, " ${1}");
+    let s = remove_symbols_and_diacritics(&s, ".%$¢€£");
+    let s = standardize_numbers(&s);
+    let s = CURRENCY_PREFIX.replace_all(&s, " ${1}");
+    let s = PERCENT_SUFFIX.replace_all(&s, "${1} ");
+    collapse_whitespace(&s)
+}
+
+// -------------------------
+// Spelled-out numbers
+// -------------------------
+
+fn ones(word: &str) -> Option<u64> {
+    const ONES: &[&str] = &[
+        "zero",
+        "one",
+        "two",
+        "three",
+        "four",
+        "five",
+        "six",
+        "seven",
+        "eight",
+        "nine",
+        "ten",
+        "eleven",
+        "twelve",
+        "thirteen",
+        "fourteen",
+        "fifteen",
+        "sixteen",
+        "seventeen",
+        "eighteen",
+        "nineteen",
+    ];
+    ONES.iter().position(|&w| w == word).map(|i| i as u64)
+}
+
+fn tens(word: &str) -> Option<u64> {
+    const TENS: &[&str] = &[
+        "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+    ];
+    TENS.iter()
+        .position(|&w| w == word)
+        .map(|i| (i as u64 + 2) * 10)
+}
+
+fn multiplier(word: &str) -> Option<u64> {
+    Some(match word {
+        "hundred" => 100,
+        "thousand" => 1_000,
+        "million" => 1_000_000,
+        "billion" => 1_000_000_000,
+        "trillion" => 1_000_000_000_000,
+        _ => return None,
+    })
+}
+
+/// The cardinal word of an ordinal ("twentieth" -> "twenty").
+fn ordinal_base(word: &str) -> Option<String> {
+    let base = match word {
+        "first" => "one",
+        "second" => "two",
+        "third" => "three",
+        "fifth" => "five",
+        "eighth" => "eight",
+        "ninth" => "nine",
+        "twelfth" => "twelve",
+        _ => {
+            if let Some(stem) = word.strip_suffix("ieth") {
+                return Some(format!("{stem}y"));
+            }
+            let stem = word.strip_suffix("th")?;
+            return (ones(stem).is_some() || multiplier(stem).is_some()).then(|| stem.to_string());
+        }
+    };
+    Some(base.to_string())
+}
+
+/// Whether `word` (lowercase) is an English number word, cardinal or ordinal.
+pub(crate) fn is_number_word(word: &str) -> bool {
+    ones(word).is_some()
+        || tens(word).is_some()
+        || multiplier(word).is_some()
+        || ordinal_base(word).is_some_and(|base| is_number_word(&base))
+}
+
+fn ordinal_suffix(n: u64) -> &'static str {
+    match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+/// A spelled-out number being read word by word.
+#[derive(Default)]
+struct Number {
+    /// Value of the completed thousands/millions/... groups.
+    total: u64,
+    /// Value below the last multiplier word.
+    current: u64,
+    /// Smallest large multiplier used so far; the next must be smaller.
+    last_big: Option<u64>,
+    /// What the last word was, to tell "twenty one" from "one two".
+    last: Last,
+    words: usize,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+enum Last {
+    #[default]
+    Nothing,
+    Ones,
+    Teen,
+    Tens,
+    Hundred,
+    Big,
+}
+
+impl Number {
+    /// Add `word` if it continues this number.
+    fn push(&mut self, word: &str) -> bool {
+        let accepted = if let Some(n) = ones(word) {
+            let fits = match self.last {
+                Last::Nothing | Last::Hundred | Last::Big => true,
+                Last::Tens => (1..10).contains(&n),
+                Last::Ones | Last::Teen => false,
+            };
+            if fits {
+                self.current += n;
+                self.last = if n < 10 { Last::Ones } else { Last::Teen };
+            }
+            fits
+        } else if let Some(n) = tens(word) {
+            let fits = matches!(self.last, Last::Nothing | Last::Hundred | Last::Big);
+            if fits {
+                self.current += n;
+                self.last = Last::Tens;
+            }
+            fits
+        } else if word == "hundred" {
+            let fits =
+                matches!(self.last, Last::Ones | Last::Teen | Last::Tens) && self.current < 100;
+            if fits {
+                self.current *= 100;
+                self.last = Last::Hundred;
+            }
+            fits
+        } else if let Some(n) = multiplier(word) {
+            let fits = matches!(
+                self.last,
+                Last::Ones | Last::Teen | Last::Tens | Last::Hundred
+            ) && self.last_big.is_none_or(|big| n < big);
+            if fits {
+                self.total += self.current * n;
+                self.current = 0;
+                self.last_big = Some(n);
+                self.last = Last::Big;
+            }
+            fits
+        } else {
+            false
+        };
+        self.words += usize::from(accepted);
+        accepted
+    }
+
+    fn value(&self) -> u64 {
+        self.total + self.current
+    }
+}
+
+/// Replace spelled-out numbers with digits: "twenty one" -> "21", "the
+/// third" -> "the 3rd", "two point five percent" -> "2.5%".
+fn standardize_numbers(text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut out: Vec<String> = Vec::with_capacity(words.len());
+    let mut i = 0;
+
+    while i < words.len() {
+        let mut number = Number::default();
+        let mut j = i;
+        while j < words.len() && number.push(words[j]) {
+            j += 1;
+        }
+
+        // An ordinal ends the number ("twenty first", "hundredth").
+        let mut suffix = "";
+        if j < words.len()
+            && let Some(base) = ordinal_base(words[j])
+            && number.push(&base)
+        {
+            suffix = ordinal_suffix(number.value());
+            j += 1;
+        }
+
+        if number.words == 0 {
+            out.push(words[i].to_string());
+            i += 1;
+            continue;
+        }
+
+        let mut digits = number.value().to_string();
+        if suffix.is_empty() {
+            // Decimals: "point" followed by single digits.
+            if j + 1 < words.len() && words[j] == "point" {
+                let decimals: String = words[j + 1..]
+                    .iter()
+                    .map_while(|w| ones(w).filter(|&n| n < 10))
+                    .map(|n| char::from(b'0' + n as u8))
+                    .collect();
+                if !decimals.is_empty() {
+                    j += 1 + decimals.len();
+                    digits = format!("{digits}.{decimals}");
+                }
+            }
+            if j < words.len() && words[j] == "percent" {
+                digits.push('%');
+                j += 1;
+            }
+        }
+        out.push(format!("{digits}{suffix}"));
+        i = j;
+    }
+
+    out.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_normalizer_strips_symbols_and_brackets() {
+        assert_eq!(
+            whisper_basic("Hallo, Welt! [Musik] (lacht) Straße"),
+            "hallo welt straße"
+        );
+        assert_eq!(whisper_basic("  «Ça va?»  "), "ça va");
+    }
+
+    #[test]
+    fn english_normalizer_expands_contractions_and_fillers() {
+        assert_eq!(
+            whisper_english("Um, I can't go, Mr. Smith."),
+            "i can not go mister smith"
+        );
+        assert_eq!(
+            whisper_english("She's been there; we'll see"),
+            "she has been there we will see"
+        );
+        assert_eq!(whisper_english("Café naïve"), "cafe naive");
+    }
+
+    #[test]
+    fn english_normalizer_writes_numbers_as_digits() {
+        assert_eq!(whisper_english("twenty one pilots"), "21 pilots");
+        assert_eq!(whisper_english("one hundred twenty three"), "123");
+        assert_eq!(
+            whisper_english("the twenty first century"),
+            "the 21st century"
+        );
+        assert_eq!(whisper_english("two point five percent"), "2.5%");
+        assert_eq!(whisper_english("one two three"), "1 2 3");
+        assert_eq!(
+            whisper_english("It cost 1,000 dollars."),
+            "it cost 1000 dollars"
+        );
+    }
+
+    #[test]
+    fn parses_normalizer_names() {
+        assert_eq!(
+            "whisper-en".parse::<Normalizer>().unwrap(),
+            Normalizer::WhisperEnglish
+        );
+        assert_eq!("none".parse::<Normalizer>().unwrap().apply(" a  b "), "a b");
+        assert!("nope".parse::<Normalizer>().is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::keywords::Detection;
+use crate::metrics::{self, ErrorCounts};
+
+/// Scores of one utterance, as written to the results JSONL.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UtteranceResult {
+    pub audio_path: String,
+    pub reference: String,
+    pub hypothesis: String,
+    pub wer: f64,
+    pub cer: f64,
+    pub words: ErrorCounts,
+    pub chars: ErrorCounts,
+
+    /// Confidence of the hypothesis in `[0, 1]`, if the model reported one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+
+    /// Manifest metadata of the utterance, kept for per-group breakdowns.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+
+    /// Keywords found in the hypothesis (with `--keywords`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<Detection>,
+}
+
+impl UtteranceResult {
+    /// Score `hypothesis` against `reference`; both should already be normalized.
+    pub fn score(audio_path: &str, reference: &str, hypothesis: &str) -> Self {
+        let words = metrics::wer(reference, hypothesis);
+        let chars = metrics::cer(reference, hypothesis);
+        Self {
+            audio_path: audio_path.to_string(),
+            reference: reference.to_string(),
+            hypothesis: hypothesis.to_string(),
+            wer: words.rate(),
+            cer: chars.rate(),
+            words,
+            chars,
+            confidence: None,
+            metadata: BTreeMap::new(),
+            keywords: Vec::new(),
+        }
+    }
+}
+
+/// Results written by an earlier `shout eval` run.
+pub fn read_results<P: AsRef<Path>>(path: P) -> Result<Vec<UtteranceResult>> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).with_context(|| format!("Failed to open results: {}", path.display()))?;
+
+    let mut out = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid result line {} in {}", i + 1, path.display()))?;
+        out.push(result);
+    }
+    Ok(out)
+}
+
+/// Corpus-level scores: errors summed over utterances, divided by the
+/// summed reference length (not a mean of per-utterance rates).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EvalSummary {
+    pub utterances: usize,
+    pub failed: usize,
+    pub words: ErrorCounts,
+    pub chars: ErrorCounts,
+}
+
+impl EvalSummary {
+    pub fn add(&mut self, result: &UtteranceResult) {
+        self.utterances += 1;
+        self.words += result.words;
+        self.chars += result.chars;
+    }
+
+    pub fn wer(&self) -> f64 {
+        self.words.rate()
+    }
+
+    pub fn cer(&self) -> f64 {
+        self.chars.rate()
+    }
+}
+
+impl fmt::Display for EvalSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Utterances: {} ({} failed)",
+            self.utterances, self.failed
+        )?;
+        writeln!(
+            f,
+            "WER: {:.2}% (S {} D {} I {} / {} words)",
+            self.wer() * 100.0,
+            self.words.substitutions,
+            self.words.deletions,
+            self.words.insertions,
+            self.words.reference_len()
+        )?;
+        writeln!(
+            f,
+            "CER: {:.2}% (S {} D {} I {} / {} chars)",
+            self.cer() * 100.0,
+            self.chars.substitutions,
+            self.chars.deletions,
+            self.chars.insertions,
+            self.chars.reference_len()
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+//! Human review of reference transcripts.
+//!
+//! Utterances the model disagrees with (high WER) or is unsure about (low
+//! confidence) are exported to a review file, one JSON object per line with
+//! the audio, the current reference and the hypothesis. Reviewers fill in
+//! `corrected` and the file is imported back into the manifest, which keeps
+//! the replaced text and who corrected it, when and from which review file.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::manifest::ReferenceEntry;
+use crate::report::UtteranceResult;
+
+/// Manifest fields recording a correction.
+pub const ORIGINAL_TEXT: &str = "original_text";
+pub const CORRECTED_BY: &str = "corrected_by";
+pub const CORRECTED_AT: &str = "corrected_at";
+pub const CORRECTION_SOURCE: &str = "correction_source";
+
+/// One utterance to review, as a line of the review file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewItem {
+    pub audio_path: String,
+
+    /// The manifest text at export time, unnormalized.
+    pub reference: String,
+
+    /// What the model heard (normalized, as scored).
+    pub hypothesis: String,
+    pub wer: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+
+    /// The corrected transcript; left `null` by the export. Equal to
+    /// `reference` to confirm it.
+    #[serde(default)]
+    pub corrected: Option<String>,
+
+    /// Who reviewed the item; overrides the reviewer given on import.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reviewer: Option<String>,
+}
+
+/// Which utterances need a look: any with WER of at least `min_wer` or a
+/// confidence below `max_confidence`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReviewCriteria {
+    pub min_wer: Option<f64>,
+    pub max_confidence: Option<f32>,
+}
+
+impl ReviewCriteria {
+    pub fn selects(&self, result: &UtteranceResult) -> bool {
+        let wer = self.min_wer.is_some_and(|min| result.wer >= min);
+        let unsure = match (self.max_confidence, result.confidence) {
+            (Some(max), Some(confidence)) => confidence < max,
+            _ => false,
+        };
+        wer || unsure
+    }
+}
+
+/// Review items for the eval `results` selected by `criteria`, worst WER
+/// first. The reference is taken from `entries` so reviewers see the text as
+/// stored rather than normalized; results without a manifest entry are left
+/// out.
+pub fn select(
+    results: &[UtteranceResult],
+    entries: &[ReferenceEntry],
+    criteria: ReviewCriteria,
+) -> Vec<ReviewItem> {
+    let texts: HashMap<&str, &str> = entries
+        .iter()
+        .map(|e| (e.audio_path.as_str(), e.text.as_str()))
+        .collect();
+    let mut items: Vec<ReviewItem> = results
+        .iter()
+        .filter(|result| criteria.selects(result))
+        .filter_map(|result| {
+            Some(ReviewItem {
+                audio_path: result.audio_path.clone(),
+                reference: texts.get(result.audio_path.as_str())?.to_string(),
+                hypothesis: result.hypothesis.clone(),
+                wer: result.wer,
+                confidence: result.confidence,
+                corrected: None,
+                reviewer: None,
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| b.wer.total_cmp(&a.wer));
+    items
+}
+
+pub fn write_review<P: AsRef<Path>>(path: P, items: &[ReviewItem]) -> Result<()> {
+    let path = path.as_ref();
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create review file: {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    for item in items {
+        serde_json::to_writer(&mut out, item)?;
+        writeln!(out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+pub fn read_review<P: AsRef<Path>>(path: P) -> Result<Vec<ReviewItem>> {
+    let path = path.as_ref();
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open review file: {}", path.display()))?;
+
+    let mut out = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let line = line.strip_prefix('\u{feff}').unwrap_or(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item = serde_json::from_str(line)
+            .with_context(|| format!("Invalid review line {} in {}", i + 1, path.display()))?;
+        out.push(item);
+    }
+    Ok(out)
+}
+
+/// Where corrections come from, recorded on every corrected entry.
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    /// Reviewer for items that do not name one.
+    pub reviewer: Option<String>,
+
+    /// Name of the review file.
+    pub source: String,
+
+    /// Time of the import, in seconds since the Unix epoch.
+    pub at: u64,
+}
+
+/// What importing a review file did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Entries whose text was replaced.
+    pub corrected: usize,
+
+    /// Items whose reference was confirmed as is.
+    pub confirmed: usize,
+
+    /// Items without a `corrected` text.
+    pub unreviewed: usize,
+
+    /// Items whose manifest text changed since the export; left alone.
+    pub conflicts: usize,
+
+    /// Items whose audio is no longer in the manifest.
+    pub missing: usize,
+}
+
+/// Apply the reviewed `items` to the manifest `entries`. The first
+/// correction of an entry keeps its old text in `original_text`, so repeated
+/// rounds of review still point back to the transcript the corpus shipped
+/// with.
+pub fn apply(
+    entries: &mut [ReferenceEntry],
+    items: &[ReviewItem],
+    provenance: &Provenance,
+) -> ImportSummary {
+    let index: HashMap<String, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.audio_path.clone(), i))
+        .collect();
+    let mut summary = ImportSummary::default();
+    for item in items {
+        let Some(corrected) = item.corrected.as_deref().map(str::trim) else {
+            summary.unreviewed += 1;
+            continue;
+        };
+        let Some(&i) = index.get(&item.audio_path) else {
+            summary.missing += 1;
+            continue;
+        };
+        let entry = &mut entries[i];
+        if entry.text != item.reference {
+            summary.conflicts += 1;
+            continue;
+        }
+        if corrected == entry.text {
+            summary.confirmed += 1;
+            continue;
+        }
+
+        let original = std::mem::replace(&mut entry.text, corrected.to_string());
+        let meta = &mut entry.metadata;
+        meta.entry(ORIGINAL_TEXT.to_string())
+            .or_insert(Value::String(original));
+        match item.reviewer.as_ref().or(provenance.reviewer.as_ref()) {
+            Some(reviewer) => meta.insert(CORRECTED_BY.into(), Value::String(reviewer.clone())),
+            None => meta.remove(CORRECTED_BY),
+        };
+        meta.insert(CORRECTED_AT.into(), Value::from(provenance.at));
+        meta.insert(
+            CORRECTION_SOURCE.into(),
+            Value::String(provenance.source.clone()),
+        );
+        summary.corrected += 1;
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, text: &str) -> ReferenceEntry {
+        serde_json::from_value(serde_json::json!({"audio_path": path, "text": text})).unwrap()
+    }
+
+    #[test]
+    fn corrections_keep_the_first_original_and_skip_conflicts() {
+        let mut entries = vec![entry("a.wav", "helo world"), entry("b.wav", "fine")];
+        let mut results = vec![
+            UtteranceResult::score("a.wav", "helo world", "hello world"),
+            UtteranceResult::score("b.wav", "fine", "fine"),
+        ];
+        results[1].confidence = Some(0.2);
+        let criteria = ReviewCriteria {
+            min_wer: Some(0.3),
+            max_confidence: Some(0.5),
+        };
+        let mut items = select(&results, &entries, criteria);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].audio_path, "a.wav");
+
+        items[0].corrected = Some("hello world".into());
+        items[1].corrected = Some("fine".into());
+        let provenance = Provenance {
+            reviewer: Some("ana".into()),
+            source: "round1.jsonl".into(),
+            at: 1_767_225_600,
+        };
+        let summary = apply(&mut entries, &items, &provenance);
+        assert_eq!((summary.corrected, summary.confirmed), (1, 1));
+        assert_eq!(entries[0].text, "hello world");
+        assert_eq!(
+            entries[0].field(ORIGINAL_TEXT).as_deref(),
+            Some("helo world")
+        );
+        assert_eq!(entries[0].field(CORRECTED_BY).as_deref(), Some("ana"));
+
+        // The same file again: the manifest has moved on.
+        items[0].corrected = Some("hello, world".into());
+        let summary = apply(&mut entries, &items, &provenance);
+        assert_eq!(summary.conflicts, 1);
+        assert_eq!(entries[0].text, "hello world");
+    }
+}
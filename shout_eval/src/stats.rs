@@ -0,0 +1,169 @@
+//! Bootstrap resampling over utterances: confidence intervals for a corpus
+//! WER and a paired test between two systems scored on the same utterances.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::metrics::ErrorCounts;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceInterval {
+    pub estimate: f64,
+    pub lower: f64,
+    pub upper: f64,
+    /// Coverage, e.g. 0.95.
+    pub level: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairedComparison {
+    pub utterances: usize,
+    pub rate_a: f64,
+    pub rate_b: f64,
+    /// Interval of `rate_b - rate_a`; negative means B makes fewer errors.
+    pub delta: ConfidenceInterval,
+    /// Two-sided: how often the resampled difference falls on the other side
+    /// of zero, doubled. Small values mean the difference is unlikely to be
+    /// an artifact of which utterances happened to be in the test set.
+    pub p_value: f64,
+}
+
+fn corpus_rate<'a>(counts: impl Iterator<Item = &'a ErrorCounts>) -> f64 {
+    counts
+        .fold(ErrorCounts::default(), |total, c| total + *c)
+        .rate()
+}
+
+/// Percentile interval of the corpus error rate over `iterations` resamples
+/// (with replacement) of the utterances.
+pub fn bootstrap_interval(
+    counts: &[ErrorCounts],
+    iterations: usize,
+    level: f64,
+    seed: u64,
+) -> ConfidenceInterval {
+    let estimate = corpus_rate(counts.iter());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut samples: Vec<f64> = (0..iterations)
+        .map(|_| {
+            let mut total = ErrorCounts::default();
+            for _ in 0..counts.len() {
+                total += counts[rng.random_range(0..counts.len())];
+            }
+            total.rate()
+        })
+        .collect();
+
+    let (lower, upper) = percentiles(&mut samples, level, estimate);
+    ConfidenceInterval {
+        estimate,
+        lower,
+        upper,
+        level,
+    }
+}
+
+/// Paired bootstrap test (Bisani & Ney, 2004). `a[i]` and `b[i]` must be
+/// the scores of the same utterance.
+pub fn paired_bootstrap(
+    a: &[ErrorCounts],
+    b: &[ErrorCounts],
+    iterations: usize,
+    level: f64,
+    seed: u64,
+) -> PairedComparison {
+    assert_eq!(
+        a.len(),
+        b.len(),
+        "paired bootstrap needs the same utterances on both sides"
+    );
+
+    let rate_a = corpus_rate(a.iter());
+    let rate_b = corpus_rate(b.iter());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut samples: Vec<f64> = (0..iterations)
+        .map(|_| {
+            let (mut total_a, mut total_b) = (ErrorCounts::default(), ErrorCounts::default());
+            for _ in 0..a.len() {
+                let i = rng.random_range(0..a.len());
+                total_a += a[i];
+                total_b += b[i];
+            }
+            total_b.rate() - total_a.rate()
+        })
+        .collect();
+
+    let at_or_below = samples.iter().filter(|&&d| d <= 0.0).count();
+    let at_or_above = samples.iter().filter(|&&d| d >= 0.0).count();
+    let p_value = if samples.is_empty() {
+        1.0
+    } else {
+        (2.0 * at_or_below.min(at_or_above) as f64 / samples.len() as f64).min(1.0)
+    };
+
+    let estimate = rate_b - rate_a;
+    let (lower, upper) = percentiles(&mut samples, level, estimate);
+    PairedComparison {
+        utterances: a.len(),
+        rate_a,
+        rate_b,
+        delta: ConfidenceInterval {
+            estimate,
+            lower,
+            upper,
+            level,
+        },
+        p_value,
+    }
+}
+
+/// Bounds of the central `level` mass of `samples`; `fallback` for both if
+/// there are no samples.
+fn percentiles(samples: &mut [f64], level: f64, fallback: f64) -> (f64, f64) {
+    if samples.is_empty() {
+        return (fallback, fallback);
+    }
+    samples.sort_by(f64::total_cmp);
+    let tail = (1.0 - level) / 2.0;
+    let at = |q: f64| samples[(q * (samples.len() - 1) as f64).round() as usize];
+    (at(tail), at(1.0 - tail))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utt(hits: usize, substitutions: usize) -> ErrorCounts {
+        ErrorCounts {
+            hits,
+            substitutions,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn interval_contains_the_estimate() {
+        let counts: Vec<_> = (0..50).map(|i| utt(10 - i % 3, i % 3)).collect();
+        let ci = bootstrap_interval(&counts, 500, 0.95, 1);
+        assert!(ci.lower <= ci.estimate && ci.estimate <= ci.upper);
+        assert!(ci.upper - ci.lower > 0.0);
+    }
+
+    #[test]
+    fn consistent_improvement_is_significant() {
+        let a: Vec<_> = (0..100).map(|_| utt(7, 3)).collect();
+        let b: Vec<_> = (0..100).map(|i| utt(9 - i % 2, 1 + i % 2)).collect();
+        let cmp = paired_bootstrap(&a, &b, 1000, 0.95, 7);
+        assert!(cmp.delta.estimate < 0.0);
+        assert!(cmp.delta.upper < 0.0);
+        assert!(cmp.p_value < 0.01);
+    }
+
+    #[test]
+    fn identical_systems_are_not_significant() {
+        let a: Vec<_> = (0..40).map(|i| utt(5, i % 4)).collect();
+        let cmp = paired_bootstrap(&a, &a, 200, 0.95, 3);
+        assert_eq!(cmp.delta.estimate, 0.0);
+        assert_eq!(cmp.p_value, 1.0);
+    }
+}
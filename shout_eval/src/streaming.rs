@@ -0,0 +1,199 @@
+//! Latency and stability of streaming transcription.
+//!
+//! A stream is described by what the client saw after every decoding step
+//! and when it saw it, on a simulated clock where audio arrives in real time
+//! and each step takes as long as it took to compute. From that:
+//!
+//! - first-partial latency: from the start of speech to the first text shown;
+//! - final latency: from the end of each word to the step that finalized it;
+//! - churn: words of the displayed text later taken back (erased or
+//!   rewritten), per word of the final transcript. Zero means partial text
+//!   only ever grew.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// A word that became final, with its end time in the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FinalWord {
+    pub text: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// What one decoding step produced.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StreamStep {
+    /// When the result reached the client, in ms since the stream started.
+    pub emitted_ms: f64,
+    /// Words finalized by this step.
+    pub finals: Vec<FinalWord>,
+    /// Partial text after this step.
+    pub partial: String,
+}
+
+/// Scores of one stream.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StreamScore {
+    pub first_partial_ms: Option<f64>,
+    pub final_latencies_ms: Vec<f64>,
+    pub erased_words: usize,
+    pub final_words: usize,
+}
+
+impl StreamScore {
+    pub fn from_steps(steps: &[StreamStep]) -> Self {
+        let speech_start = steps
+            .iter()
+            .flat_map(|s| &s.finals)
+            .map(|w| w.start_ms as f64)
+            .next()
+            .unwrap_or(0.0);
+
+        let mut score = Self::default();
+        let mut shown: Vec<String> = Vec::new();
+        let mut finalized = 0usize;
+        for step in steps {
+            for word in &step.finals {
+                score
+                    .final_latencies_ms
+                    .push((step.emitted_ms - word.end_ms as f64).max(0.0));
+            }
+            // The finals shown so far, then this step's finals and partial.
+            let now: Vec<String> = shown
+                .iter()
+                .take(finalized)
+                .cloned()
+                .chain(step.finals.iter().map(|w| w.text.clone()))
+                .chain(step.partial.split_whitespace().map(str::to_string))
+                .collect();
+            finalized += step.finals.len();
+
+            let kept = shown.iter().zip(&now).take_while(|(a, b)| a == b).count();
+            score.erased_words += shown.len() - kept;
+            if score.first_partial_ms.is_none() && !now.is_empty() {
+                score.first_partial_ms = Some((step.emitted_ms - speech_start).max(0.0));
+            }
+            shown = now;
+        }
+        score.final_words = finalized;
+        score
+    }
+
+    pub fn churn(&self) -> f64 {
+        self.erased_words as f64 / self.final_words.max(1) as f64
+    }
+}
+
+/// Scores summed over all streams of an evaluation.
+#[derive(Debug, Clone, Default)]
+pub struct StreamingSummary {
+    pub streams: usize,
+    first_partial_ms: Vec<f64>,
+    final_latencies_ms: Vec<f64>,
+    erased_words: usize,
+    final_words: usize,
+}
+
+impl StreamingSummary {
+    pub fn add(&mut self, score: &StreamScore) {
+        self.streams += 1;
+        self.first_partial_ms.extend(score.first_partial_ms);
+        self.final_latencies_ms.extend(&score.final_latencies_ms);
+        self.erased_words += score.erased_words;
+        self.final_words += score.final_words;
+    }
+
+    pub fn churn(&self) -> f64 {
+        self.erased_words as f64 / self.final_words.max(1) as f64
+    }
+}
+
+impl fmt::Display for StreamingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Streams: {}", self.streams)?;
+        for (name, values) in [
+            ("First partial", &self.first_partial_ms),
+            ("Final", &self.final_latencies_ms),
+        ] {
+            let mut values = values.clone();
+            values.sort_by(f64::total_cmp);
+            writeln!(
+                f,
+                "{name} latency: p50 {:.0} ms  p90 {:.0} ms  max {:.0} ms",
+                percentile(&values, 50.0),
+                percentile(&values, 90.0),
+                values.last().copied().unwrap_or(0.0)
+            )?;
+        }
+        writeln!(
+            f,
+            "Churn: {:.3} ({} erased / {} final words)",
+            self.churn(),
+            self.erased_words,
+            self.final_words
+        )?;
+        Ok(())
+    }
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, start_ms: u64, end_ms: u64) -> FinalWord {
+        FinalWord {
+            text: text.into(),
+            start_ms,
+            end_ms,
+        }
+    }
+
+    fn step(emitted_ms: f64, finals: Vec<FinalWord>, partial: &str) -> StreamStep {
+        StreamStep {
+            emitted_ms,
+            finals,
+            partial: partial.into(),
+        }
+    }
+
+    #[test]
+    fn measures_latency_and_churn() {
+        let steps = [
+            step(1_100.0, vec![], ""),
+            step(2_100.0, vec![], "guten morgan"),
+            step(3_100.0, vec![], "guten morgen wie"),
+            step(
+                4_100.0,
+                vec![word("guten", 1_200, 1_500), word("morgen", 1_500, 1_900)],
+                "wie geht",
+            ),
+            step(
+                5_000.0,
+                vec![word("wie", 2_000, 2_300), word("geht's", 2_300, 2_800)],
+                "",
+            ),
+        ];
+        let score = StreamScore::from_steps(&steps);
+
+        assert_eq!(score.first_partial_ms, Some(900.0));
+        assert_eq!(
+            score.final_latencies_ms,
+            vec![2_600.0, 2_200.0, 2_700.0, 2_200.0]
+        );
+        // "morgan" rewritten once, "geht" once.
+        assert_eq!(score.erased_words, 2);
+        assert_eq!(score.final_words, 4);
+        assert!((score.churn() - 0.5).abs() < 1e-9);
+    }
+}
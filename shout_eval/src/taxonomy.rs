@@ -0,0 +1,264 @@
+//! Classification of word errors by likely cause, computed on the texts
+//! before normalization (which would hide casing and punctuation errors).
+//!
+//! Casing and punctuation errors point at normalization or output
+//! formatting, compound splits and merges at the tokenizer, numbers at
+//! written-vs-spoken forms, and OOV words at the training data; what remains
+//! is left to the acoustic model.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::metrics::{AlignedPair, EditOp, align};
+use crate::normalize::is_number_word;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Same word, different capitalization.
+    Casing,
+    /// Differs only in punctuation, or a punctuation-only token.
+    Punctuation,
+    /// A word written as several (or several as one): "Kühlschrank" vs "Kühl schrank".
+    Compound,
+    /// Either side is a number, in digits or spelled out.
+    Number,
+    /// The reference word is not in the training vocabulary.
+    Oov,
+    Other,
+}
+
+impl ErrorCategory {
+    pub const ALL: [ErrorCategory; 6] = [
+        ErrorCategory::Casing,
+        ErrorCategory::Punctuation,
+        ErrorCategory::Compound,
+        ErrorCategory::Number,
+        ErrorCategory::Oov,
+        ErrorCategory::Other,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCategory::Casing => "casing",
+            ErrorCategory::Punctuation => "punctuation",
+            ErrorCategory::Compound => "compound",
+            ErrorCategory::Number => "number",
+            ErrorCategory::Oov => "oov",
+            ErrorCategory::Other => "other",
+        }
+    }
+}
+
+/// Words seen in training, compared lowercased without punctuation.
+#[derive(Debug, Clone, Default)]
+pub struct Vocabulary {
+    words: HashSet<String>,
+}
+
+impl Vocabulary {
+    /// Read a word list or a JSONL manifest (the `text` field of each line).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open vocabulary: {}", path.display()))?;
+
+        let mut words = HashSet::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let text = match serde_json::from_str(&line) {
+                Ok(serde_json::Value::Object(fields)) => fields
+                    .get("text")
+                    .and_then(|t| t.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                _ => line,
+            };
+            words.extend(text.split_whitespace().map(key).filter(|k| !k.is_empty()));
+        }
+        Ok(Self { words })
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(&key(word))
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+/// Lowercased with everything but letters and digits removed.
+fn key(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn is_number(word: &str) -> bool {
+    word.chars().any(|c| c.is_ascii_digit()) || is_number_word(&key(word))
+}
+
+/// Error counts (edits, as in the WER) per category.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ErrorTaxonomy {
+    pub counts: BTreeMap<ErrorCategory, usize>,
+}
+
+impl ErrorTaxonomy {
+    /// Classify the errors between the raw `reference` and `hypothesis`.
+    /// Without a vocabulary, nothing is classified as OOV.
+    pub fn classify(reference: &str, hypothesis: &str, vocabulary: Option<&Vocabulary>) -> Self {
+        let reference: Vec<&str> = reference.split_whitespace().collect();
+        let hypothesis: Vec<&str> = hypothesis.split_whitespace().collect();
+        let pairs = align(&reference, &hypothesis).pairs;
+
+        let mut taxonomy = Self::default();
+        let mut run: Vec<&AlignedPair<&str>> = Vec::new();
+        for pair in &pairs {
+            if let Some(category) = formatting_error(pair) {
+                taxonomy.flush(&mut run, vocabulary);
+                taxonomy.add(category, 1);
+            } else if pair.op == EditOp::Hit {
+                taxonomy.flush(&mut run, vocabulary);
+            } else {
+                run.push(pair);
+            }
+        }
+        taxonomy.flush(&mut run, vocabulary);
+        taxonomy
+    }
+
+    fn add(&mut self, category: ErrorCategory, n: usize) {
+        *self.counts.entry(category).or_default() += n;
+    }
+
+    /// Classify a run of adjacent errors: as one compound error if both
+    /// sides spell the same letters, otherwise word by word.
+    fn flush(&mut self, run: &mut Vec<&AlignedPair<&str>>, vocabulary: Option<&Vocabulary>) {
+        if run.is_empty() {
+            return;
+        }
+        let joined_ref: String = run.iter().filter_map(|p| p.reference).map(key).collect();
+        let joined_hyp: String = run.iter().filter_map(|p| p.hypothesis).map(key).collect();
+        if run.len() > 1 && !joined_ref.is_empty() && joined_ref == joined_hyp {
+            self.add(ErrorCategory::Compound, run.len());
+            run.clear();
+            return;
+        }
+
+        for pair in run.drain(..) {
+            let words = [pair.reference, pair.hypothesis];
+            let category = if words.iter().flatten().any(|w| is_number(w)) {
+                ErrorCategory::Number
+            } else if let (Some(vocabulary), Some(word)) = (vocabulary, pair.reference)
+                && !vocabulary.contains(word)
+            {
+                ErrorCategory::Oov
+            } else {
+                ErrorCategory::Other
+            };
+            self.add(category, 1);
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
+
+impl fmt::Display for ErrorTaxonomy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total().max(1);
+        writeln!(f, "Errors by category (before normalization):")?;
+        for category in ErrorCategory::ALL {
+            let n = self.counts.get(&category).copied().unwrap_or(0);
+            writeln!(
+                f,
+                "  {:<12} {:>6}  ({:.1}%)",
+                category.name(),
+                n,
+                n as f64 * 100.0 / total as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::ops::AddAssign<&ErrorTaxonomy> for ErrorTaxonomy {
+    fn add_assign(&mut self, other: &ErrorTaxonomy) {
+        for (&category, &n) in &other.counts {
+            self.add(category, n);
+        }
+    }
+}
+
+/// Casing and punctuation errors, recognizable from the pair alone.
+fn formatting_error(pair: &AlignedPair<&str>) -> Option<ErrorCategory> {
+    match (pair.op, pair.reference, pair.hypothesis) {
+        (EditOp::Substitution, Some(r), Some(h)) => {
+            if r.to_lowercase() == h.to_lowercase() {
+                Some(ErrorCategory::Casing)
+            } else if key(r) == key(h) {
+                Some(ErrorCategory::Punctuation)
+            } else {
+                None
+            }
+        }
+        (EditOp::Deletion | EditOp::Insertion, r, h) => {
+            let word = r.or(h).unwrap_or_default();
+            key(word).is_empty().then_some(ErrorCategory::Punctuation)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(t: &ErrorTaxonomy, category: ErrorCategory) -> usize {
+        t.counts.get(&category).copied().unwrap_or(0)
+    }
+
+    #[test]
+    fn classifies_formatting_errors() {
+        let t = ErrorTaxonomy::classify(
+            "Guten Morgen, Welt – hallo",
+            "guten Morgen Welt hallo",
+            None,
+        );
+        assert_eq!(count(&t, ErrorCategory::Casing), 1);
+        assert_eq!(count(&t, ErrorCategory::Punctuation), 2);
+        assert_eq!(t.total(), 3);
+    }
+
+    #[test]
+    fn classifies_compounds_numbers_and_oov() {
+        let vocabulary = Vocabulary {
+            words: ["der", "kühlschrank", "ist", "leer", "bald"]
+                .map(String::from)
+                .into(),
+        };
+        let t = ErrorTaxonomy::classify(
+            "der Kühlschrank ist seit 3 Tagen leer",
+            "der Kühl Schrank ist seit drei Tag leer",
+            Some(&vocabulary),
+        );
+        assert_eq!(count(&t, ErrorCategory::Compound), 2);
+        assert_eq!(count(&t, ErrorCategory::Number), 1);
+        assert_eq!(count(&t, ErrorCategory::Oov), 1);
+        assert_eq!(t.total(), 4);
+    }
+}
@@ -0,0 +1,107 @@
+//! Side-by-side rendering of word alignments for reading errors by eye.
+
+use std::fmt::Write;
+
+use crate::metrics::{Alignment, EditOp, word_alignment};
+use crate::report::UtteranceResult;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Three lines, columns padded to line up: the reference, the hypothesis,
+/// and an S/D/I marker under each error. Deletions show as `*` in the
+/// hypothesis and insertions as `*` in the reference. With `color`,
+/// substitutions are yellow, deletions red and insertions green.
+pub fn render(alignment: &Alignment<String>, color: bool) -> [String; 3] {
+    let mut lines = [String::new(), String::new(), String::new()];
+
+    for (i, pair) in alignment.pairs.iter().enumerate() {
+        let reference = pair.reference.as_deref().unwrap_or("");
+        let hypothesis = pair.hypothesis.as_deref().unwrap_or("");
+        let width = reference.chars().count().max(hypothesis.chars().count());
+        let (marker, paint) = match pair.op {
+            EditOp::Hit => ("", ""),
+            EditOp::Substitution => ("S", YELLOW),
+            EditOp::Deletion => ("D", RED),
+            EditOp::Insertion => ("I", GREEN),
+        };
+
+        let cells = [
+            cell(pair.reference.as_deref(), width),
+            cell(pair.hypothesis.as_deref(), width),
+        ];
+        for (line, text) in lines.iter_mut().zip(cells) {
+            if i > 0 {
+                line.push(' ');
+            }
+            if color && !paint.is_empty() {
+                let _ = write!(line, "{paint}{text}{RESET}");
+            } else {
+                line.push_str(&text);
+            }
+        }
+        if i > 0 {
+            lines[2].push(' ');
+        }
+        let _ = write!(lines[2], "{marker:width$}");
+    }
+
+    let end = lines[2].trim_end().len();
+    lines[2].truncate(end);
+    lines
+}
+
+fn cell(word: Option<&str>, width: usize) -> String {
+    match word {
+        Some(word) => format!("{word:width$}"),
+        None => "*".repeat(width),
+    }
+}
+
+/// Report of the `limit` utterances with the most word errors, worst first.
+pub fn error_report(results: &[UtteranceResult], limit: usize, color: bool) -> String {
+    let mut worst: Vec<&UtteranceResult> =
+        results.iter().filter(|r| r.words.errors() > 0).collect();
+    worst.sort_by(|a, b| {
+        b.words
+            .errors()
+            .cmp(&a.words.errors())
+            .then(b.wer.total_cmp(&a.wer))
+    });
+
+    let mut out = String::new();
+    for result in worst.into_iter().take(limit) {
+        let alignment = word_alignment(&result.reference, &result.hypothesis);
+        let _ = writeln!(
+            out,
+            "{} ({} errors, WER {:.1}%)",
+            result.audio_path,
+            result.words.errors(),
+            result.wer * 100.0
+        );
+        let [reference, hypothesis, markers] = render(&alignment, color);
+        let _ = writeln!(out, "  REF: {reference}");
+        let _ = writeln!(out, "  HYP: {hypothesis}");
+        let _ = writeln!(out, "       {markers}");
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_line_up() {
+        let [reference, hypothesis, markers] = render(
+            &word_alignment("the cat sat down", "a cat sat down now"),
+            false,
+        );
+        assert_eq!(reference, "the cat sat down ***");
+        assert_eq!(hypothesis, "a   cat sat down now");
+        assert_eq!(markers, "S                I");
+    }
+}
@@ -0,0 +1,354 @@
+//! C API for embedding shout in C, C++, Go, Swift and other languages that can
+//! call C functions. Building the crate writes the header to `include/shout.h`.
+//!
+//! ```c
+//! ShoutTranscriber *t = shout_transcriber_new("models/base", "auto");
+//! if (!t) { fprintf(stderr, "%s\n", shout_last_error()); return 1; }
+//! char *json = shout_transcribe_file(t, "speech.wav");
+//! if (json) { puts(json); shout_string_free(json); }
+//! shout_transcriber_free(t);
+//! ```
+//!
+//! Conventions:
+//!
+//! - Transcripts and stream updates are returned as JSON strings, in the same
+//!   format as `shout transcribe --format json`; free them with
+//!   [`shout_string_free`].
+//! - Functions returning a pointer return NULL on failure, functions returning
+//!   an `int` return -1; [`shout_last_error`] then describes the failure.
+//! - Audio is mono `float` samples at 16 kHz.
+//! - A transcriber or stream must not be used from two threads at once;
+//!   different transcribers can run in parallel.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::Path;
+use std::ptr;
+
+use serde::Serialize;
+
+use shout_core::backend::device::{DeviceSpec, select_device};
+use shout_core::decoding::prompt::Task;
+use shout_core::inference::{ShoutModel, Transcriber, load_transcriber};
+use shout_core::pipeline::WindowTranscriber;
+use shout_core::pipeline::streaming::{StreamingOptions, StreamingSession};
+use shout_core::transcript::Segment;
+use shout_core::{Result, ShoutError};
+
+/// A loaded model with its decoding settings.
+pub struct ShoutTranscriber {
+    inner: Transcriber<ShoutModel>,
+}
+
+/// A live stream decoded by a [`ShoutTranscriber`].
+pub struct ShoutStream {
+    session: StreamingSession<Borrowed>,
+}
+
+/// The transcriber of a stream, which the caller keeps alive (see [`shout_stream_new`]).
+struct Borrowed(*mut ShoutTranscriber);
+
+impl WindowTranscriber for Borrowed {
+    fn transcribe_window(&mut self, pcm: &[f32], prompt: &str) -> Result<Vec<Segment>> {
+        // SAFETY: `shout_stream_new` requires the transcriber to outlive the
+        // stream and not to be used by anything else in the meantime.
+        unsafe { (*self.0).inner.transcribe_window(pcm, prompt) }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+// -----------------------------------------------------------------------------
+// Errors and strings
+// -----------------------------------------------------------------------------
+
+/// Version of the library, e.g. `0.1.0`. Static; do not free.
+#[unsafe(no_mangle)]
+pub extern "C" fn shout_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Message of the last failure on the calling thread, or NULL if nothing has
+/// failed yet. Valid until the next failure on the thread; do not free.
+#[unsafe(no_mangle)]
+pub extern "C" fn shout_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |msg| msg.as_ptr()))
+}
+
+/// Free a string returned by this library. NULL is ignored.
+///
+/// # Safety
+///
+/// `s` must be NULL or a string returned by this library that was not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Transcriber
+// -----------------------------------------------------------------------------
+
+/// Load the model in `model_dir` on `device` (`auto`, `cpu`, `cuda[:N]` or
+/// `metal[:N]`; NULL means `auto`, which falls back to the CPU).
+///
+/// # Safety
+///
+/// `model_dir` and `device` must be NULL or NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_transcriber_new(
+    model_dir: *const c_char,
+    device: *const c_char,
+) -> *mut ShoutTranscriber {
+    guard(ptr::null_mut(), || {
+        let model_dir = unsafe { str_arg(model_dir, "model_dir") }?;
+        let spec = if device.is_null() {
+            DeviceSpec::Auto
+        } else {
+            unsafe { str_arg(device, "device") }?.parse()?
+        };
+        let selected = select_device(spec, None)?;
+        let inner = load_transcriber(Path::new(model_dir), &selected.device)?;
+        Ok(Box::into_raw(Box::new(ShoutTranscriber { inner })))
+    })
+}
+
+/// Free a transcriber. NULL is ignored.
+///
+/// # Safety
+///
+/// `transcriber` must be NULL or come from [`shout_transcriber_new`], and no
+/// stream on it may be left.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_transcriber_free(transcriber: *mut ShoutTranscriber) {
+    if !transcriber.is_null() {
+        drop(unsafe { Box::from_raw(transcriber) });
+    }
+}
+
+/// Set the spoken language: a language code, or `auto` to detect it.
+///
+/// # Safety
+///
+/// `transcriber` must come from [`shout_transcriber_new`]; `language` must be
+/// a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_transcriber_set_language(
+    transcriber: *mut ShoutTranscriber,
+    language: *const c_char,
+) -> c_int {
+    guard(-1, || {
+        let transcriber = unsafe { handle(transcriber, "transcriber") }?;
+        transcriber.inner.options.language = unsafe { str_arg(language, "language") }?.parse()?;
+        Ok(0)
+    })
+}
+
+/// Set the task: `transcribe`, or `translate` to English.
+///
+/// # Safety
+///
+/// `transcriber` must come from [`shout_transcriber_new`]; `task` must be a
+/// NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_transcriber_set_task(
+    transcriber: *mut ShoutTranscriber,
+    task: *const c_char,
+) -> c_int {
+    guard(-1, || {
+        let transcriber = unsafe { handle(transcriber, "transcriber") }?;
+        transcriber.inner.options.task = unsafe { str_arg(task, "task") }?.parse::<Task>()?;
+        Ok(0)
+    })
+}
+
+/// Decode and transcribe an audio file; returns the transcript as JSON.
+///
+/// # Safety
+///
+/// `transcriber` must come from [`shout_transcriber_new`]; `path` must be a
+/// NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_transcribe_file(
+    transcriber: *mut ShoutTranscriber,
+    path: *const c_char,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let transcriber = unsafe { handle(transcriber, "transcriber") }?;
+        let path = unsafe { str_arg(path, "path") }?;
+        json_string(&transcriber.inner.transcribe_file(path)?)
+    })
+}
+
+/// Transcribe `len` samples; returns the transcript as JSON.
+///
+/// # Safety
+///
+/// `transcriber` must come from [`shout_transcriber_new`]; `pcm` must point to
+/// `len` floats (or be NULL with `len == 0`).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_transcribe_pcm(
+    transcriber: *mut ShoutTranscriber,
+    pcm: *const f32,
+    len: usize,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let transcriber = unsafe { handle(transcriber, "transcriber") }?;
+        let pcm = unsafe { pcm_arg(pcm, len) }?;
+        json_string(&transcriber.inner.transcribe_pcm(pcm)?)
+    })
+}
+
+// -----------------------------------------------------------------------------
+// Streaming
+// -----------------------------------------------------------------------------
+
+/// Start a live stream on `transcriber`, with the default step (1 s) and
+/// finalization settings.
+///
+/// # Safety
+///
+/// `transcriber` must come from [`shout_transcriber_new`]. It must stay alive
+/// until the stream is freed and must not be used for anything else meanwhile.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_stream_new(transcriber: *mut ShoutTranscriber) -> *mut ShoutStream {
+    guard(ptr::null_mut(), || {
+        unsafe { handle(transcriber, "transcriber") }?;
+        let session = StreamingSession::new(Borrowed(transcriber), StreamingOptions::default());
+        Ok(Box::into_raw(Box::new(ShoutStream { session })))
+    })
+}
+
+/// Add `len` samples. On success `*update` is set to the new `{"finals",
+/// "partial"}` update as JSON, or to NULL while waiting for more audio.
+///
+/// # Safety
+///
+/// `stream` must come from [`shout_stream_new`]; `pcm` must point to `len`
+/// floats (or be NULL with `len == 0`); `update` must point to a `char *`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_stream_push(
+    stream: *mut ShoutStream,
+    pcm: *const f32,
+    len: usize,
+    update: *mut *mut c_char,
+) -> c_int {
+    guard(-1, || {
+        let stream = unsafe { handle(stream, "stream") }?;
+        let update = unsafe { handle(update, "update") }?;
+        *update = ptr::null_mut();
+        if let Some(u) = stream.session.push(unsafe { pcm_arg(pcm, len) }?)? {
+            *update = json_string(&u)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Decode the rest of the stream and finalize it; returns the last update as JSON.
+///
+/// # Safety
+///
+/// `stream` must come from [`shout_stream_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_stream_finish(stream: *mut ShoutStream) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let stream = unsafe { handle(stream, "stream") }?;
+        json_string(&stream.session.finish()?)
+    })
+}
+
+/// Free a stream, which releases its transcriber for other use. NULL is ignored.
+///
+/// # Safety
+///
+/// `stream` must be NULL or come from [`shout_stream_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout_stream_free(stream: *mut ShoutStream) {
+    if !stream.is_null() {
+        drop(unsafe { Box::from_raw(stream) });
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Helpers
+// -----------------------------------------------------------------------------
+
+/// Run `f`, turning an error or panic into `on_error` and the thread's last error.
+fn guard<T>(on_error: T, f: impl FnOnce() -> Result<T>) -> T {
+    let message = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(e)) => e.to_string(),
+        Err(panic) => match panic.downcast::<String>() {
+            Ok(msg) => format!("panic: {msg}"),
+            Err(panic) => match panic.downcast::<&str>() {
+                Ok(msg) => format!("panic: {msg}"),
+                Err(_) => "panic".to_string(),
+            },
+        },
+    };
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+    on_error
+}
+
+unsafe fn handle<'a, T>(ptr: *mut T, name: &str) -> Result<&'a mut T> {
+    unsafe { ptr.as_mut() }.ok_or_else(|| ShoutError::InvalidArgument(format!("{name} is NULL")))
+}
+
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(ShoutError::InvalidArgument(format!("{name} is NULL")));
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|_| ShoutError::InvalidArgument(format!("{name} is not UTF-8")))
+}
+
+unsafe fn pcm_arg<'a>(pcm: *const f32, len: usize) -> Result<&'a [f32]> {
+    match (pcm.is_null(), len) {
+        (_, 0) => Ok(&[]),
+        (true, _) => Err(ShoutError::InvalidArgument("pcm is NULL".into())),
+        (false, _) => Ok(unsafe { std::slice::from_raw_parts(pcm, len) }),
+    }
+}
+
+fn json_string<T: Serialize>(value: &T) -> Result<*mut c_char> {
+    // JSON escapes NUL, so the conversion cannot fail.
+    let json = serde_json::to_string(value)?;
+    Ok(CString::new(json).unwrap_or_default().into_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(shout_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn failures_set_the_last_error() {
+        let dir = c"/does/not/exist";
+        let transcriber = unsafe { shout_transcriber_new(dir.as_ptr(), c"cpu".as_ptr()) };
+        assert!(transcriber.is_null());
+        assert!(last_error().contains("/does/not/exist"), "{}", last_error());
+
+        let status = unsafe { shout_transcriber_set_language(ptr::null_mut(), c"de".as_ptr()) };
+        assert_eq!(status, -1);
+        assert_eq!(last_error(), "transcriber is NULL");
+    }
+
+    #[test]
+    fn panics_do_not_cross_the_boundary() {
+        let value = guard(7, || panic!("boom"));
+        assert_eq!(value, 7);
+        assert_eq!(last_error(), "panic: boom");
+    }
+}
@@ -0,0 +1,269 @@
+//! Node.js bindings: file, buffer and streaming transcription with Promise
+//! APIs. Model loading and decoding run on the libuv thread pool, so they do
+//! not block the event loop.
+//!
+//! ```js
+//! const { load } = require("shout-node");
+//! const transcriber = await load("models/base", "auto");
+//! transcriber.setLanguage("de");
+//! const transcript = await transcriber.transcribeFile("speech.wav");
+//! console.log(transcript.segments.map((s) => s.text).join(""));
+//! ```
+//!
+//! Transcripts have the shape of `shout transcribe --format json`. Build with
+//! `npm run build` in this directory.
+
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use napi::bindgen_prelude::*;
+use napi::{Env, Task};
+use napi_derive::napi;
+use serde_json::Value;
+
+use shout_core::backend::device::{select_device, DeviceSpec};
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::decoding::prompt::Task as DecodeTask;
+use shout_core::inference::{self, ShoutModel, Transcript};
+use shout_core::pipeline::streaming::{StreamingOptions, StreamingSession};
+use shout_core::pipeline::WindowTranscriber;
+use shout_core::transcript::Segment;
+
+type Shared = Arc<Mutex<inference::Transcriber<ShoutModel>>>;
+
+fn js_error(e: impl Display) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+/// A panic in one task should not make the transcriber unusable.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// -----------------------------------------------------------------------------
+// Loading
+// -----------------------------------------------------------------------------
+
+pub struct Load {
+    model_dir: String,
+    device: Option<String>,
+}
+
+#[napi]
+impl Task for Load {
+    type Output = inference::Transcriber<ShoutModel>;
+    type JsValue = Transcriber;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let spec: DeviceSpec = self.device.as_deref().unwrap_or("auto").parse().map_err(js_error)?;
+        let selected = select_device(spec, None).map_err(js_error)?;
+        inference::load_transcriber(Path::new(&self.model_dir), &selected.device).map_err(js_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Transcriber> {
+        Ok(Transcriber {
+            inner: Arc::new(Mutex::new(output)),
+        })
+    }
+}
+
+/// Load the model in `modelDir` on `device`: `auto` (the default), `cpu`,
+/// `cuda[:N]` or `metal[:N]`.
+#[napi]
+pub fn load(model_dir: String, device: Option<String>) -> AsyncTask<Load> {
+    AsyncTask::new(Load { model_dir, device })
+}
+
+// -----------------------------------------------------------------------------
+// Transcriber
+// -----------------------------------------------------------------------------
+
+/// A loaded model. Calls are queued: one transcription runs at a time.
+#[napi]
+pub struct Transcriber {
+    inner: Shared,
+}
+
+#[napi]
+impl Transcriber {
+    /// Spoken language code, or `auto` to detect it.
+    #[napi]
+    pub fn set_language(&self, language: String) -> Result<()> {
+        let language = language.parse::<LanguageSelection>().map_err(js_error)?;
+        lock(&self.inner).options.language = language;
+        Ok(())
+    }
+
+    /// `transcribe`, or `translate` to English.
+    #[napi]
+    pub fn set_task(&self, task: String) -> Result<()> {
+        let task = task.parse::<DecodeTask>().map_err(js_error)?;
+        lock(&self.inner).options.task = task;
+        Ok(())
+    }
+
+    /// Decode and transcribe an audio file.
+    #[napi]
+    pub fn transcribe_file(&self, path: String) -> AsyncTask<Transcribe> {
+        self.task(Input::File(path))
+    }
+
+    /// Transcribe the contents of an audio file (WAV, MP3, FLAC, Ogg Vorbis);
+    /// `extension`, e.g. `"mp3"`, helps detect the format.
+    #[napi]
+    pub fn transcribe_buffer(
+        &self,
+        audio: Buffer,
+        extension: Option<String>,
+    ) -> AsyncTask<Transcribe> {
+        self.task(Input::Bytes(audio.to_vec(), extension))
+    }
+
+    /// Transcribe mono samples at 16 kHz.
+    #[napi]
+    pub fn transcribe_pcm(&self, pcm: Float32Array) -> AsyncTask<Transcribe> {
+        self.task(Input::Pcm(pcm.to_vec()))
+    }
+
+    /// Start a live stream. Its decoding steps take turns with the other calls
+    /// on this transcriber.
+    #[napi]
+    pub fn create_stream(&self) -> Stream {
+        let window = SharedWindow(self.inner.clone());
+        let session = StreamingSession::new(window, StreamingOptions::default());
+        Stream {
+            session: Arc::new(Mutex::new(session)),
+        }
+    }
+
+    fn task(&self, input: Input) -> AsyncTask<Transcribe> {
+        AsyncTask::new(Transcribe {
+            transcriber: self.inner.clone(),
+            input,
+        })
+    }
+}
+
+enum Input {
+    File(String),
+    Bytes(Vec<u8>, Option<String>),
+    Pcm(Vec<f32>),
+}
+
+pub struct Transcribe {
+    transcriber: Shared,
+    input: Input,
+}
+
+#[napi]
+impl Task for Transcribe {
+    type Output = Value;
+    type JsValue = Value;
+
+    fn compute(&mut self) -> Result<Value> {
+        let mut transcriber = lock(&self.transcriber);
+        let transcript = match &self.input {
+            Input::File(path) => transcriber.transcribe_file(path),
+            Input::Bytes(bytes, extension) => {
+                transcribe_bytes(&mut transcriber, bytes, extension.as_deref())
+            }
+            Input::Pcm(pcm) => transcriber.transcribe_pcm(pcm),
+        }
+        .map_err(js_error)?;
+        serde_json::to_value(&transcript).map_err(js_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Value) -> Result<Value> {
+        Ok(output)
+    }
+}
+
+/// The decoder reads from a path; the extension helps it pick the container.
+fn transcribe_bytes(
+    transcriber: &mut inference::Transcriber<ShoutModel>,
+    bytes: &[u8],
+    extension: Option<&str>,
+) -> shout_core::Result<Transcript> {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let mut path = std::env::temp_dir().join(format!("shout-node-{}-{n}", std::process::id()));
+    if let Some(extension) = extension {
+        path.set_extension(extension);
+    }
+    std::fs::write(&path, bytes)?;
+
+    let result = transcriber.transcribe_file(&path);
+    let _ = std::fs::remove_file(&path);
+    let mut transcript = result?;
+    transcript.metadata.audio_path = None;
+    Ok(transcript)
+}
+
+// -----------------------------------------------------------------------------
+// Streaming
+// -----------------------------------------------------------------------------
+
+struct SharedWindow(Shared);
+
+impl WindowTranscriber for SharedWindow {
+    fn transcribe_window(&mut self, pcm: &[f32], prompt: &str) -> shout_core::Result<Vec<Segment>> {
+        lock(&self.0).transcribe_window(pcm, prompt)
+    }
+}
+
+/// A live stream. Await each `push` before the next so samples stay in order.
+#[napi]
+pub struct Stream {
+    session: Arc<Mutex<StreamingSession<SharedWindow>>>,
+}
+
+#[napi]
+impl Stream {
+    /// Add mono samples at 16 kHz. Resolves to `{ finals, partial }` when a
+    /// decoding step ran, `null` while waiting for more audio.
+    #[napi]
+    pub fn push(&self, pcm: Float32Array) -> AsyncTask<StreamStep> {
+        AsyncTask::new(StreamStep {
+            session: self.session.clone(),
+            pcm: Some(pcm.to_vec()),
+        })
+    }
+
+    /// Decode the rest of the stream and finalize it; resolves to the last update.
+    #[napi]
+    pub fn finish(&self) -> AsyncTask<StreamStep> {
+        AsyncTask::new(StreamStep {
+            session: self.session.clone(),
+            pcm: None,
+        })
+    }
+}
+
+pub struct StreamStep {
+    session: Arc<Mutex<StreamingSession<SharedWindow>>>,
+
+    /// `None` finishes the stream.
+    pcm: Option<Vec<f32>>,
+}
+
+#[napi]
+impl Task for StreamStep {
+    type Output = Value;
+    type JsValue = Value;
+
+    fn compute(&mut self) -> Result<Value> {
+        let mut session = lock(&self.session);
+        let update = match &self.pcm {
+            Some(pcm) => session.push(pcm),
+            None => session.finish().map(Some),
+        }
+        .map_err(js_error)?;
+        serde_json::to_value(&update).map_err(js_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Value) -> Result<Value> {
+        Ok(output)
+    }
+}
@@ -0,0 +1,181 @@
+//! Appending to a manifest without duplicating what it already lists, so a
+//! growing corpus can be imported again and again.
+//!
+//! A new entry is a duplicate when its stored audio path is already in the
+//! manifest, or when its audio file has the same content as a file listed
+//! under another name (same size and FNV-1a hash). Files are only read and
+//! hashed when their sizes collide, so most imports never hash at all.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use shout_config::paths::strip_bom;
+
+/// How an importer treats an existing output manifest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Replace it.
+    #[default]
+    Overwrite,
+
+    /// Keep it and add only entries it does not list yet.
+    Append,
+}
+
+/// Open the manifest `out_path` for writing in `mode`, creating its
+/// directory. In append mode the audio it already lists comes with it.
+pub fn open_output(
+    out_path: &Path,
+    mode: WriteMode,
+    resolve: impl Fn(&str) -> PathBuf,
+) -> Result<(BufWriter<File>, Option<KnownAudio>)> {
+    if let Some(parent) = out_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let known = match mode {
+        WriteMode::Overwrite => None,
+        WriteMode::Append => Some(KnownAudio::read(out_path, resolve)?),
+    };
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(mode == WriteMode::Append)
+        .truncate(mode == WriteMode::Overwrite)
+        .open(out_path)
+        .with_context(|| format!("Failed to create output: {}", out_path.display()))?;
+    Ok((BufWriter::new(file), known))
+}
+
+/// The audio a manifest already lists.
+#[derive(Debug, Default)]
+pub struct KnownAudio {
+    stored: HashSet<String>,
+
+    /// Files not hashed yet, by size.
+    unhashed: HashMap<u64, Vec<PathBuf>>,
+
+    /// `(size, hash)` of the hashed files.
+    hashed: HashSet<(u64, u64)>,
+}
+
+impl KnownAudio {
+    /// The audio of the manifest at `path`; nothing if it does not exist.
+    /// `resolve` turns a stored `audio_path` into the file.
+    pub fn read(path: &Path, resolve: impl Fn(&str) -> PathBuf) -> Result<Self> {
+        let mut known = Self::default();
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(known),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to open {}", path.display()));
+            }
+        };
+
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            let line = strip_bom(&line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let value: Value = serde_json::from_str(line)
+                .with_context(|| format!("{}:{}: invalid JSON", path.display(), i + 1))?;
+            let Some(stored) = value.get("audio_path").and_then(Value::as_str) else {
+                continue;
+            };
+            let audio = resolve(stored);
+            if let Ok(meta) = fs::metadata(&audio) {
+                known.unhashed.entry(meta.len()).or_default().push(audio);
+            }
+            known.stored.insert(stored.to_string());
+        }
+        Ok(known)
+    }
+
+    /// Number of entries known.
+    pub fn len(&self) -> usize {
+        self.stored.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stored.is_empty()
+    }
+
+    /// Record the entry with `stored` path and `audio` file; returns whether
+    /// it was new.
+    pub fn insert(&mut self, stored: &str, audio: &Path) -> Result<bool> {
+        if self.stored.contains(stored) {
+            return Ok(false);
+        }
+        let size = fs::metadata(audio)
+            .with_context(|| format!("Failed to read {}", audio.display()))?
+            .len();
+
+        let hashed_before = self.hashed.iter().any(|&(s, _)| s == size);
+        let new = if let Some(same_size) = self.unhashed.remove(&size) {
+            for path in same_size {
+                self.hashed.insert((size, hash_file(&path)?));
+            }
+            self.hashed.insert((size, hash_file(audio)?))
+        } else if hashed_before {
+            self.hashed.insert((size, hash_file(audio)?))
+        } else {
+            self.unhashed
+                .entry(size)
+                .or_default()
+                .push(audio.to_path_buf());
+            true
+        };
+        self.stored.insert(stored.to_string());
+        Ok(new)
+    }
+}
+
+/// FNV-1a of the file's bytes.
+fn hash_file(path: &Path) -> Result<u64> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(hash);
+        }
+        for &b in &buf[..n] {
+            hash = (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_known_paths_and_copies() {
+        let dir = std::env::temp_dir().join(format!("shout_append_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, bytes) in [("a.wav", "aaaa"), ("b.wav", "bbbb"), ("c.wav", "aaaa")] {
+            fs::write(dir.join(name), bytes).unwrap();
+        }
+        let manifest = dir.join("train.jsonl");
+        fs::write(&manifest, "{\"audio_path\": \"a.wav\", \"text\": \"a\"}\n").unwrap();
+
+        let mut known = KnownAudio::read(&manifest, |p| dir.join(p)).unwrap();
+        let fresh = [
+            ("a.wav", false),
+            ("b.wav", true),
+            ("c.wav", false),
+            ("b.wav", false),
+        ];
+        for (name, new) in fresh {
+            assert_eq!(known.insert(name, &dir.join(name)).unwrap(), new, "{name}");
+        }
+        assert_eq!(known.len(), 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,87 @@
+//! Importing Mozilla Common Voice releases: `<split>.tsv` next to `clips/`,
+//! with the audio in `path`, the transcript in `sentence` and the speaker in
+//! `client_id`. Locale, gender and age fill the manifest's fields; votes and
+//! accent go into its tags.
+
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::append::WriteMode;
+use crate::tsv_to_jsonl::{self, ColumnMap, TsvRow, TsvSource};
+
+/// Columns kept as tags when the release has them (`accent` is called
+/// `accents` since Common Voice 8).
+pub const TAG_COLUMNS: [&str; 5] = ["up_votes", "down_votes", "accent", "accents", "variant"];
+
+/// Which clips to keep, by their validation votes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VoteFilter {
+    pub min_up_votes: u32,
+    pub max_down_votes: Option<u32>,
+}
+
+impl VoteFilter {
+    /// Whether a clip with these votes passes. Missing or malformed votes
+    /// count as zero.
+    pub fn keeps(&self, up_votes: Option<&str>, down_votes: Option<&str>) -> bool {
+        let votes = |v: Option<&str>| v.and_then(|v| v.parse::<u32>().ok()).unwrap_or(0);
+        votes(up_votes) >= self.min_up_votes
+            && self
+                .max_down_votes
+                .is_none_or(|max| votes(down_votes) <= max)
+    }
+}
+
+/// The split `split` (`validated`, `train`, `dev`, `test`, ...) of the
+/// release extracted to `release_dir` (the directory holding `clips/`).
+pub fn source(release_dir: &Path, split: &str) -> TsvSource {
+    TsvSource {
+        tsv: release_dir.join(format!("{split}.tsv")),
+        audio_dir: release_dir.join("clips"),
+        columns: ColumnMap {
+            audio: "path".into(),
+            text: "sentence".into(),
+            duration: None,
+            speaker: Some("client_id".into()),
+            language: Some("locale".into()),
+            gender: Some("gender".into()),
+            age: Some("age".into()),
+            tags: TAG_COLUMNS.iter().map(|c| c.to_string()).collect(),
+        },
+    }
+}
+
+/// Import `split` of the release in `release_dir` into the manifest
+/// `out_path`, keeping the clips `votes` accepts; returns the number of
+/// entries written.
+pub fn import(
+    release_dir: &Path,
+    split: &str,
+    votes: VoteFilter,
+    out_path: &Path,
+    mode: WriteMode,
+) -> Result<usize> {
+    let source = source(release_dir, split);
+    tsv_to_jsonl::convert_tsv_filtered(&source, out_path, mode, |row: &TsvRow| {
+        votes.keeps(row.get("up_votes"), row.get("down_votes"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_by_votes() {
+        let filter = VoteFilter {
+            min_up_votes: 2,
+            max_down_votes: Some(0),
+        };
+        assert!(filter.keeps(Some("2"), Some("0")));
+        assert!(!filter.keeps(Some("1"), Some("0")));
+        assert!(!filter.keeps(Some("3"), Some("1")));
+        assert!(!filter.keeps(None, None));
+        assert!(VoteFilter::default().keeps(None, Some("5")));
+    }
+}
@@ -0,0 +1,268 @@
+//! Kaldi data directories: `wav.scp`, `text` and optionally `utt2spk`,
+//! `utt2dur` and `spk2gender`, one `<id> <value>` per line.
+//!
+//! Importing turns a data directory into a manifest; exporting writes a
+//! manifest out as one, with `spk2utt` as well so Kaldi's
+//! `utils/validate_data_dir.sh` accepts it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+use shout_config::paths::{self, strip_bom};
+use tracing::{info, warn};
+
+use crate::append::{WriteMode, open_output};
+use crate::tsv_to_jsonl::{ManifestLine, probe_duration_ms};
+
+/// Import the Kaldi data directory `data_dir` into the manifest `out_path`;
+/// returns the number of entries written. Utterances without a transcript,
+/// with missing audio, or whose `wav.scp` entry is a command pipeline are
+/// skipped. Relative audio paths are taken from the working directory, as
+/// in a Kaldi recipe; the utterance id is kept as the tag `utt_id`.
+pub fn import(data_dir: &Path, out_path: &Path, mode: WriteMode) -> Result<usize> {
+    let config = shout_config::init()?;
+    let wav_scp =
+        read_table(&data_dir.join("wav.scp"))?.context("A Kaldi data directory needs a wav.scp")?;
+    let text: HashMap<_, _> = read_table(&data_dir.join("text"))?
+        .context("A Kaldi data directory needs a text")?
+        .into_iter()
+        .collect();
+    let optional = |name: &str| -> Result<HashMap<String, String>> {
+        Ok(read_table(&data_dir.join(name))?
+            .unwrap_or_default()
+            .into_iter()
+            .collect())
+    };
+    let utt2spk = optional("utt2spk")?;
+    let utt2dur = optional("utt2dur")?;
+    let spk2gender = optional("spk2gender")?;
+
+    let (mut writer, mut known) = open_output(out_path, mode, |p| config.audio_path(p))?;
+    let mut kept = 0usize;
+    let mut skipped_pipes = 0usize;
+    let mut skipped_no_text = 0usize;
+    let mut skipped_missing_audio = 0usize;
+    let mut skipped_known = 0usize;
+
+    for (utt, location) in wav_scp {
+        if location.ends_with('|') {
+            skipped_pipes += 1;
+            continue;
+        }
+        let Some(transcript) = text.get(&utt).filter(|t| !t.is_empty()) else {
+            skipped_no_text += 1;
+            continue;
+        };
+        let audio_path = paths::from_manifest(&location, None);
+        if !audio_path.exists() {
+            skipped_missing_audio += 1;
+            continue;
+        }
+        let stored = config.manifest_path(&audio_path)?;
+        if let Some(known) = &mut known
+            && !known.insert(&stored, &audio_path)?
+        {
+            skipped_known += 1;
+            continue;
+        }
+
+        let duration_ms = utt2dur
+            .get(&utt)
+            .and_then(|d| d.parse::<f64>().ok())
+            .map(|secs| (secs * 1000.0).round() as u32)
+            .or_else(|| probe_duration_ms(&audio_path));
+        let speaker = utt2spk.get(&utt).cloned();
+        let line = ManifestLine {
+            audio_path: stored,
+            text: transcript.clone(),
+            duration_ms,
+            gender: speaker.as_ref().and_then(|s| spk2gender.get(s)).cloned(),
+            speaker,
+            language: None,
+            age: None,
+            tags: BTreeMap::from([("utt_id".to_string(), utt)]),
+        };
+        serde_json::to_writer(&mut writer, &line)?;
+        writer.write_all(b"\n")?;
+        kept += 1;
+    }
+    writer.flush()?;
+
+    if skipped_pipes > 0 {
+        warn!(
+            skipped_pipes,
+            "Skipped wav.scp commands; extract their audio first"
+        );
+    }
+    info!(
+        kept,
+        skipped_no_text,
+        skipped_missing_audio,
+        skipped_known,
+        "Wrote {}",
+        out_path.display()
+    );
+    Ok(kept)
+}
+
+/// Write the manifest `manifest` as the Kaldi data directory `data_dir`;
+/// returns the number of utterances. Utterance ids are `<speaker>-<index>`,
+/// so they sort by speaker as Kaldi requires; entries without a speaker are
+/// their own speaker.
+pub fn export(manifest: &Path, data_dir: &Path) -> Result<usize> {
+    let config = shout_config::init()?;
+    let file = File::open(manifest)
+        .with_context(|| format!("Failed to open manifest: {}", manifest.display()))?;
+
+    let mut utts = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let line = strip_bom(&line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(line)
+            .with_context(|| format!("{}:{}: invalid JSON", manifest.display(), i + 1))?;
+        let (Some(stored), Some(text)) = (
+            value.get("audio_path").and_then(Value::as_str),
+            value.get("text").and_then(Value::as_str),
+        ) else {
+            bail!(
+                "{}:{}: needs audio_path and text",
+                manifest.display(),
+                i + 1
+            );
+        };
+
+        let index = utts.len();
+        let speaker = value.get("speaker").and_then(Value::as_str).map(kaldi_id);
+        let utt = match &speaker {
+            Some(speaker) => format!("{speaker}-{index:06}"),
+            None => format!("utt{index:06}"),
+        };
+        utts.push(Utterance {
+            speaker: speaker.unwrap_or_else(|| utt.clone()),
+            id: utt,
+            audio: config.audio_path(stored).display().to_string(),
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            duration_ms: value.get("duration_ms").and_then(Value::as_u64),
+        });
+    }
+    utts.sort_by(|a, b| a.id.cmp(&b.id));
+
+    fs::create_dir_all(data_dir)
+        .with_context(|| format!("Failed to create {}", data_dir.display()))?;
+    write_table(
+        &data_dir.join("wav.scp"),
+        utts.iter().map(|u| (&u.id, u.audio.clone())),
+    )?;
+    write_table(
+        &data_dir.join("text"),
+        utts.iter().map(|u| (&u.id, u.text.clone())),
+    )?;
+    write_table(
+        &data_dir.join("utt2spk"),
+        utts.iter().map(|u| (&u.id, u.speaker.clone())),
+    )?;
+    let mut spk2utt: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for u in &utts {
+        spk2utt.entry(u.speaker.as_str()).or_default().push(&u.id);
+    }
+    let spk2utt = spk2utt.iter().map(|(spk, ids)| (*spk, ids.join(" ")));
+    write_table(&data_dir.join("spk2utt"), spk2utt)?;
+    if utts.iter().all(|u| u.duration_ms.is_some()) {
+        let durations = utts.iter().map(|u| {
+            let secs = u.duration_ms.unwrap_or_default() as f64 / 1000.0;
+            (&u.id, format!("{secs:.3}"))
+        });
+        write_table(&data_dir.join("utt2dur"), durations)?;
+    }
+
+    info!(utterances = utts.len(), "Wrote {}", data_dir.display());
+    Ok(utts.len())
+}
+
+struct Utterance {
+    id: String,
+    speaker: String,
+    audio: String,
+    text: String,
+    duration_ms: Option<u64>,
+}
+
+/// The `<key> <value>` lines of a Kaldi table, in file order; `None` if the
+/// file does not exist.
+fn read_table(path: &Path) -> Result<Option<Vec<(String, String)>>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {}", path.display())),
+    };
+    let mut rows = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.with_context(|| format!("Failed to read {}", path.display()))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        rows.push((key.to_string(), value.trim().to_string()));
+    }
+    Ok(Some(rows))
+}
+
+fn write_table<K: AsRef<str>>(path: &Path, rows: impl Iterator<Item = (K, String)>) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    for (key, value) in rows {
+        writeln!(out, "{} {value}", key.as_ref())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// `name` as a Kaldi id: no whitespace, and no `-` so speaker prefixes stay
+/// unambiguous.
+fn kaldi_id(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_whitespace() || c == '-' {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_tables_with_spaces_in_values() {
+        let path = std::env::temp_dir().join(format!("shout_kaldi_text_{}", std::process::id()));
+        fs::write(
+            &path,
+            "utt1 hello  world\nutt2\n\nutt3 sox a.flac -t wav - |\n",
+        )
+        .unwrap();
+        let rows = read_table(&path).unwrap().unwrap();
+        assert_eq!(rows[0], ("utt1".into(), "hello  world".into()));
+        assert_eq!(rows[1], ("utt2".into(), String::new()));
+        assert!(rows[2].1.ends_with('|'));
+        assert!(
+            read_table(&path.with_extension("missing"))
+                .unwrap()
+                .is_none()
+        );
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(kaldi_id("spk 1-a"), "spk_1_a");
+    }
+}
@@ -0,0 +1,8 @@
+//! Dataset preparation: turning corpora into the JSONL manifests the other
+//! tools read. The `shout_tools` binary and `shout data` run these.
+
+pub mod append;
+pub mod common_voice;
+pub mod kaldi;
+pub mod progress;
+pub mod tsv_to_jsonl;
@@ -1,5 +1,57 @@
-mod tsv_to_jsonl;
+use std::path::PathBuf;
 
-fn main() {
-    tsv_to_jsonl::convert().expect("TODO: panic message");
+use anyhow::Result;
+use clap::Parser;
+
+use shout_tools::append::WriteMode;
+use shout_tools::tsv_to_jsonl::{self, ColumnMap, TsvSource};
+
+/// Convert a TSV corpus into a JSONL manifest.
+#[derive(Parser)]
+#[command(name = "shout_tools")]
+struct Args {
+    /// Corpus TSV (default: the corpus under `paths.data_root`).
+    #[arg(long)]
+    tsv: Option<PathBuf>,
+
+    /// Directory the TSV's audio paths are relative to (default: the TSV's
+    /// directory, or `audios/` for the default corpus).
+    #[arg(long)]
+    audio_dir: Option<PathBuf>,
+
+    /// Manifest to write (default: `train.jsonl` in `paths.manifests_dir`).
+    #[arg(long, short)]
+    out: Option<PathBuf>,
+
+    /// Which columns hold the fields: `audio=COL,text=COL[,duration=COL][,speaker=COL]`.
+    #[arg(long)]
+    columns: Option<ColumnMap>,
+
+    /// Keep the existing manifest and add only audio it does not list yet.
+    #[arg(long)]
+    append: bool,
+}
+
+fn main() -> Result<()> {
+    // Progress on stderr at info level; RUST_LOG overrides it.
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+
+    let args = Args::parse();
+    let source = TsvSource::from_args(args.tsv, args.audio_dir, args.columns)?;
+    let out = match args.out {
+        Some(out) => out,
+        None => shout_config::init()?.manifests_dir().join("train.jsonl"),
+    };
+    let mode = if args.append {
+        WriteMode::Append
+    } else {
+        WriteMode::Overwrite
+    };
+    tsv_to_jsonl::convert_tsv(&source, &out, mode)?;
+    Ok(())
 }
@@ -0,0 +1,16 @@
+//! Progress bars for long imports: position, rate and time left on stderr.
+//! They stay hidden when stderr is not a terminal, so logs stay clean.
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// A bar over `len` items, labeled `message`.
+pub fn bar(len: u64, message: &str) -> ProgressBar {
+    let style = ProgressStyle::with_template(
+        "{msg} [{bar:30}] {human_pos}/{human_len} ({per_sec}, ETA {eta})",
+    )
+    .expect("valid progress template")
+    .progress_chars("=> ");
+    ProgressBar::new(len)
+        .with_style(style)
+        .with_message(message.to_string())
+}
@@ -1,79 +1,355 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, bail};
+use rayon::prelude::*;
 use serde::Serialize;
+use shout_config::paths;
+use shout_core::audio::probe;
 use std::{
-    fs::File,
-    io::{BufWriter, Write},
-    path::PathBuf,
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Instant,
 };
-#[derive(Debug, serde::Deserialize)]
-struct Row {
-    client_id: String,
-    audio_file: String,
-    duration_ms: String, 
-    prompt_id: String,
-    prompt: String,
-    transcription: String,
-    votes: String,
-    age: String,
-    gender: String,
-    language: String,
-    split: String,
-    char_per_sec: String,
-    quality_tags: String,
-}
+use tracing::{debug, info};
+
+use crate::append::{WriteMode, open_output};
+use crate::progress;
 
+/// One line of the manifests the importers write.
 #[derive(Debug, Serialize)]
-struct ManifestLine {
-    audio_path: String,
-    text: String,
-    duration_ms: Option<u32>
+pub(crate) struct ManifestLine {
+    pub audio_path: String,
+    pub text: String,
+    pub duration_ms: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gender: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<String>,
+
+    /// Anything else about the entry, for balancing and per-group scores.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+/// Directory of the corpus under `paths.data_root`.
+pub const CORPUS_DIR: &str = "sps-corpus-2.0-2025-12-05-de";
+
+/// Which TSV columns hold the manifest fields.
+///
+/// Parses from `audio=COL,text=COL[,duration=COL][,speaker=COL]...` with
+/// the optional fields `duration`, `speaker`, `language`, `gender`, `age`
+/// and `tag` (repeatable); `audio` and `text` are required.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMap {
+    /// Audio file name, relative to the audio directory (or absolute).
+    pub audio: String,
+    pub text: String,
+
+    /// Duration in milliseconds.
+    pub duration: Option<String>,
+    pub speaker: Option<String>,
+    pub language: Option<String>,
+    pub gender: Option<String>,
+    pub age: Option<String>,
+
+    /// Columns copied into the manifest's `tags` under their own names, when
+    /// the TSV has them and they are not empty.
+    pub tags: Vec<String>,
+}
+
+impl ColumnMap {
+    /// The columns of the Spontaneous Speech corpus TSV.
+    pub fn sps_corpus() -> Self {
+        Self {
+            audio: "audio_file".into(),
+            text: "prompt".into(),
+            duration: Some("duration_ms".into()),
+            speaker: Some("client_id".into()),
+            language: Some("language".into()),
+            gender: Some("gender".into()),
+            age: Some("age".into()),
+            tags: vec!["quality_tags".into(), "split".into()],
+        }
+    }
+}
+
+impl FromStr for ColumnMap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut fields: BTreeMap<&str, String> = BTreeMap::new();
+        let mut tags = Vec::new();
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (field, column) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected FIELD=COLUMN, got `{pair}`"))?;
+            let (field, column) = (field.trim(), column.trim().to_string());
+            match field {
+                "tag" => tags.push(column),
+                "audio" | "text" | "duration" | "speaker" | "language" | "gender" | "age" => {
+                    fields.insert(field, column);
+                }
+                other => bail!(
+                    "Unknown field `{other}` (expected audio, text, duration, speaker, \
+                     language, gender, age or tag)"
+                ),
+            }
+        }
+        Ok(Self {
+            audio: fields
+                .remove("audio")
+                .context("The column mapping needs `audio=COLUMN`")?,
+            text: fields
+                .remove("text")
+                .context("The column mapping needs `text=COLUMN`")?,
+            duration: fields.remove("duration"),
+            speaker: fields.remove("speaker"),
+            language: fields.remove("language"),
+            gender: fields.remove("gender"),
+            age: fields.remove("age"),
+            tags,
+        })
+    }
+}
+
+/// A TSV corpus: the table, the directory its audio paths are relative to,
+/// and which columns to read.
+#[derive(Debug, Clone)]
+pub struct TsvSource {
+    pub tsv: PathBuf,
+    pub audio_dir: PathBuf,
+    pub columns: ColumnMap,
+}
+
+impl TsvSource {
+    /// The Spontaneous Speech corpus extracted to `dataset_root`
+    /// (`ss-corpus-de.tsv` and `audios/`).
+    pub fn sps_corpus(dataset_root: &Path) -> Self {
+        Self {
+            tsv: dataset_root.join("ss-corpus-de.tsv"),
+            audio_dir: dataset_root.join("audios"),
+            columns: ColumnMap::sps_corpus(),
+        }
+    }
+
+    /// The corpus given on the command line. Without a TSV this is the
+    /// corpus under `paths.data_root`; the audio directory defaults to the
+    /// TSV's directory and the columns to the corpus's.
+    pub fn from_args(
+        tsv: Option<PathBuf>,
+        audio_dir: Option<PathBuf>,
+        columns: Option<ColumnMap>,
+    ) -> Result<Self> {
+        let mut source = match tsv {
+            Some(tsv) => {
+                let dir = tsv.parent().map(Path::to_path_buf).unwrap_or_default();
+                Self {
+                    tsv,
+                    audio_dir: dir,
+                    columns: ColumnMap::sps_corpus(),
+                }
+            }
+            None => Self::sps_corpus(&shout_config::init()?.data_root().join(CORPUS_DIR)),
+        };
+        if let Some(dir) = audio_dir {
+            source.audio_dir = dir;
+        }
+        if let Some(columns) = columns {
+            source.columns = columns;
+        }
+        Ok(source)
+    }
 }
 
-pub fn convert()->Result<(), anyhow::Error>{
-    println!("Converting TSV to JSONL");
-    let dataset_root = PathBuf::from(r"C:\Rust\shout\shout_train\data\sps-corpus-2.0-2025-12-05-de");
-    let tsv_path = dataset_root.join("ss-corpus-de.tsv");
-    let audios_dir = dataset_root.join("audios");
+/// Convert the corpus under `paths.data_root` into `train.jsonl` in
+/// `paths.manifests_dir`.
+pub fn convert(mode: WriteMode) -> Result<()> {
+    let config = shout_config::init()?;
+    let dataset_root = config.data_root().join(CORPUS_DIR);
+    let out_path = config.manifests_dir().join("train.jsonl");
+    convert_corpus(&dataset_root, &out_path, mode)?;
+    Ok(())
+}
+
+/// Convert the corpus in `dataset_root` (`ss-corpus-de.tsv` and `audios/`)
+/// into the manifest `out_path`; returns the number of entries written.
+pub fn convert_corpus(dataset_root: &Path, out_path: &Path, mode: WriteMode) -> Result<usize> {
+    convert_tsv(&TsvSource::sps_corpus(dataset_root), out_path, mode)
+}
 
-    let out_path = PathBuf::from("manifests/train.jsonl");
-    std::fs::create_dir_all(out_path.parent().unwrap())?;
+/// A row of a TSV being converted.
+pub struct TsvRow<'a> {
+    headers: &'a csv::StringRecord,
+    record: &'a csv::StringRecord,
+}
+
+impl TsvRow<'_> {
+    /// The trimmed value of the column `name`, if the TSV has it.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let col = self.headers.iter().position(|h| h == name)?;
+        Some(self.record.get(col)?.trim())
+    }
+}
+
+/// Convert `source` into the manifest `out_path`; returns the number of
+/// entries written. Rows with empty text or missing audio are skipped. With
+/// [`WriteMode::Append`], entries whose audio `out_path` already lists are
+/// left out.
+pub fn convert_tsv(source: &TsvSource, out_path: &Path, mode: WriteMode) -> Result<usize> {
+    convert_tsv_filtered(source, out_path, mode, |_| true)
+}
+
+/// What checking a TSV row found.
+enum Checked {
+    Kept {
+        line: ManifestLine,
+        audio_path: PathBuf,
+    },
+    Filtered,
+    EmptyText,
+    MissingAudio,
+}
+
+/// [`convert_tsv`], keeping only the rows `keep` accepts.
+pub fn convert_tsv_filtered<F>(
+    source: &TsvSource,
+    out_path: &Path,
+    mode: WriteMode,
+    keep: F,
+) -> Result<usize>
+where
+    F: Fn(&TsvRow) -> bool + Sync,
+{
+    info!("Converting TSV to JSONL");
+    let config = shout_config::init()?;
+    let tsv_path = &source.tsv;
 
     let mut rdr = csv::ReaderBuilder::new()
         .delimiter(b'\t')
         .has_headers(true)
-        .from_path(&tsv_path)
+        .from_path(tsv_path)
         .with_context(|| format!("Failed to open TSV: {}", tsv_path.display()))?;
 
-    let out_file = File::create(&out_path)
-        .with_context(|| format!("Failed to create output: {}", out_path.display()))?;
-    let mut writer = BufWriter::new(out_file);
-
-    let mut kept = 0usize;
-    let mut skipped_missing_audio = 0usize;
-    let mut skipped_empty_prompt = 0usize;
-
-    for result in rdr.deserialize::<Row>() {
-        let row = result.context("Failed to parse a TSV row")?;
+    let headers = rdr
+        .headers()
+        .context("Failed to read the TSV header")?
+        .clone();
+    let column = |name: &str| {
+        headers.iter().position(|h| h == name).with_context(|| {
+            let found: Vec<&str> = headers.iter().collect();
+            format!(
+                "No column `{name}` in {} (found: {})",
+                tsv_path.display(),
+                found.join(", ")
+            )
+        })
+    };
+    let audio_col = column(&source.columns.audio)?;
+    let text_col = column(&source.columns.text)?;
+    let duration_col = source.columns.duration.as_deref().map(column).transpose()?;
+    let speaker_col = source.columns.speaker.as_deref().map(column).transpose()?;
+    let language_col = source.columns.language.as_deref().map(column).transpose()?;
+    let gender_col = source.columns.gender.as_deref().map(column).transpose()?;
+    let age_col = source.columns.age.as_deref().map(column).transpose()?;
+    let tag_cols: Vec<(&str, usize)> = source
+        .columns
+        .tags
+        .iter()
+        .filter_map(|name| Some((name.as_str(), headers.iter().position(|h| h == name)?)))
+        .collect();
 
-        let text = row.prompt.trim();
+    let check_row = |row: &csv::StringRecord| -> Result<Checked> {
+        let cell = |col: usize| row.get(col).unwrap_or("").trim();
+        if !keep(&TsvRow {
+            headers: &headers,
+            record: row,
+        }) {
+            return Ok(Checked::Filtered);
+        }
+        let text = cell(text_col);
         if text.is_empty() {
-            skipped_empty_prompt += 1;
-            continue;
+            return Ok(Checked::EmptyText);
         }
-
-        let audio_path = audios_dir.join(row.audio_file.trim());
+        let audio_path = paths::from_manifest(cell(audio_col), Some(source.audio_dir.as_path()));
         if !audio_path.exists() {
-            skipped_missing_audio += 1;
-            continue;
+            return Ok(Checked::MissingAudio);
         }
 
-        let duration_ms = row.duration_ms.trim().parse::<u32>().ok();
-
+        let optional =
+            |col: Option<usize>| col.map(cell).filter(|s| !s.is_empty()).map(str::to_string);
         let line = ManifestLine {
-            audio_path: audio_path.to_string_lossy().to_string(),
+            audio_path: config.manifest_path(&audio_path)?,
             text: text.to_string(),
-            duration_ms
+            duration_ms: duration_col
+                .and_then(|col| cell(col).parse::<u32>().ok())
+                .or_else(|| probe_duration_ms(&audio_path)),
+            speaker: optional(speaker_col),
+            language: optional(language_col),
+            gender: optional(gender_col),
+            age: optional(age_col),
+            tags: tag_cols
+                .iter()
+                .filter(|&&(_, col)| !cell(col).is_empty())
+                .map(|&(name, col)| (name.to_string(), cell(col).to_string()))
+                .collect(),
         };
+        Ok(Checked::Kept { line, audio_path })
+    };
+
+    let started = Instant::now();
+    let rows: Vec<csv::StringRecord> = rdr
+        .records()
+        .collect::<Result<_, _>>()
+        .context("Failed to parse a TSV row")?;
+
+    // File system checks and duration probes dominate on slow disks, so rows
+    // are checked in parallel; collecting keeps them in TSV order.
+    let rows_total = rows.len();
+    let progress = progress::bar(rows_total as u64, "Checking audio");
+    let checked: Vec<Checked> = rows
+        .par_iter()
+        .map(|row| {
+            let checked = check_row(row);
+            progress.inc(1);
+            checked
+        })
+        .collect::<Result<_>>()?;
+    progress.finish_and_clear();
+
+    let (mut writer, mut known) = open_output(out_path, mode, |p| config.audio_path(p))?;
+
+    let mut kept = 0usize;
+    let mut skipped_missing_audio = 0usize;
+    let mut skipped_empty_prompt = 0usize;
+    let mut skipped_known = 0usize;
+    let mut skipped_filtered = 0usize;
+
+    for checked in checked {
+        let (line, audio_path) = match checked {
+            Checked::Kept { line, audio_path } => (line, audio_path),
+            Checked::Filtered => {
+                skipped_filtered += 1;
+                continue;
+            }
+            Checked::EmptyText => {
+                skipped_empty_prompt += 1;
+                continue;
+            }
+            Checked::MissingAudio => {
+                skipped_missing_audio += 1;
+                continue;
+            }
+        };
+        if let Some(known) = &mut known
+            && !known.insert(&line.audio_path, &audio_path)?
+        {
+            skipped_known += 1;
+            continue;
+        }
 
         serde_json::to_writer(&mut writer, &line)?;
         writer.write_all(b"\n")?;
@@ -82,11 +358,52 @@ pub fn convert()->Result<(), anyhow::Error>{
 
     writer.flush()?;
 
-    println!("Wrote: {}", out_path.display());
-    println!("Kept: {}", kept);
-    println!("Skipped (empty prompt): {}", skipped_empty_prompt);
-    println!("Skipped (missing audio file): {}", skipped_missing_audio);
+    info!(
+        kept,
+        skipped_empty_prompt,
+        skipped_missing_audio,
+        skipped_known,
+        skipped_filtered,
+        rows_per_sec = (rows_total as f64 / started.elapsed().as_secs_f64()).round(),
+        "Wrote {}",
+        out_path.display()
+    );
 
-    Ok(())
+    Ok(kept)
 }
 
+/// Duration of `audio` from its container, for entries whose source does
+/// not state one; `None` if the file cannot be probed.
+pub(crate) fn probe_duration_ms(audio: &Path) -> Option<u32> {
+    match probe::duration_ms(audio) {
+        Ok(ms) => u32::try_from(ms).ok(),
+        Err(e) => {
+            debug!("Could not probe {}: {e}", audio.display());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_column_mapping() {
+        let map: ColumnMap = "audio=path, text=sentence,speaker=client_id,tag=accent,tag=variant"
+            .parse()
+            .unwrap();
+        assert_eq!(map.audio, "path");
+        assert_eq!(map.text, "sentence");
+        assert_eq!(map.duration, None);
+        assert_eq!(map.speaker.as_deref(), Some("client_id"));
+        assert_eq!(map.tags, ["accent", "variant"]);
+
+        assert!("text=sentence".parse::<ColumnMap>().is_err());
+        assert!(
+            "audio=path,text=sentence,lang=locale"
+                .parse::<ColumnMap>()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,8 @@
+//! Training and fine-tuning of shout models (planned; see `crate structure`).
+
+use anyhow::{Result, bail};
+
+/// Run a training job. Not implemented yet.
+pub fn train() -> Result<()> {
+    bail!("training is not implemented yet")
+}
@@ -1,3 +1,3 @@
-fn main() {
-    println!("Hello, world!");
-}
\ No newline at end of file
+fn main() -> anyhow::Result<()> {
+    shout_train::train()
+}
@@ -0,0 +1,94 @@
+//! In-browser transcription: shout_core's PCM-in path behind a wasm-bindgen API.
+//!
+//! There is no audio file decoding here; the page captures or decodes audio with
+//! Web Audio and passes mono `Float32Array` samples at their native rate. Models
+//! are loaded from bytes (a quantized GGUF checkpoint keeps the download small).
+//!
+//! ```js
+//! import init, { Transcriber } from "./pkg/shout_wasm.js";
+//! await init();
+//! const t = new Transcriber(configJson, tokenizerBytes, modelBytes);
+//! t.setLanguage("de");
+//! const transcript = JSON.parse(t.transcribe(audioBuffer.getChannelData(0), audioBuffer.sampleRate));
+//! ```
+//!
+//! Build with `wasm-pack build --target web shout_wasm`.
+
+use candle_core::Device;
+use wasm_bindgen::prelude::*;
+
+use shout_core::audio::mel::TimeMajor;
+use shout_core::audio::resample::StreamResampler;
+use shout_core::config::model::ModelConfig;
+use shout_core::decoding::language::LanguageSelection;
+use shout_core::decoding::prompt::Task;
+use shout_core::features;
+use shout_core::model::shout::ShoutModel;
+use shout_core::pipeline::transcribe;
+use shout_core::tokenizer::bpe::Tokenizer;
+use shout_core::Result;
+
+const SAMPLE_RATE: u32 = 16_000;
+
+#[wasm_bindgen]
+pub struct Transcriber {
+    inner: transcribe::Transcriber<ShoutModel>,
+}
+
+#[wasm_bindgen]
+impl Transcriber {
+    /// Load a model from the contents of `config.json`, `tokenizer.json` and a `*.gguf` file.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        config_json: &str,
+        tokenizer_json: &[u8],
+        model_gguf: &[u8],
+    ) -> Result<Transcriber, JsError> {
+        let config: ModelConfig = serde_json::from_str(config_json)?;
+        let front_end = features::front_end(&config)?;
+        let model = ShoutModel::load_gguf_bytes(config, model_gguf, &Device::Cpu)?;
+        let tokenizer = Tokenizer::from_bytes(tokenizer_json)?;
+        let mut inner = transcribe::Transcriber::new(model, tokenizer, front_end.feature_dim());
+        inner.set_front_end(front_end);
+        Ok(Self { inner })
+    }
+
+    /// Spoken language code, or `auto` to detect it.
+    #[wasm_bindgen(js_name = setLanguage)]
+    pub fn set_language(&mut self, language: &str) -> Result<(), JsError> {
+        self.inner.options.language = language.parse::<LanguageSelection>()?;
+        Ok(())
+    }
+
+    /// `transcribe` or `translate` (to English).
+    #[wasm_bindgen(js_name = setTask)]
+    pub fn set_task(&mut self, task: &str) -> Result<(), JsError> {
+        self.inner.options.task = task.parse::<Task>()?;
+        Ok(())
+    }
+
+    /// Transcribe mono samples at `sample_rate` Hz; returns the transcript as JSON.
+    pub fn transcribe(&mut self, pcm: &[f32], sample_rate: u32) -> Result<String, JsError> {
+        let pcm = to_16k(pcm, sample_rate)?;
+        let transcript = self.inner.transcribe_pcm(&pcm)?;
+        Ok(serde_json::to_string(&transcript)?)
+    }
+}
+
+/// Log-mel spectrogram of mono samples at `sample_rate` Hz, frame-major
+/// (`n_frames * n_mels` values), e.g. for visualisation.
+#[wasm_bindgen(js_name = logMel)]
+pub fn log_mel(pcm: &[f32], sample_rate: u32, n_mels: usize) -> Result<Vec<f32>, JsError> {
+    let pcm = to_16k(pcm, sample_rate)?;
+    Ok(shout_core::audio::mel::log_mel(&pcm, n_mels).to_flat_time_major())
+}
+
+fn to_16k(pcm: &[f32], sample_rate: u32) -> Result<Vec<f32>> {
+    if sample_rate == SAMPLE_RATE {
+        return Ok(pcm.to_vec());
+    }
+    let mut resampler = StreamResampler::new(sample_rate, SAMPLE_RATE)?;
+    let mut out = resampler.push(pcm)?;
+    out.extend(resampler.finish()?);
+    Ok(out)
+}